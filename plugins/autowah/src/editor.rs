@@ -0,0 +1,49 @@
+use crate::{AutowahParams, Source};
+use nih_plug::prelude::*;
+use nih_plug_egui::create_egui_editor;
+use std::sync::Arc;
+use ui_widgets::{param_combo, EditorParams, EditorShell, ParamKnob};
+
+pub(crate) fn default_params() -> EditorParams {
+    EditorParams::new((400, 300))
+}
+
+pub(crate) fn create(params: Arc<AutowahParams>) -> Option<Box<dyn Editor>> {
+    create_egui_editor(
+        params.editor.state.clone(),
+        (),
+        |_, _| {},
+        move |egui_ctx, setter, _state| {
+            EditorShell::new(&params.editor)
+                .with_bypass(&params.bypass)
+                .show(egui_ctx, setter, |ui| {
+                    ui.horizontal(|ui| {
+                        param_combo(ui, &params.source, setter);
+                        param_combo(ui, &params.filter, setter);
+                        param_combo(ui, &params.direction, setter)
+                            .on_hover_text("Whether louder input sweeps the filter up or down");
+                    });
+                    ui.horizontal(|ui| {
+                        ui.add(ParamKnob::for_param(&params.frequency, setter))
+                            .on_hover_text("The bottom of the sweep");
+                        ui.add(ParamKnob::for_param(&params.range, setter))
+                            .on_hover_text("How many octaves the filter sweeps");
+                        ui.add(ParamKnob::for_param(&params.q, setter));
+                        ui.add(ParamKnob::for_param(&params.mix, setter));
+                    });
+                    let envelope = params.source.value() == Source::Envelope;
+                    ui.horizontal(|ui| {
+                        ui.add_enabled_ui(envelope, |ui| {
+                            ui.add(ParamKnob::for_param(&params.sensitivity, setter))
+                                .on_hover_text("Boost quiet input so it sweeps the whole range");
+                            ui.add(ParamKnob::for_param(&params.attack, setter));
+                            ui.add(ParamKnob::for_param(&params.release, setter));
+                        });
+                        ui.add_enabled_ui(!envelope, |ui| {
+                            ui.add(ParamKnob::for_param(&params.rate, setter));
+                        });
+                    });
+                });
+        },
+    )
+}
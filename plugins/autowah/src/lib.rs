@@ -0,0 +1,573 @@
+use dsp_core::dynamics::{db_to_gain, EnvelopeFollower};
+use dsp_core::filters::{FilterMode, StateVariableFilter};
+use dsp_core::guard;
+use dsp_core::lfo::Lfo;
+use dsp_core::mix::MixStage;
+use nih_plug::prelude::*;
+use plugin_scaffold::formatters::{s2v_f32_ms_then_s, v2s_f32_ms_then_s};
+use plugin_scaffold::layouts;
+use std::sync::Arc;
+use ui_widgets::EditorParams;
+
+mod editor;
+
+/// An envelope filter: the input's level, or an LFO, sweeps a resonant filter up or down from a
+/// base frequency.
+struct Autowah {
+    params: Arc<AutowahParams>,
+    /// Follows the input's peak across channels, so both channels sweep together.
+    follower: EnvelopeFollower,
+    lfo: Lfo,
+    /// One filter per channel.
+    filters: Vec<StateVariableFilter>,
+    /// Blends in the dry signal, and fades to it for a click-free bypass.
+    mix: MixStage,
+}
+
+/// What sweeps the filter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+pub enum Source {
+    /// The input's level, the classic envelope filter.
+    Envelope,
+    #[name = "LFO"]
+    Lfo,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+pub enum WahFilter {
+    /// The vocal wah sound, only the swept band passes.
+    #[name = "Band Pass"]
+    BandPass,
+    /// Keeps the lows, for a funkier envelope filter.
+    #[name = "Low Pass"]
+    LowPass,
+}
+
+/// Which way louder input, or the top of the LFO cycle, moves the filter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+pub enum Direction {
+    /// From the base frequency up.
+    Up,
+    /// From the top of the range down to the base frequency.
+    Down,
+}
+
+#[derive(Params)]
+struct AutowahParams {
+    #[nested]
+    editor: EditorParams,
+
+    /// The host's bypass switch.
+    #[id = "bypass"]
+    pub bypass: BoolParam,
+
+    #[id = "source"]
+    pub source: EnumParam<Source>,
+
+    #[id = "filter"]
+    pub filter: EnumParam<WahFilter>,
+
+    #[id = "direction"]
+    pub direction: EnumParam<Direction>,
+
+    /// The bottom of the sweep.
+    #[id = "frequency"]
+    pub frequency: FloatParam,
+
+    /// How far the filter sweeps, in octaves above the base frequency.
+    #[id = "range"]
+    pub range: FloatParam,
+
+    #[id = "q"]
+    pub q: FloatParam,
+
+    /// Gain into the envelope follower. The sweep reaches the top of the range once the
+    /// boosted input reaches full scale.
+    #[id = "sensitivity"]
+    pub sensitivity: FloatParam,
+
+    /// How quickly the envelope opens the filter, in milliseconds.
+    #[id = "attack"]
+    pub attack: FloatParam,
+
+    /// How quickly the filter falls back when the input gets quieter, in milliseconds.
+    #[id = "release"]
+    pub release: FloatParam,
+
+    /// The LFO's rate in Hz.
+    #[id = "rate"]
+    pub rate: FloatParam,
+
+    #[id = "mix"]
+    pub mix: FloatParam,
+}
+
+/// The filter's cutoff for a sweep `amount` from 0 to 1.
+fn sweep_cutoff(frequency: f32, range: f32, direction: Direction, amount: f32) -> f32 {
+    let amount = amount.clamp(0.0, 1.0);
+    let octaves = match direction {
+        Direction::Up => range * amount,
+        Direction::Down => range * (1.0 - amount),
+    };
+    frequency * octaves.exp2()
+}
+
+impl Default for Autowah {
+    fn default() -> Self {
+        Self {
+            params: Arc::new(AutowahParams::default()),
+            follower: EnvelopeFollower::new(44100.0),
+            lfo: Lfo::new(44100.0),
+            filters: Vec::new(),
+            mix: MixStage::new(44100.0, 0, 0, 0),
+        }
+    }
+}
+
+impl Default for AutowahParams {
+    fn default() -> Self {
+        Self {
+            editor: editor::default_params(),
+
+            bypass: BoolParam::new("Bypass", false).make_bypass(),
+
+            source: EnumParam::new("Source", Source::Envelope),
+            filter: EnumParam::new("Filter", WahFilter::BandPass),
+            direction: EnumParam::new("Direction", Direction::Up),
+
+            frequency: FloatParam::new(
+                "Frequency",
+                300.0,
+                FloatRange::Skewed {
+                    min: 50.0,
+                    max: 5000.0,
+                    factor: FloatRange::skew_factor(-2.0),
+                },
+            )
+            .with_smoother(SmoothingStyle::Logarithmic(20.0))
+            .with_value_to_string(formatters::v2s_f32_hz_then_khz(0))
+            .with_string_to_value(formatters::s2v_f32_hz_then_khz()),
+
+            range: FloatParam::new("Range", 3.0, FloatRange::Linear { min: 0.0, max: 6.0 })
+                .with_smoother(SmoothingStyle::Linear(20.0))
+                .with_unit(" oct")
+                .with_value_to_string(formatters::v2s_f32_rounded(1)),
+
+            q: FloatParam::new(
+                "Q",
+                4.0,
+                FloatRange::Skewed {
+                    min: 0.5,
+                    max: 20.0,
+                    factor: FloatRange::skew_factor(-1.0),
+                },
+            )
+            .with_smoother(SmoothingStyle::Logarithmic(20.0))
+            .with_value_to_string(formatters::v2s_f32_rounded(1)),
+
+            sensitivity: FloatParam::new(
+                "Sensitivity",
+                12.0,
+                FloatRange::Linear {
+                    min: -12.0,
+                    max: 36.0,
+                },
+            )
+            .with_smoother(SmoothingStyle::Linear(20.0))
+            .with_unit(" dB")
+            .with_value_to_string(formatters::v2s_f32_rounded(1)),
+
+            attack: FloatParam::new(
+                "Attack",
+                5.0,
+                FloatRange::Skewed {
+                    min: 0.5,
+                    max: 200.0,
+                    factor: FloatRange::skew_factor(-2.0),
+                },
+            )
+            .with_value_to_string(v2s_f32_ms_then_s(1))
+            .with_string_to_value(s2v_f32_ms_then_s()),
+
+            release: FloatParam::new(
+                "Release",
+                150.0,
+                FloatRange::Skewed {
+                    min: 10.0,
+                    max: 2000.0,
+                    factor: FloatRange::skew_factor(-2.0),
+                },
+            )
+            .with_value_to_string(v2s_f32_ms_then_s(0))
+            .with_string_to_value(s2v_f32_ms_then_s()),
+
+            rate: FloatParam::new(
+                "Rate",
+                1.0,
+                FloatRange::Skewed {
+                    min: 0.05,
+                    max: 10.0,
+                    factor: FloatRange::skew_factor(-2.0),
+                },
+            )
+            .with_smoother(SmoothingStyle::Logarithmic(20.0))
+            .with_unit(" Hz")
+            .with_value_to_string(formatters::v2s_f32_rounded(2)),
+
+            mix: FloatParam::new("Mix", 1.0, FloatRange::Linear { min: 0.0, max: 1.0 })
+                .with_value_to_string(formatters::v2s_f32_percentage(0))
+                .with_string_to_value(formatters::s2v_f32_percentage())
+                .with_unit(" %"),
+        }
+    }
+}
+
+impl Plugin for Autowah {
+    const NAME: &'static str = "Autowah";
+    const VENDOR: &'static str = "Your Studio";
+    const URL: &'static str = env!("CARGO_PKG_HOMEPAGE");
+    const EMAIL: &'static str = "contact@yourstudio.com";
+    const VERSION: &'static str = env!("CARGO_PKG_VERSION");
+
+    const AUDIO_IO_LAYOUTS: &'static [AudioIOLayout] = &[layouts::STEREO, layouts::MONO];
+
+    type SysExMessage = ();
+    type BackgroundTask = ();
+
+    fn params(&self) -> Arc<dyn Params> {
+        self.params.clone()
+    }
+
+    fn editor(&mut self, _async_executor: AsyncExecutor<Self>) -> Option<Box<dyn Editor>> {
+        editor::create(self.params.clone())
+    }
+
+    fn initialize(
+        &mut self,
+        audio_io_layout: &AudioIOLayout,
+        buffer_config: &BufferConfig,
+        _context: &mut impl InitContext<Self>,
+    ) -> bool {
+        let num_channels = audio_io_layout
+            .main_output_channels
+            .map_or(0, |channels| channels.get() as usize);
+        let sample_rate = buffer_config.sample_rate;
+
+        self.follower = EnvelopeFollower::new(sample_rate);
+        self.lfo = Lfo::new(sample_rate);
+        self.filters = vec![StateVariableFilter::new(sample_rate); num_channels];
+        self.mix = MixStage::new(
+            sample_rate,
+            num_channels,
+            buffer_config.max_buffer_size as usize,
+            0,
+        );
+        self.mix.set_mix(self.target_mix());
+        self.mix.reset();
+        true
+    }
+
+    fn reset(&mut self) {
+        self.follower.reset();
+        self.lfo.reset();
+        for filter in &mut self.filters {
+            filter.reset();
+        }
+        self.mix.reset();
+    }
+
+    fn process(
+        &mut self,
+        buffer: &mut Buffer,
+        _aux: &mut AuxiliaryBuffers,
+        _context: &mut impl ProcessContext<Self>,
+    ) -> ProcessStatus {
+        self.process_channels(buffer.as_slice());
+
+        ProcessStatus::Normal
+    }
+}
+
+impl Autowah {
+    fn process_channels(&mut self, channels: &mut [&mut [f32]]) {
+        self.mix.set_mix(self.target_mix());
+        self.mix.capture_dry(channels);
+
+        let params = &self.params;
+        let source = params.source.value();
+        let direction = params.direction.value();
+        let mode = match params.filter.value() {
+            WahFilter::BandPass => FilterMode::BandPass,
+            WahFilter::LowPass => FilterMode::LowPass,
+        };
+        self.follower.set_attack(params.attack.value() / 1000.0);
+        self.follower.set_release(params.release.value() / 1000.0);
+        for filter in &mut self.filters {
+            filter.set_mode(mode);
+        }
+
+        let num_samples = channels.first().map_or(0, |channel| channel.len());
+        for i in 0..num_samples {
+            let frequency = params.frequency.smoothed.next();
+            let range = params.range.smoothed.next();
+            let q = params.q.smoothed.next();
+            let sensitivity = db_to_gain(params.sensitivity.smoothed.next());
+            self.lfo.set_rate(params.rate.smoothed.next());
+
+            // Both run all the time so switching the source doesn't restart the sweep
+            let peak = channels
+                .iter()
+                .map(|channel| channel[i].abs())
+                .fold(0.0, f32::max);
+            let envelope = self.follower.process(peak * sensitivity);
+            let lfo = 0.5 + 0.5 * self.lfo.next_sample();
+            let amount = match source {
+                Source::Envelope => envelope,
+                Source::Lfo => lfo,
+            };
+
+            // The band pass peaks at Q times the input, scale it back to unity
+            let cutoff = sweep_cutoff(frequency, range, direction, amount);
+            let gain = if mode == FilterMode::BandPass {
+                q.recip()
+            } else {
+                1.0
+            };
+            for (filter, channel) in self.filters.iter_mut().zip(channels.iter_mut()) {
+                filter.set_params(cutoff, q);
+                channel[i] = filter.process(channel[i]) * gain;
+            }
+        }
+
+        for channel in channels.iter() {
+            guard::check_block("Autowah", channel);
+        }
+        self.mix.mix_into(channels);
+    }
+
+    /// Bypassing fades to the dry signal through the mix stage.
+    fn target_mix(&self) -> f32 {
+        if self.params.bypass.value() {
+            0.0
+        } else {
+            self.params.mix.value()
+        }
+    }
+}
+
+impl ClapPlugin for Autowah {
+    const CLAP_ID: &'static str = "com.yourstudio.autowah";
+    const CLAP_DESCRIPTION: Option<&'static str> =
+        Some("An envelope filter and auto-wah, swept by the input's level or an LFO");
+    const CLAP_MANUAL_URL: Option<&'static str> = Some(Self::URL);
+    const CLAP_SUPPORT_URL: Option<&'static str> = None;
+    const CLAP_FEATURES: &'static [ClapFeature] = &[
+        ClapFeature::AudioEffect,
+        ClapFeature::Filter,
+        ClapFeature::Stereo,
+        ClapFeature::Mono,
+    ];
+}
+
+impl Vst3Plugin for Autowah {
+    const VST3_CLASS_ID: [u8; 16] = *b"AutowahEnvFilter";
+    const VST3_SUBCATEGORIES: &'static [Vst3SubCategory] =
+        &[Vst3SubCategory::Fx, Vst3SubCategory::Filter];
+}
+
+nih_export_clap!(Autowah);
+nih_export_vst3!(Autowah);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use plugin_scaffold::test_util::assert_no_alloc;
+
+    plugin_scaffold::install_alloc_disabler!();
+
+    const SAMPLE_RATE: f32 = 48000.0;
+    const BLOCK_SIZE: usize = 512;
+
+    fn test_plugin(params: AutowahParams) -> Autowah {
+        for param in [
+            &params.frequency,
+            &params.range,
+            &params.q,
+            &params.sensitivity,
+            &params.rate,
+        ] {
+            param.smoothed.reset(param.value());
+        }
+
+        let mut plugin = Autowah {
+            params: Arc::new(params),
+            follower: EnvelopeFollower::new(SAMPLE_RATE),
+            lfo: Lfo::new(SAMPLE_RATE),
+            filters: vec![StateVariableFilter::new(SAMPLE_RATE)],
+            mix: MixStage::new(SAMPLE_RATE, 1, BLOCK_SIZE, 0),
+        };
+        plugin.mix.set_mix(plugin.target_mix());
+        plugin.mix.reset();
+        plugin
+    }
+
+    /// A low pass sweeping four octaves up from 200 Hz.
+    fn low_pass_params() -> AutowahParams {
+        AutowahParams {
+            filter: EnumParam::new("Filter", WahFilter::LowPass),
+            frequency: FloatParam::new(
+                "Frequency",
+                200.0,
+                FloatRange::Linear {
+                    min: 50.0,
+                    max: 5000.0,
+                },
+            ),
+            range: FloatParam::new("Range", 4.0, FloatRange::Linear { min: 0.0, max: 6.0 }),
+            q: FloatParam::new(
+                "Q",
+                0.7,
+                FloatRange::Linear {
+                    min: 0.5,
+                    max: 20.0,
+                },
+            ),
+            ..AutowahParams::default()
+        }
+    }
+
+    /// One second of a sine at `frequency`.
+    fn tone(frequency: f32, amplitude: f32) -> Vec<f32> {
+        (0..SAMPLE_RATE as usize)
+            .map(|i| (std::f32::consts::TAU * frequency * i as f32 / SAMPLE_RATE).sin() * amplitude)
+            .collect()
+    }
+
+    fn process(plugin: &mut Autowah, input: &[f32]) -> Vec<f32> {
+        let mut output = input.to_vec();
+        for block in output.chunks_mut(BLOCK_SIZE) {
+            plugin.process_channels(&mut [block]);
+        }
+        output
+    }
+
+    /// The peak level from `start` to `end` seconds.
+    fn peak(samples: &[f32], start: f32, end: f32) -> f32 {
+        samples[(start * SAMPLE_RATE) as usize..(end * SAMPLE_RATE) as usize]
+            .iter()
+            .fold(0.0, |peak, sample| f32::max(peak, sample.abs()))
+    }
+
+    #[test]
+    fn direction_sets_where_the_sweep_starts() {
+        assert_eq!(sweep_cutoff(200.0, 3.0, Direction::Up, 0.0), 200.0);
+        assert_eq!(sweep_cutoff(200.0, 3.0, Direction::Up, 1.0), 1600.0);
+        assert_eq!(sweep_cutoff(200.0, 3.0, Direction::Down, 0.0), 1600.0);
+        assert_eq!(sweep_cutoff(200.0, 3.0, Direction::Down, 1.0), 200.0);
+        // Loud input beyond full scale stays at the end of the range
+        assert_eq!(sweep_cutoff(200.0, 3.0, Direction::Up, 4.0), 1600.0);
+    }
+
+    #[test]
+    fn louder_input_opens_the_filter() {
+        let quiet = process(&mut test_plugin(low_pass_params()), &tone(3000.0, 0.01));
+        let loud = process(&mut test_plugin(low_pass_params()), &tone(3000.0, 0.5));
+
+        let quiet_gain = peak(&quiet, 0.5, 1.0) / 0.01;
+        let loud_gain = peak(&loud, 0.5, 1.0) / 0.5;
+        assert!(loud_gain > quiet_gain * 10.0, "{loud_gain} vs {quiet_gain}");
+    }
+
+    #[test]
+    fn down_direction_closes_the_filter_on_loud_input() {
+        let params = AutowahParams {
+            direction: EnumParam::new("Direction", Direction::Down),
+            ..low_pass_params()
+        };
+        let loud = process(&mut test_plugin(params), &tone(3000.0, 0.5));
+        let loud_gain = peak(&loud, 0.5, 1.0) / 0.5;
+        assert!(loud_gain < 0.05, "{loud_gain}");
+    }
+
+    #[test]
+    fn lfo_sweeps_a_steady_tone() {
+        let params = AutowahParams {
+            source: EnumParam::new("Source", Source::Lfo),
+            filter: EnumParam::new("Filter", WahFilter::BandPass),
+            q: FloatParam::new(
+                "Q",
+                4.0,
+                FloatRange::Linear {
+                    min: 0.5,
+                    max: 20.0,
+                },
+            ),
+            rate: FloatParam::new(
+                "Rate",
+                2.0,
+                FloatRange::Linear {
+                    min: 0.05,
+                    max: 10.0,
+                },
+            ),
+            ..low_pass_params()
+        };
+        let output = process(&mut test_plugin(params), &tone(800.0, 0.25));
+
+        let windows: Vec<f32> = (0..20)
+            .map(|window| {
+                let start = window as f32 * 0.05;
+                peak(&output, start, start + 0.05)
+            })
+            .collect();
+        let loudest = windows.iter().cloned().fold(0.0, f32::max);
+        let quietest = windows.iter().cloned().fold(f32::INFINITY, f32::min);
+        // The band passes the tone at full level as it sweeps past
+        assert!((loudest - 0.25).abs() < 0.03, "{loudest}");
+        assert!(quietest < loudest * 0.25, "{quietest} vs {loudest}");
+    }
+
+    #[test]
+    fn maximum_resonance_stays_bounded() {
+        let params = AutowahParams {
+            filter: EnumParam::new("Filter", WahFilter::LowPass),
+            q: FloatParam::new(
+                "Q",
+                20.0,
+                FloatRange::Linear {
+                    min: 0.5,
+                    max: 20.0,
+                },
+            ),
+            sensitivity: FloatParam::new(
+                "Sensitivity",
+                36.0,
+                FloatRange::Linear {
+                    min: -12.0,
+                    max: 36.0,
+                },
+            ),
+            ..AutowahParams::default()
+        };
+        // A saw with every harmonic for the resonance to catch as it sweeps
+        let input: Vec<f32> = (0..SAMPLE_RATE as usize)
+            .map(|i| (i as f32 * 110.0 / SAMPLE_RATE).fract() * 2.0 - 1.0)
+            .collect();
+        let output = process(&mut test_plugin(params), &input);
+
+        for sample in output {
+            assert!(sample.is_finite() && sample.abs() < 25.0, "{sample}");
+        }
+    }
+
+    #[test]
+    fn processing_does_not_allocate() {
+        let mut plugin = test_plugin(low_pass_params());
+        let mut samples = tone(3000.0, 0.5);
+        assert_no_alloc(|| {
+            for block in samples.chunks_mut(BLOCK_SIZE) {
+                plugin.process_channels(&mut [block]);
+            }
+        });
+    }
+}
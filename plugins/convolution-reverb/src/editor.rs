@@ -0,0 +1,103 @@
+use crate::ir::IrBank;
+use crate::ReverbParams;
+use nih_plug::prelude::*;
+use nih_plug_egui::create_egui_editor;
+use nih_plug_egui::egui;
+use std::path::Path;
+use std::sync::Arc;
+use ui_widgets::{EditorParams, EditorShell, ParamKnob};
+
+pub(crate) fn default_params() -> EditorParams {
+    EditorParams::new((380, 260))
+}
+
+/// GUI-thread editor state.
+struct EditorState {
+    /// The path being typed into the path field.
+    path: String,
+    load_error: Option<String>,
+}
+
+pub(crate) fn create(params: Arc<ReverbParams>, bank: Arc<IrBank>) -> Option<Box<dyn Editor>> {
+    let state = EditorState {
+        path: bank.path().unwrap_or_default(),
+        load_error: None,
+    };
+
+    create_egui_editor(
+        params.editor.state.clone(),
+        state,
+        |_, _| {},
+        move |egui_ctx, setter, state| {
+            EditorShell::new(&params.editor)
+                .with_bypass(&params.bypass)
+                .show(egui_ctx, setter, |ui| {
+                    impulse_response_row(ui, &params, &bank, state);
+                    ui.separator();
+
+                    ui.horizontal(|ui| {
+                        ui.add(ParamKnob::for_param(&params.pre_delay, setter));
+                        ui.add(ParamKnob::for_param(&params.stretch, setter))
+                            .on_hover_text("Lengthen or shorten the impulse response");
+                        ui.add(ParamKnob::for_param(&params.damping, setter))
+                            .on_hover_text(
+                                "Make the high frequencies die out faster than the lows",
+                            );
+                        ui.add(ParamKnob::for_param(&params.mix, setter));
+                    });
+                });
+        },
+    )
+}
+
+/// The loaded impulse response, and the path field for loading another one.
+fn impulse_response_row(
+    ui: &mut egui::Ui,
+    params: &ReverbParams,
+    bank: &IrBank,
+    state: &mut EditorState,
+) {
+    let info = bank.info();
+    let name = match &info.path {
+        Some(path) => Path::new(path)
+            .file_name()
+            .map_or_else(|| path.clone(), |name| name.to_string_lossy().into_owned()),
+        None => "Built-in room".to_owned(),
+    };
+    let channels = match info.num_channels {
+        1 => "mono".to_owned(),
+        2 => "stereo".to_owned(),
+        channels => format!("{channels} channels"),
+    };
+    ui.label(format!("{name}, {:.2} s, {channels}", info.length_seconds));
+
+    ui.horizontal(|ui| {
+        ui.add(
+            egui::TextEdit::singleline(&mut state.path)
+                .hint_text("Path to an impulse response file")
+                .desired_width(230.0),
+        );
+        if ui.button("Load").clicked() {
+            let path = state.path.trim().to_owned();
+            match bank.load(&path) {
+                Ok(()) => {
+                    bank.build(params.ir_settings());
+                    *params.ir_path.write().unwrap() = Some(path);
+                    state.load_error = None;
+                }
+                Err(err) => state.load_error = Some(err.to_string()),
+            }
+        }
+        if ui.button("Built-in").clicked() {
+            bank.load_built_in();
+            bank.build(params.ir_settings());
+            *params.ir_path.write().unwrap() = None;
+            state.path.clear();
+            state.load_error = None;
+        }
+    });
+
+    if let Some(err) = &state.load_error {
+        ui.colored_label(ui.visuals().error_fg_color, err);
+    }
+}
@@ -0,0 +1,248 @@
+//! The impulse response the reverb convolves with: the loaded file, and the convolvers built
+//! from it with the current stretch and damping.
+//!
+//! Files are decoded on the editor's thread and convolvers are rebuilt on the background thread,
+//! since both take far too long for the audio thread. New convolvers are handed over through a
+//! mutex the audio thread only ever `try_lock()`s, and the ones they replace are passed back the
+//! same way, so the audio thread never allocates or frees them.
+
+use dsp_core::convolution::Convolver;
+use dsp_core::random::Xorshift32;
+use std::sync::Mutex;
+
+/// The convolvers' block size, which is also the plugin's latency.
+pub const BLOCK_SIZE: usize = 256;
+
+/// Longer responses are cut off to bound the CPU load.
+pub const MAX_LENGTH_SECONDS: f32 = 10.0;
+
+/// Damping fades the response towards a low passed copy of itself at this cutoff.
+const DAMPING_CUTOFF: f32 = 2000.0;
+
+/// How the loaded response is reshaped before convolving with it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IrSettings {
+    /// The length relative to the file's, which also shifts its pitch.
+    pub stretch: f32,
+    /// From 0 to 1, how much the high frequencies die out over the response.
+    pub damping: f32,
+}
+
+/// An impulse response as loaded, before it's stretched or damped.
+struct Source {
+    /// The file it was loaded from, `None` for the built-in room.
+    path: Option<String>,
+    sample_rate: f32,
+    channels: Vec<Vec<f32>>,
+}
+
+impl Source {
+    /// Two seconds of exponentially decaying noise, decorrelated between the channels, so the
+    /// plugin sounds like a room before any file is loaded.
+    fn built_in_room() -> Self {
+        const SAMPLE_RATE: f32 = 48000.0;
+        const LENGTH_SECONDS: f32 = 2.0;
+        // Decays by 60 dB over the length
+        let time_constant = LENGTH_SECONDS / 1000f32.ln();
+
+        let channels = (0..2)
+            .map(|channel| {
+                let mut random = Xorshift32::new(0x5eed + channel);
+                (0..(SAMPLE_RATE * LENGTH_SECONDS) as usize)
+                    .map(|i| {
+                        let t = i as f32 / SAMPLE_RATE;
+                        random.next_bipolar() * (-t / time_constant).exp()
+                    })
+                    .collect()
+            })
+            .collect();
+
+        Self {
+            path: None,
+            sample_rate: SAMPLE_RATE,
+            channels,
+        }
+    }
+
+    /// The response resampled to `sample_rate` and reshaped by `settings`, one buffer per
+    /// channel, normalized so the loudest channel has unit energy. A reverb built from it then
+    /// has about the same level as its input.
+    fn prepare(&self, sample_rate: f32, settings: IrSettings) -> Vec<Vec<f32>> {
+        let step = self.sample_rate / (sample_rate * settings.stretch);
+        let source_len = self.channels[0].len();
+        let len =
+            ((source_len as f32 / step) as usize).min((MAX_LENGTH_SECONDS * sample_rate) as usize);
+        let lowpass = 1.0 - (-std::f32::consts::TAU * DAMPING_CUTOFF / sample_rate).exp();
+
+        let mut channels: Vec<Vec<f32>> = self
+            .channels
+            .iter()
+            .map(|source| {
+                let mut filtered = 0.0;
+                (0..len)
+                    .map(|i| {
+                        // Linear interpolation is plenty for the diffuse tail of a reverb
+                        let position = i as f32 * step;
+                        let index = position as usize;
+                        let frac = position - index as f32;
+                        let current = source.get(index).copied().unwrap_or(0.0);
+                        let next = source.get(index + 1).copied().unwrap_or(0.0);
+                        let sample = current + (next - current) * frac;
+
+                        filtered += (sample - filtered) * lowpass;
+                        let damping = settings.damping * i as f32 / len as f32;
+                        sample + (filtered - sample) * damping
+                    })
+                    .collect()
+            })
+            .collect();
+
+        let energy = channels
+            .iter()
+            .map(|channel| channel.iter().map(|sample| sample * sample).sum::<f32>())
+            .fold(0.0, f32::max);
+        if energy > 0.0 {
+            let gain = energy.sqrt().recip();
+            for sample in channels.iter_mut().flatten() {
+                *sample *= gain;
+            }
+        }
+
+        channels
+    }
+}
+
+struct Handover {
+    /// Whether `convolvers` are new ones for the audio thread, rather than the ones it replaced.
+    fresh: bool,
+    convolvers: Vec<Convolver>,
+}
+
+/// The shape of the audio the convolvers are built for.
+#[derive(Clone, Copy)]
+struct Layout {
+    sample_rate: f32,
+    num_channels: usize,
+}
+
+/// Information about the loaded response for the editor.
+pub struct IrInfo {
+    pub path: Option<String>,
+    pub num_channels: usize,
+    pub length_seconds: f32,
+}
+
+pub struct IrBank {
+    source: Mutex<Source>,
+    layout: Mutex<Layout>,
+    handover: Mutex<Handover>,
+}
+
+impl IrBank {
+    pub fn new() -> Self {
+        Self {
+            source: Mutex::new(Source::built_in_room()),
+            layout: Mutex::new(Layout {
+                sample_rate: 44100.0,
+                num_channels: 0,
+            }),
+            handover: Mutex::new(Handover {
+                fresh: false,
+                convolvers: Vec::new(),
+            }),
+        }
+    }
+
+    /// Set the sample rate and channel count to build for, from `initialize()`.
+    pub fn set_layout(&self, sample_rate: f32, num_channels: usize) {
+        *self.layout.lock().unwrap() = Layout {
+            sample_rate,
+            num_channels,
+        };
+    }
+
+    /// Load a WAV, AIFF, or FLAC impulse response. Blocks on file IO, so never call this from
+    /// the audio thread. The convolvers still need to be rebuilt with [`build()`][Self::build()].
+    pub fn load(&self, path: &str) -> Result<(), audio_file::Error> {
+        let audio = audio_file::read(path)?;
+        self.set_source(
+            Some(path.to_owned()),
+            audio.sample_rate as f32,
+            audio.channels,
+        );
+        Ok(())
+    }
+
+    /// Go back to the built-in room.
+    pub fn load_built_in(&self) {
+        *self.source.lock().unwrap() = Source::built_in_room();
+    }
+
+    /// Replace the response with one decoded elsewhere.
+    pub fn set_source(&self, path: Option<String>, sample_rate: f32, channels: Vec<Vec<f32>>) {
+        *self.source.lock().unwrap() = Source {
+            path,
+            sample_rate,
+            channels,
+        };
+    }
+
+    /// The file the response was loaded from, `None` for the built-in room.
+    pub fn path(&self) -> Option<String> {
+        self.source.lock().unwrap().path.clone()
+    }
+
+    pub fn info(&self) -> IrInfo {
+        let source = self.source.lock().unwrap();
+        IrInfo {
+            path: source.path.clone(),
+            num_channels: source.channels.len(),
+            length_seconds: source.channels[0].len() as f32 / source.sample_rate,
+        }
+    }
+
+    /// Build convolvers for every channel with the given settings and queue them up for the
+    /// audio thread. Takes a while for long responses, so never call this from the audio thread.
+    /// Channels beyond the response's wrap around, so a mono response is used for both sides.
+    pub fn build(&self, settings: IrSettings) {
+        let convolvers = self.build_now(settings);
+
+        // This also frees the convolvers the audio thread passed back
+        *self.handover.lock().unwrap() = Handover {
+            fresh: true,
+            convolvers,
+        };
+    }
+
+    /// Build convolvers and return them straight away, for `initialize()`.
+    pub fn build_now(&self, settings: IrSettings) -> Vec<Convolver> {
+        let layout = *self.layout.lock().unwrap();
+        let responses = self
+            .source
+            .lock()
+            .unwrap()
+            .prepare(layout.sample_rate, settings);
+
+        (0..layout.num_channels)
+            .map(|channel| Convolver::new(&responses[channel % responses.len()], BLOCK_SIZE))
+            .collect()
+    }
+
+    /// Pick up newly built convolvers on the audio thread. The new ones replace `current`, which
+    /// move to `previous` to ring out, and the old `previous` ones are passed back to be freed.
+    /// Returns whether anything changed, which it doesn't when another thread is busy with the
+    /// handover or the new convolvers were built for a different channel count.
+    pub fn take_fresh(&self, current: &mut Vec<Convolver>, previous: &mut Vec<Convolver>) -> bool {
+        let Ok(mut handover) = self.handover.try_lock() else {
+            return false;
+        };
+        if !handover.fresh || handover.convolvers.len() != current.len() {
+            return false;
+        }
+
+        std::mem::swap(current, &mut handover.convolvers);
+        std::mem::swap(previous, &mut handover.convolvers);
+        handover.fresh = false;
+        true
+    }
+}
@@ -0,0 +1,505 @@
+use dsp_core::convolution::Convolver;
+use dsp_core::guard;
+use dsp_core::mix::MixStage;
+use ir::{IrBank, IrSettings, BLOCK_SIZE};
+use nih_plug::prelude::*;
+use plugin_scaffold::formatters::{s2v_f32_ms_then_s, v2s_f32_ms_then_s};
+use plugin_scaffold::layouts;
+use std::sync::{Arc, RwLock};
+use ui_widgets::EditorParams;
+
+mod editor;
+mod ir;
+
+/// The longest pre-delay, in milliseconds.
+const MAX_PRE_DELAY_MS: f32 = 500.0;
+
+/// A reverb that convolves with an impulse response loaded from a WAV, AIFF, or FLAC file.
+struct ConvolutionReverb {
+    params: Arc<ReverbParams>,
+    bank: Arc<IrBank>,
+    /// One convolver per channel.
+    convolvers: Vec<Convolver>,
+    /// The convolvers replaced by the last impulse response change. They keep ringing out with
+    /// silent input so the old tail isn't cut off.
+    previous: Vec<Convolver>,
+    /// How many more samples `previous` has to ring out.
+    previous_remaining: usize,
+    pre_delays: Vec<PreDelay>,
+    /// The settings the convolvers were last built or requested with.
+    requested: IrSettings,
+    sample_rate: f32,
+    /// Mixes in the dry signal, delayed to line up with the reverb.
+    mix: MixStage,
+}
+
+#[derive(Params)]
+struct ReverbParams {
+    #[nested]
+    editor: EditorParams,
+
+    /// The file the impulse response was loaded from, reloaded with the plugin state.
+    /// `None` uses the built-in room.
+    #[persist = "impulse-response"]
+    pub ir_path: RwLock<Option<String>>,
+
+    /// The host's bypass switch.
+    #[id = "bypass"]
+    pub bypass: BoolParam,
+
+    /// Delays the reverb behind the dry signal, in milliseconds.
+    #[id = "pre_delay"]
+    pub pre_delay: FloatParam,
+
+    /// Lengthens or shortens the impulse response, in percent.
+    #[id = "stretch"]
+    pub stretch: FloatParam,
+
+    /// Makes the high frequencies die out faster than the lows, in percent.
+    #[id = "damping"]
+    pub damping: FloatParam,
+
+    #[id = "mix"]
+    pub mix: FloatParam,
+}
+
+impl ReverbParams {
+    pub fn ir_settings(&self) -> IrSettings {
+        IrSettings {
+            stretch: self.stretch.value() / 100.0,
+            damping: self.damping.value() / 100.0,
+        }
+    }
+}
+
+/// Rebuilding the convolvers after the stretch or damping changed.
+pub enum ReverbTask {
+    Rebuild(IrSettings),
+}
+
+/// A delay line for the pre-delay.
+struct PreDelay {
+    buffer: Vec<f32>,
+    pos: usize,
+}
+
+impl PreDelay {
+    fn new(max_delay: usize) -> Self {
+        Self {
+            buffer: vec![0.0; max_delay + 1],
+            pos: 0,
+        }
+    }
+
+    fn reset(&mut self) {
+        self.buffer.fill(0.0);
+        self.pos = 0;
+    }
+
+    /// Delay `input` by `delay` samples, up to the maximum delay.
+    #[inline]
+    fn process(&mut self, input: f32, delay: usize) -> f32 {
+        let len = self.buffer.len();
+        self.buffer[self.pos] = input;
+        let output = self.buffer[(self.pos + len - delay.min(len - 1)) % len];
+        self.pos = (self.pos + 1) % len;
+        output
+    }
+}
+
+impl Default for ConvolutionReverb {
+    fn default() -> Self {
+        let params = Arc::new(ReverbParams::default());
+        Self {
+            requested: params.ir_settings(),
+            params,
+            bank: Arc::new(IrBank::new()),
+            convolvers: Vec::new(),
+            previous: Vec::new(),
+            previous_remaining: 0,
+            pre_delays: Vec::new(),
+            sample_rate: 44100.0,
+            mix: MixStage::new(44100.0, 0, 0, 0),
+        }
+    }
+}
+
+impl Default for ReverbParams {
+    fn default() -> Self {
+        let percentage = |name, default, min, max| {
+            FloatParam::new(name, default, FloatRange::Linear { min, max })
+                .with_unit(" %")
+                .with_value_to_string(formatters::v2s_f32_rounded(0))
+        };
+
+        Self {
+            editor: editor::default_params(),
+            ir_path: RwLock::new(None),
+
+            bypass: BoolParam::new("Bypass", false).make_bypass(),
+
+            pre_delay: FloatParam::new(
+                "Pre-Delay",
+                0.0,
+                FloatRange::Skewed {
+                    min: 0.0,
+                    max: MAX_PRE_DELAY_MS,
+                    factor: FloatRange::skew_factor(-1.0),
+                },
+            )
+            .with_value_to_string(v2s_f32_ms_then_s(1))
+            .with_string_to_value(s2v_f32_ms_then_s()),
+
+            stretch: percentage("Stretch", 100.0, 50.0, 200.0),
+            damping: percentage("Damping", 0.0, 0.0, 100.0),
+
+            mix: FloatParam::new("Mix", 0.3, FloatRange::Linear { min: 0.0, max: 1.0 })
+                .with_value_to_string(formatters::v2s_f32_percentage(0))
+                .with_string_to_value(formatters::s2v_f32_percentage())
+                .with_unit(" %"),
+        }
+    }
+}
+
+impl Plugin for ConvolutionReverb {
+    const NAME: &'static str = "Convolution Reverb";
+    const VENDOR: &'static str = "Your Studio";
+    const URL: &'static str = env!("CARGO_PKG_HOMEPAGE");
+    const EMAIL: &'static str = "contact@yourstudio.com";
+    const VERSION: &'static str = env!("CARGO_PKG_VERSION");
+
+    const AUDIO_IO_LAYOUTS: &'static [AudioIOLayout] = &[layouts::STEREO, layouts::MONO];
+
+    type SysExMessage = ();
+    type BackgroundTask = ReverbTask;
+
+    fn params(&self) -> Arc<dyn Params> {
+        self.params.clone()
+    }
+
+    fn task_executor(&mut self) -> TaskExecutor<Self> {
+        let bank = self.bank.clone();
+        Box::new(move |task| match task {
+            ReverbTask::Rebuild(settings) => bank.build(settings),
+        })
+    }
+
+    fn editor(&mut self, _async_executor: AsyncExecutor<Self>) -> Option<Box<dyn Editor>> {
+        editor::create(self.params.clone(), self.bank.clone())
+    }
+
+    fn initialize(
+        &mut self,
+        audio_io_layout: &AudioIOLayout,
+        buffer_config: &BufferConfig,
+        context: &mut impl InitContext<Self>,
+    ) -> bool {
+        let num_channels = audio_io_layout
+            .main_output_channels
+            .map_or(0, |channels| channels.get() as usize);
+
+        self.load_impulse_response();
+        self.activate(
+            buffer_config.sample_rate,
+            num_channels,
+            buffer_config.max_buffer_size as usize,
+        );
+
+        context.set_latency_samples(BLOCK_SIZE as u32);
+        true
+    }
+
+    fn reset(&mut self) {
+        for convolver in &mut self.convolvers {
+            convolver.reset();
+        }
+        for pre_delay in &mut self.pre_delays {
+            pre_delay.reset();
+        }
+        self.previous_remaining = 0;
+        self.mix.reset();
+    }
+
+    fn process(
+        &mut self,
+        buffer: &mut Buffer,
+        _aux: &mut AuxiliaryBuffers,
+        context: &mut impl ProcessContext<Self>,
+    ) -> ProcessStatus {
+        let settings = self.params.ir_settings();
+        if settings != self.requested {
+            self.requested = settings;
+            context.execute_background(ReverbTask::Rebuild(settings));
+        }
+
+        self.process_channels(buffer.as_slice());
+
+        ProcessStatus::Normal
+    }
+}
+
+impl ConvolutionReverb {
+    /// Load the impulse response saved with the plugin state. The file may have been loaded
+    /// already when the plugin is reactivated, or by the editor after the state was saved.
+    fn load_impulse_response(&mut self) {
+        let path = self.params.ir_path.read().unwrap().clone();
+        if self.bank.path() == path {
+            return;
+        }
+
+        match path {
+            Some(path) => {
+                if let Err(err) = self.bank.load(&path) {
+                    nih_warn!("Could not load the impulse response '{path}': {err}");
+                }
+            }
+            None => self.bank.load_built_in(),
+        }
+    }
+
+    /// Allocate everything for the sample rate and channel count, building the convolvers
+    /// right away.
+    fn activate(&mut self, sample_rate: f32, num_channels: usize, max_buffer_size: usize) {
+        self.sample_rate = sample_rate;
+        self.bank.set_layout(sample_rate, num_channels);
+        self.requested = self.params.ir_settings();
+        self.convolvers = self.bank.build_now(self.requested);
+        self.previous = Vec::new();
+        self.previous_remaining = 0;
+
+        let max_pre_delay = (MAX_PRE_DELAY_MS / 1000.0 * sample_rate).ceil() as usize;
+        self.pre_delays = (0..num_channels)
+            .map(|_| PreDelay::new(max_pre_delay))
+            .collect();
+
+        self.mix = MixStage::new(sample_rate, num_channels, max_buffer_size, BLOCK_SIZE);
+        self.mix.set_latency(BLOCK_SIZE);
+        self.mix.set_mix(self.target_mix());
+        self.mix.reset();
+    }
+
+    fn process_channels(&mut self, channels: &mut [&mut [f32]]) {
+        if self
+            .bank
+            .take_fresh(&mut self.convolvers, &mut self.previous)
+        {
+            self.previous_remaining = self
+                .previous
+                .first()
+                .map_or(0, |convolver| convolver.len() + convolver.latency());
+        }
+
+        self.mix.set_mix(self.target_mix());
+        self.mix.capture_dry(channels);
+
+        let pre_delay = (self.params.pre_delay.value() / 1000.0 * self.sample_rate) as usize;
+        let num_samples = channels.first().map_or(0, |channel| channel.len());
+        let ringing = self.previous_remaining > 0;
+        for (idx, channel) in channels.iter_mut().enumerate() {
+            let (Some(convolver), Some(delay)) =
+                (self.convolvers.get_mut(idx), self.pre_delays.get_mut(idx))
+            else {
+                channel.fill(0.0);
+                continue;
+            };
+
+            let mut previous = self.previous.get_mut(idx).filter(|_| ringing);
+            for sample in channel.iter_mut() {
+                let mut wet = convolver.process(delay.process(*sample, pre_delay));
+                if let Some(previous) = previous.as_mut() {
+                    wet += previous.process(0.0);
+                }
+                *sample = wet;
+            }
+        }
+        self.previous_remaining = self.previous_remaining.saturating_sub(num_samples);
+
+        for channel in channels.iter() {
+            guard::check_block("ConvolutionReverb", channel);
+        }
+        self.mix.mix_into(channels);
+    }
+
+    /// Bypassing fades to the dry signal through the mix stage, which keeps delaying it by the
+    /// latency the host is compensating for.
+    fn target_mix(&self) -> f32 {
+        if self.params.bypass.value() {
+            0.0
+        } else {
+            self.params.mix.value()
+        }
+    }
+}
+
+impl ClapPlugin for ConvolutionReverb {
+    const CLAP_ID: &'static str = "com.yourstudio.convolution-reverb";
+    const CLAP_DESCRIPTION: Option<&'static str> =
+        Some("A convolution reverb for impulse responses loaded from audio files");
+    const CLAP_MANUAL_URL: Option<&'static str> = Some(Self::URL);
+    const CLAP_SUPPORT_URL: Option<&'static str> = None;
+    const CLAP_FEATURES: &'static [ClapFeature] = &[
+        ClapFeature::AudioEffect,
+        ClapFeature::Reverb,
+        ClapFeature::Stereo,
+        ClapFeature::Mono,
+    ];
+}
+
+impl Vst3Plugin for ConvolutionReverb {
+    const VST3_CLASS_ID: [u8; 16] = *b"ConvolutionRvb00";
+    const VST3_SUBCATEGORIES: &'static [Vst3SubCategory] =
+        &[Vst3SubCategory::Fx, Vst3SubCategory::Reverb];
+}
+
+nih_export_clap!(ConvolutionReverb);
+nih_export_vst3!(ConvolutionReverb);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use plugin_scaffold::test_util::assert_no_alloc;
+
+    plugin_scaffold::install_alloc_disabler!();
+
+    const SAMPLE_RATE: f32 = 48000.0;
+    const MAX_BLOCK_SIZE: usize = 512;
+
+    /// A fully wet reverb with a mono impulse response at the plugin's sample rate.
+    fn test_plugin(
+        impulse_response: Vec<f32>,
+        pre_delay_ms: f32,
+        stretch: f32,
+    ) -> ConvolutionReverb {
+        let params = ReverbParams {
+            pre_delay: FloatParam::new(
+                "Pre-Delay",
+                pre_delay_ms,
+                FloatRange::Linear {
+                    min: 0.0,
+                    max: MAX_PRE_DELAY_MS,
+                },
+            ),
+            stretch: FloatParam::new(
+                "Stretch",
+                stretch,
+                FloatRange::Linear {
+                    min: 50.0,
+                    max: 200.0,
+                },
+            ),
+            mix: FloatParam::new("Mix", 1.0, FloatRange::Linear { min: 0.0, max: 1.0 }),
+            ..ReverbParams::default()
+        };
+
+        let bank = Arc::new(IrBank::new());
+        bank.set_source(None, SAMPLE_RATE, vec![impulse_response]);
+        let mut plugin = ConvolutionReverb {
+            params: Arc::new(params),
+            bank,
+            ..ConvolutionReverb::default()
+        };
+        plugin.activate(SAMPLE_RATE, 1, MAX_BLOCK_SIZE);
+        plugin
+    }
+
+    fn impulse(len: usize, at: usize) -> Vec<f32> {
+        let mut samples = vec![0.0; len];
+        samples[at] = 1.0;
+        samples
+    }
+
+    fn process(plugin: &mut ConvolutionReverb, input: &[f32]) -> Vec<f32> {
+        let mut output = input.to_vec();
+        for block in output.chunks_mut(MAX_BLOCK_SIZE) {
+            plugin.process_channels(&mut [block]);
+        }
+        output
+    }
+
+    fn loudest_sample(samples: &[f32]) -> usize {
+        samples
+            .iter()
+            .enumerate()
+            .max_by(|(_, a), (_, b)| a.abs().total_cmp(&b.abs()))
+            .map_or(0, |(idx, _)| idx)
+    }
+
+    #[test]
+    fn impulse_comes_back_as_the_impulse_response() {
+        let mut plugin = test_plugin(impulse(1000, 100), 0.0, 100.0);
+        let output = process(&mut plugin, &impulse(4096, 0));
+
+        for (idx, sample) in output.iter().enumerate() {
+            let expected = if idx == BLOCK_SIZE + 100 { 1.0 } else { 0.0 };
+            assert!((sample - expected).abs() < 1e-5, "{sample} at {idx}");
+        }
+    }
+
+    #[test]
+    fn pre_delay_and_stretch_move_the_response() {
+        // 10 ms is 480 samples
+        let mut plugin = test_plugin(impulse(1000, 100), 10.0, 100.0);
+        let output = process(&mut plugin, &impulse(4096, 0));
+        assert_eq!(loudest_sample(&output), BLOCK_SIZE + 480 + 100);
+
+        let mut plugin = test_plugin(impulse(1000, 100), 0.0, 200.0);
+        let output = process(&mut plugin, &impulse(4096, 0));
+        assert_eq!(loudest_sample(&output), BLOCK_SIZE + 200);
+    }
+
+    #[test]
+    fn rebuilding_lets_the_old_tail_ring_out() {
+        let decaying: Vec<f32> = (0..24000)
+            .map(|i| (i as f32 * 0.37).sin() * (-(i as f32) / 4000.0).exp())
+            .collect();
+        let input = impulse(48000, 0);
+        let expected = process(&mut test_plugin(decaying.clone(), 0.0, 100.0), &input);
+
+        // Swapping in a damped response halfway through the tail doesn't touch it, since the
+        // new response only hears the silence after the swap
+        let mut plugin = test_plugin(decaying, 0.0, 100.0);
+        let mut output = input.clone();
+        let (before, after) = output.split_at_mut(4096);
+        for block in before.chunks_mut(MAX_BLOCK_SIZE) {
+            plugin.process_channels(&mut [block]);
+        }
+        plugin.bank.build(IrSettings {
+            stretch: 1.0,
+            damping: 1.0,
+        });
+        for block in after.chunks_mut(MAX_BLOCK_SIZE) {
+            plugin.process_channels(&mut [block]);
+        }
+
+        assert!(plugin.previous_remaining == 0 && !plugin.previous.is_empty());
+        for (idx, (output, expected)) in output.iter().zip(&expected).enumerate() {
+            assert!(
+                (output - expected).abs() < 1e-5,
+                "{output} != {expected} at {idx}"
+            );
+        }
+    }
+
+    #[test]
+    fn processing_does_not_allocate() {
+        let mut plugin = test_plugin(impulse(1000, 100), 10.0, 100.0);
+        let mut samples = impulse(48000, 0);
+        let (before, after) = samples.split_at_mut(4096);
+        assert_no_alloc(|| {
+            for block in before.chunks_mut(MAX_BLOCK_SIZE) {
+                plugin.process_channels(&mut [block]);
+            }
+        });
+
+        // The replaced convolvers are handed back to be freed by the next rebuild
+        plugin.bank.build(IrSettings {
+            stretch: 1.0,
+            damping: 1.0,
+        });
+        assert_no_alloc(|| {
+            for block in after.chunks_mut(MAX_BLOCK_SIZE) {
+                plugin.process_channels(&mut [block]);
+            }
+        });
+    }
+}
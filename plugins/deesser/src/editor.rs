@@ -0,0 +1,82 @@
+use crate::DeesserParams;
+use atomic_float::AtomicF32;
+use nih_plug::prelude::*;
+use nih_plug_egui::create_egui_editor;
+use nih_plug_egui::egui::{Align2, Color32, FontId, Rect, Sense, Ui, Vec2};
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use ui_widgets::{param_combo, param_toggle, EditorParams, EditorShell, ParamKnob};
+
+/// The gain reduction at the right end of the meter, the range's maximum.
+const MAX_REDUCTION_DB: f32 = 24.0;
+
+const REDUCTION_COLOR: Color32 = Color32::from_rgb(230, 140, 60);
+
+pub(crate) fn default_params() -> EditorParams {
+    EditorParams::new((340, 240))
+}
+
+/// A bar growing from the left with the current gain reduction.
+fn reduction_meter(ui: &mut Ui, reduction: f32) {
+    let (rect, _) = ui.allocate_exact_size(Vec2::new(320.0, 18.0), Sense::hover());
+    if !ui.is_rect_visible(rect) {
+        return;
+    }
+
+    let visuals = ui.visuals();
+    let painter = ui.painter_at(rect);
+    painter.rect_filled(rect, 0.0, visuals.extreme_bg_color);
+
+    let width = (reduction / MAX_REDUCTION_DB).clamp(0.0, 1.0) * rect.width();
+    painter.rect_filled(
+        Rect::from_min_size(rect.min, Vec2::new(width, rect.height())),
+        0.0,
+        REDUCTION_COLOR,
+    );
+    painter.text(
+        rect.right_center() - Vec2::new(4.0, 0.0),
+        Align2::RIGHT_CENTER,
+        format!("-{reduction:.1} dB"),
+        FontId::proportional(11.0),
+        visuals.text_color(),
+    );
+}
+
+pub(crate) fn create(
+    params: Arc<DeesserParams>,
+    gain_reduction: Arc<AtomicF32>,
+) -> Option<Box<dyn Editor>> {
+    create_egui_editor(
+        params.editor.state.clone(),
+        (),
+        |_, _| {},
+        move |egui_ctx, setter, _state| {
+            let reduction = gain_reduction.load(Ordering::Relaxed);
+
+            EditorShell::new(&params.editor)
+                .with_bypass(&params.bypass)
+                .show(egui_ctx, setter, |ui| {
+                    ui.horizontal(|ui| {
+                        param_combo(ui, &params.mode, setter).on_hover_text(
+                            "Turn down only the highs, or the whole signal whenever they're loud",
+                        );
+                        param_toggle(ui, &params.listen, setter)
+                            .on_hover_text("Hear only the band the detector listens to");
+                    });
+
+                    ui.horizontal(|ui| {
+                        ui.add(ParamKnob::for_param(&params.frequency, setter));
+                        ui.add(ParamKnob::for_param(&params.threshold, setter));
+                        ui.add(ParamKnob::for_param(&params.range, setter))
+                            .on_hover_text("The most the sibilance is turned down");
+                    });
+                    ui.separator();
+
+                    reduction_meter(ui, reduction);
+                });
+
+            // The gain reduction is live, so keep repainting while the editor is open
+            egui_ctx.request_repaint();
+        },
+    )
+}
@@ -0,0 +1,420 @@
+use atomic_float::AtomicF32;
+use dsp_core::crossover::LinkwitzRiley;
+use dsp_core::dynamics::{db_to_gain, gain_to_db, EnvelopeFollower};
+use dsp_core::guard;
+use dsp_core::mix::MixStage;
+use nih_plug::prelude::*;
+use plugin_scaffold::layouts;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use ui_widgets::EditorParams;
+
+mod editor;
+
+/// Sibilance comes and goes within a syllable, so the detector reacts almost at once and lets go
+/// quickly enough not to dull the vowel that follows.
+const ATTACK_SECONDS: f32 = 0.0005;
+const RELEASE_SECONDS: f32 = 0.06;
+
+/// Turns down sibilance. A Linkwitz-Riley crossover splits off the band above the frequency
+/// control, and whenever that band's level rises above the threshold it is turned down by as
+/// much as it's over, up to the range.
+struct Deesser {
+    params: Arc<DeesserParams>,
+    /// One crossover per channel.
+    crossovers: Vec<LinkwitzRiley>,
+    /// Follows the loudest channel's high band, so the stereo image doesn't shift while the
+    /// sibilance is reduced.
+    detector: EnvelopeFollower,
+    gain_reduction: Arc<AtomicF32>,
+    /// Only used for a click-free bypass.
+    mix: MixStage,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+pub enum Mode {
+    /// Only the band above the frequency is turned down.
+    #[name = "Split Band"]
+    Split,
+    /// The whole signal is turned down, keyed by the band above the frequency.
+    #[name = "Wideband"]
+    Wideband,
+}
+
+#[derive(Params)]
+struct DeesserParams {
+    #[nested]
+    editor: EditorParams,
+
+    /// The host's bypass switch.
+    #[id = "bypass"]
+    pub bypass: BoolParam,
+
+    #[id = "mode"]
+    pub mode: EnumParam<Mode>,
+
+    /// Where the sibilant band starts.
+    #[id = "frequency"]
+    pub frequency: FloatParam,
+
+    #[id = "threshold"]
+    pub threshold: FloatParam,
+
+    /// The most the sibilance is turned down, in decibels.
+    #[id = "range"]
+    pub range: FloatParam,
+
+    /// Play only the band the detector listens to, for tuning the frequency.
+    #[id = "listen"]
+    pub listen: BoolParam,
+}
+
+impl Default for Deesser {
+    fn default() -> Self {
+        Self {
+            params: Arc::new(DeesserParams::default()),
+            crossovers: Vec::new(),
+            detector: Self::detector(44100.0),
+            gain_reduction: Arc::new(AtomicF32::new(0.0)),
+            mix: MixStage::new(44100.0, 0, 0, 0),
+        }
+    }
+}
+
+impl Default for DeesserParams {
+    fn default() -> Self {
+        Self {
+            editor: editor::default_params(),
+
+            bypass: BoolParam::new("Bypass", false).make_bypass(),
+
+            mode: EnumParam::new("Mode", Mode::Split),
+
+            frequency: FloatParam::new(
+                "Frequency",
+                6000.0,
+                FloatRange::Skewed {
+                    min: 4000.0,
+                    max: 10000.0,
+                    factor: FloatRange::skew_factor(-1.0),
+                },
+            )
+            .with_value_to_string(formatters::v2s_f32_hz_then_khz(1))
+            .with_string_to_value(formatters::s2v_f32_hz_then_khz()),
+
+            threshold: FloatParam::new(
+                "Threshold",
+                -24.0,
+                FloatRange::Linear {
+                    min: -60.0,
+                    max: 0.0,
+                },
+            )
+            .with_smoother(SmoothingStyle::Linear(20.0))
+            .with_step_size(0.1)
+            .with_unit(" dB"),
+
+            range: FloatParam::new(
+                "Range",
+                12.0,
+                FloatRange::Linear {
+                    min: 0.0,
+                    max: 24.0,
+                },
+            )
+            .with_smoother(SmoothingStyle::Linear(20.0))
+            .with_step_size(0.1)
+            .with_unit(" dB"),
+
+            listen: BoolParam::new("Listen", false),
+        }
+    }
+}
+
+impl Plugin for Deesser {
+    const NAME: &'static str = "De-esser";
+    const VENDOR: &'static str = "Your Studio";
+    const URL: &'static str = env!("CARGO_PKG_HOMEPAGE");
+    const EMAIL: &'static str = "contact@yourstudio.com";
+    const VERSION: &'static str = env!("CARGO_PKG_VERSION");
+
+    const AUDIO_IO_LAYOUTS: &'static [AudioIOLayout] = &[layouts::STEREO, layouts::MONO];
+
+    type SysExMessage = ();
+    type BackgroundTask = ();
+
+    fn params(&self) -> Arc<dyn Params> {
+        self.params.clone()
+    }
+
+    fn editor(&mut self, _async_executor: AsyncExecutor<Self>) -> Option<Box<dyn Editor>> {
+        editor::create(self.params.clone(), self.gain_reduction.clone())
+    }
+
+    fn initialize(
+        &mut self,
+        audio_io_layout: &AudioIOLayout,
+        buffer_config: &BufferConfig,
+        _context: &mut impl InitContext<Self>,
+    ) -> bool {
+        let num_channels = audio_io_layout
+            .main_output_channels
+            .map_or(0, |channels| channels.get() as usize);
+        let sample_rate = buffer_config.sample_rate;
+
+        self.crossovers =
+            vec![LinkwitzRiley::new(sample_rate, self.params.frequency.value()); num_channels];
+        self.detector = Self::detector(sample_rate);
+        self.mix = MixStage::new(
+            sample_rate,
+            num_channels,
+            buffer_config.max_buffer_size as usize,
+            0,
+        );
+        self.mix.set_mix(self.target_mix());
+        self.mix.reset();
+        true
+    }
+
+    fn reset(&mut self) {
+        for crossover in &mut self.crossovers {
+            crossover.reset();
+        }
+        self.detector.reset();
+        self.mix.reset();
+    }
+
+    fn process(
+        &mut self,
+        buffer: &mut Buffer,
+        _aux: &mut AuxiliaryBuffers,
+        _context: &mut impl ProcessContext<Self>,
+    ) -> ProcessStatus {
+        self.process_channels(buffer.as_slice());
+
+        ProcessStatus::Normal
+    }
+}
+
+impl Deesser {
+    fn detector(sample_rate: f32) -> EnvelopeFollower {
+        let mut detector = EnvelopeFollower::new(sample_rate);
+        detector.set_attack(ATTACK_SECONDS);
+        detector.set_release(RELEASE_SECONDS);
+        detector
+    }
+
+    fn process_channels(&mut self, channels: &mut [&mut [f32]]) {
+        self.mix.set_mix(self.target_mix());
+        self.mix.capture_dry(channels);
+
+        let params = &self.params;
+        let frequency = params.frequency.value();
+        for crossover in &mut self.crossovers {
+            if crossover.frequency() != frequency {
+                crossover.set_frequency(frequency);
+            }
+        }
+        let mode = params.mode.value();
+        let listen = params.listen.value();
+        let mut max_reduction = 0.0f32;
+
+        let num_samples = channels.first().map_or(0, |channel| channel.len());
+        for i in 0..num_samples {
+            // Split every channel first, the detector needs all of their high bands. The
+            // layouts are mono or stereo, so two channels is the most there can be.
+            let mut peak = 0.0f32;
+            let mut splits = [(0.0, 0.0); 2];
+            for ((crossover, channel), split) in self
+                .crossovers
+                .iter_mut()
+                .zip(channels.iter())
+                .zip(&mut splits)
+            {
+                *split = crossover.process(channel[i]);
+                peak = peak.max(split.1.abs());
+            }
+
+            let threshold = params.threshold.smoothed.next();
+            let range = params.range.smoothed.next();
+            let level = self.detector.process(peak);
+            let reduction = (gain_to_db(level) - threshold).clamp(0.0, range);
+            let gain = db_to_gain(-reduction);
+            max_reduction = max_reduction.max(reduction);
+
+            for (channel, &(low, high)) in channels.iter_mut().zip(&splits) {
+                channel[i] = match (listen, mode) {
+                    (true, _) => high,
+                    (false, Mode::Split) => low + high * gain,
+                    // The input rather than the summed bands, which keeps the crossover's phase
+                    // shift out of the signal
+                    (false, Mode::Wideband) => channel[i] * gain,
+                };
+            }
+        }
+        self.gain_reduction.store(max_reduction, Ordering::Relaxed);
+
+        for channel in channels.iter() {
+            guard::check_block("Deesser", channel);
+        }
+        self.mix.mix_into(channels);
+    }
+
+    /// Bypassing fades to the dry signal through the mix stage.
+    fn target_mix(&self) -> f32 {
+        if self.params.bypass.value() {
+            0.0
+        } else {
+            1.0
+        }
+    }
+}
+
+impl ClapPlugin for Deesser {
+    const CLAP_ID: &'static str = "com.yourstudio.deesser";
+    const CLAP_DESCRIPTION: Option<&'static str> =
+        Some("A split band or wideband de-esser for taming sibilance");
+    const CLAP_MANUAL_URL: Option<&'static str> = Some(Self::URL);
+    const CLAP_SUPPORT_URL: Option<&'static str> = None;
+    const CLAP_FEATURES: &'static [ClapFeature] = &[
+        ClapFeature::AudioEffect,
+        ClapFeature::Compressor,
+        ClapFeature::Stereo,
+        ClapFeature::Mono,
+    ];
+}
+
+impl Vst3Plugin for Deesser {
+    const VST3_CLASS_ID: [u8; 16] = *b"Deesser000000000";
+    const VST3_SUBCATEGORIES: &'static [Vst3SubCategory] =
+        &[Vst3SubCategory::Fx, Vst3SubCategory::Dynamics];
+}
+
+nih_export_clap!(Deesser);
+nih_export_vst3!(Deesser);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use plugin_scaffold::test_util::assert_no_alloc;
+    use std::f32::consts::TAU;
+
+    plugin_scaffold::install_alloc_disabler!();
+
+    const SAMPLE_RATE: f32 = 48000.0;
+    const BLOCK_SIZE: usize = 512;
+
+    fn test_plugin(mode: Mode, threshold: f32, listen: bool) -> Deesser {
+        let params = DeesserParams {
+            mode: EnumParam::new("Mode", mode),
+            threshold: FloatParam::new(
+                "Threshold",
+                threshold,
+                FloatRange::Linear {
+                    min: -60.0,
+                    max: 0.0,
+                },
+            ),
+            listen: BoolParam::new("Listen", listen),
+            ..DeesserParams::default()
+        };
+        params.threshold.smoothed.reset(threshold);
+        params.range.smoothed.reset(params.range.value());
+
+        let mut plugin = Deesser {
+            crossovers: vec![LinkwitzRiley::new(SAMPLE_RATE, params.frequency.value())],
+            detector: Deesser::detector(SAMPLE_RATE),
+            mix: MixStage::new(SAMPLE_RATE, 1, BLOCK_SIZE, 0),
+            params: Arc::new(params),
+            ..Deesser::default()
+        };
+        plugin.mix.reset();
+        plugin
+    }
+
+    /// One and a half seconds of sines at the given frequencies and amplitudes.
+    fn sines(partials: &[(f32, f32)]) -> Vec<f32> {
+        (0..(SAMPLE_RATE * 1.5) as usize)
+            .map(|i| {
+                let t = i as f32 / SAMPLE_RATE;
+                partials
+                    .iter()
+                    .map(|(frequency, amplitude)| (TAU * frequency * t).sin() * amplitude)
+                    .sum()
+            })
+            .collect()
+    }
+
+    fn process(plugin: &mut Deesser, mut samples: Vec<f32>) -> Vec<f32> {
+        for block in samples.chunks_mut(BLOCK_SIZE) {
+            plugin.process_channels(&mut [block]);
+        }
+        samples
+    }
+
+    /// The amplitude of the `frequency` partial in the last second of `samples`, after half a
+    /// second to settle.
+    fn partial_level(samples: &[f32], frequency: f32) -> f32 {
+        let settled = &samples[SAMPLE_RATE as usize / 2..];
+        let (re, im) = settled
+            .iter()
+            .enumerate()
+            .fold((0.0, 0.0), |(re, im), (i, sample)| {
+                let phase = TAU * frequency * i as f32 / SAMPLE_RATE;
+                (re + sample * phase.cos(), im + sample * phase.sin())
+            });
+        2.0 * (re * re + im * im).sqrt() / settled.len() as f32
+    }
+
+    #[test]
+    fn quiet_sibilance_passes_untouched() {
+        let mut plugin = test_plugin(Mode::Split, -24.0, false);
+        let output = process(&mut plugin, sines(&[(8000.0, 0.01)]));
+        let level = partial_level(&output, 8000.0);
+        assert!(util::gain_to_db(level / 0.01).abs() < 0.05, "{level}");
+    }
+
+    #[test]
+    fn loud_sibilance_is_reduced_by_at_most_the_range() {
+        // 8 kHz at -6 dB is far over the threshold, so it's turned down by the full 12 dB range
+        let mut plugin = test_plugin(Mode::Wideband, -40.0, false);
+        let output = process(&mut plugin, sines(&[(8000.0, 0.5)]));
+        let reduction = util::gain_to_db(0.5 / partial_level(&output, 8000.0));
+        assert!((reduction - 12.0).abs() < 0.5, "{reduction}");
+    }
+
+    #[test]
+    fn split_band_mode_leaves_the_lows_alone() {
+        let input = sines(&[(200.0, 0.5), (9000.0, 0.5)]);
+
+        let split = process(&mut test_plugin(Mode::Split, -40.0, false), input.clone());
+        let low = partial_level(&split, 200.0);
+        assert!(util::gain_to_db(low / 0.5).abs() < 0.1, "{low}");
+        // The low band's skirt still carries some of the 9 kHz partial past the crossover
+        let high = partial_level(&split, 9000.0);
+        assert!(util::gain_to_db(high / 0.5) < -6.0, "{high}");
+
+        let wideband = process(&mut test_plugin(Mode::Wideband, -40.0, false), input);
+        let low = partial_level(&wideband, 200.0);
+        assert!(util::gain_to_db(low / 0.5) < -10.0, "{low}");
+    }
+
+    #[test]
+    fn listening_plays_only_the_detected_band() {
+        let mut plugin = test_plugin(Mode::Split, -40.0, true);
+        let output = process(&mut plugin, sines(&[(200.0, 0.5), (9000.0, 0.5)]));
+        assert!(partial_level(&output, 200.0) < 0.001);
+        assert!(partial_level(&output, 9000.0) > 0.4);
+    }
+
+    #[test]
+    fn processing_does_not_allocate() {
+        let mut plugin = test_plugin(Mode::Split, -40.0, false);
+        let mut samples = sines(&[(200.0, 0.5), (7000.0, 0.5)]);
+        assert_no_alloc(|| {
+            for block in samples.chunks_mut(BLOCK_SIZE) {
+                plugin.process_channels(&mut [block]);
+            }
+        });
+    }
+}
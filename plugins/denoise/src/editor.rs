@@ -0,0 +1,52 @@
+use crate::DenoiseParams;
+use nih_plug::prelude::*;
+use nih_plug_egui::create_egui_editor;
+use std::sync::Arc;
+use ui_widgets::{param_toggle, EditorParams, EditorShell, ParamKnob};
+
+pub(crate) fn default_params() -> EditorParams {
+    EditorParams::new((360, 230))
+}
+
+pub(crate) fn create(params: Arc<DenoiseParams>) -> Option<Box<dyn Editor>> {
+    create_egui_editor(
+        params.editor.state.clone(),
+        (),
+        |_, _| {},
+        move |egui_ctx, setter, _state| {
+            EditorShell::new(&params.editor)
+                .with_bypass(&params.bypass)
+                .show(egui_ctx, setter, |ui| {
+                    ui.horizontal(|ui| {
+                        param_toggle(ui, &params.learn, setter).on_hover_text(
+                            "Play a stretch of only the noise while this is on, then turn it off",
+                        );
+                        ui.label(profile_status(&params));
+                    });
+                    ui.separator();
+
+                    ui.horizontal(|ui| {
+                        ui.add(ParamKnob::for_param(&params.reduction, setter));
+                        ui.add(ParamKnob::for_param(&params.threshold, setter))
+                            .on_hover_text(
+                                "Raise to also catch noise that peaks above its average",
+                            );
+                        ui.add(ParamKnob::for_param(&params.release, setter));
+                    });
+                });
+        },
+    )
+}
+
+fn profile_status(params: &DenoiseParams) -> &'static str {
+    if params.learn.value() {
+        return "Learning the noise...";
+    }
+
+    // The audio thread only holds the lock for a moment while storing a new profile
+    match params.noise_profile.try_read() {
+        Ok(profile) if profile.iter().any(|&power| power > 0.0) => "Noise profile learned",
+        Ok(_) => "No noise profile yet",
+        Err(_) => "Storing the noise profile...",
+    }
+}
@@ -0,0 +1,380 @@
+use dsp_core::denoise::{Denoiser, NoiseProfile, LATENCY, NUM_BINS};
+use dsp_core::guard;
+use dsp_core::mix::MixStage;
+use nih_plug::prelude::*;
+use plugin_scaffold::formatters::{s2v_f32_ms_then_s, v2s_f32_ms_then_s};
+use plugin_scaffold::layouts;
+use std::sync::{Arc, RwLock};
+use ui_widgets::EditorParams;
+
+mod editor;
+
+/// Spectral noise reduction. The noise is learned from a stretch of the input where only the
+/// noise plays, like the room tone before a take, and then turned down wherever the signal
+/// doesn't rise above it.
+struct Denoise {
+    params: Arc<DenoiseParams>,
+    /// One denoiser per channel, all reducing with the same profile.
+    denoisers: Vec<Denoiser>,
+    profile: NoiseProfile,
+    /// Whether the profile was being learned during the last block.
+    learning: bool,
+    /// A newly learned profile that couldn't be stored in the parameters yet, because the
+    /// editor or the host was reading them.
+    profile_unsaved: bool,
+    /// Mixes in the dry signal while bypassed, delayed to line up with the denoised signal.
+    mix: MixStage,
+}
+
+#[derive(Params)]
+struct DenoiseParams {
+    #[nested]
+    editor: EditorParams,
+
+    /// The learned noise's power in each frequency bin, all zero until a profile is learned.
+    #[persist = "noise-profile"]
+    pub noise_profile: RwLock<Vec<f32>>,
+
+    /// The host's bypass switch.
+    #[id = "bypass"]
+    pub bypass: BoolParam,
+
+    /// While on, the input is passed through and its noise is measured. Turning it off replaces
+    /// the noise profile.
+    #[id = "learn"]
+    pub learn: BoolParam,
+
+    /// How far the noise is turned down at most, in decibels.
+    #[id = "reduction"]
+    pub reduction: FloatParam,
+
+    /// How far above the learned noise a frequency has to be to pass untouched, in decibels.
+    #[id = "threshold"]
+    pub threshold: FloatParam,
+
+    /// How long a frequency takes to fade down once its signal drops into the noise, in
+    /// milliseconds.
+    #[id = "release"]
+    pub release: FloatParam,
+}
+
+impl Default for Denoise {
+    fn default() -> Self {
+        Self {
+            params: Arc::new(DenoiseParams::default()),
+            denoisers: Vec::new(),
+            profile: NoiseProfile::new(),
+            learning: false,
+            profile_unsaved: false,
+            mix: MixStage::new(44100.0, 0, 0, 0),
+        }
+    }
+}
+
+impl Default for DenoiseParams {
+    fn default() -> Self {
+        Self {
+            editor: editor::default_params(),
+
+            noise_profile: RwLock::new(vec![0.0; NUM_BINS]),
+
+            bypass: BoolParam::new("Bypass", false).make_bypass(),
+
+            learn: BoolParam::new("Learn Noise", false),
+
+            reduction: FloatParam::new(
+                "Reduction",
+                12.0,
+                FloatRange::Linear {
+                    min: 0.0,
+                    max: 40.0,
+                },
+            )
+            .with_unit(" dB")
+            .with_value_to_string(formatters::v2s_f32_rounded(1)),
+
+            threshold: FloatParam::new(
+                "Threshold",
+                6.0,
+                FloatRange::Linear {
+                    min: -6.0,
+                    max: 18.0,
+                },
+            )
+            .with_unit(" dB")
+            .with_value_to_string(formatters::v2s_f32_rounded(1)),
+
+            release: FloatParam::new(
+                "Release",
+                100.0,
+                FloatRange::Skewed {
+                    min: 10.0,
+                    max: 1000.0,
+                    factor: FloatRange::skew_factor(-1.0),
+                },
+            )
+            .with_value_to_string(v2s_f32_ms_then_s(0))
+            .with_string_to_value(s2v_f32_ms_then_s()),
+        }
+    }
+}
+
+impl Plugin for Denoise {
+    const NAME: &'static str = "Denoise";
+    const VENDOR: &'static str = "Your Studio";
+    const URL: &'static str = env!("CARGO_PKG_HOMEPAGE");
+    const EMAIL: &'static str = "contact@yourstudio.com";
+    const VERSION: &'static str = env!("CARGO_PKG_VERSION");
+
+    const AUDIO_IO_LAYOUTS: &'static [AudioIOLayout] = &[layouts::STEREO, layouts::MONO];
+
+    type SysExMessage = ();
+    type BackgroundTask = ();
+
+    fn params(&self) -> Arc<dyn Params> {
+        self.params.clone()
+    }
+
+    fn editor(&mut self, _async_executor: AsyncExecutor<Self>) -> Option<Box<dyn Editor>> {
+        editor::create(self.params.clone())
+    }
+
+    fn initialize(
+        &mut self,
+        audio_io_layout: &AudioIOLayout,
+        buffer_config: &BufferConfig,
+        context: &mut impl InitContext<Self>,
+    ) -> bool {
+        let num_channels = audio_io_layout
+            .main_output_channels
+            .map_or(0, |channels| channels.get() as usize);
+        let sample_rate = buffer_config.sample_rate;
+
+        self.denoisers = (0..num_channels)
+            .map(|_| Denoiser::new(sample_rate))
+            .collect();
+        self.mix = MixStage::new(
+            sample_rate,
+            num_channels,
+            buffer_config.max_buffer_size as usize,
+            LATENCY,
+        );
+        self.mix.set_latency(LATENCY);
+        self.mix.set_mix(self.target_mix());
+        self.mix.reset();
+        self.load_profile();
+
+        context.set_latency_samples(LATENCY as u32);
+        true
+    }
+
+    fn reset(&mut self) {
+        for denoiser in &mut self.denoisers {
+            denoiser.reset();
+        }
+        self.mix.reset();
+    }
+
+    fn process(
+        &mut self,
+        buffer: &mut Buffer,
+        _aux: &mut AuxiliaryBuffers,
+        _context: &mut impl ProcessContext<Self>,
+    ) -> ProcessStatus {
+        self.process_channels(buffer.as_slice());
+
+        ProcessStatus::Normal
+    }
+}
+
+impl Denoise {
+    fn process_channels(&mut self, channels: &mut [&mut [f32]]) {
+        let learning = self.params.learn.value();
+        if learning && !self.learning {
+            self.profile.start_learning();
+        } else if !learning && self.learning && self.profile.finish_learning() {
+            self.profile_unsaved = true;
+        }
+        self.learning = learning;
+        if self.profile_unsaved {
+            self.save_profile();
+        }
+
+        let reduction = self.params.reduction.value();
+        let threshold = self.params.threshold.value();
+        let release = self.params.release.value() / 1000.0;
+        for denoiser in &mut self.denoisers {
+            denoiser.set_reduction(reduction);
+            denoiser.set_threshold(threshold);
+            denoiser.set_release(release);
+        }
+
+        self.mix.set_mix(self.target_mix());
+        self.mix.capture_dry(channels);
+        for (channel, denoiser) in channels.iter_mut().zip(&mut self.denoisers) {
+            denoiser.process_block(channel, &mut self.profile, learning);
+            guard::check_block("Denoiser", channel);
+        }
+        self.mix.mix_into(channels);
+    }
+
+    /// Pick up the profile from the restored plugin state. One that doesn't fit the FFT size is
+    /// replaced by an empty profile of the right size, so storing a new one never allocates.
+    fn load_profile(&mut self) {
+        let mut stored = self.params.noise_profile.write().unwrap();
+        if !self.profile.set_power(&stored) {
+            stored.clear();
+            stored.resize(NUM_BINS, 0.0);
+            self.profile.clear();
+        }
+        self.profile_unsaved = false;
+    }
+
+    /// Store the learned profile with the plugin state, or try again next block if the
+    /// parameters are being read.
+    fn save_profile(&mut self) {
+        if let Ok(mut stored) = self.params.noise_profile.try_write() {
+            if stored.len() == NUM_BINS {
+                stored.copy_from_slice(self.profile.power());
+            }
+            self.profile_unsaved = false;
+        }
+    }
+
+    /// Bypassing fades to the dry signal through the mix stage, which keeps delaying it by the
+    /// latency the host is compensating for.
+    fn target_mix(&self) -> f32 {
+        if self.params.bypass.value() {
+            0.0
+        } else {
+            1.0
+        }
+    }
+}
+
+impl ClapPlugin for Denoise {
+    const CLAP_ID: &'static str = "com.yourstudio.denoise";
+    const CLAP_DESCRIPTION: Option<&'static str> =
+        Some("Spectral noise reduction with a noise profile learned from the input");
+    const CLAP_MANUAL_URL: Option<&'static str> = Some(Self::URL);
+    const CLAP_SUPPORT_URL: Option<&'static str> = None;
+    const CLAP_FEATURES: &'static [ClapFeature] = &[
+        ClapFeature::AudioEffect,
+        ClapFeature::Restoration,
+        ClapFeature::Stereo,
+        ClapFeature::Mono,
+    ];
+}
+
+impl Vst3Plugin for Denoise {
+    const VST3_CLASS_ID: [u8; 16] = *b"Denoise000000000";
+    const VST3_SUBCATEGORIES: &'static [Vst3SubCategory] =
+        &[Vst3SubCategory::Fx, Vst3SubCategory::Restoration];
+}
+
+nih_export_clap!(Denoise);
+nih_export_vst3!(Denoise);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dsp_core::random::Xorshift32;
+    use plugin_scaffold::test_util::assert_no_alloc;
+
+    plugin_scaffold::install_alloc_disabler!();
+
+    const SAMPLE_RATE: f32 = 48000.0;
+    const BLOCK_SIZE: usize = 512;
+
+    fn test_plugin(params: DenoiseParams) -> Denoise {
+        let mut plugin = Denoise {
+            params: Arc::new(params),
+            ..Denoise::default()
+        };
+        plugin.denoisers = vec![Denoiser::new(SAMPLE_RATE)];
+        plugin.mix = MixStage::new(SAMPLE_RATE, 1, BLOCK_SIZE, LATENCY);
+        plugin.mix.set_latency(LATENCY);
+        plugin.mix.set_mix(plugin.target_mix());
+        plugin.mix.reset();
+        plugin.load_profile();
+        plugin
+    }
+
+    fn learning(learn: bool) -> DenoiseParams {
+        DenoiseParams {
+            learn: BoolParam::new("Learn Noise", learn),
+            ..DenoiseParams::default()
+        }
+    }
+
+    fn noise(seed: u32, num_samples: usize) -> Vec<f32> {
+        let mut rng = Xorshift32::new(seed);
+        (0..num_samples).map(|_| rng.next_bipolar() * 0.1).collect()
+    }
+
+    fn rms(samples: &[f32]) -> f32 {
+        (samples.iter().map(|s| s * s).sum::<f32>() / samples.len() as f32).sqrt()
+    }
+
+    fn process(plugin: &mut Denoise, input: &[f32]) -> Vec<f32> {
+        let mut output = input.to_vec();
+        for block in output.chunks_mut(BLOCK_SIZE) {
+            plugin.process_channels(&mut [block]);
+        }
+        output
+    }
+
+    #[test]
+    fn without_a_profile_the_input_passes_delayed_by_the_latency() {
+        let mut plugin = test_plugin(DenoiseParams::default());
+        let input = noise(1, SAMPLE_RATE as usize);
+        let output = process(&mut plugin, &input);
+
+        for (input, output) in input.iter().zip(&output[LATENCY..]) {
+            assert!((input - output).abs() < 1e-4, "{input} != {output}");
+        }
+    }
+
+    #[test]
+    fn learned_profiles_are_stored_and_reduce_the_noise() {
+        // Learn for a second, then switch learning off by swapping in parameters without it
+        let mut plugin = test_plugin(learning(true));
+        process(&mut plugin, &noise(1, SAMPLE_RATE as usize));
+        plugin.params = Arc::new(learning(false));
+
+        let input = noise(2, SAMPLE_RATE as usize);
+        let output = process(&mut plugin, &input);
+        assert!(plugin.params.noise_profile.read().unwrap()[1] > 0.0);
+
+        let reduction = rms(&output[24000..]) / rms(&input[24000..]);
+        assert!(reduction < 0.5, "{reduction}");
+    }
+
+    #[test]
+    fn restored_profiles_are_picked_up() {
+        let mut power = vec![0.0; NUM_BINS];
+        power[10] = 1.0;
+        let mut plugin = test_plugin(DenoiseParams {
+            noise_profile: RwLock::new(power),
+            ..DenoiseParams::default()
+        });
+        assert_eq!(plugin.profile.power()[10], 1.0);
+
+        // A profile from a different FFT size is dropped
+        *plugin.params.noise_profile.write().unwrap() = vec![1.0; 3];
+        plugin.load_profile();
+        assert!(plugin.profile.is_empty());
+        assert_eq!(plugin.params.noise_profile.read().unwrap().len(), NUM_BINS);
+    }
+
+    #[test]
+    fn processing_does_not_allocate() {
+        let mut plugin = test_plugin(learning(true));
+        let mut samples = noise(1, SAMPLE_RATE as usize);
+        assert_no_alloc(|| {
+            for block in samples.chunks_mut(BLOCK_SIZE) {
+                plugin.process_channels(&mut [block]);
+            }
+        });
+    }
+}
@@ -0,0 +1,59 @@
+//! The drawbars, and the tonewheels they pull in.
+
+use dsp_core::dynamics::db_to_gain;
+use nih_plug::prelude::*;
+
+pub const NUM_DRAWBARS: usize = 9;
+
+/// Each drawbar's pipe length, which is how organists name them.
+const FOOTAGES: [&str; NUM_DRAWBARS] = [
+    "16'", "5 1/3'", "8'", "4'", "2 2/3'", "2'", "1 3/5'", "1 1/3'", "1'",
+];
+
+/// Each drawbar's pitch as a ratio to the note: the sub octave, the fifth above it, the note,
+/// and the harmonics above.
+pub const RATIOS: [f32; NUM_DRAWBARS] = [0.5, 1.5, 1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 8.0];
+
+/// The classic "888000000" registration.
+const DEFAULT_REGISTRATION: [i32; NUM_DRAWBARS] = [8, 8, 8, 0, 0, 0, 0, 0, 0];
+
+/// The highest tonewheel, in Hz. Drawbars that would go above it play an octave lower instead.
+const HIGHEST_TONEWHEEL: f32 = 5920.0;
+
+#[derive(Params)]
+pub struct DrawbarParams {
+    /// From out at 0 to all the way in at 8.
+    #[id = "level"]
+    pub level: IntParam,
+}
+
+impl DrawbarParams {
+    pub fn new(idx: usize) -> Self {
+        Self {
+            level: IntParam::new(
+                FOOTAGES[idx],
+                DEFAULT_REGISTRATION[idx],
+                IntRange::Linear { min: 0, max: 8 },
+            ),
+        }
+    }
+}
+
+/// The linear gain of a drawbar position. Every step is about 3 dB.
+pub fn drawbar_gain(level: i32) -> f32 {
+    if level <= 0 {
+        0.0
+    } else {
+        db_to_gain(3.0 * (level.min(8) - 8) as f32)
+    }
+}
+
+/// The ratio a drawbar actually plays at for a note at `frequency` Hz, folded back below the
+/// highest tonewheel.
+pub fn tonewheel_ratio(frequency: f32, ratio: f32) -> f32 {
+    let mut ratio = ratio;
+    while frequency * ratio > HIGHEST_TONEWHEEL && ratio > 0.5 {
+        ratio /= 2.0;
+    }
+    ratio
+}
@@ -0,0 +1,48 @@
+use crate::{DrawbarOrgan, DrawbarOrganParams};
+use nih_plug::prelude::*;
+use nih_plug_egui::create_egui_editor;
+use plugin_scaffold::presets::FactoryPresets;
+use std::sync::Arc;
+use ui_widgets::{param_combo, param_toggle, EditorParams, EditorShell, KeyboardState, ParamKnob};
+
+pub(crate) fn default_params() -> EditorParams {
+    EditorParams::new((620, 370))
+}
+
+pub(crate) fn create(
+    params: Arc<DrawbarOrganParams>,
+    keyboard: Arc<KeyboardState>,
+) -> Option<Box<dyn Editor>> {
+    create_egui_editor(
+        params.editor.state.clone(),
+        (),
+        |_, _| {},
+        move |egui_ctx, setter, _state| {
+            EditorShell::new(&params.editor)
+                .with_presets(DrawbarOrgan::FACTORY_PRESETS, &*params)
+                .with_bypass(&params.bypass)
+                .with_keyboard(&keyboard)
+                .show(egui_ctx, setter, |ui| {
+                    ui.horizontal(|ui| {
+                        for drawbar in &params.drawbars {
+                            ui.add(ParamKnob::for_param(&drawbar.level, setter));
+                        }
+                    });
+                    ui.separator();
+                    ui.horizontal(|ui| {
+                        param_toggle(ui, &params.percussion, setter)
+                            .on_hover_text("Only on notes played while no other key is held");
+                        param_combo(ui, &params.percussion_harmonic, setter);
+                        param_combo(ui, &params.percussion_decay, setter);
+                        param_combo(ui, &params.percussion_volume, setter);
+                    });
+                    ui.horizontal(|ui| {
+                        param_combo(ui, &params.scanner, setter)
+                            .on_hover_text("V is vibrato, C mixes in the dry signal for chorus");
+                        ui.add(ParamKnob::for_param(&params.click, setter));
+                        ui.add(ParamKnob::for_param(&params.gain, setter));
+                    });
+                });
+        },
+    )
+}
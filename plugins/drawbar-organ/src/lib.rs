@@ -0,0 +1,838 @@
+use drawbars::{drawbar_gain, tonewheel_ratio, DrawbarParams, NUM_DRAWBARS, RATIOS};
+use dsp_core::additive::AdditiveOsc;
+use dsp_core::bypass::SoftBypass;
+use dsp_core::chorus::Chorus;
+use dsp_core::envelopes::ADSREnvelope;
+use dsp_core::lfo::LfoShape;
+use dsp_core::oscillators::SineOsc;
+use dsp_core::random::Xorshift32;
+use dsp_core::utils::{OnePoleSmoother, Smoother};
+use dsp_core::{guard, utils::midi_to_freq};
+use nih_plug::prelude::*;
+use plugin_scaffold::layouts;
+use plugin_scaffold::notes::{NoteHandler, PendingEvents};
+use plugin_scaffold::presets::{embed_presets, FactoryPreset, FactoryPresets};
+use std::sync::Arc;
+use ui_widgets::{EditorParams, KeyboardInput, KeyboardState};
+
+mod drawbars;
+mod editor;
+
+const MAX_VOICES: usize = 16;
+
+/// Scales the voices down so chords with several drawbars pulled out don't clip.
+const VOICE_GAIN: f32 = 0.1;
+
+/// The scanner's fixed rate in Hz.
+const SCANNER_RATE: f32 = 6.9;
+/// The scanner's delay in the middle of its sweep, in seconds.
+const SCANNER_CENTER: f32 = 0.001;
+/// How far the scanner swings the delay at each vibrato depth, in seconds.
+const SCANNER_DEPTHS: [f32; 3] = [0.0002, 0.0004, 0.0008];
+
+/// How long the key click takes to fall by 60 dB, in seconds.
+const CLICK_DECAY: f32 = 0.01;
+/// The key click's level at 100%.
+const CLICK_GAIN: f32 = 0.3;
+
+/// A tonewheel organ: every key plays nine sine partials set by the drawbars, with the
+/// percussion, key click, and vibrato scanner of the classic console organs.
+struct DrawbarOrgan {
+    params: Arc<DrawbarOrganParams>,
+    voices: [Voice; MAX_VOICES],
+    /// The next voice to steal.
+    next_voice: usize,
+
+    /// The drawbars' gains, gliding to the drawbar positions so moving one doesn't click.
+    drawbar_gains: [OnePoleSmoother; NUM_DRAWBARS],
+    /// The noise for the key clicks.
+    rng: Xorshift32,
+    /// When the last percussion was triggered in this block, so every note of a chord struck at
+    /// once gets it.
+    percussion_timing: Option<u32>,
+    /// How much the percussion and the key clicks fall every sample.
+    percussion_decay: f32,
+    click_decay: f32,
+    scanner: Chorus,
+    sample_rate: f32,
+
+    /// Notes played on the editor's keyboard, and the notes received from the host for it to
+    /// show.
+    keyboard: Arc<KeyboardState>,
+    keyboard_input: KeyboardInput,
+
+    /// `VoiceTerminated` events waiting to be sent to the host at the end of the block.
+    pending_events: PendingEvents<()>,
+
+    /// Fades the output out and back in when the plugin is bypassed.
+    bypass: SoftBypass,
+}
+
+#[derive(Clone)]
+struct Voice {
+    tonewheels: AdditiveOsc<f32, NUM_DRAWBARS>,
+    /// The key contacts, which open and close almost instantly.
+    env: ADSREnvelope,
+    percussion: SineOsc,
+    /// The percussion's level, falling from its start level every sample.
+    percussion_level: f32,
+    /// The key click's level, falling to silence every sample.
+    click_level: f32,
+    /// Whether the key is down, as opposed to releasing.
+    held: bool,
+    note: Option<u8>,
+
+    /// The host's ID for this voice, cleared once the host has been told the voice terminated.
+    voice_id: Option<i32>,
+    channel: u8,
+}
+
+/// The percussion's pitch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+pub enum PercussionHarmonic {
+    /// An octave above the note.
+    Second,
+    /// An octave and a fifth above the note.
+    Third,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+pub enum PercussionDecay {
+    Fast,
+    Slow,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+pub enum PercussionVolume {
+    Normal,
+    Soft,
+}
+
+/// The vibrato scanner's settings: vibrato only, or chorus with the dry signal mixed back in,
+/// each at three depths.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+pub enum Scanner {
+    Off,
+    V1,
+    V2,
+    V3,
+    C1,
+    C2,
+    C3,
+}
+
+impl Scanner {
+    /// The delay swing in seconds and the mix. Off mixes in none of the scanner, which keeps
+    /// running so switching it on doesn't play stale audio.
+    fn settings(self) -> (f32, f32) {
+        match self {
+            Scanner::Off => (0.0, 0.0),
+            Scanner::V1 => (SCANNER_DEPTHS[0], 1.0),
+            Scanner::V2 => (SCANNER_DEPTHS[1], 1.0),
+            Scanner::V3 => (SCANNER_DEPTHS[2], 1.0),
+            Scanner::C1 => (SCANNER_DEPTHS[0], 0.5),
+            Scanner::C2 => (SCANNER_DEPTHS[1], 0.5),
+            Scanner::C3 => (SCANNER_DEPTHS[2], 0.5),
+        }
+    }
+}
+
+#[derive(Params)]
+struct DrawbarOrganParams {
+    #[nested]
+    editor: EditorParams,
+
+    /// The host's bypass switch.
+    #[id = "bypass"]
+    pub bypass: BoolParam,
+
+    #[id = "gain"]
+    pub gain: FloatParam,
+
+    #[nested(array, group = "Drawbar")]
+    pub drawbars: [DrawbarParams; NUM_DRAWBARS],
+
+    /// A decaying harmonic on notes played while no other key is held. Like on the original,
+    /// it takes over the 1' drawbar's tonewheels, so that drawbar goes silent while it's on.
+    #[id = "percussion"]
+    pub percussion: BoolParam,
+
+    #[id = "percussion_harmonic"]
+    pub percussion_harmonic: EnumParam<PercussionHarmonic>,
+
+    #[id = "percussion_decay"]
+    pub percussion_decay: EnumParam<PercussionDecay>,
+
+    #[id = "percussion_volume"]
+    pub percussion_volume: EnumParam<PercussionVolume>,
+
+    /// The burst of noise from the key contacts closing and opening.
+    #[id = "click"]
+    pub click: FloatParam,
+
+    #[id = "scanner"]
+    pub scanner: EnumParam<Scanner>,
+}
+
+impl Default for DrawbarOrgan {
+    fn default() -> Self {
+        Self {
+            params: Arc::new(DrawbarOrganParams::default()),
+            voices: std::array::from_fn(|_| Voice::new(44100.0)),
+            next_voice: 0,
+            drawbar_gains: std::array::from_fn(|_| OnePoleSmoother::new(44100.0, 0.01)),
+            rng: Xorshift32::new(1),
+            percussion_timing: None,
+            percussion_decay: 0.0,
+            click_decay: 0.0,
+            scanner: Chorus::new(44100.0, scanner_max_delay()),
+            sample_rate: 44100.0,
+            keyboard: Arc::new(KeyboardState::new()),
+            keyboard_input: KeyboardInput::default(),
+            pending_events: PendingEvents::with_capacity(MAX_VOICES * 2),
+            bypass: SoftBypass::new(44100.0),
+        }
+    }
+}
+
+impl Default for DrawbarOrganParams {
+    fn default() -> Self {
+        Self {
+            editor: editor::default_params(),
+
+            bypass: BoolParam::new("Bypass", false).make_bypass(),
+
+            gain: FloatParam::new(
+                "Gain",
+                util::db_to_gain(-6.0),
+                FloatRange::Skewed {
+                    min: util::db_to_gain(-30.0),
+                    max: util::db_to_gain(6.0),
+                    factor: FloatRange::gain_skew_factor(-30.0, 6.0),
+                },
+            )
+            .with_smoother(SmoothingStyle::Logarithmic(50.0))
+            .with_unit(" dB")
+            .with_value_to_string(formatters::v2s_f32_gain_to_db(2))
+            .with_string_to_value(formatters::s2v_f32_gain_to_db()),
+
+            drawbars: std::array::from_fn(DrawbarParams::new),
+
+            percussion: BoolParam::new("Percussion", false),
+            percussion_harmonic: EnumParam::new("Percussion Harmonic", PercussionHarmonic::Third),
+            percussion_decay: EnumParam::new("Percussion Decay", PercussionDecay::Fast),
+            percussion_volume: EnumParam::new("Percussion Volume", PercussionVolume::Normal),
+
+            click: FloatParam::new("Key Click", 0.3, FloatRange::Linear { min: 0.0, max: 1.0 })
+                .with_value_to_string(formatters::v2s_f32_percentage(0))
+                .with_string_to_value(formatters::s2v_f32_percentage())
+                .with_unit(" %"),
+
+            scanner: EnumParam::new("Vibrato", Scanner::C3),
+        }
+    }
+}
+
+impl Plugin for DrawbarOrgan {
+    const NAME: &'static str = "Drawbar Organ";
+    const VENDOR: &'static str = "Your Studio";
+    const URL: &'static str = env!("CARGO_PKG_HOMEPAGE");
+    const EMAIL: &'static str = "contact@yourstudio.com";
+    const VERSION: &'static str = env!("CARGO_PKG_VERSION");
+
+    const AUDIO_IO_LAYOUTS: &'static [AudioIOLayout] = layouts::INSTRUMENT_LAYOUTS;
+    const MIDI_INPUT: MidiConfig = MidiConfig::Basic;
+    const SAMPLE_ACCURATE_AUTOMATION: bool = true;
+
+    type SysExMessage = ();
+    type BackgroundTask = ();
+
+    fn params(&self) -> Arc<dyn Params> {
+        self.params.clone()
+    }
+
+    fn editor(&mut self, _async_executor: AsyncExecutor<Self>) -> Option<Box<dyn Editor>> {
+        editor::create(self.params.clone(), self.keyboard.clone())
+    }
+
+    fn initialize(
+        &mut self,
+        _audio_io_layout: &AudioIOLayout,
+        buffer_config: &BufferConfig,
+        _context: &mut impl InitContext<Self>,
+    ) -> bool {
+        let sample_rate = buffer_config.sample_rate;
+        self.sample_rate = sample_rate;
+        self.bypass = SoftBypass::new(sample_rate);
+        self.bypass.reset(self.params.bypass.value());
+
+        for voice in &mut self.voices {
+            *voice = Voice::new(sample_rate);
+        }
+        self.drawbar_gains = std::array::from_fn(|_| OnePoleSmoother::new(sample_rate, 0.01));
+        self.reset_drawbar_gains();
+        self.scanner = Chorus::new(sample_rate, scanner_max_delay());
+        self.scanner.set_rate(SCANNER_RATE);
+        self.scanner.set_shape(LfoShape::Triangle);
+        true
+    }
+
+    fn reset(&mut self) {
+        self.keyboard.clear_received();
+        self.scanner.reset();
+        self.reset_drawbar_gains();
+    }
+
+    fn process(
+        &mut self,
+        buffer: &mut Buffer,
+        _aux: &mut AuxiliaryBuffers,
+        context: &mut impl ProcessContext<Self>,
+    ) -> ProcessStatus {
+        self.update_block_params();
+
+        self.handle_keyboard_notes();
+        let keyboard = self.keyboard.clone();
+        let mut next_event = keyboard.echo(context.next_event());
+        for (sample_id, channel_samples) in buffer.iter_samples().enumerate() {
+            self.handle_due_events(sample_id as u32, &mut next_event, || {
+                keyboard.echo(context.next_event())
+            });
+
+            let sample = self.render_sample() * self.params.gain.smoothed.next();
+            for output in channel_samples {
+                *output = sample;
+            }
+        }
+
+        // Voices keep running while bypassed so notes still end when they should
+        self.bypass.set_bypassed(self.params.bypass.value());
+        self.bypass.process_to_silence(buffer.as_slice());
+
+        for channel in buffer.as_slice_immutable() {
+            guard::check_block("drawbar-organ output", channel);
+        }
+
+        let last_sample = buffer.samples().saturating_sub(1) as u32;
+        self.terminate_finished_voices(last_sample);
+        self.pending_events.send(context);
+
+        self.process_status()
+    }
+}
+
+impl Voice {
+    fn new(sample_rate: f32) -> Self {
+        let mut env = ADSREnvelope::new(sample_rate);
+        env.set_attack(0.002);
+        env.set_decay(0.001);
+        env.set_sustain(1.0);
+        env.set_release(0.01);
+
+        Self {
+            tonewheels: AdditiveOsc::new(sample_rate, RATIOS),
+            env,
+            percussion: SineOsc::new(sample_rate),
+            percussion_level: 0.0,
+            click_level: 0.0,
+            held: false,
+            note: None,
+            voice_id: None,
+            channel: 0,
+        }
+    }
+
+    fn is_active(&self) -> bool {
+        self.env.is_active()
+    }
+
+    /// Queue a `VoiceTerminated` event for this voice if the host still thinks it's playing.
+    fn terminate(&mut self, timing: u32, pending_events: &mut PendingEvents<()>) {
+        pending_events.voice_terminated(timing, self.voice_id.take(), self.channel, self.note);
+    }
+}
+
+/// Compute a voice ID in case the host doesn't provide them.
+const fn compute_fallback_voice_id(note: u8, channel: u8) -> i32 {
+    note as i32 | ((channel as i32) << 16)
+}
+
+/// The longest delay the scanner needs, in seconds.
+fn scanner_max_delay() -> f32 {
+    SCANNER_CENTER + SCANNER_DEPTHS[2]
+}
+
+/// The factor that makes a level fall by 60 dB in `seconds`.
+fn decay_factor(seconds: f32, sample_rate: f32) -> f32 {
+    (-6.907_755 / (seconds * sample_rate)).exp()
+}
+
+impl DrawbarOrgan {
+    fn reset_drawbar_gains(&mut self) {
+        for (gain, drawbar) in self.drawbar_gains.iter_mut().zip(&self.params.drawbars) {
+            gain.reset(drawbar_gain(drawbar.level.value()));
+        }
+    }
+
+    /// Apply the parameters that only change between blocks: the drawbars, the percussion, and
+    /// the scanner.
+    fn update_block_params(&mut self) {
+        let percussion = self.params.percussion.value();
+        for (idx, (gain, drawbar)) in self
+            .drawbar_gains
+            .iter_mut()
+            .zip(&self.params.drawbars)
+            .enumerate()
+        {
+            let silenced = percussion && idx == NUM_DRAWBARS - 1;
+            gain.set_target(if silenced {
+                0.0
+            } else {
+                drawbar_gain(drawbar.level.value())
+            });
+        }
+
+        let percussion_decay = match self.params.percussion_decay.value() {
+            PercussionDecay::Fast => 0.3,
+            PercussionDecay::Slow => 1.2,
+        };
+        self.percussion_decay = decay_factor(percussion_decay, self.sample_rate);
+        self.click_decay = decay_factor(CLICK_DECAY, self.sample_rate);
+        self.percussion_timing = None;
+
+        let (depth, mix) = self.params.scanner.value().settings();
+        self.scanner.set_delay(SCANNER_CENTER, depth);
+        self.scanner.set_mix(mix);
+    }
+
+    /// Play the keys pressed and released on the editor's keyboard since the last block.
+    fn handle_keyboard_notes(&mut self) {
+        let notes = self.keyboard_input.poll(&self.keyboard);
+        self.handle_events(notes);
+    }
+
+    /// Organ keys aren't velocity sensitive.
+    fn note_on(&mut self, timing: u32, voice_id: Option<i32>, channel: u8, note: u8) {
+        // The percussion is single triggered, it only sounds on notes played while no other
+        // key is held
+        let params = &self.params;
+        let percussion = params.percussion.value()
+            && (self.voices.iter().all(|voice| !voice.held)
+                || self.percussion_timing == Some(timing));
+        if percussion {
+            self.percussion_timing = Some(timing);
+        }
+
+        // Find available voice or steal oldest
+        let voice_idx = self
+            .voices
+            .iter()
+            .position(|voice| !voice.is_active())
+            .unwrap_or_else(|| {
+                let idx = self.next_voice;
+                self.next_voice = (idx + 1) % MAX_VOICES;
+                idx
+            });
+
+        // The host needs to know a stolen voice ended before its ID is reused
+        let voice = &mut self.voices[voice_idx];
+        voice.terminate(timing, &mut self.pending_events);
+
+        // The tonewheels never stop turning, so their phases carry on from the last note
+        let frequency = midi_to_freq(note);
+        voice.tonewheels.set_frequency(frequency);
+        for (idx, &ratio) in RATIOS.iter().enumerate() {
+            voice
+                .tonewheels
+                .set_ratio(idx, tonewheel_ratio(frequency, ratio));
+        }
+        let harmonic = match params.percussion_harmonic.value() {
+            PercussionHarmonic::Second => 2.0,
+            PercussionHarmonic::Third => 3.0,
+        };
+        voice.percussion.set_frequency(frequency * harmonic);
+        voice.percussion.reset();
+        voice.percussion_level = match (percussion, params.percussion_volume.value()) {
+            (false, _) => 0.0,
+            (true, PercussionVolume::Normal) => 1.0,
+            (true, PercussionVolume::Soft) => 0.5,
+        };
+        voice.click_level = params.click.value();
+        voice.env.reset();
+        voice.env.note_on();
+
+        voice.held = true;
+        voice.note = Some(note);
+        voice.voice_id = Some(voice_id.unwrap_or_else(|| compute_fallback_voice_id(note, channel)));
+        voice.channel = channel;
+    }
+
+    fn note_off(&mut self, voice_id: Option<i32>, channel: u8, note: u8) {
+        // Release the voice with this ID, or every voice playing this note when there is none
+        let click = self.params.click.value();
+        for voice in &mut self.voices {
+            let matches = match voice_id {
+                Some(voice_id) => voice.voice_id == Some(voice_id),
+                None => voice.note == Some(note) && voice.channel == channel,
+            };
+            if matches && voice.held {
+                voice.held = false;
+                voice.env.note_off();
+                // The contacts click when they open too
+                voice.click_level = voice.click_level.max(click);
+            }
+        }
+    }
+
+    fn render_sample(&mut self) -> f32 {
+        let gains: [f32; NUM_DRAWBARS] = std::array::from_fn(|idx| self.drawbar_gains[idx].next());
+
+        let mut sum = 0.0;
+        for voice in &mut self.voices {
+            if !voice.is_active() {
+                continue;
+            }
+
+            for (idx, &gain) in gains.iter().enumerate() {
+                voice.tonewheels.set_level(idx, gain);
+            }
+            let env = voice.env.next_sample();
+            let percussion = voice.percussion.next_sample() * voice.percussion_level;
+            voice.percussion_level *= self.percussion_decay;
+            let click = self.rng.next_bipolar() * voice.click_level * CLICK_GAIN;
+            voice.click_level *= self.click_decay;
+
+            sum += (voice.tonewheels.next_sample() + percussion) * env + click;
+        }
+
+        self.scanner.process(sum * VOICE_GAIN)
+    }
+
+    /// Keep the plugin alive while notes are held, and report the remaining release time once
+    /// they're all released so hosts that suspend silent plugins don't cut off the tails.
+    fn process_status(&self) -> ProcessStatus {
+        let tail = self.voices.iter().try_fold(0, |tail: u32, voice| {
+            voice
+                .env
+                .remaining_release_samples()
+                .map(|remaining| tail.max(remaining))
+        });
+
+        match tail {
+            None => ProcessStatus::KeepAlive,
+            Some(0) => ProcessStatus::Normal,
+            Some(samples) => ProcessStatus::Tail(samples),
+        }
+    }
+
+    fn terminate_finished_voices(&mut self, timing: u32) {
+        for voice in &mut self.voices {
+            if !voice.is_active() {
+                voice.terminate(timing, &mut self.pending_events);
+            }
+        }
+    }
+}
+
+impl FactoryPresets for DrawbarOrgan {
+    const FACTORY_PRESETS: &'static [FactoryPreset] = embed_presets!("presets/*.json");
+}
+
+impl NoteHandler<()> for DrawbarOrgan {
+    fn handle_event(&mut self, event: PluginNoteEvent<Self>) {
+        match event {
+            // A note on with zero velocity is a note off in MIDI terms
+            NoteEvent::NoteOn {
+                voice_id,
+                channel,
+                note,
+                velocity,
+                ..
+            } if velocity <= 0.0 => self.note_off(voice_id, channel, note),
+            NoteEvent::NoteOn {
+                timing,
+                voice_id,
+                channel,
+                note,
+                ..
+            } => self.note_on(timing, voice_id, channel, note),
+            NoteEvent::NoteOff {
+                voice_id,
+                channel,
+                note,
+                ..
+            } => self.note_off(voice_id, channel, note),
+            _ => {}
+        }
+    }
+}
+
+impl ClapPlugin for DrawbarOrgan {
+    const CLAP_ID: &'static str = "com.yourstudio.drawbar-organ";
+    const CLAP_DESCRIPTION: Option<&'static str> =
+        Some("A tonewheel organ with drawbars, percussion, key click, and a vibrato scanner");
+    const CLAP_MANUAL_URL: Option<&'static str> = Some(Self::URL);
+    const CLAP_SUPPORT_URL: Option<&'static str> = None;
+    const CLAP_FEATURES: &'static [ClapFeature] = &[
+        ClapFeature::Instrument,
+        ClapFeature::Synthesizer,
+        ClapFeature::Stereo,
+    ];
+}
+
+impl Vst3Plugin for DrawbarOrgan {
+    const VST3_CLASS_ID: [u8; 16] = *b"DrawbarOrganTW9!";
+    const VST3_SUBCATEGORIES: &'static [Vst3SubCategory] =
+        &[Vst3SubCategory::Instrument, Vst3SubCategory::Synth];
+}
+
+nih_export_clap!(DrawbarOrgan);
+nih_export_vst3!(DrawbarOrgan);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use plugin_scaffold::test_util::assert_no_alloc;
+
+    plugin_scaffold::install_alloc_disabler!();
+
+    const SAMPLE_RATE: f32 = 48000.0;
+    const BLOCK_SIZE: usize = 256;
+
+    fn test_plugin(params: DrawbarOrganParams) -> DrawbarOrgan {
+        let mut plugin = DrawbarOrgan {
+            params: Arc::new(params),
+            voices: std::array::from_fn(|_| Voice::new(SAMPLE_RATE)),
+            drawbar_gains: std::array::from_fn(|_| OnePoleSmoother::new(SAMPLE_RATE, 0.01)),
+            scanner: Chorus::new(SAMPLE_RATE, scanner_max_delay()),
+            sample_rate: SAMPLE_RATE,
+            ..DrawbarOrgan::default()
+        };
+        plugin.scanner.set_rate(SCANNER_RATE);
+        plugin.scanner.set_shape(LfoShape::Triangle);
+        plugin.reset_drawbar_gains();
+        plugin
+    }
+
+    fn params(registration: [i32; NUM_DRAWBARS], scanner: Scanner) -> DrawbarOrganParams {
+        DrawbarOrganParams {
+            drawbars: std::array::from_fn(|idx| DrawbarParams {
+                level: IntParam::new(
+                    "Drawbar",
+                    registration[idx],
+                    IntRange::Linear { min: 0, max: 8 },
+                ),
+            }),
+            click: FloatParam::new("Key Click", 0.0, FloatRange::Linear { min: 0.0, max: 1.0 }),
+            scanner: EnumParam::new("Vibrato", scanner),
+            ..DrawbarOrganParams::default()
+        }
+    }
+
+    fn with_percussion(params: DrawbarOrganParams) -> DrawbarOrganParams {
+        DrawbarOrganParams {
+            percussion: BoolParam::new("Percussion", true),
+            ..params
+        }
+    }
+
+    fn note_on(note: u8) -> PluginNoteEvent<DrawbarOrgan> {
+        NoteEvent::NoteOn {
+            timing: 0,
+            voice_id: None,
+            channel: 0,
+            note,
+            velocity: 1.0,
+        }
+    }
+
+    fn note_off(note: u8) -> PluginNoteEvent<DrawbarOrgan> {
+        NoteEvent::NoteOff {
+            timing: 0,
+            voice_id: None,
+            channel: 0,
+            note,
+            velocity: 0.0,
+        }
+    }
+
+    /// Mirror of `process()` without the output gain, with `events` at the start of the first
+    /// block.
+    fn render_into(
+        plugin: &mut DrawbarOrgan,
+        events: impl IntoIterator<Item = PluginNoteEvent<DrawbarOrgan>>,
+        output: &mut [f32],
+    ) {
+        let mut events = events.into_iter();
+        let mut next_event = events.next();
+
+        for block in output.chunks_mut(BLOCK_SIZE) {
+            plugin.update_block_params();
+            for (sample_id, sample) in block.iter_mut().enumerate() {
+                plugin.handle_due_events(sample_id as u32, &mut next_event, || events.next());
+                *sample = plugin.render_sample();
+            }
+            plugin.terminate_finished_voices(0);
+        }
+    }
+
+    fn render(
+        plugin: &mut DrawbarOrgan,
+        events: Vec<PluginNoteEvent<DrawbarOrgan>>,
+        seconds: f32,
+    ) -> Vec<f32> {
+        let mut output = vec![0.0; (seconds * SAMPLE_RATE) as usize];
+        render_into(plugin, events, &mut output);
+        output
+    }
+
+    fn rising_crossings(samples: &[f32]) -> Vec<f32> {
+        samples
+            .windows(2)
+            .enumerate()
+            .filter(|(_, pair)| pair[0] < 0.0 && pair[1] >= 0.0)
+            .map(|(i, pair)| i as f32 + pair[0] / (pair[0] - pair[1]))
+            .collect()
+    }
+
+    fn active_voices(plugin: &DrawbarOrgan) -> usize {
+        plugin
+            .voices
+            .iter()
+            .filter(|voice| voice.is_active())
+            .count()
+    }
+
+    #[test]
+    fn drawbars_pick_the_partials() {
+        let mut eight_foot = [0; NUM_DRAWBARS];
+        eight_foot[2] = 8;
+        let mut sixteen_foot = [0; NUM_DRAWBARS];
+        sixteen_foot[0] = 8;
+
+        for (registration, frequency) in [(eight_foot, 440.0), (sixteen_foot, 220.0)] {
+            let mut plugin = test_plugin(params(registration, Scanner::Off));
+            let output = render(&mut plugin, vec![note_on(69)], 1.0);
+
+            let crossings = rising_crossings(&output[SAMPLE_RATE as usize / 2..]).len();
+            assert!(
+                crossings.abs_diff(frequency as usize / 2) <= 1,
+                "{crossings}"
+            );
+            let peak = output
+                .iter()
+                .fold(0.0, |peak: f32, sample| peak.max(sample.abs()));
+            assert!((peak - VOICE_GAIN).abs() < 0.01 * VOICE_GAIN, "{peak}");
+        }
+    }
+
+    #[test]
+    fn high_drawbars_fold_back_below_the_highest_tonewheel() {
+        assert_eq!(tonewheel_ratio(440.0, 8.0), 8.0);
+        assert_eq!(tonewheel_ratio(2000.0, 8.0), 2.0);
+        assert_eq!(tonewheel_ratio(2000.0, 0.5), 0.5);
+    }
+
+    #[test]
+    fn percussion_is_single_triggered() {
+        let registration = [8, 8, 8, 0, 0, 0, 0, 0, 0];
+        let mut plugin = test_plugin(with_percussion(params(registration, Scanner::Off)));
+        let percussion = |plugin: &DrawbarOrgan, note| {
+            plugin
+                .voices
+                .iter()
+                .find(|voice| voice.held && voice.note == Some(note))
+                .unwrap()
+                .percussion_level
+        };
+
+        // Every note of a chord struck at once gets it, notes added to a held chord don't
+        render(&mut plugin, vec![note_on(60), note_on(64)], 0.01);
+        assert!(percussion(&plugin, 60) > 0.5 && percussion(&plugin, 64) > 0.5);
+        render(&mut plugin, vec![note_on(67)], 0.01);
+        assert_eq!(percussion(&plugin, 67), 0.0);
+
+        render(
+            &mut plugin,
+            vec![note_off(60), note_off(64), note_off(67)],
+            0.01,
+        );
+        render(&mut plugin, vec![note_on(72)], 0.01);
+        assert!(percussion(&plugin, 72) > 0.5);
+
+        // The fast decay is nearly gone after half a second
+        render(&mut plugin, Vec::new(), 0.5);
+        assert!(percussion(&plugin, 72) < 0.01);
+    }
+
+    #[test]
+    fn percussion_takes_the_one_foot_drawbar() {
+        let registration = [8; NUM_DRAWBARS];
+        let mut plugin = test_plugin(with_percussion(params(registration, Scanner::Off)));
+        plugin.update_block_params();
+        assert_eq!(plugin.drawbar_gains[NUM_DRAWBARS - 1].target(), 0.0);
+        assert_eq!(plugin.drawbar_gains[NUM_DRAWBARS - 2].target(), 1.0);
+    }
+
+    #[test]
+    fn the_scanner_bends_the_pitch() {
+        let mut eight_foot = [0; NUM_DRAWBARS];
+        eight_foot[2] = 8;
+        let pitch_swing = |scanner| {
+            let mut plugin = test_plugin(params(eight_foot, scanner));
+            let output = render(&mut plugin, vec![note_on(81)], 1.0);
+            let crossings = rising_crossings(&output[SAMPLE_RATE as usize / 10..]);
+            let periods: Vec<f32> = crossings.windows(2).map(|pair| pair[1] - pair[0]).collect();
+            let longest = periods.iter().cloned().fold(f32::MIN, f32::max);
+            let shortest = periods.iter().cloned().fold(f32::MAX, f32::min);
+            longest / shortest
+        };
+
+        assert!(pitch_swing(Scanner::Off) < 1.001);
+        assert!(pitch_swing(Scanner::V1) > 1.005);
+        assert!(pitch_swing(Scanner::V3) > pitch_swing(Scanner::V1) * 1.02);
+    }
+
+    #[test]
+    fn released_notes_end_after_their_tail() {
+        let mut plugin = test_plugin(params([8; NUM_DRAWBARS], Scanner::C3));
+        render(&mut plugin, vec![note_on(60), note_on(64)], 0.1);
+        assert_eq!(active_voices(&plugin), 2);
+        assert!(matches!(plugin.process_status(), ProcessStatus::KeepAlive));
+
+        render(&mut plugin, vec![note_off(60), note_off(64)], 0.01);
+        let ProcessStatus::Tail(tail) = plugin.process_status() else {
+            panic!("releasing voices should report a tail");
+        };
+        render(&mut plugin, Vec::new(), tail as f32 / SAMPLE_RATE + 0.01);
+        assert_eq!(active_voices(&plugin), 0);
+        assert_eq!(plugin.pending_events.len(), 2);
+    }
+
+    #[test]
+    fn factory_presets_resolve() {
+        let params = DrawbarOrganParams::default();
+        assert!(!DrawbarOrgan::FACTORY_PRESETS.is_empty());
+        for preset in DrawbarOrgan::FACTORY_PRESETS {
+            let values = preset
+                .resolve(&params)
+                .unwrap_or_else(|err| panic!("{}: {err}", preset.name));
+            assert!(values.iter().all(|(_, value)| (0.0..=1.0).contains(value)));
+        }
+    }
+
+    #[test]
+    fn processing_does_not_allocate() {
+        let mut plugin = test_plugin(with_percussion(params([8; NUM_DRAWBARS], Scanner::C3)));
+        let mut output = vec![0.0; BLOCK_SIZE * 40];
+        let (held, released) = output.split_at_mut(BLOCK_SIZE * 20);
+        assert_no_alloc(|| {
+            render_into(&mut plugin, [note_on(60), note_on(64), note_on(67)], held);
+            render_into(
+                &mut plugin,
+                [note_off(60), note_off(64), note_off(67)],
+                released,
+            );
+        });
+    }
+}
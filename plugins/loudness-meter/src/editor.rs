@@ -0,0 +1,142 @@
+use crate::{LoudnessParams, Readings};
+use atomic_float::AtomicF32;
+use nih_plug::prelude::*;
+use nih_plug_egui::create_egui_editor;
+use nih_plug_egui::egui::{self, Color32, Rect, RichText, Sense, Stroke, Ui, Vec2};
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use ui_widgets::{EditorParams, EditorShell, ParamKnob};
+
+/// The range of the loudness bars, in LUFS.
+const BAR_MIN_LUFS: f32 = -60.0;
+const BAR_MAX_LUFS: f32 = 0.0;
+
+/// True peaks above this leave too little headroom for lossy encoding.
+const TRUE_PEAK_LIMIT: f32 = -1.0;
+
+const WARNING_COLOR: Color32 = Color32::from_rgb(230, 80, 60);
+
+pub(crate) fn default_params() -> EditorParams {
+    EditorParams::new((380, 340))
+}
+
+/// Format a level, showing silence as `-inf`.
+fn format_level(level: f32, unit: &str) -> String {
+    if level.is_finite() {
+        format!("{level:.1} {unit}")
+    } else {
+        format!("-inf {unit}")
+    }
+}
+
+/// A horizontal bar from [`BAR_MIN_LUFS`] to [`BAR_MAX_LUFS`] with a marker at the target.
+fn loudness_bar(ui: &mut Ui, lufs: f32, target: f32) {
+    let (rect, _) = ui.allocate_exact_size(Vec2::new(200.0, 14.0), Sense::hover());
+    if !ui.is_rect_visible(rect) {
+        return;
+    }
+
+    let visuals = ui.visuals();
+    let painter = ui.painter_at(rect);
+    let lufs_to_x = |lufs: f32| {
+        let t = ((lufs - BAR_MIN_LUFS) / (BAR_MAX_LUFS - BAR_MIN_LUFS)).clamp(0.0, 1.0);
+        rect.left() + t * rect.width()
+    };
+
+    painter.rect_filled(rect, 0.0, visuals.extreme_bg_color);
+    if lufs.is_finite() {
+        painter.rect_filled(
+            Rect::from_min_max(rect.left_top(), egui::pos2(lufs_to_x(lufs), rect.bottom())),
+            0.0,
+            visuals.selection.bg_fill,
+        );
+    }
+
+    let target_x = lufs_to_x(target);
+    painter.line_segment(
+        [
+            egui::pos2(target_x, rect.top()),
+            egui::pos2(target_x, rect.bottom()),
+        ],
+        Stroke::new(2.0, visuals.strong_text_color()),
+    );
+}
+
+fn load(level: &AtomicF32) -> f32 {
+    level.load(Ordering::Relaxed)
+}
+
+pub(crate) fn create(
+    params: Arc<LoudnessParams>,
+    readings: Arc<Readings>,
+) -> Option<Box<dyn Editor>> {
+    create_egui_editor(
+        params.editor.state.clone(),
+        (),
+        |_, _| {},
+        move |egui_ctx, setter, _state| {
+            let target = params.target.value();
+            let integrated = load(&readings.integrated);
+            let true_peak = load(&readings.true_peak);
+
+            EditorShell::new(&params.editor).show(egui_ctx, setter, |ui| {
+                ui.label("Integrated");
+                ui.horizontal(|ui| {
+                    ui.label(
+                        RichText::new(format_level(integrated, "LUFS"))
+                            .size(32.0)
+                            .strong(),
+                    );
+                    if integrated.is_finite() {
+                        ui.label(format!("{:+.1} LU from target", integrated - target));
+                    }
+                });
+                ui.separator();
+
+                egui::Grid::new("readings")
+                    .num_columns(3)
+                    .spacing(Vec2::new(12.0, 8.0))
+                    .show(ui, |ui| {
+                        for (label, reading) in [
+                            ("Momentary", &readings.momentary),
+                            ("Short-term", &readings.short_term),
+                        ] {
+                            let lufs = load(reading);
+                            ui.label(label);
+                            loudness_bar(ui, lufs, target);
+                            ui.label(format_level(lufs, "LUFS"));
+                            ui.end_row();
+                        }
+
+                        let true_peak_text = RichText::new(format_level(true_peak, "dBTP"));
+                        ui.label("True peak");
+                        ui.label(if true_peak > TRUE_PEAK_LIMIT {
+                            true_peak_text.color(WARNING_COLOR)
+                        } else {
+                            true_peak_text
+                        })
+                        .on_hover_text(format!(
+                            "The highest peak between samples, keep it below {TRUE_PEAK_LIMIT} \
+                             dBTP to leave headroom for lossy encoding"
+                        ));
+                        ui.end_row();
+                    });
+                ui.separator();
+
+                ui.horizontal(|ui| {
+                    ui.add(ParamKnob::for_param(&params.target, setter));
+                    if ui
+                        .button("Reset")
+                        .on_hover_text("Start a new integrated loudness and true peak measurement")
+                        .clicked()
+                    {
+                        readings.reset_requested.store(true, Ordering::Relaxed);
+                    }
+                });
+            });
+
+            // The readings are live, so keep repainting while the editor is open
+            egui_ctx.request_repaint();
+        },
+    )
+}
@@ -0,0 +1,254 @@
+use atomic_float::AtomicF32;
+use dsp_core::loudness::LoudnessMeter as Meter;
+use nih_plug::prelude::*;
+use plugin_scaffold::layouts;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use ui_widgets::EditorParams;
+
+mod editor;
+
+/// Passes audio through untouched while measuring its loudness.
+struct LoudnessMeter {
+    params: Arc<LoudnessParams>,
+    meter: Meter,
+    readings: Arc<Readings>,
+}
+
+#[derive(Params)]
+struct LoudnessParams {
+    #[nested]
+    editor: EditorParams,
+
+    /// The integrated loudness the mix is aimed at, e.g. -14 LUFS for most streaming services.
+    #[id = "target"]
+    pub target: FloatParam,
+}
+
+/// The latest measurements, shared between the audio thread and the editor. Levels are in LUFS
+/// and dBTP, negative infinity while there's nothing to measure.
+pub struct Readings {
+    pub momentary: AtomicF32,
+    pub short_term: AtomicF32,
+    pub integrated: AtomicF32,
+    pub true_peak: AtomicF32,
+    /// Set by the editor to start a new measurement.
+    pub reset_requested: AtomicBool,
+}
+
+impl Readings {
+    fn new() -> Self {
+        Self {
+            momentary: AtomicF32::new(f32::NEG_INFINITY),
+            short_term: AtomicF32::new(f32::NEG_INFINITY),
+            integrated: AtomicF32::new(f32::NEG_INFINITY),
+            true_peak: AtomicF32::new(f32::NEG_INFINITY),
+            reset_requested: AtomicBool::new(false),
+        }
+    }
+
+    fn update(&self, meter: &Meter) {
+        self.momentary.store(meter.momentary(), Ordering::Relaxed);
+        self.short_term.store(meter.short_term(), Ordering::Relaxed);
+        self.integrated.store(meter.integrated(), Ordering::Relaxed);
+        self.true_peak.store(meter.true_peak(), Ordering::Relaxed);
+    }
+}
+
+impl Default for LoudnessMeter {
+    fn default() -> Self {
+        Self {
+            params: Arc::new(LoudnessParams::default()),
+            meter: Meter::new(44100.0, 0),
+            readings: Arc::new(Readings::new()),
+        }
+    }
+}
+
+impl Default for LoudnessParams {
+    fn default() -> Self {
+        Self {
+            editor: editor::default_params(),
+
+            target: FloatParam::new(
+                "Target",
+                -14.0,
+                FloatRange::Linear {
+                    min: -36.0,
+                    max: -6.0,
+                },
+            )
+            .with_step_size(0.5)
+            .with_unit(" LUFS")
+            .with_value_to_string(formatters::v2s_f32_rounded(1)),
+        }
+    }
+}
+
+impl Plugin for LoudnessMeter {
+    const NAME: &'static str = "Loudness Meter";
+    const VENDOR: &'static str = "Your Studio";
+    const URL: &'static str = env!("CARGO_PKG_HOMEPAGE");
+    const EMAIL: &'static str = "contact@yourstudio.com";
+    const VERSION: &'static str = env!("CARGO_PKG_VERSION");
+
+    const AUDIO_IO_LAYOUTS: &'static [AudioIOLayout] = &[layouts::STEREO, layouts::MONO];
+
+    type SysExMessage = ();
+    type BackgroundTask = ();
+
+    fn params(&self) -> Arc<dyn Params> {
+        self.params.clone()
+    }
+
+    fn editor(&mut self, _async_executor: AsyncExecutor<Self>) -> Option<Box<dyn Editor>> {
+        editor::create(self.params.clone(), self.readings.clone())
+    }
+
+    fn initialize(
+        &mut self,
+        audio_io_layout: &AudioIOLayout,
+        buffer_config: &BufferConfig,
+        _context: &mut impl InitContext<Self>,
+    ) -> bool {
+        let num_channels = audio_io_layout
+            .main_output_channels
+            .map_or(0, |channels| channels.get() as usize);
+
+        self.meter = Meter::new(buffer_config.sample_rate, num_channels);
+        self.readings.update(&self.meter);
+        true
+    }
+
+    fn process(
+        &mut self,
+        buffer: &mut Buffer,
+        _aux: &mut AuxiliaryBuffers,
+        _context: &mut impl ProcessContext<Self>,
+    ) -> ProcessStatus {
+        // The integrated loudness has to cover the whole programme, so this keeps measuring
+        // while the editor is closed
+        self.measure(buffer.as_slice_immutable());
+
+        ProcessStatus::Normal
+    }
+}
+
+impl LoudnessMeter {
+    fn measure(&mut self, channels: &[&mut [f32]]) {
+        if self.readings.reset_requested.swap(false, Ordering::Relaxed) {
+            self.meter.reset();
+        }
+
+        self.meter.process(channels);
+        self.readings.update(&self.meter);
+    }
+}
+
+impl ClapPlugin for LoudnessMeter {
+    const CLAP_ID: &'static str = "com.yourstudio.loudness-meter";
+    const CLAP_DESCRIPTION: Option<&'static str> =
+        Some("An EBU R 128 loudness meter with true peak measurement");
+    const CLAP_MANUAL_URL: Option<&'static str> = Some(Self::URL);
+    const CLAP_SUPPORT_URL: Option<&'static str> = None;
+    const CLAP_FEATURES: &'static [ClapFeature] = &[
+        ClapFeature::AudioEffect,
+        ClapFeature::Analyzer,
+        ClapFeature::Utility,
+        ClapFeature::Stereo,
+        ClapFeature::Mono,
+    ];
+}
+
+impl Vst3Plugin for LoudnessMeter {
+    const VST3_CLASS_ID: [u8; 16] = *b"LoudnessMeter000";
+    const VST3_SUBCATEGORIES: &'static [Vst3SubCategory] =
+        &[Vst3SubCategory::Fx, Vst3SubCategory::Analyzer];
+}
+
+nih_export_clap!(LoudnessMeter);
+nih_export_vst3!(LoudnessMeter);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use plugin_scaffold::test_util::assert_no_alloc;
+
+    plugin_scaffold::install_alloc_disabler!();
+
+    const SAMPLE_RATE: f32 = 48000.0;
+    const BLOCK_SIZE: usize = 512;
+
+    fn test_plugin() -> LoudnessMeter {
+        LoudnessMeter {
+            meter: Meter::new(SAMPLE_RATE, 2),
+            ..LoudnessMeter::default()
+        }
+    }
+
+    /// A 1 kHz sine at `level_db` dBFS in both channels.
+    fn measure_sine(plugin: &mut LoudnessMeter, level_db: f32, seconds: f32) {
+        let amplitude = util::db_to_gain(level_db);
+        let mut left: Vec<f32> = (0..(SAMPLE_RATE * seconds) as usize)
+            .map(|i| (std::f32::consts::TAU * 1000.0 * i as f32 / SAMPLE_RATE).sin() * amplitude)
+            .collect();
+        let mut right = left.clone();
+        for (left, right) in left
+            .chunks_mut(BLOCK_SIZE)
+            .zip(right.chunks_mut(BLOCK_SIZE))
+        {
+            plugin.measure(&[left, right]);
+        }
+    }
+
+    #[test]
+    fn stereo_sine_at_minus_23_dbfs_reads_minus_23_lufs() {
+        let mut plugin = test_plugin();
+        measure_sine(&mut plugin, -23.0, 10.0);
+
+        for reading in [
+            &plugin.readings.momentary,
+            &plugin.readings.short_term,
+            &plugin.readings.integrated,
+        ] {
+            let lufs = reading.load(Ordering::Relaxed);
+            assert!((lufs + 23.0).abs() < 0.1, "{lufs}");
+        }
+        let true_peak = plugin.readings.true_peak.load(Ordering::Relaxed);
+        assert!((true_peak + 23.0).abs() < 0.2, "{true_peak}");
+    }
+
+    #[test]
+    fn reset_starts_a_new_measurement() {
+        let mut plugin = test_plugin();
+        measure_sine(&mut plugin, -10.0, 2.0);
+
+        plugin
+            .readings
+            .reset_requested
+            .store(true, Ordering::Relaxed);
+        measure_sine(&mut plugin, -30.0, 5.0);
+
+        let integrated = plugin.readings.integrated.load(Ordering::Relaxed);
+        let true_peak = plugin.readings.true_peak.load(Ordering::Relaxed);
+        assert!((integrated + 30.0).abs() < 0.1, "{integrated}");
+        assert!(true_peak < -29.0, "{true_peak}");
+    }
+
+    #[test]
+    fn measuring_does_not_allocate() {
+        let mut plugin = test_plugin();
+        let mut left: Vec<f32> = (0..SAMPLE_RATE as usize * 4)
+            .map(|i| (std::f32::consts::TAU * 1000.0 * i as f32 / SAMPLE_RATE).sin() * 0.5)
+            .collect();
+        let mut right = left.clone();
+        assert_no_alloc(|| {
+            for (left, right) in left
+                .chunks_mut(BLOCK_SIZE)
+                .zip(right.chunks_mut(BLOCK_SIZE))
+            {
+                plugin.measure(&[left, right]);
+            }
+        });
+    }
+}
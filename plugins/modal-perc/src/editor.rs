@@ -0,0 +1,40 @@
+use crate::ModalPercParams;
+use nih_plug::prelude::*;
+use nih_plug_egui::create_egui_editor;
+use std::sync::Arc;
+use ui_widgets::{param_combo, EditorParams, EditorShell, KeyboardState, ParamKnob};
+
+pub(crate) fn default_params() -> EditorParams {
+    EditorParams::new((400, 330))
+}
+
+pub(crate) fn create(
+    params: Arc<ModalPercParams>,
+    keyboard: Arc<KeyboardState>,
+) -> Option<Box<dyn Editor>> {
+    create_egui_editor(
+        params.editor.state.clone(),
+        (),
+        |_, _| {},
+        move |egui_ctx, setter, _state| {
+            EditorShell::new(&params.editor)
+                .with_bypass(&params.bypass)
+                .with_keyboard(&keyboard)
+                .show(egui_ctx, setter, |ui| {
+                    ui.horizontal(|ui| {
+                        param_combo(ui, &params.material, setter)
+                            .on_hover_text("Takes effect from the next note");
+                        ui.add(ParamKnob::for_param(&params.size, setter))
+                            .on_hover_text("Bigger bodies ring longer");
+                        ui.add(ParamKnob::for_param(&params.gain, setter));
+                    });
+                    ui.horizontal(|ui| {
+                        ui.add(ParamKnob::for_param(&params.hardness, setter))
+                            .on_hover_text("Harder mallets bring out the higher modes");
+                        ui.add(ParamKnob::for_param(&params.position, setter))
+                            .on_hover_text("From the edge to the middle of the object");
+                    });
+                });
+        },
+    )
+}
@@ -0,0 +1,562 @@
+use dsp_core::bypass::SoftBypass;
+use dsp_core::modal::{ModalResonator, Strike};
+use dsp_core::{guard, utils::midi_to_freq};
+use material::{Material, MAX_MODES};
+use nih_plug::prelude::*;
+use plugin_scaffold::layouts;
+use plugin_scaffold::notes::{NoteHandler, PendingEvents};
+use std::sync::Arc;
+use ui_widgets::{EditorParams, KeyboardInput, KeyboardState};
+
+mod editor;
+mod material;
+
+const MAX_VOICES: usize = 16;
+
+/// Scales the voices down so a few notes struck together don't clip.
+const VOICE_GAIN: f32 = 0.5;
+
+/// A voice is freed once the loudest it could still get falls below this, about -80 dB.
+const SILENCE: f32 = 1e-4;
+
+/// How long the softest and the hardest mallets stay in contact, in seconds.
+const SOFT_CONTACT: f32 = 0.004;
+const HARD_CONTACT: f32 = 0.0001;
+
+/// A modal percussion instrument: every note strikes a bank of resonators tuned to the modes of
+/// a bar, a glass, or a bell. Notes ring out on their own, so note offs are ignored.
+struct ModalPerc {
+    params: Arc<ModalPercParams>,
+    voices: [Voice; MAX_VOICES],
+    /// The next voice to steal.
+    next_voice: usize,
+
+    /// Notes played on the editor's keyboard, and the notes received from the host for it to
+    /// show.
+    keyboard: Arc<KeyboardState>,
+    keyboard_input: KeyboardInput,
+
+    /// `VoiceTerminated` events waiting to be sent to the host at the end of the block.
+    pending_events: PendingEvents<()>,
+
+    /// Fades the output out and back in when the plugin is bypassed.
+    bypass: SoftBypass,
+}
+
+#[derive(Clone)]
+struct Voice {
+    resonator: ModalResonator,
+    strike: Strike,
+    /// Whether the voice is still ringing, updated at the end of every block.
+    active: bool,
+    note: Option<u8>,
+
+    /// The host's ID for this voice, cleared once the host has been told the voice terminated.
+    voice_id: Option<i32>,
+    channel: u8,
+}
+
+#[derive(Params)]
+struct ModalPercParams {
+    #[nested]
+    editor: EditorParams,
+
+    /// The host's bypass switch.
+    #[id = "bypass"]
+    pub bypass: BoolParam,
+
+    #[id = "gain"]
+    pub gain: FloatParam,
+
+    /// Changes take effect from the next note.
+    #[id = "material"]
+    pub material: EnumParam<Material>,
+
+    /// Scales every mode's decay time.
+    #[id = "size"]
+    pub size: FloatParam,
+
+    /// From a soft yarn mallet at 0% to a hard metal beater at 100%. Harder mallets excite the
+    /// higher modes more.
+    #[id = "hardness"]
+    pub hardness: FloatParam,
+
+    /// Where the object is struck, from the edge at 0% to the middle at 50%.
+    #[id = "position"]
+    pub position: FloatParam,
+}
+
+impl Default for ModalPerc {
+    fn default() -> Self {
+        Self {
+            params: Arc::new(ModalPercParams::default()),
+            voices: std::array::from_fn(|_| Voice::new(44100.0)),
+            next_voice: 0,
+            keyboard: Arc::new(KeyboardState::new()),
+            keyboard_input: KeyboardInput::default(),
+            pending_events: PendingEvents::with_capacity(MAX_VOICES * 2),
+            bypass: SoftBypass::new(44100.0),
+        }
+    }
+}
+
+impl Default for ModalPercParams {
+    fn default() -> Self {
+        Self {
+            editor: editor::default_params(),
+
+            bypass: BoolParam::new("Bypass", false).make_bypass(),
+
+            gain: FloatParam::new(
+                "Gain",
+                util::db_to_gain(-6.0),
+                FloatRange::Skewed {
+                    min: util::db_to_gain(-30.0),
+                    max: util::db_to_gain(6.0),
+                    factor: FloatRange::gain_skew_factor(-30.0, 6.0),
+                },
+            )
+            .with_smoother(SmoothingStyle::Logarithmic(50.0))
+            .with_unit(" dB")
+            .with_value_to_string(formatters::v2s_f32_gain_to_db(2))
+            .with_string_to_value(formatters::s2v_f32_gain_to_db()),
+
+            material: EnumParam::new("Material", Material::Marimba),
+
+            size: FloatParam::new(
+                "Size",
+                1.0,
+                FloatRange::Skewed {
+                    min: 0.25,
+                    max: 4.0,
+                    factor: FloatRange::skew_factor(-1.0),
+                },
+            )
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage())
+            .with_unit(" %"),
+
+            hardness: FloatParam::new("Hardness", 0.5, FloatRange::Linear { min: 0.0, max: 1.0 })
+                .with_value_to_string(formatters::v2s_f32_percentage(0))
+                .with_string_to_value(formatters::s2v_f32_percentage())
+                .with_unit(" %"),
+
+            position: FloatParam::new(
+                "Position",
+                0.3,
+                FloatRange::Linear {
+                    min: 0.05,
+                    max: 0.5,
+                },
+            )
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage())
+            .with_unit(" %"),
+        }
+    }
+}
+
+impl Plugin for ModalPerc {
+    const NAME: &'static str = "Modal Perc";
+    const VENDOR: &'static str = "Your Studio";
+    const URL: &'static str = env!("CARGO_PKG_HOMEPAGE");
+    const EMAIL: &'static str = "contact@yourstudio.com";
+    const VERSION: &'static str = env!("CARGO_PKG_VERSION");
+
+    const AUDIO_IO_LAYOUTS: &'static [AudioIOLayout] = layouts::INSTRUMENT_LAYOUTS;
+    const MIDI_INPUT: MidiConfig = MidiConfig::Basic;
+    const SAMPLE_ACCURATE_AUTOMATION: bool = true;
+
+    type SysExMessage = ();
+    type BackgroundTask = ();
+
+    fn params(&self) -> Arc<dyn Params> {
+        self.params.clone()
+    }
+
+    fn editor(&mut self, _async_executor: AsyncExecutor<Self>) -> Option<Box<dyn Editor>> {
+        editor::create(self.params.clone(), self.keyboard.clone())
+    }
+
+    fn initialize(
+        &mut self,
+        _audio_io_layout: &AudioIOLayout,
+        buffer_config: &BufferConfig,
+        _context: &mut impl InitContext<Self>,
+    ) -> bool {
+        self.bypass = SoftBypass::new(buffer_config.sample_rate);
+        self.bypass.reset(self.params.bypass.value());
+
+        for voice in &mut self.voices {
+            *voice = Voice::new(buffer_config.sample_rate);
+        }
+        true
+    }
+
+    fn reset(&mut self) {
+        self.keyboard.clear_received();
+        for voice in &mut self.voices {
+            voice.resonator.reset();
+            voice.strike.reset();
+        }
+    }
+
+    fn process(
+        &mut self,
+        buffer: &mut Buffer,
+        _aux: &mut AuxiliaryBuffers,
+        context: &mut impl ProcessContext<Self>,
+    ) -> ProcessStatus {
+        self.handle_keyboard_notes();
+        let keyboard = self.keyboard.clone();
+        let mut next_event = keyboard.echo(context.next_event());
+        for (sample_id, channel_samples) in buffer.iter_samples().enumerate() {
+            self.handle_due_events(sample_id as u32, &mut next_event, || {
+                keyboard.echo(context.next_event())
+            });
+
+            let sample = self.render_sample() * self.params.gain.smoothed.next();
+            for output in channel_samples {
+                *output = sample;
+            }
+        }
+
+        // Voices keep ringing while bypassed so they don't come back mid-note
+        self.bypass.set_bypassed(self.params.bypass.value());
+        self.bypass.process_to_silence(buffer.as_slice());
+
+        for channel in buffer.as_slice_immutable() {
+            guard::check_block("modal-perc output", channel);
+        }
+
+        let last_sample = buffer.samples().saturating_sub(1) as u32;
+        self.terminate_finished_voices(last_sample);
+        self.pending_events.send(context);
+
+        if self.voices.iter().any(|voice| voice.active) {
+            ProcessStatus::KeepAlive
+        } else {
+            ProcessStatus::Normal
+        }
+    }
+}
+
+impl Voice {
+    fn new(sample_rate: f32) -> Self {
+        Self {
+            resonator: ModalResonator::new(sample_rate, MAX_MODES),
+            strike: Strike::new(sample_rate),
+            active: false,
+            note: None,
+            voice_id: None,
+            channel: 0,
+        }
+    }
+
+    /// Queue a `VoiceTerminated` event for this voice if the host still thinks it's playing.
+    fn terminate(&mut self, timing: u32, pending_events: &mut PendingEvents<()>) {
+        pending_events.voice_terminated(timing, self.voice_id.take(), self.channel, self.note);
+    }
+}
+
+/// Compute a voice ID in case the host doesn't provide them.
+const fn compute_fallback_voice_id(note: u8, channel: u8) -> i32 {
+    note as i32 | ((channel as i32) << 16)
+}
+
+/// How long the mallet stays in contact. Harder hits bounce off sooner, down to half as long at
+/// full velocity.
+fn contact_time(hardness: f32, velocity: f32) -> f32 {
+    SOFT_CONTACT * (HARD_CONTACT / SOFT_CONTACT).powf(hardness) * (1.5 - velocity)
+}
+
+impl ModalPerc {
+    /// Play the keys pressed and released on the editor's keyboard since the last block.
+    fn handle_keyboard_notes(&mut self) {
+        let notes = self.keyboard_input.poll(&self.keyboard);
+        self.handle_events(notes);
+    }
+
+    fn note_on(
+        &mut self,
+        timing: u32,
+        voice_id: Option<i32>,
+        channel: u8,
+        note: u8,
+        velocity: f32,
+    ) {
+        // Striking a note that's still ringing hits the same object again, the new strike adds
+        // to what's already there
+        let ringing = self
+            .voices
+            .iter()
+            .position(|voice| voice.active && voice.note == Some(note) && voice.channel == channel);
+        let voice_idx = ringing
+            .or_else(|| self.voices.iter().position(|voice| !voice.active))
+            .unwrap_or_else(|| {
+                let idx = self.next_voice;
+                self.next_voice = (idx + 1) % MAX_VOICES;
+                idx
+            });
+
+        // The host needs to know a stolen voice ended before its ID is reused
+        let voice = &mut self.voices[voice_idx];
+        voice.terminate(timing, &mut self.pending_events);
+        if ringing.is_none() {
+            voice.resonator.reset();
+        }
+
+        let params = &self.params;
+        let (modes, num_modes) = params.material.value().modes(
+            midi_to_freq(note),
+            params.size.value(),
+            params.position.value(),
+        );
+        voice.resonator.set_modes(&modes[..num_modes]);
+        voice
+            .strike
+            .strike(velocity, contact_time(params.hardness.value(), velocity));
+
+        voice.active = true;
+        voice.note = Some(note);
+        voice.voice_id = Some(voice_id.unwrap_or_else(|| compute_fallback_voice_id(note, channel)));
+        voice.channel = channel;
+    }
+
+    fn render_sample(&mut self) -> f32 {
+        let mut sum = 0.0;
+        for voice in &mut self.voices {
+            if voice.active {
+                sum += voice.resonator.process(voice.strike.next_sample());
+            }
+        }
+
+        sum * VOICE_GAIN
+    }
+
+    /// Free the voices that have rung out.
+    fn terminate_finished_voices(&mut self, timing: u32) {
+        for voice in &mut self.voices {
+            if voice.active && !voice.strike.is_active() && voice.resonator.envelope() < SILENCE {
+                voice.active = false;
+                voice.terminate(timing, &mut self.pending_events);
+            }
+        }
+    }
+}
+
+impl NoteHandler<()> for ModalPerc {
+    fn handle_event(&mut self, event: PluginNoteEvent<Self>) {
+        if let NoteEvent::NoteOn {
+            timing,
+            voice_id,
+            channel,
+            note,
+            velocity,
+        } = event
+        {
+            if velocity > 0.0 {
+                self.note_on(timing, voice_id, channel, note, velocity);
+            }
+        }
+    }
+}
+
+impl ClapPlugin for ModalPerc {
+    const CLAP_ID: &'static str = "com.yourstudio.modal-perc";
+    const CLAP_DESCRIPTION: Option<&'static str> =
+        Some("Mallet, bell, and metal percussion from modal synthesis");
+    const CLAP_MANUAL_URL: Option<&'static str> = Some(Self::URL);
+    const CLAP_SUPPORT_URL: Option<&'static str> = None;
+    const CLAP_FEATURES: &'static [ClapFeature] = &[
+        ClapFeature::Instrument,
+        ClapFeature::Synthesizer,
+        ClapFeature::Stereo,
+    ];
+}
+
+impl Vst3Plugin for ModalPerc {
+    const VST3_CLASS_ID: [u8; 16] = *b"ModalPercussion!";
+    const VST3_SUBCATEGORIES: &'static [Vst3SubCategory] = &[
+        Vst3SubCategory::Instrument,
+        Vst3SubCategory::Drum,
+        Vst3SubCategory::Synth,
+    ];
+}
+
+nih_export_clap!(ModalPerc);
+nih_export_vst3!(ModalPerc);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use plugin_scaffold::test_util::assert_no_alloc;
+
+    plugin_scaffold::install_alloc_disabler!();
+
+    const SAMPLE_RATE: f32 = 48000.0;
+    const BLOCK_SIZE: usize = 256;
+
+    fn test_plugin(params: ModalPercParams) -> ModalPerc {
+        ModalPerc {
+            params: Arc::new(params),
+            voices: std::array::from_fn(|_| Voice::new(SAMPLE_RATE)),
+            ..ModalPerc::default()
+        }
+    }
+
+    fn params(material: Material, size: f32, hardness: f32) -> ModalPercParams {
+        let linear =
+            |name, value| FloatParam::new(name, value, FloatRange::Linear { min: 0.0, max: 4.0 });
+        ModalPercParams {
+            material: EnumParam::new("Material", material),
+            size: linear("Size", size),
+            hardness: linear("Hardness", hardness),
+            ..ModalPercParams::default()
+        }
+    }
+
+    fn note_on(timing: u32, note: u8, velocity: f32) -> PluginNoteEvent<ModalPerc> {
+        NoteEvent::NoteOn {
+            timing,
+            voice_id: None,
+            channel: 0,
+            note,
+            velocity,
+        }
+    }
+
+    /// Mirror of the per-sample loop in `process()`, without the output gain.
+    fn render_into(
+        plugin: &mut ModalPerc,
+        events: impl IntoIterator<Item = PluginNoteEvent<ModalPerc>>,
+        output: &mut [f32],
+    ) {
+        let mut events = events.into_iter();
+        let mut next_event = events.next();
+
+        for block in output.chunks_mut(BLOCK_SIZE) {
+            for (sample_id, sample) in block.iter_mut().enumerate() {
+                plugin.handle_due_events(sample_id as u32, &mut next_event, || events.next());
+                *sample = plugin.render_sample();
+            }
+            plugin.terminate_finished_voices(0);
+        }
+    }
+
+    fn render(
+        plugin: &mut ModalPerc,
+        events: Vec<PluginNoteEvent<ModalPerc>>,
+        seconds: f32,
+    ) -> Vec<f32> {
+        let mut output = vec![0.0; (seconds * SAMPLE_RATE) as usize];
+        render_into(plugin, events, &mut output);
+        output
+    }
+
+    fn active_voices(plugin: &ModalPerc) -> usize {
+        plugin.voices.iter().filter(|voice| voice.active).count()
+    }
+
+    fn peak(samples: &[f32]) -> f32 {
+        samples
+            .iter()
+            .fold(0.0, |peak, sample| f32::max(peak, sample.abs()))
+    }
+
+    #[test]
+    fn every_material_rings_at_the_note() {
+        for material in [
+            Material::Marimba,
+            Material::Vibraphone,
+            Material::Glass,
+            Material::Bell,
+            Material::Metal,
+        ] {
+            let (modes, num_modes) = material.modes(440.0, 1.0, 0.3);
+            let modes = &modes[..num_modes];
+            assert!(
+                modes.iter().any(|mode| mode.frequency == 440.0),
+                "{material:?}"
+            );
+
+            let total: f32 = modes.iter().map(|mode| mode.gain).sum();
+            assert!(total > 0.5 && total <= 1.0, "{material:?}: {total}");
+        }
+    }
+
+    #[test]
+    fn striking_the_middle_leaves_out_every_other_mode() {
+        let (modes, num_modes) = Material::Metal.modes(440.0, 1.0, 0.5);
+        for (idx, mode) in modes[..num_modes].iter().enumerate() {
+            assert_eq!(mode.gain.abs() < 1e-6, idx % 2 == 1, "mode {idx}");
+        }
+    }
+
+    #[test]
+    fn notes_ring_out_and_free_their_voices() {
+        let mut plugin = test_plugin(params(Material::Marimba, 1.0, 0.5));
+        let output = render(&mut plugin, vec![note_on(0, 60, 1.0)], 0.5);
+        assert!(peak(&output) > 0.1);
+        assert_eq!(active_voices(&plugin), 1);
+
+        render(&mut plugin, Vec::new(), 3.0);
+        assert_eq!(active_voices(&plugin), 0);
+        assert!(matches!(
+            plugin.pending_events[..],
+            [NoteEvent::VoiceTerminated { note: 60, .. }]
+        ));
+    }
+
+    #[test]
+    fn bigger_sizes_ring_longer() {
+        let level_after_a_second = |size| {
+            let mut plugin = test_plugin(params(Material::Vibraphone, size, 0.5));
+            let output = render(&mut plugin, vec![note_on(0, 60, 1.0)], 1.1);
+            peak(&output[SAMPLE_RATE as usize..])
+        };
+
+        let small = level_after_a_second(0.5);
+        let large = level_after_a_second(2.0);
+        assert!(large > small * 4.0, "{small} vs {large}");
+    }
+
+    #[test]
+    fn harder_mallets_sound_brighter() {
+        // The level of the sample to sample differences, which weigh the highs more
+        let brightness = |hardness| {
+            let mut plugin = test_plugin(params(Material::Metal, 1.0, hardness));
+            let output = render(&mut plugin, vec![note_on(0, 60, 1.0)], 0.2);
+            let differences: Vec<f32> = output.windows(2).map(|pair| pair[1] - pair[0]).collect();
+            peak(&differences) / peak(&output)
+        };
+
+        let soft = brightness(0.0);
+        let hard = brightness(1.0);
+        assert!(hard > soft * 2.0, "{soft} vs {hard}");
+    }
+
+    #[test]
+    fn restriking_a_ringing_note_reuses_its_voice() {
+        let mut plugin = test_plugin(params(Material::Bell, 1.0, 0.5));
+        render(
+            &mut plugin,
+            vec![note_on(0, 60, 1.0), note_on(100, 60, 1.0)],
+            0.1,
+        );
+        assert_eq!(active_voices(&plugin), 1);
+        assert_eq!(plugin.pending_events.len(), 1);
+    }
+
+    #[test]
+    fn processing_does_not_allocate() {
+        let mut plugin = test_plugin(params(Material::Bell, 1.0, 0.5));
+        let mut output = vec![0.0; SAMPLE_RATE as usize];
+        let events = [
+            note_on(0, 60, 1.0),
+            note_on(100, 67, 0.5),
+            note_on(200, 72, 0.8),
+        ];
+        assert_no_alloc(|| render_into(&mut plugin, events, &mut output));
+    }
+}
@@ -0,0 +1,114 @@
+//! The modes of each material: their frequencies relative to the note, their levels, and how
+//! quickly they fade.
+
+use dsp_core::modal::Mode;
+use nih_plug::prelude::Enum;
+
+/// The most modes any material uses.
+pub const MAX_MODES: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+pub enum Material {
+    /// A wooden bar with an undercut tuning its overtones to two octaves and a bit over three.
+    Marimba,
+    /// An aluminium bar tuned like the marimba's but ringing for much longer.
+    Vibraphone,
+    /// A wine glass, with widely spaced and slightly flat overtones.
+    Glass,
+    /// A church bell, with the hum an octave below the note and the minor third above it.
+    Bell,
+    /// An untuned steel bar, for clangy metallic hits.
+    Metal,
+}
+
+struct Model {
+    /// Each mode's frequency as a ratio to the note's, and its relative level.
+    partials: &'static [(f32, f32)],
+    /// The decay time of a mode at the note's frequency, in seconds.
+    decay: f32,
+    /// How much faster higher modes fade. Each mode's decay time is divided by its ratio to the
+    /// power of this.
+    damping: f32,
+}
+
+impl Material {
+    fn model(self) -> Model {
+        match self {
+            Material::Marimba => Model {
+                partials: &[(1.0, 1.0), (3.99, 0.5), (10.65, 0.25)],
+                decay: 0.8,
+                damping: 1.0,
+            },
+            Material::Vibraphone => Model {
+                partials: &[(1.0, 1.0), (4.0, 0.35), (10.0, 0.15)],
+                decay: 4.0,
+                damping: 0.8,
+            },
+            Material::Glass => Model {
+                partials: &[
+                    (1.0, 1.0),
+                    (2.32, 0.6),
+                    (4.25, 0.35),
+                    (6.63, 0.2),
+                    (9.38, 0.1),
+                ],
+                decay: 2.5,
+                damping: 0.6,
+            },
+            Material::Bell => Model {
+                partials: &[
+                    (0.5, 0.5),
+                    (1.0, 0.7),
+                    (1.19, 0.6),
+                    (1.56, 0.35),
+                    (2.0, 1.0),
+                    (2.51, 0.4),
+                    (2.66, 0.3),
+                    (3.01, 0.25),
+                ],
+                decay: 8.0,
+                damping: 0.5,
+            },
+            Material::Metal => Model {
+                partials: &[
+                    (1.0, 1.0),
+                    (2.756, 0.6),
+                    (5.404, 0.4),
+                    (8.933, 0.3),
+                    (13.344, 0.2),
+                    (18.64, 0.12),
+                ],
+                decay: 3.0,
+                damping: 0.7,
+            },
+        }
+    }
+
+    /// The modes of a note at `frequency` Hz, and how many of them there are. `size` scales the
+    /// decay times, bigger bodies ring longer. `position` is where it's struck, from the edge at
+    /// 0 to the middle at 0.5: each mode is as loud as its shape moves there, so striking the
+    /// middle of a bar leaves out every other mode.
+    pub fn modes(self, frequency: f32, size: f32, position: f32) -> ([Mode; MAX_MODES], usize) {
+        let model = self.model();
+        // Normalized so a unit strike can't go over full scale, even with every mode in phase
+        let total: f32 = model.partials.iter().map(|(_, gain)| gain).sum();
+
+        let mut modes = [Mode {
+            frequency: 0.0,
+            decay: 0.0,
+            gain: 0.0,
+        }; MAX_MODES];
+        for (idx, (mode, &(ratio, gain))) in modes.iter_mut().zip(model.partials).enumerate() {
+            let shape = (std::f32::consts::PI * (idx + 1) as f32 * position)
+                .sin()
+                .abs();
+            *mode = Mode {
+                frequency: frequency * ratio,
+                decay: model.decay * size / ratio.powf(model.damping),
+                gain: gain / total * shape,
+            };
+        }
+
+        (modes, model.partials.len())
+    }
+}
@@ -0,0 +1,178 @@
+//! The parameters and gain computer for each band.
+
+use dsp_core::dynamics::{gain_to_db, EnvelopeFollower};
+use nih_plug::prelude::*;
+use plugin_scaffold::formatters::{s2v_f32_ms_then_s, v2s_f32_ms_then_s};
+use std::sync::Arc;
+
+pub const NUM_BANDS: usize = 4;
+
+/// The width of the soft knee around the threshold, in dB.
+const KNEE_DB: f32 = 6.0;
+
+#[derive(Params)]
+pub struct BandParams {
+    #[id = "threshold"]
+    pub threshold: FloatParam,
+
+    #[id = "ratio"]
+    pub ratio: FloatParam,
+
+    #[id = "attack"]
+    pub attack: FloatParam,
+
+    #[id = "release"]
+    pub release: FloatParam,
+
+    #[id = "makeup"]
+    pub makeup: FloatParam,
+
+    /// Only listen to the soloed bands.
+    #[id = "solo"]
+    pub solo: BoolParam,
+
+    /// Pass the band through without compressing it.
+    #[id = "bypass"]
+    pub bypass: BoolParam,
+
+    /// Which of the mid and side signals the band compresses in mid/side mode.
+    #[id = "ms_target"]
+    pub ms_target: EnumParam<MidSideTarget>,
+}
+
+/// Which of the mid and side signals a band compresses in mid/side mode. Each one has its own
+/// detector, so a loud mid doesn't pull down the side or the other way around.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+pub enum MidSideTarget {
+    #[name = "Mid + Side"]
+    Both,
+    #[name = "Mid"]
+    Mid,
+    #[name = "Side"]
+    Side,
+}
+
+impl MidSideTarget {
+    /// Whether the band compresses the mid signal, 0, or the side signal, 1.
+    pub fn includes(self, signal: usize) -> bool {
+        match self {
+            MidSideTarget::Both => true,
+            MidSideTarget::Mid => signal == 0,
+            MidSideTarget::Side => signal == 1,
+        }
+    }
+}
+
+impl Default for BandParams {
+    fn default() -> Self {
+        Self {
+            threshold: FloatParam::new(
+                "Threshold",
+                -18.0,
+                FloatRange::Linear {
+                    min: -60.0,
+                    max: 0.0,
+                },
+            )
+            .with_smoother(SmoothingStyle::Linear(20.0))
+            .with_step_size(0.1)
+            .with_unit(" dB"),
+
+            ratio: FloatParam::new(
+                "Ratio",
+                2.0,
+                FloatRange::Skewed {
+                    min: 1.0,
+                    max: 20.0,
+                    factor: FloatRange::skew_factor(-1.5),
+                },
+            )
+            .with_smoother(SmoothingStyle::Linear(20.0))
+            .with_value_to_string(Arc::new(|ratio| format!("{ratio:.1}:1")))
+            .with_string_to_value(Arc::new(|string| {
+                string.trim().trim_end_matches(":1").parse().ok()
+            })),
+
+            attack: FloatParam::new(
+                "Attack",
+                10.0,
+                FloatRange::Skewed {
+                    min: 0.1,
+                    max: 100.0,
+                    factor: FloatRange::skew_factor(-2.0),
+                },
+            )
+            .with_value_to_string(v2s_f32_ms_then_s(1))
+            .with_string_to_value(s2v_f32_ms_then_s()),
+
+            release: FloatParam::new(
+                "Release",
+                150.0,
+                FloatRange::Skewed {
+                    min: 10.0,
+                    max: 1000.0,
+                    factor: FloatRange::skew_factor(-1.0),
+                },
+            )
+            .with_value_to_string(v2s_f32_ms_then_s(0))
+            .with_string_to_value(s2v_f32_ms_then_s()),
+
+            makeup: FloatParam::new(
+                "Makeup",
+                util::db_to_gain(0.0),
+                FloatRange::Skewed {
+                    min: util::db_to_gain(0.0),
+                    max: util::db_to_gain(24.0),
+                    factor: FloatRange::gain_skew_factor(0.0, 24.0),
+                },
+            )
+            .with_smoother(SmoothingStyle::Logarithmic(50.0))
+            .with_unit(" dB")
+            .with_value_to_string(formatters::v2s_f32_gain_to_db(2))
+            .with_string_to_value(formatters::s2v_f32_gain_to_db()),
+
+            solo: BoolParam::new("Solo", false),
+            bypass: BoolParam::new("Bypass", false),
+            ms_target: EnumParam::new("M/S Target", MidSideTarget::Both),
+        }
+    }
+}
+
+/// Follows a band's peak level and works out how much to turn it down. Left and right share one
+/// compressor so the stereo image doesn't shift, while mid and side get one each.
+pub struct BandCompressor {
+    follower: EnvelopeFollower,
+}
+
+impl BandCompressor {
+    pub fn new(sample_rate: f32) -> Self {
+        Self {
+            follower: EnvelopeFollower::new(sample_rate),
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.follower.reset();
+    }
+
+    /// Set the attack and release times in milliseconds.
+    pub fn set_times(&mut self, attack_ms: f32, release_ms: f32) {
+        self.follower.set_attack(attack_ms / 1000.0);
+        self.follower.set_release(release_ms / 1000.0);
+    }
+
+    /// The gain reduction in dB for the band's current peak across channels.
+    pub fn gain_reduction_db(&mut self, peak: f32, threshold_db: f32, ratio: f32) -> f32 {
+        let over = gain_to_db(self.follower.process(peak)) - threshold_db;
+        let slope = 1.0 - 1.0 / ratio;
+
+        if over <= -KNEE_DB / 2.0 {
+            0.0
+        } else if over < KNEE_DB / 2.0 {
+            let into_knee = over + KNEE_DB / 2.0;
+            slope * into_knee * into_knee / (2.0 * KNEE_DB)
+        } else {
+            slope * over
+        }
+    }
+}
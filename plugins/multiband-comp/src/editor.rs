@@ -0,0 +1,156 @@
+use crate::band::NUM_BANDS;
+use crate::{GainReduction, MultibandParams, StereoMode};
+use nih_plug::prelude::*;
+use nih_plug_egui::create_egui_editor;
+use nih_plug_egui::egui::{self, Align2, Color32, FontId, Rect, Sense, Stroke, Ui, Vec2};
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use ui_widgets::spectrum::frequency_to_x;
+use ui_widgets::{param_combo, param_toggle, EditorParams, EditorShell, ParamKnob};
+
+/// The gain reduction at the bottom of the display.
+const MAX_REDUCTION_DB: f32 = 24.0;
+
+const REDUCTION_COLOR: Color32 = Color32::from_rgb(230, 140, 60);
+
+pub(crate) fn default_params() -> EditorParams {
+    EditorParams::new((560, 600))
+}
+
+/// Format a crossover frequency the same way as the parameters.
+fn format_frequency(frequency: f32) -> String {
+    if frequency >= 1000.0 {
+        format!("{:.1} kHz", frequency / 1000.0)
+    } else {
+        format!("{frequency:.0} Hz")
+    }
+}
+
+/// The bands on a log frequency axis, with the crossover frequencies marked and each band's gain
+/// reduction hanging down from the top.
+fn crossover_display(ui: &mut Ui, crossovers: &[f32], gain_reduction: &[f32]) {
+    let (rect, _) = ui.allocate_exact_size(Vec2::new(540.0, 100.0), Sense::hover());
+    if !ui.is_rect_visible(rect) {
+        return;
+    }
+
+    let visuals = ui.visuals();
+    let painter = ui.painter_at(rect);
+    painter.rect_filled(rect, 0.0, visuals.extreme_bg_color);
+
+    let edges: Vec<f32> = std::iter::once(rect.left())
+        .chain(
+            crossovers
+                .iter()
+                .map(|&frequency| frequency_to_x(rect, frequency)),
+        )
+        .chain(std::iter::once(rect.right()))
+        .collect();
+    for (band, (edges, reduction)) in edges.windows(2).zip(gain_reduction).enumerate() {
+        let band_rect = Rect::from_x_y_ranges(edges[0]..=edges[1], rect.y_range());
+        if band % 2 == 1 {
+            painter.rect_filled(band_rect, 0.0, visuals.faint_bg_color);
+        }
+
+        let depth = (reduction / MAX_REDUCTION_DB).clamp(0.0, 1.0) * rect.height();
+        painter.rect_filled(
+            Rect::from_x_y_ranges(
+                edges[0] + 4.0..=edges[1] - 4.0,
+                rect.top()..=rect.top() + depth,
+            ),
+            0.0,
+            REDUCTION_COLOR,
+        );
+        painter.text(
+            band_rect.center_bottom() - Vec2::new(0.0, 4.0),
+            Align2::CENTER_BOTTOM,
+            format!("{}", band + 1),
+            FontId::proportional(12.0),
+            visuals.weak_text_color(),
+        );
+    }
+
+    for &frequency in crossovers {
+        let x = frequency_to_x(rect, frequency);
+        painter.line_segment(
+            [egui::pos2(x, rect.top()), egui::pos2(x, rect.bottom())],
+            Stroke::new(1.5, visuals.strong_text_color()),
+        );
+        painter.text(
+            egui::pos2(x + 3.0, rect.center().y),
+            Align2::LEFT_CENTER,
+            format_frequency(frequency),
+            FontId::proportional(11.0),
+            visuals.text_color(),
+        );
+    }
+}
+
+pub(crate) fn create(
+    params: Arc<MultibandParams>,
+    gain_reduction: Arc<GainReduction>,
+) -> Option<Box<dyn Editor>> {
+    create_egui_editor(
+        params.editor.state.clone(),
+        (),
+        |_, _| {},
+        move |egui_ctx, setter, _state| {
+            let num_bands = params.band_count.value().bands();
+            let mid_side = params.stereo_mode.value() == StereoMode::MidSide;
+            let crossovers = params.crossovers();
+            let reduction: [f32; NUM_BANDS] =
+                std::array::from_fn(|band| gain_reduction.bands[band].load(Ordering::Relaxed));
+
+            EditorShell::new(&params.editor)
+                .with_bypass(&params.bypass)
+                .show(egui_ctx, setter, |ui| {
+                    ui.horizontal(|ui| {
+                        param_combo(ui, &params.band_count, setter);
+                        param_combo(ui, &params.stereo_mode, setter).on_hover_text(
+                            "Compress the mid and side signals on their own, each band can \
+                             target either or both",
+                        );
+                        ui.add(ParamKnob::for_param(&params.output, setter));
+                    });
+
+                    crossover_display(ui, &crossovers[..num_bands - 1], &reduction[..num_bands]);
+
+                    ui.horizontal(|ui| {
+                        ui.add(ParamKnob::for_param(&params.low_crossover, setter));
+                        ui.add(ParamKnob::for_param(&params.mid_crossover, setter));
+                        ui.add_enabled_ui(num_bands == 4, |ui| {
+                            ui.add(ParamKnob::for_param(&params.high_crossover, setter));
+                        });
+                    });
+                    ui.separator();
+
+                    ui.horizontal(|ui| {
+                        for (band, band_params) in params.bands[..num_bands].iter().enumerate() {
+                            ui.push_id(band, |ui| {
+                                ui.vertical(|ui| {
+                                    ui.label(format!("Band {}", band + 1));
+                                    ui.add(ParamKnob::for_param(&band_params.threshold, setter));
+                                    ui.add(ParamKnob::for_param(&band_params.ratio, setter));
+                                    ui.horizontal(|ui| {
+                                        ui.add(ParamKnob::for_param(&band_params.attack, setter));
+                                        ui.add(ParamKnob::for_param(&band_params.release, setter));
+                                    });
+                                    ui.add(ParamKnob::for_param(&band_params.makeup, setter));
+                                    ui.horizontal(|ui| {
+                                        param_toggle(ui, &band_params.solo, setter);
+                                        param_toggle(ui, &band_params.bypass, setter);
+                                    });
+                                    ui.add_enabled_ui(mid_side, |ui| {
+                                        param_combo(ui, &band_params.ms_target, setter);
+                                    });
+                                });
+                            });
+                        }
+                    });
+                });
+
+            // The gain reduction is live, so keep repainting while the editor is open
+            egui_ctx.request_repaint();
+        },
+    )
+}
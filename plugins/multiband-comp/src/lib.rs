@@ -0,0 +1,642 @@
+use atomic_float::AtomicF32;
+use band::{BandCompressor, BandParams, NUM_BANDS};
+use dsp_core::crossover::BandSplitter;
+use dsp_core::dynamics::db_to_gain;
+use dsp_core::guard;
+use dsp_core::mix::MixStage;
+use dsp_core::stereo::{decode_mid_side, encode_mid_side};
+use nih_plug::prelude::*;
+use plugin_scaffold::layouts;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use ui_widgets::EditorParams;
+
+mod band;
+mod editor;
+
+/// Splits the signal into three or four bands with Linkwitz-Riley crossovers and compresses
+/// each band on its own.
+struct MultibandComp {
+    params: Arc<MultibandParams>,
+    /// One splitter per channel, always splitting into [`NUM_BANDS`] bands. With three bands the
+    /// top two are summed again, which leaves the response just as flat. In mid/side mode the
+    /// first splits the mid signal and the second the side signal.
+    splitters: Vec<BandSplitter>,
+    /// The current sample of every band, per channel.
+    frames: Vec<[f32; NUM_BANDS]>,
+    /// The first set compresses left and right together, or the mid signal in mid/side mode. The
+    /// second set compresses the side signal.
+    compressors: [[BandCompressor; NUM_BANDS]; 2],
+    /// Whether the last block was processed in mid/side.
+    mid_side: bool,
+    gain_reduction: Arc<GainReduction>,
+    /// Only used for a click-free bypass.
+    mix: MixStage,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+pub enum BandCount {
+    #[name = "3 Bands"]
+    Three,
+    #[name = "4 Bands"]
+    Four,
+}
+
+impl BandCount {
+    pub fn bands(self) -> usize {
+        match self {
+            BandCount::Three => 3,
+            BandCount::Four => 4,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+pub enum StereoMode {
+    #[name = "Stereo"]
+    LeftRight,
+    #[name = "Mid/Side"]
+    MidSide,
+}
+
+#[derive(Params)]
+struct MultibandParams {
+    #[nested]
+    editor: EditorParams,
+
+    /// The host's bypass switch.
+    #[id = "bypass"]
+    pub bypass: BoolParam,
+
+    #[id = "band_count"]
+    pub band_count: EnumParam<BandCount>,
+
+    /// Compress the mid and side signals on their own instead of left and right together. Mono
+    /// inputs are always compressed as they are.
+    #[id = "stereo_mode"]
+    pub stereo_mode: EnumParam<StereoMode>,
+
+    #[id = "low_crossover"]
+    pub low_crossover: FloatParam,
+
+    #[id = "mid_crossover"]
+    pub mid_crossover: FloatParam,
+
+    /// Only used with four bands.
+    #[id = "high_crossover"]
+    pub high_crossover: FloatParam,
+
+    #[nested(array, group = "Band")]
+    pub bands: [BandParams; NUM_BANDS],
+
+    #[id = "output"]
+    pub output: FloatParam,
+}
+
+impl MultibandParams {
+    /// The crossover frequencies from low to high, each kept above the one below it.
+    pub fn crossovers(&self) -> [f32; NUM_BANDS - 1] {
+        let low = self.low_crossover.value();
+        let mid = self.mid_crossover.value().max(low);
+        let high = self.high_crossover.value().max(mid);
+        [low, mid, high]
+    }
+}
+
+/// How far each band is currently turned down in dB, shared with the editor.
+pub struct GainReduction {
+    pub bands: [AtomicF32; NUM_BANDS],
+}
+
+impl Default for MultibandComp {
+    fn default() -> Self {
+        let params = Arc::new(MultibandParams::default());
+        Self {
+            splitters: Vec::new(),
+            frames: Vec::new(),
+            compressors: compressors(44100.0),
+            mid_side: false,
+            gain_reduction: Arc::new(GainReduction {
+                bands: Default::default(),
+            }),
+            mix: MixStage::new(44100.0, 0, 0, 0),
+            params,
+        }
+    }
+}
+
+impl Default for MultibandParams {
+    fn default() -> Self {
+        let crossover = |name, default| {
+            FloatParam::new(
+                name,
+                default,
+                FloatRange::Skewed {
+                    min: 20.0,
+                    max: 20000.0,
+                    factor: FloatRange::skew_factor(-2.0),
+                },
+            )
+            .with_value_to_string(formatters::v2s_f32_hz_then_khz(0))
+            .with_string_to_value(formatters::s2v_f32_hz_then_khz())
+        };
+
+        Self {
+            editor: editor::default_params(),
+
+            bypass: BoolParam::new("Bypass", false).make_bypass(),
+
+            band_count: EnumParam::new("Bands", BandCount::Three),
+
+            stereo_mode: EnumParam::new("Stereo Mode", StereoMode::LeftRight),
+
+            low_crossover: crossover("Low Crossover", 120.0),
+            mid_crossover: crossover("Mid Crossover", 1000.0),
+            high_crossover: crossover("High Crossover", 6000.0),
+
+            bands: Default::default(),
+
+            output: FloatParam::new(
+                "Output",
+                util::db_to_gain(0.0),
+                FloatRange::Skewed {
+                    min: util::db_to_gain(-24.0),
+                    max: util::db_to_gain(12.0),
+                    factor: FloatRange::gain_skew_factor(-24.0, 12.0),
+                },
+            )
+            .with_smoother(SmoothingStyle::Logarithmic(50.0))
+            .with_unit(" dB")
+            .with_value_to_string(formatters::v2s_f32_gain_to_db(2))
+            .with_string_to_value(formatters::s2v_f32_gain_to_db()),
+        }
+    }
+}
+
+impl Plugin for MultibandComp {
+    const NAME: &'static str = "Multiband Compressor";
+    const VENDOR: &'static str = "Your Studio";
+    const URL: &'static str = env!("CARGO_PKG_HOMEPAGE");
+    const EMAIL: &'static str = "contact@yourstudio.com";
+    const VERSION: &'static str = env!("CARGO_PKG_VERSION");
+
+    const AUDIO_IO_LAYOUTS: &'static [AudioIOLayout] = &[layouts::STEREO, layouts::MONO];
+
+    type SysExMessage = ();
+    type BackgroundTask = ();
+
+    fn params(&self) -> Arc<dyn Params> {
+        self.params.clone()
+    }
+
+    fn editor(&mut self, _async_executor: AsyncExecutor<Self>) -> Option<Box<dyn Editor>> {
+        editor::create(self.params.clone(), self.gain_reduction.clone())
+    }
+
+    fn initialize(
+        &mut self,
+        audio_io_layout: &AudioIOLayout,
+        buffer_config: &BufferConfig,
+        _context: &mut impl InitContext<Self>,
+    ) -> bool {
+        let num_channels = audio_io_layout
+            .main_output_channels
+            .map_or(0, |channels| channels.get() as usize);
+        let sample_rate = buffer_config.sample_rate;
+
+        self.splitters =
+            vec![BandSplitter::new(sample_rate, &self.params.crossovers()); num_channels];
+        self.frames = vec![[0.0; NUM_BANDS]; num_channels];
+        self.compressors = compressors(sample_rate);
+        self.mix = MixStage::new(
+            sample_rate,
+            num_channels,
+            buffer_config.max_buffer_size as usize,
+            0,
+        );
+        self.mix.set_mix(self.target_mix());
+        self.mix.reset();
+        true
+    }
+
+    fn reset(&mut self) {
+        for splitter in &mut self.splitters {
+            splitter.reset();
+        }
+        for compressor in self.compressors.iter_mut().flatten() {
+            compressor.reset();
+        }
+        self.mix.reset();
+    }
+
+    fn process(
+        &mut self,
+        buffer: &mut Buffer,
+        _aux: &mut AuxiliaryBuffers,
+        _context: &mut impl ProcessContext<Self>,
+    ) -> ProcessStatus {
+        self.process_channels(buffer.as_slice());
+
+        ProcessStatus::Normal
+    }
+}
+
+impl MultibandComp {
+    fn process_channels(&mut self, channels: &mut [&mut [f32]]) {
+        self.mix.set_mix(self.target_mix());
+        self.mix.capture_dry(channels);
+        self.update_bands();
+
+        let params = &self.params;
+        let num_bands = params.band_count.value().bands();
+        let bands = &params.bands[..num_bands];
+        let any_solo = bands.iter().any(|band| band.solo.value());
+        let audible: [bool; NUM_BANDS] =
+            std::array::from_fn(|band| band < num_bands && (!any_solo || bands[band].solo.value()));
+        let mut max_reduction = [0.0f32; NUM_BANDS];
+
+        // The filters and detectors hold on to the other mode's signals, so start them afresh
+        let mid_side = params.stereo_mode.value() == StereoMode::MidSide && channels.len() == 2;
+        if mid_side != self.mid_side {
+            self.mid_side = mid_side;
+            for splitter in &mut self.splitters {
+                splitter.reset();
+            }
+            for compressor in self.compressors.iter_mut().flatten() {
+                compressor.reset();
+            }
+        }
+        let num_signals = if mid_side { 2 } else { 1 };
+
+        let num_samples = channels.first().map_or(0, |channel| channel.len());
+        for i in 0..num_samples {
+            if mid_side {
+                let (mid, side) = encode_mid_side(channels[0][i], channels[1][i]);
+                self.splitters[0].process(mid, &mut self.frames[0]);
+                self.splitters[1].process(side, &mut self.frames[1]);
+            } else {
+                for ((splitter, frame), channel) in self
+                    .splitters
+                    .iter_mut()
+                    .zip(&mut self.frames)
+                    .zip(channels.iter())
+                {
+                    splitter.process(channel[i], frame);
+                }
+            }
+            if num_bands == 3 {
+                for frame in &mut self.frames {
+                    frame[2] += frame[3];
+                }
+            }
+
+            // Left and right share the first set of gains, mid and side get one set each
+            let mut gains = [[0.0; NUM_BANDS]; 2];
+            for (band, band_params) in bands.iter().enumerate() {
+                let threshold = band_params.threshold.smoothed.next();
+                let ratio = band_params.ratio.smoothed.next();
+                let makeup = band_params.makeup.smoothed.next();
+                let target = band_params.ms_target.value();
+
+                for signal in 0..num_signals {
+                    let peak = if mid_side {
+                        self.frames[signal][band].abs()
+                    } else {
+                        self.frames
+                            .iter()
+                            .map(|frame| frame[band].abs())
+                            .fold(0.0, f32::max)
+                    };
+                    let reduction =
+                        self.compressors[signal][band].gain_reduction_db(peak, threshold, ratio);
+
+                    let gain = &mut gains[signal][band];
+                    if band_params.bypass.value() || (mid_side && !target.includes(signal)) {
+                        *gain = 1.0;
+                    } else {
+                        *gain = db_to_gain(-reduction) * makeup;
+                        max_reduction[band] = max_reduction[band].max(reduction);
+                    }
+                    if !audible[band] {
+                        *gain = 0.0;
+                    }
+                }
+            }
+            if !mid_side {
+                gains[1] = gains[0];
+            }
+
+            // Mono or stereo, so there are at most two signals to sum
+            let output = params.output.smoothed.next();
+            let mut sums = [0.0; 2];
+            for ((frame, gains), sum) in self.frames.iter().zip(&gains).zip(&mut sums) {
+                *sum = frame
+                    .iter()
+                    .zip(gains)
+                    .map(|(sample, gain)| sample * gain)
+                    .sum::<f32>()
+                    * output;
+            }
+            if mid_side {
+                let (left, right) = decode_mid_side(sums[0], sums[1]);
+                channels[0][i] = left;
+                channels[1][i] = right;
+            } else {
+                for (channel, sum) in channels.iter_mut().zip(sums) {
+                    channel[i] = sum;
+                }
+            }
+        }
+
+        for (band, reduction) in self.gain_reduction.bands.iter().enumerate() {
+            let reduction_db = if band < num_bands {
+                max_reduction[band]
+            } else {
+                0.0
+            };
+            reduction.store(reduction_db, Ordering::Relaxed);
+        }
+
+        for channel in channels.iter() {
+            guard::check_block("MultibandComp", channel);
+        }
+        self.mix.mix_into(channels);
+    }
+
+    /// Pick up crossover and timing changes once per block.
+    fn update_bands(&mut self) {
+        let crossovers = self.params.crossovers();
+        for splitter in &mut self.splitters {
+            for (index, &frequency) in crossovers.iter().enumerate() {
+                if splitter.frequency(index) != frequency {
+                    splitter.set_frequency(index, frequency);
+                }
+            }
+        }
+
+        for compressors in &mut self.compressors {
+            for (compressor, params) in compressors.iter_mut().zip(&self.params.bands) {
+                compressor.set_times(params.attack.value(), params.release.value());
+            }
+        }
+    }
+
+    /// Bypassing fades to the dry signal through the mix stage.
+    fn target_mix(&self) -> f32 {
+        if self.params.bypass.value() {
+            0.0
+        } else {
+            1.0
+        }
+    }
+}
+
+/// One set of band compressors for left and right or the mid signal, and one for the side signal.
+fn compressors(sample_rate: f32) -> [[BandCompressor; NUM_BANDS]; 2] {
+    std::array::from_fn(|_| std::array::from_fn(|_| BandCompressor::new(sample_rate)))
+}
+
+impl ClapPlugin for MultibandComp {
+    const CLAP_ID: &'static str = "com.yourstudio.multiband-comp";
+    const CLAP_DESCRIPTION: Option<&'static str> =
+        Some("A three or four band compressor with Linkwitz-Riley crossovers");
+    const CLAP_MANUAL_URL: Option<&'static str> = Some(Self::URL);
+    const CLAP_SUPPORT_URL: Option<&'static str> = None;
+    const CLAP_FEATURES: &'static [ClapFeature] = &[
+        ClapFeature::AudioEffect,
+        ClapFeature::Compressor,
+        ClapFeature::Stereo,
+        ClapFeature::Mono,
+    ];
+}
+
+impl Vst3Plugin for MultibandComp {
+    const VST3_CLASS_ID: [u8; 16] = *b"MultibandComp000";
+    const VST3_SUBCATEGORIES: &'static [Vst3SubCategory] =
+        &[Vst3SubCategory::Fx, Vst3SubCategory::Dynamics];
+}
+
+nih_export_clap!(MultibandComp);
+nih_export_vst3!(MultibandComp);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use plugin_scaffold::test_util::assert_no_alloc;
+
+    plugin_scaffold::install_alloc_disabler!();
+
+    const SAMPLE_RATE: f32 = 48000.0;
+    const BLOCK_SIZE: usize = 512;
+
+    /// A band that leaves the signal alone unless `threshold` and `ratio` say otherwise.
+    fn band(threshold: f32, ratio: f32, solo: bool) -> BandParams {
+        let params = BandParams {
+            threshold: FloatParam::new(
+                "Threshold",
+                threshold,
+                FloatRange::Linear {
+                    min: -60.0,
+                    max: 0.0,
+                },
+            ),
+            ratio: FloatParam::new(
+                "Ratio",
+                ratio,
+                FloatRange::Linear {
+                    min: 1.0,
+                    max: 20.0,
+                },
+            ),
+            solo: BoolParam::new("Solo", solo),
+            ..BandParams::default()
+        };
+        params.threshold.smoothed.reset(threshold);
+        params.ratio.smoothed.reset(ratio);
+        params.makeup.smoothed.reset(params.makeup.value());
+        params
+    }
+
+    /// A band like [`band()`] that only compresses `target` in mid/side mode.
+    fn targeting(target: MidSideTarget, threshold: f32, ratio: f32) -> BandParams {
+        BandParams {
+            ms_target: EnumParam::new("M/S Target", target),
+            ..band(threshold, ratio, false)
+        }
+    }
+
+    fn build_plugin(params: MultibandParams, num_channels: usize) -> MultibandComp {
+        params.output.smoothed.reset(params.output.value());
+
+        let mut plugin = MultibandComp {
+            splitters: vec![BandSplitter::new(SAMPLE_RATE, &params.crossovers()); num_channels],
+            frames: vec![[0.0; NUM_BANDS]; num_channels],
+            compressors: compressors(SAMPLE_RATE),
+            mix: MixStage::new(SAMPLE_RATE, num_channels, BLOCK_SIZE, 0),
+            params: Arc::new(params),
+            ..MultibandComp::default()
+        };
+        plugin.mix.reset();
+        plugin
+    }
+
+    fn test_plugin(band_count: BandCount, bands: [BandParams; NUM_BANDS]) -> MultibandComp {
+        let params = MultibandParams {
+            band_count: EnumParam::new("Bands", band_count),
+            bands,
+            ..MultibandParams::default()
+        };
+        build_plugin(params, 1)
+    }
+
+    /// A stereo plugin in mid/side mode.
+    fn mid_side_plugin(bands: [BandParams; NUM_BANDS]) -> MultibandComp {
+        let params = MultibandParams {
+            stereo_mode: EnumParam::new("Stereo Mode", StereoMode::MidSide),
+            bands,
+            ..MultibandParams::default()
+        };
+        build_plugin(params, 2)
+    }
+
+    fn neutral_bands() -> [BandParams; NUM_BANDS] {
+        std::array::from_fn(|_| band(0.0, 1.0, false))
+    }
+
+    /// One and a half seconds of a sine.
+    fn sine(frequency: f32, amplitude: f32) -> Vec<f32> {
+        (0..(SAMPLE_RATE * 1.5) as usize)
+            .map(|i| (std::f32::consts::TAU * frequency * i as f32 / SAMPLE_RATE).sin() * amplitude)
+            .collect()
+    }
+
+    /// The level of the last second of `samples`, after half a second to settle, as the
+    /// amplitude of a sine with the same RMS.
+    fn settled_level(samples: &[f32]) -> f32 {
+        let settled = &samples[SAMPLE_RATE as usize / 2..];
+        let mean_square =
+            settled.iter().map(|sample| sample * sample).sum::<f32>() / settled.len() as f32;
+        (mean_square * 2.0).sqrt()
+    }
+
+    fn output_level(plugin: &mut MultibandComp, frequency: f32, amplitude: f32) -> f32 {
+        let mut samples = sine(frequency, amplitude);
+        for block in samples.chunks_mut(BLOCK_SIZE) {
+            plugin.process_channels(&mut [block]);
+        }
+        settled_level(&samples)
+    }
+
+    /// The levels of the left and right outputs for the given inputs.
+    fn stereo_output_levels(
+        plugin: &mut MultibandComp,
+        mut left: Vec<f32>,
+        mut right: Vec<f32>,
+    ) -> (f32, f32) {
+        for (left, right) in left
+            .chunks_mut(BLOCK_SIZE)
+            .zip(right.chunks_mut(BLOCK_SIZE))
+        {
+            plugin.process_channels(&mut [left, right]);
+        }
+        (settled_level(&left), settled_level(&right))
+    }
+
+    #[test]
+    fn neutral_bands_sum_back_to_the_input_level() {
+        for band_count in [BandCount::Three, BandCount::Four] {
+            for frequency in [50.0, 120.0, 440.0, 1000.0, 3000.0, 6000.0, 15000.0] {
+                let mut plugin = test_plugin(band_count, neutral_bands());
+                let level = output_level(&mut plugin, frequency, 0.5);
+                assert!(
+                    util::gain_to_db(level / 0.5).abs() < 0.01,
+                    "{level} at {frequency} Hz with {band_count:?} bands"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn loud_band_is_compressed_without_touching_the_others() {
+        let bands = || {
+            let mut bands = neutral_bands();
+            bands[0] = band(-30.0, 4.0, false);
+            bands
+        };
+
+        // 24 dB over the threshold at 4:1 comes out 18 dB down, a little less since the envelope
+        // sags between the peaks of a low note
+        let low = output_level(&mut test_plugin(BandCount::Three, bands()), 30.0, 0.5);
+        let reduction = util::gain_to_db(0.5 / low);
+        assert!((reduction - 18.0).abs() < 1.5, "{reduction}");
+
+        let high = output_level(&mut test_plugin(BandCount::Three, bands()), 5000.0, 0.5);
+        assert!(util::gain_to_db(high / 0.5).abs() < 0.01, "{high}");
+    }
+
+    #[test]
+    fn soloing_a_band_mutes_the_others() {
+        let mut bands = neutral_bands();
+        bands[3] = band(0.0, 1.0, true);
+
+        let low = output_level(&mut test_plugin(BandCount::Four, bands), 50.0, 0.5);
+        assert!(low < 0.001, "{low}");
+
+        let mut bands = neutral_bands();
+        bands[3] = band(0.0, 1.0, true);
+        let high = output_level(&mut test_plugin(BandCount::Four, bands), 18000.0, 0.5);
+        assert!(util::gain_to_db(high / 0.5).abs() < 0.1, "{high}");
+    }
+
+    #[test]
+    fn neutral_bands_sum_back_to_the_input_level_in_mid_side_mode() {
+        let mut plugin = mid_side_plugin(neutral_bands());
+        let (left, right) = stereo_output_levels(&mut plugin, sine(440.0, 0.5), sine(3000.0, 0.25));
+        assert!(util::gain_to_db(left / 0.5).abs() < 0.01, "{left}");
+        assert!(util::gain_to_db(right / 0.25).abs() < 0.01, "{right}");
+    }
+
+    #[test]
+    fn mid_side_bands_only_compress_their_target() {
+        // Opposite channels are all side signal, which then compresses just like the mono signal
+        // in `loud_band_is_compressed_without_touching_the_others()`
+        let left = sine(30.0, 0.5);
+        let right: Vec<f32> = left.iter().map(|sample| -sample).collect();
+
+        let bands = std::array::from_fn(|_| targeting(MidSideTarget::Mid, -30.0, 4.0));
+        let (level, _) =
+            stereo_output_levels(&mut mid_side_plugin(bands), left.clone(), right.clone());
+        assert!(util::gain_to_db(level / 0.5).abs() < 0.01, "{level}");
+
+        let bands = std::array::from_fn(|_| targeting(MidSideTarget::Side, -30.0, 4.0));
+        let (level, _) = stereo_output_levels(&mut mid_side_plugin(bands), left, right);
+        let reduction = util::gain_to_db(0.5 / level);
+        assert!((reduction - 18.0).abs() < 1.5, "{reduction}");
+    }
+
+    #[test]
+    fn processing_does_not_allocate() {
+        let bands = std::array::from_fn(|_| band(-20.0, 4.0, false));
+        let mut plugin = test_plugin(BandCount::Four, bands);
+        let mut samples: Vec<f32> = (0..SAMPLE_RATE as usize)
+            .map(|i| (std::f32::consts::TAU * 440.0 * i as f32 / SAMPLE_RATE).sin() * 0.5)
+            .collect();
+        assert_no_alloc(|| {
+            for block in samples.chunks_mut(BLOCK_SIZE) {
+                plugin.process_channels(&mut [block]);
+            }
+        });
+
+        let bands = std::array::from_fn(|_| targeting(MidSideTarget::Both, -20.0, 4.0));
+        let mut plugin = mid_side_plugin(bands);
+        let mut right = samples.clone();
+        assert_no_alloc(|| {
+            for (left, right) in samples
+                .chunks_mut(BLOCK_SIZE)
+                .zip(right.chunks_mut(BLOCK_SIZE))
+            {
+                plugin.process_channels(&mut [left, right]);
+            }
+        });
+    }
+}
@@ -0,0 +1,31 @@
+use crate::PitchShiftParams;
+use nih_plug::prelude::*;
+use nih_plug_egui::create_egui_editor;
+use std::sync::Arc;
+use ui_widgets::{param_toggle, EditorParams, EditorShell, ParamKnob};
+
+pub(crate) fn default_params() -> EditorParams {
+    EditorParams::new((320, 200))
+}
+
+pub(crate) fn create(params: Arc<PitchShiftParams>) -> Option<Box<dyn Editor>> {
+    create_egui_editor(
+        params.editor.state.clone(),
+        (),
+        |_, _| {},
+        move |egui_ctx, setter, _state| {
+            EditorShell::new(&params.editor)
+                .with_bypass(&params.bypass)
+                .show(egui_ctx, setter, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.add(ParamKnob::for_param(&params.semitones, setter));
+                        ui.add(ParamKnob::for_param(&params.cents, setter));
+                        ui.add(ParamKnob::for_param(&params.mix, setter));
+                    });
+                    param_toggle(ui, &params.formants, setter).on_hover_text(
+                        "Keep the character of voices and instruments while their pitch moves",
+                    );
+                });
+        },
+    )
+}
@@ -0,0 +1,289 @@
+use dsp_core::guard;
+use dsp_core::mix::MixStage;
+use dsp_core::pitch_shift::{PitchShifter, LATENCY};
+use nih_plug::prelude::*;
+use plugin_scaffold::layouts;
+use std::sync::Arc;
+use ui_widgets::EditorParams;
+
+mod editor;
+
+/// A pitch shifter, or a simple harmonizer when the shifted signal is mixed with the dry input.
+struct PitchShift {
+    params: Arc<PitchShiftParams>,
+    /// One shifter per channel.
+    shifters: Vec<PitchShifter>,
+    /// Mixes in the dry signal, delayed to line up with the shifted signal.
+    mix: MixStage,
+}
+
+#[derive(Params)]
+struct PitchShiftParams {
+    #[nested]
+    editor: EditorParams,
+
+    /// The host's bypass switch.
+    #[id = "bypass"]
+    pub bypass: BoolParam,
+
+    #[id = "semitones"]
+    pub semitones: IntParam,
+
+    #[id = "cents"]
+    pub cents: FloatParam,
+
+    /// Keep the spectral envelope in place so voices don't turn into chipmunks.
+    #[id = "formants"]
+    pub formants: BoolParam,
+
+    #[id = "mix"]
+    pub mix: FloatParam,
+}
+
+impl Default for PitchShift {
+    fn default() -> Self {
+        Self {
+            params: Arc::new(PitchShiftParams::default()),
+            shifters: Vec::new(),
+            mix: MixStage::new(44100.0, 0, 0, 0),
+        }
+    }
+}
+
+impl Default for PitchShiftParams {
+    fn default() -> Self {
+        Self {
+            editor: editor::default_params(),
+
+            bypass: BoolParam::new("Bypass", false).make_bypass(),
+
+            semitones: IntParam::new("Semitones", 0, IntRange::Linear { min: -24, max: 24 })
+                .with_unit(" st"),
+
+            cents: FloatParam::new(
+                "Cents",
+                0.0,
+                FloatRange::Linear {
+                    min: -100.0,
+                    max: 100.0,
+                },
+            )
+            .with_unit(" cents")
+            .with_value_to_string(formatters::v2s_f32_rounded(1)),
+
+            formants: BoolParam::new("Preserve Formants", false),
+
+            mix: FloatParam::new("Mix", 1.0, FloatRange::Linear { min: 0.0, max: 1.0 })
+                .with_value_to_string(formatters::v2s_f32_percentage(0))
+                .with_string_to_value(formatters::s2v_f32_percentage())
+                .with_unit(" %"),
+        }
+    }
+}
+
+impl PitchShiftParams {
+    /// The total shift in semitones.
+    fn shift(&self) -> f32 {
+        self.semitones.value() as f32 + self.cents.value() / 100.0
+    }
+}
+
+impl Plugin for PitchShift {
+    const NAME: &'static str = "Pitch Shift";
+    const VENDOR: &'static str = "Your Studio";
+    const URL: &'static str = env!("CARGO_PKG_HOMEPAGE");
+    const EMAIL: &'static str = "contact@yourstudio.com";
+    const VERSION: &'static str = env!("CARGO_PKG_VERSION");
+
+    // Every input channel is shifted on its own, so mono to stereo would only shift silence
+    const AUDIO_IO_LAYOUTS: &'static [AudioIOLayout] = &[layouts::STEREO, layouts::MONO];
+
+    type SysExMessage = ();
+    type BackgroundTask = ();
+
+    fn params(&self) -> Arc<dyn Params> {
+        self.params.clone()
+    }
+
+    fn editor(&mut self, _async_executor: AsyncExecutor<Self>) -> Option<Box<dyn Editor>> {
+        editor::create(self.params.clone())
+    }
+
+    fn initialize(
+        &mut self,
+        audio_io_layout: &AudioIOLayout,
+        buffer_config: &BufferConfig,
+        context: &mut impl InitContext<Self>,
+    ) -> bool {
+        let num_channels = audio_io_layout
+            .main_output_channels
+            .map_or(0, |channels| channels.get() as usize);
+        let sample_rate = buffer_config.sample_rate;
+
+        self.shifters = (0..num_channels)
+            .map(|_| PitchShifter::new(sample_rate))
+            .collect();
+        self.mix = MixStage::new(
+            sample_rate,
+            num_channels,
+            buffer_config.max_buffer_size as usize,
+            LATENCY,
+        );
+        self.mix.set_latency(LATENCY);
+        self.mix.set_mix(self.target_mix());
+        self.mix.reset();
+
+        context.set_latency_samples(LATENCY as u32);
+        true
+    }
+
+    fn reset(&mut self) {
+        for shifter in &mut self.shifters {
+            shifter.reset();
+        }
+        self.mix.reset();
+    }
+
+    fn process(
+        &mut self,
+        buffer: &mut Buffer,
+        _aux: &mut AuxiliaryBuffers,
+        _context: &mut impl ProcessContext<Self>,
+    ) -> ProcessStatus {
+        self.process_channels(buffer.as_slice());
+
+        ProcessStatus::Normal
+    }
+}
+
+impl PitchShift {
+    fn process_channels(&mut self, channels: &mut [&mut [f32]]) {
+        let shift = self.params.shift();
+        let formants = self.params.formants.value();
+        for shifter in &mut self.shifters {
+            shifter.set_semitones(shift);
+            shifter.set_preserve_formants(formants);
+        }
+
+        self.mix.set_mix(self.target_mix());
+        self.mix.capture_dry(channels);
+        for (channel, shifter) in channels.iter_mut().zip(&mut self.shifters) {
+            shifter.process_block(channel);
+            guard::check_block("PitchShifter", channel);
+        }
+        self.mix.mix_into(channels);
+    }
+
+    /// Bypassing fades to the dry signal through the mix stage, which keeps delaying it by the
+    /// latency the host is compensating for.
+    fn target_mix(&self) -> f32 {
+        if self.params.bypass.value() {
+            0.0
+        } else {
+            self.params.mix.value()
+        }
+    }
+}
+
+impl ClapPlugin for PitchShift {
+    const CLAP_ID: &'static str = "com.yourstudio.pitch-shift";
+    const CLAP_DESCRIPTION: Option<&'static str> =
+        Some("A phase vocoder pitch shifter and harmonizer with formant preservation");
+    const CLAP_MANUAL_URL: Option<&'static str> = Some(Self::URL);
+    const CLAP_SUPPORT_URL: Option<&'static str> = None;
+    const CLAP_FEATURES: &'static [ClapFeature] = &[
+        ClapFeature::AudioEffect,
+        ClapFeature::PitchShifter,
+        ClapFeature::Stereo,
+        ClapFeature::Mono,
+    ];
+}
+
+impl Vst3Plugin for PitchShift {
+    const VST3_CLASS_ID: [u8; 16] = *b"PitchShift000000";
+    const VST3_SUBCATEGORIES: &'static [Vst3SubCategory] =
+        &[Vst3SubCategory::Fx, Vst3SubCategory::PitchShift];
+}
+
+nih_export_clap!(PitchShift);
+nih_export_vst3!(PitchShift);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use plugin_scaffold::test_util::assert_no_alloc;
+
+    plugin_scaffold::install_alloc_disabler!();
+
+    const SAMPLE_RATE: f32 = 48000.0;
+    const BLOCK_SIZE: usize = 512;
+
+    fn test_plugin(params: PitchShiftParams) -> PitchShift {
+        let mut plugin = PitchShift {
+            params: Arc::new(params),
+            ..PitchShift::default()
+        };
+        plugin.shifters = vec![PitchShifter::new(SAMPLE_RATE)];
+        plugin.mix = MixStage::new(SAMPLE_RATE, 1, BLOCK_SIZE, LATENCY);
+        plugin.mix.set_latency(LATENCY);
+        plugin.mix.set_mix(plugin.target_mix());
+        plugin.mix.reset();
+        plugin
+    }
+
+    fn sine(frequency: f32, num_samples: usize) -> Vec<f32> {
+        (0..num_samples)
+            .map(|i| (std::f32::consts::TAU * frequency * i as f32 / SAMPLE_RATE).sin() * 0.5)
+            .collect()
+    }
+
+    fn process(plugin: &mut PitchShift, input: &[f32]) -> Vec<f32> {
+        let mut output = input.to_vec();
+        for block in output.chunks_mut(BLOCK_SIZE) {
+            plugin.process_channels(&mut [block]);
+        }
+        output
+    }
+
+    #[test]
+    fn unshifted_output_is_the_input_delayed_by_the_latency() {
+        let mut plugin = test_plugin(PitchShiftParams::default());
+        let input = sine(440.0, SAMPLE_RATE as usize);
+        let output = process(&mut plugin, &input);
+
+        for (input, output) in input.iter().zip(&output[LATENCY..]) {
+            assert!((input - output).abs() < 1e-4, "{input} != {output}");
+        }
+    }
+
+    #[test]
+    fn dry_signal_lines_up_with_the_shifted_signal() {
+        // Half dry and half unshifted wet sums back to the delayed input, unless the dry
+        // signal is misaligned
+        let mut plugin = test_plugin(PitchShiftParams {
+            mix: FloatParam::new("Mix", 0.5, FloatRange::Linear { min: 0.0, max: 1.0 }),
+            ..PitchShiftParams::default()
+        });
+        let input = sine(440.0, SAMPLE_RATE as usize);
+        let output = process(&mut plugin, &input);
+
+        for (input, output) in input.iter().zip(&output[LATENCY..]) {
+            assert!((input - output).abs() < 1e-4, "{input} != {output}");
+        }
+    }
+
+    #[test]
+    fn processing_does_not_allocate() {
+        let mut plugin = test_plugin(PitchShiftParams {
+            semitones: IntParam::new("Semitones", 7, IntRange::Linear { min: -24, max: 24 }),
+            formants: BoolParam::new("Formants", true),
+            ..PitchShiftParams::default()
+        });
+        let mut samples = sine(440.0, SAMPLE_RATE as usize);
+        assert_no_alloc(|| {
+            for block in samples.chunks_mut(BLOCK_SIZE) {
+                plugin.process_channels(&mut [block]);
+            }
+        });
+    }
+}
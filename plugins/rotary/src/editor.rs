@@ -0,0 +1,42 @@
+use crate::RotaryParams;
+use nih_plug::prelude::*;
+use nih_plug_egui::create_egui_editor;
+use std::sync::Arc;
+use ui_widgets::{param_combo, EditorParams, EditorShell, ParamKnob};
+
+pub(crate) fn default_params() -> EditorParams {
+    EditorParams::new((400, 260))
+}
+
+pub(crate) fn create(params: Arc<RotaryParams>) -> Option<Box<dyn Editor>> {
+    create_egui_editor(
+        params.editor.state.clone(),
+        (),
+        |_, _| {},
+        move |egui_ctx, setter, _state| {
+            EditorShell::new(&params.editor)
+                .with_bypass(&params.bypass)
+                .show(egui_ctx, setter, |ui| {
+                    ui.horizontal(|ui| {
+                        param_combo(ui, &params.speed, setter)
+                            .on_hover_text("Brake lets the rotors coast to a stop");
+                        ui.add(ParamKnob::for_param(&params.ramp, setter))
+                            .on_hover_text("How long the horn takes to change speed");
+                    });
+                    ui.horizontal(|ui| {
+                        ui.add(ParamKnob::for_param(&params.doppler, setter))
+                            .on_hover_text(
+                                "Pitch vibrato from the rotors moving past the microphones",
+                            );
+                        ui.add(ParamKnob::for_param(&params.tremolo, setter))
+                            .on_hover_text("Level changes from the rotors facing towards and away");
+                        ui.add(ParamKnob::for_param(&params.spread, setter))
+                            .on_hover_text("The angle between the microphones");
+                        ui.add(ParamKnob::for_param(&params.balance, setter))
+                            .on_hover_text("Drum at -100%, horn at 100%");
+                        ui.add(ParamKnob::for_param(&params.output, setter));
+                    });
+                });
+        },
+    )
+}
@@ -0,0 +1,515 @@
+use dsp_core::crossover::LinkwitzRiley;
+use dsp_core::guard;
+use dsp_core::mix::MixStage;
+use nih_plug::prelude::*;
+use plugin_scaffold::formatters::{s2v_f32_s_then_ms, v2s_f32_s_then_ms};
+use plugin_scaffold::layouts;
+use rotor::{Rotor, DRUM, HORN};
+use std::sync::Arc;
+use ui_widgets::EditorParams;
+
+mod editor;
+mod rotor;
+
+/// Where the cabinet splits the signal between the drum and the horn, in Hz.
+const CROSSOVER_FREQUENCY: f32 = 800.0;
+
+/// A rotary speaker cabinet: the lows go to a rotating drum and the highs to a rotating horn,
+/// each picked up by a pair of microphones around the cabinet.
+struct Rotary {
+    params: Arc<RotaryParams>,
+    crossover: LinkwitzRiley,
+    horn: Rotor,
+    drum: Rotor,
+    num_inputs: usize,
+    /// Only used for a click-free bypass.
+    mix: MixStage,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+pub enum Speed {
+    /// Chorale.
+    Slow,
+    /// Tremolo.
+    Fast,
+    /// The rotors coast to a stop wherever they are.
+    Brake,
+}
+
+#[derive(Params)]
+struct RotaryParams {
+    #[nested]
+    editor: EditorParams,
+
+    /// The host's bypass switch.
+    #[id = "bypass"]
+    pub bypass: BoolParam,
+
+    #[id = "speed"]
+    pub speed: EnumParam<Speed>,
+
+    /// How long the horn takes to change speed. The heavier drum takes four times as long.
+    #[id = "ramp"]
+    pub ramp: FloatParam,
+
+    /// The depth of the pitch vibrato from the rotors moving towards and away from the
+    /// microphones.
+    #[id = "doppler"]
+    pub doppler: FloatParam,
+
+    /// The depth of the level changes from the rotors facing towards and away from the
+    /// microphones.
+    #[id = "tremolo"]
+    pub tremolo: FloatParam,
+
+    /// The angle between the two microphones, in degrees. Zero is mono.
+    #[id = "spread"]
+    pub spread: FloatParam,
+
+    /// From only the drum at -100% to only the horn at 100%.
+    #[id = "balance"]
+    pub balance: FloatParam,
+
+    #[id = "output"]
+    pub output: FloatParam,
+}
+
+impl Default for Rotary {
+    fn default() -> Self {
+        Self {
+            params: Arc::new(RotaryParams::default()),
+            crossover: LinkwitzRiley::new(44100.0, CROSSOVER_FREQUENCY),
+            horn: Rotor::new(HORN, 44100.0),
+            drum: Rotor::new(DRUM, 44100.0),
+            num_inputs: 0,
+            mix: MixStage::new(44100.0, 0, 0, 0),
+        }
+    }
+}
+
+impl Default for RotaryParams {
+    fn default() -> Self {
+        let percentage = |name, default| {
+            FloatParam::new(name, default, FloatRange::Linear { min: 0.0, max: 1.0 })
+                .with_smoother(SmoothingStyle::Linear(20.0))
+                .with_value_to_string(formatters::v2s_f32_percentage(0))
+                .with_string_to_value(formatters::s2v_f32_percentage())
+                .with_unit(" %")
+        };
+
+        Self {
+            editor: editor::default_params(),
+
+            bypass: BoolParam::new("Bypass", false).make_bypass(),
+
+            speed: EnumParam::new("Speed", Speed::Slow),
+
+            ramp: FloatParam::new(
+                "Ramp",
+                0.8,
+                FloatRange::Skewed {
+                    min: 0.1,
+                    max: 5.0,
+                    factor: FloatRange::skew_factor(-1.0),
+                },
+            )
+            .with_value_to_string(v2s_f32_s_then_ms(0))
+            .with_string_to_value(s2v_f32_s_then_ms()),
+
+            doppler: percentage("Doppler", 1.0),
+            tremolo: percentage("Tremolo", 0.5),
+
+            spread: FloatParam::new(
+                "Spread",
+                120.0,
+                FloatRange::Linear {
+                    min: 0.0,
+                    max: 180.0,
+                },
+            )
+            .with_smoother(SmoothingStyle::Linear(20.0))
+            .with_unit("°")
+            .with_value_to_string(formatters::v2s_f32_rounded(0)),
+
+            balance: FloatParam::new(
+                "Balance",
+                0.0,
+                FloatRange::Linear {
+                    min: -100.0,
+                    max: 100.0,
+                },
+            )
+            .with_smoother(SmoothingStyle::Linear(20.0))
+            .with_unit(" %")
+            .with_value_to_string(formatters::v2s_f32_rounded(0)),
+
+            output: FloatParam::new(
+                "Output",
+                util::db_to_gain(0.0),
+                FloatRange::Skewed {
+                    min: util::db_to_gain(-24.0),
+                    max: util::db_to_gain(12.0),
+                    factor: FloatRange::gain_skew_factor(-24.0, 12.0),
+                },
+            )
+            .with_smoother(SmoothingStyle::Logarithmic(50.0))
+            .with_unit(" dB")
+            .with_value_to_string(formatters::v2s_f32_gain_to_db(2))
+            .with_string_to_value(formatters::s2v_f32_gain_to_db()),
+        }
+    }
+}
+
+impl RotaryParams {
+    /// The speeds the horn and the drum are heading for, in Hz.
+    fn target_speeds(&self) -> (f32, f32) {
+        match self.speed.value() {
+            Speed::Slow => (HORN.slow, DRUM.slow),
+            Speed::Fast => (HORN.fast, DRUM.fast),
+            Speed::Brake => (0.0, 0.0),
+        }
+    }
+}
+
+impl Plugin for Rotary {
+    const NAME: &'static str = "Rotary";
+    const VENDOR: &'static str = "Your Studio";
+    const URL: &'static str = env!("CARGO_PKG_HOMEPAGE");
+    const EMAIL: &'static str = "contact@yourstudio.com";
+    const VERSION: &'static str = env!("CARGO_PKG_VERSION");
+
+    const AUDIO_IO_LAYOUTS: &'static [AudioIOLayout] =
+        &[layouts::STEREO, layouts::MONO_TO_STEREO, layouts::MONO];
+
+    type SysExMessage = ();
+    type BackgroundTask = ();
+
+    fn params(&self) -> Arc<dyn Params> {
+        self.params.clone()
+    }
+
+    fn editor(&mut self, _async_executor: AsyncExecutor<Self>) -> Option<Box<dyn Editor>> {
+        editor::create(self.params.clone())
+    }
+
+    fn initialize(
+        &mut self,
+        audio_io_layout: &AudioIOLayout,
+        buffer_config: &BufferConfig,
+        _context: &mut impl InitContext<Self>,
+    ) -> bool {
+        let num_channels = audio_io_layout
+            .main_output_channels
+            .map_or(0, |channels| channels.get() as usize);
+        let sample_rate = buffer_config.sample_rate;
+
+        self.num_inputs = audio_io_layout
+            .main_input_channels
+            .map_or(0, |channels| channels.get() as usize);
+        self.crossover = LinkwitzRiley::new(sample_rate, CROSSOVER_FREQUENCY);
+        self.horn = Rotor::new(HORN, sample_rate);
+        self.drum = Rotor::new(DRUM, sample_rate);
+        let (horn_speed, drum_speed) = self.params.target_speeds();
+        self.horn.reset_speed(horn_speed);
+        self.drum.reset_speed(drum_speed);
+
+        self.mix = MixStage::new(
+            sample_rate,
+            num_channels,
+            buffer_config.max_buffer_size as usize,
+            0,
+        );
+        self.mix.set_mix(self.target_mix());
+        self.mix.reset();
+        true
+    }
+
+    fn reset(&mut self) {
+        self.crossover.reset();
+        self.horn.reset();
+        self.drum.reset();
+        self.mix.reset();
+    }
+
+    fn process(
+        &mut self,
+        buffer: &mut Buffer,
+        _aux: &mut AuxiliaryBuffers,
+        _context: &mut impl ProcessContext<Self>,
+    ) -> ProcessStatus {
+        self.process_channels(buffer.as_slice());
+
+        ProcessStatus::Normal
+    }
+}
+
+impl Rotary {
+    fn process_channels(&mut self, channels: &mut [&mut [f32]]) {
+        // A mono input is copied to every output first, so the dry signal stays centered
+        if self.num_inputs == 1 {
+            if let Some((input, outputs)) = channels.split_first_mut() {
+                for output in outputs {
+                    output.copy_from_slice(input);
+                }
+            }
+        }
+
+        self.mix.set_mix(self.target_mix());
+        self.mix.capture_dry(channels);
+
+        let (horn_speed, drum_speed) = self.params.target_speeds();
+        let ramp = self.params.ramp.value();
+        self.horn.set_speed(horn_speed, ramp);
+        self.drum.set_speed(drum_speed, ramp);
+
+        let num_channels = channels.len();
+        let num_samples = channels.first().map_or(0, |channel| channel.len());
+        for i in 0..num_samples {
+            let doppler = self.params.doppler.smoothed.next();
+            let tremolo = self.params.tremolo.smoothed.next();
+            let spread = self.params.spread.smoothed.next() / 360.0;
+            let balance = self.params.balance.smoothed.next() / 100.0;
+            let output = self.params.output.smoothed.next();
+            let horn_gain = (1.0 + balance).min(1.0) * output;
+            let drum_gain = (1.0 - balance).min(1.0) * output;
+
+            let input =
+                channels.iter().map(|channel| channel[i]).sum::<f32>() / num_channels as f32;
+            let (low, high) = self.crossover.process(input);
+
+            // The microphones are spread evenly across the spread angle, facing the cabinet
+            for (channel_idx, channel) in channels.iter_mut().enumerate() {
+                let mic_angle = if num_channels > 1 {
+                    spread * (channel_idx as f32 / (num_channels - 1) as f32 - 0.5)
+                } else {
+                    0.0
+                };
+                channel[i] = self.horn.listen(mic_angle, doppler, tremolo) * horn_gain
+                    + self.drum.listen(mic_angle, doppler, tremolo) * drum_gain;
+            }
+            self.horn.advance(high);
+            self.drum.advance(low);
+        }
+
+        for channel in channels.iter() {
+            guard::check_block("Rotary", channel);
+        }
+        self.mix.mix_into(channels);
+    }
+
+    /// Bypassing fades to the dry signal through the mix stage.
+    fn target_mix(&self) -> f32 {
+        if self.params.bypass.value() {
+            0.0
+        } else {
+            1.0
+        }
+    }
+}
+
+impl ClapPlugin for Rotary {
+    const CLAP_ID: &'static str = "com.yourstudio.rotary";
+    const CLAP_DESCRIPTION: Option<&'static str> =
+        Some("A rotary speaker cabinet with a spinning horn and drum");
+    const CLAP_MANUAL_URL: Option<&'static str> = Some(Self::URL);
+    const CLAP_SUPPORT_URL: Option<&'static str> = None;
+    const CLAP_FEATURES: &'static [ClapFeature] = &[
+        ClapFeature::AudioEffect,
+        ClapFeature::Tremolo,
+        ClapFeature::Chorus,
+        ClapFeature::Stereo,
+        ClapFeature::Mono,
+    ];
+}
+
+impl Vst3Plugin for Rotary {
+    const VST3_CLASS_ID: [u8; 16] = *b"RotarySpeakerSim";
+    const VST3_SUBCATEGORIES: &'static [Vst3SubCategory] =
+        &[Vst3SubCategory::Fx, Vst3SubCategory::Modulation];
+}
+
+nih_export_clap!(Rotary);
+nih_export_vst3!(Rotary);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use plugin_scaffold::test_util::assert_no_alloc;
+
+    plugin_scaffold::install_alloc_disabler!();
+
+    const SAMPLE_RATE: f32 = 48000.0;
+    const BLOCK_SIZE: usize = 512;
+
+    fn test_plugin(params: RotaryParams, num_channels: usize) -> Rotary {
+        for param in [
+            &params.doppler,
+            &params.tremolo,
+            &params.spread,
+            &params.balance,
+            &params.output,
+        ] {
+            param.smoothed.reset(param.value());
+        }
+
+        let mut plugin = Rotary {
+            params: Arc::new(params),
+            crossover: LinkwitzRiley::new(SAMPLE_RATE, CROSSOVER_FREQUENCY),
+            horn: Rotor::new(HORN, SAMPLE_RATE),
+            drum: Rotor::new(DRUM, SAMPLE_RATE),
+            num_inputs: num_channels,
+            mix: MixStage::new(SAMPLE_RATE, num_channels, BLOCK_SIZE, 0),
+        };
+        let (horn_speed, drum_speed) = plugin.params.target_speeds();
+        plugin.horn.reset_speed(horn_speed);
+        plugin.drum.reset_speed(drum_speed);
+        plugin.mix.reset();
+        plugin
+    }
+
+    fn params(speed: Speed, doppler: f32, tremolo: f32) -> RotaryParams {
+        let percentage =
+            |name, value| FloatParam::new(name, value, FloatRange::Linear { min: 0.0, max: 1.0 });
+        RotaryParams {
+            speed: EnumParam::new("Speed", speed),
+            doppler: percentage("Doppler", doppler),
+            tremolo: percentage("Tremolo", tremolo),
+            ..RotaryParams::default()
+        }
+    }
+
+    /// One second of a sine at `frequency`.
+    fn tone(frequency: f32) -> Vec<f32> {
+        (0..SAMPLE_RATE as usize)
+            .map(|i| (std::f32::consts::TAU * frequency * i as f32 / SAMPLE_RATE).sin() * 0.5)
+            .collect()
+    }
+
+    /// Run mono `input` through the plugin as the left and right channels.
+    fn process_stereo(plugin: &mut Rotary, input: &[f32]) -> (Vec<f32>, Vec<f32>) {
+        let mut left = input.to_vec();
+        let mut right = input.to_vec();
+        for (left, right) in left
+            .chunks_mut(BLOCK_SIZE)
+            .zip(right.chunks_mut(BLOCK_SIZE))
+        {
+            plugin.process_channels(&mut [left, right]);
+        }
+        (left, right)
+    }
+
+    /// The peak level of each 10 ms window from `start` seconds on.
+    fn window_peaks(samples: &[f32], start: f32) -> Vec<f32> {
+        samples[(start * SAMPLE_RATE) as usize..]
+            .chunks(SAMPLE_RATE as usize / 100)
+            .map(|window| {
+                window
+                    .iter()
+                    .fold(0.0, |peak, sample| f32::max(peak, sample.abs()))
+            })
+            .collect()
+    }
+
+    fn spread_of(values: &[f32]) -> f32 {
+        let max = values.iter().cloned().fold(f32::MIN, f32::max);
+        let min = values.iter().cloned().fold(f32::MAX, f32::min);
+        max / min
+    }
+
+    #[test]
+    fn without_modulation_the_bands_sum_back_flat() {
+        for frequency in [100.0, 800.0, 5000.0] {
+            let mut plugin = test_plugin(params(Speed::Brake, 0.0, 0.0), 2);
+            let (left, right) = process_stereo(&mut plugin, &tone(frequency));
+
+            for channel in [&left, &right] {
+                let peak = window_peaks(channel, 0.5).into_iter().fold(0.0, f32::max);
+                assert!((peak - 0.5).abs() < 0.01, "{frequency} Hz: {peak}");
+            }
+        }
+    }
+
+    #[test]
+    fn fast_speed_swirls_the_level_of_the_highs() {
+        let mut plugin = test_plugin(params(Speed::Fast, 0.0, 1.0), 2);
+        let (left, _) = process_stereo(&mut plugin, &tone(3000.0));
+        let swirl = spread_of(&window_peaks(&left, 0.2));
+        assert!(swirl > 2.0, "{swirl}");
+
+        let mut plugin = test_plugin(params(Speed::Brake, 0.0, 1.0), 2);
+        let (left, _) = process_stereo(&mut plugin, &tone(3000.0));
+        let still = spread_of(&window_peaks(&left, 0.2));
+        assert!(still < 1.01, "{still}");
+    }
+
+    #[test]
+    fn doppler_bends_the_pitch_of_the_horn() {
+        let mut plugin = test_plugin(params(Speed::Fast, 1.0, 0.0), 2);
+        let (left, _) = process_stereo(&mut plugin, &tone(2000.0));
+
+        // The time between rising zero crossings, interpolated between samples
+        let crossings: Vec<f32> = left
+            .windows(2)
+            .enumerate()
+            .skip(SAMPLE_RATE as usize / 10)
+            .filter(|(_, pair)| pair[0] < 0.0 && pair[1] >= 0.0)
+            .map(|(i, pair)| i as f32 + pair[0] / (pair[0] - pair[1]))
+            .collect();
+        let periods: Vec<f32> = crossings.windows(2).map(|pair| pair[1] - pair[0]).collect();
+        let bend = spread_of(&periods);
+        assert!(bend > 1.02, "{bend}");
+    }
+
+    #[test]
+    fn the_drum_speeds_up_slower_than_the_horn() {
+        let mut plugin = test_plugin(params(Speed::Slow, 1.0, 0.5), 2);
+        process_stereo(&mut plugin, &tone(440.0)[..BLOCK_SIZE]);
+        plugin.params = Arc::new(params(Speed::Fast, 1.0, 0.5));
+        process_stereo(&mut plugin, &tone(440.0)[..SAMPLE_RATE as usize / 2]);
+
+        let horn = plugin.horn.speed() / HORN.fast;
+        let drum = plugin.drum.speed() / DRUM.fast;
+        assert!(horn > 0.4 && drum < horn * 0.6, "horn {horn}, drum {drum}");
+    }
+
+    #[test]
+    fn spread_separates_the_microphones() {
+        let mono = RotaryParams {
+            spread: FloatParam::new(
+                "Spread",
+                0.0,
+                FloatRange::Linear {
+                    min: 0.0,
+                    max: 180.0,
+                },
+            ),
+            ..params(Speed::Fast, 1.0, 0.5)
+        };
+        let (left, right) = process_stereo(&mut test_plugin(mono, 2), &tone(3000.0));
+        assert_eq!(left, right);
+
+        let (left, right) = process_stereo(
+            &mut test_plugin(params(Speed::Fast, 1.0, 0.5), 2),
+            &tone(3000.0),
+        );
+        let difference = left.iter().zip(&right).fold(0.0, |peak, (left, right)| {
+            f32::max(peak, (left - right).abs())
+        });
+        assert!(difference > 0.1, "{difference}");
+    }
+
+    #[test]
+    fn processing_does_not_allocate() {
+        let mut plugin = test_plugin(params(Speed::Fast, 1.0, 1.0), 2);
+        let mut left = tone(440.0);
+        let mut right = left.clone();
+        assert_no_alloc(|| {
+            for (left, right) in left
+                .chunks_mut(BLOCK_SIZE)
+                .zip(right.chunks_mut(BLOCK_SIZE))
+            {
+                plugin.process_channels(&mut [left, right]);
+            }
+        });
+    }
+}
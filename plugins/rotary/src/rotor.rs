@@ -0,0 +1,107 @@
+//! The cabinet's two rotors. Each one is heard through a delay line whose length follows the
+//! rotor's distance to the microphone, which gives the Doppler vibrato, and a gain that follows
+//! which way it's facing, which gives the tremolo.
+
+use dsp_core::delay::DelayLine;
+use dsp_core::utils::{OnePoleSmoother, Smoother};
+
+/// The physical differences between the horn and the drum.
+#[derive(Debug, Clone, Copy)]
+pub struct RotorKind {
+    /// Rotation speeds in Hz.
+    pub slow: f32,
+    pub fast: f32,
+    /// How much longer than the horn this rotor takes to change speed.
+    pub inertia: f32,
+    /// How far the delay swings at full Doppler, in seconds. The horn's mouth travels further
+    /// than the drum's baffle.
+    pub excursion: f32,
+    /// How much quieter this rotor gets facing away from the microphone at full tremolo.
+    pub directivity: f32,
+    /// The horn spins counterclockwise and the drum clockwise.
+    pub direction: f32,
+}
+
+pub const HORN: RotorKind = RotorKind {
+    slow: 0.8,
+    fast: 6.7,
+    inertia: 1.0,
+    excursion: 0.0005,
+    directivity: 1.0,
+    direction: 1.0,
+};
+
+pub const DRUM: RotorKind = RotorKind {
+    slow: 0.67,
+    fast: 5.7,
+    inertia: 4.0,
+    excursion: 0.0003,
+    directivity: 0.5,
+    direction: -1.0,
+};
+
+pub struct Rotor {
+    kind: RotorKind,
+    sample_rate: f32,
+    delay: DelayLine,
+    /// The rotation speed in Hz, gliding to the selected speed.
+    speed: OnePoleSmoother,
+    /// The rotor's angle in turns, from 0 to 1.
+    phase: f32,
+}
+
+impl Rotor {
+    /// Allocates. Starts at rest, facing the microphone.
+    pub fn new(kind: RotorKind, sample_rate: f32) -> Self {
+        let max_delay = DelayLine::<f32>::samples_for(sample_rate, kind.excursion * 2.0) + 2;
+        Self {
+            kind,
+            sample_rate,
+            delay: DelayLine::new(max_delay),
+            speed: OnePoleSmoother::new(sample_rate, kind.inertia),
+            phase: 0.0,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.delay.reset();
+    }
+
+    /// Glide to `speed` in Hz, with `ramp` as the horn's time constant in seconds.
+    pub fn set_speed(&mut self, speed: f32, ramp: f32) {
+        self.speed.set_time(ramp * self.kind.inertia);
+        self.speed.set_target(speed);
+    }
+
+    /// Jump to `speed`, e.g. when the plugin starts.
+    pub fn reset_speed(&mut self, speed: f32) {
+        self.speed.reset(speed);
+    }
+
+    /// The current rotation speed in Hz.
+    #[cfg(test)]
+    pub fn speed(&self) -> f32 {
+        self.speed.current()
+    }
+
+    /// What a microphone at `mic_angle` turns around the cabinet hears of the rotor now.
+    /// `doppler` and `tremolo` go from 0 to 1.
+    #[inline]
+    pub fn listen(&self, mic_angle: f32, doppler: f32, tremolo: f32) -> f32 {
+        let angle = std::f32::consts::TAU * (self.kind.direction * self.phase - mic_angle);
+        // 0 facing the microphone, 1 facing away
+        let away = 0.5 - 0.5 * angle.cos();
+        let excursion = self.kind.excursion * self.sample_rate * doppler;
+        let gain = 1.0 - tremolo * self.kind.directivity * away;
+        self.delay.read(1.0 + 2.0 * excursion * away) * gain
+    }
+
+    /// Feed the rotor its band's next sample and turn it by one sample. Call this after every
+    /// microphone has listened.
+    #[inline]
+    pub fn advance(&mut self, input: f32) {
+        self.delay.write(input);
+        self.phase += self.speed.next() / self.sample_rate;
+        self.phase -= self.phase.floor();
+    }
+}
@@ -0,0 +1,37 @@
+use crate::SaturatorParams;
+use nih_plug::prelude::*;
+use nih_plug_egui::create_egui_editor;
+use std::sync::Arc;
+use ui_widgets::{param_combo, param_toggle, EditorParams, EditorShell, ParamKnob};
+
+pub(crate) fn default_params() -> EditorParams {
+    EditorParams::new((320, 240))
+}
+
+pub(crate) fn create(params: Arc<SaturatorParams>) -> Option<Box<dyn Editor>> {
+    create_egui_editor(
+        params.editor.state.clone(),
+        (),
+        |_, _| {},
+        move |egui_ctx, setter, _state| {
+            EditorShell::new(&params.editor)
+                .with_bypass(&params.bypass)
+                .show(egui_ctx, setter, |ui| {
+                    ui.horizontal(|ui| {
+                        param_combo(ui, &params.model, setter);
+                        param_combo(ui, &params.oversampling, setter).on_hover_text(
+                            "Higher factors alias less but add latency and CPU load",
+                        );
+                    });
+                    ui.horizontal(|ui| {
+                        ui.add(ParamKnob::for_param(&params.drive, setter));
+                        ui.add(ParamKnob::for_param(&params.output, setter));
+                        ui.add(ParamKnob::for_param(&params.mix, setter));
+                    });
+                    param_toggle(ui, &params.auto_gain, setter).on_hover_text(
+                        "Keep the loudness constant as the drive changes, for level matched A/B",
+                    );
+                });
+        },
+    )
+}
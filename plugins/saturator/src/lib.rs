@@ -0,0 +1,471 @@
+use dsp_core::guard;
+use dsp_core::mix::MixStage;
+use dsp_core::oversampling::Oversampler;
+use model::{Model, Saturation};
+use nih_plug::prelude::*;
+use plugin_scaffold::layouts;
+use std::sync::Arc;
+use ui_widgets::EditorParams;
+
+mod editor;
+mod model;
+
+/// The oversamplers are allocated for the highest factor so switching never allocates.
+const MAX_OVERSAMPLING: usize = 8;
+
+/// Tape, tube, and transformer style saturation, oversampled to keep the added harmonics from
+/// aliasing.
+struct Saturator {
+    params: Arc<SaturatorParams>,
+    sample_rate: f32,
+    /// One per channel.
+    oversamplers: Vec<Oversampler>,
+    saturation: Vec<Saturation>,
+    /// The model and oversampling factor `saturation` was set up for.
+    model: Model,
+    factor: usize,
+    /// Follows the auto gain for the current drive with the same smoothing as the drive.
+    compensation: Smoother<f32>,
+    /// Delays the dry signal for parallel saturation by the oversampling latency.
+    mix: MixStage,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+pub enum Oversampling {
+    #[name = "Off"]
+    Off,
+    #[name = "2x"]
+    X2,
+    #[name = "4x"]
+    X4,
+    #[name = "8x"]
+    X8,
+}
+
+impl Oversampling {
+    pub fn factor(self) -> usize {
+        match self {
+            Oversampling::Off => 1,
+            Oversampling::X2 => 2,
+            Oversampling::X4 => 4,
+            Oversampling::X8 => 8,
+        }
+    }
+}
+
+#[derive(Params)]
+struct SaturatorParams {
+    #[nested]
+    editor: EditorParams,
+
+    /// The host's bypass switch.
+    #[id = "bypass"]
+    pub bypass: BoolParam,
+
+    #[id = "model"]
+    pub model: EnumParam<Model>,
+
+    #[id = "drive"]
+    pub drive: FloatParam,
+
+    /// Changes the latency, so it can't be automated.
+    #[id = "oversampling"]
+    pub oversampling: EnumParam<Oversampling>,
+
+    /// Turn the output down as the drive goes up, so only the character changes and not the
+    /// loudness.
+    #[id = "auto_gain"]
+    pub auto_gain: BoolParam,
+
+    #[id = "output"]
+    pub output: FloatParam,
+
+    #[id = "mix"]
+    pub mix: FloatParam,
+}
+
+impl Default for Saturator {
+    fn default() -> Self {
+        Self {
+            params: Arc::new(SaturatorParams::default()),
+            sample_rate: 44100.0,
+            oversamplers: Vec::new(),
+            saturation: Vec::new(),
+            model: Model::Tape,
+            factor: 1,
+            compensation: Smoother::new(SmoothingStyle::Logarithmic(50.0)),
+            mix: MixStage::new(44100.0, 0, 0, 0),
+        }
+    }
+}
+
+impl Default for SaturatorParams {
+    fn default() -> Self {
+        Self {
+            editor: editor::default_params(),
+
+            bypass: BoolParam::new("Bypass", false).make_bypass(),
+
+            model: EnumParam::new("Model", Model::Tape),
+
+            drive: FloatParam::new(
+                "Drive",
+                util::db_to_gain(12.0),
+                FloatRange::Skewed {
+                    min: util::db_to_gain(0.0),
+                    max: util::db_to_gain(36.0),
+                    factor: FloatRange::gain_skew_factor(0.0, 36.0),
+                },
+            )
+            .with_smoother(SmoothingStyle::Logarithmic(50.0))
+            .with_unit(" dB")
+            .with_value_to_string(formatters::v2s_f32_gain_to_db(1))
+            .with_string_to_value(formatters::s2v_f32_gain_to_db()),
+
+            oversampling: EnumParam::new("Oversampling", Oversampling::X4).non_automatable(),
+
+            auto_gain: BoolParam::new("Auto Gain", true),
+
+            output: FloatParam::new(
+                "Output",
+                util::db_to_gain(0.0),
+                FloatRange::Skewed {
+                    min: util::db_to_gain(-24.0),
+                    max: util::db_to_gain(12.0),
+                    factor: FloatRange::gain_skew_factor(-24.0, 12.0),
+                },
+            )
+            .with_smoother(SmoothingStyle::Logarithmic(50.0))
+            .with_unit(" dB")
+            .with_value_to_string(formatters::v2s_f32_gain_to_db(2))
+            .with_string_to_value(formatters::s2v_f32_gain_to_db()),
+
+            mix: FloatParam::new("Mix", 1.0, FloatRange::Linear { min: 0.0, max: 1.0 })
+                .with_value_to_string(formatters::v2s_f32_percentage(0))
+                .with_string_to_value(formatters::s2v_f32_percentage())
+                .with_unit(" %"),
+        }
+    }
+}
+
+impl Plugin for Saturator {
+    const NAME: &'static str = "Saturator";
+    const VENDOR: &'static str = "Your Studio";
+    const URL: &'static str = env!("CARGO_PKG_HOMEPAGE");
+    const EMAIL: &'static str = "contact@yourstudio.com";
+    const VERSION: &'static str = env!("CARGO_PKG_VERSION");
+
+    const AUDIO_IO_LAYOUTS: &'static [AudioIOLayout] = &[layouts::STEREO, layouts::MONO];
+
+    type SysExMessage = ();
+    type BackgroundTask = ();
+
+    fn params(&self) -> Arc<dyn Params> {
+        self.params.clone()
+    }
+
+    fn editor(&mut self, _async_executor: AsyncExecutor<Self>) -> Option<Box<dyn Editor>> {
+        editor::create(self.params.clone())
+    }
+
+    fn initialize(
+        &mut self,
+        audio_io_layout: &AudioIOLayout,
+        buffer_config: &BufferConfig,
+        context: &mut impl InitContext<Self>,
+    ) -> bool {
+        let num_channels = audio_io_layout
+            .main_output_channels
+            .map_or(0, |channels| channels.get() as usize);
+        self.sample_rate = buffer_config.sample_rate;
+
+        self.oversamplers = vec![Oversampler::new(MAX_OVERSAMPLING); num_channels];
+        self.saturation = vec![Saturation::new(self.model, self.sample_rate); num_channels];
+        // New oversamplers start out at the highest factor, which has the most latency
+        self.mix = MixStage::new(
+            self.sample_rate,
+            num_channels,
+            buffer_config.max_buffer_size as usize,
+            self.oversamplers
+                .first()
+                .map_or(0, |oversampler| oversampler.latency()),
+        );
+        // Force the model and factor to be set up from the parameters
+        self.factor = 0;
+        let latency = self.update_setup();
+        self.compensation.reset(self.target_compensation());
+        self.mix.set_mix(self.target_mix());
+        self.mix.reset();
+
+        context.set_latency_samples(latency as u32);
+        true
+    }
+
+    fn reset(&mut self) {
+        for oversampler in &mut self.oversamplers {
+            oversampler.reset();
+        }
+        self.factor = 0;
+        self.update_setup();
+        self.compensation.reset(self.target_compensation());
+        self.mix.reset();
+    }
+
+    fn process(
+        &mut self,
+        buffer: &mut Buffer,
+        _aux: &mut AuxiliaryBuffers,
+        context: &mut impl ProcessContext<Self>,
+    ) -> ProcessStatus {
+        let factor = self.factor;
+        let latency = self.update_setup();
+        if self.factor != factor {
+            context.set_latency_samples(latency as u32);
+        }
+        self.process_channels(buffer.as_slice());
+
+        ProcessStatus::Normal
+    }
+}
+
+impl Saturator {
+    /// Rebuild the per-channel saturation when the model or oversampling factor changed, and
+    /// retarget the auto gain. Returns the latency. Doesn't allocate.
+    fn update_setup(&mut self) -> usize {
+        let model = self.params.model.value();
+        let factor = self.params.oversampling.value().factor();
+        if model != self.model || factor != self.factor {
+            for (oversampler, saturation) in self.oversamplers.iter_mut().zip(&mut self.saturation)
+            {
+                oversampler.set_factor(factor);
+                *saturation = Saturation::new(model, self.sample_rate * factor as f32);
+            }
+            self.model = model;
+            self.factor = factor;
+        }
+
+        let latency = self
+            .oversamplers
+            .first()
+            .map_or(0, |oversampler| oversampler.latency());
+        self.mix.set_latency(latency);
+
+        self.compensation
+            .set_target(self.sample_rate, self.target_compensation());
+        latency
+    }
+
+    /// The auto gain for the drive the parameter is heading to.
+    fn target_compensation(&self) -> f32 {
+        if !self.params.auto_gain.value() {
+            return 1.0;
+        }
+
+        let mut saturation = Saturation::new(self.model, self.sample_rate);
+        saturation.set_drive(self.params.drive.value());
+        saturation.level_compensation()
+    }
+
+    fn process_channels(&mut self, channels: &mut [&mut [f32]]) {
+        self.mix.set_mix(self.target_mix());
+        self.mix.capture_dry(channels);
+
+        let num_samples = channels.first().map_or(0, |channel| channel.len());
+        for i in 0..num_samples {
+            let drive = self.params.drive.smoothed.next();
+            let gain = self.compensation.next() * self.params.output.smoothed.next();
+
+            for ((channel, oversampler), saturation) in channels
+                .iter_mut()
+                .zip(&mut self.oversamplers)
+                .zip(&mut self.saturation)
+            {
+                saturation.set_drive(drive);
+                channel[i] =
+                    oversampler.process(channel[i], |sample| saturation.process(sample)) * gain;
+            }
+        }
+
+        for channel in channels.iter() {
+            guard::check_block("Saturator", channel);
+        }
+        self.mix.mix_into(channels);
+    }
+
+    /// Bypassing fades to the dry signal through the mix stage, which keeps delaying it by the
+    /// latency the host is compensating for.
+    fn target_mix(&self) -> f32 {
+        if self.params.bypass.value() {
+            0.0
+        } else {
+            self.params.mix.value()
+        }
+    }
+}
+
+impl ClapPlugin for Saturator {
+    const CLAP_ID: &'static str = "com.yourstudio.saturator";
+    const CLAP_DESCRIPTION: Option<&'static str> =
+        Some("Oversampled tape, tube, and transformer saturation with automatic gain matching");
+    const CLAP_MANUAL_URL: Option<&'static str> = Some(Self::URL);
+    const CLAP_SUPPORT_URL: Option<&'static str> = None;
+    const CLAP_FEATURES: &'static [ClapFeature] = &[
+        ClapFeature::AudioEffect,
+        ClapFeature::Distortion,
+        ClapFeature::Stereo,
+        ClapFeature::Mono,
+    ];
+}
+
+impl Vst3Plugin for Saturator {
+    const VST3_CLASS_ID: [u8; 16] = *b"Saturator0000000";
+    const VST3_SUBCATEGORIES: &'static [Vst3SubCategory] =
+        &[Vst3SubCategory::Fx, Vst3SubCategory::Distortion];
+}
+
+nih_export_clap!(Saturator);
+nih_export_vst3!(Saturator);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use plugin_scaffold::test_util::assert_no_alloc;
+
+    plugin_scaffold::install_alloc_disabler!();
+
+    const SAMPLE_RATE: f32 = 48000.0;
+    const BLOCK_SIZE: usize = 512;
+
+    fn test_plugin(params: SaturatorParams) -> Saturator {
+        params.drive.smoothed.reset(params.drive.value());
+        params.output.smoothed.reset(params.output.value());
+
+        let mut plugin = Saturator {
+            params: Arc::new(params),
+            sample_rate: SAMPLE_RATE,
+            oversamplers: vec![Oversampler::new(MAX_OVERSAMPLING)],
+            saturation: vec![Saturation::new(Model::Tape, SAMPLE_RATE)],
+            ..Saturator::default()
+        };
+        let max_latency = plugin.oversamplers[0].latency();
+        plugin.mix = MixStage::new(SAMPLE_RATE, 1, BLOCK_SIZE, max_latency);
+        plugin.factor = 0;
+        plugin.update_setup();
+        plugin.compensation.reset(plugin.target_compensation());
+        plugin.mix.set_mix(plugin.target_mix());
+        plugin.mix.reset();
+        plugin
+    }
+
+    fn with_drive(model: Model, drive_db: f32) -> SaturatorParams {
+        let defaults = SaturatorParams::default();
+        SaturatorParams {
+            model: EnumParam::new("Model", model),
+            drive: FloatParam::new(
+                "Drive",
+                util::db_to_gain(drive_db),
+                FloatRange::Linear {
+                    min: 1.0,
+                    max: 100.0,
+                },
+            ),
+            ..defaults
+        }
+    }
+
+    fn sine(frequency: f32, amplitude: f32) -> Vec<f32> {
+        (0..SAMPLE_RATE as usize)
+            .map(|i| (std::f32::consts::TAU * frequency * i as f32 / SAMPLE_RATE).sin() * amplitude)
+            .collect()
+    }
+
+    fn process(plugin: &mut Saturator, input: &[f32]) -> Vec<f32> {
+        let mut output = input.to_vec();
+        for block in output.chunks_mut(BLOCK_SIZE) {
+            plugin.process_channels(&mut [block]);
+        }
+        output
+    }
+
+    /// The RMS level of the second half, after the filters have settled.
+    fn rms_db(samples: &[f32]) -> f32 {
+        let settled = &samples[samples.len() / 2..];
+        let mean_square = settled.iter().map(|sample| sample * sample).sum::<f32>();
+        util::gain_to_db((mean_square / settled.len() as f32).sqrt())
+    }
+
+    #[test]
+    fn auto_gain_matches_the_input_level() {
+        // Between the tape and transformer emphasis shelves, where the level matching is exact
+        let input = sine(
+            500.0,
+            util::db_to_gain(model::REFERENCE_LEVEL_DB) * 2f32.sqrt(),
+        );
+        for model in [Model::Tape, Model::Tube, Model::Transformer] {
+            for drive_db in [6.0, 18.0, 30.0] {
+                let output = process(&mut test_plugin(with_drive(model, drive_db)), &input);
+                let difference = rms_db(&output) - rms_db(&input);
+                assert!(
+                    difference.abs() < 1.0,
+                    "{model:?} at {drive_db} dB: {difference} dB"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn quiet_signals_pass_through_delayed_by_the_latency() {
+        let mut plugin = test_plugin(SaturatorParams {
+            oversampling: EnumParam::new("Oversampling", Oversampling::X2),
+            ..with_drive(Model::Transformer, 0.0)
+        });
+        let latency = plugin.update_setup();
+        let input = sine(1000.0, 0.01);
+        let output = process(&mut plugin, &input);
+
+        for (input, output) in input.iter().zip(&output[latency..]).skip(BLOCK_SIZE) {
+            assert!((input - output).abs() < 1e-4, "{input} != {output}");
+        }
+    }
+
+    #[test]
+    fn oversampling_factor_sets_the_latency() {
+        let mut latencies = Vec::new();
+        for oversampling in [
+            Oversampling::Off,
+            Oversampling::X2,
+            Oversampling::X4,
+            Oversampling::X8,
+        ] {
+            let mut plugin = test_plugin(SaturatorParams {
+                oversampling: EnumParam::new("Oversampling", oversampling),
+                ..SaturatorParams::default()
+            });
+            let latency = plugin.update_setup();
+            assert_eq!(plugin.mix.latency(), latency);
+            latencies.push(latency);
+        }
+
+        assert_eq!(latencies[0], 0);
+        assert!(
+            latencies.windows(2).all(|pair| pair[0] < pair[1]),
+            "{latencies:?}"
+        );
+    }
+
+    #[test]
+    fn processing_does_not_allocate() {
+        let mut plugin = test_plugin(with_drive(Model::Tube, 12.0));
+        let mut samples = sine(440.0, 0.5);
+        assert_no_alloc(|| {
+            for (idx, block) in samples.chunks_mut(BLOCK_SIZE).enumerate() {
+                // As if the oversampling was changed halfway through
+                if idx == 40 {
+                    plugin.factor = 0;
+                }
+                plugin.update_setup();
+                plugin.process_channels(&mut [block]);
+            }
+        });
+    }
+}
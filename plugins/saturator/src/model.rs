@@ -0,0 +1,158 @@
+//! The saturation models. Each one is a waveshaper with a first-order emphasis filter in front
+//! of it and the exact inverse of that filter behind it, so the emphasis only changes which
+//! frequencies saturate first and not the tone of clean signals.
+
+use dsp_core::waveshaper::{Curve, Waveshaper};
+use nih_plug::prelude::Enum;
+
+/// The level auto gain is calibrated at. Signals around this level keep their loudness as the
+/// drive changes.
+pub const REFERENCE_LEVEL_DB: f32 = -18.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+pub enum Model {
+    /// Soft and symmetric, with the highs pushed into the curve first so transients round off.
+    Tape,
+    /// Asymmetric, for warm even harmonics.
+    Tube,
+    /// A harder knee, with the lows pushed into the curve first like a saturating core.
+    Transformer,
+}
+
+/// Which side of the emphasis frequency is boosted by 6 dB before the curve.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Emphasis {
+    Flat,
+    Highs(f32),
+    Lows(f32),
+}
+
+impl Model {
+    fn curve(self) -> Curve {
+        match self {
+            Model::Tape => Curve::Tanh,
+            Model::Tube => Curve::Algebraic,
+            Model::Transformer => Curve::Cubic,
+        }
+    }
+
+    fn bias(self) -> f32 {
+        match self {
+            Model::Tube => 0.3,
+            Model::Tape | Model::Transformer => 0.0,
+        }
+    }
+
+    fn emphasis(self) -> Emphasis {
+        match self {
+            Model::Tape => Emphasis::Highs(3000.0),
+            Model::Tube => Emphasis::Flat,
+            Model::Transformer => Emphasis::Lows(120.0),
+        }
+    }
+}
+
+/// A first-order IIR filter, `(b0 + b1 z^-1) / (1 + a1 z^-1)`.
+#[derive(Debug, Clone)]
+struct FirstOrder {
+    b0: f32,
+    b1: f32,
+    a1: f32,
+    previous_input: f32,
+    previous_output: f32,
+}
+
+impl FirstOrder {
+    /// Boosts one side of the emphasis frequency by 6 dB by adding a one-pole low or high pass
+    /// to the input.
+    fn new(emphasis: Emphasis, sample_rate: f32) -> Self {
+        let coefficient =
+            |frequency: f32| 1.0 - (-std::f32::consts::TAU * frequency / sample_rate).exp();
+        let (b0, b1, a1) = match emphasis {
+            Emphasis::Flat => (1.0, 0.0, 0.0),
+            // 1 + lowpass
+            Emphasis::Lows(frequency) => {
+                let a = coefficient(frequency);
+                (1.0 + a, a - 1.0, a - 1.0)
+            }
+            // 2 - lowpass, or 1 + highpass
+            Emphasis::Highs(frequency) => {
+                let a = coefficient(frequency);
+                (2.0 - a, 2.0 * (a - 1.0), a - 1.0)
+            }
+        };
+
+        Self {
+            b0,
+            b1,
+            a1,
+            previous_input: 0.0,
+            previous_output: 0.0,
+        }
+    }
+
+    /// The exact inverse, which is stable since the zero of every emphasis filter lies inside
+    /// the unit circle.
+    fn inverse(&self) -> Self {
+        Self {
+            b0: 1.0 / self.b0,
+            b1: self.a1 / self.b0,
+            a1: self.b1 / self.b0,
+            previous_input: 0.0,
+            previous_output: 0.0,
+        }
+    }
+
+    #[inline]
+    fn process(&mut self, input: f32) -> f32 {
+        let output =
+            self.b0 * input + self.b1 * self.previous_input - self.a1 * self.previous_output;
+        self.previous_input = input;
+        self.previous_output = output;
+        output
+    }
+}
+
+/// One channel of a model, running at the oversampled rate.
+#[derive(Debug, Clone)]
+pub struct Saturation {
+    shaper: Waveshaper,
+    pre_emphasis: FirstOrder,
+    de_emphasis: FirstOrder,
+}
+
+impl Saturation {
+    /// Doesn't allocate, so models can be switched on the audio thread.
+    pub fn new(model: Model, sample_rate: f32) -> Self {
+        let mut shaper = Waveshaper::new(sample_rate);
+        shaper.set_curve(model.curve());
+        shaper.set_bias(model.bias());
+        let pre_emphasis = FirstOrder::new(model.emphasis(), sample_rate);
+        let de_emphasis = pre_emphasis.inverse();
+
+        Self {
+            shaper,
+            pre_emphasis,
+            de_emphasis,
+        }
+    }
+
+    /// Set the linear gain into the curve.
+    pub fn set_drive(&mut self, drive: f32) {
+        self.shaper.set_drive(drive);
+    }
+
+    /// The gain that keeps a signal at the [`REFERENCE_LEVEL_DB`] at the same loudness with the
+    /// current drive. Frequencies the emphasis boosts saturate harder and come out a little
+    /// quieter.
+    pub fn level_compensation(&self) -> f32 {
+        let amplitude = nih_plug::util::db_to_gain(REFERENCE_LEVEL_DB) * std::f32::consts::SQRT_2;
+        self.shaper.level_compensation(amplitude)
+    }
+
+    #[inline]
+    pub fn process(&mut self, input: f32) -> f32 {
+        let emphasized = self.pre_emphasis.process(input);
+        self.de_emphasis.process(self.shaper.process(emphasized))
+    }
+}
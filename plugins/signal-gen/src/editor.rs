@@ -0,0 +1,51 @@
+use crate::{Signal, SignalGenParams};
+use nih_plug::prelude::*;
+use nih_plug_egui::create_egui_editor;
+use std::sync::Arc;
+use ui_widgets::{param_combo, EditorParams, EditorShell, ParamKnob};
+
+pub(crate) fn default_params() -> EditorParams {
+    EditorParams::new((360, 240))
+}
+
+pub(crate) fn create(params: Arc<SignalGenParams>) -> Option<Box<dyn Editor>> {
+    create_egui_editor(
+        params.editor.state.clone(),
+        (),
+        |_, _| {},
+        move |egui_ctx, setter, _state| {
+            EditorShell::new(&params.editor)
+                .with_bypass(&params.bypass)
+                .show(egui_ctx, setter, |ui| {
+                    ui.horizontal(|ui| {
+                        param_combo(ui, &params.signal, setter);
+                        param_combo(ui, &params.channels, setter)
+                            .on_hover_text("Send the signal to one channel to check the routing");
+                    });
+
+                    // Only the controls for the current signal
+                    ui.horizontal(|ui| {
+                        ui.add(ParamKnob::for_param(&params.level, setter))
+                            .on_hover_text(
+                                "The peak level of tones and impulses, noise has the RMS level \
+                                 of a sine at this level",
+                            );
+                        match params.signal.value() {
+                            Signal::Sine | Signal::Square => {
+                                ui.add(ParamKnob::for_param(&params.frequency, setter));
+                            }
+                            Signal::Sweep => {
+                                ui.add(ParamKnob::for_param(&params.sweep_start, setter));
+                                ui.add(ParamKnob::for_param(&params.sweep_end, setter));
+                                ui.add(ParamKnob::for_param(&params.sweep_time, setter));
+                            }
+                            Signal::Impulses => {
+                                ui.add(ParamKnob::for_param(&params.impulse_interval, setter));
+                            }
+                            Signal::WhiteNoise | Signal::PinkNoise => {}
+                        }
+                    });
+                });
+        },
+    )
+}
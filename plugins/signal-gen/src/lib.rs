@@ -0,0 +1,495 @@
+use dsp_core::generators::{ImpulseTrain, LogSweep, PinkNoise, SquareOsc, WhiteNoise};
+use dsp_core::guard;
+use dsp_core::oscillators::SineOsc;
+use dsp_core::utils::{LinearSmoother, Smoother};
+use nih_plug::prelude::*;
+use plugin_scaffold::formatters::{
+    s2v_f32_ms_then_s, s2v_f32_s_then_ms, v2s_f32_ms_then_s, v2s_f32_s_then_ms,
+};
+use plugin_scaffold::layouts;
+use std::sync::Arc;
+use ui_widgets::EditorParams;
+
+mod editor;
+
+/// How long the output fades out and back in when the signal is switched or bypassed, in
+/// seconds.
+const FADE_TIME: f32 = 0.005;
+
+/// Generates test signals at calibrated levels for measuring other plugins and checking the
+/// host's routing.
+struct SignalGen {
+    params: Arc<SignalGenParams>,
+    /// The signal being generated, which lags the parameter while the output fades out.
+    signal: Signal,
+    sine: SineOsc,
+    sweep: LogSweep,
+    white: WhiteNoise,
+    pink: PinkNoise,
+    impulses: ImpulseTrain,
+    square: SquareOsc,
+    sample_rate: f32,
+    /// Fades the output out when switching signals or bypassing.
+    fade: LinearSmoother,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+pub enum Signal {
+    Sine,
+    #[name = "Sine Sweep"]
+    Sweep,
+    #[name = "White Noise"]
+    WhiteNoise,
+    #[name = "Pink Noise"]
+    PinkNoise,
+    Impulses,
+    Square,
+}
+
+impl Signal {
+    /// The gain that puts the signal at the level parameter. Tones and impulses peak at the
+    /// level, while noise has the same RMS level as a sine at that level.
+    fn level_gain(self) -> f32 {
+        match self {
+            Signal::WhiteNoise | Signal::PinkNoise => std::f32::consts::FRAC_1_SQRT_2,
+            _ => 1.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+pub enum OutputChannels {
+    Both,
+    Left,
+    Right,
+}
+
+#[derive(Params)]
+struct SignalGenParams {
+    #[nested]
+    editor: EditorParams,
+
+    /// The host's bypass switch, fades to silence.
+    #[id = "bypass"]
+    pub bypass: BoolParam,
+
+    #[id = "signal"]
+    pub signal: EnumParam<Signal>,
+
+    /// The peak level of tones and impulses, and the RMS level of a sine at this level for
+    /// noise.
+    #[id = "level"]
+    pub level: FloatParam,
+
+    /// The frequency of the sine and the square wave.
+    #[id = "frequency"]
+    pub frequency: FloatParam,
+
+    #[id = "sweep_start"]
+    pub sweep_start: FloatParam,
+
+    #[id = "sweep_end"]
+    pub sweep_end: FloatParam,
+
+    /// How long a sweep from the start to the end frequency takes.
+    #[id = "sweep_time"]
+    pub sweep_time: FloatParam,
+
+    /// The time between impulses.
+    #[id = "impulse_interval"]
+    pub impulse_interval: FloatParam,
+
+    /// Which channels get the signal, the others stay silent.
+    #[id = "channels"]
+    pub channels: EnumParam<OutputChannels>,
+}
+
+impl Default for SignalGen {
+    fn default() -> Self {
+        let params = Arc::new(SignalGenParams::default());
+        let mut plugin = Self {
+            signal: params.signal.value(),
+            sine: SineOsc::new(44100.0),
+            sweep: LogSweep::new(44100.0, 20.0, 20000.0, 5.0),
+            white: WhiteNoise::new(1),
+            pink: PinkNoise::new(1),
+            impulses: ImpulseTrain::new(1),
+            square: SquareOsc::new(44100.0),
+            sample_rate: 44100.0,
+            fade: LinearSmoother::new(0),
+            params,
+        };
+        plugin.set_sample_rate(44100.0);
+        plugin
+    }
+}
+
+impl Default for SignalGenParams {
+    fn default() -> Self {
+        let frequency = |name, default| {
+            FloatParam::new(
+                name,
+                default,
+                FloatRange::Skewed {
+                    min: 20.0,
+                    max: 20000.0,
+                    factor: FloatRange::skew_factor(-2.0),
+                },
+            )
+            .with_value_to_string(formatters::v2s_f32_hz_then_khz(1))
+            .with_string_to_value(formatters::s2v_f32_hz_then_khz())
+        };
+
+        Self {
+            editor: editor::default_params(),
+
+            bypass: BoolParam::new("Bypass", false).make_bypass(),
+
+            signal: EnumParam::new("Signal", Signal::Sine),
+
+            level: FloatParam::new(
+                "Level",
+                util::db_to_gain(-18.0),
+                FloatRange::Skewed {
+                    min: util::db_to_gain(-60.0),
+                    max: util::db_to_gain(0.0),
+                    factor: FloatRange::gain_skew_factor(-60.0, 0.0),
+                },
+            )
+            .with_smoother(SmoothingStyle::Logarithmic(50.0))
+            .with_unit(" dBFS")
+            .with_value_to_string(formatters::v2s_f32_gain_to_db(1))
+            .with_string_to_value(formatters::s2v_f32_gain_to_db()),
+
+            frequency: frequency("Frequency", 1000.0)
+                .with_smoother(SmoothingStyle::Logarithmic(20.0)),
+            sweep_start: frequency("Sweep Start", 20.0),
+            sweep_end: frequency("Sweep End", 20000.0),
+            sweep_time: FloatParam::new(
+                "Sweep Time",
+                5.0,
+                FloatRange::Skewed {
+                    min: 0.1,
+                    max: 30.0,
+                    factor: FloatRange::skew_factor(-1.0),
+                },
+            )
+            .with_value_to_string(v2s_f32_s_then_ms(0))
+            .with_string_to_value(s2v_f32_s_then_ms()),
+
+            impulse_interval: FloatParam::new(
+                "Impulse Interval",
+                500.0,
+                FloatRange::Skewed {
+                    min: 10.0,
+                    max: 5000.0,
+                    factor: FloatRange::skew_factor(-1.0),
+                },
+            )
+            .with_value_to_string(v2s_f32_ms_then_s(0))
+            .with_string_to_value(s2v_f32_ms_then_s()),
+
+            channels: EnumParam::new("Channels", OutputChannels::Both),
+        }
+    }
+}
+
+impl Plugin for SignalGen {
+    const NAME: &'static str = "Signal Generator";
+    const VENDOR: &'static str = "Your Studio";
+    const URL: &'static str = env!("CARGO_PKG_HOMEPAGE");
+    const EMAIL: &'static str = "contact@yourstudio.com";
+    const VERSION: &'static str = env!("CARGO_PKG_VERSION");
+
+    const AUDIO_IO_LAYOUTS: &'static [AudioIOLayout] = layouts::INSTRUMENT_LAYOUTS;
+
+    type SysExMessage = ();
+    type BackgroundTask = ();
+
+    fn params(&self) -> Arc<dyn Params> {
+        self.params.clone()
+    }
+
+    fn editor(&mut self, _async_executor: AsyncExecutor<Self>) -> Option<Box<dyn Editor>> {
+        editor::create(self.params.clone())
+    }
+
+    fn initialize(
+        &mut self,
+        _audio_io_layout: &AudioIOLayout,
+        buffer_config: &BufferConfig,
+        _context: &mut impl InitContext<Self>,
+    ) -> bool {
+        self.set_sample_rate(buffer_config.sample_rate);
+        self.reset();
+        true
+    }
+
+    fn reset(&mut self) {
+        self.signal = self.params.signal.value();
+        self.reset_generators();
+        self.fade.reset(self.target_fade());
+    }
+
+    fn process(
+        &mut self,
+        buffer: &mut Buffer,
+        _aux: &mut AuxiliaryBuffers,
+        _context: &mut impl ProcessContext<Self>,
+    ) -> ProcessStatus {
+        self.process_channels(buffer.as_slice());
+
+        // The signal never ends
+        ProcessStatus::KeepAlive
+    }
+}
+
+impl SignalGen {
+    fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        self.sine.set_sample_rate(sample_rate);
+        self.sweep.set_sample_rate(sample_rate);
+        self.square.set_sample_rate(sample_rate);
+        self.fade
+            .set_ramp_length((FADE_TIME * sample_rate) as usize);
+    }
+
+    /// Start every signal from the beginning, so sweeps and impulses line up with the moment
+    /// the signal was switched on.
+    fn reset_generators(&mut self) {
+        self.sine.reset();
+        self.sweep.reset();
+        self.pink.reset();
+        self.impulses.reset();
+        self.square.reset();
+    }
+
+    fn process_channels(&mut self, channels: &mut [&mut [f32]]) {
+        self.sweep.set_range(
+            self.params.sweep_start.value(),
+            self.params.sweep_end.value(),
+        );
+        self.sweep.set_duration(self.params.sweep_time.value());
+        let interval = self.params.impulse_interval.value() / 1000.0 * self.sample_rate;
+        self.impulses.set_interval(interval as u32);
+        self.fade.set_target(self.target_fade());
+
+        let output_channels = self.params.channels.value();
+        let num_samples = channels.first().map_or(0, |channel| channel.len());
+        for i in 0..num_samples {
+            // Switch once the old signal has faded out
+            let signal = self.params.signal.value();
+            if signal != self.signal && self.fade.current() == 0.0 {
+                self.signal = signal;
+                self.reset_generators();
+                self.fade.set_target(self.target_fade());
+            }
+
+            let level = self.params.level.smoothed.next();
+            let frequency = self.params.frequency.smoothed.next();
+            let sample =
+                self.next_sample(frequency) * self.signal.level_gain() * level * self.fade.next();
+
+            for (channel_idx, channel) in channels.iter_mut().enumerate() {
+                let enabled = match output_channels {
+                    OutputChannels::Both => true,
+                    OutputChannels::Left => channel_idx == 0,
+                    OutputChannels::Right => channel_idx == 1,
+                };
+                channel[i] = if enabled { sample } else { 0.0 };
+            }
+        }
+
+        for channel in channels.iter() {
+            guard::check_block("SignalGen", channel);
+        }
+    }
+
+    #[inline]
+    fn next_sample(&mut self, frequency: f32) -> f32 {
+        match self.signal {
+            Signal::Sine => {
+                self.sine.set_frequency(frequency);
+                self.sine.next_sample()
+            }
+            Signal::Sweep => self.sweep.next_sample(),
+            Signal::WhiteNoise => self.white.next_sample(),
+            Signal::PinkNoise => self.pink.next_sample(),
+            Signal::Impulses => self.impulses.next_sample(),
+            Signal::Square => {
+                self.square.set_frequency(frequency);
+                self.square.next_sample()
+            }
+        }
+    }
+
+    /// Silent while bypassed, and while waiting to switch to another signal.
+    fn target_fade(&self) -> f32 {
+        if self.params.bypass.value() || self.params.signal.value() != self.signal {
+            0.0
+        } else {
+            1.0
+        }
+    }
+}
+
+impl ClapPlugin for SignalGen {
+    const CLAP_ID: &'static str = "com.yourstudio.signal-gen";
+    const CLAP_DESCRIPTION: Option<&'static str> =
+        Some("Sine sweeps, noise, impulses, and square waves at calibrated levels");
+    const CLAP_MANUAL_URL: Option<&'static str> = Some(Self::URL);
+    const CLAP_SUPPORT_URL: Option<&'static str> = None;
+    const CLAP_FEATURES: &'static [ClapFeature] = &[
+        ClapFeature::Instrument,
+        ClapFeature::Utility,
+        ClapFeature::Stereo,
+    ];
+}
+
+impl Vst3Plugin for SignalGen {
+    const VST3_CLASS_ID: [u8; 16] = *b"SignalGenerator0";
+    const VST3_SUBCATEGORIES: &'static [Vst3SubCategory] =
+        &[Vst3SubCategory::Instrument, Vst3SubCategory::Generator];
+}
+
+nih_export_clap!(SignalGen);
+nih_export_vst3!(SignalGen);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use plugin_scaffold::test_util::assert_no_alloc;
+
+    plugin_scaffold::install_alloc_disabler!();
+
+    const SAMPLE_RATE: f32 = 48000.0;
+    const BLOCK_SIZE: usize = 512;
+
+    fn test_plugin(params: SignalGenParams) -> SignalGen {
+        params.level.smoothed.reset(params.level.value());
+        params.frequency.smoothed.reset(params.frequency.value());
+
+        let mut plugin = SignalGen {
+            params: Arc::new(params),
+            ..SignalGen::default()
+        };
+        plugin.set_sample_rate(SAMPLE_RATE);
+        plugin.reset();
+        plugin
+    }
+
+    fn signal(signal: Signal) -> SignalGenParams {
+        SignalGenParams {
+            signal: EnumParam::new("Signal", signal),
+            ..SignalGenParams::default()
+        }
+    }
+
+    /// One second of output.
+    fn process(plugin: &mut SignalGen) -> (Vec<f32>, Vec<f32>) {
+        let mut left = vec![0.0; SAMPLE_RATE as usize];
+        let mut right = vec![0.0; SAMPLE_RATE as usize];
+        for (left, right) in left
+            .chunks_mut(BLOCK_SIZE)
+            .zip(right.chunks_mut(BLOCK_SIZE))
+        {
+            plugin.process_channels(&mut [left, right]);
+        }
+        (left, right)
+    }
+
+    fn peak_db(samples: &[f32]) -> f32 {
+        util::gain_to_db(
+            samples
+                .iter()
+                .fold(0.0, |peak, sample| f32::max(peak, sample.abs())),
+        )
+    }
+
+    fn rms_db(samples: &[f32]) -> f32 {
+        let mean_square = samples.iter().map(|sample| sample * sample).sum::<f32>();
+        util::gain_to_db((mean_square / samples.len() as f32).sqrt())
+    }
+
+    #[test]
+    fn tones_peak_at_the_level() {
+        for tone in [Signal::Sine, Signal::Sweep, Signal::Square] {
+            let (left, right) = process(&mut test_plugin(signal(tone)));
+            assert!((peak_db(&left) + 18.0).abs() < 0.01, "{tone:?}");
+            assert_eq!(left, right);
+        }
+
+        let (left, _) = process(&mut test_plugin(signal(Signal::Sine)));
+        assert!((rms_db(&left) + 21.01).abs() < 0.01);
+    }
+
+    #[test]
+    fn noise_has_the_rms_level_of_a_sine() {
+        for noise in [Signal::WhiteNoise, Signal::PinkNoise] {
+            let (left, _) = process(&mut test_plugin(signal(noise)));
+            let rms = rms_db(&left);
+            assert!((rms + 21.01).abs() < 0.25, "{noise:?} at {rms} dBFS");
+        }
+    }
+
+    #[test]
+    fn impulses_repeat_at_the_interval() {
+        let (left, _) = process(&mut test_plugin(signal(Signal::Impulses)));
+        let impulses: Vec<usize> = (0..left.len()).filter(|&i| left[i] != 0.0).collect();
+
+        assert_eq!(impulses, [0, 24000]);
+        assert!((peak_db(&left) + 18.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn only_the_chosen_channel_gets_the_signal() {
+        let mut plugin = test_plugin(SignalGenParams {
+            channels: EnumParam::new("Channels", OutputChannels::Right),
+            ..SignalGenParams::default()
+        });
+        let (left, right) = process(&mut plugin);
+
+        assert!(left.iter().all(|&sample| sample == 0.0));
+        assert!((peak_db(&right) + 18.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn switching_signals_fades_out_and_back_in() {
+        let mut plugin = test_plugin(signal(Signal::Sine));
+        process(&mut plugin);
+
+        plugin.params = Arc::new(signal(Signal::Impulses));
+        plugin
+            .params
+            .level
+            .smoothed
+            .reset(plugin.params.level.value());
+        let (left, _) = process(&mut plugin);
+
+        // The sine fades out over the fade time, then the impulses start from the beginning
+        let level = util::db_to_gain(-18.0);
+        let fade = (FADE_TIME * SAMPLE_RATE) as usize;
+        let peak = |samples: &[f32]| samples.iter().fold(0.0, |peak, s| f32::max(peak, s.abs()));
+        assert!(peak(&left[..24]) > level * 0.9);
+        assert!(peak(&left[fade - 24..fade]) < level * 0.11);
+        assert_eq!(plugin.signal, Signal::Impulses);
+        assert!(left[fade] > 0.0 && left[fade + 1] == 0.0);
+    }
+
+    #[test]
+    fn processing_does_not_allocate() {
+        for generator in [Signal::Sweep, Signal::PinkNoise, Signal::Impulses] {
+            let mut plugin = test_plugin(signal(generator));
+            let mut left = vec![0.0; SAMPLE_RATE as usize];
+            let mut right = vec![0.0; SAMPLE_RATE as usize];
+            assert_no_alloc(|| {
+                for (left, right) in left
+                    .chunks_mut(BLOCK_SIZE)
+                    .zip(right.chunks_mut(BLOCK_SIZE))
+                {
+                    plugin.process_channels(&mut [left, right]);
+                }
+            });
+        }
+    }
+}
@@ -0,0 +1,267 @@
+//! MIDI CC learn. Right clicking a knob in the editor arms it, and the next CC that arrives is
+//! mapped to that parameter. Mappings are saved with the plugin state.
+//!
+//! Plugins can't set their own parameters from the audio thread, so a mapped CC overrides the
+//! parameter's value inside the synth instead of moving the host's parameter. Once a mapped CC has
+//! been received it takes precedence over the parameter until the mapping is removed.
+
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+
+/// Stored in [`CcLearnState::last_cc`] while no CC has been received since the last reset.
+const NO_CC: u8 = u8::MAX;
+
+/// The parameters CCs can be mapped to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CcTarget {
+    Gain,
+    Attack,
+    Decay,
+    Sustain,
+    Release,
+}
+
+impl CcTarget {
+    pub const ALL: [CcTarget; 5] = [
+        CcTarget::Gain,
+        CcTarget::Attack,
+        CcTarget::Decay,
+        CcTarget::Sustain,
+        CcTarget::Release,
+    ];
+
+    pub fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// How the CC value is shaped before it's scaled to the mapping's range.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum CcCurve {
+    #[default]
+    Linear,
+    /// Slow start, fast end.
+    Exponential,
+    /// Fast start, slow end.
+    Logarithmic,
+}
+
+impl CcCurve {
+    pub const ALL: [CcCurve; 3] = [CcCurve::Linear, CcCurve::Exponential, CcCurve::Logarithmic];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            CcCurve::Linear => "Linear",
+            CcCurve::Exponential => "Exponential",
+            CcCurve::Logarithmic => "Logarithmic",
+        }
+    }
+
+    fn apply(self, value: f32) -> f32 {
+        match self {
+            CcCurve::Linear => value,
+            CcCurve::Exponential => value * value,
+            CcCurve::Logarithmic => value.sqrt(),
+        }
+    }
+}
+
+/// Maps a CC number to a parameter. `min` and `max` are normalized parameter values, `max` may be
+/// lower than `min` to invert the control.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct CcMapping {
+    pub cc: u8,
+    pub target: CcTarget,
+    pub min: f32,
+    pub max: f32,
+    pub curve: CcCurve,
+}
+
+impl CcMapping {
+    pub fn new(cc: u8, target: CcTarget) -> Self {
+        Self {
+            cc,
+            target,
+            min: 0.0,
+            max: 1.0,
+            curve: CcCurve::Linear,
+        }
+    }
+
+    /// The normalized parameter value for a CC value in `[0, 1]`.
+    pub fn normalized_value(&self, cc_value: f32) -> f32 {
+        let shaped = self.curve.apply(cc_value.clamp(0.0, 1.0));
+        (self.min + (self.max - self.min) * shaped).clamp(0.0, 1.0)
+    }
+}
+
+/// Learn state shared between the audio thread and the editor. The editor decides which
+/// parameter is being learned and writes the mapping, the audio thread only reports which CC it
+/// saw last so it never has to allocate.
+pub struct CcLearnState {
+    last_cc: AtomicU8,
+    /// Set by the editor after removing a mapping so the audio thread drops stale overrides.
+    pub overrides_changed: AtomicBool,
+}
+
+impl Default for CcLearnState {
+    fn default() -> Self {
+        Self {
+            last_cc: AtomicU8::new(NO_CC),
+            overrides_changed: AtomicBool::new(false),
+        }
+    }
+}
+
+impl CcLearnState {
+    pub fn report_cc(&self, cc: u8) {
+        self.last_cc.store(cc, Ordering::Relaxed);
+    }
+
+    /// The CC received since the last call, if any.
+    pub fn take_last_cc(&self) -> Option<u8> {
+        match self.last_cc.swap(NO_CC, Ordering::Relaxed) {
+            NO_CC => None,
+            cc => Some(cc),
+        }
+    }
+}
+
+/// Audio thread view of the CC overrides, as normalized values per [`CcTarget`].
+#[derive(Debug, Default, Clone)]
+pub struct CcOverrides {
+    values: [Option<f32>; CcTarget::ALL.len()],
+}
+
+impl CcOverrides {
+    pub fn get(&self, target: CcTarget) -> Option<f32> {
+        self.values[target.index()]
+    }
+
+    pub fn set(&mut self, target: CcTarget, normalized: f32) {
+        self.values[target.index()] = Some(normalized);
+    }
+
+    /// Drop the overrides for targets that no longer have a mapping.
+    pub fn retain_mapped(&mut self, mappings: &[CcMapping]) {
+        for target in CcTarget::ALL {
+            if !mappings.iter().any(|mapping| mapping.target == target) {
+                self.values[target.index()] = None;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// nih-plug hands CC values over divided by 127.
+    fn cc(value: u8) -> f32 {
+        value as f32 / 127.0
+    }
+
+    fn mapping(min: f32, max: f32, curve: CcCurve) -> CcMapping {
+        CcMapping {
+            min,
+            max,
+            curve,
+            ..CcMapping::new(1, CcTarget::Gain)
+        }
+    }
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!((actual - expected).abs() < 1e-6, "{actual} vs {expected}");
+    }
+
+    #[test]
+    fn the_cc_range_ends_map_to_min_and_max() {
+        for curve in CcCurve::ALL {
+            let mapping = mapping(0.2, 0.7, curve);
+            assert_close(mapping.normalized_value(cc(0)), 0.2);
+            assert_close(mapping.normalized_value(cc(127)), 0.7);
+        }
+    }
+
+    #[test]
+    fn inverted_ranges_turn_the_control_around() {
+        for curve in CcCurve::ALL {
+            let mapping = mapping(0.9, 0.1, curve);
+            assert_close(mapping.normalized_value(cc(0)), 0.9);
+            assert_close(mapping.normalized_value(cc(127)), 0.1);
+        }
+
+        let mapping = mapping(1.0, 0.0, CcCurve::Linear);
+        assert_close(mapping.normalized_value(0.25), 0.75);
+    }
+
+    #[test]
+    fn curves_shape_the_middle_of_the_range() {
+        assert_close(
+            mapping(0.0, 1.0, CcCurve::Linear).normalized_value(0.5),
+            0.5,
+        );
+        assert_close(
+            mapping(0.0, 1.0, CcCurve::Exponential).normalized_value(0.5),
+            0.25,
+        );
+        assert_close(
+            mapping(0.0, 1.0, CcCurve::Logarithmic).normalized_value(0.25),
+            0.5,
+        );
+        // Scaled into the range after shaping
+        assert_close(
+            mapping(0.5, 1.0, CcCurve::Exponential).normalized_value(0.5),
+            0.625,
+        );
+    }
+
+    #[test]
+    fn out_of_range_values_are_clamped() {
+        for curve in CcCurve::ALL {
+            let mapping = mapping(0.0, 1.0, curve);
+            assert_close(mapping.normalized_value(-0.5), 0.0);
+            assert_close(mapping.normalized_value(1.5), 1.0);
+        }
+
+        // Ranges beyond the parameter's still produce valid normalized values
+        let mapping = mapping(-0.5, 1.5, CcCurve::Linear);
+        assert_close(mapping.normalized_value(cc(0)), 0.0);
+        assert_close(mapping.normalized_value(cc(127)), 1.0);
+    }
+
+    #[test]
+    fn overrides_without_a_mapping_are_dropped() {
+        let mut overrides = CcOverrides::default();
+        for target in CcTarget::ALL {
+            overrides.set(target, 0.5);
+        }
+
+        overrides.retain_mapped(&[
+            CcMapping::new(1, CcTarget::Attack),
+            CcMapping::new(2, CcTarget::Release),
+            CcMapping::new(3, CcTarget::Release),
+        ]);
+        for target in CcTarget::ALL {
+            let expected = matches!(target, CcTarget::Attack | CcTarget::Release).then_some(0.5);
+            assert_eq!(overrides.get(target), expected, "{target:?}");
+        }
+
+        overrides.retain_mapped(&[]);
+        assert!(CcTarget::ALL
+            .iter()
+            .all(|&target| overrides.get(target).is_none()));
+    }
+
+    #[test]
+    fn learning_reports_each_cc_once() {
+        let learn = CcLearnState::default();
+        assert_eq!(learn.take_last_cc(), None);
+
+        learn.report_cc(74);
+        learn.report_cc(1);
+        assert_eq!(learn.take_last_cc(), Some(1));
+        assert_eq!(learn.take_last_cc(), None);
+    }
+}
@@ -0,0 +1,108 @@
+//! Chord mode, where every incoming note plays a stored chord built on top of it. Chords are
+//! learned by holding them on the keyboard and clicking learn in the editor, and saved with the
+//! plugin state.
+//!
+//! The chord's notes can be strummed, each one starting a little after the one below it. Like
+//! scale lock, chords are expanded before notes are assigned to voices, and every voice keeps
+//! the received note so the note off releases the whole chord.
+
+use plugin_scaffold::notes::NoteMask;
+use std::sync::atomic::AtomicBool;
+
+/// Chords are limited to this many notes, learning more keeps the lowest ones.
+pub const MAX_CHORD_NOTES: usize = 8;
+
+/// The longest delay between strummed notes, in milliseconds.
+pub const MAX_STRUM_MS: f32 = 100.0;
+
+/// The chord's intervals in semitones above the played note, lowest first. The audio thread's
+/// copy of the saved chord, so it never has to lock the parameters while playing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Chord {
+    intervals: [u8; MAX_CHORD_NOTES],
+    len: usize,
+}
+
+impl Default for Chord {
+    /// A major triad.
+    fn default() -> Self {
+        Self::from_intervals(&[0, 4, 7])
+    }
+}
+
+impl Chord {
+    /// Just the played note, for when chord mode is off.
+    pub const SINGLE: Chord = Chord {
+        intervals: [0; MAX_CHORD_NOTES],
+        len: 1,
+    };
+
+    /// Intervals past [`MAX_CHORD_NOTES`] are dropped. An empty list plays just the note.
+    pub fn from_intervals(intervals: &[u8]) -> Self {
+        if intervals.is_empty() {
+            return Self::SINGLE;
+        }
+
+        let mut chord = Self {
+            intervals: [0; MAX_CHORD_NOTES],
+            len: intervals.len().min(MAX_CHORD_NOTES),
+        };
+        chord.intervals[..chord.len].copy_from_slice(&intervals[..chord.len]);
+        chord
+    }
+
+    /// The intervals of held notes above the lowest one, or `None` when no notes are held.
+    pub fn from_held_notes(held: &[u8]) -> Option<Vec<u8>> {
+        let root = *held.iter().min()?;
+        let mut intervals: Vec<u8> = held.iter().map(|&note| note - root).collect();
+        intervals.sort_unstable();
+        intervals.dedup();
+        intervals.truncate(MAX_CHORD_NOTES);
+        Some(intervals)
+    }
+
+    pub fn intervals(&self) -> &[u8] {
+        &self.intervals[..self.len]
+    }
+}
+
+/// Learn state shared between the audio thread and the editor. The audio thread tracks which
+/// notes are held and the editor turns them into a chord when learn is clicked.
+#[derive(Default)]
+pub struct ChordLearnState {
+    held: NoteMask,
+    /// Set by the editor after storing a chord so the audio thread picks it up.
+    pub chord_changed: AtomicBool,
+}
+
+impl ChordLearnState {
+    pub fn note_on(&self, note: u8) {
+        self.held.set(note, true);
+    }
+
+    pub fn note_off(&self, note: u8) {
+        self.held.set(note, false);
+    }
+
+    /// Forget the held notes, e.g. when the plugin is reset.
+    pub fn clear(&self) {
+        self.held.clear();
+    }
+
+    /// The held notes, lowest first.
+    pub fn held_notes(&self) -> Vec<u8> {
+        self.held.notes().collect()
+    }
+}
+
+/// A chord note waiting for its turn in a strum.
+#[derive(Debug, Clone, Copy)]
+pub struct StrummedNote {
+    /// Samples left until the note starts.
+    pub remaining: u32,
+    pub voice_id: Option<i32>,
+    pub channel: u8,
+    pub note: u8,
+    pub interval: u8,
+    pub velocity: f32,
+}
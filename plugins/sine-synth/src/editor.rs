@@ -0,0 +1,390 @@
+use crate::cc::{CcCurve, CcLearnState, CcMapping, CcTarget};
+use crate::chord::{Chord, ChordLearnState};
+use crate::parts::PartMode;
+use crate::persist::Versioned;
+use crate::sysex::SysExState;
+use crate::visualizer::{VisualizerOutput, SNAPSHOT_SIZE};
+use crate::SynthParams;
+use atomic_float::AtomicF32;
+use nih_plug::prelude::*;
+use nih_plug_egui::create_egui_editor;
+use nih_plug_egui::egui::{self, Vec2};
+use realfft::num_complex::Complex32;
+use realfft::{RealFftPlanner, RealToComplex};
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
+use ui_widgets::undo;
+use ui_widgets::{
+    param_combo, param_toggle, EditorParams, EditorShell, Keyboard, KeyboardState, MeterState,
+    ParamKnob, Scope, SpectrumPanel,
+};
+
+/// Number of samples shown in the oscilloscope after the trigger point.
+const SCOPE_WINDOW: usize = 1024;
+
+/// The keys shown on the zone editor's keyboards, C1 to C7.
+const ZONE_KEYBOARD_LOW: u8 = 24;
+const ZONE_KEYBOARD_HIGH: u8 = 96;
+
+pub(crate) fn default_params() -> EditorParams {
+    EditorParams::new((560, 620))
+}
+
+/// GUI-thread editor state.
+struct EditorState {
+    visualizer: VisualizerState,
+    /// The parameter waiting for a CC to be mapped to it.
+    cc_learn_target: Option<CcTarget>,
+}
+
+/// GUI-thread state for turning output snapshots into scope and spectrum data.
+struct VisualizerState {
+    output: Arc<Mutex<VisualizerOutput>>,
+    fft: Arc<dyn RealToComplex<f32>>,
+    window: Vec<f32>,
+    fft_input: Vec<f32>,
+    fft_output: Vec<Complex32>,
+    scope: Vec<f32>,
+    magnitudes_db: Vec<f32>,
+}
+
+impl VisualizerState {
+    fn new(output: Arc<Mutex<VisualizerOutput>>) -> Self {
+        let fft = RealFftPlanner::<f32>::new().plan_fft_forward(SNAPSHOT_SIZE);
+        let window = (0..SNAPSHOT_SIZE)
+            .map(|i| 0.5 - 0.5 * (std::f32::consts::TAU * i as f32 / SNAPSHOT_SIZE as f32).cos())
+            .collect();
+
+        Self {
+            output,
+            fft_input: fft.make_input_vec(),
+            fft_output: fft.make_output_vec(),
+            fft,
+            window,
+            scope: Vec::with_capacity(SCOPE_WINDOW),
+            magnitudes_db: vec![util::MINUS_INFINITY_DB; SNAPSHOT_SIZE / 2 + 1],
+        }
+    }
+
+    /// Read the latest snapshot from the audio thread and recompute the displays.
+    fn update(&mut self) {
+        let mut output = self.output.lock().unwrap();
+        let snapshot = output.read();
+
+        // Trigger on the first rising zero crossing so periodic waveforms stand still
+        let trigger = (1..SNAPSHOT_SIZE - SCOPE_WINDOW)
+            .find(|&i| snapshot[i - 1] <= 0.0 && snapshot[i] > 0.0)
+            .unwrap_or(SNAPSHOT_SIZE - SCOPE_WINDOW);
+        self.scope.clear();
+        self.scope
+            .extend_from_slice(&snapshot[trigger..trigger + SCOPE_WINDOW]);
+
+        for ((input, sample), window) in self
+            .fft_input
+            .iter_mut()
+            .zip(snapshot.iter())
+            .zip(&self.window)
+        {
+            *input = sample * window;
+        }
+        drop(output);
+
+        if self
+            .fft
+            .process(&mut self.fft_input, &mut self.fft_output)
+            .is_ok()
+        {
+            // A Hann window has a coherent gain of 0.5, so a full scale sine reads as 0 dBFS
+            let scale = 4.0 / SNAPSHOT_SIZE as f32;
+            for (db, bin) in self.magnitudes_db.iter_mut().zip(&self.fft_output) {
+                *db = util::gain_to_db(bin.norm() * scale);
+            }
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn create(
+    params: Arc<SynthParams>,
+    visualizer: Arc<Mutex<VisualizerOutput>>,
+    sample_rate: Arc<AtomicF32>,
+    meters: Arc<MeterState>,
+    sysex: Arc<SysExState>,
+    cc_learn: Arc<CcLearnState>,
+    chord_learn: Arc<ChordLearnState>,
+    keyboard: Arc<KeyboardState>,
+) -> Option<Box<dyn Editor>> {
+    create_egui_editor(
+        params.editor.state.clone(),
+        EditorState {
+            visualizer: VisualizerState::new(visualizer),
+            cc_learn_target: None,
+        },
+        |_, _| {},
+        move |egui_ctx, setter, state| {
+            state.visualizer.update();
+
+            if let Some(cc) = cc_learn.take_last_cc() {
+                if let Some(target) = state.cc_learn_target.take() {
+                    let mut mappings = params.cc_mappings.write().unwrap();
+                    mappings.retain(|mapping| mapping.target != target && mapping.cc != cc);
+                    mappings.push(CcMapping::new(cc, target));
+                    cc_learn.overrides_changed.store(true, Ordering::Relaxed);
+                }
+            }
+
+            let incoming_patch = sysex.incoming_patch.lock().unwrap().take();
+            if let Some(patch) = incoming_patch {
+                undo::record_changes(egui_ctx, sysex.patch_changes(&patch));
+                sysex.apply_patch(setter, &patch);
+            }
+
+            EditorShell::new(&params.editor)
+                .with_bypass(&params.bypass)
+                .with_keyboard(&keyboard)
+                .with_meter(&meters)
+                .show(egui_ctx, setter, |ui| {
+                    ui.horizontal(|ui| {
+                        for (param, target) in [
+                            (&params.gain, CcTarget::Gain),
+                            (&params.env.attack, CcTarget::Attack),
+                            (&params.env.decay, CcTarget::Decay),
+                            (&params.env.sustain, CcTarget::Sustain),
+                            (&params.env.release, CcTarget::Release),
+                        ] {
+                            cc_knob(ui, param, setter, target, &params, &cc_learn, state);
+                        }
+                    });
+
+                    ui.horizontal(|ui| {
+                        ui.add(ParamKnob::for_param(&params.osc.coarse_tune, setter));
+                        ui.add(ParamKnob::for_param(&params.osc.fine_tune, setter));
+                        ui.add(ParamKnob::for_param(&params.voices, setter));
+                        ui.add(ParamKnob::for_param(&params.osc.onset_ramp, setter));
+                        ui.add(ParamKnob::for_param(&params.humanize_pitch, setter));
+                        ui.add(ParamKnob::for_param(&params.humanize_velocity, setter));
+                    });
+
+                    ui.horizontal(|ui| {
+                        param_toggle(ui, &params.chord_mode, setter);
+                        ui.add(ParamKnob::for_param(&params.strum, setter).with_diameter(32.0));
+                        chord_learn_button(ui, &params, &chord_learn);
+                    });
+
+                    ui.horizontal(|ui| {
+                        if ui
+                            .add_enabled(undo::can_undo(ui.ctx()), egui::Button::new("Undo"))
+                            .on_hover_text("Ctrl+Z")
+                            .clicked()
+                        {
+                            undo::undo(ui.ctx(), setter);
+                        }
+                        if ui
+                            .add_enabled(undo::can_redo(ui.ctx()), egui::Button::new("Redo"))
+                            .on_hover_text("Ctrl+Shift+Z")
+                            .clicked()
+                        {
+                            undo::redo(ui.ctx(), setter);
+                        }
+
+                        if ui
+                            .button("Send SysEx dump")
+                            .on_hover_text("Send the current patch to the MIDI output as SysEx")
+                            .clicked()
+                        {
+                            sysex.dump_requested.store(true, Ordering::Relaxed);
+                        }
+
+                        param_combo(ui, &params.osc.start_phase, setter).on_hover_text(
+                            "Random start phases keep the notes of a chord from adding up \
+                             into a thump, fixed ones make every note sound the same",
+                        );
+
+                        param_combo(ui, &params.scale_lock, setter);
+                        ui.add(
+                            ParamKnob::for_param(&params.scale_root, setter).with_diameter(32.0),
+                        );
+
+                        param_combo(ui, &params.part_mode, setter).on_hover_text(
+                            "Play the main sound, a separate part on each of MIDI channels 1 \
+                             to 4, or split and layer parts 1 and 2 across the keyboard",
+                        );
+                    });
+
+                    ui.add_space(8.0);
+                    ui.label("Oscilloscope");
+                    ui.add(Scope::new(&state.visualizer.scope).with_size(Vec2::new(500.0, 120.0)));
+
+                    ui.add_space(8.0);
+                    ui.label("Spectrum");
+                    ui.add(
+                        SpectrumPanel::new(
+                            &state.visualizer.magnitudes_db,
+                            sample_rate.load(Ordering::Relaxed),
+                        )
+                        .with_size(Vec2::new(500.0, 140.0)),
+                    );
+                });
+
+            let part_mode = params.part_mode.value();
+            if part_mode != PartMode::Single {
+                egui::Window::new("Parts")
+                    .resizable(false)
+                    .show(egui_ctx, |ui| {
+                        parts_grid(ui, &params, setter, part_mode);
+                        if part_mode == PartMode::Zones {
+                            ui.separator();
+                            zones_grid(ui, &params, setter);
+                        }
+                    });
+            }
+
+            // The displays are live, so keep repainting while the editor is open
+            egui_ctx.request_repaint();
+        },
+    )
+}
+
+/// Learns the held notes as the chord, next to a label with the current chord's intervals.
+fn chord_learn_button(ui: &mut egui::Ui, params: &SynthParams, chord_learn: &ChordLearnState) {
+    if ui
+        .button("Learn chord")
+        .on_hover_text("Hold a chord on the keyboard and click to play it from every note")
+        .clicked()
+    {
+        if let Some(intervals) = Chord::from_held_notes(&chord_learn.held_notes()) {
+            *params.chord.write().unwrap() = Versioned(intervals);
+            chord_learn.chord_changed.store(true, Ordering::Relaxed);
+        }
+    }
+
+    let intervals = params
+        .chord
+        .read()
+        .unwrap()
+        .iter()
+        .map(|interval| interval.to_string())
+        .collect::<Vec<_>>()
+        .join(" ");
+    ui.label(format!("Chord: {intervals}"));
+}
+
+/// One row of knobs per part.
+fn parts_grid(ui: &mut egui::Ui, params: &SynthParams, setter: &ParamSetter, mode: PartMode) {
+    egui::Grid::new("parts").show(ui, |ui| {
+        for (part_idx, part) in params.parts.iter().enumerate() {
+            match mode {
+                PartMode::MultiTimbral => {
+                    ui.label(format!("Part {} (ch. {})", part_idx + 1, part_idx + 1))
+                }
+                _ => ui.label(format!("Part {}", part_idx + 1)),
+            };
+            ui.add(ParamKnob::for_param(&part.gain, setter).with_diameter(36.0));
+            ui.add(ParamKnob::for_param(&part.transpose, setter).with_diameter(36.0));
+            ui.add(ParamKnob::for_param(&part.fine_tune, setter).with_diameter(36.0));
+            ui.add(ParamKnob::for_param(&part.attack, setter).with_diameter(36.0));
+            ui.add(ParamKnob::for_param(&part.decay, setter).with_diameter(36.0));
+            ui.add(ParamKnob::for_param(&part.sustain, setter).with_diameter(36.0));
+            ui.add(ParamKnob::for_param(&part.release, setter).with_diameter(36.0));
+            ui.end_row();
+        }
+    });
+}
+
+/// One row per zone with its range controls and a keyboard showing the keys it covers.
+fn zones_grid(ui: &mut egui::Ui, params: &SynthParams, setter: &ParamSetter) {
+    egui::Grid::new("zones").show(ui, |ui| {
+        for (zone_idx, zone) in params.zones.iter().enumerate() {
+            ui.label(format!("Zone {} (part {})", zone_idx + 1, zone_idx + 1));
+            ui.add(ParamKnob::for_param(&zone.key_low, setter).with_diameter(36.0));
+            ui.add(ParamKnob::for_param(&zone.key_high, setter).with_diameter(36.0));
+            ui.add(ParamKnob::for_param(&zone.transpose, setter).with_diameter(36.0));
+            ui.add(ParamKnob::for_param(&zone.velocity_low, setter).with_diameter(36.0));
+            ui.add(ParamKnob::for_param(&zone.velocity_high, setter).with_diameter(36.0));
+
+            let key_range = zone.key_low.value()..=zone.key_high.value();
+            let highlighted = std::array::from_fn(|note| key_range.contains(&(note as i32)));
+            Keyboard::new(ZONE_KEYBOARD_LOW, ZONE_KEYBOARD_HIGH)
+                .with_highlighted(&highlighted)
+                .with_size(Vec2::new(220.0, 36.0))
+                .show(ui);
+            ui.end_row();
+        }
+    });
+}
+
+/// A parameter knob with a right click menu for CC learn and for editing the CC mapping's range
+/// and curve.
+fn cc_knob(
+    ui: &mut egui::Ui,
+    param: &FloatParam,
+    setter: &ParamSetter,
+    target: CcTarget,
+    params: &SynthParams,
+    cc_learn: &CcLearnState,
+    state: &mut EditorState,
+) {
+    let response = ui.add(ParamKnob::for_param(param, setter));
+    let mapping = params
+        .cc_mappings
+        .read()
+        .unwrap()
+        .iter()
+        .find(|mapping| mapping.target == target)
+        .copied();
+
+    if state.cc_learn_target == Some(target) {
+        ui.painter().circle_stroke(
+            response.rect.center(),
+            response.rect.width() / 2.0,
+            egui::Stroke::new(2.0, ui.visuals().selection.bg_fill),
+        );
+    }
+
+    let response = match mapping {
+        Some(mapping) => response.on_hover_text(format!("Mapped to CC {}", mapping.cc)),
+        None => response,
+    };
+    response.context_menu(|ui| {
+        if state.cc_learn_target == Some(target) {
+            if ui.button("Cancel MIDI learn").clicked() {
+                state.cc_learn_target = None;
+                ui.close_menu();
+            }
+        } else if ui.button("MIDI learn").clicked() {
+            // Ignore CCs that arrived before learning started
+            cc_learn.take_last_cc();
+            state.cc_learn_target = Some(target);
+            ui.close_menu();
+        }
+
+        let Some(mut edited) = mapping else {
+            return;
+        };
+        ui.separator();
+        ui.label(format!("CC {}", edited.cc));
+        ui.add(egui::Slider::new(&mut edited.min, 0.0..=1.0).text("Min"));
+        ui.add(egui::Slider::new(&mut edited.max, 0.0..=1.0).text("Max"));
+        egui::ComboBox::from_label("Curve")
+            .selected_text(edited.curve.name())
+            .show_ui(ui, |ui| {
+                for curve in CcCurve::ALL {
+                    ui.selectable_value(&mut edited.curve, curve, curve.name());
+                }
+            });
+        let remove = ui.button("Remove mapping").clicked();
+
+        // Only take the write lock when something changed so the audio thread isn't locked out
+        // while the menu is open
+        if remove || mapping != Some(edited) {
+            let mut mappings = params.cc_mappings.write().unwrap();
+            mappings.retain(|mapping| mapping.target != target);
+            if remove {
+                cc_learn.overrides_changed.store(true, Ordering::Relaxed);
+                ui.close_menu();
+            } else {
+                mappings.push(edited);
+            }
+        }
+    });
+}
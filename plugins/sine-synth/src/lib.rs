@@ -1,52 +1,256 @@
-use dsp_core::{envelopes::ADSREnvelope, oscillators::SineOsc, utils::midi_to_freq};
+use atomic_float::AtomicF32;
+use cc::{CcLearnState, CcMapping, CcOverrides, CcTarget};
+use chord::{Chord, ChordLearnState, StrummedNote, MAX_STRUM_MS};
+use dsp_core::bypass::SoftBypass;
+use dsp_core::envelopes::{ADSREnvelope, FadeOut};
+use dsp_core::random::Xorshift32;
+use dsp_core::utils::{LinearSmoother, Smoother as _};
+use dsp_core::{guard, oscillators::SineOsc, utils::midi_to_freq};
+use mono::{HeldNote, HeldNotes};
 use nih_plug::prelude::*;
-use std::sync::Arc;
+use onset::StartPhase;
+use parts::{part_for_channel, PartMode, PartParams, ZoneParams, NUM_PARTS, NUM_ZONES};
+use persist::Versioned;
+use plugin_scaffold::formatters::{s2v_f32_ms_then_s, v2s_f32_ms_then_s};
+use plugin_scaffold::layouts;
+use plugin_scaffold::notes::{NoteHandler, PendingEvents};
+use scale::ScaleLock;
+use sections::{EnvParams, OscParams};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use sysex::{Patch, SynthSysEx, SysExState};
+use ui_widgets::{EditorParams, KeyboardInput, KeyboardState, MeterState};
+use visualizer::{VisualizerInput, VisualizerOutput};
 
+mod cc;
+mod chord;
+mod editor;
+mod mono;
+mod onset;
+mod parts;
+mod persist;
+mod scale;
+mod sections;
+mod sysex;
+mod visualizer;
+
+/// Voices per part. In single mode only the first part's voices are used.
 const MAX_VOICES: usize = 16;
 
+/// The longest stretch rendered at once. Blocks are also split at every event, and the smoothed
+/// gains and tunings are computed per sample for each of these sub-blocks.
+const MAX_BLOCK_SIZE: usize = 64;
+
+/// Strummed chord notes that can wait at once. Notes beyond this start without a delay.
+const MAX_STRUMMED_NOTES: usize = 32;
+
+/// How long a stolen voice takes to fade out, in seconds.
+const STEAL_FADE_TIME: f32 = 0.005;
+
+/// Identifies the gain parameter in CLAP polyphonic modulation events.
+const GAIN_POLY_MOD_ID: u32 = 0;
+
 struct SineSynth {
     params: Arc<SynthParams>,
-    voices: [Voice; MAX_VOICES],
-    next_voice: usize,
+    /// One pool of [`MAX_VOICES`] voices per part, stored back to back.
+    voices: [Voice; MAX_VOICES * NUM_PARTS],
+    /// The next voice to steal in each part's pool.
+    next_voice: [usize; NUM_PARTS],
+    /// The notes held on each part in mono mode, most recent last.
+    held_notes: [HeldNotes; NUM_PARTS],
+
+    /// Feeds the editor's oscilloscope and spectrum displays.
+    visualizer: VisualizerInput,
+    visualizer_output: Arc<Mutex<VisualizerOutput>>,
+    sample_rate: Arc<AtomicF32>,
+    /// Output levels for the editor's meters.
+    meters: Arc<MeterState>,
+
+    /// Notes played on the editor's keyboard, and the notes received from the host for it to
+    /// show.
+    keyboard: Arc<KeyboardState>,
+    keyboard_input: KeyboardInput,
+
+    /// `VoiceTerminated` events waiting to be sent to the host at the end of the block.
+    pending_events: PendingEvents<SynthSysEx>,
+
+    /// Patch dump requests and received patches, shared with the editor.
+    sysex: Arc<SysExState>,
+    /// A received patch dump that couldn't be handed to the editor yet.
+    received_patch: Option<Patch>,
+
+    /// CC learn state shared with the editor.
+    cc_learn: Arc<CcLearnState>,
+    /// Parameter values set by mapped CCs, these take precedence over the parameters.
+    cc_overrides: CcOverrides,
+    /// Smooths the gain when it's controlled by a CC, since CCs only have 128 steps.
+    cc_gain: Smoother<f32>,
+
+    /// Held notes for chord learn, shared with the editor.
+    chord_learn: Arc<ChordLearnState>,
+    /// The audio thread's copy of the learned chord.
+    chord: Chord,
+    /// Chord notes waiting for their turn in a strum.
+    strummed_notes: [Option<StrummedNote>; MAX_STRUMMED_NOTES],
+
+    /// Randomizes new notes. Reseeded from the saved seed whenever playback restarts.
+    humanize_rng: Xorshift32,
+    /// Picks random start phases, seeded like `humanize_rng` but kept separate so the start
+    /// phase setting doesn't change how notes are humanized.
+    start_phase_rng: Xorshift32,
+
+    /// Fades the output out and back in when the plugin is bypassed.
+    bypass: SoftBypass,
 }
 
 #[derive(Clone)]
 struct Voice {
     osc: SineOsc,
     env: ADSREnvelope,
+    /// The note's frequency before tuning, after transposing and humanizing.
+    frequency: f32,
+    /// Fades the voice in from silence when it starts.
+    onset: LinearSmoother,
+    /// The note this voice played before it was stolen, fading out under the new one.
+    stolen: Option<StolenSound>,
+    declick: FadeOut,
+    /// The note as received, before the part's transpose.
     note: Option<u8>,
     velocity: f32,
+    part: usize,
+
+    /// The host's ID for this voice, cleared once the host has been told the voice terminated.
+    voice_id: Option<i32>,
+    channel: u8,
+    /// Per-voice gain from CLAP polyphonic modulation, as the normalized offset from the
+    /// parameter's value and a smoother for the resulting gain.
+    gain_mod: Option<(f32, Smoother<f32>)>,
+}
+
+/// What a voice was playing when it was stolen. It keeps running until its fade out is done.
+#[derive(Clone)]
+struct StolenSound {
+    osc: SineOsc,
+    env: ADSREnvelope,
+    velocity: f32,
+    part: usize,
+    /// The voice's own gain if it was polyphonically modulated, otherwise the part's gain is
+    /// used.
+    gain: Option<f32>,
 }
 
 #[derive(Params)]
 struct SynthParams {
+    #[nested]
+    editor: EditorParams,
+
+    /// MIDI CC mappings made with CC learn in the editor.
+    #[persist = "cc-mappings"]
+    pub cc_mappings: RwLock<Versioned<Vec<CcMapping>>>,
+
+    /// The chord played in chord mode, as semitones above the played note.
+    #[persist = "chord"]
+    pub chord: RwLock<Versioned<Vec<u8>>>,
+
+    /// The host's bypass switch.
+    #[id = "bypass"]
+    pub bypass: BoolParam,
+
     #[id = "gain"]
     pub gain: FloatParam,
 
-    #[id = "attack"]
-    pub attack: FloatParam,
+    #[nested(group = "Oscillator")]
+    pub osc: OscParams,
+
+    #[nested(group = "Envelope")]
+    pub env: EnvParams,
+
+    /// The number of voices per part. A single voice plays monophonically with last note
+    /// priority.
+    #[id = "voices"]
+    pub voices: IntParam,
+
+    /// Seeds the humanize randomization. Saved with the project so every render of it sounds the
+    /// same.
+    #[persist = "humanize-seed"]
+    humanize_seed: AtomicU32,
+
+    /// Maximum random detune per note.
+    #[id = "humanize_pitch"]
+    pub humanize_pitch: FloatParam,
+
+    /// Maximum random velocity change per note, relative to the note's velocity.
+    #[id = "humanize_velocity"]
+    pub humanize_velocity: FloatParam,
+
+    /// Quantizes incoming notes to a scale before they're assigned to voices.
+    #[id = "scale_lock"]
+    pub scale_lock: EnumParam<ScaleLock>,
+
+    #[id = "scale_root"]
+    pub scale_root: IntParam,
+
+    /// Play the learned chord on top of every note. Needs more than one voice.
+    #[id = "chord_mode"]
+    pub chord_mode: BoolParam,
+
+    /// The delay between the notes of a chord, from the lowest up.
+    #[id = "strum"]
+    pub strum: FloatParam,
 
-    #[id = "decay"]
-    pub decay: FloatParam,
+    /// Whether notes play the parameters above, or are assigned to parts by channel or zone.
+    #[id = "part_mode"]
+    pub part_mode: EnumParam<PartMode>,
 
-    #[id = "sustain"]
-    pub sustain: FloatParam,
+    #[nested(array, group = "Part")]
+    pub parts: [PartParams; NUM_PARTS],
 
-    #[id = "release"]
-    pub release: FloatParam,
+    #[nested(array, group = "Zone")]
+    pub zones: [ZoneParams; NUM_ZONES],
 }
 
 impl Default for SineSynth {
     fn default() -> Self {
+        let (visualizer, visualizer_output) = visualizer::channel();
+        let params = Arc::new(SynthParams::default());
+        let sysex = Arc::new(SysExState::new(&params));
+
         Self {
-            params: Arc::new(SynthParams::default()),
+            params,
             voices: std::array::from_fn(|_| Voice {
                 osc: SineOsc::new(44100.0),
                 env: ADSREnvelope::new(44100.0),
+                frequency: 0.0,
+                onset: LinearSmoother::new(0),
+                stolen: None,
+                declick: FadeOut::new(44100.0, STEAL_FADE_TIME),
                 note: None,
                 velocity: 0.0,
+                part: 0,
+                voice_id: None,
+                channel: 0,
+                gain_mod: None,
             }),
-            next_voice: 0,
+            next_voice: [0; NUM_PARTS],
+            held_notes: Default::default(),
+            visualizer,
+            visualizer_output: Arc::new(Mutex::new(visualizer_output)),
+            sample_rate: Arc::new(AtomicF32::new(44100.0)),
+            meters: Arc::new(MeterState::new(2)),
+            keyboard: Arc::new(KeyboardState::new()),
+            keyboard_input: KeyboardInput::default(),
+            pending_events: PendingEvents::with_capacity(MAX_VOICES * 4),
+            sysex,
+            received_patch: None,
+            cc_learn: Arc::new(CcLearnState::default()),
+            cc_overrides: CcOverrides::default(),
+            cc_gain: Smoother::new(SmoothingStyle::Logarithmic(50.0)),
+            chord_learn: Arc::new(ChordLearnState::default()),
+            chord: Chord::default(),
+            strummed_notes: [None; MAX_STRUMMED_NOTES],
+            humanize_rng: Xorshift32::new(0),
+            start_phase_rng: Xorshift32::new(0),
+            bypass: SoftBypass::new(44100.0),
         }
     }
 }
@@ -54,6 +258,12 @@ impl Default for SineSynth {
 impl Default for SynthParams {
     fn default() -> Self {
         Self {
+            editor: editor::default_params(),
+            cc_mappings: RwLock::new(Versioned::default()),
+            chord: RwLock::new(Versioned(Chord::default().intervals().to_vec())),
+
+            bypass: BoolParam::new("Bypass", false).make_bypass(),
+
             gain: FloatParam::new(
                 "Gain",
                 util::db_to_gain(-12.0),
@@ -63,49 +273,65 @@ impl Default for SynthParams {
                     factor: FloatRange::gain_skew_factor(-30.0, 0.0),
                 },
             )
+            .with_poly_modulation_id(GAIN_POLY_MOD_ID)
             .with_smoother(SmoothingStyle::Logarithmic(50.0))
             .with_unit(" dB")
             .with_value_to_string(formatters::v2s_f32_gain_to_db(2))
             .with_string_to_value(formatters::s2v_f32_gain_to_db()),
 
-            attack: FloatParam::new(
-                "Attack",
-                0.01,
-                FloatRange::Skewed {
-                    min: 0.001,
-                    max: 5.0,
-                    factor: 0.25,
+            osc: OscParams::default(),
+            env: EnvParams::default(),
+
+            voices: IntParam::new(
+                "Voices",
+                MAX_VOICES as i32,
+                IntRange::Linear {
+                    min: 1,
+                    max: MAX_VOICES as i32,
                 },
-            )
-            .with_unit(" s")
-            .with_value_to_string(formatters::v2s_f32_rounded(3)),
+            ),
 
-            decay: FloatParam::new(
-                "Decay",
-                0.1,
-                FloatRange::Skewed {
-                    min: 0.001,
-                    max: 5.0,
-                    factor: 0.25,
+            humanize_seed: AtomicU32::new(new_humanize_seed()),
+            humanize_pitch: FloatParam::new(
+                "Humanize Pitch",
+                0.0,
+                FloatRange::Linear {
+                    min: 0.0,
+                    max: 50.0,
                 },
             )
-            .with_unit(" s")
-            .with_value_to_string(formatters::v2s_f32_rounded(3)),
+            .with_unit(" cents")
+            .with_value_to_string(formatters::v2s_f32_rounded(1)),
+            humanize_velocity: FloatParam::new(
+                "Humanize Velocity",
+                0.0,
+                FloatRange::Linear { min: 0.0, max: 0.5 },
+            )
+            .with_unit(" %")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
 
-            sustain: FloatParam::new("Sustain", 0.7, FloatRange::Linear { min: 0.0, max: 1.0 })
-                .with_value_to_string(formatters::v2s_f32_percentage(1)),
+            scale_lock: EnumParam::new("Scale Lock", ScaleLock::Off),
+            scale_root: IntParam::new("Scale Root", 0, IntRange::Linear { min: 0, max: 11 })
+                .with_value_to_string(scale::v2s_pitch_class())
+                .with_string_to_value(scale::s2v_pitch_class()),
 
-            release: FloatParam::new(
-                "Release",
-                0.2,
+            chord_mode: BoolParam::new("Chord Mode", false),
+            strum: FloatParam::new(
+                "Strum",
+                0.0,
                 FloatRange::Skewed {
-                    min: 0.001,
-                    max: 5.0,
-                    factor: 0.25,
+                    min: 0.0,
+                    max: MAX_STRUM_MS,
+                    factor: 0.5,
                 },
             )
-            .with_unit(" s")
-            .with_value_to_string(formatters::v2s_f32_rounded(3)),
+            .with_value_to_string(v2s_f32_ms_then_s(1))
+            .with_string_to_value(s2v_f32_ms_then_s()),
+
+            part_mode: EnumParam::new("Part Mode", PartMode::Single),
+            parts: Default::default(),
+            zones: Default::default(),
         }
     }
 }
@@ -117,125 +343,880 @@ impl Plugin for SineSynth {
     const EMAIL: &'static str = "contact@yourstudio.com";
     const VERSION: &'static str = env!("CARGO_PKG_VERSION");
 
-    const AUDIO_IO_LAYOUTS: &'static [AudioIOLayout] = &[AudioIOLayout {
-        main_input_channels: None,
-        main_output_channels: NonZeroU32::new(2),
-        aux_input_ports: &[],
-        aux_output_ports: &[],
-        names: PortNames::const_default(),
-    }];
+    const AUDIO_IO_LAYOUTS: &'static [AudioIOLayout] = layouts::INSTRUMENT_LAYOUTS;
 
-    const MIDI_INPUT: MidiConfig = MidiConfig::Basic;
+    const MIDI_INPUT: MidiConfig = MidiConfig::MidiCCs;
+    const MIDI_OUTPUT: MidiConfig = MidiConfig::Basic;
     const SAMPLE_ACCURATE_AUTOMATION: bool = true;
 
-    type SysExMessage = ();
+    type SysExMessage = SynthSysEx;
     type BackgroundTask = ();
 
     fn params(&self) -> Arc<dyn Params> {
         self.params.clone()
     }
 
+    fn editor(&mut self, _async_executor: AsyncExecutor<Self>) -> Option<Box<dyn Editor>> {
+        editor::create(
+            self.params.clone(),
+            self.visualizer_output.clone(),
+            self.sample_rate.clone(),
+            self.meters.clone(),
+            self.sysex.clone(),
+            self.cc_learn.clone(),
+            self.chord_learn.clone(),
+            self.keyboard.clone(),
+        )
+    }
+
     fn initialize(
         &mut self,
         _audio_io_layout: &AudioIOLayout,
         buffer_config: &BufferConfig,
         _context: &mut impl InitContext<Self>,
     ) -> bool {
-        // Initialize all voices with correct sample rate
+        self.sample_rate
+            .store(buffer_config.sample_rate, Ordering::Relaxed);
+        self.meters.set_sample_rate(buffer_config.sample_rate);
+        // The mappings and the chord may have changed if the state was just restored
+        self.cc_overrides = CcOverrides::default();
+        self.chord_learn
+            .chord_changed
+            .store(true, Ordering::Relaxed);
+        self.bypass.set_sample_rate(buffer_config.sample_rate);
         for voice in &mut self.voices {
-            voice.osc = SineOsc::new(buffer_config.sample_rate);
-            voice.env = ADSREnvelope::new(buffer_config.sample_rate);
+            voice.osc.set_sample_rate(buffer_config.sample_rate);
+            voice.env.set_sample_rate(buffer_config.sample_rate);
+            voice.declick.set_sample_rate(buffer_config.sample_rate);
         }
         true
     }
 
+    fn reset(&mut self) {
+        self.keyboard.clear_received();
+        let seed = self.params.humanize_seed.load(Ordering::Relaxed);
+        self.humanize_rng = Xorshift32::new(seed);
+        self.start_phase_rng = Xorshift32::new(!seed);
+        self.bypass.reset(self.params.bypass.value());
+
+        // Hosts reset when playback stops, so everything still sounding is cut off
+        for voice in &mut self.voices {
+            voice.osc.reset();
+            voice.env.reset();
+            voice.onset.reset(0.0);
+            voice.stolen = None;
+            voice.declick.reset();
+            voice.note = None;
+            voice.voice_id = None;
+            voice.gain_mod = None;
+        }
+        for held_notes in &mut self.held_notes {
+            held_notes.clear();
+        }
+        self.next_voice = [0; NUM_PARTS];
+        self.strummed_notes = [None; MAX_STRUMMED_NOTES];
+        self.chord_learn.clear();
+    }
+
     fn process(
         &mut self,
         buffer: &mut Buffer,
         _aux: &mut AuxiliaryBuffers,
         context: &mut impl ProcessContext<Self>,
     ) -> ProcessStatus {
-        let mut next_event = context.next_event();
-        let gain = self.params.gain.smoothed.next();
-
-        for (sample_id, channel_samples) in buffer.iter_samples().enumerate() {
-            // Process MIDI events for this sample
-            while let Some(event) = next_event {
-                if event.timing() != sample_id as u32 {
-                    break;
-                }
+        self.sync_cc_overrides();
+        self.sync_chord();
 
-                match event {
-                    NoteEvent::NoteOn { note, velocity, .. } => {
-                        // Find available voice or steal oldest
-                        let voice_idx = self.find_free_voice().unwrap_or_else(|| {
-                            let idx = self.next_voice;
-                            self.next_voice = (self.next_voice + 1) % MAX_VOICES;
-                            idx
-                        });
-
-                        let voice = &mut self.voices[voice_idx];
-                        voice.note = Some(note);
-                        voice.velocity = velocity;
-                        voice.osc.set_frequency(midi_to_freq(note));
-                        voice.osc.reset();
-                        voice.env.set_attack(self.params.attack.smoothed.next());
-                        voice.env.set_decay(self.params.decay.smoothed.next());
-                        voice.env.set_sustain(self.params.sustain.smoothed.next());
-                        voice.env.set_release(self.params.release.smoothed.next());
-                        voice.env.note_on();
-                    }
-                    NoteEvent::NoteOff { note, .. } => {
-                        // Find and release the voice playing this note
-                        for voice in &mut self.voices {
-                            if voice.note == Some(note) {
-                                voice.env.note_off();
-                            }
-                        }
+        self.handle_keyboard_notes();
+        let keyboard = self.keyboard.clone();
+        let mut next_event = keyboard.echo(context.next_event());
+        self.render(buffer.as_slice(), &mut next_event, || {
+            keyboard.echo(context.next_event())
+        });
+
+        // Voices keep running while bypassed so notes still end when they should
+        self.bypass.set_bypassed(self.params.bypass.value());
+        self.bypass.process_to_silence(buffer.as_slice());
+
+        for channel in buffer.as_slice_immutable() {
+            guard::check_block("sine-synth output", channel);
+        }
+
+        let last_sample = buffer.samples().saturating_sub(1) as u32;
+        self.terminate_finished_voices(last_sample);
+        self.handle_sysex(last_sample);
+        self.pending_events.send(context);
+
+        if self.params.editor.state.is_open() {
+            self.visualizer.publish();
+            for (channel_idx, channel) in buffer.as_slice_immutable().iter().enumerate() {
+                self.meters.update(channel_idx, channel);
+            }
+        }
+
+        self.process_status()
+    }
+}
+
+impl Voice {
+    /// Switch a mono voice to another held note without retriggering its envelope.
+    fn play_held_note(&mut self, held: &HeldNote) {
+        self.note = Some(held.note);
+        self.channel = held.channel;
+        self.voice_id = Some(held.voice_id);
+        self.velocity = held.velocity;
+        self.gain_mod = None;
+        self.frequency = held.frequency;
+    }
+
+    /// Queue a `VoiceTerminated` event for this voice if the host still thinks it's playing.
+    fn terminate(&mut self, timing: u32, pending_events: &mut PendingEvents<SynthSysEx>) {
+        pending_events.voice_terminated(timing, self.voice_id.take(), self.channel, self.note);
+    }
+}
+
+/// The part and sound a new voice plays, resolved from the part mode.
+struct VoiceSound {
+    part: usize,
+    /// The note's frequency before tuning, after transposing.
+    frequency: f32,
+    attack: f32,
+    decay: f32,
+    sustain: f32,
+    release: f32,
+}
+
+impl VoiceSound {
+    fn detuned(mut self, ratio: f32) -> Self {
+        self.frequency *= ratio;
+        self
+    }
+}
+
+/// A fresh seed for each new plugin instance, so separate instances don't randomize in lockstep.
+fn new_humanize_seed() -> u32 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|time| time.subsec_nanos())
+        .unwrap_or_default()
+}
+
+/// Compute a voice ID in case the host doesn't provide them. Polyphonic modulation will not work
+/// in this case, but playing notes will.
+const fn compute_fallback_voice_id(note: u8, channel: u8) -> i32 {
+    note as i32 | ((channel as i32) << 16)
+}
+
+impl SineSynth {
+    /// A free voice among the first `polyphony` voices of the part's pool.
+    fn find_free_voice(&self, part: usize, polyphony: usize) -> Option<usize> {
+        let pool = part * MAX_VOICES..part * MAX_VOICES + polyphony;
+        self.voices[pool.clone()]
+            .iter()
+            .position(|v| !v.env.is_active())
+            .map(|idx| pool.start + idx)
+    }
+
+    /// Fill the first `len` samples of `gains` with each part's output gain. In single mode
+    /// every voice uses the main gain, which may be controlled by a CC.
+    fn part_gains(&self, gains: &mut [[f32; MAX_BLOCK_SIZE]; NUM_PARTS], len: usize) {
+        if self.params.part_mode.value() != PartMode::Single {
+            for (part, gains) in gains.iter_mut().enumerate() {
+                self.params.parts[part].gain.smoothed.next_block(gains, len);
+            }
+            return;
+        }
+
+        let [main, others @ ..] = gains;
+        if self.cc_overrides.get(CcTarget::Gain).is_some() {
+            self.cc_gain.next_block(main, len);
+        } else {
+            self.params.gain.smoothed.next_block(main, len);
+        }
+        for gains in others {
+            gains[..len].copy_from_slice(&main[..len]);
+        }
+    }
+
+    /// Fill the first `len` samples of `tunings` with the frequency ratio for each part's
+    /// voices, from the global tuning and in multi-timbral and zone modes the part's fine
+    /// tuning.
+    fn part_tunings(&self, tunings: &mut [[f32; MAX_BLOCK_SIZE]; NUM_PARTS], len: usize) {
+        let coarse_cents = self.params.osc.coarse_tune.value() as f32 * 100.0;
+        let mut fine_cents = [0.0; MAX_BLOCK_SIZE];
+        self.params
+            .osc
+            .fine_tune
+            .smoothed
+            .next_block(&mut fine_cents, len);
+
+        let part_mode = self.params.part_mode.value();
+        for (part, tunings) in tunings.iter_mut().enumerate() {
+            if part_mode != PartMode::Single {
+                self.params.parts[part]
+                    .fine_tune
+                    .smoothed
+                    .next_block(tunings, len);
+            } else {
+                tunings[..len].fill(0.0);
+            }
+
+            for (tuning, fine_cents) in tunings[..len].iter_mut().zip(&fine_cents) {
+                *tuning = 2.0f32.powf((coarse_cents + fine_cents + *tuning) / 1200.0);
+            }
+        }
+    }
+
+    /// Play the keys pressed and released on the editor's keyboard since the last block.
+    fn handle_keyboard_notes(&mut self) {
+        let notes = self.keyboard_input.poll(&self.keyboard);
+        self.handle_events(notes);
+    }
+
+    /// Play the note, or in chord mode the chord built on it. Strummed chord notes wait in
+    /// [`strummed_notes`][Self::strummed_notes] until their turn.
+    fn note_on(
+        &mut self,
+        timing: u32,
+        voice_id: Option<i32>,
+        channel: u8,
+        note: u8,
+        velocity: f32,
+    ) {
+        self.chord_learn.note_on(note);
+
+        // The mono voice can only play one note at a time
+        let chord = if self.params.chord_mode.value() && self.params.voices.value() > 1 {
+            self.chord
+        } else {
+            Chord::SINGLE
+        };
+        let strum_seconds = self.params.strum.value() / 1000.0;
+        let strum_samples = (strum_seconds * self.sample_rate.load(Ordering::Relaxed)) as u32;
+
+        for (idx, &interval) in chord.intervals().iter().enumerate() {
+            let strummed = StrummedNote {
+                remaining: idx as u32 * strum_samples,
+                voice_id,
+                channel,
+                note,
+                interval,
+                velocity,
+            };
+            let free_slot = self.strummed_notes.iter_mut().find(|slot| slot.is_none());
+            match free_slot {
+                Some(slot) if strummed.remaining > 0 => *slot = Some(strummed),
+                _ => self.play_note(timing, voice_id, channel, note, interval, velocity),
+            }
+        }
+    }
+
+    /// Start the strummed notes whose turn has come.
+    fn start_strummed_notes(&mut self, sample_id: u32) {
+        for idx in 0..MAX_STRUMMED_NOTES {
+            let Some(strummed) = &mut self.strummed_notes[idx] else {
+                continue;
+            };
+
+            if strummed.remaining == 0 {
+                let strummed = *strummed;
+                self.strummed_notes[idx] = None;
+                self.play_note(
+                    sample_id,
+                    strummed.voice_id,
+                    strummed.channel,
+                    strummed.note,
+                    strummed.interval,
+                    strummed.velocity,
+                );
+            } else {
+                strummed.remaining -= 1;
+            }
+        }
+    }
+
+    /// Start `interval` semitones above the note on the part or parts it's assigned to. With
+    /// scale lock enabled the quantized pitch decides the zone and pitch, so chords stay in the
+    /// scale, while the voice keeps the received note so the note off still finds it.
+    fn play_note(
+        &mut self,
+        timing: u32,
+        voice_id: Option<i32>,
+        channel: u8,
+        note: u8,
+        interval: u8,
+        velocity: f32,
+    ) {
+        let pitch = note.saturating_add(interval).min(127);
+        let pitch = match self
+            .params
+            .scale_lock
+            .value()
+            .quantizer(self.params.scale_root.value())
+        {
+            Some(quantizer) => quantizer.quantize(pitch),
+            None => pitch,
+        };
+        let (velocity, detune) = self.humanize(velocity);
+
+        match self.params.part_mode.value() {
+            PartMode::Single => {
+                let sound = self.main_sound(pitch);
+                self.start_voice(
+                    timing,
+                    voice_id,
+                    channel,
+                    note,
+                    velocity,
+                    sound.detuned(detune),
+                );
+            }
+            // Channels without a part are ignored
+            PartMode::MultiTimbral => {
+                if let Some(part) = part_for_channel(channel) {
+                    let sound = self.part_sound(part, pitch, 0);
+                    self.start_voice(
+                        timing,
+                        voice_id,
+                        channel,
+                        note,
+                        velocity,
+                        sound.detuned(detune),
+                    );
+                }
+            }
+            // Overlapping zones layer, so a note may start a voice in more than one part
+            PartMode::Zones => {
+                let midi_velocity = (velocity * 127.0).round().clamp(1.0, 127.0) as u8;
+                for zone in 0..NUM_ZONES {
+                    let zone_params = &self.params.zones[zone];
+                    if zone_params.contains(pitch, midi_velocity) {
+                        let sound = self.part_sound(zone, pitch, zone_params.transpose.value());
+                        self.start_voice(
+                            timing,
+                            voice_id,
+                            channel,
+                            note,
+                            velocity,
+                            sound.detuned(detune),
+                        );
                     }
-                    _ => {}
                 }
+            }
+        }
+    }
+
+    /// Randomize a new note's velocity and compute a random detune ratio for it. Layered zones
+    /// share the same randomization.
+    fn humanize(&mut self, velocity: f32) -> (f32, f32) {
+        let velocity_amount = self.params.humanize_velocity.value();
+        let pitch_amount = self.params.humanize_pitch.value();
+        if velocity_amount == 0.0 && pitch_amount == 0.0 {
+            return (velocity, 1.0);
+        }
+
+        // Keep humanized notes audible, a zero velocity would be silent
+        let velocity = (velocity * (1.0 + self.humanize_rng.next_bipolar() * velocity_amount))
+            .clamp(1.0 / 127.0, 1.0);
+        let cents = self.humanize_rng.next_bipolar() * pitch_amount;
+
+        (velocity, 2.0f32.powf(cents / 1200.0))
+    }
+
+    /// The main parameters' sound, with any CC overrides applied.
+    fn main_sound(&self, note: u8) -> VoiceSound {
+        VoiceSound {
+            part: 0,
+            frequency: midi_to_freq(note),
+            attack: self.envelope_param(CcTarget::Attack, &self.params.env.attack),
+            decay: self.envelope_param(CcTarget::Decay, &self.params.env.decay),
+            sustain: self.envelope_param(CcTarget::Sustain, &self.params.env.sustain),
+            release: self.envelope_param(CcTarget::Release, &self.params.env.release),
+        }
+    }
+
+    /// A part's sound, with `transpose` added to the part's own transpose.
+    fn part_sound(&self, part: usize, note: u8, transpose: i32) -> VoiceSound {
+        let part_params = &self.params.parts[part];
+        let transpose = transpose + part_params.transpose.value();
+
+        VoiceSound {
+            part,
+            frequency: midi_to_freq((note as i32 + transpose).clamp(0, 127) as u8),
+            attack: part_params.attack.smoothed.next(),
+            decay: part_params.decay.smoothed.next(),
+            sustain: part_params.sustain.smoothed.next(),
+            release: part_params.release.smoothed.next(),
+        }
+    }
 
-                next_event = context.next_event();
+    fn start_voice(
+        &mut self,
+        timing: u32,
+        voice_id: Option<i32>,
+        channel: u8,
+        note: u8,
+        velocity: f32,
+        sound: VoiceSound,
+    ) {
+        let part = sound.part;
+        let voice_id = voice_id.unwrap_or_else(|| compute_fallback_voice_id(note, channel));
+        let polyphony = self.params.voices.value() as usize;
+
+        let voice_idx = if polyphony == 1 {
+            let held = HeldNote {
+                note,
+                channel,
+                voice_id,
+                velocity,
+                frequency: sound.frequency,
+            };
+            let legato = !self.held_notes[part].is_empty();
+            if let Some(dropped) = self.held_notes[part].push(held) {
+                self.queue_voice_terminated(timing, &dropped);
+            }
+
+            // While another note is held the voice just changes pitch, the older note keeps its
+            // ID until it's released
+            let voice = &mut self.voices[part * MAX_VOICES];
+            if legato && voice.env.is_active() {
+                voice.play_held_note(&held);
+                return;
+            }
+
+            part * MAX_VOICES
+        } else {
+            self.held_notes[part].clear();
+
+            // Find available voice or steal oldest
+            self.find_free_voice(part, polyphony).unwrap_or_else(|| {
+                let idx = self.next_voice[part] % polyphony;
+                self.next_voice[part] = (idx + 1) % polyphony;
+                part * MAX_VOICES + idx
+            })
+        };
+
+        // The host needs to know a stolen voice ended before its ID is reused
+        let voice = &mut self.voices[voice_idx];
+        voice.terminate(timing, &mut self.pending_events);
+
+        // Resetting the oscillator mid-waveform would click, so the old note fades out under the
+        // new one instead
+        if voice.env.is_active() {
+            voice.stolen = Some(StolenSound {
+                osc: voice.osc.clone(),
+                env: voice.env.clone(),
+                velocity: voice.velocity,
+                part: voice.part,
+                gain: voice
+                    .gain_mod
+                    .as_ref()
+                    .map(|(_, smoother)| smoother.previous_value()),
+            });
+            voice.declick.start();
+        }
+
+        voice.note = Some(note);
+        voice.velocity = velocity;
+        voice.part = part;
+        voice.voice_id = Some(voice_id);
+        voice.channel = channel;
+        voice.gain_mod = None;
+        voice.frequency = sound.frequency;
+        match self.params.osc.start_phase.value() {
+            StartPhase::Fixed => voice.osc.reset(),
+            StartPhase::Random => voice.osc.set_phase(self.start_phase_rng.next_unipolar()),
+        }
+        // The envelope picks up from its current level when a voice is retriggered, so without
+        // the ramp the jump in phase would click
+        let onset_seconds = self.params.osc.onset_ramp.value() / 1000.0;
+        let onset_samples = onset_seconds * self.sample_rate.load(Ordering::Relaxed);
+        voice.onset.set_ramp_length(onset_samples.round() as usize);
+        voice.onset.reset(0.0);
+        voice.onset.set_target(1.0);
+        voice.env.set_attack(sound.attack);
+        voice.env.set_decay(sound.decay);
+        voice.env.set_sustain(sound.sustain);
+        voice.env.set_release(sound.release);
+        voice.env.note_on();
+    }
+
+    /// The value of an envelope parameter, or the value set by its mapped CC.
+    fn envelope_param(&self, target: CcTarget, param: &FloatParam) -> f32 {
+        match self.cc_overrides.get(target) {
+            Some(normalized) => param.preview_plain(normalized),
+            None => param.smoothed.next(),
+        }
+    }
+
+    /// Apply a CC to the parameters it's mapped to. The editor may be writing the mappings, in
+    /// which case the CC is only reported for learning.
+    fn handle_cc(&mut self, cc: u8, value: f32) {
+        self.cc_learn.report_cc(cc);
+
+        let Ok(mappings) = self.params.cc_mappings.try_read() else {
+            return;
+        };
+        for mapping in mappings.iter().filter(|mapping| mapping.cc == cc) {
+            let normalized = mapping.normalized_value(value);
+            if mapping.target == CcTarget::Gain {
+                let target = self.params.gain.preview_plain(normalized);
+                // Glide from the parameter's value when the CC takes over
+                if self.cc_overrides.get(CcTarget::Gain).is_none() {
+                    self.cc_gain.reset(self.params.gain.value());
+                }
+                self.cc_gain
+                    .set_target(self.sample_rate.load(Ordering::Relaxed), target);
+            }
+            self.cc_overrides.set(mapping.target, normalized);
+        }
+    }
+
+    /// Pick up a chord learned in the editor. The editor may be writing it, in which case this
+    /// tries again on the next block.
+    fn sync_chord(&mut self) {
+        if self
+            .chord_learn
+            .chord_changed
+            .swap(false, Ordering::Relaxed)
+        {
+            match self.params.chord.try_read() {
+                Ok(intervals) => self.chord = Chord::from_intervals(&intervals),
+                Err(_) => self
+                    .chord_learn
+                    .chord_changed
+                    .store(true, Ordering::Relaxed),
             }
+        }
+    }
 
-            // Generate audio from active voices
-            let mut sample_l = 0.0;
-            let mut sample_r = 0.0;
+    /// Drop the overrides of CCs whose mapping was removed in the editor.
+    fn sync_cc_overrides(&mut self) {
+        if self
+            .cc_learn
+            .overrides_changed
+            .swap(false, Ordering::Relaxed)
+        {
+            match self.params.cc_mappings.try_read() {
+                Ok(mappings) => self.cc_overrides.retain_mapped(&mappings),
+                Err(_) => self
+                    .cc_learn
+                    .overrides_changed
+                    .store(true, Ordering::Relaxed),
+            }
+        }
+    }
 
-            for voice in &mut self.voices {
-                if voice.env.is_active() {
-                    let osc_sample = voice.osc.next_sample();
-                    let env_sample = voice.env.next_sample();
-                    let voice_sample = osc_sample * env_sample * voice.velocity * gain;
+    /// Start or update the per-voice gain offset for the voice with `voice_id`.
+    fn poly_modulate_gain(&mut self, voice_id: i32, normalized_offset: f32) {
+        let sample_rate = self.sample_rate.load(Ordering::Relaxed);
+        let target = self.params.gain.preview_modulated(normalized_offset);
+        // Layered zones share the host's voice ID
+        for voice in self
+            .voices
+            .iter_mut()
+            .filter(|voice| voice.voice_id == Some(voice_id) && voice.env.is_active())
+        {
+            match &mut voice.gain_mod {
+                Some((offset, smoother)) => {
+                    *offset = normalized_offset;
+                    smoother.set_target(sample_rate, target);
+                }
+                // A fresh modulation starts at its target, otherwise new notes would glide in
+                None => {
+                    let smoother = Smoother::new(SmoothingStyle::Logarithmic(50.0));
+                    smoother.reset(target);
+                    voice.gain_mod = Some((normalized_offset, smoother));
+                }
+            }
+        }
+    }
+
+    /// Automation of a polyphonically modulated parameter needs to be applied on top of each
+    /// voice's modulation offset.
+    fn automate_modulated_gain(&mut self, normalized_value: f32) {
+        let sample_rate = self.sample_rate.load(Ordering::Relaxed);
+        for voice in &mut self.voices {
+            if let Some((offset, smoother)) = &mut voice.gain_mod {
+                let target = self.params.gain.preview_plain(normalized_value + *offset);
+                smoother.set_target(sample_rate, target);
+            }
+        }
+    }
+
+    /// Remove a released note from the mono held-note stacks. When it was the sounding note the
+    /// voice returns to the previous held note, and when no notes are left it's released by
+    /// [`note_off()`][Self::note_off()] as usual.
+    fn mono_note_off(&mut self, timing: u32, voice_id: Option<i32>, channel: u8, note: u8) {
+        for part in 0..NUM_PARTS {
+            let Some((released, was_sounding)) =
+                self.held_notes[part].remove(|held| match voice_id {
+                    Some(voice_id) => held.voice_id == voice_id,
+                    None => held.note == note && held.channel == channel,
+                })
+            else {
+                continue;
+            };
 
-                    sample_l += voice_sample;
-                    sample_r += voice_sample;
+            if let Some(previous) = self.held_notes[part].last() {
+                self.queue_voice_terminated(timing, &released);
+                if was_sounding {
+                    self.voices[part * MAX_VOICES].play_held_note(&previous);
                 }
             }
+        }
+    }
 
-            // Apply to all channels
-            for (channel_idx, sample) in channel_samples.into_iter().enumerate() {
-                *sample = if channel_idx % 2 == 0 {
-                    sample_l / self.voices.len() as f32
-                } else {
-                    sample_r / self.voices.len() as f32
+    /// Tell the host that a held note which no longer has a voice has ended.
+    fn queue_voice_terminated(&mut self, timing: u32, held: &HeldNote) {
+        self.pending_events.voice_terminated(
+            timing,
+            Some(held.voice_id),
+            held.channel,
+            Some(held.note),
+        );
+    }
+
+    /// Keep the plugin alive while notes are held, and report the remaining release time once
+    /// they're all released so hosts that suspend silent plugins don't cut off the tails.
+    fn process_status(&self) -> ProcessStatus {
+        let tail = self.voices.iter().try_fold(0, |tail: u32, voice| {
+            voice
+                .env
+                .remaining_release_samples()
+                .map(|remaining| tail.max(remaining))
+        });
+
+        match tail {
+            None => ProcessStatus::KeepAlive,
+            Some(0) => ProcessStatus::Normal,
+            Some(samples) => ProcessStatus::Tail(samples),
+        }
+    }
+
+    fn terminate_finished_voices(&mut self, timing: u32) {
+        for idx in 0..self.voices.len() {
+            let voice = &self.voices[idx];
+            if voice.env.is_active() {
+                continue;
+            }
+
+            // Layered zones share the host's voice ID, which only ends with the last of its voices
+            let layered = voice.voice_id.is_some()
+                && self
+                    .voices
+                    .iter()
+                    .any(|other| other.env.is_active() && other.voice_id == voice.voice_id);
+            let voice = &mut self.voices[idx];
+            if layered {
+                voice.voice_id = None;
+            } else {
+                voice.terminate(timing, &mut self.pending_events);
+            }
+        }
+    }
+
+    /// Answer dump requests and hand received patches to the editor. The editor may be holding the
+    /// lock, in which case the patch is handed over on a later block instead.
+    fn handle_sysex(&mut self, timing: u32) {
+        if !self.pending_events.is_full()
+            && self.sysex.dump_requested.swap(false, Ordering::Relaxed)
+        {
+            self.pending_events.push(NoteEvent::MidiSysEx {
+                timing,
+                message: SynthSysEx::PatchDump(self.sysex.current_patch()),
+            });
+        }
+
+        if let Some(patch) = self.received_patch.take() {
+            match self.sysex.incoming_patch.try_lock() {
+                Ok(mut incoming_patch) => *incoming_patch = Some(patch),
+                Err(_) => self.received_patch = Some(patch),
+            }
+        }
+    }
+
+    /// Render into `output`, handling events as they come due. The block is split into
+    /// sub-blocks at every event, so a CC taking over the gain starts smoothing on the sample
+    /// it's timed at, and the smoothed gains and tunings advance every sample. Even channels get
+    /// the left signal and odd channels the right.
+    fn render(
+        &mut self,
+        output: &mut [&mut [f32]],
+        next_event: &mut Option<PluginNoteEvent<Self>>,
+        mut pull_event: impl FnMut() -> Option<PluginNoteEvent<Self>>,
+    ) {
+        let num_samples = output.first().map_or(0, |channel| channel.len());
+        let mut gains = [[0.0; MAX_BLOCK_SIZE]; NUM_PARTS];
+        let mut tunings = [[0.0; MAX_BLOCK_SIZE]; NUM_PARTS];
+
+        let mut block_start = 0;
+        while block_start < num_samples {
+            // The sub-block runs until the next event that isn't due yet
+            self.handle_due_events(block_start as u32, next_event, &mut pull_event);
+            let next_timing = next_event
+                .as_ref()
+                .map_or(num_samples, |event| event.timing() as usize);
+            let block_end = next_timing
+                .min(num_samples)
+                .min(block_start + MAX_BLOCK_SIZE);
+            let block_len = block_end - block_start;
+
+            self.part_gains(&mut gains, block_len);
+            self.part_tunings(&mut tunings, block_len);
+            for idx in 0..block_len {
+                let sample_id = block_start + idx;
+                self.start_strummed_notes(sample_id as u32);
+
+                let (sample_l, sample_r) = self.render_frame(
+                    &std::array::from_fn(|part| gains[part][idx]),
+                    &std::array::from_fn(|part| tunings[part][idx]),
+                );
+                self.visualizer.push(sample_l);
+                for (channel_idx, channel) in output.iter_mut().enumerate() {
+                    channel[sample_id] = if channel_idx.is_multiple_of(2) {
+                        sample_l
+                    } else {
+                        sample_r
+                    };
+                }
+            }
+
+            block_start = block_end;
+        }
+    }
+
+    /// Render one stereo frame from all active voices, scaled down by a part's voice count.
+    fn render_frame(&mut self, gains: &[f32; NUM_PARTS], tunings: &[f32; NUM_PARTS]) -> (f32, f32) {
+        let mut sample_l = 0.0;
+        let mut sample_r = 0.0;
+
+        for voice in &mut self.voices {
+            if voice.env.is_active() {
+                voice
+                    .osc
+                    .set_frequency(voice.frequency * tunings[voice.part]);
+                let osc_sample = voice.osc.next_sample();
+                let env_sample = voice.env.next_sample();
+                guard::check_sample("SineOsc", osc_sample);
+                guard::check_sample("ADSREnvelope", env_sample);
+                let voice_gain = match &voice.gain_mod {
+                    Some((_, smoother)) => smoother.next(),
+                    None => gains[voice.part],
                 };
+                let voice_sample =
+                    osc_sample * env_sample * voice.onset.next() * voice.velocity * voice_gain;
+
+                sample_l += voice_sample;
+                sample_r += voice_sample;
+            }
+
+            if let Some(stolen) = &mut voice.stolen {
+                let gain = stolen.gain.unwrap_or(gains[stolen.part]);
+                let stolen_sample = stolen.osc.next_sample()
+                    * stolen.env.next_sample()
+                    * stolen.velocity
+                    * gain
+                    * voice.declick.next_gain();
+                sample_l += stolen_sample;
+                sample_r += stolen_sample;
+
+                if !voice.declick.is_active() {
+                    voice.stolen = None;
+                }
             }
         }
 
-        ProcessStatus::Normal
+        // Scaled for one part's pool rather than all of them, so single mode, which only uses the
+        // first part's voices, isn't 12 dB quieter. Layered parts are balanced with their own
+        // gains, which start out at -12 dB.
+        let scale = 1.0 / MAX_VOICES as f32;
+        (sample_l * scale, sample_r * scale)
+    }
+
+    fn note_off(&mut self, timing: u32, voice_id: Option<i32>, channel: u8, note: u8) {
+        self.chord_learn.note_off(note);
+
+        // Chord notes that haven't been strummed yet never start
+        for slot in &mut self.strummed_notes {
+            let matches = slot.is_some_and(|strummed| match voice_id {
+                Some(voice_id) => strummed.voice_id == Some(voice_id),
+                None => strummed.note == note && strummed.channel == channel,
+            });
+            if matches {
+                *slot = None;
+            }
+        }
+
+        if self.params.voices.value() == 1 {
+            self.mono_note_off(timing, voice_id, channel, note);
+        }
+
+        // Release the voice with this ID, or every voice playing this note when there is none
+        for voice in &mut self.voices {
+            let matches = match voice_id {
+                Some(voice_id) => voice.voice_id == Some(voice_id),
+                None => voice.note == Some(note) && voice.channel == channel,
+            };
+            if matches && voice.env.is_active() {
+                voice.env.note_off();
+            }
+        }
     }
 }
 
-impl SineSynth {
-    fn find_free_voice(&self) -> Option<usize> {
-        self.voices.iter().position(|v| !v.env.is_active())
+impl NoteHandler<SynthSysEx> for SineSynth {
+    fn handle_event(&mut self, event: PluginNoteEvent<Self>) {
+        match event {
+            // A note on with zero velocity is a note off in MIDI terms
+            NoteEvent::NoteOn {
+                timing,
+                voice_id,
+                channel,
+                note,
+                velocity,
+            } if velocity <= 0.0 => self.note_off(timing, voice_id, channel, note),
+            NoteEvent::NoteOn {
+                timing,
+                voice_id,
+                channel,
+                note,
+                velocity,
+            } => self.note_on(timing, voice_id, channel, note, velocity),
+            NoteEvent::NoteOff {
+                timing,
+                voice_id,
+                channel,
+                note,
+                ..
+            } => self.note_off(timing, voice_id, channel, note),
+            NoteEvent::PolyModulation {
+                voice_id,
+                poly_modulation_id: GAIN_POLY_MOD_ID,
+                normalized_offset,
+                ..
+            } => self.poly_modulate_gain(voice_id, normalized_offset),
+            NoteEvent::MonoAutomation {
+                poly_modulation_id: GAIN_POLY_MOD_ID,
+                normalized_value,
+                ..
+            } => self.automate_modulated_gain(normalized_value),
+            NoteEvent::MidiSysEx { message, .. } => match message {
+                SynthSysEx::DumpRequest => self.sysex.dump_requested.store(true, Ordering::Relaxed),
+                SynthSysEx::PatchDump(patch) => self.received_patch = Some(patch),
+            },
+            NoteEvent::MidiCC { cc, value, .. } => self.handle_cc(cc, value),
+            _ => {}
+        }
     }
 }
 
 impl ClapPlugin for SineSynth {
     const CLAP_ID: &'static str = "com.yourstudio.sine-synth";
+    const CLAP_POLY_MODULATION_CONFIG: Option<PolyModulationConfig> = Some(PolyModulationConfig {
+        max_voice_capacity: (MAX_VOICES * NUM_PARTS) as u32,
+        supports_overlapping_voices: true,
+    });
     const CLAP_DESCRIPTION: Option<&'static str> = Some("A polyphonic sine wave synthesizer");
     const CLAP_MANUAL_URL: Option<&'static str> = Some(Self::URL);
     const CLAP_SUPPORT_URL: Option<&'static str> = None;
@@ -254,3 +1235,573 @@ impl Vst3Plugin for SineSynth {
 
 nih_export_clap!(SineSynth);
 nih_export_vst3!(SineSynth);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use plugin_scaffold::test_util::assert_no_alloc;
+
+    plugin_scaffold::install_alloc_disabler!();
+
+    const SAMPLE_RATE: f32 = 44100.0;
+    const BLOCK_SIZE: usize = 256;
+
+    /// Small xorshift generator so the fuzz runs are reproducible without extra dependencies.
+    struct XorShift(u32);
+
+    impl XorShift {
+        fn next(&mut self) -> u32 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 17;
+            self.0 ^= self.0 << 5;
+            self.0
+        }
+
+        fn below(&mut self, max: u32) -> u32 {
+            self.next() % max
+        }
+
+        fn unit(&mut self) -> f32 {
+            self.next() as f32 / u32::MAX as f32
+        }
+    }
+
+    fn test_synth() -> SineSynth {
+        test_synth_with(SynthParams::default())
+    }
+
+    fn test_synth_with(params: SynthParams) -> SineSynth {
+        let synth = SineSynth {
+            params: Arc::new(params),
+            ..SineSynth::default()
+        };
+        let params = &synth.params;
+        for param in [
+            &params.gain,
+            &params.env.attack,
+            &params.env.decay,
+            &params.env.sustain,
+            &params.env.release,
+        ] {
+            param.smoothed.reset(param.value());
+        }
+        for part in &params.parts {
+            part.gain.smoothed.reset(part.gain.value());
+            part.fine_tune.smoothed.reset(part.fine_tune.value());
+        }
+
+        synth
+    }
+
+    fn note_on(timing: u32, note: u8, velocity: f32) -> PluginNoteEvent<SineSynth> {
+        channel_note_on(timing, 0, note, velocity)
+    }
+
+    fn note_off(timing: u32, note: u8) -> PluginNoteEvent<SineSynth> {
+        channel_note_off(timing, 0, note)
+    }
+
+    fn channel_note_on(
+        timing: u32,
+        channel: u8,
+        note: u8,
+        velocity: f32,
+    ) -> PluginNoteEvent<SineSynth> {
+        NoteEvent::NoteOn {
+            timing,
+            voice_id: None,
+            channel,
+            note,
+            velocity,
+        }
+    }
+
+    fn channel_note_off(timing: u32, channel: u8, note: u8) -> PluginNoteEvent<SineSynth> {
+        NoteEvent::NoteOff {
+            timing,
+            voice_id: None,
+            channel,
+            note,
+            velocity: 0.0,
+        }
+    }
+
+    /// Render the left channel the way `process()` does.
+    fn render_into(
+        synth: &mut SineSynth,
+        events: impl IntoIterator<Item = PluginNoteEvent<SineSynth>>,
+        output: &mut [f32],
+    ) {
+        let mut events = events.into_iter();
+        let mut next_event = events.next();
+        synth.render(&mut [output], &mut next_event, || events.next());
+    }
+
+    fn render_block(
+        synth: &mut SineSynth,
+        events: Vec<PluginNoteEvent<SineSynth>>,
+        num_samples: usize,
+    ) -> Vec<f32> {
+        let mut output = vec![0.0; num_samples];
+        render_into(synth, events, &mut output);
+        output
+    }
+
+    fn active_voices(synth: &SineSynth) -> usize {
+        synth.voices.iter().filter(|v| v.env.is_active()).count()
+    }
+
+    fn release_everything(synth: &mut SineSynth) {
+        let all_off = (0..=127).map(|note| note_off(0, note)).collect();
+        render_block(synth, all_off, BLOCK_SIZE);
+        render_block(synth, Vec::new(), (SAMPLE_RATE * 3.0) as usize);
+    }
+
+    #[test]
+    fn random_event_streams_stay_finite_and_bounded() {
+        for seed in 1..=64 {
+            let mut rng = XorShift(seed);
+            let mut synth = test_synth();
+
+            for _ in 0..32 {
+                let events = (0..rng.below(12))
+                    .map(|_| {
+                        // Timings are deliberately unsorted and may exceed the block length
+                        let timing = rng.below(BLOCK_SIZE as u32 + 16);
+                        let note = rng.below(128) as u8;
+                        match rng.below(4) {
+                            0 => note_on(timing, note, 0.0),
+                            1 => note_off(timing, note),
+                            _ => note_on(timing, note, rng.unit()),
+                        }
+                    })
+                    .collect();
+
+                for sample in render_block(&mut synth, events, BLOCK_SIZE) {
+                    assert!(sample.is_finite(), "seed {seed} produced {sample}");
+                    assert!(sample.abs() <= 1.0, "seed {seed} produced {sample}");
+                }
+            }
+
+            release_everything(&mut synth);
+            assert_eq!(active_voices(&synth), 0, "seed {seed} left voices hanging");
+        }
+    }
+
+    #[test]
+    fn overlapping_notes_beyond_polyphony_release_cleanly() {
+        let mut synth = test_synth();
+        let events = (0..MAX_VOICES as u8 * 2)
+            .flat_map(|i| [note_on(i as u32, 60, 1.0), note_on(i as u32, 60 + i, 1.0)])
+            .collect();
+        render_block(&mut synth, events, BLOCK_SIZE);
+        assert_eq!(active_voices(&synth), MAX_VOICES);
+
+        release_everything(&mut synth);
+        assert_eq!(active_voices(&synth), 0);
+    }
+
+    #[test]
+    fn note_off_without_note_on_is_silent() {
+        let mut synth = test_synth();
+        let events = (0..=127).map(|note| note_off(note as u32, note)).collect();
+
+        let output = render_block(&mut synth, events, BLOCK_SIZE);
+        assert!(output.iter().all(|&sample| sample == 0.0));
+        assert_eq!(active_voices(&synth), 0);
+    }
+
+    #[test]
+    fn zero_velocity_note_on_acts_as_note_off() {
+        let mut synth = test_synth();
+        render_block(&mut synth, vec![note_on(0, 60, 0.8)], BLOCK_SIZE);
+        assert_eq!(active_voices(&synth), 1);
+
+        render_block(&mut synth, vec![note_on(0, 60, 0.0)], BLOCK_SIZE);
+        render_block(&mut synth, Vec::new(), (SAMPLE_RATE * 3.0) as usize);
+        assert_eq!(active_voices(&synth), 0);
+    }
+
+    #[test]
+    fn held_and_releasing_voices_keep_the_plugin_processing() {
+        let mut synth = test_synth();
+        assert!(matches!(synth.process_status(), ProcessStatus::Normal));
+
+        render_block(&mut synth, vec![note_on(0, 60, 1.0)], BLOCK_SIZE);
+        assert!(matches!(synth.process_status(), ProcessStatus::KeepAlive));
+
+        render_block(&mut synth, vec![note_off(0, 60)], BLOCK_SIZE);
+        let ProcessStatus::Tail(tail) = synth.process_status() else {
+            panic!("a releasing voice should report a tail");
+        };
+        render_block(&mut synth, Vec::new(), tail as usize);
+        assert_eq!(active_voices(&synth), 0);
+        assert!(matches!(synth.process_status(), ProcessStatus::Normal));
+    }
+
+    #[test]
+    fn out_of_order_events_do_not_block_the_queue() {
+        let mut synth = test_synth();
+        let events = vec![
+            note_on(10, 60, 1.0),
+            note_on(2, 64, 1.0),
+            note_on(20, 67, 1.0),
+        ];
+
+        render_block(&mut synth, events, BLOCK_SIZE);
+        assert_eq!(active_voices(&synth), 3);
+    }
+
+    #[test]
+    fn smoothed_gains_advance_every_sample() {
+        let mut synth = test_synth();
+        synth.cc_overrides.set(CcTarget::Gain, 1.0);
+        synth.cc_gain.reset(0.1);
+        synth.cc_gain.set_target(SAMPLE_RATE, 1.0);
+        let expected = synth.cc_gain.clone();
+        for _ in 0..BLOCK_SIZE {
+            expected.next();
+        }
+
+        // The note splits the block, the smoother still takes one step per sample
+        render_block(&mut synth, vec![note_on(100, 60, 1.0)], BLOCK_SIZE);
+        assert_eq!(synth.cc_gain.previous_value(), expected.previous_value());
+    }
+
+    #[test]
+    fn stolen_voices_fade_in_over_the_onset_ramp() {
+        let mut synth = test_synth();
+        let events = (0..MAX_VOICES as u8)
+            .map(|i| note_on(0, 48 + i, 1.0))
+            .collect();
+        render_block(&mut synth, events, BLOCK_SIZE);
+        render_block(&mut synth, vec![note_on(0, 90, 1.0)], 1);
+
+        // The stolen voice's envelope is still up, so only the ramp keeps it from jumping in
+        let voice = synth.voices.iter().find(|v| v.note == Some(90)).unwrap();
+        let ramp_samples = (synth.params.osc.onset_ramp.value() / 1000.0 * SAMPLE_RATE).round();
+        assert!(voice.env.is_active());
+        assert!((voice.onset.current() - 1.0 / ramp_samples).abs() < 1e-6);
+    }
+
+    #[test]
+    fn stolen_voices_fade_out_instead_of_cutting_off() {
+        let chord = || {
+            (0..MAX_VOICES as u8)
+                .map(|i| note_on(0, 48 + i, 1.0))
+                .collect()
+        };
+        let mut held = test_synth();
+        let mut stolen = test_synth();
+        render_block(&mut held, chord(), BLOCK_SIZE);
+        render_block(&mut stolen, chord(), BLOCK_SIZE);
+
+        // The new note starts from silence while the stolen one is still at full level
+        let held_output = render_block(&mut held, Vec::new(), BLOCK_SIZE);
+        let stolen_output = render_block(&mut stolen, vec![note_on(0, 90, 1.0)], BLOCK_SIZE);
+        assert!((held_output[0] - stolen_output[0]).abs() < 1e-4);
+        assert!(stolen.voices.iter().all(|voice| voice.stolen.is_none()));
+    }
+
+    #[test]
+    fn reset_silences_held_notes() {
+        let mut synth = test_synth();
+        render_block(&mut synth, vec![note_on(0, 60, 1.0)], BLOCK_SIZE);
+        synth.reset();
+        assert_eq!(active_voices(&synth), 0);
+
+        // The note off arriving after the reset has nothing left to release
+        let output = render_block(&mut synth, vec![note_off(0, 60)], BLOCK_SIZE);
+        assert!(output.iter().all(|&sample| sample == 0.0));
+    }
+
+    #[test]
+    fn strummed_notes_start_in_turn_until_released() {
+        let mut synth = test_synth();
+        for (slot, interval) in [4, 7].into_iter().enumerate() {
+            synth.strummed_notes[slot] = Some(StrummedNote {
+                remaining: 10 * (slot as u32 + 1),
+                voice_id: None,
+                channel: 0,
+                note: 60,
+                interval,
+                velocity: 1.0,
+            });
+        }
+
+        render_block(&mut synth, Vec::new(), 15);
+        assert_eq!(active_voices(&synth), 1);
+        render_block(&mut synth, Vec::new(), 10);
+        assert_eq!(active_voices(&synth), 2);
+
+        // Every chord note keeps the played note, so one note off releases them all
+        assert!(synth
+            .voices
+            .iter()
+            .filter(|v| v.env.is_active())
+            .all(|v| v.note == Some(60)));
+        synth.strummed_notes[0] = Some(StrummedNote {
+            remaining: 10,
+            voice_id: None,
+            channel: 0,
+            note: 60,
+            interval: 12,
+            velocity: 1.0,
+        });
+        render_block(&mut synth, vec![note_off(0, 60)], BLOCK_SIZE);
+        assert!(synth.strummed_notes.iter().all(Option::is_none));
+        assert!(synth
+            .voices
+            .iter()
+            .all(|v| v.env.remaining_release_samples().is_some()));
+    }
+
+    #[test]
+    fn humanize_without_amounts_changes_nothing() {
+        let mut synth = test_synth();
+        for velocity in [1.0 / 127.0, 0.3, 0.77, 1.0] {
+            assert_eq!(synth.humanize(velocity), (velocity, 1.0));
+        }
+    }
+
+    #[test]
+    fn humanized_notes_stay_in_range() {
+        let mut synth = test_synth_with(SynthParams {
+            humanize_pitch: FloatParam::new(
+                "Humanize Pitch",
+                50.0,
+                FloatRange::Linear {
+                    min: 0.0,
+                    max: 50.0,
+                },
+            ),
+            humanize_velocity: FloatParam::new(
+                "Humanize Velocity",
+                0.5,
+                FloatRange::Linear { min: 0.0, max: 0.5 },
+            ),
+            ..SynthParams::default()
+        });
+
+        let max_detune = 2.0f32.powf(50.0 / 1200.0);
+        for velocity in [0.0, 1.0 / 127.0, 0.5, 1.0] {
+            for _ in 0..1000 {
+                let (humanized, detune) = synth.humanize(velocity);
+                assert!((1.0 / 127.0..=1.0).contains(&humanized), "{humanized}");
+                assert!(
+                    (1.0 / max_detune..=max_detune).contains(&detune),
+                    "{detune}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn mono_mode_plays_the_last_note_and_returns_to_held_notes() {
+        let mut synth = test_synth_with(SynthParams {
+            voices: IntParam::new(
+                "Voices",
+                1,
+                IntRange::Linear {
+                    min: 1,
+                    max: MAX_VOICES as i32,
+                },
+            ),
+            ..SynthParams::default()
+        });
+        let sounding = |synth: &SineSynth| {
+            assert_eq!(active_voices(synth), 1);
+            let voice = &synth.voices[0];
+            assert!(voice.env.remaining_release_samples().is_none());
+            (voice.note, voice.frequency)
+        };
+
+        let events = vec![
+            note_on(0, 60, 1.0),
+            note_on(10, 64, 1.0),
+            note_on(20, 67, 1.0),
+        ];
+        render_block(&mut synth, events, BLOCK_SIZE);
+        assert_eq!(sounding(&synth), (Some(67), midi_to_freq(67)));
+
+        // Releasing the sounding note goes back to the most recent one still held
+        render_block(&mut synth, vec![note_off(0, 67)], BLOCK_SIZE);
+        assert_eq!(sounding(&synth), (Some(64), midi_to_freq(64)));
+
+        // Releasing an older note doesn't interrupt the sounding one
+        render_block(&mut synth, vec![note_off(0, 60)], BLOCK_SIZE);
+        assert_eq!(sounding(&synth), (Some(64), midi_to_freq(64)));
+
+        render_block(&mut synth, vec![note_off(0, 64)], BLOCK_SIZE);
+        assert!(synth.voices[0].env.remaining_release_samples().is_some());
+        assert!(synth.held_notes[0].is_empty());
+    }
+
+    #[test]
+    fn scale_lock_plays_the_quantized_pitch_and_releases_the_received_note() {
+        let mut synth = test_synth_with(SynthParams {
+            scale_lock: EnumParam::new("Scale Lock", ScaleLock::Major),
+            scale_root: IntParam::new("Scale Root", 2, IntRange::Linear { min: 0, max: 11 }),
+            ..SynthParams::default()
+        });
+
+        // C is between B and C# in D major, ties go down
+        render_block(&mut synth, vec![note_on(0, 60, 1.0)], BLOCK_SIZE);
+        let voice = synth.voices.iter().find(|v| v.env.is_active()).unwrap();
+        assert_eq!(voice.note, Some(60));
+        assert_eq!(voice.frequency, midi_to_freq(59));
+
+        render_block(&mut synth, vec![note_off(0, 60)], BLOCK_SIZE);
+        assert!(synth
+            .voices
+            .iter()
+            .all(|v| v.env.remaining_release_samples().is_some()));
+    }
+
+    #[test]
+    fn multi_timbral_parts_play_and_release_independently() {
+        let mut synth = test_synth_with(SynthParams {
+            part_mode: EnumParam::new("Part Mode", PartMode::MultiTimbral),
+            ..SynthParams::default()
+        });
+        let part_voices = |synth: &SineSynth, part: usize| {
+            synth.voices[part * MAX_VOICES..(part + 1) * MAX_VOICES]
+                .iter()
+                .filter(|v| v.env.is_active())
+                .map(|v| v.note)
+                .collect::<Vec<_>>()
+        };
+
+        // Each channel plays in its own part's pool
+        let output = render_block(
+            &mut synth,
+            vec![
+                channel_note_on(0, 0, 60, 1.0),
+                channel_note_on(0, 1, 67, 1.0),
+            ],
+            BLOCK_SIZE,
+        );
+        assert!(output.iter().any(|&sample| sample.abs() > 1e-3));
+        assert_eq!(part_voices(&synth, 0), [Some(60)]);
+        assert_eq!(part_voices(&synth, 1), [Some(67)]);
+
+        // A note off on one channel doesn't touch the same note on the other part
+        render_block(&mut synth, vec![channel_note_off(0, 0, 67)], BLOCK_SIZE);
+        assert_eq!(part_voices(&synth, 1), [Some(67)]);
+
+        render_block(&mut synth, vec![channel_note_off(0, 0, 60)], BLOCK_SIZE);
+        render_block(&mut synth, Vec::new(), (SAMPLE_RATE * 3.0) as usize);
+        assert!(part_voices(&synth, 0).is_empty());
+        assert_eq!(part_voices(&synth, 1), [Some(67)]);
+        let output = render_block(&mut synth, Vec::new(), BLOCK_SIZE);
+        assert!(output.iter().any(|&sample| sample.abs() > 1e-3));
+    }
+
+    #[test]
+    fn persisted_state_is_saved_with_its_version() {
+        let mappings = Versioned(vec![CcMapping::new(74, CcTarget::Gain)]);
+        let saved = serde_json::to_value(&mappings).unwrap();
+        assert_eq!(saved["version"], 1);
+        assert_eq!(saved["data"][0]["cc"], 74);
+
+        let loaded: Versioned<Vec<CcMapping>> = serde_json::from_value(saved).unwrap();
+        assert_eq!(loaded, mappings);
+    }
+
+    #[test]
+    fn unversioned_state_from_older_sessions_migrates() {
+        // As saved before the fields were versioned
+        let mappings: Versioned<Vec<CcMapping>> = serde_json::from_str(
+            r#"[{"cc":1,"target":"release","min":0.25,"max":1.0,"curve":"Exponential"}]"#,
+        )
+        .unwrap();
+        assert_eq!(mappings.len(), 1);
+        assert_eq!(mappings[0].target, CcTarget::Release);
+        assert_eq!(mappings[0].min, 0.25);
+        assert_eq!(mappings[0].curve, cc::CcCurve::Exponential);
+
+        let chord: Versioned<Vec<u8>> = serde_json::from_str("[0,4,7]").unwrap();
+        assert_eq!(*chord, [0, 4, 7]);
+    }
+
+    #[test]
+    fn state_from_newer_versions_is_rejected() {
+        let saved = r#"{"version":2,"data":{"intervals":[0,4,7]}}"#;
+        assert!(serde_json::from_str::<Versioned<Vec<u8>>>(saved).is_err());
+    }
+
+    #[test]
+    fn grouped_parameters_keep_their_ids() {
+        let params = SynthParams::default();
+        let groups: Vec<(String, String)> = params
+            .param_map()
+            .into_iter()
+            .map(|(id, _, group)| (id, group))
+            .collect();
+        for (id, group) in [
+            ("coarse_tune", "Oscillator"),
+            ("onset_ramp", "Oscillator"),
+            ("attack", "Envelope"),
+            ("release", "Envelope"),
+            ("gain", ""),
+        ] {
+            assert!(
+                groups.contains(&(id.to_string(), group.to_string())),
+                "{id}"
+            );
+        }
+    }
+
+    #[test]
+    fn times_switch_between_milliseconds_and_seconds() {
+        let params = SynthParams::default();
+        let attack = &params.env.attack;
+        assert_eq!(
+            attack.normalized_value_to_string(attack.preview_normalized(0.25), true),
+            "250.0 ms"
+        );
+        assert_eq!(
+            attack.normalized_value_to_string(attack.preview_normalized(2.5), true),
+            "2.5 s"
+        );
+        assert_eq!(
+            attack.string_to_normalized_value("40 ms"),
+            Some(attack.preview_normalized(0.04))
+        );
+        assert_eq!(
+            attack.string_to_normalized_value("1.5"),
+            Some(attack.preview_normalized(1.5))
+        );
+    }
+
+    #[test]
+    fn processing_does_not_allocate() {
+        let mut synth = test_synth();
+        let mut block = vec![0.0; BLOCK_SIZE];
+        let notes = [
+            note_on(0, 60, 1.0),
+            note_on(10, 64, 0.8),
+            note_on(20, 67, 0.6),
+        ];
+        let releases = [note_off(0, 60), note_off(0, 64), note_off(0, 67)];
+        synth.sysex.dump_requested.store(true, Ordering::Relaxed);
+        assert_no_alloc(|| {
+            for idx in 0..(SAMPLE_RATE as usize / BLOCK_SIZE) {
+                let events = match idx {
+                    0 => &notes[..],
+                    100 => &releases[..],
+                    _ => &[],
+                };
+                synth.sync_cc_overrides();
+                synth.sync_chord();
+                render_into(&mut synth, events.iter().cloned(), &mut block);
+
+                synth.terminate_finished_voices(0);
+                synth.handle_sysex(0);
+                synth.pending_events.clear();
+                synth.visualizer.publish();
+                synth.meters.update(0, &block);
+            }
+        });
+    }
+}
@@ -0,0 +1,72 @@
+//! The held-note stack for monophonic mode. Releasing the most recent note returns to the note
+//! that was held before it, like on classic mono synths.
+
+/// Notes beyond this are dropped from the bottom of the stack.
+const MAX_HELD_NOTES: usize = 32;
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HeldNote {
+    pub note: u8,
+    pub channel: u8,
+    pub voice_id: i32,
+    pub velocity: f32,
+    /// The frequency the note plays at, after transposing and humanizing.
+    pub frequency: f32,
+}
+
+/// Held notes in the order they were pressed. Fixed size so it can be used on the audio thread.
+#[derive(Debug, Clone)]
+pub struct HeldNotes {
+    notes: [HeldNote; MAX_HELD_NOTES],
+    len: usize,
+}
+
+impl Default for HeldNotes {
+    fn default() -> Self {
+        Self {
+            notes: [HeldNote::default(); MAX_HELD_NOTES],
+            len: 0,
+        }
+    }
+}
+
+impl HeldNotes {
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    pub fn last(&self) -> Option<HeldNote> {
+        self.notes[..self.len].last().copied()
+    }
+
+    /// Push a newly pressed note. When the stack is full the oldest note is dropped and returned.
+    pub fn push(&mut self, note: HeldNote) -> Option<HeldNote> {
+        let mut dropped = None;
+        if self.len == MAX_HELD_NOTES {
+            dropped = Some(self.notes[0]);
+            self.notes.copy_within(1.., 0);
+            self.len -= 1;
+        }
+
+        self.notes[self.len] = note;
+        self.len += 1;
+
+        dropped
+    }
+
+    /// Remove the most recent note matching `predicate`, returning it and whether it was the
+    /// most recent note overall, i.e. the one that was sounding.
+    pub fn remove(&mut self, predicate: impl Fn(&HeldNote) -> bool) -> Option<(HeldNote, bool)> {
+        let idx = self.notes[..self.len].iter().rposition(predicate)?;
+        let note = self.notes[idx];
+        let was_sounding = idx == self.len - 1;
+        self.notes.copy_within(idx + 1..self.len, idx);
+        self.len -= 1;
+
+        Some((note, was_sounding))
+    }
+}
@@ -0,0 +1,16 @@
+//! How new voices start. Starting every voice at the same phase makes the first cycles of a
+//! dense chord add up into a thump, while a random phase makes the sine start mid-waveform, so
+//! voices also fade in over a short onset ramp.
+
+use nih_plug::prelude::*;
+
+/// The longest onset ramp, in milliseconds.
+pub const MAX_ONSET_MS: f32 = 2.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+pub enum StartPhase {
+    /// Every voice starts at the beginning of the cycle, so notes sound the same every time.
+    Fixed,
+    /// Every voice starts somewhere else in the cycle.
+    Random,
+}
@@ -0,0 +1,179 @@
+//! Parts for multi-timbral and zone modes. Every part has its own sound, transpose, and output
+//! gain, and its own pool of voices so a busy part can't steal another part's notes.
+//!
+//! In multi-timbral mode MIDI channels 1 to [`NUM_PARTS`] each play their own part. In zone mode
+//! notes from any channel are split or layered across the first [`NUM_ZONES`] parts based on each
+//! zone's key and velocity range.
+
+use nih_plug::prelude::*;
+use plugin_scaffold::formatters::{s2v_f32_s_then_ms, v2s_f32_s_then_ms};
+
+pub const NUM_PARTS: usize = 4;
+pub const NUM_ZONES: usize = 2;
+
+/// How incoming notes are assigned to parts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+pub enum PartMode {
+    /// Every note plays the main sound.
+    Single,
+    #[name = "Multi-timbral"]
+    MultiTimbral,
+    /// Notes play every zone whose key and velocity ranges they fall in.
+    Zones,
+}
+
+#[derive(Params)]
+pub struct PartParams {
+    #[id = "gain"]
+    pub gain: FloatParam,
+
+    /// Transpose in semitones, applied when a note starts.
+    #[id = "transpose"]
+    pub transpose: IntParam,
+
+    /// Tuning in cents on top of the global tuning, applied to held notes as well.
+    #[id = "fine_tune"]
+    pub fine_tune: FloatParam,
+
+    #[id = "attack"]
+    pub attack: FloatParam,
+
+    #[id = "decay"]
+    pub decay: FloatParam,
+
+    #[id = "sustain"]
+    pub sustain: FloatParam,
+
+    #[id = "release"]
+    pub release: FloatParam,
+}
+
+impl Default for PartParams {
+    fn default() -> Self {
+        Self {
+            gain: FloatParam::new(
+                "Gain",
+                util::db_to_gain(-12.0),
+                FloatRange::Skewed {
+                    min: util::db_to_gain(-30.0),
+                    max: util::db_to_gain(0.0),
+                    factor: FloatRange::gain_skew_factor(-30.0, 0.0),
+                },
+            )
+            .with_smoother(SmoothingStyle::Logarithmic(50.0))
+            .with_unit(" dB")
+            .with_value_to_string(formatters::v2s_f32_gain_to_db(2))
+            .with_string_to_value(formatters::s2v_f32_gain_to_db()),
+
+            transpose: IntParam::new("Transpose", 0, IntRange::Linear { min: -24, max: 24 })
+                .with_unit(" st"),
+            fine_tune: FloatParam::new(
+                "Fine Tune",
+                0.0,
+                FloatRange::Linear {
+                    min: -100.0,
+                    max: 100.0,
+                },
+            )
+            .with_smoother(SmoothingStyle::Linear(20.0))
+            .with_unit(" cents")
+            .with_value_to_string(formatters::v2s_f32_rounded(1)),
+
+            attack: FloatParam::new(
+                "Attack",
+                0.01,
+                FloatRange::Skewed {
+                    min: 0.001,
+                    max: 5.0,
+                    factor: 0.25,
+                },
+            )
+            .with_value_to_string(v2s_f32_s_then_ms(1))
+            .with_string_to_value(s2v_f32_s_then_ms()),
+
+            decay: FloatParam::new(
+                "Decay",
+                0.1,
+                FloatRange::Skewed {
+                    min: 0.001,
+                    max: 5.0,
+                    factor: 0.25,
+                },
+            )
+            .with_value_to_string(v2s_f32_s_then_ms(1))
+            .with_string_to_value(s2v_f32_s_then_ms()),
+
+            sustain: FloatParam::new("Sustain", 0.7, FloatRange::Linear { min: 0.0, max: 1.0 })
+                .with_value_to_string(formatters::v2s_f32_percentage(1)),
+
+            release: FloatParam::new(
+                "Release",
+                0.2,
+                FloatRange::Skewed {
+                    min: 0.001,
+                    max: 5.0,
+                    factor: 0.25,
+                },
+            )
+            .with_value_to_string(v2s_f32_s_then_ms(1))
+            .with_string_to_value(s2v_f32_s_then_ms()),
+        }
+    }
+}
+
+/// The part a MIDI channel plays in multi-timbral mode, if any.
+pub fn part_for_channel(channel: u8) -> Option<usize> {
+    let part = channel as usize;
+    (part < NUM_PARTS).then_some(part)
+}
+
+/// A key and velocity range that plays the part with the same index.
+#[derive(Params)]
+pub struct ZoneParams {
+    #[id = "key_low"]
+    pub key_low: IntParam,
+
+    #[id = "key_high"]
+    pub key_high: IntParam,
+
+    /// Transpose in semitones, added to the part's transpose.
+    #[id = "transpose"]
+    pub transpose: IntParam,
+
+    #[id = "velocity_low"]
+    pub velocity_low: IntParam,
+
+    #[id = "velocity_high"]
+    pub velocity_high: IntParam,
+}
+
+impl Default for ZoneParams {
+    fn default() -> Self {
+        Self {
+            key_low: IntParam::new("Key Low", 0, IntRange::Linear { min: 0, max: 127 })
+                .with_value_to_string(formatters::v2s_i32_note_formatter())
+                .with_string_to_value(formatters::s2v_i32_note_formatter()),
+            key_high: IntParam::new("Key High", 127, IntRange::Linear { min: 0, max: 127 })
+                .with_value_to_string(formatters::v2s_i32_note_formatter())
+                .with_string_to_value(formatters::s2v_i32_note_formatter()),
+            transpose: IntParam::new("Transpose", 0, IntRange::Linear { min: -24, max: 24 })
+                .with_unit(" st"),
+            velocity_low: IntParam::new("Velocity Low", 1, IntRange::Linear { min: 1, max: 127 }),
+            velocity_high: IntParam::new(
+                "Velocity High",
+                127,
+                IntRange::Linear { min: 1, max: 127 },
+            ),
+        }
+    }
+}
+
+impl ZoneParams {
+    /// Whether a note with a MIDI velocity between 1 and 127 falls in this zone.
+    pub fn contains(&self, note: u8, velocity: u8) -> bool {
+        let note = note as i32;
+        let velocity = velocity as i32;
+        (self.key_low.value()..=self.key_high.value()).contains(&note)
+            && (self.velocity_low.value()..=self.velocity_high.value()).contains(&velocity)
+    }
+}
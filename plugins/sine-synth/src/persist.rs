@@ -0,0 +1,99 @@
+//! Versioned formats for the state saved with `#[persist]` fields, the things that aren't
+//! parameters but still need to come back with the session.
+//!
+//! Each field is saved as `{"version": n, "data": ...}`, so a later version of the synth can tell
+//! which format it's reading and migrate it. State saved before the fields were versioned has no
+//! envelope and loads as version 0.
+
+use crate::cc::CcMapping;
+use serde::de::{DeserializeOwned, Error as _};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::Value;
+use std::cmp::Ordering;
+use std::ops::{Deref, DerefMut};
+
+/// A persisted format and how to read its older versions.
+pub trait Migrate: Serialize + DeserializeOwned {
+    /// The current format. Bump it whenever the saved format changes, and handle the older
+    /// version in [`migrate()`][Self::migrate()].
+    const VERSION: u32;
+
+    /// Read data saved in an older format. The default parses it as the current format, which is
+    /// all that's needed while new fields have serde defaults.
+    fn migrate(version: u32, data: Value) -> serde_json::Result<Self> {
+        let _ = version;
+        serde_json::from_value(data)
+    }
+}
+
+/// Version 1 only added the envelope.
+impl Migrate for Vec<CcMapping> {
+    const VERSION: u32 = 1;
+}
+
+/// The chord's intervals. Version 1 only added the envelope.
+impl Migrate for Vec<u8> {
+    const VERSION: u32 = 1;
+}
+
+/// A persisted value saved together with its format version. Derefs to the value.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Versioned<T>(pub T);
+
+impl<T> Deref for Versioned<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for Versioned<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+#[derive(Serialize)]
+struct Envelope<'a, T> {
+    version: u32,
+    data: &'a T,
+}
+
+impl<T: Migrate> Serialize for Versioned<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        Envelope {
+            version: T::VERSION,
+            data: &self.0,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de, T: Migrate> Deserialize<'de> for Versioned<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let (version, data) = match Value::deserialize(deserializer)? {
+            Value::Object(mut envelope) if envelope.len() == 2 && envelope.contains_key("data") => {
+                let version = envelope
+                    .get("version")
+                    .and_then(Value::as_u64)
+                    .ok_or_else(|| D::Error::missing_field("version"))?;
+                (version, envelope.remove("data").unwrap_or_default())
+            }
+            unversioned => (0, unversioned),
+        };
+
+        let value = match version.cmp(&u64::from(T::VERSION)) {
+            Ordering::Equal => serde_json::from_value(data),
+            Ordering::Less => T::migrate(version as u32, data),
+            Ordering::Greater => {
+                return Err(D::Error::custom(format!(
+                    "saved by a newer version of the plugin (format {version}, this one reads up \
+                     to {})",
+                    T::VERSION
+                )))
+            }
+        };
+        value.map(Versioned).map_err(D::Error::custom)
+    }
+}
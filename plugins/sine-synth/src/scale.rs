@@ -0,0 +1,70 @@
+//! Scale lock, which quantizes incoming notes to a scale before they're assigned to voices.
+
+use dsp_core::midi::{Scale, ScaleQuantizer};
+use nih_plug::prelude::*;
+use std::sync::Arc;
+
+const PITCH_CLASS_NAMES: [&str; 12] = [
+    "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+pub enum ScaleLock {
+    Off,
+    Major,
+    #[name = "Natural Minor"]
+    NaturalMinor,
+    #[name = "Harmonic Minor"]
+    HarmonicMinor,
+    #[name = "Melodic Minor"]
+    MelodicMinor,
+    Dorian,
+    Phrygian,
+    Lydian,
+    Mixolydian,
+    Locrian,
+    #[name = "Major Pentatonic"]
+    MajorPentatonic,
+    #[name = "Minor Pentatonic"]
+    MinorPentatonic,
+    Blues,
+}
+
+impl ScaleLock {
+    /// A quantizer for this scale, or `None` when scale lock is off.
+    pub fn quantizer(self, root: i32) -> Option<ScaleQuantizer> {
+        let scale = match self {
+            ScaleLock::Off => return None,
+            ScaleLock::Major => Scale::Major,
+            ScaleLock::NaturalMinor => Scale::NaturalMinor,
+            ScaleLock::HarmonicMinor => Scale::HarmonicMinor,
+            ScaleLock::MelodicMinor => Scale::MelodicMinor,
+            ScaleLock::Dorian => Scale::Dorian,
+            ScaleLock::Phrygian => Scale::Phrygian,
+            ScaleLock::Lydian => Scale::Lydian,
+            ScaleLock::Mixolydian => Scale::Mixolydian,
+            ScaleLock::Locrian => Scale::Locrian,
+            ScaleLock::MajorPentatonic => Scale::MajorPentatonic,
+            ScaleLock::MinorPentatonic => Scale::MinorPentatonic,
+            ScaleLock::Blues => Scale::Blues,
+        };
+
+        Some(ScaleQuantizer::new(scale, root.rem_euclid(12) as u8))
+    }
+}
+
+/// Formats a pitch class from 0 to 11 as its note name.
+pub fn v2s_pitch_class() -> Arc<dyn Fn(i32) -> String + Send + Sync> {
+    Arc::new(|value| PITCH_CLASS_NAMES[value.rem_euclid(12) as usize].to_owned())
+}
+
+/// Parses a note name like `F#` back to its pitch class.
+pub fn s2v_pitch_class() -> Arc<dyn Fn(&str) -> Option<i32> + Send + Sync> {
+    Arc::new(|string| {
+        let string = string.trim();
+        PITCH_CLASS_NAMES
+            .iter()
+            .position(|name| name.eq_ignore_ascii_case(string))
+            .map(|pitch_class| pitch_class as i32)
+    })
+}
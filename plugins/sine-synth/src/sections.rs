@@ -0,0 +1,120 @@
+//! Parameters for the synth's oscillator and envelope sections, nested so hosts list them in
+//! their own groups. The nesting doesn't change the parameter IDs, so projects saved before the
+//! grouping still load.
+
+use crate::onset::{StartPhase, MAX_ONSET_MS};
+use nih_plug::prelude::*;
+use plugin_scaffold::formatters::{
+    s2v_f32_ms_then_s, s2v_f32_s_then_ms, v2s_f32_ms_then_s, v2s_f32_s_then_ms,
+};
+
+#[derive(Params)]
+pub struct OscParams {
+    /// Tuning in semitones, applied to every voice including held ones.
+    #[id = "coarse_tune"]
+    pub coarse_tune: IntParam,
+
+    /// Tuning in cents, applied to every voice including held ones.
+    #[id = "fine_tune"]
+    pub fine_tune: FloatParam,
+
+    /// Where in the cycle new voices start.
+    #[id = "start_phase"]
+    pub start_phase: EnumParam<StartPhase>,
+
+    /// How long new voices take to fade in, on top of the attack.
+    #[id = "onset_ramp"]
+    pub onset_ramp: FloatParam,
+}
+
+impl Default for OscParams {
+    fn default() -> Self {
+        Self {
+            coarse_tune: IntParam::new("Coarse Tune", 0, IntRange::Linear { min: -24, max: 24 })
+                .with_unit(" st"),
+            fine_tune: FloatParam::new(
+                "Fine Tune",
+                0.0,
+                FloatRange::Linear {
+                    min: -100.0,
+                    max: 100.0,
+                },
+            )
+            .with_smoother(SmoothingStyle::Linear(20.0))
+            .with_unit(" cents")
+            .with_value_to_string(formatters::v2s_f32_rounded(1)),
+
+            start_phase: EnumParam::new("Start Phase", StartPhase::Fixed),
+            onset_ramp: FloatParam::new(
+                "Onset Ramp",
+                1.0,
+                FloatRange::Linear {
+                    min: 0.0,
+                    max: MAX_ONSET_MS,
+                },
+            )
+            .with_value_to_string(v2s_f32_ms_then_s(2))
+            .with_string_to_value(s2v_f32_ms_then_s()),
+        }
+    }
+}
+
+#[derive(Params)]
+pub struct EnvParams {
+    #[id = "attack"]
+    pub attack: FloatParam,
+
+    #[id = "decay"]
+    pub decay: FloatParam,
+
+    #[id = "sustain"]
+    pub sustain: FloatParam,
+
+    #[id = "release"]
+    pub release: FloatParam,
+}
+
+impl Default for EnvParams {
+    fn default() -> Self {
+        Self {
+            attack: FloatParam::new(
+                "Attack",
+                0.01,
+                FloatRange::Skewed {
+                    min: 0.001,
+                    max: 5.0,
+                    factor: 0.25,
+                },
+            )
+            .with_value_to_string(v2s_f32_s_then_ms(1))
+            .with_string_to_value(s2v_f32_s_then_ms()),
+
+            decay: FloatParam::new(
+                "Decay",
+                0.1,
+                FloatRange::Skewed {
+                    min: 0.001,
+                    max: 5.0,
+                    factor: 0.25,
+                },
+            )
+            .with_value_to_string(v2s_f32_s_then_ms(1))
+            .with_string_to_value(s2v_f32_s_then_ms()),
+
+            sustain: FloatParam::new("Sustain", 0.7, FloatRange::Linear { min: 0.0, max: 1.0 })
+                .with_value_to_string(formatters::v2s_f32_percentage(1)),
+
+            release: FloatParam::new(
+                "Release",
+                0.2,
+                FloatRange::Skewed {
+                    min: 0.001,
+                    max: 5.0,
+                    factor: 0.25,
+                },
+            )
+            .with_value_to_string(v2s_f32_s_then_ms(1))
+            .with_string_to_value(s2v_f32_s_then_ms()),
+        }
+    }
+}
@@ -0,0 +1,328 @@
+//! SysEx patch dumps so hardware-style librarians can back up and restore patches over MIDI.
+//!
+//! Messages use the non-commercial manufacturer ID:
+//!
+//! - Dump request: `F0 7D 01 01 F7`
+//! - Patch dump: `F0 7D 01 02 <value hi, value lo>... <checksum> F7`
+//!
+//! Values are the normalized parameter values as 14-bit numbers, in the order of
+//! [`PATCH_PARAM_IDS`]. The checksum makes the 7-bit sum of the value bytes and the checksum zero,
+//! like Roland's.
+
+use crate::SynthParams;
+use nih_plug::prelude::*;
+use std::sync::atomic::AtomicBool;
+use std::sync::Mutex;
+use ui_widgets::undo::ParamChange;
+
+const SYSEX_START: u8 = 0xF0;
+const SYSEX_END: u8 = 0xF7;
+const MANUFACTURER_ID: u8 = 0x7D;
+const DEVICE_ID: u8 = 0x01;
+const DUMP_REQUEST: u8 = 0x01;
+const PATCH_DUMP: u8 = 0x02;
+
+/// The parameters stored in a patch dump, in order. New parameters must be appended so older
+/// dumps keep loading; values missing from a dump leave the parameter untouched.
+pub const PATCH_PARAM_IDS: &[&str] = &[
+    "gain",
+    "attack",
+    "decay",
+    "sustain",
+    "release",
+    "coarse_tune",
+    "fine_tune",
+];
+
+/// Room for future parameters without changing the message buffer size.
+const MAX_PATCH_PARAMS: usize = 32;
+const HEADER_LEN: usize = 4;
+const BUFFER_SIZE: usize = HEADER_LEN + MAX_PATCH_PARAMS * 2 + 2;
+
+const MAX_14_BIT: f32 = 16383.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SynthSysEx {
+    DumpRequest,
+    PatchDump(Patch),
+}
+
+/// Normalized parameter values in [`PATCH_PARAM_IDS`] order, stored as 14-bit integers.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Patch {
+    values: [u16; MAX_PATCH_PARAMS],
+    len: usize,
+}
+
+impl Patch {
+    pub fn from_normalized(values: impl IntoIterator<Item = f32>) -> Self {
+        let mut patch = Patch {
+            values: [0; MAX_PATCH_PARAMS],
+            len: 0,
+        };
+        for value in values.into_iter().take(MAX_PATCH_PARAMS) {
+            patch.values[patch.len] = (value.clamp(0.0, 1.0) * MAX_14_BIT).round() as u16;
+            patch.len += 1;
+        }
+
+        patch
+    }
+
+    pub fn normalized(&self) -> impl Iterator<Item = f32> + '_ {
+        self.values[..self.len]
+            .iter()
+            .map(|&value| value as f32 / MAX_14_BIT)
+    }
+}
+
+impl SysExMessage for SynthSysEx {
+    type Buffer = [u8; BUFFER_SIZE];
+
+    fn from_buffer(buffer: &[u8]) -> Option<Self> {
+        match buffer {
+            [SYSEX_START, MANUFACTURER_ID, DEVICE_ID, DUMP_REQUEST, SYSEX_END] => {
+                Some(SynthSysEx::DumpRequest)
+            }
+            [SYSEX_START, MANUFACTURER_ID, DEVICE_ID, PATCH_DUMP, payload @ .., SYSEX_END] => {
+                let (checksum, data) = payload.split_last()?;
+                if !data.len().is_multiple_of(2)
+                    || data.len() / 2 > MAX_PATCH_PARAMS
+                    || data.iter().any(|&byte| byte > 0x7F)
+                    || checksum_of(data) != *checksum
+                {
+                    return None;
+                }
+
+                let mut patch = Patch {
+                    values: [0; MAX_PATCH_PARAMS],
+                    len: data.len() / 2,
+                };
+                for (value, bytes) in patch.values.iter_mut().zip(data.chunks_exact(2)) {
+                    *value = ((bytes[0] as u16) << 7) | bytes[1] as u16;
+                }
+
+                Some(SynthSysEx::PatchDump(patch))
+            }
+            _ => None,
+        }
+    }
+
+    fn to_buffer(self) -> (Self::Buffer, usize) {
+        let mut buffer = [0; BUFFER_SIZE];
+        buffer[..HEADER_LEN].copy_from_slice(&[SYSEX_START, MANUFACTURER_ID, DEVICE_ID, 0]);
+
+        let len = match self {
+            SynthSysEx::DumpRequest => {
+                buffer[3] = DUMP_REQUEST;
+                HEADER_LEN
+            }
+            SynthSysEx::PatchDump(patch) => {
+                buffer[3] = PATCH_DUMP;
+                let data_end = HEADER_LEN + patch.len * 2;
+                for (bytes, value) in buffer[HEADER_LEN..data_end]
+                    .chunks_exact_mut(2)
+                    .zip(&patch.values[..patch.len])
+                {
+                    bytes[0] = (value >> 7) as u8 & 0x7F;
+                    bytes[1] = *value as u8 & 0x7F;
+                }
+                buffer[data_end] = checksum_of(&buffer[HEADER_LEN..data_end]);
+                data_end + 1
+            }
+        };
+        buffer[len] = SYSEX_END;
+
+        (buffer, len + 1)
+    }
+}
+
+fn checksum_of(data: &[u8]) -> u8 {
+    let sum = data
+        .iter()
+        .fold(0u8, |sum, &byte| sum.wrapping_add(byte) & 0x7F);
+    (0x80 - sum) & 0x7F
+}
+
+/// SysEx state shared between the audio thread and the editor. Plugins can't set their own
+/// parameters from the audio thread, so received patches are handed to the editor, which applies
+/// them through the host like any other parameter change.
+///
+/// The `ParamPtr`s point into the plugin's `SynthParams`, which outlives both the editor and the
+/// audio thread, so dereferencing them is always safe.
+pub struct SysExState {
+    /// Pointers to the [`PATCH_PARAM_IDS`] parameters, in order.
+    params: Vec<ParamPtr>,
+    /// The most recently received patch dump, waiting for the editor to apply it.
+    pub incoming_patch: Mutex<Option<Patch>>,
+    /// Set by the editor to have the audio thread send a patch dump.
+    pub dump_requested: AtomicBool,
+}
+
+impl SysExState {
+    pub fn new(params: &SynthParams) -> Self {
+        let param_map = params.param_map();
+        let params = PATCH_PARAM_IDS
+            .iter()
+            .filter_map(|id| {
+                param_map
+                    .iter()
+                    .find(|(param_id, _, _)| param_id.as_str() == *id)
+                    .map(|(_, param_ptr, _)| *param_ptr)
+            })
+            .collect();
+
+        Self {
+            params,
+            incoming_patch: Mutex::new(None),
+            dump_requested: AtomicBool::new(false),
+        }
+    }
+
+    /// Capture the current parameter values.
+    pub fn current_patch(&self) -> Patch {
+        // SAFETY: See the struct documentation
+        Patch::from_normalized(
+            self.params
+                .iter()
+                .map(|param| unsafe { param.unmodulated_normalized_value() }),
+        )
+    }
+
+    /// The parameter changes applying `patch` would make, for the editor's undo history.
+    pub fn patch_changes<'a>(&'a self, patch: &'a Patch) -> impl Iterator<Item = ParamChange> + 'a {
+        self.params
+            .iter()
+            .zip(patch.normalized())
+            .map(|(&param, after)| ParamChange {
+                param,
+                // SAFETY: See the struct documentation
+                before: unsafe { param.unmodulated_normalized_value() },
+                after,
+            })
+    }
+
+    /// Apply a received patch through the host. Must be called from the GUI thread.
+    pub fn apply_patch(&self, setter: &ParamSetter, patch: &Patch) {
+        for (&param, value) in self.params.iter().zip(patch.normalized()) {
+            // SAFETY: See the struct documentation
+            unsafe {
+                setter.raw_context.raw_begin_set_parameter(param);
+                setter
+                    .raw_context
+                    .raw_set_parameter_normalized(param, value);
+                setter.raw_context.raw_end_set_parameter(param);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(message: SynthSysEx) -> Vec<u8> {
+        let (buffer, len) = message.to_buffer();
+        buffer[..len].to_vec()
+    }
+
+    fn patch_dump(data: &[u8]) -> Vec<u8> {
+        let mut message = vec![SYSEX_START, MANUFACTURER_ID, DEVICE_ID, PATCH_DUMP];
+        message.extend_from_slice(data);
+        message.push(checksum_of(data));
+        message.push(SYSEX_END);
+        message
+    }
+
+    #[test]
+    fn messages_round_trip() {
+        let request = encode(SynthSysEx::DumpRequest);
+        assert_eq!(request, [0xF0, 0x7D, 0x01, 0x01, 0xF7]);
+        assert_eq!(
+            SynthSysEx::from_buffer(&request),
+            Some(SynthSysEx::DumpRequest)
+        );
+
+        let values = [0.0, 0.25, 0.5, 0.75, 1.0, 1.0 / MAX_14_BIT, 0.123];
+        let patch = Patch::from_normalized(values);
+        let dump = encode(SynthSysEx::PatchDump(patch));
+        assert_eq!(dump.len(), HEADER_LEN + values.len() * 2 + 2);
+        assert!(dump[1..dump.len() - 1].iter().all(|&byte| byte <= 0x7F));
+
+        let Some(SynthSysEx::PatchDump(decoded)) = SynthSysEx::from_buffer(&dump) else {
+            panic!("{dump:02X?} didn't decode");
+        };
+        assert_eq!(decoded, patch);
+        for (decoded, value) in decoded.normalized().zip(values) {
+            assert!(
+                (decoded - value).abs() <= 0.5 / MAX_14_BIT,
+                "{decoded} vs {value}"
+            );
+        }
+    }
+
+    #[test]
+    fn values_are_packed_into_two_seven_bit_bytes() {
+        let dump = encode(SynthSysEx::PatchDump(Patch::from_normalized([1.0, 0.0])));
+        assert_eq!(&dump[HEADER_LEN..HEADER_LEN + 4], [0x7F, 0x7F, 0x00, 0x00]);
+        // 0x7F + 0x7F + checksum must be a multiple of 0x80
+        assert_eq!(dump[HEADER_LEN + 4], 0x02);
+    }
+
+    #[test]
+    fn out_of_range_values_are_clamped() {
+        let patch = Patch::from_normalized([-1.0, 2.0]);
+        assert_eq!(patch.normalized().collect::<Vec<_>>(), [0.0, 1.0]);
+    }
+
+    #[test]
+    fn bad_checksums_are_rejected() {
+        let mut dump = encode(SynthSysEx::PatchDump(Patch::from_normalized([0.5, 0.25])));
+        let checksum = dump.len() - 2;
+        dump[checksum] = (dump[checksum] + 1) & 0x7F;
+        assert_eq!(SynthSysEx::from_buffer(&dump), None);
+
+        let mut dump = encode(SynthSysEx::PatchDump(Patch::from_normalized([0.5, 0.25])));
+        dump[HEADER_LEN + 1] ^= 0x01;
+        assert_eq!(SynthSysEx::from_buffer(&dump), None);
+    }
+
+    #[test]
+    fn truncated_dumps_are_rejected() {
+        let dump = encode(SynthSysEx::PatchDump(Patch::from_normalized([0.5, 0.25])));
+        for len in 0..dump.len() {
+            assert_eq!(SynthSysEx::from_buffer(&dump[..len]), None, "{len} bytes");
+        }
+
+        // A dump without a checksum
+        assert_eq!(
+            SynthSysEx::from_buffer(&[
+                SYSEX_START,
+                MANUFACTURER_ID,
+                DEVICE_ID,
+                PATCH_DUMP,
+                SYSEX_END
+            ]),
+            None
+        );
+    }
+
+    #[test]
+    fn malformed_payloads_are_rejected() {
+        // A valid checksum doesn't save a dangling value byte
+        assert_eq!(
+            SynthSysEx::from_buffer(&patch_dump(&[0x10, 0x20, 0x30])),
+            None
+        );
+        assert_eq!(SynthSysEx::from_buffer(&patch_dump(&[0x10, 0x80])), None);
+        assert_eq!(
+            SynthSysEx::from_buffer(&patch_dump(&[0; (MAX_PATCH_PARAMS + 1) * 2])),
+            None
+        );
+
+        // An empty dump is valid, it just doesn't change anything
+        let Some(SynthSysEx::PatchDump(empty)) = SynthSysEx::from_buffer(&patch_dump(&[])) else {
+            panic!("an empty dump didn't decode");
+        };
+        assert_eq!(empty.normalized().count(), 0);
+    }
+}
@@ -0,0 +1,45 @@
+//! Lock-free transfer of the synth's output from the audio thread to the editor's oscilloscope and
+//! spectrum displays.
+
+/// Number of samples in each snapshot sent to the editor. Also the FFT size.
+pub const SNAPSHOT_SIZE: usize = 2048;
+
+pub type Snapshot = [f32; SNAPSHOT_SIZE];
+pub type VisualizerOutput = triple_buffer::Output<Snapshot>;
+
+/// Audio thread side of the visualizer channel. Samples are collected in a ring buffer and the
+/// most recent [`SNAPSHOT_SIZE`] samples are published through a triple buffer, so neither side
+/// ever blocks and the editor always sees the latest output.
+pub struct VisualizerInput {
+    ring: Box<Snapshot>,
+    write_pos: usize,
+    input: triple_buffer::Input<Snapshot>,
+}
+
+pub fn channel() -> (VisualizerInput, VisualizerOutput) {
+    let (input, output) = triple_buffer::TripleBuffer::new(&[0.0; SNAPSHOT_SIZE]).split();
+    let input = VisualizerInput {
+        ring: Box::new([0.0; SNAPSHOT_SIZE]),
+        write_pos: 0,
+        input,
+    };
+
+    (input, output)
+}
+
+impl VisualizerInput {
+    #[inline]
+    pub fn push(&mut self, sample: f32) {
+        self.ring[self.write_pos] = sample;
+        self.write_pos = (self.write_pos + 1) % SNAPSHOT_SIZE;
+    }
+
+    /// Publish the collected samples, oldest first. Only worth doing while the editor is open.
+    pub fn publish(&mut self) {
+        let (newest, oldest) = self.ring.split_at(self.write_pos);
+        let snapshot = self.input.input_buffer_mut();
+        snapshot[..oldest.len()].copy_from_slice(oldest);
+        snapshot[oldest.len()..].copy_from_slice(newest);
+        self.input.publish();
+    }
+}
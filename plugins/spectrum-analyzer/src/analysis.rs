@@ -0,0 +1,172 @@
+//! Turns snapshots of the input into a calibrated magnitude spectrum, with averaging and peak
+//! hold. This runs on the GUI thread, so it's free to allocate when the FFT size changes.
+
+use dsp_core::fft::{bin_frequency, coherent_gain, Complex32, Fft, Window};
+use nih_plug::util;
+
+/// Blackman-Harris keeps leakage below the display's floor, so quiet partials next to loud ones
+/// stay visible.
+const WINDOW: Window = Window::BlackmanHarris;
+
+pub struct Analyzer {
+    fft: Fft,
+    window: Vec<f32>,
+    /// Turns bin magnitudes into the amplitude of the sinusoid they came from, so a full scale
+    /// sine reads as 0 dBFS whatever the FFT size.
+    scale: f32,
+    frame: Vec<f32>,
+    spectrum: Vec<Complex32>,
+    /// Each bin's averaged power.
+    power: Vec<f32>,
+
+    pub magnitudes_db: Vec<f32>,
+    /// The highest level each bin has reached since the peaks were last reset.
+    pub peaks_db: Vec<f32>,
+}
+
+impl Analyzer {
+    pub fn new(fft_size: usize) -> Self {
+        let fft = Fft::new(fft_size);
+        let window = WINDOW.build(fft_size);
+        let num_bins = fft.num_bins();
+
+        Self {
+            scale: 2.0 / (fft_size as f32 * coherent_gain(&window)),
+            window,
+            frame: vec![0.0; fft_size],
+            spectrum: fft.make_spectrum(),
+            power: vec![0.0; num_bins],
+            magnitudes_db: vec![util::MINUS_INFINITY_DB; num_bins],
+            peaks_db: vec![util::MINUS_INFINITY_DB; num_bins],
+            fft,
+        }
+    }
+
+    pub fn fft_size(&self) -> usize {
+        self.fft.size()
+    }
+
+    /// Analyze the newest [`fft_size()`][Self::fft_size()] samples, which must be at least that
+    /// many. `elapsed` is the time since the last update and `averaging` the time constant for
+    /// the exponential average over power, both in seconds. Zero averaging shows every update as
+    /// is.
+    pub fn update(&mut self, samples: &[f32], elapsed: f32, averaging: f32) {
+        let newest = &samples[samples.len() - self.fft.size()..];
+        for ((frame, sample), window) in self.frame.iter_mut().zip(newest).zip(&self.window) {
+            *frame = sample * window;
+        }
+        self.fft.forward(&mut self.frame, &mut self.spectrum);
+
+        let keep = if averaging > 0.0 {
+            (-elapsed / averaging).exp()
+        } else {
+            0.0
+        };
+        for (((power, db), peak_db), bin) in self
+            .power
+            .iter_mut()
+            .zip(&mut self.magnitudes_db)
+            .zip(&mut self.peaks_db)
+            .zip(&self.spectrum)
+        {
+            let magnitude = bin.norm() * self.scale;
+            *power = *power * keep + magnitude * magnitude * (1.0 - keep);
+            *db = util::gain_to_db(power.sqrt());
+            *peak_db = peak_db.max(*db);
+        }
+    }
+
+    pub fn reset_peaks(&mut self) {
+        self.peaks_db.fill(util::MINUS_INFINITY_DB);
+    }
+
+    /// The frequency and level of the loudest bin, refined by fitting a parabola through it and
+    /// its neighbours.
+    pub fn loudest(&self, sample_rate: f32) -> Option<(f32, f32)> {
+        let db = &self.magnitudes_db;
+        let bin = (1..db.len() - 1).max_by(|&a, &b| db[a].total_cmp(&db[b]))?;
+        let (left, center, right) = (db[bin - 1], db[bin], db[bin + 1]);
+
+        let curvature = left - 2.0 * center + right;
+        let offset = if curvature < 0.0 {
+            0.5 * (left - right) / curvature
+        } else {
+            0.0
+        };
+        let frequency = bin_frequency(bin, self.fft.size(), sample_rate)
+            + offset * sample_rate / self.fft.size() as f32;
+
+        Some((frequency, center - 0.25 * (left - right) * offset))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: f32 = 48000.0;
+    const FFT_SIZE: usize = 4096;
+
+    fn sine(frequency: f32, amplitude: f32) -> Vec<f32> {
+        (0..FFT_SIZE)
+            .map(|i| (std::f32::consts::TAU * frequency * i as f32 / SAMPLE_RATE).sin() * amplitude)
+            .collect()
+    }
+
+    #[test]
+    fn full_scale_sine_reads_zero_dbfs() {
+        let mut analyzer = Analyzer::new(FFT_SIZE);
+        for frequency in [1000.0, 1003.0, 5000.0] {
+            analyzer.update(&sine(frequency, 1.0), 0.0, 0.0);
+
+            let (peak_frequency, peak_db) = analyzer.loudest(SAMPLE_RATE).unwrap();
+            assert!(peak_db.abs() < 0.5, "{frequency} Hz read as {peak_db} dBFS");
+            assert!(
+                (peak_frequency - frequency).abs() < 2.0,
+                "{frequency} Hz read as {peak_frequency} Hz"
+            );
+        }
+
+        analyzer.update(&sine(1000.0, 0.5), 0.0, 0.0);
+        let (_, peak_db) = analyzer.loudest(SAMPLE_RATE).unwrap();
+        assert!(
+            (peak_db + 6.02).abs() < 0.5,
+            "half scale read as {peak_db} dBFS"
+        );
+    }
+
+    #[test]
+    fn peaks_are_held_after_the_signal_stops() {
+        let mut analyzer = Analyzer::new(FFT_SIZE);
+        analyzer.update(&sine(1000.0, 1.0), 0.0, 0.0);
+        analyzer.update(&[0.0; FFT_SIZE], 0.1, 0.0);
+
+        let (_, level) = analyzer.loudest(SAMPLE_RATE).unwrap();
+        let held = analyzer.peaks_db.iter().copied().fold(f32::MIN, f32::max);
+        assert!(level < -90.0);
+        assert!(held > -0.5);
+
+        analyzer.reset_peaks();
+        assert!(analyzer
+            .peaks_db
+            .iter()
+            .all(|&db| db == util::MINUS_INFINITY_DB));
+    }
+
+    #[test]
+    fn averaging_smooths_level_changes() {
+        let mut analyzer = Analyzer::new(FFT_SIZE);
+        analyzer.update(&sine(1000.0, 1.0), 0.0, 0.3);
+        let (_, first) = analyzer.loudest(SAMPLE_RATE).unwrap();
+
+        // Starting from silence the first update barely registers, and one time constant
+        // later the power has covered about 63% of the way
+        let mut level = first;
+        for _ in 0..30 {
+            analyzer.update(&sine(1000.0, 1.0), 0.01, 0.3);
+            level = analyzer.loudest(SAMPLE_RATE).unwrap().1;
+        }
+        assert!(first < -20.0, "{first}");
+        assert!((level - 10.0 * 0.632f32.log10()).abs() < 0.5, "{level}");
+    }
+}
@@ -0,0 +1,101 @@
+use crate::analysis::Analyzer;
+use crate::snapshot::SnapshotOutput;
+use crate::AnalyzerParams;
+use atomic_float::AtomicF32;
+use nih_plug::prelude::*;
+use nih_plug_egui::create_egui_editor;
+use nih_plug_egui::egui::Vec2;
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
+use ui_widgets::{param_combo, param_toggle, EditorParams, EditorShell, ParamKnob, SpectrumPanel};
+
+/// The level at the top of the display, leaving some headroom above full scale.
+const CEILING_DB: f32 = 6.0;
+const DB_GRID_STEP: f32 = 12.0;
+
+pub(crate) fn default_params() -> EditorParams {
+    EditorParams::new((640, 440))
+}
+
+/// GUI-thread editor state.
+struct EditorState {
+    output: Arc<Mutex<SnapshotOutput>>,
+    analyzer: Analyzer,
+    peak_hold: bool,
+}
+
+impl EditorState {
+    /// Analyze the latest snapshot from the audio thread.
+    fn update(&mut self, params: &AnalyzerParams, elapsed: f32) {
+        let fft_size = params.resolution.value().fft_size();
+        if fft_size != self.analyzer.fft_size() {
+            self.analyzer = Analyzer::new(fft_size);
+        }
+
+        // Peaks held before the hold was switched on would be stale
+        let peak_hold = params.peak_hold.value();
+        if peak_hold && !self.peak_hold {
+            self.analyzer.reset_peaks();
+        }
+        self.peak_hold = peak_hold;
+
+        let mut output = self.output.lock().unwrap();
+        let averaging = params.averaging.value() / 1000.0;
+        self.analyzer.update(output.read(), elapsed, averaging);
+    }
+}
+
+pub(crate) fn create(
+    params: Arc<AnalyzerParams>,
+    output: Arc<Mutex<SnapshotOutput>>,
+    sample_rate: Arc<AtomicF32>,
+) -> Option<Box<dyn Editor>> {
+    let analyzer = Analyzer::new(params.resolution.value().fft_size());
+
+    create_egui_editor(
+        params.editor.state.clone(),
+        EditorState {
+            output,
+            analyzer,
+            peak_hold: false,
+        },
+        |_, _| {},
+        move |egui_ctx, setter, state| {
+            let elapsed = egui_ctx.input(|input| input.stable_dt);
+            state.update(&params, elapsed);
+            let sample_rate = sample_rate.load(Ordering::Relaxed);
+
+            EditorShell::new(&params.editor).show(egui_ctx, setter, |ui| {
+                ui.horizontal(|ui| {
+                    ui.add(ParamKnob::for_param(&params.averaging, setter));
+                    ui.add(ParamKnob::for_param(&params.floor, setter));
+                    ui.vertical(|ui| {
+                        param_combo(ui, &params.resolution, setter);
+                        param_toggle(ui, &params.peak_hold, setter);
+                        if ui.button("Reset peaks").clicked() {
+                            state.analyzer.reset_peaks();
+                        }
+                    });
+                });
+
+                let mut panel = SpectrumPanel::new(&state.analyzer.magnitudes_db, sample_rate)
+                    .with_db_range(params.floor.value(), CEILING_DB)
+                    .with_db_grid(DB_GRID_STEP)
+                    .with_size(Vec2::new(620.0, 280.0));
+                if state.peak_hold {
+                    panel = panel.with_peaks(&state.analyzer.peaks_db);
+                }
+                ui.add(panel);
+
+                if let Some((frequency, db)) = state.analyzer.loudest(sample_rate) {
+                    if db > params.floor.value() {
+                        ui.label(format!("Peak: {frequency:.1} Hz at {db:.1} dBFS"));
+                    }
+                }
+            });
+
+            // The display is live, so keep repainting while the editor is open
+            egui_ctx.request_repaint();
+        },
+    )
+}
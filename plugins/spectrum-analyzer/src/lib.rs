@@ -0,0 +1,235 @@
+use atomic_float::AtomicF32;
+use nih_plug::prelude::*;
+use plugin_scaffold::formatters::{s2v_f32_ms_then_s, v2s_f32_ms_then_s};
+use plugin_scaffold::layouts;
+use snapshot::{SnapshotInput, SnapshotOutput};
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
+use ui_widgets::EditorParams;
+
+mod analysis;
+mod editor;
+mod snapshot;
+
+/// Passes audio through untouched and shows its spectrum in the editor.
+struct SpectrumAnalyzer {
+    params: Arc<AnalyzerParams>,
+    snapshot: SnapshotInput,
+    snapshot_output: Arc<Mutex<SnapshotOutput>>,
+    sample_rate: Arc<AtomicF32>,
+}
+
+/// The parameters only affect the display, so they're kept out of host automation.
+#[derive(Params)]
+struct AnalyzerParams {
+    #[nested]
+    editor: EditorParams,
+
+    /// The FFT size, trading time resolution for frequency resolution.
+    #[id = "resolution"]
+    pub resolution: EnumParam<Resolution>,
+
+    /// The time constant for averaging the spectrum, in milliseconds.
+    #[id = "averaging"]
+    pub averaging: FloatParam,
+
+    #[id = "peak-hold"]
+    pub peak_hold: BoolParam,
+
+    /// The level at the bottom of the display.
+    #[id = "floor"]
+    pub floor: FloatParam,
+}
+
+#[derive(Enum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resolution {
+    #[name = "1024"]
+    Fft1024,
+    #[name = "2048"]
+    Fft2048,
+    #[name = "4096"]
+    Fft4096,
+    #[name = "8192"]
+    Fft8192,
+}
+
+impl Resolution {
+    pub fn fft_size(self) -> usize {
+        match self {
+            Resolution::Fft1024 => 1024,
+            Resolution::Fft2048 => 2048,
+            Resolution::Fft4096 => 4096,
+            Resolution::Fft8192 => 8192,
+        }
+    }
+}
+
+impl Default for SpectrumAnalyzer {
+    fn default() -> Self {
+        let (snapshot, snapshot_output) = snapshot::channel();
+
+        Self {
+            params: Arc::new(AnalyzerParams::default()),
+            snapshot,
+            snapshot_output: Arc::new(Mutex::new(snapshot_output)),
+            sample_rate: Arc::new(AtomicF32::new(44100.0)),
+        }
+    }
+}
+
+impl Default for AnalyzerParams {
+    fn default() -> Self {
+        Self {
+            editor: editor::default_params(),
+
+            resolution: EnumParam::new("Resolution", Resolution::Fft4096).non_automatable(),
+
+            averaging: FloatParam::new(
+                "Averaging",
+                300.0,
+                FloatRange::Skewed {
+                    min: 0.0,
+                    max: 3000.0,
+                    factor: FloatRange::skew_factor(-2.0),
+                },
+            )
+            .with_value_to_string(v2s_f32_ms_then_s(0))
+            .with_string_to_value(s2v_f32_ms_then_s())
+            .non_automatable(),
+
+            peak_hold: BoolParam::new("Peak Hold", true).non_automatable(),
+
+            floor: FloatParam::new(
+                "Floor",
+                -90.0,
+                FloatRange::Linear {
+                    min: -100.0,
+                    max: -36.0,
+                },
+            )
+            .with_step_size(6.0)
+            .with_unit(" dB")
+            .with_value_to_string(formatters::v2s_f32_rounded(0))
+            .non_automatable(),
+        }
+    }
+}
+
+impl Plugin for SpectrumAnalyzer {
+    const NAME: &'static str = "Spectrum Analyzer";
+    const VENDOR: &'static str = "Your Studio";
+    const URL: &'static str = env!("CARGO_PKG_HOMEPAGE");
+    const EMAIL: &'static str = "contact@yourstudio.com";
+    const VERSION: &'static str = env!("CARGO_PKG_VERSION");
+
+    const AUDIO_IO_LAYOUTS: &'static [AudioIOLayout] = &[layouts::STEREO, layouts::MONO];
+
+    type SysExMessage = ();
+    type BackgroundTask = ();
+
+    fn params(&self) -> Arc<dyn Params> {
+        self.params.clone()
+    }
+
+    fn editor(&mut self, _async_executor: AsyncExecutor<Self>) -> Option<Box<dyn Editor>> {
+        editor::create(
+            self.params.clone(),
+            self.snapshot_output.clone(),
+            self.sample_rate.clone(),
+        )
+    }
+
+    fn initialize(
+        &mut self,
+        _audio_io_layout: &AudioIOLayout,
+        buffer_config: &BufferConfig,
+        _context: &mut impl InitContext<Self>,
+    ) -> bool {
+        self.sample_rate
+            .store(buffer_config.sample_rate, Ordering::Relaxed);
+        true
+    }
+
+    fn process(
+        &mut self,
+        buffer: &mut Buffer,
+        _aux: &mut AuxiliaryBuffers,
+        _context: &mut impl ProcessContext<Self>,
+    ) -> ProcessStatus {
+        // The audio passes through untouched, the editor only needs the channels' average
+        if self.params.editor.state.is_open() {
+            self.capture(buffer.as_slice_immutable());
+        }
+
+        ProcessStatus::Normal
+    }
+}
+
+impl SpectrumAnalyzer {
+    fn capture(&mut self, channels: &[&mut [f32]]) {
+        let num_samples = channels.first().map_or(0, |channel| channel.len());
+        for i in 0..num_samples {
+            let sum: f32 = channels.iter().map(|channel| channel[i]).sum();
+            self.snapshot.push(sum / channels.len() as f32);
+        }
+        self.snapshot.publish();
+    }
+}
+
+impl ClapPlugin for SpectrumAnalyzer {
+    const CLAP_ID: &'static str = "com.yourstudio.spectrum-analyzer";
+    const CLAP_DESCRIPTION: Option<&'static str> =
+        Some("A calibrated FFT spectrum analyzer with averaging and peak hold");
+    const CLAP_MANUAL_URL: Option<&'static str> = Some(Self::URL);
+    const CLAP_SUPPORT_URL: Option<&'static str> = None;
+    const CLAP_FEATURES: &'static [ClapFeature] = &[
+        ClapFeature::AudioEffect,
+        ClapFeature::Analyzer,
+        ClapFeature::Utility,
+        ClapFeature::Stereo,
+        ClapFeature::Mono,
+    ];
+}
+
+impl Vst3Plugin for SpectrumAnalyzer {
+    const VST3_CLASS_ID: [u8; 16] = *b"SpectrumAnalyzr0";
+    const VST3_SUBCATEGORIES: &'static [Vst3SubCategory] =
+        &[Vst3SubCategory::Fx, Vst3SubCategory::Analyzer];
+}
+
+nih_export_clap!(SpectrumAnalyzer);
+nih_export_vst3!(SpectrumAnalyzer);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use plugin_scaffold::test_util::assert_no_alloc;
+
+    plugin_scaffold::install_alloc_disabler!();
+
+    const SAMPLE_RATE: f32 = 48000.0;
+    const BLOCK_SIZE: usize = 512;
+
+    #[test]
+    fn capturing_does_not_allocate() {
+        let mut plugin = SpectrumAnalyzer::default();
+        let mut left: Vec<f32> = (0..SAMPLE_RATE as usize)
+            .map(|i| (std::f32::consts::TAU * 440.0 * i as f32 / SAMPLE_RATE).sin() * 0.5)
+            .collect();
+        let mut right = vec![0.0; left.len()];
+        assert_no_alloc(|| {
+            for (left, right) in left
+                .chunks_mut(BLOCK_SIZE)
+                .zip(right.chunks_mut(BLOCK_SIZE))
+            {
+                plugin.capture(&[left, right]);
+            }
+        });
+
+        // The editor sees the average of the channels, newest sample last
+        let mut output = plugin.snapshot_output.lock().unwrap();
+        let latest = output.read();
+        let expected = left[left.len() - 1] / 2.0;
+        assert_eq!(latest[snapshot::SNAPSHOT_SIZE - 1], expected);
+    }
+}
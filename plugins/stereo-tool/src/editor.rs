@@ -0,0 +1,54 @@
+use crate::StereoToolParams;
+use nih_plug::prelude::*;
+use nih_plug_egui::create_egui_editor;
+use nih_plug_egui::egui::Vec2;
+use std::sync::Arc;
+use ui_widgets::{
+    param_toggle, CorrelationMeter, EditorParams, EditorShell, Goniometer, ParamKnob, PhaseState,
+};
+
+pub(crate) fn default_params() -> EditorParams {
+    EditorParams::new((380, 360))
+}
+
+pub(crate) fn create(
+    params: Arc<StereoToolParams>,
+    phase: Arc<PhaseState>,
+) -> Option<Box<dyn Editor>> {
+    create_egui_editor(
+        params.editor.state.clone(),
+        (),
+        |_, _| {},
+        move |egui_ctx, setter, _state| {
+            EditorShell::new(&params.editor)
+                .with_bypass(&params.bypass)
+                .show(egui_ctx, setter, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.add(Goniometer::new(&phase).with_size(140.0));
+                        ui.vertical(|ui| {
+                            ui.label("Correlation");
+                            ui.add(CorrelationMeter::new(&phase).with_size(Vec2::new(210.0, 28.0)));
+                        });
+                    });
+                    ui.separator();
+
+                    ui.horizontal(|ui| {
+                        ui.add(ParamKnob::for_param(&params.width, setter));
+                        ui.add(ParamKnob::for_param(&params.rotation, setter));
+                        ui.add(ParamKnob::for_param(&params.balance, setter));
+                        ui.add(ParamKnob::for_param(&params.output, setter));
+                    });
+                    ui.horizontal(|ui| {
+                        param_toggle(ui, &params.mono_bass, setter)
+                            .on_hover_text("Fold the low end to mono so it stays centered");
+                        ui.add_enabled_ui(params.mono_bass.value(), |ui| {
+                            ui.add(ParamKnob::for_param(&params.mono_frequency, setter));
+                        });
+                    });
+                });
+
+            // The meters are live, so keep repainting while the editor is open
+            egui_ctx.request_repaint();
+        },
+    )
+}
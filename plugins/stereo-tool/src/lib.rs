@@ -0,0 +1,421 @@
+use dsp_core::analysis::{GoniometerPoints, StereoCorrelation};
+use dsp_core::guard;
+use dsp_core::mix::MixStage;
+use dsp_core::stereo::{self, MonoBass};
+use nih_plug::prelude::*;
+use plugin_scaffold::layouts;
+use std::sync::Arc;
+use ui_widgets::{EditorParams, PhaseState};
+
+mod editor;
+
+/// How far back the correlation meter looks, in seconds.
+const CORRELATION_SECONDS: f32 = 0.3;
+
+/// The goniometer shows every this many samples.
+const GONIOMETER_DECIMATION: usize = 8;
+/// How many points the goniometer shows, about a fifth of a second at 48 kHz.
+const GONIOMETER_POINTS: usize = 1024;
+
+/// Adjusts the width, rotation, and balance of a stereo signal, and can fold the low end to
+/// mono.
+struct StereoTool {
+    params: Arc<StereoToolParams>,
+    mono_bass: MonoBass,
+    correlation: StereoCorrelation,
+    goniometer: GoniometerPoints,
+    /// The output's correlation and goniometer points for the editor.
+    phase: Arc<PhaseState>,
+    /// Only used for a click-free bypass.
+    mix: MixStage,
+}
+
+#[derive(Params)]
+struct StereoToolParams {
+    #[nested]
+    editor: EditorParams,
+
+    /// The host's bypass switch.
+    #[id = "bypass"]
+    pub bypass: BoolParam,
+
+    /// The side signal's level, from mono at 0 to twice as wide at 2.
+    #[id = "width"]
+    pub width: FloatParam,
+
+    /// Turns the stereo image, in degrees.
+    #[id = "rotation"]
+    pub rotation: FloatParam,
+
+    #[id = "mono_bass"]
+    pub mono_bass: BoolParam,
+
+    /// Everything below this is folded to mono when mono bass is on.
+    #[id = "mono_frequency"]
+    pub mono_frequency: FloatParam,
+
+    /// From -1 for only the left channel to 1 for only the right channel.
+    #[id = "balance"]
+    pub balance: FloatParam,
+
+    #[id = "output"]
+    pub output: FloatParam,
+}
+
+impl Default for StereoTool {
+    fn default() -> Self {
+        let params = Arc::new(StereoToolParams::default());
+        Self {
+            mono_bass: MonoBass::new(44100.0, params.mono_frequency.value()),
+            correlation: StereoCorrelation::new(44100.0, CORRELATION_SECONDS),
+            goniometer: GoniometerPoints::new(0, GONIOMETER_DECIMATION),
+            phase: Arc::new(PhaseState::new(GONIOMETER_POINTS)),
+            mix: MixStage::new(44100.0, 0, 0, 0),
+            params,
+        }
+    }
+}
+
+impl Default for StereoToolParams {
+    fn default() -> Self {
+        Self {
+            editor: editor::default_params(),
+
+            bypass: BoolParam::new("Bypass", false).make_bypass(),
+
+            width: FloatParam::new("Width", 1.0, FloatRange::Linear { min: 0.0, max: 2.0 })
+                .with_smoother(SmoothingStyle::Linear(20.0))
+                .with_value_to_string(formatters::v2s_f32_percentage(0))
+                .with_string_to_value(formatters::s2v_f32_percentage())
+                .with_unit(" %"),
+
+            rotation: FloatParam::new(
+                "Rotation",
+                0.0,
+                FloatRange::Linear {
+                    min: -45.0,
+                    max: 45.0,
+                },
+            )
+            .with_smoother(SmoothingStyle::Linear(20.0))
+            .with_value_to_string(formatters::v2s_f32_rounded(1))
+            .with_unit("°"),
+
+            mono_bass: BoolParam::new("Mono Bass", false),
+
+            mono_frequency: FloatParam::new(
+                "Mono Below",
+                120.0,
+                FloatRange::Skewed {
+                    min: 20.0,
+                    max: 500.0,
+                    factor: FloatRange::skew_factor(-1.0),
+                },
+            )
+            .with_value_to_string(formatters::v2s_f32_hz_then_khz(0))
+            .with_string_to_value(formatters::s2v_f32_hz_then_khz()),
+
+            balance: FloatParam::new(
+                "Balance",
+                0.0,
+                FloatRange::Linear {
+                    min: -1.0,
+                    max: 1.0,
+                },
+            )
+            .with_smoother(SmoothingStyle::Linear(20.0))
+            .with_value_to_string(formatters::v2s_f32_panning())
+            .with_string_to_value(formatters::s2v_f32_panning()),
+
+            output: FloatParam::new(
+                "Output",
+                util::db_to_gain(0.0),
+                FloatRange::Skewed {
+                    min: util::db_to_gain(-24.0),
+                    max: util::db_to_gain(12.0),
+                    factor: FloatRange::gain_skew_factor(-24.0, 12.0),
+                },
+            )
+            .with_smoother(SmoothingStyle::Logarithmic(50.0))
+            .with_unit(" dB")
+            .with_value_to_string(formatters::v2s_f32_gain_to_db(2))
+            .with_string_to_value(formatters::s2v_f32_gain_to_db()),
+        }
+    }
+}
+
+impl Plugin for StereoTool {
+    const NAME: &'static str = "Stereo Tool";
+    const VENDOR: &'static str = "Your Studio";
+    const URL: &'static str = env!("CARGO_PKG_HOMEPAGE");
+    const EMAIL: &'static str = "contact@yourstudio.com";
+    const VERSION: &'static str = env!("CARGO_PKG_VERSION");
+
+    // Everything here is about the relationship between two channels
+    const AUDIO_IO_LAYOUTS: &'static [AudioIOLayout] = &[layouts::STEREO];
+
+    type SysExMessage = ();
+    type BackgroundTask = ();
+
+    fn params(&self) -> Arc<dyn Params> {
+        self.params.clone()
+    }
+
+    fn editor(&mut self, _async_executor: AsyncExecutor<Self>) -> Option<Box<dyn Editor>> {
+        editor::create(self.params.clone(), self.phase.clone())
+    }
+
+    fn initialize(
+        &mut self,
+        _audio_io_layout: &AudioIOLayout,
+        buffer_config: &BufferConfig,
+        _context: &mut impl InitContext<Self>,
+    ) -> bool {
+        let sample_rate = buffer_config.sample_rate;
+
+        self.mono_bass = MonoBass::new(sample_rate, self.params.mono_frequency.value());
+        self.correlation = StereoCorrelation::new(sample_rate, CORRELATION_SECONDS);
+        // Room for every point in a block, since they're handed to the editor after each one
+        let max_buffer_size = buffer_config.max_buffer_size as usize;
+        self.goniometer = GoniometerPoints::new(
+            max_buffer_size / GONIOMETER_DECIMATION + 1,
+            GONIOMETER_DECIMATION,
+        );
+        self.mix = MixStage::new(sample_rate, 2, max_buffer_size, 0);
+        self.mix.set_mix(self.target_mix());
+        self.mix.reset();
+        true
+    }
+
+    fn reset(&mut self) {
+        self.mono_bass.reset();
+        self.correlation.reset();
+        self.goniometer.clear();
+        self.phase.reset();
+        self.mix.reset();
+    }
+
+    fn process(
+        &mut self,
+        buffer: &mut Buffer,
+        _aux: &mut AuxiliaryBuffers,
+        _context: &mut impl ProcessContext<Self>,
+    ) -> ProcessStatus {
+        self.process_channels(buffer.as_slice());
+
+        ProcessStatus::Normal
+    }
+}
+
+impl StereoTool {
+    fn process_channels(&mut self, channels: &mut [&mut [f32]]) {
+        self.mix.set_mix(self.target_mix());
+        self.mix.capture_dry(channels);
+
+        let mono_bass = self.params.mono_bass.value();
+        self.mono_bass
+            .set_frequency(self.params.mono_frequency.value());
+
+        let [left, right] = channels else {
+            return;
+        };
+        for (left, right) in left.iter_mut().zip(right.iter_mut()) {
+            let width = self.params.width.smoothed.next();
+            let rotation = self.params.rotation.smoothed.next().to_radians();
+            let (left_gain, right_gain) =
+                stereo::balance_gains(self.params.balance.smoothed.next());
+            let output = self.params.output.smoothed.next();
+
+            let (mut l, mut r) = stereo::rotate(*left, *right, rotation);
+            (l, r) = stereo::set_width(l, r, width);
+            if mono_bass {
+                (l, r) = self.mono_bass.process(l, r);
+            }
+            *left = l * left_gain * output;
+            *right = r * right_gain * output;
+        }
+
+        for channel in channels.iter() {
+            guard::check_block("StereoTool", channel);
+        }
+        self.mix.mix_into(channels);
+
+        // Metered after the mix so the meters show what's actually coming out
+        let [left, right] = channels else {
+            return;
+        };
+        self.correlation.process_block(left, right);
+        self.goniometer.process_block(left, right);
+        self.phase.set_correlation(self.correlation.value());
+        self.phase.push_points(self.goniometer.points());
+        self.goniometer.clear();
+    }
+
+    /// Bypassing fades to the dry signal through the mix stage.
+    fn target_mix(&self) -> f32 {
+        if self.params.bypass.value() {
+            0.0
+        } else {
+            1.0
+        }
+    }
+}
+
+impl ClapPlugin for StereoTool {
+    const CLAP_ID: &'static str = "com.yourstudio.stereo-tool";
+    const CLAP_DESCRIPTION: Option<&'static str> =
+        Some("Stereo width, rotation, balance, and mono bass with phase metering");
+    const CLAP_MANUAL_URL: Option<&'static str> = Some(Self::URL);
+    const CLAP_SUPPORT_URL: Option<&'static str> = None;
+    const CLAP_FEATURES: &'static [ClapFeature] = &[
+        ClapFeature::AudioEffect,
+        ClapFeature::Utility,
+        ClapFeature::Stereo,
+    ];
+}
+
+impl Vst3Plugin for StereoTool {
+    const VST3_CLASS_ID: [u8; 16] = *b"StereoTool000000";
+    const VST3_SUBCATEGORIES: &'static [Vst3SubCategory] =
+        &[Vst3SubCategory::Fx, Vst3SubCategory::Spatial];
+}
+
+nih_export_clap!(StereoTool);
+nih_export_vst3!(StereoTool);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use plugin_scaffold::test_util::assert_no_alloc;
+
+    plugin_scaffold::install_alloc_disabler!();
+
+    const SAMPLE_RATE: f32 = 48000.0;
+    const BLOCK_SIZE: usize = 512;
+
+    fn test_plugin(params: StereoToolParams) -> StereoTool {
+        params.width.smoothed.reset(params.width.value());
+        params.rotation.smoothed.reset(params.rotation.value());
+        params.balance.smoothed.reset(params.balance.value());
+        params.output.smoothed.reset(params.output.value());
+
+        let mut plugin = StereoTool {
+            mono_bass: MonoBass::new(SAMPLE_RATE, params.mono_frequency.value()),
+            correlation: StereoCorrelation::new(SAMPLE_RATE, CORRELATION_SECONDS),
+            params: Arc::new(params),
+            ..StereoTool::default()
+        };
+        plugin.mix = MixStage::new(SAMPLE_RATE, 2, BLOCK_SIZE, 0);
+        plugin.mix.reset();
+        plugin
+    }
+
+    fn sine(frequency: f32, amplitude: f32) -> Vec<f32> {
+        (0..SAMPLE_RATE as usize)
+            .map(|i| (std::f32::consts::TAU * frequency * i as f32 / SAMPLE_RATE).sin() * amplitude)
+            .collect()
+    }
+
+    fn process(plugin: &mut StereoTool, left: &[f32], right: &[f32]) -> (Vec<f32>, Vec<f32>) {
+        let (mut left, mut right) = (left.to_vec(), right.to_vec());
+        for (left, right) in left
+            .chunks_mut(BLOCK_SIZE)
+            .zip(right.chunks_mut(BLOCK_SIZE))
+        {
+            plugin.process_channels(&mut [left, right]);
+        }
+        (left, right)
+    }
+
+    /// The RMS level of the second half, after the filters have settled.
+    fn rms(samples: &[f32]) -> f32 {
+        let settled = &samples[samples.len() / 2..];
+        (settled.iter().map(|sample| sample * sample).sum::<f32>() / settled.len() as f32).sqrt()
+    }
+
+    #[test]
+    fn zero_width_folds_to_mono() {
+        let mut plugin = test_plugin(StereoToolParams {
+            width: FloatParam::new("Width", 0.0, FloatRange::Linear { min: 0.0, max: 2.0 }),
+            ..StereoToolParams::default()
+        });
+        let (left, right) = process(&mut plugin, &sine(440.0, 0.5), &sine(660.0, 0.25));
+
+        for (left, right) in left.iter().zip(&right) {
+            assert!((left - right).abs() < 1e-6, "{left} != {right}");
+        }
+        assert!(plugin.correlation.value() > 0.99);
+    }
+
+    #[test]
+    fn mono_bass_only_removes_low_side_signal() {
+        let params = || StereoToolParams {
+            mono_bass: BoolParam::new("Mono Bass", true),
+            ..StereoToolParams::default()
+        };
+        let silence = vec![0.0; SAMPLE_RATE as usize];
+
+        // A signal in only one channel is half mid and half side
+        for (frequency, expected_side) in [(30.0, 0.0), (2000.0, 0.5)] {
+            let input = sine(frequency, 1.0);
+            let (left, right) = process(&mut test_plugin(params()), &input, &silence);
+            let side: Vec<f32> = left
+                .iter()
+                .zip(&right)
+                .map(|(l, r)| (l - r) / 2.0)
+                .collect();
+            let mid: Vec<f32> = left
+                .iter()
+                .zip(&right)
+                .map(|(l, r)| (l + r) / 2.0)
+                .collect();
+
+            let expected_side = expected_side * rms(&input);
+            assert!((rms(&side) - expected_side).abs() < 0.02, "{frequency} Hz");
+            assert!(
+                (rms(&mid) - rms(&input) / 2.0).abs() < 0.01,
+                "{frequency} Hz"
+            );
+        }
+    }
+
+    #[test]
+    fn balance_only_turns_one_side_down() {
+        let mut plugin = test_plugin(StereoToolParams {
+            balance: FloatParam::new(
+                "Balance",
+                1.0,
+                FloatRange::Linear {
+                    min: -1.0,
+                    max: 1.0,
+                },
+            ),
+            ..StereoToolParams::default()
+        });
+        let input = sine(440.0, 0.5);
+        let (left, right) = process(&mut plugin, &input, &input);
+
+        assert!(left.iter().all(|sample| sample.abs() < 1e-6));
+        for (input, right) in input.iter().zip(&right) {
+            assert!((input - right).abs() < 1e-6, "{input} != {right}");
+        }
+    }
+
+    #[test]
+    fn processing_does_not_allocate() {
+        let mut plugin = test_plugin(StereoToolParams {
+            mono_bass: BoolParam::new("Mono Bass", true),
+            ..StereoToolParams::default()
+        });
+        let mut left = sine(100.0, 0.5);
+        let mut right = sine(440.0, 0.5);
+        assert_no_alloc(|| {
+            for (left, right) in left
+                .chunks_mut(BLOCK_SIZE)
+                .zip(right.chunks_mut(BLOCK_SIZE))
+            {
+                plugin.process_channels(&mut [left, right]);
+            }
+        });
+    }
+}
@@ -0,0 +1,45 @@
+use crate::StringMachineParams;
+use nih_plug::prelude::*;
+use nih_plug_egui::create_egui_editor;
+use std::sync::Arc;
+use ui_widgets::{EditorParams, EditorShell, KeyboardState, ParamKnob};
+
+pub(crate) fn default_params() -> EditorParams {
+    EditorParams::new((460, 350))
+}
+
+pub(crate) fn create(
+    params: Arc<StringMachineParams>,
+    keyboard: Arc<KeyboardState>,
+) -> Option<Box<dyn Editor>> {
+    create_egui_editor(
+        params.editor.state.clone(),
+        (),
+        |_, _| {},
+        move |egui_ctx, setter, _state| {
+            EditorShell::new(&params.editor)
+                .with_bypass(&params.bypass)
+                .with_keyboard(&keyboard)
+                .show(egui_ctx, setter, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.add(ParamKnob::for_param(&params.saw, setter));
+                        ui.add(ParamKnob::for_param(&params.square, setter));
+                        ui.add(ParamKnob::for_param(&params.sub, setter));
+                        ui.add(ParamKnob::for_param(&params.tone, setter));
+                    });
+                    ui.separator();
+                    ui.horizontal(|ui| {
+                        ui.add(ParamKnob::for_param(&params.attack, setter))
+                            .on_hover_text(
+                                "Starts with the first key down, legato doesn't restart it",
+                            );
+                        ui.add(ParamKnob::for_param(&params.release, setter))
+                            .on_hover_text("Starts once the last key is up");
+                        ui.add(ParamKnob::for_param(&params.ensemble_depth, setter));
+                        ui.add(ParamKnob::for_param(&params.ensemble_mix, setter));
+                        ui.add(ParamKnob::for_param(&params.gain, setter));
+                    });
+                });
+        },
+    )
+}
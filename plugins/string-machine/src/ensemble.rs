@@ -0,0 +1,84 @@
+//! The ensemble: three copies of the strings through delays swept by a slow chorus and a fast
+//! vibrato at once, each copy a third of a cycle behind the last. The copies never line up, which
+//! is what turns a thin divide-down organ into a string section.
+
+use dsp_core::delay::DelayLine;
+use std::f32::consts::TAU;
+
+const TAPS: usize = 3;
+
+/// The delay in the middle of each tap's sweep, in seconds.
+const CENTER: f32 = 0.006;
+/// The slow sweep's rate in Hz and how far it swings each tap at full depth, in seconds.
+const SLOW_RATE: f32 = 0.6;
+const SLOW_DEPTH: f32 = 0.002;
+/// The fast sweep's rate in Hz and swing.
+const FAST_RATE: f32 = 6.0;
+const FAST_DEPTH: f32 = 0.0003;
+
+#[derive(Debug, Clone)]
+pub struct Ensemble {
+    delay: DelayLine<f32>,
+    slow_phase: f32,
+    fast_phase: f32,
+    sample_rate: f32,
+    depth: f32,
+    mix: f32,
+}
+
+impl Ensemble {
+    /// Allocates, so call this from `initialize()`.
+    pub fn new(sample_rate: f32) -> Self {
+        let max_delay = CENTER + SLOW_DEPTH + FAST_DEPTH;
+        Self {
+            delay: DelayLine::new(DelayLine::<f32>::samples_for(sample_rate, max_delay) + 2),
+            slow_phase: 0.0,
+            fast_phase: 0.0,
+            sample_rate,
+            depth: 1.0,
+            mix: 1.0,
+        }
+    }
+
+    /// Scales both sweeps, from a plain delay at 0 to the full swing at 1.
+    pub fn set_depth(&mut self, depth: f32) {
+        self.depth = depth.clamp(0.0, 1.0);
+    }
+
+    /// From only the dry signal at 0 to only the ensemble at 1.
+    pub fn set_mix(&mut self, mix: f32) {
+        self.mix = mix.clamp(0.0, 1.0);
+    }
+
+    pub fn reset(&mut self) {
+        self.delay.reset();
+        self.slow_phase = 0.0;
+        self.fast_phase = 0.0;
+    }
+
+    /// Spread a mono signal to stereo. The middle tap is shared by both sides and the outer ones
+    /// are panned apart.
+    #[inline]
+    pub fn process(&mut self, input: f32) -> (f32, f32) {
+        let taps: [f32; TAPS] = std::array::from_fn(|tap| {
+            let offset = tap as f32 / TAPS as f32;
+            let swing = SLOW_DEPTH * (TAU * (self.slow_phase + offset)).sin()
+                + FAST_DEPTH * (TAU * (self.fast_phase + offset)).sin();
+            self.delay
+                .read((CENTER + swing * self.depth) * self.sample_rate)
+        });
+        self.delay.write(input);
+
+        self.slow_phase += SLOW_RATE / self.sample_rate;
+        self.slow_phase -= self.slow_phase.floor();
+        self.fast_phase += FAST_RATE / self.sample_rate;
+        self.fast_phase -= self.fast_phase.floor();
+
+        let left = (taps[0] + taps[1]) * 0.5;
+        let right = (taps[1] + taps[2]) * 0.5;
+        (
+            input + (left - input) * self.mix,
+            input + (right - input) * self.mix,
+        )
+    }
+}
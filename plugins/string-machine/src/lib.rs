@@ -0,0 +1,842 @@
+use dsp_core::bypass::SoftBypass;
+use dsp_core::envelopes::{ADSREnvelope, FadeOut};
+use dsp_core::filters::StateVariableFilter;
+use dsp_core::generators::{SawOsc, SquareOsc};
+use dsp_core::utils::{OnePoleSmoother, Smoother};
+use dsp_core::{guard, utils::midi_to_freq};
+use ensemble::Ensemble;
+use nih_plug::prelude::*;
+use plugin_scaffold::formatters::{s2v_f32_s_then_ms, v2s_f32_s_then_ms};
+use plugin_scaffold::layouts;
+use plugin_scaffold::notes::{NoteHandler, PendingEvents};
+use std::sync::Arc;
+use ui_widgets::{EditorParams, KeyboardInput, KeyboardState};
+
+mod editor;
+mod ensemble;
+
+const MAX_KEYS: usize = 16;
+
+/// Scales the keys down so full chords with every register up don't clip.
+const VOICE_GAIN: f32 = 0.15;
+
+/// How long a key takes to fade out when it's let go while others are held, in seconds.
+const KEY_FADE_TIME: f32 = 0.005;
+
+/// A paraphonic string machine: every key has its own saw, square, and sub octave oscillators,
+/// but they all play through one envelope and one tone filter, and then through the ensemble.
+/// The envelope starts with the first key down and only releases once the last key is up, so
+/// playing legato never restarts the swell.
+struct StringMachine {
+    params: Arc<StringMachineParams>,
+    keys: [Key; MAX_KEYS],
+    /// The next key to steal.
+    next_key: usize,
+
+    /// The envelope every key plays through.
+    env: ADSREnvelope,
+    /// The tone control, shared by every key like the envelope.
+    tone: StateVariableFilter,
+    cutoff: OnePoleSmoother,
+    /// The saw, square, and sub octave levels, gliding to the register settings so moving them
+    /// doesn't click.
+    layer_gains: [OnePoleSmoother; 3],
+    ensemble: Ensemble,
+    sample_rate: f32,
+
+    /// Notes played on the editor's keyboard, and the notes received from the host for it to
+    /// show.
+    keyboard: Arc<KeyboardState>,
+    keyboard_input: KeyboardInput,
+
+    /// `VoiceTerminated` events waiting to be sent to the host at the end of the block.
+    pending_events: PendingEvents<()>,
+
+    /// Fades the output out and back in when the plugin is bypassed.
+    bypass: SoftBypass,
+}
+
+/// A key's oscillators. They have no envelope of their own, the key only decides whether they're
+/// heard at all.
+#[derive(Clone)]
+struct Key {
+    saw: SawOsc,
+    square: SquareOsc,
+    /// A square an octave below the note.
+    sub: SquareOsc,
+    state: KeyState,
+    /// Cuts the key off without a click when it has to stop before the shared envelope does.
+    declick: FadeOut,
+    note: Option<u8>,
+
+    /// The host's ID for this key, cleared once the host has been told the voice terminated.
+    voice_id: Option<i32>,
+    channel: u8,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum KeyState {
+    Free,
+    /// Down, and heard through the shared envelope.
+    Held,
+    /// Let go after every other key, still heard through the shared envelope's release.
+    Releasing,
+    /// Let go while other keys hold the envelope open, or cut by a new key after every key was
+    /// let go. The envelope won't end it, so it fades out on its own.
+    Fading,
+}
+
+#[derive(Params)]
+struct StringMachineParams {
+    #[nested]
+    editor: EditorParams,
+
+    /// The host's bypass switch.
+    #[id = "bypass"]
+    pub bypass: BoolParam,
+
+    #[id = "gain"]
+    pub gain: FloatParam,
+
+    /// The bright saw register, the violins.
+    #[id = "saw"]
+    pub saw: FloatParam,
+
+    /// The hollow square register, closer to a reed or an organ.
+    #[id = "square"]
+    pub square: FloatParam,
+
+    /// A square an octave down, for the cellos and basses.
+    #[id = "sub"]
+    pub sub: FloatParam,
+
+    /// How long the strings take to swell in once the first key is down.
+    #[id = "attack"]
+    pub attack: FloatParam,
+
+    /// How long the strings take to fade once the last key is up.
+    #[id = "release"]
+    pub release: FloatParam,
+
+    /// The cutoff of the low pass filter every key plays through.
+    #[id = "tone"]
+    pub tone: FloatParam,
+
+    /// How far the ensemble sweeps its delays.
+    #[id = "ensemble_depth"]
+    pub ensemble_depth: FloatParam,
+
+    #[id = "ensemble_mix"]
+    pub ensemble_mix: FloatParam,
+}
+
+impl Default for StringMachine {
+    fn default() -> Self {
+        Self {
+            params: Arc::new(StringMachineParams::default()),
+            keys: std::array::from_fn(|_| Key::new(44100.0)),
+            next_key: 0,
+            env: new_envelope(44100.0),
+            tone: StateVariableFilter::new(44100.0),
+            cutoff: OnePoleSmoother::new(44100.0, 0.02),
+            layer_gains: std::array::from_fn(|_| OnePoleSmoother::new(44100.0, 0.01)),
+            ensemble: Ensemble::new(44100.0),
+            sample_rate: 44100.0,
+            keyboard: Arc::new(KeyboardState::new()),
+            keyboard_input: KeyboardInput::default(),
+            pending_events: PendingEvents::with_capacity(MAX_KEYS * 2),
+            bypass: SoftBypass::new(44100.0),
+        }
+    }
+}
+
+impl Default for StringMachineParams {
+    fn default() -> Self {
+        let level = |name, default| {
+            FloatParam::new(name, default, FloatRange::Linear { min: 0.0, max: 1.0 })
+                .with_value_to_string(formatters::v2s_f32_percentage(0))
+                .with_string_to_value(formatters::s2v_f32_percentage())
+                .with_unit(" %")
+        };
+
+        Self {
+            editor: editor::default_params(),
+
+            bypass: BoolParam::new("Bypass", false).make_bypass(),
+
+            gain: FloatParam::new(
+                "Gain",
+                util::db_to_gain(-6.0),
+                FloatRange::Skewed {
+                    min: util::db_to_gain(-30.0),
+                    max: util::db_to_gain(6.0),
+                    factor: FloatRange::gain_skew_factor(-30.0, 6.0),
+                },
+            )
+            .with_smoother(SmoothingStyle::Logarithmic(50.0))
+            .with_unit(" dB")
+            .with_value_to_string(formatters::v2s_f32_gain_to_db(2))
+            .with_string_to_value(formatters::s2v_f32_gain_to_db()),
+
+            saw: level("Saw", 1.0),
+            square: level("Square", 0.0),
+            sub: level("Sub Octave", 0.5),
+
+            attack: FloatParam::new(
+                "Attack",
+                0.3,
+                FloatRange::Skewed {
+                    min: 0.005,
+                    max: 5.0,
+                    factor: 0.25,
+                },
+            )
+            .with_value_to_string(v2s_f32_s_then_ms(1))
+            .with_string_to_value(s2v_f32_s_then_ms()),
+
+            release: FloatParam::new(
+                "Release",
+                1.5,
+                FloatRange::Skewed {
+                    min: 0.01,
+                    max: 10.0,
+                    factor: 0.25,
+                },
+            )
+            .with_value_to_string(v2s_f32_s_then_ms(1))
+            .with_string_to_value(s2v_f32_s_then_ms()),
+
+            tone: FloatParam::new(
+                "Tone",
+                6000.0,
+                FloatRange::Skewed {
+                    min: 500.0,
+                    max: 16000.0,
+                    factor: FloatRange::skew_factor(-1.0),
+                },
+            )
+            .with_value_to_string(formatters::v2s_f32_hz_then_khz(0))
+            .with_string_to_value(formatters::s2v_f32_hz_then_khz()),
+
+            ensemble_depth: level("Ensemble Depth", 0.7),
+            ensemble_mix: level("Ensemble Mix", 1.0),
+        }
+    }
+}
+
+impl Plugin for StringMachine {
+    const NAME: &'static str = "String Machine";
+    const VENDOR: &'static str = "Your Studio";
+    const URL: &'static str = env!("CARGO_PKG_HOMEPAGE");
+    const EMAIL: &'static str = "contact@yourstudio.com";
+    const VERSION: &'static str = env!("CARGO_PKG_VERSION");
+
+    const AUDIO_IO_LAYOUTS: &'static [AudioIOLayout] = layouts::INSTRUMENT_LAYOUTS;
+    const MIDI_INPUT: MidiConfig = MidiConfig::Basic;
+    const SAMPLE_ACCURATE_AUTOMATION: bool = true;
+
+    type SysExMessage = ();
+    type BackgroundTask = ();
+
+    fn params(&self) -> Arc<dyn Params> {
+        self.params.clone()
+    }
+
+    fn editor(&mut self, _async_executor: AsyncExecutor<Self>) -> Option<Box<dyn Editor>> {
+        editor::create(self.params.clone(), self.keyboard.clone())
+    }
+
+    fn initialize(
+        &mut self,
+        _audio_io_layout: &AudioIOLayout,
+        buffer_config: &BufferConfig,
+        _context: &mut impl InitContext<Self>,
+    ) -> bool {
+        let sample_rate = buffer_config.sample_rate;
+        self.sample_rate = sample_rate;
+        self.bypass = SoftBypass::new(sample_rate);
+        self.bypass.reset(self.params.bypass.value());
+
+        for key in &mut self.keys {
+            *key = Key::new(sample_rate);
+        }
+        self.env = new_envelope(sample_rate);
+        self.tone = StateVariableFilter::new(sample_rate);
+        self.cutoff = OnePoleSmoother::new(sample_rate, 0.02);
+        self.layer_gains = std::array::from_fn(|_| OnePoleSmoother::new(sample_rate, 0.01));
+        self.reset_smoothers();
+        self.ensemble = Ensemble::new(sample_rate);
+        true
+    }
+
+    fn reset(&mut self) {
+        self.keyboard.clear_received();
+        self.env.reset();
+        self.tone.reset();
+        self.ensemble.reset();
+        self.reset_smoothers();
+    }
+
+    fn process(
+        &mut self,
+        buffer: &mut Buffer,
+        _aux: &mut AuxiliaryBuffers,
+        context: &mut impl ProcessContext<Self>,
+    ) -> ProcessStatus {
+        self.update_block_params();
+
+        self.handle_keyboard_notes();
+        let keyboard = self.keyboard.clone();
+        let mut next_event = keyboard.echo(context.next_event());
+        for (sample_id, channel_samples) in buffer.iter_samples().enumerate() {
+            self.handle_due_events(sample_id as u32, &mut next_event, || {
+                keyboard.echo(context.next_event())
+            });
+
+            let gain = self.params.gain.smoothed.next();
+            let (left, right) = self.render_frame();
+            for (channel_idx, sample) in channel_samples.into_iter().enumerate() {
+                *sample = if channel_idx.is_multiple_of(2) {
+                    left * gain
+                } else {
+                    right * gain
+                };
+            }
+        }
+
+        // Keys keep running while bypassed so notes still end when they should
+        self.bypass.set_bypassed(self.params.bypass.value());
+        self.bypass.process_to_silence(buffer.as_slice());
+
+        for channel in buffer.as_slice_immutable() {
+            guard::check_block("string-machine output", channel);
+        }
+
+        let last_sample = buffer.samples().saturating_sub(1) as u32;
+        self.terminate_finished_keys(last_sample);
+        self.pending_events.send(context);
+
+        self.process_status()
+    }
+}
+
+impl Key {
+    fn new(sample_rate: f32) -> Self {
+        Self {
+            saw: SawOsc::new(sample_rate),
+            square: SquareOsc::new(sample_rate),
+            sub: SquareOsc::new(sample_rate),
+            state: KeyState::Free,
+            declick: FadeOut::new(sample_rate, KEY_FADE_TIME),
+            note: None,
+            voice_id: None,
+            channel: 0,
+        }
+    }
+
+    fn is_active(&self) -> bool {
+        self.state != KeyState::Free
+    }
+
+    /// Start fading the key out, it's freed once the fade is over.
+    fn fade(&mut self) {
+        self.state = KeyState::Fading;
+        self.declick.start();
+    }
+
+    /// Queue a `VoiceTerminated` event for this key if the host still thinks it's playing.
+    fn terminate(&mut self, timing: u32, pending_events: &mut PendingEvents<()>) {
+        pending_events.voice_terminated(timing, self.voice_id.take(), self.channel, self.note);
+    }
+}
+
+/// The shared envelope. String machines have no decay, the strings hold at full level until the
+/// last key is up.
+fn new_envelope(sample_rate: f32) -> ADSREnvelope {
+    let mut env = ADSREnvelope::new(sample_rate);
+    env.set_decay(0.001);
+    env.set_sustain(1.0);
+    env
+}
+
+/// Compute a voice ID in case the host doesn't provide them.
+const fn compute_fallback_voice_id(note: u8, channel: u8) -> i32 {
+    note as i32 | ((channel as i32) << 16)
+}
+
+impl StringMachine {
+    fn reset_smoothers(&mut self) {
+        self.cutoff.reset(self.params.tone.value());
+        for (gain, level) in self.layer_gains.iter_mut().zip(self.layer_levels()) {
+            gain.reset(level);
+        }
+    }
+
+    fn layer_levels(&self) -> [f32; 3] {
+        [
+            self.params.saw.value(),
+            self.params.square.value(),
+            self.params.sub.value(),
+        ]
+    }
+
+    /// Apply the parameters that only change between blocks.
+    fn update_block_params(&mut self) {
+        let params = &self.params;
+        self.env.set_attack(params.attack.value());
+        self.env.set_release(params.release.value());
+        self.cutoff.set_target(params.tone.value());
+        for (gain, level) in self.layer_gains.iter_mut().zip(self.layer_levels()) {
+            gain.set_target(level);
+        }
+        self.ensemble.set_depth(params.ensemble_depth.value());
+        self.ensemble.set_mix(params.ensemble_mix.value());
+    }
+
+    /// Play the keys pressed and released on the editor's keyboard since the last block.
+    fn handle_keyboard_notes(&mut self) {
+        let notes = self.keyboard_input.poll(&self.keyboard);
+        self.handle_events(notes);
+    }
+
+    /// Like the original keyboards, the keys aren't velocity sensitive.
+    fn note_on(&mut self, timing: u32, voice_id: Option<i32>, channel: u8, note: u8) {
+        // The first key down starts the swell. Keys still ringing out from before belong to the
+        // release that's being cut short, so they go.
+        if self.keys.iter().all(|key| key.state != KeyState::Held) {
+            for key in &mut self.keys {
+                if key.state == KeyState::Releasing {
+                    key.fade();
+                }
+            }
+            self.env.note_on();
+        }
+
+        // Find a free key or steal the oldest
+        let key_idx = self
+            .keys
+            .iter()
+            .position(|key| !key.is_active())
+            .unwrap_or_else(|| {
+                let idx = self.next_key;
+                self.next_key = (idx + 1) % MAX_KEYS;
+                idx
+            });
+
+        // The host needs to know a stolen key ended before its ID is reused
+        let key = &mut self.keys[key_idx];
+        key.terminate(timing, &mut self.pending_events);
+
+        // Like a divide-down organ the oscillators run freely, so their phases carry on
+        let frequency = midi_to_freq(note);
+        key.saw.set_frequency(frequency);
+        key.square.set_frequency(frequency);
+        key.sub.set_frequency(frequency * 0.5);
+        key.declick.reset();
+
+        key.state = KeyState::Held;
+        key.note = Some(note);
+        key.voice_id = Some(voice_id.unwrap_or_else(|| compute_fallback_voice_id(note, channel)));
+        key.channel = channel;
+    }
+
+    fn note_off(&mut self, voice_id: Option<i32>, channel: u8, note: u8) {
+        // Let go of the key with this ID, or every key playing this note when there is none
+        let mut released = false;
+        for key in &mut self.keys {
+            let matches = match voice_id {
+                Some(voice_id) => key.voice_id == Some(voice_id),
+                None => key.note == Some(note) && key.channel == channel,
+            };
+            if matches && key.state == KeyState::Held {
+                key.state = KeyState::Releasing;
+                released = true;
+            }
+        }
+        if !released {
+            return;
+        }
+
+        // Keys can't release on their own while others hold the envelope open, so they're cut
+        if self.keys.iter().any(|key| key.state == KeyState::Held) {
+            for key in &mut self.keys {
+                if key.state == KeyState::Releasing {
+                    key.fade();
+                }
+            }
+        } else {
+            self.env.note_off();
+        }
+    }
+
+    fn render_frame(&mut self) -> (f32, f32) {
+        let [saw, square, sub]: [f32; 3] = std::array::from_fn(|idx| self.layer_gains[idx].next());
+
+        let mut sum = 0.0;
+        for key in &mut self.keys {
+            if !key.is_active() {
+                continue;
+            }
+
+            let gate = if key.state == KeyState::Fading {
+                key.declick.next_gain()
+            } else {
+                1.0
+            };
+            sum += (key.saw.next_sample() * saw
+                + key.square.next_sample() * square
+                + key.sub.next_sample() * sub)
+                * gate;
+
+            if key.state == KeyState::Fading && !key.declick.is_active() {
+                key.state = KeyState::Free;
+            }
+        }
+
+        let env = self.env.next_sample();
+        if !self.env.is_active() {
+            for key in &mut self.keys {
+                if key.state == KeyState::Releasing {
+                    key.state = KeyState::Free;
+                }
+            }
+        }
+
+        self.tone
+            .set_params(self.cutoff.next(), std::f32::consts::FRAC_1_SQRT_2);
+        let strings = self.tone.process(sum * env * VOICE_GAIN);
+        self.ensemble.process(strings)
+    }
+
+    /// Keep the plugin alive while keys are held, and report the remaining release time once
+    /// they're all up so hosts that suspend silent plugins don't cut off the tail.
+    fn process_status(&self) -> ProcessStatus {
+        if self.keys.iter().any(|key| key.state == KeyState::Held) {
+            return ProcessStatus::KeepAlive;
+        }
+        if self.keys.iter().all(|key| !key.is_active()) {
+            return ProcessStatus::Normal;
+        }
+
+        let fade = (KEY_FADE_TIME * self.sample_rate) as u32 + 1;
+        let release = self.env.remaining_release_samples().unwrap_or(0);
+        ProcessStatus::Tail(release.max(fade))
+    }
+
+    fn terminate_finished_keys(&mut self, timing: u32) {
+        for key in &mut self.keys {
+            if !key.is_active() {
+                key.terminate(timing, &mut self.pending_events);
+            }
+        }
+    }
+}
+
+impl NoteHandler<()> for StringMachine {
+    fn handle_event(&mut self, event: PluginNoteEvent<Self>) {
+        match event {
+            // A note on with zero velocity is a note off in MIDI terms
+            NoteEvent::NoteOn {
+                voice_id,
+                channel,
+                note,
+                velocity,
+                ..
+            } if velocity <= 0.0 => self.note_off(voice_id, channel, note),
+            NoteEvent::NoteOn {
+                timing,
+                voice_id,
+                channel,
+                note,
+                ..
+            } => self.note_on(timing, voice_id, channel, note),
+            NoteEvent::NoteOff {
+                voice_id,
+                channel,
+                note,
+                ..
+            } => self.note_off(voice_id, channel, note),
+            _ => {}
+        }
+    }
+}
+
+impl ClapPlugin for StringMachine {
+    const CLAP_ID: &'static str = "com.yourstudio.string-machine";
+    const CLAP_DESCRIPTION: Option<&'static str> =
+        Some("A paraphonic string ensemble with saw, square, and sub octave registers");
+    const CLAP_MANUAL_URL: Option<&'static str> = Some(Self::URL);
+    const CLAP_SUPPORT_URL: Option<&'static str> = None;
+    const CLAP_FEATURES: &'static [ClapFeature] = &[
+        ClapFeature::Instrument,
+        ClapFeature::Synthesizer,
+        ClapFeature::Stereo,
+    ];
+}
+
+impl Vst3Plugin for StringMachine {
+    const VST3_CLASS_ID: [u8; 16] = *b"StringMachineEn!";
+    const VST3_SUBCATEGORIES: &'static [Vst3SubCategory] =
+        &[Vst3SubCategory::Instrument, Vst3SubCategory::Synth];
+}
+
+nih_export_clap!(StringMachine);
+nih_export_vst3!(StringMachine);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use plugin_scaffold::test_util::assert_no_alloc;
+
+    plugin_scaffold::install_alloc_disabler!();
+
+    const SAMPLE_RATE: f32 = 48000.0;
+    const BLOCK_SIZE: usize = 256;
+
+    fn test_plugin(params: StringMachineParams) -> StringMachine {
+        let mut plugin = StringMachine {
+            params: Arc::new(params),
+            keys: std::array::from_fn(|_| Key::new(SAMPLE_RATE)),
+            env: new_envelope(SAMPLE_RATE),
+            tone: StateVariableFilter::new(SAMPLE_RATE),
+            cutoff: OnePoleSmoother::new(SAMPLE_RATE, 0.02),
+            layer_gains: std::array::from_fn(|_| OnePoleSmoother::new(SAMPLE_RATE, 0.01)),
+            ensemble: Ensemble::new(SAMPLE_RATE),
+            sample_rate: SAMPLE_RATE,
+            ..StringMachine::default()
+        };
+        plugin.reset_smoothers();
+        plugin
+    }
+
+    fn level(default: f32) -> FloatParam {
+        FloatParam::new("Level", default, FloatRange::Linear { min: 0.0, max: 1.0 })
+    }
+
+    /// Only the saw register, a fast envelope, and no ensemble.
+    fn dry_saw() -> StringMachineParams {
+        StringMachineParams {
+            sub: level(0.0),
+            attack: FloatParam::new("Attack", 0.005, FloatRange::Linear { min: 0.0, max: 5.0 }),
+            release: FloatParam::new(
+                "Release",
+                0.1,
+                FloatRange::Linear {
+                    min: 0.0,
+                    max: 10.0,
+                },
+            ),
+            ensemble_mix: level(0.0),
+            ..StringMachineParams::default()
+        }
+    }
+
+    fn note_on(note: u8) -> PluginNoteEvent<StringMachine> {
+        NoteEvent::NoteOn {
+            timing: 0,
+            voice_id: None,
+            channel: 0,
+            note,
+            velocity: 1.0,
+        }
+    }
+
+    fn note_off(note: u8) -> PluginNoteEvent<StringMachine> {
+        NoteEvent::NoteOff {
+            timing: 0,
+            voice_id: None,
+            channel: 0,
+            note,
+            velocity: 0.0,
+        }
+    }
+
+    /// Mirror of `process()` without the output gain, with `events` at the start of the first
+    /// block.
+    fn render_into(
+        plugin: &mut StringMachine,
+        events: impl IntoIterator<Item = PluginNoteEvent<StringMachine>>,
+        output: &mut [(f32, f32)],
+    ) {
+        let keyboard = plugin.keyboard.clone();
+        let mut events = events.into_iter();
+        let mut next_event = keyboard.echo(events.next());
+
+        for block in output.chunks_mut(BLOCK_SIZE) {
+            plugin.update_block_params();
+            plugin.handle_keyboard_notes();
+            for (sample_id, frame) in block.iter_mut().enumerate() {
+                plugin.handle_due_events(sample_id as u32, &mut next_event, || {
+                    keyboard.echo(events.next())
+                });
+                *frame = plugin.render_frame();
+            }
+            plugin.terminate_finished_keys(0);
+        }
+    }
+
+    fn render(
+        plugin: &mut StringMachine,
+        events: Vec<PluginNoteEvent<StringMachine>>,
+        seconds: f32,
+    ) -> Vec<(f32, f32)> {
+        let mut output = vec![(0.0, 0.0); (seconds * SAMPLE_RATE) as usize];
+        render_into(plugin, events, &mut output);
+        output
+    }
+
+    fn rising_crossings(samples: &[f32]) -> usize {
+        samples
+            .windows(2)
+            .filter(|pair| pair[0] < 0.0 && pair[1] >= 0.0)
+            .count()
+    }
+
+    fn sounding(plugin: &StringMachine) -> Vec<u8> {
+        plugin
+            .keys
+            .iter()
+            .filter(|key| key.is_active())
+            .filter_map(|key| key.note)
+            .collect()
+    }
+
+    /// The shared envelope's level, without advancing it.
+    fn envelope_level(plugin: &StringMachine) -> f32 {
+        plugin.env.clone().next_sample()
+    }
+
+    #[test]
+    fn the_registers_set_the_pitch() {
+        let sub_only = StringMachineParams {
+            saw: level(0.0),
+            sub: level(1.0),
+            ..dry_saw()
+        };
+
+        for (params, frequency) in [(dry_saw(), 440), (sub_only, 220)] {
+            let mut plugin = test_plugin(params);
+            let output = render(&mut plugin, vec![note_on(69)], 1.0);
+            let left: Vec<f32> = output[SAMPLE_RATE as usize / 2..]
+                .iter()
+                .map(|(left, _)| *left)
+                .collect();
+
+            let crossings = rising_crossings(&left);
+            assert!(crossings.abs_diff(frequency / 2) <= 1, "{crossings}");
+        }
+    }
+
+    #[test]
+    fn letting_go_of_one_key_cuts_it_while_the_others_hold_the_envelope() {
+        let mut plugin = test_plugin(dry_saw());
+        render(&mut plugin, vec![note_on(60), note_on(64)], 0.1);
+        assert!((envelope_level(&plugin) - 1.0).abs() < 1e-6);
+
+        render(&mut plugin, vec![note_off(60)], 0.05);
+        assert_eq!(sounding(&plugin), vec![64]);
+        assert_eq!(plugin.pending_events.len(), 1);
+        assert!((envelope_level(&plugin) - 1.0).abs() < 1e-6);
+        assert!(matches!(plugin.process_status(), ProcessStatus::KeepAlive));
+    }
+
+    #[test]
+    fn the_last_keys_ring_out_through_the_shared_release() {
+        let mut plugin = test_plugin(dry_saw());
+        render(&mut plugin, vec![note_on(60), note_on(64)], 0.1);
+
+        render(&mut plugin, vec![note_off(60), note_off(64)], 0.05);
+        assert_eq!(sounding(&plugin).len(), 2);
+        let level = envelope_level(&plugin);
+        assert!(level > 0.2 && level < 1.0, "{level}");
+
+        let ProcessStatus::Tail(tail) = plugin.process_status() else {
+            panic!("releasing keys should report a tail");
+        };
+        render(&mut plugin, Vec::new(), tail as f32 / SAMPLE_RATE + 0.01);
+        assert!(sounding(&plugin).is_empty());
+        assert_eq!(plugin.pending_events.len(), 2);
+    }
+
+    #[test]
+    fn a_new_key_cuts_the_ones_ringing_out() {
+        let mut plugin = test_plugin(dry_saw());
+        render(&mut plugin, vec![note_on(60)], 0.1);
+        render(&mut plugin, vec![note_off(60)], 0.01);
+        let released = envelope_level(&plugin);
+
+        // The swell picks up from where the release got to instead of starting from silence
+        render(&mut plugin, vec![note_on(67)], 0.001);
+        assert!(envelope_level(&plugin) > released);
+        render(&mut plugin, Vec::new(), 0.05);
+        assert_eq!(sounding(&plugin), vec![67]);
+    }
+
+    #[test]
+    fn the_editor_keyboard_plays_and_shows_notes() {
+        let mut plugin = test_plugin(dry_saw());
+        plugin.keyboard.press(60, true);
+        plugin.keyboard.press(64, true);
+        render(&mut plugin, Vec::new(), 0.01);
+        assert_eq!(sounding(&plugin), vec![60, 64]);
+
+        // Holding a key doesn't retrigger it on every block
+        render(&mut plugin, Vec::new(), 0.01);
+        assert_eq!(sounding(&plugin), vec![60, 64]);
+
+        plugin.keyboard.press(60, false);
+        render(&mut plugin, vec![note_on(67)], 0.05);
+        let mut notes = sounding(&plugin);
+        notes.sort();
+        assert_eq!(notes, vec![64, 67]);
+        assert!(plugin.keyboard.is_received(67));
+        assert!(!plugin.keyboard.is_received(60));
+
+        render(&mut plugin, vec![note_off(67)], 0.01);
+        assert!(!plugin.keyboard.is_received(67));
+    }
+
+    #[test]
+    fn the_ensemble_spreads_the_strings_to_stereo() {
+        let spread = |mix| {
+            let params = StringMachineParams {
+                ensemble_depth: level(1.0),
+                ensemble_mix: level(mix),
+                ..dry_saw()
+            };
+            let mut plugin = test_plugin(params);
+            let output = render(&mut plugin, vec![note_on(57), note_on(64)], 1.0);
+
+            let (difference, total) = output[SAMPLE_RATE as usize / 2..].iter().fold(
+                (0.0, 0.0),
+                |(difference, total), (left, right)| {
+                    (
+                        difference + (left - right).powi(2),
+                        total + left.powi(2) + right.powi(2),
+                    )
+                },
+            );
+            (difference / total).sqrt()
+        };
+
+        assert_eq!(spread(0.0), 0.0);
+        let full = spread(1.0);
+        assert!(full > 0.1, "{full}");
+    }
+
+    #[test]
+    fn processing_does_not_allocate() {
+        let mut plugin = test_plugin(StringMachineParams {
+            ensemble_depth: level(1.0),
+            ensemble_mix: level(0.5),
+            ..dry_saw()
+        });
+        let mut output = vec![(0.0, 0.0); BLOCK_SIZE * 40];
+        let (held, released) = output.split_at_mut(BLOCK_SIZE * 20);
+        plugin.keyboard.press(48, true);
+        assert_no_alloc(|| render_into(&mut plugin, [note_on(60), note_on(64)], held));
+
+        plugin.keyboard.press(48, false);
+        assert_no_alloc(|| render_into(&mut plugin, [note_off(60), note_off(64)], released));
+    }
+}
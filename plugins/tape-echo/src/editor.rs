@@ -0,0 +1,47 @@
+use crate::TapeEchoParams;
+use nih_plug::prelude::*;
+use nih_plug_egui::create_egui_editor;
+use std::sync::Arc;
+use ui_widgets::{param_combo, param_toggle, EditorParams, EditorShell, ParamKnob};
+
+pub(crate) fn default_params() -> EditorParams {
+    EditorParams::new((400, 260))
+}
+
+pub(crate) fn create(params: Arc<TapeEchoParams>) -> Option<Box<dyn Editor>> {
+    create_egui_editor(
+        params.editor.state.clone(),
+        (),
+        |_, _| {},
+        move |egui_ctx, setter, _state| {
+            EditorShell::new(&params.editor)
+                .with_bypass(&params.bypass)
+                .show(egui_ctx, setter, |ui| {
+                    ui.horizontal(|ui| {
+                        param_combo(ui, &params.heads, setter).on_hover_text(
+                            "Multi-Head repeats at one, two and three times the time",
+                        );
+                        if params.sync.value() {
+                            param_combo(ui, &params.division, setter);
+                        } else {
+                            ui.add(ParamKnob::for_param(&params.time, setter));
+                        }
+                        param_toggle(ui, &params.sync, setter)
+                            .on_hover_text("Follow the host's tempo");
+                        ui.add(ParamKnob::for_param(&params.feedback, setter))
+                            .on_hover_text("Above 100% the echo builds up into self-oscillation");
+                        ui.add(ParamKnob::for_param(&params.mix, setter));
+                    });
+                    ui.horizontal(|ui| {
+                        ui.add(ParamKnob::for_param(&params.saturation, setter));
+                        ui.add(ParamKnob::for_param(&params.tone, setter))
+                            .on_hover_text("Darkens the repeats on every pass");
+                        ui.add(ParamKnob::for_param(&params.wow, setter))
+                            .on_hover_text("Slow drift of the tape's speed");
+                        ui.add(ParamKnob::for_param(&params.flutter, setter))
+                            .on_hover_text("Fast wobble of the tape's speed");
+                    });
+                });
+        },
+    )
+}
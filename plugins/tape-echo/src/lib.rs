@@ -0,0 +1,581 @@
+use dsp_core::dynamics::db_to_gain;
+use dsp_core::guard;
+use dsp_core::lfo::Lfo;
+use dsp_core::mix::MixStage;
+use nih_plug::prelude::*;
+use plugin_scaffold::formatters::{s2v_f32_ms_then_s, v2s_f32_ms_then_s, NoteDivision};
+use plugin_scaffold::layouts;
+use std::sync::Arc;
+use tape::Tape;
+use ui_widgets::EditorParams;
+
+mod editor;
+mod tape;
+
+/// The shortest and longest time between playback heads, in milliseconds.
+const MIN_TIME: f32 = 20.0;
+const MAX_TIME: f32 = 1000.0;
+/// The heads in multi-head mode, spaced evenly like the playback heads on a tape echo.
+const MAX_HEADS: usize = 3;
+
+/// Wow is the slow drift of the tape's speed, flutter the fast wobble from the capstan. These
+/// are how far each swings the delay at full depth, in seconds.
+const WOW_EXCURSION: f32 = 0.0015;
+const WOW_RATE: f32 = 0.6;
+const FLUTTER_EXCURSION: f32 = 0.0001;
+const FLUTTER_RATE: f32 = 7.0;
+
+/// A tape echo: the input is recorded onto a loop of tape and played back by one or three heads.
+/// The tape's speed wavers, it saturates, and it loses highs on every repeat.
+struct TapeEcho {
+    params: Arc<TapeEchoParams>,
+    /// One tape loop per channel.
+    tapes: Vec<Tape>,
+    /// Shared by all channels, since they're on the same tape.
+    wow: Lfo,
+    flutter: Lfo,
+    /// The delay to the first head in milliseconds, gliding to either the time parameter or the
+    /// synced note division.
+    time: Smoother<f32>,
+    /// The host's tempo, kept from the last block that had one.
+    tempo: f64,
+    sample_rate: f32,
+    /// Blends in the dry signal, and fades to it for a click-free bypass.
+    mix: MixStage,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+pub enum HeadMode {
+    /// One playback head, a single echo per repeat.
+    #[name = "Single Head"]
+    Single,
+    /// Three playback heads at one, two and three times the delay time, for a rhythmic
+    /// pattern of echoes. The last head is the one that's fed back.
+    #[name = "Multi-Head"]
+    Multi,
+}
+
+impl HeadMode {
+    fn heads(self) -> usize {
+        match self {
+            HeadMode::Single => 1,
+            HeadMode::Multi => MAX_HEADS,
+        }
+    }
+}
+
+#[derive(Params)]
+struct TapeEchoParams {
+    #[nested]
+    editor: EditorParams,
+
+    /// The host's bypass switch.
+    #[id = "bypass"]
+    pub bypass: BoolParam,
+
+    #[id = "heads"]
+    pub heads: EnumParam<HeadMode>,
+
+    /// The delay to the first playback head, in milliseconds. Changing it glides like changing
+    /// the tape's speed, bending the pitch of the repeats.
+    #[id = "time"]
+    pub time: FloatParam,
+
+    /// Follow the host's tempo, using the division instead of the time.
+    #[id = "sync"]
+    pub sync: BoolParam,
+
+    /// The synced delay to the first head. Limited to the time parameter's range, so long
+    /// divisions at slow tempos stop at a second.
+    #[id = "division"]
+    pub division: EnumParam<NoteDivision>,
+
+    /// How much of the last head is recorded again. Above 100% the echoes build up until the
+    /// tape's saturation holds them at a steady self-oscillation.
+    #[id = "feedback"]
+    pub feedback: FloatParam,
+
+    /// Gain into the tape's saturation, in decibels.
+    #[id = "saturation"]
+    pub saturation: FloatParam,
+
+    /// The cutoff of the low pass the repeats go through on every pass.
+    #[id = "tone"]
+    pub tone: FloatParam,
+
+    #[id = "wow"]
+    pub wow: FloatParam,
+
+    #[id = "flutter"]
+    pub flutter: FloatParam,
+
+    #[id = "mix"]
+    pub mix: FloatParam,
+}
+
+impl Default for TapeEcho {
+    fn default() -> Self {
+        Self {
+            params: Arc::new(TapeEchoParams::default()),
+            tapes: Vec::new(),
+            wow: Lfo::new(44100.0),
+            flutter: Lfo::new(44100.0),
+            time: Smoother::new(SmoothingStyle::Logarithmic(250.0)),
+            tempo: 120.0,
+            sample_rate: 44100.0,
+            mix: MixStage::new(44100.0, 0, 0, 0),
+        }
+    }
+}
+
+impl Default for TapeEchoParams {
+    fn default() -> Self {
+        let percentage = |name, default, max| {
+            FloatParam::new(name, default, FloatRange::Linear { min: 0.0, max })
+                .with_smoother(SmoothingStyle::Linear(20.0))
+                .with_value_to_string(formatters::v2s_f32_percentage(0))
+                .with_string_to_value(formatters::s2v_f32_percentage())
+                .with_unit(" %")
+        };
+
+        Self {
+            editor: editor::default_params(),
+
+            bypass: BoolParam::new("Bypass", false).make_bypass(),
+
+            heads: EnumParam::new("Heads", HeadMode::Single),
+
+            time: FloatParam::new(
+                "Time",
+                350.0,
+                FloatRange::Skewed {
+                    min: MIN_TIME,
+                    max: MAX_TIME,
+                    factor: FloatRange::skew_factor(-1.0),
+                },
+            )
+            .with_value_to_string(v2s_f32_ms_then_s(0))
+            .with_string_to_value(s2v_f32_ms_then_s()),
+
+            sync: BoolParam::new("Sync", false),
+            division: EnumParam::new("Division", NoteDivision::EighthDotted),
+
+            feedback: percentage("Feedback", 0.45, 1.2),
+
+            saturation: FloatParam::new(
+                "Saturation",
+                6.0,
+                FloatRange::Linear {
+                    min: 0.0,
+                    max: 24.0,
+                },
+            )
+            .with_smoother(SmoothingStyle::Linear(20.0))
+            .with_unit(" dB")
+            .with_value_to_string(formatters::v2s_f32_rounded(1)),
+
+            tone: FloatParam::new(
+                "Tone",
+                5000.0,
+                FloatRange::Skewed {
+                    min: 1000.0,
+                    max: 16000.0,
+                    factor: FloatRange::skew_factor(-1.0),
+                },
+            )
+            .with_smoother(SmoothingStyle::Logarithmic(20.0))
+            .with_value_to_string(formatters::v2s_f32_hz_then_khz(0))
+            .with_string_to_value(formatters::s2v_f32_hz_then_khz()),
+
+            wow: percentage("Wow", 0.3, 1.0),
+            flutter: percentage("Flutter", 0.3, 1.0),
+
+            mix: FloatParam::new("Mix", 0.35, FloatRange::Linear { min: 0.0, max: 1.0 })
+                .with_value_to_string(formatters::v2s_f32_percentage(0))
+                .with_string_to_value(formatters::s2v_f32_percentage())
+                .with_unit(" %"),
+        }
+    }
+}
+
+impl Plugin for TapeEcho {
+    const NAME: &'static str = "Tape Echo";
+    const VENDOR: &'static str = "Your Studio";
+    const URL: &'static str = env!("CARGO_PKG_HOMEPAGE");
+    const EMAIL: &'static str = "contact@yourstudio.com";
+    const VERSION: &'static str = env!("CARGO_PKG_VERSION");
+
+    const AUDIO_IO_LAYOUTS: &'static [AudioIOLayout] = &[layouts::STEREO, layouts::MONO];
+
+    type SysExMessage = ();
+    type BackgroundTask = ();
+
+    fn params(&self) -> Arc<dyn Params> {
+        self.params.clone()
+    }
+
+    fn editor(&mut self, _async_executor: AsyncExecutor<Self>) -> Option<Box<dyn Editor>> {
+        editor::create(self.params.clone())
+    }
+
+    fn initialize(
+        &mut self,
+        audio_io_layout: &AudioIOLayout,
+        buffer_config: &BufferConfig,
+        _context: &mut impl InitContext<Self>,
+    ) -> bool {
+        let num_channels = audio_io_layout
+            .main_output_channels
+            .map_or(0, |channels| channels.get() as usize);
+        let sample_rate = buffer_config.sample_rate;
+
+        self.sample_rate = sample_rate;
+        self.tapes = (0..num_channels)
+            .map(|_| Tape::new(sample_rate, max_delay()))
+            .collect();
+        self.wow = Lfo::new(sample_rate);
+        self.wow.set_rate(WOW_RATE);
+        self.flutter = Lfo::new(sample_rate);
+        self.flutter.set_rate(FLUTTER_RATE);
+        self.time.reset(self.target_time());
+
+        self.mix = MixStage::new(
+            sample_rate,
+            num_channels,
+            buffer_config.max_buffer_size as usize,
+            0,
+        );
+        self.mix.set_mix(self.target_mix());
+        self.mix.reset();
+        true
+    }
+
+    fn reset(&mut self) {
+        for tape in &mut self.tapes {
+            tape.reset();
+        }
+        self.wow.reset();
+        self.flutter.reset();
+        self.time.reset(self.target_time());
+        self.mix.reset();
+    }
+
+    fn process(
+        &mut self,
+        buffer: &mut Buffer,
+        _aux: &mut AuxiliaryBuffers,
+        context: &mut impl ProcessContext<Self>,
+    ) -> ProcessStatus {
+        if let Some(tempo) = context.transport().tempo {
+            self.tempo = tempo;
+        }
+        self.process_channels(buffer.as_slice());
+
+        ProcessStatus::Normal
+    }
+}
+
+/// The longest delay to the last head, with room for the wow and flutter, in seconds.
+fn max_delay() -> f32 {
+    (MAX_TIME / 1000.0 + WOW_EXCURSION + FLUTTER_EXCURSION) * MAX_HEADS as f32
+}
+
+impl TapeEcho {
+    fn process_channels(&mut self, channels: &mut [&mut [f32]]) {
+        self.mix.set_mix(self.target_mix());
+        self.mix.capture_dry(channels);
+
+        self.time.set_target(self.sample_rate, self.target_time());
+
+        let params = &self.params;
+        let heads = params.heads.value().heads();
+
+        let num_samples = channels.first().map_or(0, |channel| channel.len());
+        for i in 0..num_samples {
+            let time = self.time.next() / 1000.0;
+            let feedback = params.feedback.smoothed.next();
+            let drive = db_to_gain(params.saturation.smoothed.next());
+            let tone = params.tone.smoothed.next();
+            let wow = params.wow.smoothed.next() * WOW_EXCURSION * self.wow.next_sample();
+            let flutter =
+                params.flutter.smoothed.next() * FLUTTER_EXCURSION * self.flutter.next_sample();
+
+            // Later heads read further back along the wavering tape, so they waver more
+            let spacing = (time + wow + flutter) * self.sample_rate;
+            for (tape, channel) in self.tapes.iter_mut().zip(channels.iter_mut()) {
+                tape.set_drive(drive);
+                tape.set_tone(tone);
+                channel[i] = tape.process(channel[i], spacing, heads, feedback);
+            }
+        }
+
+        for channel in channels.iter() {
+            guard::check_block("Tape Echo", channel);
+        }
+        self.mix.mix_into(channels);
+    }
+
+    /// The delay to the first head in milliseconds.
+    fn target_time(&self) -> f32 {
+        if self.params.sync.value() {
+            let synced = self.params.division.value().seconds(self.tempo) * 1000.0;
+            synced.clamp(MIN_TIME, MAX_TIME)
+        } else {
+            self.params.time.value()
+        }
+    }
+
+    /// Bypassing fades to the dry signal through the mix stage.
+    fn target_mix(&self) -> f32 {
+        if self.params.bypass.value() {
+            0.0
+        } else {
+            self.params.mix.value()
+        }
+    }
+}
+
+impl ClapPlugin for TapeEcho {
+    const CLAP_ID: &'static str = "com.yourstudio.tape-echo";
+    const CLAP_DESCRIPTION: Option<&'static str> =
+        Some("A tape echo with wow and flutter, saturation, and multiple playback heads");
+    const CLAP_MANUAL_URL: Option<&'static str> = Some(Self::URL);
+    const CLAP_SUPPORT_URL: Option<&'static str> = None;
+    const CLAP_FEATURES: &'static [ClapFeature] = &[
+        ClapFeature::AudioEffect,
+        ClapFeature::Delay,
+        ClapFeature::Stereo,
+        ClapFeature::Mono,
+    ];
+}
+
+impl Vst3Plugin for TapeEcho {
+    const VST3_CLASS_ID: [u8; 16] = *b"TapeEchoMultiHd!";
+    const VST3_SUBCATEGORIES: &'static [Vst3SubCategory] =
+        &[Vst3SubCategory::Fx, Vst3SubCategory::Delay];
+}
+
+nih_export_clap!(TapeEcho);
+nih_export_vst3!(TapeEcho);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use plugin_scaffold::test_util::assert_no_alloc;
+
+    plugin_scaffold::install_alloc_disabler!();
+
+    const SAMPLE_RATE: f32 = 48000.0;
+    const BLOCK_SIZE: usize = 512;
+
+    fn test_plugin(params: TapeEchoParams, num_channels: usize) -> TapeEcho {
+        for param in [
+            &params.feedback,
+            &params.saturation,
+            &params.tone,
+            &params.wow,
+            &params.flutter,
+        ] {
+            param.smoothed.reset(param.value());
+        }
+
+        let mut plugin = TapeEcho {
+            params: Arc::new(params),
+            tapes: (0..num_channels)
+                .map(|_| Tape::new(SAMPLE_RATE, max_delay()))
+                .collect(),
+            wow: Lfo::new(SAMPLE_RATE),
+            flutter: Lfo::new(SAMPLE_RATE),
+            time: Smoother::new(SmoothingStyle::Logarithmic(250.0)),
+            tempo: 120.0,
+            sample_rate: SAMPLE_RATE,
+            mix: MixStage::new(SAMPLE_RATE, num_channels, BLOCK_SIZE, 0),
+        };
+        plugin.time.reset(plugin.target_time());
+        plugin.wow.set_rate(WOW_RATE);
+        plugin.flutter.set_rate(FLUTTER_RATE);
+        plugin.mix.set_mix(plugin.target_mix());
+        plugin.mix.reset();
+        plugin
+    }
+
+    fn fixed(name: &str, value: f32) -> FloatParam {
+        FloatParam::new(
+            name,
+            value,
+            FloatRange::Linear {
+                min: 0.0,
+                max: MAX_TIME,
+            },
+        )
+    }
+
+    /// Only the echoes, with a clean tape and no modulation.
+    fn params(heads: HeadMode, time: f32, feedback: f32) -> TapeEchoParams {
+        TapeEchoParams {
+            heads: EnumParam::new("Heads", heads),
+            time: fixed("Time", time),
+            feedback: fixed("Feedback", feedback),
+            saturation: fixed("Saturation", 0.0),
+            wow: fixed("Wow", 0.0),
+            flutter: fixed("Flutter", 0.0),
+            mix: fixed("Mix", 1.0),
+            ..TapeEchoParams::default()
+        }
+    }
+
+    fn impulse(seconds: f32) -> Vec<f32> {
+        let mut samples = vec![0.0; (seconds * SAMPLE_RATE) as usize];
+        samples[0] = 0.1;
+        samples
+    }
+
+    /// Run mono `input` through the plugin as the left and right channels, returning the left.
+    fn process(plugin: &mut TapeEcho, input: &[f32]) -> Vec<f32> {
+        let mut left = input.to_vec();
+        let mut right = input.to_vec();
+        for (left, right) in left
+            .chunks_mut(BLOCK_SIZE)
+            .zip(right.chunks_mut(BLOCK_SIZE))
+        {
+            plugin.process_channels(&mut [left, right]);
+        }
+        left
+    }
+
+    fn peak(samples: &[f32]) -> f32 {
+        samples
+            .iter()
+            .fold(0.0, |peak, sample| f32::max(peak, sample.abs()))
+    }
+
+    /// The sample index of the first head's echo of an impulse `time` milliseconds in.
+    fn echo_at(time: f32) -> usize {
+        (time / 1000.0 * SAMPLE_RATE) as usize
+    }
+
+    #[test]
+    fn the_echo_arrives_after_the_delay_time() {
+        let mut plugin = test_plugin(params(HeadMode::Single, 100.0, 0.0), 2);
+        let output = process(&mut plugin, &impulse(0.5));
+
+        let echo = echo_at(100.0);
+        let loudest = (0..output.len())
+            .max_by(|&a, &b| output[a].abs().total_cmp(&output[b].abs()))
+            .unwrap();
+        assert!(loudest.abs_diff(echo) < 8, "{loudest}");
+        assert_eq!(peak(&output[..echo - 10]), 0.0);
+        assert!(peak(&output[echo + 1000..]) < 1e-3);
+    }
+
+    #[test]
+    fn synced_echoes_follow_the_tempo() {
+        let synced = |division| TapeEchoParams {
+            sync: BoolParam::new("Sync", true),
+            division: EnumParam::new("Division", division),
+            ..params(HeadMode::Single, 100.0, 0.0)
+        };
+        let loudest = |output: &[f32]| {
+            (0..output.len())
+                .max_by(|&a, &b| output[a].abs().total_cmp(&output[b].abs()))
+                .unwrap()
+        };
+
+        // A dotted eighth at 150 BPM is 300 ms
+        let mut plugin = test_plugin(synced(NoteDivision::EighthDotted), 2);
+        plugin.tempo = 150.0;
+        plugin.time.reset(plugin.target_time());
+        let output = process(&mut plugin, &impulse(0.5));
+        let echo = loudest(&output);
+        assert!(echo.abs_diff(echo_at(300.0)) < 8, "{echo}");
+
+        // A whole note at 60 BPM is longer than the tape allows
+        let mut plugin = test_plugin(synced(NoteDivision::Whole), 2);
+        plugin.tempo = 60.0;
+        assert_eq!(plugin.target_time(), MAX_TIME);
+    }
+
+    #[test]
+    fn multi_head_mode_repeats_at_each_head() {
+        let mut plugin = test_plugin(params(HeadMode::Multi, 100.0, 0.0), 2);
+        let output = process(&mut plugin, &impulse(0.5));
+
+        let echo = echo_at(100.0);
+        let first = peak(&output[echo - 50..echo + 50]);
+        for head in 2..=MAX_HEADS {
+            let at = echo * head;
+            let level = peak(&output[at - 50..at + 50]);
+            assert!((level - first).abs() < first * 0.01, "head {head}: {level}");
+            assert!(peak(&output[at - echo + 200..at - 200]) < first * 0.01);
+        }
+    }
+
+    #[test]
+    fn feedback_below_unity_dies_away() {
+        let mut plugin = test_plugin(params(HeadMode::Single, 50.0, 0.5), 2);
+        let output = process(&mut plugin, &impulse(3.0));
+        assert!(peak(&output[SAMPLE_RATE as usize * 2..]) < 1e-6);
+    }
+
+    #[test]
+    fn high_feedback_self_oscillates_at_a_bounded_level() {
+        for heads in [HeadMode::Single, HeadMode::Multi] {
+            let mut plugin = test_plugin(params(heads, 50.0, 1.2), 2);
+            let output = process(&mut plugin, &impulse(5.0));
+
+            let tail = peak(&output[SAMPLE_RATE as usize * 4..]);
+            assert!(tail > 0.1, "{heads:?}: {tail}");
+            assert!(peak(&output) < MAX_HEADS as f32, "{heads:?}");
+        }
+    }
+
+    #[test]
+    fn wow_and_flutter_bend_the_pitch_of_the_echoes() {
+        let tone: Vec<f32> = (0..SAMPLE_RATE as usize)
+            .map(|i| (std::f32::consts::TAU * 1000.0 * i as f32 / SAMPLE_RATE).sin() * 0.1)
+            .collect();
+        let depth = |wow, flutter| {
+            let mut plugin = test_plugin(
+                TapeEchoParams {
+                    wow: fixed("Wow", wow),
+                    flutter: fixed("Flutter", flutter),
+                    ..params(HeadMode::Single, 100.0, 0.0)
+                },
+                2,
+            );
+            let output = process(&mut plugin, &tone);
+
+            // The time between rising zero crossings once the echo has started
+            let crossings: Vec<f32> = output
+                .windows(2)
+                .enumerate()
+                .skip(echo_at(200.0))
+                .filter(|(_, pair)| pair[0] < 0.0 && pair[1] >= 0.0)
+                .map(|(i, pair)| i as f32 + pair[0] / (pair[0] - pair[1]))
+                .collect();
+            let periods: Vec<f32> = crossings.windows(2).map(|pair| pair[1] - pair[0]).collect();
+            let longest = periods.iter().cloned().fold(f32::MIN, f32::max);
+            let shortest = periods.iter().cloned().fold(f32::MAX, f32::min);
+            longest / shortest
+        };
+
+        assert!(depth(0.0, 0.0) < 1.001);
+        assert!(depth(1.0, 0.0) > 1.005);
+        assert!(depth(0.0, 1.0) > 1.005);
+    }
+
+    #[test]
+    fn processing_does_not_allocate() {
+        let mut plugin = test_plugin(params(HeadMode::Multi, 100.0, 0.5), 2);
+        let mut left = impulse(1.0);
+        let mut right = left.clone();
+        assert_no_alloc(|| {
+            for (left, right) in left
+                .chunks_mut(BLOCK_SIZE)
+                .zip(right.chunks_mut(BLOCK_SIZE))
+            {
+                plugin.process_channels(&mut [left, right]);
+            }
+        });
+    }
+}
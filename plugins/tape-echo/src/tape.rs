@@ -0,0 +1,77 @@
+//! One channel's tape loop: a record head, a saturating tape, and up to three playback heads
+//! spaced evenly along it. The echo from the last head is fed back to the record head through
+//! the tape's saturation and tone, so high feedback settles into a self-oscillation instead of
+//! growing without bound.
+
+use dsp_core::delay::DelayLine;
+use dsp_core::filters::{FilterMode, StateVariableFilter};
+use dsp_core::waveshaper::{Curve, Waveshaper};
+
+/// Below this the repeats get thinner with every pass, like a worn tape machine, which also
+/// keeps the lows from piling up when it self-oscillates.
+const LOW_CUT_FREQUENCY: f32 = 80.0;
+
+pub struct Tape {
+    delay: DelayLine,
+    saturation: Waveshaper,
+    drive: f32,
+    tone: StateVariableFilter,
+    low_cut: StateVariableFilter,
+}
+
+impl Tape {
+    /// Allocates. `max_delay` is the longest delay to the last head in seconds.
+    pub fn new(sample_rate: f32, max_delay: f32) -> Self {
+        let max_delay = DelayLine::<f32>::samples_for(sample_rate, max_delay) + 2;
+        let mut saturation = Waveshaper::new(sample_rate);
+        saturation.set_curve(Curve::Tanh);
+        let mut low_cut = StateVariableFilter::new(sample_rate);
+        low_cut.set_mode(FilterMode::HighPass);
+        low_cut.set_params(LOW_CUT_FREQUENCY, std::f32::consts::FRAC_1_SQRT_2);
+
+        Self {
+            delay: DelayLine::new(max_delay),
+            saturation,
+            drive: 1.0,
+            tone: StateVariableFilter::new(sample_rate),
+            low_cut,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.delay.reset();
+        self.saturation.reset();
+        self.tone.reset();
+        self.low_cut.reset();
+    }
+
+    /// The linear gain into the tape's saturation. Quiet signals come out at the same level
+    /// whatever the drive, louder ones are squashed to at most `1 / drive`.
+    pub fn set_drive(&mut self, drive: f32) {
+        self.drive = drive.max(1.0);
+        self.saturation.set_drive(self.drive);
+    }
+
+    /// The cutoff of the low pass the repeats go through on every pass, in Hz.
+    pub fn set_tone(&mut self, cutoff: f32) {
+        self.tone
+            .set_params(cutoff, std::f32::consts::FRAC_1_SQRT_2);
+    }
+
+    /// Record `input` and return the sum of the first `heads` playback heads, the first one
+    /// `spacing` samples behind the record head and each next one another `spacing` further.
+    #[inline]
+    pub fn process(&mut self, input: f32, spacing: f32, heads: usize, feedback: f32) -> f32 {
+        let mut echo = 0.0;
+        let mut last = 0.0;
+        for head in 1..=heads {
+            last = self.delay.read(spacing * head as f32);
+            echo += last;
+        }
+
+        let recorded = self.saturation.process(input + last * feedback) / self.drive;
+        let recorded = self.low_cut.process(self.tone.process(recorded));
+        self.delay.write(recorded);
+        echo
+    }
+}
@@ -0,0 +1,38 @@
+use crate::TransientShaperParams;
+use nih_plug::prelude::*;
+use nih_plug_egui::create_egui_editor;
+use std::sync::Arc;
+use ui_widgets::{param_toggle, EditorParams, EditorShell, ParamKnob};
+
+pub(crate) fn default_params() -> EditorParams {
+    EditorParams::new((320, 200))
+}
+
+pub(crate) fn create(params: Arc<TransientShaperParams>) -> Option<Box<dyn Editor>> {
+    create_egui_editor(
+        params.editor.state.clone(),
+        (),
+        |_, _| {},
+        move |egui_ctx, setter, _state| {
+            EditorShell::new(&params.editor)
+                .with_bypass(&params.bypass)
+                .show(egui_ctx, setter, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.add(ParamKnob::for_param(&params.attack, setter))
+                            .on_hover_text("Boost or soften the start of every sound");
+                        ui.add(ParamKnob::for_param(&params.sustain, setter))
+                            .on_hover_text("Bring out or tighten the tail of every sound");
+                        ui.add(ParamKnob::for_param(&params.output, setter));
+                    });
+                    ui.horizontal(|ui| {
+                        param_toggle(ui, &params.clip, setter).on_hover_text(
+                            "Soft clip the output so boosted attacks stay below 0 dBFS",
+                        );
+                        param_toggle(ui, &params.lookahead, setter).on_hover_text(
+                            "Start shaping 2 ms before every transient, adding 2 ms of latency",
+                        );
+                    });
+                });
+        },
+    )
+}
@@ -0,0 +1,473 @@
+use dsp_core::dynamics::{db_to_gain, gain_to_db, EnvelopeFollower};
+use dsp_core::guard;
+use dsp_core::lookahead::Lookahead;
+use dsp_core::mix::MixStage;
+use nih_plug::prelude::*;
+use plugin_scaffold::layouts;
+use std::sync::Arc;
+use ui_widgets::EditorParams;
+
+mod editor;
+
+/// The most the shaper boosts or cuts, in dB.
+const MAX_GAIN_DB: f32 = 18.0;
+
+/// How far ahead the detector looks when lookahead is on, in seconds. Long enough to cover the
+/// attack follower's rise, so boosts and cuts start right at the onset.
+const LOOKAHEAD_TIME: f32 = 0.002;
+
+/// Shapes the attack and sustain of sounds without a threshold, by comparing envelope followers
+/// with different time constants.
+struct TransientShaper {
+    params: Arc<TransientShaperParams>,
+    detector: TransientDetector,
+    /// Delays the audio behind the detector when lookahead is on.
+    lookahead: Lookahead,
+    /// Only used for a click-free bypass, with the dry signal delayed along with the lookahead.
+    mix: MixStage,
+}
+
+#[derive(Params)]
+struct TransientShaperParams {
+    #[nested]
+    editor: EditorParams,
+
+    /// The host's bypass switch.
+    #[id = "bypass"]
+    pub bypass: BoolParam,
+
+    /// Boost or soften the start of every sound, in percent.
+    #[id = "attack"]
+    pub attack: FloatParam,
+
+    /// Bring out or tighten the tail of every sound, in percent.
+    #[id = "sustain"]
+    pub sustain: FloatParam,
+
+    #[id = "output"]
+    pub output: FloatParam,
+
+    /// Soft clip the output so boosted transients can't go over full scale.
+    #[id = "clip"]
+    pub clip: BoolParam,
+
+    /// Delay the audio so the gain changes start before the transients, at the cost of latency.
+    #[id = "lookahead"]
+    pub lookahead: BoolParam,
+}
+
+/// Measures how far the signal currently is into an attack or a decay, in dB. Both channels
+/// share one detector so the stereo image doesn't shift.
+struct TransientDetector {
+    /// A short RMS average. Peak followers with different time constants disagree on the
+    /// ripple of steady low notes, so they follow this instead.
+    level: EnvelopeFollower,
+    /// Attacks are where a fast follower rises ahead of a slow one.
+    attack_fast: EnvelopeFollower,
+    attack_slow: EnvelopeFollower,
+    /// Sustains are where a slowly released follower stays above a quickly released one.
+    sustain_fast: EnvelopeFollower,
+    sustain_slow: EnvelopeFollower,
+}
+
+impl TransientDetector {
+    fn new(sample_rate: f32) -> Self {
+        let follower = |attack: f32, release: f32| {
+            let mut follower = EnvelopeFollower::new(sample_rate);
+            follower.set_attack(attack);
+            follower.set_release(release);
+            follower
+        };
+
+        Self {
+            level: follower(0.005, 0.005),
+            attack_fast: follower(0.0005, 0.05),
+            attack_slow: follower(0.02, 0.05),
+            sustain_fast: follower(0.0005, 0.03),
+            sustain_slow: follower(0.0005, 0.3),
+        }
+    }
+
+    fn reset(&mut self) {
+        self.level.reset();
+        self.attack_fast.reset();
+        self.attack_slow.reset();
+        self.sustain_fast.reset();
+        self.sustain_slow.reset();
+    }
+
+    /// The gain for the current sample in dB, given the input's peak across channels and the
+    /// attack and sustain amounts from -1 to 1.
+    fn gain_db(&mut self, peak: f32, attack: f32, sustain: f32) -> f32 {
+        let level = self.level.process(peak * peak).sqrt();
+        let attack_db = gain_to_db(self.attack_fast.process(level))
+            - gain_to_db(self.attack_slow.process(level));
+        let sustain_db = gain_to_db(self.sustain_slow.process(level))
+            - gain_to_db(self.sustain_fast.process(level));
+
+        (attack * attack_db.max(0.0) + sustain * sustain_db.max(0.0))
+            .clamp(-MAX_GAIN_DB, MAX_GAIN_DB)
+    }
+}
+
+impl Default for TransientShaper {
+    fn default() -> Self {
+        Self {
+            params: Arc::new(TransientShaperParams::default()),
+            detector: TransientDetector::new(44100.0),
+            lookahead: Lookahead::new(0, 0),
+            mix: MixStage::new(44100.0, 0, 0, 0),
+        }
+    }
+}
+
+impl Default for TransientShaperParams {
+    fn default() -> Self {
+        let amount = |name| {
+            FloatParam::new(
+                name,
+                0.0,
+                FloatRange::Linear {
+                    min: -100.0,
+                    max: 100.0,
+                },
+            )
+            .with_smoother(SmoothingStyle::Linear(20.0))
+            .with_unit(" %")
+            .with_value_to_string(formatters::v2s_f32_rounded(0))
+        };
+
+        Self {
+            editor: editor::default_params(),
+
+            bypass: BoolParam::new("Bypass", false).make_bypass(),
+
+            attack: amount("Attack"),
+            sustain: amount("Sustain"),
+
+            output: FloatParam::new(
+                "Output",
+                util::db_to_gain(0.0),
+                FloatRange::Skewed {
+                    min: util::db_to_gain(-24.0),
+                    max: util::db_to_gain(12.0),
+                    factor: FloatRange::gain_skew_factor(-24.0, 12.0),
+                },
+            )
+            .with_smoother(SmoothingStyle::Logarithmic(50.0))
+            .with_unit(" dB")
+            .with_value_to_string(formatters::v2s_f32_gain_to_db(2))
+            .with_string_to_value(formatters::s2v_f32_gain_to_db()),
+
+            clip: BoolParam::new("Clip", true),
+            lookahead: BoolParam::new("Lookahead", false),
+        }
+    }
+}
+
+impl Plugin for TransientShaper {
+    const NAME: &'static str = "Transient Shaper";
+    const VENDOR: &'static str = "Your Studio";
+    const URL: &'static str = env!("CARGO_PKG_HOMEPAGE");
+    const EMAIL: &'static str = "contact@yourstudio.com";
+    const VERSION: &'static str = env!("CARGO_PKG_VERSION");
+
+    const AUDIO_IO_LAYOUTS: &'static [AudioIOLayout] = &[layouts::STEREO, layouts::MONO];
+
+    type SysExMessage = ();
+    type BackgroundTask = ();
+
+    fn params(&self) -> Arc<dyn Params> {
+        self.params.clone()
+    }
+
+    fn editor(&mut self, _async_executor: AsyncExecutor<Self>) -> Option<Box<dyn Editor>> {
+        editor::create(self.params.clone())
+    }
+
+    fn initialize(
+        &mut self,
+        audio_io_layout: &AudioIOLayout,
+        buffer_config: &BufferConfig,
+        context: &mut impl InitContext<Self>,
+    ) -> bool {
+        let num_channels = audio_io_layout
+            .main_output_channels
+            .map_or(0, |channels| channels.get() as usize);
+        let sample_rate = buffer_config.sample_rate;
+
+        let max_lookahead = Lookahead::samples_for(sample_rate, LOOKAHEAD_TIME);
+
+        self.detector = TransientDetector::new(sample_rate);
+        self.lookahead = Lookahead::new(num_channels, max_lookahead);
+        self.mix = MixStage::new(
+            sample_rate,
+            num_channels,
+            buffer_config.max_buffer_size as usize,
+            max_lookahead,
+        );
+        self.update_lookahead();
+        self.mix.set_mix(self.target_mix());
+        self.mix.reset();
+
+        context.set_latency_samples(self.lookahead.latency_samples());
+        true
+    }
+
+    fn reset(&mut self) {
+        self.detector.reset();
+        self.lookahead.reset();
+        self.mix.reset();
+    }
+
+    fn process(
+        &mut self,
+        buffer: &mut Buffer,
+        _aux: &mut AuxiliaryBuffers,
+        context: &mut impl ProcessContext<Self>,
+    ) -> ProcessStatus {
+        if self.update_lookahead() {
+            context.set_latency_samples(self.lookahead.latency_samples());
+        }
+        self.process_channels(buffer.as_slice());
+
+        ProcessStatus::Normal
+    }
+}
+
+impl TransientShaper {
+    fn process_channels(&mut self, channels: &mut [&mut [f32]]) {
+        self.mix.set_mix(self.target_mix());
+        self.mix.capture_dry(channels);
+
+        let clip = self.params.clip.value();
+        let num_samples = channels.first().map_or(0, |channel| channel.len());
+        for i in 0..num_samples {
+            let attack = self.params.attack.smoothed.next() / 100.0;
+            let sustain = self.params.sustain.smoothed.next() / 100.0;
+            let output = self.params.output.smoothed.next();
+
+            let peak = channels
+                .iter()
+                .map(|channel| channel[i].abs())
+                .fold(0.0, f32::max);
+            let gain = db_to_gain(self.detector.gain_db(peak, attack, sustain)) * output;
+
+            // The detector has seen the current input, the gain is applied to the delayed audio
+            for (channel_idx, channel) in channels.iter_mut().enumerate() {
+                let sample = self.lookahead.process_sample(channel_idx, channel[i]) * gain;
+                channel[i] = if clip { sample.tanh() } else { sample };
+            }
+            self.lookahead.advance();
+        }
+
+        for channel in channels.iter() {
+            guard::check_block("TransientShaper", channel);
+        }
+        self.mix.mix_into(channels);
+    }
+
+    /// Switch the lookahead on or off, delaying the dry signal to match. Returns whether the
+    /// latency changed.
+    fn update_lookahead(&mut self) -> bool {
+        let delay = if self.params.lookahead.value() {
+            self.lookahead.max_delay()
+        } else {
+            0
+        };
+        if delay == self.lookahead.delay() {
+            return false;
+        }
+
+        self.lookahead.set_delay(delay);
+        self.mix.set_latency(delay);
+        true
+    }
+
+    /// Bypassing fades to the dry signal through the mix stage.
+    fn target_mix(&self) -> f32 {
+        if self.params.bypass.value() {
+            0.0
+        } else {
+            1.0
+        }
+    }
+}
+
+impl ClapPlugin for TransientShaper {
+    const CLAP_ID: &'static str = "com.yourstudio.transient-shaper";
+    const CLAP_DESCRIPTION: Option<&'static str> =
+        Some("A transient shaper for the attack and sustain of drums and percussive sounds");
+    const CLAP_MANUAL_URL: Option<&'static str> = Some(Self::URL);
+    const CLAP_SUPPORT_URL: Option<&'static str> = None;
+    const CLAP_FEATURES: &'static [ClapFeature] = &[
+        ClapFeature::AudioEffect,
+        ClapFeature::TransientShaper,
+        ClapFeature::Stereo,
+        ClapFeature::Mono,
+    ];
+}
+
+impl Vst3Plugin for TransientShaper {
+    const VST3_CLASS_ID: [u8; 16] = *b"TransientShaper0";
+    const VST3_SUBCATEGORIES: &'static [Vst3SubCategory] =
+        &[Vst3SubCategory::Fx, Vst3SubCategory::Dynamics];
+}
+
+nih_export_clap!(TransientShaper);
+nih_export_vst3!(TransientShaper);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use plugin_scaffold::test_util::assert_no_alloc;
+
+    plugin_scaffold::install_alloc_disabler!();
+
+    const SAMPLE_RATE: f32 = 48000.0;
+    const BLOCK_SIZE: usize = 512;
+
+    fn test_plugin(attack: f32, sustain: f32) -> TransientShaper {
+        test_plugin_with_lookahead(attack, sustain, false)
+    }
+
+    fn test_plugin_with_lookahead(attack: f32, sustain: f32, lookahead: bool) -> TransientShaper {
+        let params = TransientShaperParams {
+            clip: BoolParam::new("Clip", false),
+            lookahead: BoolParam::new("Lookahead", lookahead),
+            ..TransientShaperParams::default()
+        };
+        params.attack.smoothed.reset(attack);
+        params.sustain.smoothed.reset(sustain);
+        params.output.smoothed.reset(params.output.value());
+
+        let max_lookahead = Lookahead::samples_for(SAMPLE_RATE, LOOKAHEAD_TIME);
+        let mut plugin = TransientShaper {
+            params: Arc::new(params),
+            detector: TransientDetector::new(SAMPLE_RATE),
+            lookahead: Lookahead::new(1, max_lookahead),
+            mix: MixStage::new(SAMPLE_RATE, 1, BLOCK_SIZE, max_lookahead),
+        };
+        plugin.update_lookahead();
+        plugin.mix.reset();
+        plugin
+    }
+
+    /// Silence followed by a 200 Hz tone starting at 100 ms, decaying with the time constant
+    /// `decay` in seconds.
+    fn note(amplitude: f32, decay: f32) -> Vec<f32> {
+        let onset = SAMPLE_RATE as usize / 10;
+        (0..SAMPLE_RATE as usize)
+            .map(|i| match i.checked_sub(onset) {
+                Some(t) => {
+                    let t = t as f32 / SAMPLE_RATE;
+                    (std::f32::consts::TAU * 200.0 * t).sin() * amplitude * (-t / decay).exp()
+                }
+                None => 0.0,
+            })
+            .collect()
+    }
+
+    fn process(plugin: &mut TransientShaper, input: &[f32]) -> Vec<f32> {
+        let mut output = input.to_vec();
+        for block in output.chunks_mut(BLOCK_SIZE) {
+            plugin.process_channels(&mut [block]);
+        }
+        output
+    }
+
+    /// The peak level from `start` to `end` seconds.
+    fn peak(samples: &[f32], start: f32, end: f32) -> f32 {
+        samples[(start * SAMPLE_RATE) as usize..(end * SAMPLE_RATE) as usize]
+            .iter()
+            .fold(0.0, |peak, sample| f32::max(peak, sample.abs()))
+    }
+
+    #[test]
+    fn neutral_settings_pass_audio_through() {
+        let mut plugin = test_plugin(0.0, 0.0);
+        let input = note(0.25, f32::INFINITY);
+        let output = process(&mut plugin, &input);
+
+        for (input, output) in input.iter().zip(&output) {
+            assert!((input - output).abs() < 1e-6, "{input} != {output}");
+        }
+    }
+
+    #[test]
+    fn attack_boosts_the_onset_but_not_the_steady_part() {
+        let mut plugin = test_plugin(100.0, 0.0);
+        let output = process(&mut plugin, &note(0.25, f32::INFINITY));
+
+        let onset = peak(&output, 0.1, 0.11);
+        let steady = peak(&output, 0.6, 0.7);
+        assert!(onset > 0.25 * 2.0, "{onset}");
+        assert!((steady - 0.25).abs() < 0.02, "{steady}");
+    }
+
+    #[test]
+    fn sustain_lengthens_or_shortens_the_tail() {
+        let input = note(0.5, 0.15);
+        let neutral = process(&mut test_plugin(0.0, 0.0), &input);
+        let longer = process(&mut test_plugin(0.0, 100.0), &input);
+        let shorter = process(&mut test_plugin(0.0, -100.0), &input);
+
+        // The start of the note is left alone
+        let onset = peak(&neutral, 0.1, 0.11);
+        assert!((peak(&longer, 0.1, 0.11) - onset).abs() < 0.01);
+        assert!((peak(&shorter, 0.1, 0.11) - onset).abs() < 0.01);
+
+        let tail = peak(&neutral, 0.4, 0.45);
+        assert!(peak(&longer, 0.4, 0.45) > tail * 2.0);
+        assert!(peak(&shorter, 0.4, 0.45) < tail * 0.5);
+    }
+
+    #[test]
+    fn lookahead_delays_the_audio_by_the_reported_latency() {
+        let mut plugin = test_plugin_with_lookahead(0.0, 0.0, true);
+        let latency = plugin.lookahead.latency_samples() as usize;
+        assert_eq!(latency, 96);
+
+        let input = note(0.25, f32::INFINITY);
+        let output = process(&mut plugin, &input);
+        for (input, output) in input.iter().zip(&output[latency..]) {
+            assert!((input - output).abs() < 1e-6, "{input} != {output}");
+        }
+    }
+
+    #[test]
+    fn lookahead_boosts_the_onset_from_its_first_cycle() {
+        // The note swells out of a quieter tone, so the detector doesn't jump straight to the
+        // maximum gain like it does coming out of silence
+        let mut input = note(0.25, f32::INFINITY);
+        let onset = SAMPLE_RATE as usize / 10;
+        for (i, sample) in input[..onset].iter_mut().enumerate() {
+            *sample = (std::f32::consts::TAU * 200.0 * i as f32 / SAMPLE_RATE).sin() * 0.05;
+        }
+
+        let direct = process(&mut test_plugin(100.0, 0.0), &input);
+        let mut plugin = test_plugin_with_lookahead(100.0, 0.0, true);
+        let latency = plugin.lookahead.latency_samples() as usize;
+        let ahead = process(&mut plugin, &input);
+
+        // The first 20 samples of the note
+        let direct = peak(&direct, 0.1, 0.1004);
+        let ahead = peak(&ahead[latency..], 0.1, 0.1004);
+        assert!(
+            ahead > direct * 2.0,
+            "{ahead} with lookahead, {direct} without"
+        );
+    }
+
+    #[test]
+    fn processing_does_not_allocate() {
+        let mut plugin = test_plugin_with_lookahead(100.0, -50.0, true);
+        let mut samples = note(0.25, 0.1);
+        assert_no_alloc(|| {
+            for block in samples.chunks_mut(BLOCK_SIZE) {
+                plugin.update_lookahead();
+                plugin.process_channels(&mut [block]);
+            }
+        });
+    }
+}
@@ -0,0 +1,93 @@
+use crate::needle::CentsNeedle;
+use crate::{PitchState, TunerParams};
+use dsp_core::analysis::NoteOffset;
+use nih_plug::prelude::*;
+use nih_plug_egui::create_egui_editor;
+use nih_plug_egui::egui::{RichText, Vec2};
+use std::sync::Arc;
+use ui_widgets::{param_toggle, EditorParams, EditorShell, ParamKnob};
+
+const PITCH_CLASS_NAMES: [&str; 12] = [
+    "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B",
+];
+
+/// Time constant for smoothing the needle, so it doesn't jitter between estimates.
+const NEEDLE_SMOOTHING_SECONDS: f32 = 0.08;
+
+pub(crate) fn default_params() -> EditorParams {
+    EditorParams::new((340, 340))
+}
+
+/// GUI-thread editor state.
+#[derive(Default)]
+struct EditorState {
+    /// The note being tuned and the needle's smoothed offset from it.
+    needle: Option<NoteOffset>,
+}
+
+impl EditorState {
+    fn update(&mut self, offset: Option<NoteOffset>, elapsed: f32) {
+        self.needle = match (self.needle, offset) {
+            // Only smooth within a note, jumping to a new note should be immediate
+            (Some(needle), Some(offset)) if needle.note == offset.note => {
+                let keep = (-elapsed / NEEDLE_SMOOTHING_SECONDS).exp();
+                Some(NoteOffset {
+                    cents: needle.cents * keep + offset.cents * (1.0 - keep),
+                    ..offset
+                })
+            }
+            (_, offset) => offset,
+        };
+    }
+}
+
+fn note_name(note: u8) -> String {
+    let octave = note as i32 / 12 - 1;
+    format!("{}{octave}", PITCH_CLASS_NAMES[note as usize % 12])
+}
+
+pub(crate) fn create(params: Arc<TunerParams>, pitch: Arc<PitchState>) -> Option<Box<dyn Editor>> {
+    create_egui_editor(
+        params.editor.state.clone(),
+        EditorState::default(),
+        |_, _| {},
+        move |egui_ctx, setter, state| {
+            let detected = pitch.get();
+            let offset = detected
+                .map(|frequency| NoteOffset::from_frequency(frequency, params.reference.value()));
+            let elapsed = egui_ctx.input(|input| input.stable_dt);
+            state.update(offset, elapsed);
+
+            EditorShell::new(&params.editor).show(egui_ctx, setter, |ui| {
+                ui.vertical_centered(|ui| {
+                    let note = state
+                        .needle
+                        .map_or("-".to_owned(), |needle| note_name(needle.note));
+                    ui.label(RichText::new(note).size(40.0).strong());
+
+                    ui.add(
+                        CentsNeedle::new(state.needle.map(|needle| needle.cents))
+                            .with_size(Vec2::new(320.0, 150.0)),
+                    );
+
+                    match (detected, state.needle) {
+                        (Some(frequency), Some(needle)) => {
+                            ui.label(format!("{frequency:.1} Hz, {:+.1} cents", needle.cents))
+                        }
+                        _ => ui.label("No pitch"),
+                    };
+                });
+
+                ui.horizontal(|ui| {
+                    ui.add(ParamKnob::for_param(&params.reference, setter))
+                        .on_hover_text("The frequency of A4");
+                    param_toggle(ui, &params.mute, setter)
+                        .on_hover_text("Silence the output while tuning");
+                });
+            });
+
+            // The display is live, so keep repainting while the editor is open
+            egui_ctx.request_repaint();
+        },
+    )
+}
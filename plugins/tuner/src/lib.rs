@@ -0,0 +1,273 @@
+use atomic_float::AtomicF32;
+use dsp_core::analysis::PitchDetector;
+use dsp_core::bypass::SoftBypass;
+use nih_plug::prelude::*;
+use plugin_scaffold::layouts;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use ui_widgets::EditorParams;
+
+mod editor;
+mod needle;
+
+/// The lowest note the tuner listens for, a little below a five string bass's low B.
+const MIN_FREQUENCY: f32 = 28.0;
+const MAX_FREQUENCY: f32 = 4200.0;
+/// How often the pitch is estimated.
+const DETECTIONS_PER_SECOND: f32 = 25.0;
+
+/// Passes audio through, or mutes it, while detecting the pitch of the input.
+struct Tuner {
+    params: Arc<TunerParams>,
+    detector: PitchDetector,
+    pitch: Arc<PitchState>,
+    /// Fades the output in and out when muting.
+    mute: SoftBypass,
+    /// The input's average across channels.
+    mono: Vec<f32>,
+}
+
+#[derive(Params)]
+struct TunerParams {
+    #[nested]
+    editor: EditorParams,
+
+    /// The frequency of A4.
+    #[id = "reference"]
+    pub reference: FloatParam,
+
+    /// Silence the output while tuning.
+    #[id = "mute"]
+    pub mute: BoolParam,
+}
+
+/// The latest pitch estimate, shared between the audio thread and the editor.
+pub struct PitchState {
+    /// Zero when the input is silent or has no clear pitch.
+    frequency: AtomicF32,
+}
+
+impl PitchState {
+    fn new() -> Self {
+        Self {
+            frequency: AtomicF32::new(0.0),
+        }
+    }
+
+    /// The detected frequency, if there is a pitch.
+    pub fn get(&self) -> Option<f32> {
+        let frequency = self.frequency.load(Ordering::Relaxed);
+        (frequency > 0.0).then_some(frequency)
+    }
+
+    fn set(&self, frequency: Option<f32>) {
+        self.frequency
+            .store(frequency.unwrap_or(0.0), Ordering::Relaxed);
+    }
+}
+
+impl Default for Tuner {
+    fn default() -> Self {
+        Self {
+            params: Arc::new(TunerParams::default()),
+            detector: PitchDetector::new(44100.0, MIN_FREQUENCY, MAX_FREQUENCY, 1),
+            pitch: Arc::new(PitchState::new()),
+            mute: SoftBypass::new(44100.0),
+            mono: Vec::new(),
+        }
+    }
+}
+
+impl Default for TunerParams {
+    fn default() -> Self {
+        Self {
+            editor: editor::default_params(),
+
+            reference: FloatParam::new(
+                "Reference",
+                440.0,
+                FloatRange::Linear {
+                    min: 415.0,
+                    max: 466.0,
+                },
+            )
+            .with_step_size(0.1)
+            .with_unit(" Hz")
+            .with_value_to_string(formatters::v2s_f32_rounded(1)),
+
+            mute: BoolParam::new("Mute", false),
+        }
+    }
+}
+
+impl Plugin for Tuner {
+    const NAME: &'static str = "Tuner";
+    const VENDOR: &'static str = "Your Studio";
+    const URL: &'static str = env!("CARGO_PKG_HOMEPAGE");
+    const EMAIL: &'static str = "contact@yourstudio.com";
+    const VERSION: &'static str = env!("CARGO_PKG_VERSION");
+
+    const AUDIO_IO_LAYOUTS: &'static [AudioIOLayout] = &[layouts::STEREO, layouts::MONO];
+
+    type SysExMessage = ();
+    type BackgroundTask = ();
+
+    fn params(&self) -> Arc<dyn Params> {
+        self.params.clone()
+    }
+
+    fn editor(&mut self, _async_executor: AsyncExecutor<Self>) -> Option<Box<dyn Editor>> {
+        editor::create(self.params.clone(), self.pitch.clone())
+    }
+
+    fn initialize(
+        &mut self,
+        _audio_io_layout: &AudioIOLayout,
+        buffer_config: &BufferConfig,
+        _context: &mut impl InitContext<Self>,
+    ) -> bool {
+        let sample_rate = buffer_config.sample_rate;
+        let hop = (sample_rate / DETECTIONS_PER_SECOND) as usize;
+
+        self.detector = PitchDetector::new(sample_rate, MIN_FREQUENCY, MAX_FREQUENCY, hop);
+        self.detector.set_gate(-60.0);
+        self.mute = SoftBypass::new(sample_rate);
+        self.mute.reset(self.params.mute.value());
+        self.mono = vec![0.0; buffer_config.max_buffer_size as usize];
+        true
+    }
+
+    fn reset(&mut self) {
+        self.detector.reset();
+        self.pitch.set(None);
+        self.mute.reset(self.params.mute.value());
+    }
+
+    fn process(
+        &mut self,
+        buffer: &mut Buffer,
+        _aux: &mut AuxiliaryBuffers,
+        _context: &mut impl ProcessContext<Self>,
+    ) -> ProcessStatus {
+        // Nobody sees the pitch while the editor is closed, so skip the detection
+        if self.params.editor.state.is_open() {
+            self.detect(buffer.as_slice_immutable());
+        }
+
+        self.mute.set_bypassed(self.params.mute.value());
+        self.mute.process_to_silence(buffer.as_slice());
+
+        ProcessStatus::Normal
+    }
+}
+
+impl Tuner {
+    fn detect(&mut self, channels: &[&mut [f32]]) {
+        let Some(num_samples) = channels.first().map(|channel| channel.len()) else {
+            return;
+        };
+
+        let mono = &mut self.mono[..num_samples];
+        mono.fill(0.0);
+        for channel in channels {
+            for (mono, sample) in mono.iter_mut().zip(channel.iter()) {
+                *mono += sample / channels.len() as f32;
+            }
+        }
+
+        for &sample in mono.iter() {
+            if self.detector.process(sample) {
+                self.pitch
+                    .set(self.detector.pitch().map(|pitch| pitch.frequency));
+            }
+        }
+    }
+}
+
+impl ClapPlugin for Tuner {
+    const CLAP_ID: &'static str = "com.yourstudio.tuner";
+    const CLAP_DESCRIPTION: Option<&'static str> =
+        Some("A chromatic tuner with an adjustable reference pitch");
+    const CLAP_MANUAL_URL: Option<&'static str> = Some(Self::URL);
+    const CLAP_SUPPORT_URL: Option<&'static str> = None;
+    const CLAP_FEATURES: &'static [ClapFeature] = &[
+        ClapFeature::AudioEffect,
+        ClapFeature::Analyzer,
+        ClapFeature::Utility,
+        ClapFeature::Stereo,
+        ClapFeature::Mono,
+    ];
+}
+
+impl Vst3Plugin for Tuner {
+    const VST3_CLASS_ID: [u8; 16] = *b"Tuner00000000000";
+    const VST3_SUBCATEGORIES: &'static [Vst3SubCategory] =
+        &[Vst3SubCategory::Fx, Vst3SubCategory::Analyzer];
+}
+
+nih_export_clap!(Tuner);
+nih_export_vst3!(Tuner);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use plugin_scaffold::test_util::assert_no_alloc;
+
+    plugin_scaffold::install_alloc_disabler!();
+
+    const SAMPLE_RATE: f32 = 48000.0;
+    const BLOCK_SIZE: usize = 512;
+
+    fn test_tuner() -> Tuner {
+        let mut tuner = Tuner::default();
+        let hop = (SAMPLE_RATE / DETECTIONS_PER_SECOND) as usize;
+        tuner.detector = PitchDetector::new(SAMPLE_RATE, MIN_FREQUENCY, MAX_FREQUENCY, hop);
+        tuner.mono = vec![0.0; BLOCK_SIZE];
+        tuner
+    }
+
+    fn sine(frequency: f32, num_samples: usize) -> Vec<f32> {
+        (0..num_samples)
+            .map(|i| (std::f32::consts::TAU * frequency * i as f32 / SAMPLE_RATE).sin() * 0.5)
+            .collect()
+    }
+
+    #[test]
+    fn detects_the_pitch_across_channels() {
+        let mut tuner = test_tuner();
+        let mut left = sine(110.0, SAMPLE_RATE as usize / 2);
+        let mut right = left.clone();
+        for (left, right) in left
+            .chunks_mut(BLOCK_SIZE)
+            .zip(right.chunks_mut(BLOCK_SIZE))
+        {
+            tuner.detect(&[left, right]);
+        }
+
+        let frequency = tuner.pitch.get().unwrap();
+        assert!((frequency - 110.0).abs() < 0.1, "{frequency}");
+    }
+
+    #[test]
+    fn silence_has_no_pitch() {
+        let mut tuner = test_tuner();
+        let mut input = sine(440.0, SAMPLE_RATE as usize / 2);
+        input.resize(SAMPLE_RATE as usize, 0.0);
+        for block in input.chunks_mut(BLOCK_SIZE) {
+            tuner.detect(&[block]);
+        }
+
+        assert_eq!(tuner.pitch.get(), None);
+    }
+
+    #[test]
+    fn detecting_does_not_allocate() {
+        let mut tuner = test_tuner();
+        let mut input = sine(110.0, SAMPLE_RATE as usize / 2);
+        assert_no_alloc(|| {
+            for block in input.chunks_mut(BLOCK_SIZE) {
+                tuner.detect(&[block]);
+            }
+        });
+    }
+}
@@ -0,0 +1,111 @@
+//! The tuner's cents meter, a needle swinging over a scale from -50 to +50 cents.
+
+use nih_plug_egui::egui::{
+    self, Align2, Color32, FontId, Pos2, Response, Sense, Shape, Stroke, Ui, Vec2, Widget,
+};
+use std::f32::consts::{FRAC_PI_2, FRAC_PI_3};
+
+/// Notes this close count as in tune, and the scale lights up.
+pub const IN_TUNE_CENTS: f32 = 3.0;
+
+/// The needle swings this far either side of vertical at ±50 cents.
+const HALF_SWEEP: f32 = FRAC_PI_3;
+const TICK_SPACING_CENTS: i32 = 10;
+
+pub struct CentsNeedle {
+    /// `None` when there's no pitch, which parks the needle in the middle and dims it.
+    cents: Option<f32>,
+    size: Vec2,
+}
+
+impl CentsNeedle {
+    pub fn new(cents: Option<f32>) -> Self {
+        Self {
+            cents,
+            size: Vec2::new(300.0, 150.0),
+        }
+    }
+
+    pub fn with_size(mut self, size: Vec2) -> Self {
+        self.size = size;
+        self
+    }
+}
+
+/// The screen space angle for an offset in cents, zero pointing straight up.
+fn cents_to_angle(cents: f32) -> f32 {
+    -FRAC_PI_2 + (cents / 50.0).clamp(-1.0, 1.0) * HALF_SWEEP
+}
+
+fn arc(center: Pos2, radius: f32, from_cents: f32, to_cents: f32) -> Vec<Pos2> {
+    const SEGMENTS: usize = 32;
+
+    (0..=SEGMENTS)
+        .map(|i| {
+            let cents = from_cents + (to_cents - from_cents) * (i as f32 / SEGMENTS as f32);
+            center + radius * Vec2::angled(cents_to_angle(cents))
+        })
+        .collect()
+}
+
+impl Widget for CentsNeedle {
+    fn ui(self, ui: &mut Ui) -> Response {
+        let (rect, response) = ui.allocate_exact_size(self.size, Sense::hover());
+
+        if ui.is_rect_visible(rect) {
+            let visuals = ui.visuals();
+            let painter = ui.painter_at(rect);
+            painter.rect_filled(rect, 0.0, visuals.extreme_bg_color);
+
+            let pivot = egui::pos2(rect.center().x, rect.bottom() - 10.0);
+            let radius = (rect.height() - 30.0).min(rect.width() / 2.0 - 20.0);
+            let in_tune = self.cents.is_some_and(|cents| cents.abs() <= IN_TUNE_CENTS);
+            let in_tune_color = if in_tune {
+                Color32::from_rgb(80, 200, 120)
+            } else {
+                visuals.weak_text_color()
+            };
+
+            painter.add(Shape::line(
+                arc(pivot, radius, -50.0, 50.0),
+                Stroke::new(1.0, visuals.weak_text_color()),
+            ));
+            painter.add(Shape::line(
+                arc(pivot, radius, -IN_TUNE_CENTS, IN_TUNE_CENTS),
+                Stroke::new(4.0, in_tune_color),
+            ));
+
+            for cents in (-50..=50).step_by(TICK_SPACING_CENTS as usize) {
+                let direction = Vec2::angled(cents_to_angle(cents as f32));
+                let length = if cents % 50 == 0 { 10.0 } else { 5.0 };
+                painter.line_segment(
+                    [
+                        pivot + (radius - length) * direction,
+                        pivot + radius * direction,
+                    ],
+                    Stroke::new(1.0, visuals.weak_text_color()),
+                );
+                if cents.abs() == 50 {
+                    painter.text(
+                        pivot + (radius + 8.0) * direction,
+                        Align2::CENTER_CENTER,
+                        format!("{cents:+}"),
+                        FontId::proportional(10.0),
+                        visuals.weak_text_color(),
+                    );
+                }
+            }
+
+            let needle_color = match self.cents {
+                Some(_) if in_tune => in_tune_color,
+                Some(_) => visuals.strong_text_color(),
+                None => visuals.faint_bg_color,
+            };
+            let tip = pivot + radius * Vec2::angled(cents_to_angle(self.cents.unwrap_or(0.0)));
+            painter.line_segment([pivot, tip], Stroke::new(2.0, needle_color));
+            painter.circle_filled(pivot, 4.0, needle_color);
+        }
+
+        response
+    }
+}
@@ -0,0 +1,36 @@
+use crate::UtilityParams;
+use nih_plug::prelude::*;
+use nih_plug_egui::create_egui_editor;
+use std::sync::Arc;
+use ui_widgets::{param_toggle, EditorParams, EditorShell, ParamKnob};
+
+pub(crate) fn default_params() -> EditorParams {
+    EditorParams::new((300, 220))
+}
+
+pub(crate) fn create(params: Arc<UtilityParams>) -> Option<Box<dyn Editor>> {
+    create_egui_editor(
+        params.editor.state.clone(),
+        (),
+        |_, _| {},
+        move |egui_ctx, setter, _state| {
+            EditorShell::new(&params.editor)
+                .with_bypass(&params.bypass)
+                .show(egui_ctx, setter, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.add(ParamKnob::for_param(&params.gain, setter));
+                        ui.vertical(|ui| {
+                            param_toggle(ui, &params.invert_left, setter);
+                            param_toggle(ui, &params.invert_right, setter);
+                            param_toggle(ui, &params.swap, setter);
+                            param_toggle(ui, &params.mono, setter).on_hover_text(
+                                "Sum both channels to mono to check for phase issues",
+                            );
+                            param_toggle(ui, &params.dc_filter, setter)
+                                .on_hover_text("Remove DC offset with a high pass at 10 Hz");
+                        });
+                    });
+                });
+        },
+    )
+}
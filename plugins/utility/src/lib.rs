@@ -0,0 +1,443 @@
+use dsp_core::guard;
+use dsp_core::mix::MixStage;
+use dsp_core::utils::{LinearSmoother, Smoother};
+use dsp_core::waveshaper::DcBlocker;
+use nih_plug::prelude::*;
+use plugin_scaffold::layouts;
+use std::sync::Arc;
+use ui_widgets::EditorParams;
+
+mod editor;
+
+/// How long switching the polarity, swap, mono, and DC filter takes, in seconds. Long enough to
+/// avoid a click, short enough to sound like a switch.
+const SWITCH_TIME: f32 = 0.01;
+
+/// Gain, polarity, channel swapping, mono summing, and DC removal. The simplest complete effect
+/// in the workspace, and the one to copy when starting a new effect plugin.
+struct Utility {
+    params: Arc<UtilityParams>,
+    /// How much of each input channel goes to each output channel, as `[to][from]`. Polarity,
+    /// swap, and mono are all routings, so switching them crossfades the matrix.
+    routing: [[LinearSmoother; 2]; 2],
+    dc_blockers: [DcBlocker; 2],
+    /// From 0 for the unfiltered input to 1 for the DC filtered input.
+    dc_filter: LinearSmoother,
+    /// Only used for a click-free bypass.
+    mix: MixStage,
+    /// Swapping and mono summing do nothing on a mono layout.
+    stereo: bool,
+}
+
+#[derive(Params)]
+struct UtilityParams {
+    #[nested]
+    editor: EditorParams,
+
+    /// The host's bypass switch.
+    #[id = "bypass"]
+    pub bypass: BoolParam,
+
+    /// From silence up to +24 dB.
+    #[id = "gain"]
+    pub gain: FloatParam,
+
+    #[id = "invert_left"]
+    pub invert_left: BoolParam,
+
+    #[id = "invert_right"]
+    pub invert_right: BoolParam,
+
+    /// Swap the left and right channels.
+    #[id = "swap"]
+    pub swap: BoolParam,
+
+    /// Sum both channels to mono, after the polarity and swap.
+    #[id = "mono"]
+    pub mono: BoolParam,
+
+    /// Remove DC offset with a high pass at about 10 Hz.
+    #[id = "dc_filter"]
+    pub dc_filter: BoolParam,
+}
+
+impl Default for Utility {
+    fn default() -> Self {
+        Self {
+            params: Arc::new(UtilityParams::default()),
+            routing: std::array::from_fn(|_| std::array::from_fn(|_| LinearSmoother::new(0))),
+            dc_blockers: [DcBlocker::new(44100.0), DcBlocker::new(44100.0)],
+            dc_filter: LinearSmoother::new(0),
+            mix: MixStage::new(44100.0, 0, 0, 0),
+            stereo: true,
+        }
+    }
+}
+
+impl Default for UtilityParams {
+    fn default() -> Self {
+        Self {
+            editor: editor::default_params(),
+
+            bypass: BoolParam::new("Bypass", false).make_bypass(),
+
+            gain: FloatParam::new(
+                "Gain",
+                util::db_to_gain(0.0),
+                FloatRange::Skewed {
+                    min: 0.0,
+                    max: util::db_to_gain(24.0),
+                    factor: FloatRange::gain_skew_factor(util::MINUS_INFINITY_DB, 24.0),
+                },
+            )
+            // Logarithmic smoothing can't reach silence
+            .with_smoother(SmoothingStyle::Linear(20.0))
+            .with_unit(" dB")
+            .with_value_to_string(formatters::v2s_f32_gain_to_db(2))
+            .with_string_to_value(formatters::s2v_f32_gain_to_db()),
+
+            invert_left: BoolParam::new("Invert Left", false),
+            invert_right: BoolParam::new("Invert Right", false),
+            swap: BoolParam::new("Swap L/R", false),
+            mono: BoolParam::new("Mono", false),
+            dc_filter: BoolParam::new("DC Filter", false),
+        }
+    }
+}
+
+impl Plugin for Utility {
+    const NAME: &'static str = "Utility";
+    const VENDOR: &'static str = "Your Studio";
+    const URL: &'static str = env!("CARGO_PKG_HOMEPAGE");
+    const EMAIL: &'static str = "contact@yourstudio.com";
+    const VERSION: &'static str = env!("CARGO_PKG_VERSION");
+
+    const AUDIO_IO_LAYOUTS: &'static [AudioIOLayout] = &[layouts::STEREO, layouts::MONO];
+
+    type SysExMessage = ();
+    type BackgroundTask = ();
+
+    fn params(&self) -> Arc<dyn Params> {
+        self.params.clone()
+    }
+
+    fn editor(&mut self, _async_executor: AsyncExecutor<Self>) -> Option<Box<dyn Editor>> {
+        editor::create(self.params.clone())
+    }
+
+    fn initialize(
+        &mut self,
+        audio_io_layout: &AudioIOLayout,
+        buffer_config: &BufferConfig,
+        _context: &mut impl InitContext<Self>,
+    ) -> bool {
+        let num_channels = audio_io_layout
+            .main_output_channels
+            .map_or(0, |channels| channels.get() as usize);
+        let sample_rate = buffer_config.sample_rate;
+
+        self.stereo = num_channels == 2;
+        self.set_sample_rate(sample_rate);
+        self.mix = MixStage::new(
+            sample_rate,
+            num_channels,
+            buffer_config.max_buffer_size as usize,
+            0,
+        );
+        self.reset();
+        true
+    }
+
+    fn reset(&mut self) {
+        let routing = self.target_routing();
+        for (smoothers, targets) in self.routing.iter_mut().zip(routing) {
+            for (smoother, target) in smoothers.iter_mut().zip(targets) {
+                smoother.reset(target);
+            }
+        }
+        self.dc_filter.reset(self.target_dc_filter());
+        for dc_blocker in &mut self.dc_blockers {
+            dc_blocker.reset();
+        }
+        self.mix.set_mix(self.target_mix());
+        self.mix.reset();
+    }
+
+    fn process(
+        &mut self,
+        buffer: &mut Buffer,
+        _aux: &mut AuxiliaryBuffers,
+        _context: &mut impl ProcessContext<Self>,
+    ) -> ProcessStatus {
+        self.process_channels(buffer.as_slice());
+
+        ProcessStatus::Normal
+    }
+}
+
+impl Utility {
+    fn set_sample_rate(&mut self, sample_rate: f32) {
+        let switch_samples = (SWITCH_TIME * sample_rate) as usize;
+        for smoother in self.routing.iter_mut().flatten() {
+            smoother.set_ramp_length(switch_samples);
+        }
+        self.dc_filter.set_ramp_length(switch_samples);
+        for dc_blocker in &mut self.dc_blockers {
+            dc_blocker.set_sample_rate(sample_rate);
+        }
+    }
+
+    fn process_channels(&mut self, channels: &mut [&mut [f32]]) {
+        self.mix.set_mix(self.target_mix());
+        self.mix.capture_dry(channels);
+
+        let routing = self.target_routing();
+        for (smoothers, targets) in self.routing.iter_mut().zip(routing) {
+            for (smoother, target) in smoothers.iter_mut().zip(targets) {
+                smoother.set_target(target);
+            }
+        }
+        self.dc_filter.set_target(self.target_dc_filter());
+
+        let num_samples = channels.first().map_or(0, |channel| channel.len());
+        for i in 0..num_samples {
+            let gain = self.params.gain.smoothed.next();
+            let dc_filter = self.dc_filter.next();
+
+            // The blockers keep running while the filter is off so switching it on is smooth
+            let mut input = [0.0; 2];
+            for ((input, channel), dc_blocker) in input
+                .iter_mut()
+                .zip(channels.iter())
+                .zip(&mut self.dc_blockers)
+            {
+                let sample = channel[i];
+                let filtered = dc_blocker.process(sample);
+                *input = sample + (filtered - sample) * dc_filter;
+            }
+
+            let mut output = [0.0; 2];
+            for (output, smoothers) in output.iter_mut().zip(&mut self.routing) {
+                *output = smoothers[0].next() * input[0] + smoothers[1].next() * input[1];
+            }
+
+            for (channel, output) in channels.iter_mut().zip(output) {
+                channel[i] = output * gain;
+            }
+        }
+
+        for channel in channels.iter() {
+            guard::check_block("Utility", channel);
+        }
+        self.mix.mix_into(channels);
+    }
+
+    /// The routing matrix for the current switches, as `[to][from]`. A mono layout only uses
+    /// the left channel's polarity.
+    fn target_routing(&self) -> [[f32; 2]; 2] {
+        let polarity = |invert: &BoolParam| if invert.value() { -1.0 } else { 1.0 };
+        let left = polarity(&self.params.invert_left);
+        let right = polarity(&self.params.invert_right);
+
+        if !self.stereo {
+            [[left, 0.0], [0.0, 0.0]]
+        } else if self.params.mono.value() {
+            [[left * 0.5, right * 0.5], [left * 0.5, right * 0.5]]
+        } else if self.params.swap.value() {
+            [[0.0, right], [left, 0.0]]
+        } else {
+            [[left, 0.0], [0.0, right]]
+        }
+    }
+
+    fn target_dc_filter(&self) -> f32 {
+        if self.params.dc_filter.value() {
+            1.0
+        } else {
+            0.0
+        }
+    }
+
+    /// Bypassing fades to the dry signal through the mix stage.
+    fn target_mix(&self) -> f32 {
+        if self.params.bypass.value() {
+            0.0
+        } else {
+            1.0
+        }
+    }
+}
+
+impl ClapPlugin for Utility {
+    const CLAP_ID: &'static str = "com.yourstudio.utility";
+    const CLAP_DESCRIPTION: Option<&'static str> =
+        Some("Gain, polarity, channel swap, mono, and DC offset removal");
+    const CLAP_MANUAL_URL: Option<&'static str> = Some(Self::URL);
+    const CLAP_SUPPORT_URL: Option<&'static str> = None;
+    const CLAP_FEATURES: &'static [ClapFeature] = &[
+        ClapFeature::AudioEffect,
+        ClapFeature::Utility,
+        ClapFeature::Stereo,
+        ClapFeature::Mono,
+    ];
+}
+
+impl Vst3Plugin for Utility {
+    const VST3_CLASS_ID: [u8; 16] = *b"Utility000000000";
+    const VST3_SUBCATEGORIES: &'static [Vst3SubCategory] =
+        &[Vst3SubCategory::Fx, Vst3SubCategory::Tools];
+}
+
+nih_export_clap!(Utility);
+nih_export_vst3!(Utility);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use plugin_scaffold::test_util::assert_no_alloc;
+
+    plugin_scaffold::install_alloc_disabler!();
+
+    const SAMPLE_RATE: f32 = 48000.0;
+    const BLOCK_SIZE: usize = 512;
+
+    fn test_plugin(params: UtilityParams) -> Utility {
+        params.gain.smoothed.reset(params.gain.value());
+
+        let mut plugin = Utility {
+            params: Arc::new(params),
+            ..Utility::default()
+        };
+        plugin.set_sample_rate(SAMPLE_RATE);
+        plugin.mix = MixStage::new(SAMPLE_RATE, 2, BLOCK_SIZE, 0);
+        plugin.reset();
+        plugin
+    }
+
+    fn switch(name: &'static str) -> BoolParam {
+        BoolParam::new(name, true)
+    }
+
+    fn sine(frequency: f32, amplitude: f32) -> Vec<f32> {
+        (0..SAMPLE_RATE as usize)
+            .map(|i| (std::f32::consts::TAU * frequency * i as f32 / SAMPLE_RATE).sin() * amplitude)
+            .collect()
+    }
+
+    fn process(plugin: &mut Utility, left: &[f32], right: &[f32]) -> (Vec<f32>, Vec<f32>) {
+        let (mut left, mut right) = (left.to_vec(), right.to_vec());
+        for (left, right) in left
+            .chunks_mut(BLOCK_SIZE)
+            .zip(right.chunks_mut(BLOCK_SIZE))
+        {
+            plugin.process_channels(&mut [left, right]);
+        }
+        (left, right)
+    }
+
+    fn assert_same(actual: &[f32], expected: impl IntoIterator<Item = f32>) {
+        for (actual, expected) in actual.iter().zip(expected) {
+            assert!((actual - expected).abs() < 1e-6, "{actual} != {expected}");
+        }
+    }
+
+    #[test]
+    fn default_settings_pass_audio_through() {
+        let mut plugin = test_plugin(UtilityParams::default());
+        let (left, right) = (sine(440.0, 0.5), sine(660.0, 0.25));
+        let (out_left, out_right) = process(&mut plugin, &left, &right);
+
+        assert_same(&out_left, left);
+        assert_same(&out_right, right);
+    }
+
+    #[test]
+    fn invert_swap_and_mono_route_the_channels() {
+        let (left, right) = (sine(440.0, 0.5), sine(660.0, 0.25));
+
+        let mut plugin = test_plugin(UtilityParams {
+            invert_left: switch("Invert Left"),
+            swap: switch("Swap L/R"),
+            ..UtilityParams::default()
+        });
+        let (out_left, out_right) = process(&mut plugin, &left, &right);
+        assert_same(&out_left, right.iter().copied());
+        assert_same(&out_right, left.iter().map(|sample| -sample));
+
+        let mut plugin = test_plugin(UtilityParams {
+            invert_right: switch("Invert Right"),
+            mono: switch("Mono"),
+            ..UtilityParams::default()
+        });
+        let (out_left, out_right) = process(&mut plugin, &left, &right);
+        let mono = left.iter().zip(&right).map(|(l, r)| (l - r) / 2.0);
+        assert_same(&out_left, mono.clone());
+        assert_same(&out_right, mono);
+    }
+
+    #[test]
+    fn minimum_gain_is_silent() {
+        let mut plugin = test_plugin(UtilityParams {
+            gain: FloatParam::new("Gain", 0.0, FloatRange::Linear { min: 0.0, max: 1.0 }),
+            ..UtilityParams::default()
+        });
+        let input = sine(440.0, 0.5);
+        let (left, right) = process(&mut plugin, &input, &input);
+
+        assert!(left.iter().chain(&right).all(|&sample| sample == 0.0));
+    }
+
+    #[test]
+    fn dc_filter_removes_offset() {
+        let mut plugin = test_plugin(UtilityParams {
+            dc_filter: switch("DC Filter"),
+            ..UtilityParams::default()
+        });
+        let input: Vec<f32> = sine(440.0, 0.25)
+            .iter()
+            .map(|sample| sample + 0.5)
+            .collect();
+        let (left, _) = process(&mut plugin, &input, &input);
+
+        // Averaged over whole cycles of the tone, after the filter has settled
+        let cycles = &left[left.len() / 2..][..SAMPLE_RATE as usize * 10 / 440];
+        let mean = cycles.iter().sum::<f32>() / cycles.len() as f32;
+        assert!(mean.abs() < 1e-3, "{mean}");
+    }
+
+    #[test]
+    fn switching_fades_instead_of_jumping() {
+        let mut plugin = test_plugin(UtilityParams::default());
+        let input = vec![0.5; BLOCK_SIZE];
+        process(&mut plugin, &input, &input);
+
+        plugin.params = Arc::new(UtilityParams {
+            invert_left: switch("Invert Left"),
+            ..UtilityParams::default()
+        });
+        plugin.params.gain.smoothed.reset(1.0);
+        let (left, _) = process(&mut plugin, &input, &input);
+
+        let largest_step = left
+            .windows(2)
+            .map(|pair| (pair[1] - pair[0]).abs())
+            .fold(0.0, f32::max);
+        assert!(largest_step < 0.01, "{largest_step}");
+        assert!((left[BLOCK_SIZE - 1] + 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn processing_does_not_allocate() {
+        let mut plugin = test_plugin(UtilityParams::default());
+        let mut left = sine(440.0, 0.5);
+        let mut right = sine(220.0, 0.5);
+        assert_no_alloc(|| {
+            for (left, right) in left
+                .chunks_mut(BLOCK_SIZE)
+                .zip(right.chunks_mut(BLOCK_SIZE))
+            {
+                plugin.process_channels(&mut [left, right]);
+            }
+        });
+    }
+}
@@ -0,0 +1,89 @@
+//! The wavetables the oscillators can play: the factory tables, and a `.wav` table per oscillator
+//! loaded by the user.
+//!
+//! User tables are decoded on the background thread and handed to the audio thread through a
+//! [`HotSwap`], so the audio thread never locks or frees a table.
+
+use crate::sections::NUM_OSCS;
+use dsp_core::wavetable::factory::FactoryTable;
+use dsp_core::wavetable::import::ImportError;
+use dsp_core::wavetable::Wavetable;
+use plugin_scaffold::hot_swap::HotSwap;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+/// Where an oscillator's user table load is at, for the editor.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum LoadStatus {
+    #[default]
+    Idle,
+    /// A load is queued or running on the background thread.
+    Loading,
+    /// The last load failed with this error.
+    Failed(String),
+}
+
+pub struct WavetableBank {
+    /// In the order of [`FactoryTable::ALL`].
+    factory: Vec<Arc<Wavetable>>,
+    user: [HotSwap<Wavetable>; NUM_OSCS],
+    /// The files the user tables were loaded from, saved with the plugin state.
+    user_paths: [Mutex<Option<String>>; NUM_OSCS],
+    status: [Mutex<LoadStatus>; NUM_OSCS],
+}
+
+impl WavetableBank {
+    /// Generates the factory tables, so create this once per plugin instance.
+    pub fn new() -> Self {
+        Self {
+            factory: FactoryTable::ALL
+                .iter()
+                .map(|table| Arc::new(table.build()))
+                .collect(),
+            user: Default::default(),
+            user_paths: Default::default(),
+            status: Default::default(),
+        }
+    }
+
+    pub fn factory(&self, table: FactoryTable) -> &Arc<Wavetable> {
+        let idx = FactoryTable::ALL
+            .iter()
+            .position(|&factory| factory == table)
+            .unwrap_or(0);
+        &self.factory[idx]
+    }
+
+    /// Load a `.wav` table for an oscillator. Blocks on file IO and an FFT per frame, so never call
+    /// this from the audio thread.
+    pub fn load_user(&self, osc: usize, path: &str) -> Result<Arc<Wavetable>, ImportError> {
+        let table = Arc::new(Wavetable::load_wav(Path::new(path))?);
+        self.user[osc].store(table.clone());
+        *self.user_paths[osc].lock().unwrap() = Some(path.to_owned());
+
+        Ok(table)
+    }
+
+    pub fn status(&self, osc: usize) -> LoadStatus {
+        self.status[osc].lock().unwrap().clone()
+    }
+
+    pub fn set_status(&self, osc: usize, status: LoadStatus) {
+        *self.status[osc].lock().unwrap() = status;
+    }
+
+    /// The path of the oscillator's user table, if one is loaded.
+    pub fn user_path(&self, osc: usize) -> Option<String> {
+        self.user_paths[osc].lock().unwrap().clone()
+    }
+
+    /// The oscillator's user table for the editor.
+    pub fn user_table(&self, osc: usize) -> Option<Arc<Wavetable>> {
+        self.user[osc].load()
+    }
+
+    /// Pick up a newly loaded user table on the audio thread.
+    pub fn sync_user_table(&self, osc: usize, current: &mut Option<Arc<Wavetable>>) {
+        self.user[osc].sync(current);
+    }
+}
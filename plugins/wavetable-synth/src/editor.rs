@@ -0,0 +1,316 @@
+use crate::bank::{LoadStatus, WavetableBank};
+use crate::modulation::ModDestination;
+use crate::sections::{TableChoice, NUM_ENVS, NUM_LFOS, NUM_OSCS};
+use crate::{select_table, SynthParams, WavetableSynth, WavetableTask};
+use dsp_core::utils::lerp;
+use dsp_core::wavetable::{Wavetable, FRAME_SIZE};
+use nih_plug::prelude::*;
+use nih_plug_egui::create_egui_editor;
+use nih_plug_egui::egui::{self, Vec2};
+use std::sync::Arc;
+use ui_widgets::undo;
+use ui_widgets::{
+    param_combo, AdsrEditor, EditorParams, EditorShell, KeyboardState, MeterState, ModulationState,
+    ParamKnob, Scope,
+};
+
+/// The number of points drawn in each oscillator's waveform preview.
+const PREVIEW_POINTS: usize = 128;
+
+const SMALL_KNOB: f32 = 36.0;
+
+pub(crate) fn default_params() -> EditorParams {
+    EditorParams::new((720, 630))
+}
+
+/// GUI-thread editor state.
+struct EditorState {
+    bank: Arc<WavetableBank>,
+    /// The mod matrix's effect on the knobs, published by the audio thread.
+    modulation: Arc<ModulationState>,
+    keyboard: Arc<KeyboardState>,
+    async_executor: AsyncExecutor<WavetableSynth>,
+    oscs: [OscState; NUM_OSCS],
+}
+
+/// GUI-thread state for one oscillator.
+struct OscState {
+    /// The waveform the oscillator plays at its current position.
+    preview: Vec<f32>,
+    /// The number of frames in the oscillator's table.
+    num_frames: usize,
+    /// The path being edited in the user table field.
+    path: String,
+}
+
+pub(crate) fn create(
+    params: Arc<SynthParams>,
+    bank: Arc<WavetableBank>,
+    meters: Arc<MeterState>,
+    modulation: Arc<ModulationState>,
+    keyboard: Arc<KeyboardState>,
+    async_executor: AsyncExecutor<WavetableSynth>,
+) -> Option<Box<dyn Editor>> {
+    let paths = params.user_wavetables.read().unwrap().clone();
+
+    create_egui_editor(
+        params.editor.state.clone(),
+        EditorState {
+            bank,
+            modulation,
+            async_executor,
+            oscs: std::array::from_fn(|idx| OscState {
+                preview: vec![0.0; PREVIEW_POINTS],
+                num_frames: 0,
+                path: paths[idx].clone().unwrap_or_default(),
+            }),
+        },
+        |_, _| {},
+        move |egui_ctx, setter, state| {
+            for (idx, osc) in state.oscs.iter_mut().enumerate() {
+                let user_table = state.bank.user_table(idx);
+                let table = select_table(&state.bank, &user_table, params.oscs[idx].table.value());
+                osc.num_frames = table.num_frames();
+                render_preview(table, params.oscs[idx].position.value(), &mut osc.preview);
+            }
+
+            EditorShell::new(&params.editor)
+                .with_bypass(&params.bypass)
+                .with_keyboard(&keyboard)
+                .with_meter(&meters)
+                .show(egui_ctx, setter, |ui| {
+                    header_row(ui, &params, setter);
+                    ui.separator();
+
+                    ui.horizontal_top(|ui| {
+                        for idx in 0..NUM_OSCS {
+                            ui.push_id(("osc", idx), |ui| {
+                                osc_section(ui, &params, setter, idx, state);
+                            });
+                        }
+                    });
+                    ui.separator();
+
+                    ui.horizontal_top(|ui| {
+                        filter_section(ui, &params, setter, &state.modulation);
+                        for idx in 0..NUM_LFOS {
+                            ui.push_id(("lfo", idx), |ui| lfo_section(ui, &params, setter, idx));
+                        }
+                    });
+                    ui.separator();
+
+                    ui.horizontal_top(|ui| {
+                        for idx in 0..NUM_ENVS {
+                            ui.push_id(("env", idx), |ui| env_section(ui, &params, setter, idx));
+                        }
+                    });
+                    ui.separator();
+
+                    mod_matrix_grid(ui, &params, setter);
+                });
+
+            // The meters are live, so keep repainting while the editor is open
+            egui_ctx.request_repaint();
+        },
+    )
+}
+
+/// Sample the table at `position` the same way the oscillator morphs between frames.
+fn render_preview(table: &Wavetable, position: f32, preview: &mut [f32]) {
+    let last_frame = table.num_frames() - 1;
+    let frame_position = position.clamp(0.0, 1.0) * last_frame as f32;
+    let frame = (frame_position.floor() as usize).min(last_frame);
+    let morph = frame_position - frame as f32;
+    let current = table.frame(frame, 0);
+    let next = table.frame((frame + 1).min(last_frame), 0);
+
+    let step = FRAME_SIZE / preview.len();
+    for (idx, point) in preview.iter_mut().enumerate() {
+        *point = lerp(current[idx * step], next[idx * step], morph);
+    }
+}
+
+fn header_row(ui: &mut egui::Ui, params: &SynthParams, setter: &ParamSetter) {
+    ui.horizontal(|ui| {
+        ui.add(ParamKnob::for_param(&params.gain, setter));
+
+        if ui
+            .add_enabled(undo::can_undo(ui.ctx()), egui::Button::new("Undo"))
+            .on_hover_text("Ctrl+Z")
+            .clicked()
+        {
+            undo::undo(ui.ctx(), setter);
+        }
+        if ui
+            .add_enabled(undo::can_redo(ui.ctx()), egui::Button::new("Redo"))
+            .on_hover_text("Ctrl+Shift+Z")
+            .clicked()
+        {
+            undo::redo(ui.ctx(), setter);
+        }
+    });
+}
+
+fn osc_section(
+    ui: &mut egui::Ui,
+    params: &SynthParams,
+    setter: &ParamSetter,
+    idx: usize,
+    state: &mut EditorState,
+) {
+    let osc = &params.oscs[idx];
+    let modulation = &state.modulation;
+    ui.vertical(|ui| {
+        ui.label(format!(
+            "Oscillator {} ({} frames)",
+            idx + 1,
+            state.oscs[idx].num_frames
+        ));
+        param_combo(ui, &osc.table, setter);
+        if osc.table.value() == TableChoice::User {
+            user_table_row(
+                ui,
+                idx,
+                &state.bank,
+                &state.async_executor,
+                &mut state.oscs[idx],
+            );
+        }
+
+        ui.add(Scope::new(&state.oscs[idx].preview).with_size(Vec2::new(300.0, 60.0)));
+        ui.horizontal(|ui| {
+            ui.add(
+                ParamKnob::for_param(&osc.position, setter)
+                    .with_modulation(modulated(modulation, ModDestination::position(idx))),
+            );
+            ui.add(
+                ParamKnob::for_param(&osc.level, setter)
+                    .with_modulation(modulated(modulation, ModDestination::level(idx))),
+            );
+            ui.add(ParamKnob::for_param(&osc.coarse, setter).with_diameter(SMALL_KNOB));
+            ui.add(ParamKnob::for_param(&osc.fine, setter).with_diameter(SMALL_KNOB));
+        });
+    });
+}
+
+/// The path field for loading a `.wav` wavetable as the oscillator's user table. The file is
+/// loaded on the background thread, with a spinner in the meantime.
+fn user_table_row(
+    ui: &mut egui::Ui,
+    idx: usize,
+    bank: &WavetableBank,
+    async_executor: &AsyncExecutor<WavetableSynth>,
+    state: &mut OscState,
+) {
+    let status = bank.status(idx);
+    let loading = status == LoadStatus::Loading;
+    ui.horizontal(|ui| {
+        ui.add(
+            egui::TextEdit::singleline(&mut state.path)
+                .hint_text("Path to a .wav wavetable")
+                .desired_width(230.0),
+        );
+        if ui
+            .add_enabled(!loading, egui::Button::new("Load"))
+            .clicked()
+        {
+            bank.set_status(idx, LoadStatus::Loading);
+            async_executor.execute_background(WavetableTask::LoadUser {
+                osc: idx,
+                path: state.path.trim().to_owned(),
+            });
+        }
+    });
+
+    match status {
+        LoadStatus::Idle => {}
+        LoadStatus::Loading => {
+            ui.horizontal(|ui| {
+                ui.spinner();
+                ui.label("Loading...");
+            });
+        }
+        LoadStatus::Failed(err) => {
+            ui.colored_label(ui.visuals().error_fg_color, err);
+        }
+    }
+}
+
+fn filter_section(
+    ui: &mut egui::Ui,
+    params: &SynthParams,
+    setter: &ParamSetter,
+    modulation: &ModulationState,
+) {
+    let filter = &params.filter;
+    ui.vertical(|ui| {
+        ui.label("Filter");
+        param_combo(ui, &filter.filter_type, setter);
+        ui.horizontal(|ui| {
+            ui.add(
+                ParamKnob::for_param(&filter.cutoff, setter)
+                    .with_modulation(modulated(modulation, ModDestination::Cutoff)),
+            );
+            ui.add(
+                ParamKnob::for_param(&filter.resonance, setter)
+                    .with_modulation(modulated(modulation, ModDestination::Resonance)),
+            );
+            ui.add(
+                ParamKnob::for_param(&filter.vowel, setter)
+                    .with_modulation(modulated(modulation, ModDestination::Vowel)),
+            );
+        });
+    });
+}
+
+/// The value to show on a destination's knob, from the newest voice.
+fn modulated(modulation: &ModulationState, destination: ModDestination) -> Option<f32> {
+    modulation.value(destination.to_index())
+}
+
+fn lfo_section(ui: &mut egui::Ui, params: &SynthParams, setter: &ParamSetter, idx: usize) {
+    let lfo = &params.lfos[idx];
+    ui.vertical(|ui| {
+        ui.label(format!("LFO {}", idx + 1));
+        param_combo(ui, &lfo.shape, setter);
+        ui.add(ParamKnob::for_param(&lfo.rate, setter).with_diameter(SMALL_KNOB));
+    });
+}
+
+fn env_section(ui: &mut egui::Ui, params: &SynthParams, setter: &ParamSetter, idx: usize) {
+    let env = &params.envs[idx];
+    ui.vertical(|ui| {
+        ui.label(match idx {
+            0 => "Envelope 1 (amp)".to_owned(),
+            _ => format!("Envelope {}", idx + 1),
+        });
+        ui.add(
+            AdsrEditor::new(&env.attack, &env.decay, &env.sustain, &env.release, setter)
+                .with_size(Vec2::new(300.0, 80.0)),
+        );
+        ui.horizontal(|ui| {
+            ui.add(ParamKnob::for_param(&env.attack, setter).with_diameter(SMALL_KNOB));
+            ui.add(ParamKnob::for_param(&env.decay, setter).with_diameter(SMALL_KNOB));
+            ui.add(ParamKnob::for_param(&env.sustain, setter).with_diameter(SMALL_KNOB));
+            ui.add(ParamKnob::for_param(&env.release, setter).with_diameter(SMALL_KNOB));
+        });
+    });
+}
+
+/// One row per mod slot with its source, destination, and amount.
+fn mod_matrix_grid(ui: &mut egui::Ui, params: &SynthParams, setter: &ParamSetter) {
+    ui.label("Mod Matrix");
+    egui::Grid::new("mod_matrix").show(ui, |ui| {
+        for (idx, slot) in params.mod_slots.iter().enumerate() {
+            ui.label(format!("{}", idx + 1));
+            ui.push_id(("mod_slot", idx), |ui| {
+                param_combo(ui, &slot.source, setter);
+                param_combo(ui, &slot.destination, setter);
+            });
+            ui.add(ParamKnob::for_param(&slot.amount, setter).with_diameter(SMALL_KNOB));
+            ui.end_row();
+        }
+    });
+    ui.add(ParamKnob::for_param(&params.key_track_pivot, setter).with_diameter(SMALL_KNOB))
+        .on_hover_text("Notes above this key give a positive key tracking value");
+}
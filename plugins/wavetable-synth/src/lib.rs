@@ -0,0 +1,996 @@
+use bank::{LoadStatus, WavetableBank};
+use dsp_core::bypass::SoftBypass;
+use dsp_core::envelopes::ADSREnvelope;
+use dsp_core::filters::{FormantFilter, StateVariableFilter};
+use dsp_core::lfo::Lfo;
+use dsp_core::modulation::ModMatrix;
+use dsp_core::wavetable::factory::FactoryTable;
+use dsp_core::wavetable::{Wavetable, WavetableOsc};
+use dsp_core::{guard, utils::midi_to_freq};
+use modulation::{
+    key_track, ModDestination, ModSlotParams, ModSource, ModSources, Modulation, NUM_MOD_SLOTS,
+};
+use nih_plug::midi::control_change::MODULATION_MSB;
+use nih_plug::prelude::*;
+use plugin_scaffold::layouts;
+use plugin_scaffold::notes::{NoteHandler, PendingEvents};
+use sections::{
+    resonance_to_q, EnvParams, FilterParams, LfoParams, OscParams, TableChoice, NUM_ENVS, NUM_LFOS,
+    NUM_OSCS,
+};
+use std::sync::{Arc, RwLock};
+use ui_widgets::{EditorParams, KeyboardInput, KeyboardState, MeterState, ModulationState};
+
+mod bank;
+mod editor;
+mod modulation;
+mod sections;
+
+const MAX_VOICES: usize = 16;
+
+/// Scales the voices down so a chord with both oscillators at full level doesn't clip.
+const VOICE_GAIN: f32 = 0.25;
+
+struct WavetableSynth {
+    params: Arc<SynthParams>,
+    /// The factory and user tables, shared with the editor.
+    bank: Arc<WavetableBank>,
+    /// The audio thread's handle on each oscillator's user table.
+    user_tables: [Option<Arc<Wavetable>>; NUM_OSCS],
+    voices: [Voice; MAX_VOICES],
+    /// The next voice to steal.
+    next_voice: usize,
+
+    /// The voice of the last note played, whose modulation the editor's knobs show.
+    newest_voice: Option<usize>,
+
+    /// Free running LFOs shared by all voices.
+    lfos: [Lfo; NUM_LFOS],
+    mod_matrix: ModMatrix<f32, NUM_MOD_SLOTS>,
+    /// The last mod wheel position, from 0 to 1.
+    mod_wheel: f32,
+
+    /// Output levels for the editor's meters.
+    meters: Arc<MeterState>,
+    /// Modulated parameter values for the editor's knobs, indexed by `ModDestination`.
+    modulation: Arc<ModulationState>,
+
+    /// Notes played on the editor's keyboard, and the notes received from the host for it to
+    /// show.
+    keyboard: Arc<KeyboardState>,
+    keyboard_input: KeyboardInput,
+
+    /// `VoiceTerminated` events waiting to be sent to the host at the end of the block.
+    pending_events: PendingEvents<()>,
+
+    /// Fades the output out and back in when the plugin is bypassed.
+    bypass: SoftBypass,
+}
+
+#[derive(Clone)]
+struct Voice {
+    oscs: [WavetableOsc; NUM_OSCS],
+    filter: StateVariableFilter,
+    formant: FormantFilter,
+    /// Whether the formant filter is used instead of `filter`.
+    use_formant: bool,
+    /// The first envelope shapes the amplitude, both can be routed in the mod matrix.
+    envs: [ADSREnvelope; NUM_ENVS],
+    /// The note's frequency before the oscillators' tuning.
+    frequency: f32,
+    /// The key tracking modulation source's value for the note.
+    key_track: f32,
+    /// The mod matrix's output for the last rendered sample.
+    modulation: Modulation,
+    note: Option<u8>,
+    velocity: f32,
+
+    /// The host's ID for this voice, cleared once the host has been told the voice terminated.
+    voice_id: Option<i32>,
+    channel: u8,
+}
+
+/// The smoothed parameter and LFO values for one sample, shared by every voice.
+struct FrameParams {
+    position: [f32; NUM_OSCS],
+    level: [f32; NUM_OSCS],
+    /// Each oscillator's tuning in semitones.
+    pitch: [f32; NUM_OSCS],
+    cutoff: f32,
+    resonance: f32,
+    vowel: f32,
+    lfos: [f32; NUM_LFOS],
+    mod_wheel: f32,
+}
+
+#[derive(Params)]
+struct SynthParams {
+    #[nested]
+    editor: EditorParams,
+
+    /// The `.wav` files loaded as each oscillator's user table, reloaded with the plugin state.
+    #[persist = "user-wavetables"]
+    pub user_wavetables: RwLock<[Option<String>; NUM_OSCS]>,
+
+    /// The host's bypass switch.
+    #[id = "bypass"]
+    pub bypass: BoolParam,
+
+    #[id = "gain"]
+    pub gain: FloatParam,
+
+    #[nested(array, group = "Oscillator")]
+    pub oscs: [OscParams; NUM_OSCS],
+
+    #[nested(group = "Filter")]
+    pub filter: FilterParams,
+
+    /// The first envelope is the amplitude envelope.
+    #[nested(array, group = "Envelope")]
+    pub envs: [EnvParams; NUM_ENVS],
+
+    #[nested(array, group = "LFO")]
+    pub lfos: [LfoParams; NUM_LFOS],
+
+    #[nested(array, group = "Mod Slot")]
+    pub mod_slots: [ModSlotParams; NUM_MOD_SLOTS],
+
+    /// The key where the key tracking modulation source is zero.
+    #[id = "key_track_pivot"]
+    pub key_track_pivot: IntParam,
+}
+
+/// Loading a user table, which decodes the file and band-limits every frame with an FFT, far too
+/// slow for the audio or GUI thread.
+pub enum WavetableTask {
+    LoadUser { osc: usize, path: String },
+}
+
+impl Default for WavetableSynth {
+    fn default() -> Self {
+        Self {
+            params: Arc::new(SynthParams::default()),
+            bank: Arc::new(WavetableBank::new()),
+            user_tables: Default::default(),
+            voices: std::array::from_fn(|_| Voice::new(44100.0)),
+            next_voice: 0,
+            newest_voice: None,
+            lfos: std::array::from_fn(|_| Lfo::new(44100.0)),
+            mod_matrix: ModMatrix::default(),
+            mod_wheel: 0.0,
+            meters: Arc::new(MeterState::new(2)),
+            modulation: Arc::new(ModulationState::new(ModDestination::COUNT)),
+            keyboard: Arc::new(KeyboardState::new()),
+            keyboard_input: KeyboardInput::default(),
+            pending_events: PendingEvents::with_capacity(MAX_VOICES * 2),
+            bypass: SoftBypass::new(44100.0),
+        }
+    }
+}
+
+impl Default for SynthParams {
+    fn default() -> Self {
+        Self {
+            editor: editor::default_params(),
+            user_wavetables: RwLock::new(Default::default()),
+
+            bypass: BoolParam::new("Bypass", false).make_bypass(),
+
+            gain: FloatParam::new(
+                "Gain",
+                util::db_to_gain(-12.0),
+                FloatRange::Skewed {
+                    min: util::db_to_gain(-30.0),
+                    max: util::db_to_gain(0.0),
+                    factor: FloatRange::gain_skew_factor(-30.0, 0.0),
+                },
+            )
+            .with_smoother(SmoothingStyle::Logarithmic(50.0))
+            .with_unit(" dB")
+            .with_value_to_string(formatters::v2s_f32_gain_to_db(2))
+            .with_string_to_value(formatters::s2v_f32_gain_to_db()),
+
+            oscs: std::array::from_fn(OscParams::new),
+            filter: FilterParams::default(),
+            envs: Default::default(),
+            lfos: Default::default(),
+            mod_slots: std::array::from_fn(ModSlotParams::new),
+            key_track_pivot: IntParam::new(
+                "Key Track Pivot",
+                60,
+                IntRange::Linear { min: 0, max: 127 },
+            )
+            .with_value_to_string(formatters::v2s_i32_note_formatter())
+            .with_string_to_value(formatters::s2v_i32_note_formatter()),
+        }
+    }
+}
+
+impl Plugin for WavetableSynth {
+    const NAME: &'static str = "Wavetable Synth";
+    const VENDOR: &'static str = "Your Studio";
+    const URL: &'static str = env!("CARGO_PKG_HOMEPAGE");
+    const EMAIL: &'static str = "contact@yourstudio.com";
+    const VERSION: &'static str = env!("CARGO_PKG_VERSION");
+
+    const AUDIO_IO_LAYOUTS: &'static [AudioIOLayout] = layouts::INSTRUMENT_LAYOUTS;
+
+    const MIDI_INPUT: MidiConfig = MidiConfig::MidiCCs;
+    const SAMPLE_ACCURATE_AUTOMATION: bool = true;
+
+    type SysExMessage = ();
+    type BackgroundTask = WavetableTask;
+
+    fn params(&self) -> Arc<dyn Params> {
+        self.params.clone()
+    }
+
+    fn task_executor(&mut self) -> TaskExecutor<Self> {
+        let params = self.params.clone();
+        let bank = self.bank.clone();
+        Box::new(move |task| match task {
+            WavetableTask::LoadUser { osc, path } => match bank.load_user(osc, &path) {
+                Ok(_) => {
+                    params.user_wavetables.write().unwrap()[osc] = Some(path);
+                    bank.set_status(osc, LoadStatus::Idle);
+                }
+                Err(err) => bank.set_status(osc, LoadStatus::Failed(err.to_string())),
+            },
+        })
+    }
+
+    fn editor(&mut self, async_executor: AsyncExecutor<Self>) -> Option<Box<dyn Editor>> {
+        editor::create(
+            self.params.clone(),
+            self.bank.clone(),
+            self.meters.clone(),
+            self.modulation.clone(),
+            self.keyboard.clone(),
+            async_executor,
+        )
+    }
+
+    fn initialize(
+        &mut self,
+        _audio_io_layout: &AudioIOLayout,
+        buffer_config: &BufferConfig,
+        _context: &mut impl InitContext<Self>,
+    ) -> bool {
+        self.meters.set_sample_rate(buffer_config.sample_rate);
+        self.load_user_wavetables();
+        self.bypass = SoftBypass::new(buffer_config.sample_rate);
+        self.bypass.reset(self.params.bypass.value());
+
+        for voice in &mut self.voices {
+            *voice = Voice::new(buffer_config.sample_rate);
+        }
+        for lfo in &mut self.lfos {
+            *lfo = Lfo::new(buffer_config.sample_rate);
+        }
+        true
+    }
+
+    fn reset(&mut self) {
+        self.keyboard.clear_received();
+        for lfo in &mut self.lfos {
+            lfo.reset();
+        }
+        self.mod_wheel = 0.0;
+    }
+
+    fn process(
+        &mut self,
+        buffer: &mut Buffer,
+        _aux: &mut AuxiliaryBuffers,
+        context: &mut impl ProcessContext<Self>,
+    ) -> ProcessStatus {
+        self.update_block_params();
+
+        self.handle_keyboard_notes();
+        let keyboard = self.keyboard.clone();
+        let mut next_event = keyboard.echo(context.next_event());
+        for (sample_id, channel_samples) in buffer.iter_samples().enumerate() {
+            self.handle_due_events(sample_id as u32, &mut next_event, || {
+                keyboard.echo(context.next_event())
+            });
+
+            let frame = self.next_frame_params();
+            let sample = self.render_frame(&frame) * self.params.gain.smoothed.next();
+            for output in channel_samples {
+                *output = sample;
+            }
+        }
+
+        // Voices keep running while bypassed so notes still end when they should
+        self.bypass.set_bypassed(self.params.bypass.value());
+        self.bypass.process_to_silence(buffer.as_slice());
+
+        for channel in buffer.as_slice_immutable() {
+            guard::check_block("wavetable-synth output", channel);
+        }
+
+        let last_sample = buffer.samples().saturating_sub(1) as u32;
+        self.terminate_finished_voices(last_sample);
+        self.pending_events.send(context);
+
+        if self.params.editor.state.is_open() {
+            for (channel_idx, channel) in buffer.as_slice_immutable().iter().enumerate() {
+                self.meters.update(channel_idx, channel);
+            }
+            self.publish_modulation();
+        }
+
+        self.process_status()
+    }
+}
+
+impl Voice {
+    fn new(sample_rate: f32) -> Self {
+        Self {
+            oscs: std::array::from_fn(|_| WavetableOsc::new(sample_rate)),
+            filter: StateVariableFilter::new(sample_rate),
+            formant: FormantFilter::new(sample_rate),
+            use_formant: false,
+            envs: std::array::from_fn(|_| ADSREnvelope::new(sample_rate)),
+            frequency: 440.0,
+            key_track: 0.0,
+            modulation: Modulation::default(),
+            note: None,
+            velocity: 0.0,
+            voice_id: None,
+            channel: 0,
+        }
+    }
+
+    fn is_active(&self) -> bool {
+        self.envs[0].is_active()
+    }
+
+    /// Render one sample, running the voice's modulation sources through the mod matrix.
+    fn render(
+        &mut self,
+        frame: &FrameParams,
+        tables: &[&Wavetable; NUM_OSCS],
+        matrix: &ModMatrix<f32, NUM_MOD_SLOTS>,
+    ) -> f32 {
+        let amp_env = self.envs[0].next_sample();
+        let mod_env = self.envs[1].next_sample();
+        guard::check_sample("ADSREnvelope", amp_env);
+
+        let mut sources = ModSources::default();
+        sources.set(ModSource::Env1, amp_env);
+        sources.set(ModSource::Env2, mod_env);
+        sources.set(ModSource::Lfo1, frame.lfos[0]);
+        sources.set(ModSource::Lfo2, frame.lfos[1]);
+        sources.set(ModSource::Velocity, self.velocity);
+        sources.set(ModSource::ModWheel, frame.mod_wheel);
+        sources.set(ModSource::KeyTrack, self.key_track);
+        let modulation = sources.modulate(matrix);
+        self.modulation = modulation;
+
+        let mut sample = 0.0;
+        for (idx, osc) in self.oscs.iter_mut().enumerate() {
+            let semitones = frame.pitch[idx] + modulation.semitones(ModDestination::pitch(idx));
+            osc.set_frequency(self.frequency * 2.0f32.powf(semitones / 12.0));
+            osc.set_position(frame.position[idx] + modulation.get(ModDestination::position(idx)));
+
+            // Silent oscillators still run so they stay in phase when faded in
+            let osc_sample = osc.next_sample(tables[idx]);
+            guard::check_sample("WavetableOsc", osc_sample);
+            let level =
+                (frame.level[idx] + modulation.get(ModDestination::level(idx))).clamp(0.0, 1.0);
+            sample += osc_sample * level;
+        }
+
+        let filtered = if self.use_formant {
+            self.formant
+                .set_morph(frame.vowel + modulation.get(ModDestination::Vowel));
+            let filtered = self.formant.process(sample);
+            guard::check_sample("FormantFilter", filtered);
+            filtered
+        } else {
+            let resonance = frame.resonance + modulation.get(ModDestination::Resonance);
+            self.filter.set_params(
+                frame.cutoff * modulation.cutoff_ratio(),
+                resonance_to_q(resonance),
+            );
+            let filtered = self.filter.process(sample);
+            guard::check_sample("StateVariableFilter", filtered);
+            filtered
+        };
+
+        filtered * amp_env * self.velocity
+    }
+
+    /// Queue a `VoiceTerminated` event for this voice if the host still thinks it's playing.
+    fn terminate(&mut self, timing: u32, pending_events: &mut PendingEvents<()>) {
+        pending_events.voice_terminated(timing, self.voice_id.take(), self.channel, self.note);
+    }
+}
+
+/// The table an oscillator plays. The user choice plays the basic shapes until a table is loaded.
+fn select_table<'a>(
+    bank: &'a WavetableBank,
+    user_table: &'a Option<Arc<Wavetable>>,
+    choice: TableChoice,
+) -> &'a Wavetable {
+    match (choice.factory(), user_table) {
+        (Some(factory), _) => bank.factory(factory),
+        (None, Some(user_table)) => user_table,
+        (None, None) => bank.factory(FactoryTable::BasicShapes),
+    }
+}
+
+/// A destination's parameter after modulation, normalized. Pitch and envelope times span several
+/// parameters, so they have no single knob to show them on.
+fn modulated_normalized(
+    params: &SynthParams,
+    destination: ModDestination,
+    modulation: &Modulation,
+) -> Option<f32> {
+    let param = match destination {
+        ModDestination::Osc1Position => &params.oscs[0].position,
+        ModDestination::Osc2Position => &params.oscs[1].position,
+        ModDestination::Osc1Level => &params.oscs[0].level,
+        ModDestination::Osc2Level => &params.oscs[1].level,
+        ModDestination::Resonance => &params.filter.resonance,
+        ModDestination::Vowel => &params.filter.vowel,
+        ModDestination::Cutoff => {
+            let cutoff = &params.filter.cutoff;
+            return Some(cutoff.preview_normalized(cutoff.value() * modulation.cutoff_ratio()));
+        }
+        ModDestination::Osc1Pitch
+        | ModDestination::Osc2Pitch
+        | ModDestination::Env1Time
+        | ModDestination::Env2Time => return None,
+    };
+
+    Some(param.preview_normalized(param.value() + modulation.get(destination)))
+}
+
+/// Compute a voice ID in case the host doesn't provide them.
+const fn compute_fallback_voice_id(note: u8, channel: u8) -> i32 {
+    note as i32 | ((channel as i32) << 16)
+}
+
+impl WavetableSynth {
+    /// Load the user tables saved with the plugin state. The files may have been loaded already
+    /// when the plugin is reactivated, or by the editor after the state was saved.
+    fn load_user_wavetables(&mut self) {
+        let paths = self.params.user_wavetables.read().unwrap().clone();
+        for (osc, path) in paths.iter().enumerate() {
+            let Some(path) = path else {
+                continue;
+            };
+
+            if self.bank.user_path(osc).as_ref() != Some(path) {
+                if let Err(err) = self.bank.load_user(osc, path) {
+                    nih_warn!("Could not load the wavetable '{path}': {err}");
+                }
+            }
+            self.bank.sync_user_table(osc, &mut self.user_tables[osc]);
+        }
+    }
+
+    /// Apply the parameters that only change between blocks: the mod matrix routing, the LFOs,
+    /// the filter type, and newly loaded user tables.
+    fn update_block_params(&mut self) {
+        modulation::update_matrix(&mut self.mod_matrix, &self.params.mod_slots);
+        for (osc, user_table) in self.user_tables.iter_mut().enumerate() {
+            self.bank.sync_user_table(osc, user_table);
+        }
+
+        for (lfo, params) in self.lfos.iter_mut().zip(&self.params.lfos) {
+            lfo.set_shape(params.shape.value().into());
+            lfo.set_rate(params.rate.value());
+        }
+
+        let filter_mode = self.params.filter.filter_type.value().mode();
+        for voice in &mut self.voices {
+            voice.use_formant = filter_mode.is_none();
+            if let Some(mode) = filter_mode {
+                voice.filter.set_mode(mode);
+            }
+        }
+    }
+
+    fn next_frame_params(&mut self) -> FrameParams {
+        let oscs = &self.params.oscs;
+        FrameParams {
+            position: std::array::from_fn(|idx| oscs[idx].position.smoothed.next()),
+            level: std::array::from_fn(|idx| oscs[idx].level.smoothed.next()),
+            pitch: std::array::from_fn(|idx| oscs[idx].pitch()),
+            cutoff: self.params.filter.cutoff.smoothed.next(),
+            resonance: self.params.filter.resonance.smoothed.next(),
+            vowel: self.params.filter.vowel.smoothed.next(),
+            lfos: std::array::from_fn(|idx| self.lfos[idx].next_sample()),
+            mod_wheel: self.mod_wheel,
+        }
+    }
+
+    /// Play the keys pressed and released on the editor's keyboard since the last block.
+    fn handle_keyboard_notes(&mut self) {
+        let notes = self.keyboard_input.poll(&self.keyboard);
+        self.handle_events(notes);
+    }
+
+    fn note_on(
+        &mut self,
+        timing: u32,
+        voice_id: Option<i32>,
+        channel: u8,
+        note: u8,
+        velocity: f32,
+    ) {
+        // Find available voice or steal oldest
+        let voice_idx = self
+            .voices
+            .iter()
+            .position(|voice| !voice.is_active())
+            .unwrap_or_else(|| {
+                let idx = self.next_voice;
+                self.next_voice = (idx + 1) % MAX_VOICES;
+                idx
+            });
+
+        self.newest_voice = Some(voice_idx);
+
+        // The host needs to know a stolen voice ended before its ID is reused
+        let voice = &mut self.voices[voice_idx];
+        voice.terminate(timing, &mut self.pending_events);
+
+        voice.note = Some(note);
+        voice.velocity = velocity;
+        voice.voice_id = Some(voice_id.unwrap_or_else(|| compute_fallback_voice_id(note, channel)));
+        voice.channel = channel;
+        voice.frequency = midi_to_freq(note);
+        voice.key_track = key_track(note, self.params.key_track_pivot.value());
+        for osc in &mut voice.oscs {
+            osc.reset();
+        }
+        voice.filter.reset();
+        voice.formant.reset();
+
+        // Envelope times are only set when the note starts, so they can only follow the sources
+        // that stay put for the whole note
+        let mut sources = ModSources::default();
+        sources.set(ModSource::Velocity, velocity);
+        sources.set(ModSource::ModWheel, self.mod_wheel);
+        sources.set(ModSource::KeyTrack, voice.key_track);
+        let modulation = sources.modulate(&self.mod_matrix);
+        for (idx, (env, params)) in voice.envs.iter_mut().zip(&self.params.envs).enumerate() {
+            let time_ratio = modulation.time_ratio(ModDestination::env_time(idx));
+            env.set_attack(params.attack.value() * time_ratio);
+            env.set_decay(params.decay.value() * time_ratio);
+            env.set_sustain(params.sustain.value());
+            env.set_release(params.release.value() * time_ratio);
+            env.note_on();
+        }
+    }
+
+    fn note_off(&mut self, voice_id: Option<i32>, channel: u8, note: u8) {
+        // Release the voice with this ID, or every voice playing this note when there is none
+        for voice in &mut self.voices {
+            let matches = match voice_id {
+                Some(voice_id) => voice.voice_id == Some(voice_id),
+                None => voice.note == Some(note) && voice.channel == channel,
+            };
+            if matches && voice.is_active() {
+                for env in &mut voice.envs {
+                    env.note_off();
+                }
+            }
+        }
+    }
+
+    fn render_frame(&mut self, frame: &FrameParams) -> f32 {
+        let tables = std::array::from_fn(|osc| {
+            select_table(
+                &self.bank,
+                &self.user_tables[osc],
+                self.params.oscs[osc].table.value(),
+            )
+        });
+
+        let mut sum = 0.0;
+        for voice in &mut self.voices {
+            if voice.is_active() {
+                sum += voice.render(frame, &tables, &self.mod_matrix);
+            }
+        }
+
+        sum * VOICE_GAIN
+    }
+
+    /// Keep the plugin alive while notes are held, and report the remaining release time once
+    /// they're all released so hosts that suspend silent plugins don't cut off the tails.
+    fn process_status(&self) -> ProcessStatus {
+        let tail = self.voices.iter().try_fold(0, |tail: u32, voice| {
+            voice.envs[0]
+                .remaining_release_samples()
+                .map(|remaining| tail.max(remaining))
+        });
+
+        match tail {
+            None => ProcessStatus::KeepAlive,
+            Some(0) => ProcessStatus::Normal,
+            Some(samples) => ProcessStatus::Tail(samples),
+        }
+    }
+
+    /// Publish the modulation of the newest voice for the editor's knobs. Every voice is
+    /// modulated differently, and the last note played is the one most likely being listened to.
+    fn publish_modulation(&self) {
+        let voice = self
+            .newest_voice
+            .map(|idx| &self.voices[idx])
+            .filter(|voice| voice.is_active());
+
+        for idx in 0..ModDestination::COUNT {
+            let modulated = voice
+                .filter(|_| self.mod_matrix.is_routed(idx))
+                .and_then(|voice| {
+                    modulated_normalized(
+                        &self.params,
+                        ModDestination::from_index(idx),
+                        &voice.modulation,
+                    )
+                });
+            match modulated {
+                Some(normalized) => self.modulation.publish(idx, normalized),
+                None => self.modulation.clear(idx),
+            }
+        }
+    }
+
+    fn terminate_finished_voices(&mut self, timing: u32) {
+        for voice in &mut self.voices {
+            if !voice.is_active() {
+                voice.terminate(timing, &mut self.pending_events);
+            }
+        }
+    }
+}
+
+impl NoteHandler<()> for WavetableSynth {
+    fn handle_event(&mut self, event: PluginNoteEvent<Self>) {
+        match event {
+            // A note on with zero velocity is a note off in MIDI terms
+            NoteEvent::NoteOn {
+                voice_id,
+                channel,
+                note,
+                velocity,
+                ..
+            } if velocity <= 0.0 => self.note_off(voice_id, channel, note),
+            NoteEvent::NoteOn {
+                timing,
+                voice_id,
+                channel,
+                note,
+                velocity,
+            } => self.note_on(timing, voice_id, channel, note, velocity),
+            NoteEvent::NoteOff {
+                voice_id,
+                channel,
+                note,
+                ..
+            } => self.note_off(voice_id, channel, note),
+            NoteEvent::MidiCC {
+                cc: MODULATION_MSB,
+                value,
+                ..
+            } => self.mod_wheel = value,
+            _ => {}
+        }
+    }
+}
+
+impl ClapPlugin for WavetableSynth {
+    const CLAP_ID: &'static str = "com.yourstudio.wavetable-synth";
+    const CLAP_DESCRIPTION: Option<&'static str> =
+        Some("A polyphonic wavetable synthesizer with morphing oscillators and a mod matrix");
+    const CLAP_MANUAL_URL: Option<&'static str> = Some(Self::URL);
+    const CLAP_SUPPORT_URL: Option<&'static str> = None;
+    const CLAP_FEATURES: &'static [ClapFeature] = &[
+        ClapFeature::Instrument,
+        ClapFeature::Synthesizer,
+        ClapFeature::Stereo,
+    ];
+}
+
+impl Vst3Plugin for WavetableSynth {
+    const VST3_CLASS_ID: [u8; 16] = *b"WavetableSynth00";
+    const VST3_SUBCATEGORIES: &'static [Vst3SubCategory] =
+        &[Vst3SubCategory::Instrument, Vst3SubCategory::Synth];
+}
+
+nih_export_clap!(WavetableSynth);
+nih_export_vst3!(WavetableSynth);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dsp_core::modulation::ModSlot;
+    use plugin_scaffold::test_util::assert_no_alloc;
+
+    plugin_scaffold::install_alloc_disabler!();
+
+    const SAMPLE_RATE: f32 = 44100.0;
+    const BLOCK_SIZE: usize = 256;
+
+    fn test_synth() -> WavetableSynth {
+        let mut synth = WavetableSynth::default();
+        synth.update_block_params();
+        let params = &synth.params;
+        let mut smoothed = vec![
+            &params.gain,
+            &params.filter.cutoff,
+            &params.filter.resonance,
+            &params.filter.vowel,
+        ];
+        for osc in &params.oscs {
+            smoothed.extend([&osc.position, &osc.level]);
+        }
+        for param in smoothed {
+            param.smoothed.reset(param.value());
+        }
+
+        synth
+    }
+
+    fn note_on(timing: u32, note: u8, velocity: f32) -> PluginNoteEvent<WavetableSynth> {
+        NoteEvent::NoteOn {
+            timing,
+            voice_id: None,
+            channel: 0,
+            note,
+            velocity,
+        }
+    }
+
+    fn note_off(timing: u32, note: u8) -> PluginNoteEvent<WavetableSynth> {
+        NoteEvent::NoteOff {
+            timing,
+            voice_id: None,
+            channel: 0,
+            note,
+            velocity: 0.0,
+        }
+    }
+
+    /// Mirror of the per-sample loop in `process()`, without the output gain.
+    fn render_into(
+        synth: &mut WavetableSynth,
+        events: impl IntoIterator<Item = PluginNoteEvent<WavetableSynth>>,
+        output: &mut [f32],
+    ) {
+        let mut events = events.into_iter();
+        let mut next_event = events.next();
+
+        for (sample_id, sample) in output.iter_mut().enumerate() {
+            synth.handle_due_events(sample_id as u32, &mut next_event, || events.next());
+            let frame = synth.next_frame_params();
+            *sample = synth.render_frame(&frame);
+        }
+    }
+
+    fn render_block(
+        synth: &mut WavetableSynth,
+        events: Vec<PluginNoteEvent<WavetableSynth>>,
+        num_samples: usize,
+    ) -> Vec<f32> {
+        let mut output = vec![0.0; num_samples];
+        render_into(synth, events, &mut output);
+        output
+    }
+
+    fn active_voices(synth: &WavetableSynth) -> usize {
+        synth.voices.iter().filter(|v| v.is_active()).count()
+    }
+
+    #[test]
+    fn user_table_falls_back_to_basic_shapes() {
+        let synth = test_synth();
+        let basic_shapes = synth.bank.factory(FactoryTable::BasicShapes);
+
+        assert!(synth.bank.load_user(0, "does-not-exist.wav").is_err());
+        let mut user_table = None;
+        synth.bank.sync_user_table(0, &mut user_table);
+        assert!(user_table.is_none());
+
+        let table = select_table(&synth.bank, &user_table, TableChoice::User);
+        assert!(std::ptr::eq(table, &**basic_shapes));
+    }
+
+    #[test]
+    fn failed_background_load_is_reported() {
+        let mut synth = test_synth();
+        let executor = synth.task_executor();
+        executor(WavetableTask::LoadUser {
+            osc: 1,
+            path: "does-not-exist.wav".to_owned(),
+        });
+
+        assert!(matches!(synth.bank.status(1), LoadStatus::Failed(_)));
+        assert_eq!(synth.bank.status(0), LoadStatus::Idle);
+        assert!(synth.params.user_wavetables.read().unwrap()[1].is_none());
+    }
+
+    #[test]
+    fn heavy_modulation_stays_finite() {
+        let mut synth = test_synth();
+        let params = &synth.params;
+        for param in [&params.oscs[1].level, &params.filter.resonance] {
+            param.smoothed.reset(1.0);
+        }
+        synth.mod_wheel = 1.0;
+
+        // Route every source somewhere at full strength
+        let routes = [
+            (ModSource::Env2, ModDestination::Cutoff),
+            (ModSource::Lfo1, ModDestination::Osc1Pitch),
+            (ModSource::ModWheel, ModDestination::Osc2Position),
+            (ModSource::Velocity, ModDestination::Resonance),
+        ];
+        for (idx, (source, destination)) in routes.into_iter().enumerate() {
+            let slot = ModSlot {
+                source: source.to_index(),
+                destination: destination.to_index(),
+                amount: 1.0,
+            };
+            synth.mod_matrix.set_slot(idx, Some(slot));
+        }
+
+        let events = (0..MAX_VOICES as u8)
+            .map(|i| note_on(0, 24 + i * 6, 1.0))
+            .collect();
+        render_block(&mut synth, events, BLOCK_SIZE);
+        for _ in 0..(SAMPLE_RATE as usize / BLOCK_SIZE) {
+            for sample in render_block(&mut synth, Vec::new(), BLOCK_SIZE) {
+                assert!(sample.is_finite(), "produced {sample}");
+            }
+        }
+    }
+
+    #[test]
+    fn vowel_modulation_moves_the_formant_filter() {
+        let render_vowel = |mod_wheel: f32| {
+            let mut synth = test_synth();
+            for voice in &mut synth.voices {
+                voice.use_formant = true;
+            }
+            synth.mod_wheel = mod_wheel;
+            let slot = ModSlot {
+                source: ModSource::ModWheel.to_index(),
+                destination: ModDestination::Vowel.to_index(),
+                amount: 1.0,
+            };
+            synth.mod_matrix.set_slot(0, Some(slot));
+
+            render_block(&mut synth, vec![note_on(0, 48, 1.0)], BLOCK_SIZE * 8)
+        };
+
+        let a = render_vowel(0.0);
+        let u = render_vowel(1.0);
+        assert!(a.iter().chain(&u).all(|sample| sample.is_finite()));
+        assert!(a.iter().zip(&u).any(|(a, u)| (a - u).abs() > 1e-3));
+    }
+
+    #[test]
+    fn released_notes_end_after_their_tail() {
+        let mut synth = test_synth();
+        render_block(
+            &mut synth,
+            vec![note_on(0, 60, 1.0), note_on(0, 64, 1.0)],
+            BLOCK_SIZE,
+        );
+        assert_eq!(active_voices(&synth), 2);
+        assert!(matches!(synth.process_status(), ProcessStatus::KeepAlive));
+
+        render_block(
+            &mut synth,
+            vec![note_off(0, 60), note_off(0, 64)],
+            BLOCK_SIZE,
+        );
+        let ProcessStatus::Tail(tail) = synth.process_status() else {
+            panic!("releasing voices should report a tail");
+        };
+        render_block(&mut synth, Vec::new(), tail as usize);
+        assert_eq!(active_voices(&synth), 0);
+        assert!(matches!(synth.process_status(), ProcessStatus::Normal));
+    }
+
+    #[test]
+    fn notes_beyond_polyphony_steal_voices() {
+        let mut synth = test_synth();
+        let events = (0..MAX_VOICES as u8 * 2)
+            .map(|i| note_on(i as u32, 40 + i, 1.0))
+            .collect();
+        render_block(&mut synth, events, BLOCK_SIZE);
+        assert_eq!(active_voices(&synth), MAX_VOICES);
+
+        let all_off = (0..=127).map(|note| note_off(0, note)).collect();
+        render_block(&mut synth, all_off, BLOCK_SIZE);
+        render_block(&mut synth, Vec::new(), (SAMPLE_RATE * 3.0) as usize);
+        assert_eq!(active_voices(&synth), 0);
+    }
+
+    #[test]
+    fn key_tracking_shortens_high_notes() {
+        let release_samples = |note: u8| {
+            let mut synth = test_synth();
+            let slot = ModSlot {
+                source: ModSource::KeyTrack.to_index(),
+                destination: ModDestination::Env1Time.to_index(),
+                amount: -1.0,
+            };
+            synth.mod_matrix.set_slot(0, Some(slot));
+
+            render_block(&mut synth, vec![note_on(0, note, 1.0)], BLOCK_SIZE);
+            render_block(&mut synth, vec![note_off(0, note)], 1);
+            synth.voices[0].envs[0].remaining_release_samples().unwrap()
+        };
+
+        let pivot = test_synth().params.key_track_pivot.value() as u8;
+        let low = release_samples(pivot - 24);
+        let pivot_release = release_samples(pivot);
+        let high = release_samples(pivot + 24);
+        assert!(
+            low > pivot_release && pivot_release > high,
+            "{low} {pivot_release} {high}"
+        );
+    }
+
+    #[test]
+    fn newest_voice_modulation_is_published() {
+        let mut synth = test_synth();
+        let cutoff = ModDestination::Cutoff.to_index();
+        synth.publish_modulation();
+        assert_eq!(synth.modulation.value(cutoff), None);
+
+        // The default patch sweeps the cutoff up with the second envelope
+        render_block(&mut synth, vec![note_on(0, 60, 1.0)], BLOCK_SIZE);
+        synth.publish_modulation();
+        let base = synth.params.filter.cutoff.unmodulated_normalized_value();
+        let modulated = synth.modulation.value(cutoff).unwrap();
+        assert!(modulated > base, "{modulated} {base}");
+        assert_eq!(
+            synth.modulation.value(ModDestination::Resonance.to_index()),
+            None
+        );
+
+        render_block(&mut synth, vec![note_off(0, 60)], BLOCK_SIZE);
+        render_block(&mut synth, Vec::new(), (SAMPLE_RATE * 3.0) as usize);
+        synth.publish_modulation();
+        assert_eq!(synth.modulation.value(cutoff), None);
+    }
+
+    #[test]
+    fn processing_does_not_allocate() {
+        // `test_synth()` already picked up the user tables once, which sets up arc-swap's state
+        // for this thread. An audio thread does the same on its first block.
+        let mut synth = test_synth();
+        let mut block = vec![0.0; BLOCK_SIZE];
+        let notes = [
+            note_on(0, 48, 1.0),
+            note_on(10, 55, 0.8),
+            note_on(20, 60, 0.6),
+        ];
+        let releases = [note_off(0, 48), note_off(0, 55), note_off(0, 60)];
+        assert_no_alloc(|| {
+            for idx in 0..(SAMPLE_RATE as usize / BLOCK_SIZE) {
+                let events = match idx {
+                    0 => &notes[..],
+                    100 => &releases[..],
+                    _ => &[],
+                };
+                synth.update_block_params();
+                render_into(&mut synth, events.iter().cloned(), &mut block);
+                synth.terminate_finished_voices(0);
+                synth.pending_events.clear();
+                synth.publish_modulation();
+            }
+        });
+    }
+}
@@ -0,0 +1,197 @@
+//! The mod matrix. Each slot routes a source to a destination, the sources and destinations'
+//! indices are their enum indices.
+
+use dsp_core::modulation::{ModMatrix, ModSlot};
+use nih_plug::prelude::*;
+
+pub const NUM_MOD_SLOTS: usize = 4;
+
+/// How far a fully modulated pitch destination moves, in semitones.
+const PITCH_RANGE: f32 = 24.0;
+/// How far a fully modulated cutoff moves, in octaves.
+const CUTOFF_RANGE: f32 = 5.0;
+/// How far a fully modulated envelope time moves, in doublings or halvings.
+const TIME_RANGE: f32 = 4.0;
+/// The distance from the pivot key where key tracking reaches full scale, in semitones. At this
+/// range a full amount on the cutoff tracks the keyboard exactly, an octave per octave.
+const KEY_TRACK_RANGE: f32 = 12.0 * CUTOFF_RANGE;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+pub enum ModSource {
+    Off,
+    #[name = "Envelope 1"]
+    Env1,
+    #[name = "Envelope 2"]
+    Env2,
+    #[name = "LFO 1"]
+    Lfo1,
+    #[name = "LFO 2"]
+    Lfo2,
+    Velocity,
+    #[name = "Mod Wheel"]
+    ModWheel,
+    /// The note's distance from the pivot key, negative below it.
+    #[name = "Key Track"]
+    KeyTrack,
+}
+
+impl ModSource {
+    pub const COUNT: usize = 8;
+}
+
+/// The key tracking source's value for a note.
+pub fn key_track(note: u8, pivot: i32) -> f32 {
+    (note as i32 - pivot) as f32 / KEY_TRACK_RANGE
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+pub enum ModDestination {
+    #[name = "Osc 1 Position"]
+    Osc1Position,
+    #[name = "Osc 2 Position"]
+    Osc2Position,
+    #[name = "Osc 1 Pitch"]
+    Osc1Pitch,
+    #[name = "Osc 2 Pitch"]
+    Osc2Pitch,
+    #[name = "Osc 1 Level"]
+    Osc1Level,
+    #[name = "Osc 2 Level"]
+    Osc2Level,
+    Cutoff,
+    Resonance,
+    /// The formant filter's vowel.
+    Vowel,
+    /// Scales the envelope's attack, decay, and release times.
+    #[name = "Env 1 Time"]
+    Env1Time,
+    #[name = "Env 2 Time"]
+    Env2Time,
+}
+
+impl ModDestination {
+    pub const COUNT: usize = 11;
+
+    pub fn position(osc: usize) -> Self {
+        [Self::Osc1Position, Self::Osc2Position][osc]
+    }
+
+    pub fn pitch(osc: usize) -> Self {
+        [Self::Osc1Pitch, Self::Osc2Pitch][osc]
+    }
+
+    pub fn level(osc: usize) -> Self {
+        [Self::Osc1Level, Self::Osc2Level][osc]
+    }
+
+    pub fn env_time(env: usize) -> Self {
+        [Self::Env1Time, Self::Env2Time][env]
+    }
+}
+
+/// The summed modulation for every destination, from the mod matrix.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Modulation([f32; ModDestination::COUNT]);
+
+impl Modulation {
+    pub fn get(&self, destination: ModDestination) -> f32 {
+        self.0[destination.to_index()]
+    }
+
+    /// The pitch offset in semitones.
+    pub fn semitones(&self, destination: ModDestination) -> f32 {
+        self.get(destination) * PITCH_RANGE
+    }
+
+    /// The cutoff as a multiple of the unmodulated cutoff.
+    pub fn cutoff_ratio(&self) -> f32 {
+        let octaves = self.get(ModDestination::Cutoff) * CUTOFF_RANGE;
+        if octaves == 0.0 {
+            1.0
+        } else {
+            2.0f32.powf(octaves)
+        }
+    }
+
+    /// An envelope's times as a multiple of its unmodulated times.
+    pub fn time_ratio(&self, destination: ModDestination) -> f32 {
+        2.0f32.powf(self.get(destination) * TIME_RANGE)
+    }
+}
+
+/// The current value of every source for one voice. Off always reads zero.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ModSources([f32; ModSource::COUNT]);
+
+impl ModSources {
+    pub fn set(&mut self, source: ModSource, value: f32) {
+        if source != ModSource::Off {
+            self.0[source.to_index()] = value;
+        }
+    }
+
+    /// Run the sources through the matrix.
+    pub fn modulate(&self, matrix: &ModMatrix<f32, NUM_MOD_SLOTS>) -> Modulation {
+        let mut modulation = Modulation::default();
+        matrix.process(&self.0, &mut modulation.0);
+        modulation
+    }
+}
+
+#[derive(Params)]
+pub struct ModSlotParams {
+    #[id = "source"]
+    pub source: EnumParam<ModSource>,
+
+    #[id = "destination"]
+    pub destination: EnumParam<ModDestination>,
+
+    #[id = "amount"]
+    pub amount: FloatParam,
+}
+
+impl ModSlotParams {
+    /// The first slot sweeps the filter with the second envelope, the others are empty.
+    pub fn new(idx: usize) -> Self {
+        let (source, amount) = match idx {
+            0 => (ModSource::Env2, 0.3),
+            _ => (ModSource::Off, 0.0),
+        };
+
+        Self {
+            source: EnumParam::new("Source", source),
+            destination: EnumParam::new("Destination", ModDestination::Cutoff),
+            amount: FloatParam::new(
+                "Amount",
+                amount,
+                FloatRange::Linear {
+                    min: -1.0,
+                    max: 1.0,
+                },
+            )
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage())
+            .with_unit(" %"),
+        }
+    }
+
+    fn slot(&self) -> Option<ModSlot> {
+        let source = self.source.value();
+        let amount = self.amount.value();
+        (source != ModSource::Off && amount != 0.0).then(|| ModSlot {
+            source: source.to_index(),
+            destination: self.destination.value().to_index(),
+            amount,
+        })
+    }
+}
+
+/// Load the slots' current parameter values into the matrix.
+pub fn update_matrix(
+    matrix: &mut ModMatrix<f32, NUM_MOD_SLOTS>,
+    slots: &[ModSlotParams; NUM_MOD_SLOTS],
+) {
+    for (idx, slot) in slots.iter().enumerate() {
+        matrix.set_slot(idx, slot.slot());
+    }
+}
@@ -0,0 +1,313 @@
+//! Parameters for the synth's oscillator, filter, envelope, and LFO sections.
+
+use dsp_core::filters::{FilterMode, Vowel};
+use dsp_core::lfo::LfoShape;
+use dsp_core::wavetable::factory::FactoryTable;
+use nih_plug::prelude::*;
+use plugin_scaffold::formatters::{s2v_f32_s_then_ms, v2s_f32_s_then_ms};
+use std::sync::Arc;
+
+pub const NUM_OSCS: usize = 2;
+pub const NUM_ENVS: usize = 2;
+pub const NUM_LFOS: usize = 2;
+
+/// The table an oscillator plays, mirroring [`FactoryTable`] plus the oscillator's user table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+pub enum TableChoice {
+    #[name = "Basic Shapes"]
+    BasicShapes,
+    #[name = "Pulse Width"]
+    PulseWidth,
+    Harmonics,
+    #[name = "Sync Sweep"]
+    SyncSweep,
+    /// The `.wav` table loaded in the editor. Plays the basic shapes until one is loaded.
+    User,
+}
+
+impl TableChoice {
+    /// The factory table to play, or `None` for the user table.
+    pub fn factory(self) -> Option<FactoryTable> {
+        match self {
+            TableChoice::BasicShapes => Some(FactoryTable::BasicShapes),
+            TableChoice::PulseWidth => Some(FactoryTable::PulseWidth),
+            TableChoice::Harmonics => Some(FactoryTable::Harmonics),
+            TableChoice::SyncSweep => Some(FactoryTable::SyncSweep),
+            TableChoice::User => None,
+        }
+    }
+}
+
+#[derive(Params)]
+pub struct OscParams {
+    #[id = "table"]
+    pub table: EnumParam<TableChoice>,
+
+    /// Where in the wavetable to play, morphing between neighbouring frames.
+    #[id = "position"]
+    pub position: FloatParam,
+
+    #[id = "level"]
+    pub level: FloatParam,
+
+    /// Coarse tuning in semitones.
+    #[id = "coarse"]
+    pub coarse: IntParam,
+
+    /// Fine tuning in cents.
+    #[id = "fine"]
+    pub fine: FloatParam,
+}
+
+impl OscParams {
+    /// Only the first oscillator is audible by default.
+    pub fn new(idx: usize) -> Self {
+        Self {
+            table: EnumParam::new("Table", TableChoice::BasicShapes),
+
+            position: FloatParam::new("Position", 0.0, FloatRange::Linear { min: 0.0, max: 1.0 })
+                .with_smoother(SmoothingStyle::Linear(20.0))
+                .with_value_to_string(formatters::v2s_f32_percentage(0))
+                .with_string_to_value(formatters::s2v_f32_percentage())
+                .with_unit(" %"),
+
+            level: FloatParam::new(
+                "Level",
+                if idx == 0 { 1.0 } else { 0.0 },
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_smoother(SmoothingStyle::Linear(20.0))
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage())
+            .with_unit(" %"),
+
+            coarse: IntParam::new("Coarse", 0, IntRange::Linear { min: -24, max: 24 })
+                .with_unit(" st"),
+
+            fine: FloatParam::new(
+                "Fine",
+                0.0,
+                FloatRange::Linear {
+                    min: -100.0,
+                    max: 100.0,
+                },
+            )
+            .with_unit(" cents")
+            .with_value_to_string(formatters::v2s_f32_rounded(1)),
+        }
+    }
+
+    /// The oscillator's tuning in semitones.
+    pub fn pitch(&self) -> f32 {
+        self.coarse.value() as f32 + self.fine.value() / 100.0
+    }
+}
+
+/// Mirrors [`FilterMode`] so it can be a parameter, plus the formant filter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+pub enum FilterType {
+    #[name = "Low-pass"]
+    LowPass,
+    #[name = "High-pass"]
+    HighPass,
+    #[name = "Band-pass"]
+    BandPass,
+    Notch,
+    /// The vowel filter, which follows the vowel parameter instead of the cutoff and resonance.
+    Formant,
+}
+
+impl FilterType {
+    /// The state variable filter's mode, or `None` for the formant filter.
+    pub fn mode(self) -> Option<FilterMode> {
+        match self {
+            FilterType::LowPass => Some(FilterMode::LowPass),
+            FilterType::HighPass => Some(FilterMode::HighPass),
+            FilterType::BandPass => Some(FilterMode::BandPass),
+            FilterType::Notch => Some(FilterMode::Notch),
+            FilterType::Formant => None,
+        }
+    }
+}
+
+#[derive(Params)]
+pub struct FilterParams {
+    #[id = "filter_type"]
+    pub filter_type: EnumParam<FilterType>,
+
+    #[id = "cutoff"]
+    pub cutoff: FloatParam,
+
+    #[id = "resonance"]
+    pub resonance: FloatParam,
+
+    /// The formant filter's vowel, morphing through A, E, I, O, and U.
+    #[id = "vowel"]
+    pub vowel: FloatParam,
+}
+
+impl Default for FilterParams {
+    fn default() -> Self {
+        Self {
+            filter_type: EnumParam::new("Type", FilterType::LowPass),
+
+            cutoff: FloatParam::new(
+                "Cutoff",
+                8000.0,
+                FloatRange::Skewed {
+                    min: 20.0,
+                    max: 20000.0,
+                    factor: FloatRange::skew_factor(-2.0),
+                },
+            )
+            .with_smoother(SmoothingStyle::Logarithmic(20.0))
+            .with_value_to_string(formatters::v2s_f32_hz_then_khz(0))
+            .with_string_to_value(formatters::s2v_f32_hz_then_khz()),
+
+            resonance: FloatParam::new("Resonance", 0.1, FloatRange::Linear { min: 0.0, max: 1.0 })
+                .with_smoother(SmoothingStyle::Linear(20.0))
+                .with_value_to_string(formatters::v2s_f32_percentage(0))
+                .with_string_to_value(formatters::s2v_f32_percentage())
+                .with_unit(" %"),
+
+            vowel: FloatParam::new("Vowel", 0.0, FloatRange::Linear { min: 0.0, max: 1.0 })
+                .with_smoother(SmoothingStyle::Linear(20.0))
+                .with_value_to_string(Arc::new(v2s_vowel))
+                .with_string_to_value(Arc::new(s2v_vowel)),
+        }
+    }
+}
+
+/// Show the vowel position as the nearest vowel.
+fn v2s_vowel(value: f32) -> String {
+    let idx = (value.clamp(0.0, 1.0) * (Vowel::ALL.len() - 1) as f32).round() as usize;
+    format!("{:?}", Vowel::ALL[idx])
+}
+
+/// Accept a vowel name or a position from 0 to 1.
+fn s2v_vowel(string: &str) -> Option<f32> {
+    let string = string.trim();
+    Vowel::ALL
+        .iter()
+        .position(|vowel| format!("{vowel:?}").eq_ignore_ascii_case(string))
+        .map(|idx| idx as f32 / (Vowel::ALL.len() - 1) as f32)
+        .or_else(|| string.parse().ok())
+}
+
+/// The filter's Q for a resonance amount from 0 to 1, from a gentle 0.5 to a self-oscillating
+/// 20.
+pub fn resonance_to_q(resonance: f32) -> f32 {
+    0.5 * 40.0f32.powf(resonance.clamp(0.0, 1.0))
+}
+
+#[derive(Params)]
+pub struct EnvParams {
+    #[id = "attack"]
+    pub attack: FloatParam,
+
+    #[id = "decay"]
+    pub decay: FloatParam,
+
+    #[id = "sustain"]
+    pub sustain: FloatParam,
+
+    #[id = "release"]
+    pub release: FloatParam,
+}
+
+impl Default for EnvParams {
+    fn default() -> Self {
+        Self {
+            attack: FloatParam::new(
+                "Attack",
+                0.01,
+                FloatRange::Skewed {
+                    min: 0.001,
+                    max: 5.0,
+                    factor: 0.25,
+                },
+            )
+            .with_value_to_string(v2s_f32_s_then_ms(1))
+            .with_string_to_value(s2v_f32_s_then_ms()),
+
+            decay: FloatParam::new(
+                "Decay",
+                0.3,
+                FloatRange::Skewed {
+                    min: 0.001,
+                    max: 5.0,
+                    factor: 0.25,
+                },
+            )
+            .with_value_to_string(v2s_f32_s_then_ms(1))
+            .with_string_to_value(s2v_f32_s_then_ms()),
+
+            sustain: FloatParam::new("Sustain", 0.7, FloatRange::Linear { min: 0.0, max: 1.0 })
+                .with_value_to_string(formatters::v2s_f32_percentage(1)),
+
+            release: FloatParam::new(
+                "Release",
+                0.3,
+                FloatRange::Skewed {
+                    min: 0.001,
+                    max: 5.0,
+                    factor: 0.25,
+                },
+            )
+            .with_value_to_string(v2s_f32_s_then_ms(1))
+            .with_string_to_value(s2v_f32_s_then_ms()),
+        }
+    }
+}
+
+/// Mirrors [`LfoShape`] so it can be a parameter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+pub enum LfoWaveform {
+    Sine,
+    Triangle,
+    Saw,
+    Square,
+    #[name = "Sample & Hold"]
+    SampleAndHold,
+}
+
+impl From<LfoWaveform> for LfoShape {
+    fn from(waveform: LfoWaveform) -> Self {
+        match waveform {
+            LfoWaveform::Sine => LfoShape::Sine,
+            LfoWaveform::Triangle => LfoShape::Triangle,
+            LfoWaveform::Saw => LfoShape::Saw,
+            LfoWaveform::Square => LfoShape::Square,
+            LfoWaveform::SampleAndHold => LfoShape::SampleAndHold,
+        }
+    }
+}
+
+#[derive(Params)]
+pub struct LfoParams {
+    #[id = "shape"]
+    pub shape: EnumParam<LfoWaveform>,
+
+    #[id = "rate"]
+    pub rate: FloatParam,
+}
+
+impl Default for LfoParams {
+    fn default() -> Self {
+        Self {
+            shape: EnumParam::new("Shape", LfoWaveform::Sine),
+
+            rate: FloatParam::new(
+                "Rate",
+                2.0,
+                FloatRange::Skewed {
+                    min: 0.01,
+                    max: 20.0,
+                    factor: FloatRange::skew_factor(-2.0),
+                },
+            )
+            .with_unit(" Hz")
+            .with_value_to_string(formatters::v2s_f32_rounded(2)),
+        }
+    }
+}
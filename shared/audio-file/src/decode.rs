@@ -0,0 +1,103 @@
+use crate::{AudioBuffer, Error};
+use dsp_core::resampler::ResamplerQuality;
+use std::fs::File;
+use std::io::{Cursor, ErrorKind};
+use std::path::Path;
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::codecs::{DecoderOptions, CODEC_TYPE_NULL};
+use symphonia::core::errors::Error as SymphoniaError;
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::{MediaSource, MediaSourceStream};
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+
+/// Read and decode a file. The extension is only a hint, the format is detected from the
+/// contents.
+pub fn read(path: impl AsRef<Path>) -> Result<AudioBuffer, Error> {
+    let path = path.as_ref();
+    let mut hint = Hint::new();
+    if let Some(extension) = path.extension().and_then(|extension| extension.to_str()) {
+        hint.with_extension(extension);
+    }
+    decode_source(Box::new(File::open(path)?), hint)
+}
+
+/// Read and decode a file, then resample it to `sample_rate`.
+pub fn read_at_rate(
+    path: impl AsRef<Path>,
+    sample_rate: u32,
+    quality: ResamplerQuality,
+) -> Result<AudioBuffer, Error> {
+    Ok(read(path)?.resampled(sample_rate, quality))
+}
+
+/// Decode a file's contents, detecting the format.
+pub fn decode(bytes: Vec<u8>) -> Result<AudioBuffer, Error> {
+    decode_source(Box::new(Cursor::new(bytes)), Hint::new())
+}
+
+fn decode_source(source: Box<dyn MediaSource>, hint: Hint) -> Result<AudioBuffer, Error> {
+    let stream = MediaSourceStream::new(source, Default::default());
+    let probed = symphonia::default::get_probe().format(
+        &hint,
+        stream,
+        &FormatOptions::default(),
+        &MetadataOptions::default(),
+    )?;
+    let mut reader = probed.format;
+
+    let track = reader
+        .tracks()
+        .iter()
+        .find(|track| track.codec_params.codec != CODEC_TYPE_NULL)
+        .ok_or(Error::NoAudio)?;
+    let track_id = track.id;
+    let sample_rate = track.codec_params.sample_rate.ok_or(Error::NoAudio)?;
+    let num_channels = track
+        .codec_params
+        .channels
+        .map_or(0, |channels| channels.count());
+    if sample_rate == 0 || num_channels == 0 {
+        return Err(Error::NoAudio);
+    }
+    let mut decoder =
+        symphonia::default::get_codecs().make(&track.codec_params, &DecoderOptions::default())?;
+
+    let capacity = track.codec_params.n_frames.unwrap_or(0) as usize;
+    let mut channels: Vec<Vec<f32>> = (0..num_channels)
+        .map(|_| Vec::with_capacity(capacity))
+        .collect();
+    let mut interleaved: Option<SampleBuffer<f32>> = None;
+    loop {
+        let packet = match reader.next_packet() {
+            Ok(packet) => packet,
+            // The end of the stream shows up as an unexpected end of file
+            Err(SymphoniaError::IoError(err)) if err.kind() == ErrorKind::UnexpectedEof => break,
+            Err(err) => return Err(err.into()),
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+
+        let decoded = match decoder.decode(&packet) {
+            Ok(decoded) => decoded,
+            // A damaged packet is skipped rather than losing the whole file
+            Err(SymphoniaError::DecodeError(_)) => continue,
+            Err(err) => return Err(err.into()),
+        };
+        // Decoders always return buffers of the same capacity, so one buffer fits every packet
+        let buffer = interleaved
+            .get_or_insert_with(|| SampleBuffer::new(decoded.capacity() as u64, *decoded.spec()));
+        buffer.copy_interleaved_ref(decoded);
+        for frame in buffer.samples().chunks_exact(num_channels) {
+            for (channel, &sample) in channels.iter_mut().zip(frame) {
+                channel.push(sample);
+            }
+        }
+    }
+
+    Ok(AudioBuffer {
+        sample_rate,
+        channels,
+    })
+}
@@ -0,0 +1,219 @@
+use crate::{AudioBuffer, Error};
+use hound::{SampleFormat, WavSpec, WavWriter};
+use std::fs::File;
+use std::io::{BufWriter, Cursor, Seek, Write};
+use std::path::Path;
+
+/// The sample format WAV files are written in. Integer formats clip at full scale and are
+/// rounded without dither.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum WavFormat {
+    Int16,
+    Int24,
+    /// Keeps everything the plugins produce, including peaks over full scale.
+    #[default]
+    Float32,
+}
+
+impl WavFormat {
+    fn spec(self, sample_rate: u32, num_channels: usize) -> WavSpec {
+        let (bits_per_sample, sample_format) = match self {
+            WavFormat::Int16 => (16, SampleFormat::Int),
+            WavFormat::Int24 => (24, SampleFormat::Int),
+            WavFormat::Float32 => (32, SampleFormat::Float),
+        };
+        WavSpec {
+            channels: num_channels as u16,
+            sample_rate,
+            bits_per_sample,
+            sample_format,
+        }
+    }
+
+    fn write_sample<W: Write + Seek>(
+        self,
+        writer: &mut WavWriter<W>,
+        sample: f32,
+    ) -> Result<(), Error> {
+        let clipped = sample.clamp(-1.0, 1.0);
+        match self {
+            WavFormat::Int16 => writer.write_sample((clipped * 32767.0).round() as i16)?,
+            WavFormat::Int24 => writer.write_sample((clipped * 8_388_607.0).round() as i32)?,
+            WavFormat::Float32 => writer.write_sample(sample)?,
+        }
+        Ok(())
+    }
+}
+
+/// Write planar channels as interleaved frames, as long as the shortest channel.
+fn write_frames<W: Write + Seek>(
+    writer: &mut WavWriter<W>,
+    format: WavFormat,
+    channels: &[&[f32]],
+) -> Result<(), Error> {
+    let num_frames = channels
+        .iter()
+        .map(|channel| channel.len())
+        .min()
+        .unwrap_or(0);
+    for i in 0..num_frames {
+        for channel in channels {
+            format.write_sample(writer, channel[i])?;
+        }
+    }
+    Ok(())
+}
+
+fn planar(audio: &AudioBuffer) -> Vec<&[f32]> {
+    audio.channels.iter().map(Vec::as_slice).collect()
+}
+
+/// Write audio to a WAV file, replacing the file if it exists.
+pub fn write_wav(
+    path: impl AsRef<Path>,
+    audio: &AudioBuffer,
+    format: WavFormat,
+) -> Result<(), Error> {
+    let spec = format.spec(audio.sample_rate, audio.num_channels());
+    let mut writer = WavWriter::create(path, spec)?;
+    write_frames(&mut writer, format, &planar(audio))?;
+    writer.finalize()?;
+    Ok(())
+}
+
+/// Encode audio as the contents of a WAV file.
+pub fn encode_wav(audio: &AudioBuffer, format: WavFormat) -> Result<Vec<u8>, Error> {
+    let mut bytes = Vec::new();
+    let spec = format.spec(audio.sample_rate, audio.num_channels());
+    let mut writer = WavWriter::new(Cursor::new(&mut bytes), spec)?;
+    write_frames(&mut writer, format, &planar(audio))?;
+    writer.finalize()?;
+    Ok(bytes)
+}
+
+/// Writes a WAV file a block at a time, for recording and rendering audio that doesn't fit in
+/// memory. Call [`finish()`][Self::finish()] at the end so the header has the final length.
+pub struct WavRecorder {
+    writer: WavWriter<BufWriter<File>>,
+    format: WavFormat,
+    num_channels: usize,
+}
+
+impl WavRecorder {
+    /// Create the file, replacing it if it exists.
+    pub fn create(
+        path: impl AsRef<Path>,
+        sample_rate: u32,
+        num_channels: usize,
+        format: WavFormat,
+    ) -> Result<Self, Error> {
+        let spec = format.spec(sample_rate, num_channels);
+        Ok(Self {
+            writer: WavWriter::create(path, spec)?,
+            format,
+            num_channels,
+        })
+    }
+
+    /// Append a block with one slice per channel. Missing channels are written as silence and
+    /// extra ones are ignored, and the block is as long as its shortest channel.
+    pub fn write(&mut self, channels: &[&[f32]]) -> Result<(), Error> {
+        let num_frames = channels
+            .iter()
+            .map(|channel| channel.len())
+            .min()
+            .unwrap_or(0);
+        for i in 0..num_frames {
+            for channel in 0..self.num_channels {
+                let sample = channels.get(channel).map_or(0.0, |channel| channel[i]);
+                self.format.write_sample(&mut self.writer, sample)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The number of frames written so far.
+    pub fn num_frames(&self) -> u32 {
+        self.writer.duration()
+    }
+
+    /// Update the header and close the file.
+    pub fn finish(self) -> Result<(), Error> {
+        self.writer.finalize()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{decode, read};
+
+    fn test_audio() -> AudioBuffer {
+        let channel = |frequency: f32| {
+            (0..4800)
+                .map(|n| 0.8 * (std::f32::consts::TAU * frequency * n as f32 / 48000.0).sin())
+                .collect()
+        };
+        AudioBuffer {
+            sample_rate: 48000,
+            channels: vec![channel(440.0), channel(1000.0)],
+        }
+    }
+
+    #[test]
+    fn encoded_files_decode_to_the_same_audio() {
+        let audio = test_audio();
+        // Integers are written scaled to the largest positive value and decoded scaled by one
+        // more than that, so they can be off by up to about one and a half steps
+        for (format, tolerance) in [
+            (WavFormat::Int16, 2.0 / 32768.0),
+            (WavFormat::Int24, 2.0 / 8_388_608.0),
+            (WavFormat::Float32, 0.0),
+        ] {
+            let decoded = decode(encode_wav(&audio, format).unwrap()).unwrap();
+            assert_eq!(decoded.sample_rate, audio.sample_rate, "{format:?}");
+            assert_eq!(decoded.num_channels(), audio.num_channels(), "{format:?}");
+            assert_eq!(decoded.num_frames(), audio.num_frames(), "{format:?}");
+
+            for (decoded, original) in decoded.channels.iter().zip(&audio.channels) {
+                for (n, (&decoded, &original)) in decoded.iter().zip(original).enumerate() {
+                    assert!(
+                        (decoded - original).abs() <= tolerance,
+                        "{format:?}, {n}: {decoded} vs {original}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn recordings_are_complete_once_finished() {
+        let path = std::env::temp_dir().join(format!("wav-recorder-{}.wav", std::process::id()));
+        let audio = test_audio();
+        let mut recorder =
+            WavRecorder::create(&path, audio.sample_rate, 2, WavFormat::Float32).unwrap();
+        for start in (0..audio.num_frames()).step_by(1000) {
+            let end = (start + 1000).min(audio.num_frames());
+            recorder
+                .write(&[
+                    &audio.channels[0][start..end],
+                    &audio.channels[1][start..end],
+                ])
+                .unwrap();
+        }
+        // A missing channel is recorded as silence
+        recorder.write(&[&[0.5; 100]]).unwrap();
+        assert_eq!(recorder.num_frames(), 4900);
+        recorder.finish().unwrap();
+
+        let recorded = read(&path);
+        std::fs::remove_file(&path).unwrap();
+        let recorded = recorded.unwrap();
+        assert_eq!(recorded.num_frames(), 4900);
+        assert_eq!(recorded.channels[0][..4800], audio.channels[0]);
+        assert_eq!(recorded.channels[1][..4800], audio.channels[1]);
+        assert!(recorded.channels[0][4800..].iter().all(|&s| s == 0.5));
+        assert!(recorded.channels[1][4800..].iter().all(|&s| s == 0.0));
+    }
+}
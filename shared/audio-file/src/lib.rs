@@ -0,0 +1,129 @@
+//! Reading and writing audio files, for loading samples and impulse responses and for rendering
+//! and recording to disk.
+//!
+//! WAV, AIFF, and FLAC files are decoded with symphonia into one `f32` buffer per channel, and
+//! can be converted to the rate they'll be played at with dsp-core's resampler. WAV files are
+//! written with hound. Everything here allocates and blocks on file IO, so it belongs on a
+//! background thread, never the audio thread.
+
+use dsp_core::resampler::{resample, ResamplerQuality};
+use std::fmt;
+
+/// Decoding WAV, AIFF, and FLAC files
+pub mod decode;
+
+/// Writing WAV files, whole or streamed
+pub mod encode;
+
+pub use decode::{decode, read, read_at_rate};
+pub use encode::{encode_wav, write_wav, WavFormat, WavRecorder};
+
+/// Decoded audio, one buffer per channel.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AudioBuffer {
+    pub sample_rate: u32,
+    /// The samples of each channel, all the same length. There's always at least one.
+    pub channels: Vec<Vec<f32>>,
+}
+
+impl AudioBuffer {
+    pub fn num_channels(&self) -> usize {
+        self.channels.len()
+    }
+
+    /// The number of samples in each channel.
+    pub fn num_frames(&self) -> usize {
+        self.channels.first().map_or(0, Vec::len)
+    }
+
+    pub fn duration_seconds(&self) -> f32 {
+        self.num_frames() as f32 / self.sample_rate as f32
+    }
+
+    /// The same audio at another sample rate. Returns a copy when the rate already matches.
+    pub fn resampled(&self, sample_rate: u32, quality: ResamplerQuality) -> AudioBuffer {
+        if sample_rate == self.sample_rate {
+            return self.clone();
+        }
+
+        AudioBuffer {
+            sample_rate,
+            channels: self
+                .channels
+                .iter()
+                .map(|channel| {
+                    resample(
+                        channel,
+                        self.sample_rate as f64,
+                        sample_rate as f64,
+                        quality,
+                    )
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Io(std::io::Error),
+    /// The data isn't a WAV, AIFF, or FLAC file, or uses a codec that isn't supported.
+    UnsupportedFormat,
+    /// The file has no audio track, or its sample rate or channels are unknown.
+    NoAudio,
+    /// The file is damaged.
+    Decode(symphonia::core::errors::Error),
+    /// Writing the WAV file failed.
+    Encode(hound::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(err) => write!(f, "could not access the file: {err}"),
+            Error::UnsupportedFormat => write!(
+                f,
+                "unsupported format, only WAV, AIFF, and FLAC can be loaded"
+            ),
+            Error::NoAudio => write!(f, "the file has no audio"),
+            Error::Decode(err) => write!(f, "could not decode the file: {err}"),
+            Error::Encode(err) => write!(f, "could not write the file: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(err) => Some(err),
+            Error::Decode(err) => Some(err),
+            Error::Encode(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+impl From<symphonia::core::errors::Error> for Error {
+    fn from(err: symphonia::core::errors::Error) -> Self {
+        match err {
+            symphonia::core::errors::Error::IoError(err) => Error::Io(err),
+            symphonia::core::errors::Error::Unsupported(_) => Error::UnsupportedFormat,
+            err => Error::Decode(err),
+        }
+    }
+}
+
+impl From<hound::Error> for Error {
+    fn from(err: hound::Error) -> Self {
+        match err {
+            hound::Error::IoError(err) => Error::Io(err),
+            err => Error::Encode(err),
+        }
+    }
+}
@@ -0,0 +1,19 @@
+use std::env;
+use std::path::PathBuf;
+
+fn main() {
+    let crate_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+    let header = crate_dir.join("include").join("dsp_core.h");
+
+    cbindgen::Builder::new()
+        .with_crate(&crate_dir)
+        .with_language(cbindgen::Language::C)
+        .with_include_guard("DSP_CORE_H")
+        .with_cpp_compat(true)
+        .with_documentation(true)
+        .generate()
+        .expect("Unable to generate the dsp-core C header")
+        .write_to_file(header);
+
+    println!("cargo:rerun-if-changed=src");
+}
@@ -0,0 +1,235 @@
+//! C ABI for dsp-core so the DSP can be reused from C and C++ (e.g. JUCE) projects. Every type is
+//! exposed as an opaque handle created with `*_new()` and released with `*_free()`. The C header
+//! is generated into `include/dsp_core.h` by the build script.
+
+use dsp_core::{envelopes::ADSREnvelope, oscillators::SineOsc};
+use std::slice;
+
+/// Opaque handle to a sine oscillator.
+pub struct DspSineOsc(SineOsc);
+
+/// Opaque handle to an ADSR envelope.
+pub struct DspAdsr(ADSREnvelope);
+
+/// Borrow the output buffer, or an empty slice when it's null.
+///
+/// # Safety
+///
+/// `out` must be null or point to `len` writable floats.
+unsafe fn output_buffer<'a>(out: *mut f32, len: usize) -> &'a mut [f32] {
+    if out.is_null() {
+        &mut []
+    } else {
+        slice::from_raw_parts_mut(out, len)
+    }
+}
+
+/// Create a sine oscillator running at `sample_rate`. Free it with `dsp_sine_osc_free()`.
+#[no_mangle]
+pub extern "C" fn dsp_sine_osc_new(sample_rate: f32) -> *mut DspSineOsc {
+    Box::into_raw(Box::new(DspSineOsc(SineOsc::new(sample_rate))))
+}
+
+/// # Safety
+///
+/// `osc` must be null or a pointer returned by `dsp_sine_osc_new()` that hasn't been freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn dsp_sine_osc_free(osc: *mut DspSineOsc) {
+    if !osc.is_null() {
+        drop(Box::from_raw(osc));
+    }
+}
+
+/// # Safety
+///
+/// `osc` must be null or a live pointer returned by `dsp_sine_osc_new()`.
+#[no_mangle]
+pub unsafe extern "C" fn dsp_sine_osc_set_frequency(osc: *mut DspSineOsc, frequency: f32) {
+    if let Some(osc) = osc.as_mut() {
+        osc.0.set_frequency(frequency);
+    }
+}
+
+/// Reset the oscillator's phase to zero.
+///
+/// # Safety
+///
+/// `osc` must be null or a live pointer returned by `dsp_sine_osc_new()`.
+#[no_mangle]
+pub unsafe extern "C" fn dsp_sine_osc_reset(osc: *mut DspSineOsc) {
+    if let Some(osc) = osc.as_mut() {
+        osc.0.reset();
+    }
+}
+
+/// Render `len` samples into `out`.
+///
+/// # Safety
+///
+/// `osc` must be null or a live pointer returned by `dsp_sine_osc_new()`, and `out` must be null
+/// or point to `len` writable floats.
+#[no_mangle]
+pub unsafe extern "C" fn dsp_sine_osc_process(osc: *mut DspSineOsc, out: *mut f32, len: usize) {
+    if let Some(osc) = osc.as_mut() {
+        for sample in output_buffer(out, len) {
+            *sample = osc.0.next_sample();
+        }
+    }
+}
+
+/// Create an ADSR envelope running at `sample_rate`. Free it with `dsp_adsr_free()`.
+#[no_mangle]
+pub extern "C" fn dsp_adsr_new(sample_rate: f32) -> *mut DspAdsr {
+    Box::into_raw(Box::new(DspAdsr(ADSREnvelope::new(sample_rate))))
+}
+
+/// # Safety
+///
+/// `env` must be null or a pointer returned by `dsp_adsr_new()` that hasn't been freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn dsp_adsr_free(env: *mut DspAdsr) {
+    if !env.is_null() {
+        drop(Box::from_raw(env));
+    }
+}
+
+/// Set the attack, decay, and release times in seconds and the sustain level from 0 to 1.
+///
+/// # Safety
+///
+/// `env` must be null or a live pointer returned by `dsp_adsr_new()`.
+#[no_mangle]
+pub unsafe extern "C" fn dsp_adsr_set_params(
+    env: *mut DspAdsr,
+    attack: f32,
+    decay: f32,
+    sustain: f32,
+    release: f32,
+) {
+    if let Some(env) = env.as_mut() {
+        env.0.set_attack(attack);
+        env.0.set_decay(decay);
+        env.0.set_sustain(sustain);
+        env.0.set_release(release);
+    }
+}
+
+/// # Safety
+///
+/// `env` must be null or a live pointer returned by `dsp_adsr_new()`.
+#[no_mangle]
+pub unsafe extern "C" fn dsp_adsr_note_on(env: *mut DspAdsr) {
+    if let Some(env) = env.as_mut() {
+        env.0.note_on();
+    }
+}
+
+/// # Safety
+///
+/// `env` must be null or a live pointer returned by `dsp_adsr_new()`.
+#[no_mangle]
+pub unsafe extern "C" fn dsp_adsr_note_off(env: *mut DspAdsr) {
+    if let Some(env) = env.as_mut() {
+        env.0.note_off();
+    }
+}
+
+/// Whether the envelope is still producing output. Null handles are never active.
+///
+/// # Safety
+///
+/// `env` must be null or a live pointer returned by `dsp_adsr_new()`.
+#[no_mangle]
+pub unsafe extern "C" fn dsp_adsr_is_active(env: *const DspAdsr) -> bool {
+    env.as_ref().is_some_and(|env| env.0.is_active())
+}
+
+/// Render `len` envelope values into `out`.
+///
+/// # Safety
+///
+/// `env` must be null or a live pointer returned by `dsp_adsr_new()`, and `out` must be null or
+/// point to `len` writable floats.
+#[no_mangle]
+pub unsafe extern "C" fn dsp_adsr_process(env: *mut DspAdsr, out: *mut f32, len: usize) {
+    if let Some(env) = env.as_mut() {
+        for sample in output_buffer(out, len) {
+            *sample = env.0.next_sample();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ptr;
+
+    const SAMPLE_RATE: f32 = 48000.0;
+
+    #[test]
+    fn null_handles_and_buffers_are_ignored() {
+        let mut out = [0.5; 16];
+        unsafe {
+            dsp_sine_osc_set_frequency(ptr::null_mut(), 440.0);
+            dsp_sine_osc_reset(ptr::null_mut());
+            dsp_sine_osc_process(ptr::null_mut(), out.as_mut_ptr(), out.len());
+            dsp_sine_osc_free(ptr::null_mut());
+
+            dsp_adsr_set_params(ptr::null_mut(), 0.1, 0.1, 0.5, 0.1);
+            dsp_adsr_note_on(ptr::null_mut());
+            dsp_adsr_note_off(ptr::null_mut());
+            dsp_adsr_process(ptr::null_mut(), out.as_mut_ptr(), out.len());
+            assert!(!dsp_adsr_is_active(ptr::null()));
+            dsp_adsr_free(ptr::null_mut());
+
+            let osc = dsp_sine_osc_new(SAMPLE_RATE);
+            dsp_sine_osc_process(osc, ptr::null_mut(), 16);
+            dsp_sine_osc_free(osc);
+        }
+        assert_eq!(out, [0.5; 16]);
+    }
+
+    #[test]
+    fn oscillators_render_the_same_sine_as_dsp_core() {
+        let mut expected = SineOsc::new(SAMPLE_RATE);
+        expected.set_frequency(1000.0);
+        let expected: Vec<f32> = (0..96).map(|_| expected.next_sample()).collect();
+
+        let mut out = [0.0; 96];
+        unsafe {
+            let osc = dsp_sine_osc_new(SAMPLE_RATE);
+            dsp_sine_osc_set_frequency(osc, 1000.0);
+            dsp_sine_osc_process(osc, out.as_mut_ptr(), out.len());
+            assert_eq!(out[..], expected[..]);
+
+            dsp_sine_osc_reset(osc);
+            dsp_sine_osc_process(osc, out.as_mut_ptr(), 48);
+            assert_eq!(out[..48], expected[..48]);
+            dsp_sine_osc_free(osc);
+        }
+    }
+
+    #[test]
+    fn envelopes_rise_sustain_and_release() {
+        let mut out = [0.0; 480];
+        unsafe {
+            let env = dsp_adsr_new(SAMPLE_RATE);
+            dsp_adsr_set_params(env, 0.001, 0.001, 0.5, 0.001);
+            assert!(!dsp_adsr_is_active(env));
+
+            dsp_adsr_note_on(env);
+            dsp_adsr_process(env, out.as_mut_ptr(), out.len());
+            assert!(out[0] > 0.0 && out[0] < 0.1);
+            assert!(out.contains(&1.0));
+            assert_eq!(out[out.len() - 1], 0.5);
+            assert!(dsp_adsr_is_active(env));
+
+            dsp_adsr_note_off(env);
+            dsp_adsr_process(env, out.as_mut_ptr(), out.len());
+            assert!(out[0] < 0.5);
+            assert_eq!(out[out.len() - 1], 0.0);
+            assert!(!dsp_adsr_is_active(env));
+            dsp_adsr_free(env);
+        }
+    }
+}
@@ -0,0 +1,79 @@
+//! wasm-bindgen wrappers around dsp-core so sounds can be prototyped in the browser with the same
+//! DSP code the plugins use. See `www/` for an AudioWorklet demo.
+
+use dsp_core::{envelopes::ADSREnvelope, utils};
+use wasm_bindgen::prelude::*;
+
+#[wasm_bindgen]
+pub struct SineOsc(dsp_core::oscillators::SineOsc);
+
+#[wasm_bindgen]
+impl SineOsc {
+    #[wasm_bindgen(constructor)]
+    pub fn new(sample_rate: f32) -> SineOsc {
+        SineOsc(dsp_core::oscillators::SineOsc::new(sample_rate))
+    }
+
+    #[wasm_bindgen(js_name = setFrequency)]
+    pub fn set_frequency(&mut self, frequency: f32) {
+        self.0.set_frequency(frequency);
+    }
+
+    pub fn reset(&mut self) {
+        self.0.reset();
+    }
+
+    /// Fill `out` with the next `out.length` samples.
+    pub fn process(&mut self, out: &mut [f32]) {
+        for sample in out {
+            *sample = self.0.next_sample();
+        }
+    }
+}
+
+#[wasm_bindgen]
+pub struct Adsr(ADSREnvelope);
+
+#[wasm_bindgen]
+impl Adsr {
+    #[wasm_bindgen(constructor)]
+    pub fn new(sample_rate: f32) -> Adsr {
+        Adsr(ADSREnvelope::new(sample_rate))
+    }
+
+    /// Attack, decay, and release are in seconds, sustain is a level from 0 to 1.
+    #[wasm_bindgen(js_name = setParams)]
+    pub fn set_params(&mut self, attack: f32, decay: f32, sustain: f32, release: f32) {
+        self.0.set_attack(attack);
+        self.0.set_decay(decay);
+        self.0.set_sustain(sustain);
+        self.0.set_release(release);
+    }
+
+    #[wasm_bindgen(js_name = noteOn)]
+    pub fn note_on(&mut self) {
+        self.0.note_on();
+    }
+
+    #[wasm_bindgen(js_name = noteOff)]
+    pub fn note_off(&mut self) {
+        self.0.note_off();
+    }
+
+    #[wasm_bindgen(js_name = isActive)]
+    pub fn is_active(&self) -> bool {
+        self.0.is_active()
+    }
+
+    /// Multiply `buffer` in place by the envelope.
+    pub fn apply(&mut self, buffer: &mut [f32]) {
+        for sample in buffer {
+            *sample *= self.0.next_sample();
+        }
+    }
+}
+
+#[wasm_bindgen(js_name = midiToFreq)]
+pub fn midi_to_freq(note: u8) -> f32 {
+    utils::midi_to_freq(note)
+}
@@ -0,0 +1,28 @@
+// Compiles the wasm module on the main thread and hands it to the worklet, since worklets can't
+// fetch resources themselves.
+const KEYS = "awsedftgyhujk";
+const BASE_NOTE = 60;
+
+document.getElementById("start").addEventListener("click", async () => {
+  const context = new AudioContext();
+  await context.audioWorklet.addModule("synth-processor.js");
+
+  const module = await WebAssembly.compileStreaming(fetch("pkg/dsp_core_wasm_bg.wasm"));
+  const node = new AudioWorkletNode(context, "dsp-core-synth", {
+    outputChannelCount: [1],
+    processorOptions: { module },
+  });
+  node.connect(context.destination);
+
+  window.addEventListener("keydown", (event) => {
+    const index = KEYS.indexOf(event.key);
+    if (index >= 0 && !event.repeat) {
+      node.port.postMessage({ type: "noteOn", note: BASE_NOTE + index });
+    }
+  });
+  window.addEventListener("keyup", (event) => {
+    if (KEYS.includes(event.key)) {
+      node.port.postMessage({ type: "noteOff" });
+    }
+  });
+});
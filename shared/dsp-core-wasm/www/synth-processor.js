@@ -0,0 +1,32 @@
+// Must be imported before the wasm-bindgen glue, which creates a TextDecoder when it loads
+import "./text-codec-polyfill.js";
+import { initSync, Adsr, SineOsc, midiToFreq } from "./pkg/dsp_core_wasm.js";
+
+class DspCoreSynth extends AudioWorkletProcessor {
+  constructor(options) {
+    super();
+    initSync({ module: options.processorOptions.module });
+
+    this.osc = new SineOsc(sampleRate);
+    this.env = new Adsr(sampleRate);
+    this.env.setParams(0.01, 0.2, 0.6, 0.4);
+
+    this.port.onmessage = ({ data }) => {
+      if (data.type === "noteOn") {
+        this.osc.setFrequency(midiToFreq(data.note));
+        this.env.noteOn();
+      } else if (data.type === "noteOff") {
+        this.env.noteOff();
+      }
+    };
+  }
+
+  process(_inputs, outputs) {
+    const output = outputs[0][0];
+    this.osc.process(output);
+    this.env.apply(output);
+    return true;
+  }
+}
+
+registerProcessor("dsp-core-synth", DspCoreSynth);
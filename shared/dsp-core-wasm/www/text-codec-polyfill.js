@@ -0,0 +1,16 @@
+// AudioWorkletGlobalScope lacks TextDecoder/TextEncoder in some browsers, but wasm-bindgen's glue
+// code expects them to exist. They're only used for error messages here.
+if (typeof globalThis.TextDecoder === "undefined") {
+  globalThis.TextDecoder = class {
+    decode() {
+      return "";
+    }
+  };
+}
+if (typeof globalThis.TextEncoder === "undefined") {
+  globalThis.TextEncoder = class {
+    encode() {
+      return new Uint8Array();
+    }
+  };
+}
@@ -0,0 +1,407 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use dsp_core::convolution::Convolver;
+use dsp_core::delay::{Allpass, DelayLine, FixedDelayLine};
+use dsp_core::filters::{FilterMode, FormantFilter, StateVariableFilter};
+use dsp_core::oversampling::Oversampler;
+use dsp_core::pitch_shift::PitchShifter;
+use dsp_core::resampler::{Resampler, ResamplerQuality};
+use dsp_core::voices::SineVoiceBank;
+use dsp_core::waveshaper::{Curve, Waveshaper};
+use dsp_core::wavetable::{Wavetable, WavetableOsc, FRAME_SIZE, NUM_LEVELS};
+use dsp_core::{envelopes::ADSREnvelope, oscillators::SineOsc, utils::midi_to_freq};
+use std::hint::black_box;
+
+const SAMPLE_RATES: [f32; 3] = [44100.0, 48000.0, 96000.0];
+const BLOCK_SIZE: usize = 512;
+const NUM_VOICES: usize = 16;
+
+fn sine_osc(c: &mut Criterion) {
+    let mut group = c.benchmark_group("SineOsc");
+    group.throughput(Throughput::Elements(BLOCK_SIZE as u64));
+
+    for sample_rate in SAMPLE_RATES {
+        let mut osc = SineOsc::new(sample_rate);
+        osc.set_frequency(440.0);
+
+        group.bench_with_input(
+            BenchmarkId::from_parameter(sample_rate),
+            &sample_rate,
+            |b, _| {
+                b.iter(|| {
+                    for _ in 0..BLOCK_SIZE {
+                        black_box(osc.next_sample());
+                    }
+                })
+            },
+        );
+    }
+
+    group.finish();
+}
+
+fn adsr(c: &mut Criterion) {
+    let mut group = c.benchmark_group("ADSREnvelope");
+    group.throughput(Throughput::Elements(BLOCK_SIZE as u64));
+
+    for sample_rate in SAMPLE_RATES {
+        let mut env = ADSREnvelope::new(sample_rate);
+
+        group.bench_with_input(
+            BenchmarkId::from_parameter(sample_rate),
+            &sample_rate,
+            |b, _| {
+                b.iter(|| {
+                    // Alternate gates so every stage gets exercised across iterations
+                    env.note_on();
+                    for i in 0..BLOCK_SIZE {
+                        if i == BLOCK_SIZE / 2 {
+                            env.note_off();
+                        }
+                        black_box(env.next_sample());
+                    }
+                })
+            },
+        );
+    }
+
+    group.finish();
+}
+
+fn state_variable_filter(c: &mut Criterion) {
+    let mut group = c.benchmark_group("StateVariableFilter");
+    group.throughput(Throughput::Elements(BLOCK_SIZE as u64));
+
+    for mode in [
+        FilterMode::LowPass,
+        FilterMode::HighPass,
+        FilterMode::BandPass,
+        FilterMode::Notch,
+    ] {
+        let mut filter = StateVariableFilter::new(48000.0);
+        filter.set_mode(mode);
+        filter.set_params(1000.0, 2.0);
+        let mut block = [0.0f32; BLOCK_SIZE];
+
+        group.bench_with_input(
+            BenchmarkId::new("fixed cutoff", format!("{mode:?}")),
+            &mode,
+            |b, _| {
+                b.iter(|| {
+                    block[0] = 1.0;
+                    filter.process_block(&mut block);
+                    black_box(&block);
+                })
+            },
+        );
+    }
+
+    // Recomputing the coefficients every sample, as a voice with a modulated cutoff does
+    let mut filter = StateVariableFilter::new(48000.0);
+    group.bench_function("modulated cutoff", |b| {
+        b.iter(|| {
+            for i in 0..BLOCK_SIZE {
+                filter.set_params(200.0 + i as f32 * 20.0, 2.0);
+                black_box(filter.process(1.0));
+            }
+        })
+    });
+
+    group.finish();
+}
+
+fn formant_filter(c: &mut Criterion) {
+    let mut group = c.benchmark_group("FormantFilter");
+    group.throughput(Throughput::Elements(BLOCK_SIZE as u64));
+
+    let mut filter = FormantFilter::new(48000.0);
+    group.bench_function("fixed vowel", |b| {
+        b.iter(|| {
+            for _ in 0..BLOCK_SIZE {
+                black_box(filter.process(1.0));
+            }
+        })
+    });
+
+    // Morphing every sample, as a voice with a modulated vowel does
+    let mut filter = FormantFilter::new(48000.0);
+    group.bench_function("modulated vowel", |b| {
+        b.iter(|| {
+            for i in 0..BLOCK_SIZE {
+                filter.set_morph(i as f32 / BLOCK_SIZE as f32);
+                black_box(filter.process(1.0));
+            }
+        })
+    });
+
+    group.finish();
+}
+
+fn delay_line(c: &mut Criterion) {
+    let mut group = c.benchmark_group("DelayLine");
+    group.throughput(Throughput::Elements(BLOCK_SIZE as u64));
+
+    // A voice's worth of short modulated delays, on the heap and inline
+    let mut heap: Vec<DelayLine<f32>> = (0..NUM_VOICES).map(|_| DelayLine::new(61)).collect();
+    group.bench_function("heap", |b| {
+        b.iter(|| {
+            for i in 0..BLOCK_SIZE {
+                let delay = 20.0 + (i % 32) as f32;
+                for line in &mut heap {
+                    black_box(line.process(1.0, delay));
+                }
+            }
+        })
+    });
+
+    let mut fixed = [(); NUM_VOICES].map(|_| FixedDelayLine::<f32, 64>::new());
+    group.bench_function("fixed", |b| {
+        b.iter(|| {
+            for i in 0..BLOCK_SIZE {
+                let delay = 20.0 + (i % 32) as f32;
+                for line in &mut fixed {
+                    black_box(line.process(1.0, delay));
+                }
+            }
+        })
+    });
+
+    group.finish();
+}
+
+fn allpass(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Allpass");
+    group.throughput(Throughput::Elements(BLOCK_SIZE as u64));
+
+    // A diffusion chain like a reverb's input stage
+    let mut first = Allpass::<f32, 142>::new(0.75);
+    let mut second = Allpass::<f32, 107>::new(0.75);
+    let mut third = Allpass::<f32, 379>::new(0.625);
+    let mut fourth = Allpass::<f32, 277>::new(0.625);
+    group.bench_function("chain of four", |b| {
+        b.iter(|| {
+            for i in 0..BLOCK_SIZE {
+                let input = if i == 0 { 1.0 } else { 0.0 };
+                let diffused = second.process(first.process(input));
+                black_box(fourth.process(third.process(diffused)));
+            }
+        })
+    });
+
+    group.finish();
+}
+
+fn pitch_shifter(c: &mut Criterion) {
+    let mut group = c.benchmark_group("PitchShifter");
+    group.throughput(Throughput::Elements(BLOCK_SIZE as u64));
+
+    for preserve_formants in [false, true] {
+        let mut shifter = PitchShifter::new(48000.0);
+        shifter.set_semitones(7.0);
+        shifter.set_preserve_formants(preserve_formants);
+        let mut block: Vec<f32> = (0..BLOCK_SIZE).map(|i| (i as f32 * 0.05).sin()).collect();
+
+        group.bench_with_input(
+            BenchmarkId::new("preserve formants", preserve_formants),
+            &preserve_formants,
+            |b, _| {
+                b.iter(|| {
+                    shifter.process_block(&mut block);
+                    black_box(&block);
+                })
+            },
+        );
+    }
+
+    group.finish();
+}
+
+fn convolver(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Convolver");
+    group.throughput(Throughput::Elements(BLOCK_SIZE as u64));
+
+    // Two seconds of decaying noise at 48 kHz, like a medium hall
+    let impulse_response: Vec<f32> = (0..96000)
+        .map(|i| ((i as f32 * 12.9898).sin() * 43758.545).fract() * (-(i as f32) / 14000.0).exp())
+        .collect();
+    for block_size in [128, 256, 1024] {
+        let mut convolver = Convolver::new(&impulse_response, block_size);
+        let mut block: Vec<f32> = (0..BLOCK_SIZE).map(|i| (i as f32 * 0.05).sin()).collect();
+
+        group.bench_with_input(
+            BenchmarkId::new("block size", block_size),
+            &block_size,
+            |b, _| {
+                b.iter(|| {
+                    convolver.process_block(&mut block);
+                    black_box(&block);
+                })
+            },
+        );
+    }
+
+    group.finish();
+}
+
+/// A tanh waveshaper at each oversampling factor, where the filters dominate the cost.
+fn oversampled_waveshaper(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Oversampled waveshaper");
+    group.throughput(Throughput::Elements(BLOCK_SIZE as u64));
+
+    for factor in [1, 2, 4, 8] {
+        let mut oversampler = Oversampler::new(factor);
+        let mut shaper = Waveshaper::new(48000.0 * factor as f32);
+        shaper.set_curve(Curve::Tanh);
+        shaper.set_drive(4.0);
+        let mut block: Vec<f32> = (0..BLOCK_SIZE).map(|i| (i as f32 * 0.05).sin()).collect();
+
+        group.bench_with_input(BenchmarkId::new("factor", factor), &factor, |b, _| {
+            b.iter(|| {
+                oversampler.process_block(&mut block, |sample| shaper.process(sample));
+                black_box(&block);
+            })
+        });
+    }
+
+    group.finish();
+}
+
+/// 44.1 kHz to 48 kHz at each quality, where the filter length sets the cost.
+fn resampler(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Resampler");
+    group.throughput(Throughput::Elements(BLOCK_SIZE as u64));
+
+    for quality in [
+        ResamplerQuality::Low,
+        ResamplerQuality::Medium,
+        ResamplerQuality::High,
+    ] {
+        let mut resampler = Resampler::from_rates(44100.0, 48000.0, quality);
+        let input: Vec<f32> = (0..BLOCK_SIZE).map(|i| (i as f32 * 0.05).sin()).collect();
+        let mut output = vec![0.0f32; BLOCK_SIZE * 2];
+
+        group.bench_with_input(
+            BenchmarkId::from_parameter(format!("{quality:?}")),
+            &quality,
+            |b, _| {
+                b.iter(|| {
+                    black_box(resampler.process(&input, &mut output));
+                })
+            },
+        );
+    }
+
+    group.finish();
+}
+
+fn wavetable_osc(c: &mut Criterion) {
+    let mut group = c.benchmark_group("WavetableOsc");
+    group.throughput(Throughput::Elements(BLOCK_SIZE as u64));
+
+    // The contents don't matter for speed, only the number of frames to morph between
+    let num_frames = 4;
+    let data = (0..num_frames * NUM_LEVELS * FRAME_SIZE)
+        .map(|i| (std::f32::consts::TAU * (i % FRAME_SIZE) as f32 / FRAME_SIZE as f32).sin())
+        .collect();
+    let table = Wavetable::from_mipmaps(data).unwrap();
+
+    for sample_rate in SAMPLE_RATES {
+        let mut osc = WavetableOsc::new(sample_rate);
+        osc.set_frequency(440.0);
+        osc.set_position(0.4);
+
+        group.bench_with_input(
+            BenchmarkId::from_parameter(sample_rate),
+            &sample_rate,
+            |b, _| {
+                b.iter(|| {
+                    for _ in 0..BLOCK_SIZE {
+                        black_box(osc.next_sample(&table));
+                    }
+                })
+            },
+        );
+    }
+
+    group.finish();
+}
+
+/// The same per-sample work as sine-synth's process loop with every voice sounding.
+fn synth_voice_loop(c: &mut Criterion) {
+    let mut group = c.benchmark_group("16-voice synth loop");
+    group.throughput(Throughput::Elements(BLOCK_SIZE as u64));
+
+    for sample_rate in SAMPLE_RATES {
+        let mut voices: Vec<(SineOsc, ADSREnvelope)> = (0..NUM_VOICES)
+            .map(|i| {
+                let mut osc = SineOsc::new(sample_rate);
+                osc.set_frequency(midi_to_freq(48 + i as u8));
+                let mut env = ADSREnvelope::new(sample_rate);
+                env.set_release(5.0);
+                env.note_on();
+                (osc, env)
+            })
+            .collect();
+        let mut output = [0.0f32; BLOCK_SIZE];
+
+        // An array of voice structs, each processed in turn
+        group.bench_with_input(
+            BenchmarkId::new("array of structs", sample_rate),
+            &sample_rate,
+            |b, _| {
+                b.iter(|| {
+                    for sample in output.iter_mut() {
+                        let mut sum = 0.0;
+                        for (osc, env) in voices.iter_mut() {
+                            if env.is_active() {
+                                sum += osc.next_sample() * env.next_sample();
+                            }
+                        }
+                        *sample = sum / NUM_VOICES as f32;
+                    }
+                    black_box(&output);
+                })
+            },
+        );
+
+        // The same voices with each field stored across all of them
+        let mut bank = SineVoiceBank::<f32, NUM_VOICES>::new(sample_rate);
+        bank.set_release(5.0);
+        for voice in 0..NUM_VOICES {
+            bank.note_on(
+                voice,
+                midi_to_freq(48 + voice as u8),
+                1.0 / NUM_VOICES as f32,
+            );
+        }
+
+        group.bench_with_input(
+            BenchmarkId::new("struct of arrays", sample_rate),
+            &sample_rate,
+            |b, _| {
+                b.iter(|| {
+                    bank.process_block(&mut output);
+                    black_box(&output);
+                })
+            },
+        );
+    }
+
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    sine_osc,
+    adsr,
+    state_variable_filter,
+    formant_filter,
+    delay_line,
+    allpass,
+    pitch_shifter,
+    convolver,
+    oversampled_waveshaper,
+    resampler,
+    wavetable_osc,
+    synth_voice_loop
+);
+criterion_main!(benches);
@@ -0,0 +1,104 @@
+use crate::Sample;
+
+/// A stack of sine partials at set ratios to a fundamental, each with its own level, like the
+/// tonewheels under an organ's drawbars. Every partial keeps its own phase so the ratios don't
+/// have to be whole numbers, and partials at or above Nyquist are left out instead of aliasing.
+#[derive(Debug, Clone)]
+pub struct AdditiveOsc<T: Sample = f32, const PARTIALS: usize = 9> {
+    phases: [T; PARTIALS],
+    ratios: [T; PARTIALS],
+    levels: [T; PARTIALS],
+    frequency: T,
+    sample_rate: T,
+}
+
+impl<T: Sample, const PARTIALS: usize> AdditiveOsc<T, PARTIALS> {
+    /// All partials start silent.
+    pub fn new(sample_rate: T, ratios: [T; PARTIALS]) -> Self {
+        Self {
+            phases: [T::ZERO; PARTIALS],
+            ratios,
+            levels: [T::ZERO; PARTIALS],
+            frequency: T::from_f32(440.0),
+            sample_rate,
+        }
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: T) {
+        self.sample_rate = sample_rate;
+    }
+
+    /// Set the fundamental in Hz. Only the phase increments change, so this can be called every
+    /// sample for vibrato.
+    pub fn set_frequency(&mut self, frequency: T) {
+        self.frequency = frequency;
+    }
+
+    /// Set a partial's frequency as a ratio to the fundamental.
+    pub fn set_ratio(&mut self, partial: usize, ratio: T) {
+        self.ratios[partial] = ratio;
+    }
+
+    /// Set a partial's linear level.
+    pub fn set_level(&mut self, partial: usize, level: T) {
+        self.levels[partial] = level;
+    }
+
+    /// Restart every partial at zero phase.
+    pub fn reset(&mut self) {
+        self.phases = [T::ZERO; PARTIALS];
+    }
+
+    #[inline]
+    pub fn next_sample(&mut self) -> T {
+        let nyquist = self.sample_rate * T::HALF;
+        let mut sample = T::ZERO;
+        for ((phase, &ratio), &level) in self.phases.iter_mut().zip(&self.ratios).zip(&self.levels)
+        {
+            let frequency = self.frequency * ratio;
+            if frequency < nyquist {
+                sample += (*phase * T::TAU).sin() * level;
+            }
+
+            // Silent partials keep turning so they come back in phase when they're raised
+            *phase += frequency / self.sample_rate;
+            *phase -= phase.floor();
+        }
+
+        sample
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: f32 = 48000.0;
+
+    #[test]
+    fn partials_sum_at_their_ratios() {
+        let mut osc = AdditiveOsc::<f32, 2>::new(SAMPLE_RATE, [1.0, 3.0]);
+        osc.set_frequency(100.0);
+        osc.set_level(0, 1.0);
+        osc.set_level(1, 0.5);
+
+        for n in 0..1000 {
+            let t = n as f32 / SAMPLE_RATE;
+            let expected = (std::f32::consts::TAU * 100.0 * t).sin()
+                + 0.5 * (std::f32::consts::TAU * 300.0 * t).sin();
+            let sample = osc.next_sample();
+            assert!(
+                (sample - expected).abs() < 1e-3,
+                "{n}: {sample} vs {expected}"
+            );
+        }
+    }
+
+    #[test]
+    fn partials_above_nyquist_are_left_out() {
+        let mut osc = AdditiveOsc::<f32, 2>::new(SAMPLE_RATE, [1.0, 8.0]);
+        osc.set_frequency(4000.0);
+        osc.set_level(1, 1.0);
+        assert!((0..1000).all(|_| osc.next_sample() == 0.0));
+    }
+}
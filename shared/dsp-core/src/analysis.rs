@@ -0,0 +1,389 @@
+//! Signal analysis that doesn't change the audio, like pitch detection for tuners and stereo
+//! correlation for phase meters.
+
+#[cfg(not(feature = "std"))]
+use alloc::{vec, vec::Vec};
+
+use crate::Sample;
+
+/// A detected pitch.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PitchEstimate {
+    pub frequency: f32,
+    /// How periodic the signal is, from 0 for noise to 1 for a pure tone.
+    pub clarity: f32,
+}
+
+/// How far a frequency is from the nearest note.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NoteOffset {
+    /// The nearest MIDI note.
+    pub note: u8,
+    /// The offset from that note, within ±50 cents inside the MIDI note range.
+    pub cents: f32,
+}
+
+impl NoteOffset {
+    /// Find the note nearest to `frequency`, given the frequency of A4.
+    pub fn from_frequency(frequency: f32, reference_pitch: f32) -> Self {
+        let semitones =
+            69.0 + 12.0 * Sample::ln(frequency / reference_pitch) / core::f32::consts::LN_2;
+        let note = Sample::floor(semitones + 0.5).clamp(0.0, 127.0);
+
+        Self {
+            note: note as u8,
+            cents: (semitones - note) * 100.0,
+        }
+    }
+}
+
+/// Monophonic pitch detection with the YIN algorithm. Samples are collected in a window long
+/// enough for two periods of the lowest frequency, and the pitch is estimated again every hop by
+/// finding the first lag where the window is nearly as similar to itself as it gets. Every
+/// estimate costs about `(sample_rate / min_frequency)²` operations, so keep the lowest frequency
+/// only as low as it needs to be.
+///
+/// ```ignore
+/// detector.process_block(channel);
+/// if let Some(pitch) = detector.pitch() {
+///     let offset = NoteOffset::from_frequency(pitch.frequency, 440.0);
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct PitchDetector {
+    sample_rate: f32,
+    /// The shortest and longest periods searched for, in samples.
+    min_lag: usize,
+    max_lag: usize,
+    /// The number of samples compared at every lag.
+    window_len: usize,
+    threshold: f32,
+    /// Windows quieter than this, in mean square, don't have a pitch.
+    min_power: f32,
+
+    /// The last `window_len + max_lag` samples, oldest first once unwrapped.
+    ring: Vec<f32>,
+    write_pos: usize,
+    hop: usize,
+    samples_until_detection: usize,
+
+    frame: Vec<f32>,
+    /// The cumulative mean normalized difference at every lag.
+    difference: Vec<f32>,
+    pitch: Option<PitchEstimate>,
+}
+
+impl PitchDetector {
+    /// Search for pitches between `min_frequency` and `max_frequency`, estimating the pitch
+    /// again every `hop` samples. Allocates, so call this from `initialize()`.
+    pub fn new(sample_rate: f32, min_frequency: f32, max_frequency: f32, hop: usize) -> Self {
+        let min_lag = ((sample_rate / max_frequency) as usize).max(2);
+        let max_lag = ((sample_rate / min_frequency) as usize + 1).max(min_lag + 2);
+        let window_len = max_lag;
+
+        Self {
+            sample_rate,
+            min_lag,
+            max_lag,
+            window_len,
+            threshold: 0.15,
+            min_power: 1e-6,
+
+            ring: vec![0.0; window_len + max_lag],
+            write_pos: 0,
+            hop: hop.max(1),
+            samples_until_detection: hop.max(1),
+
+            frame: vec![0.0; window_len + max_lag],
+            difference: vec![0.0; max_lag + 1],
+            pitch: None,
+        }
+    }
+
+    /// The highest normalized difference accepted as periodic, 0.1 to 0.2 works for most
+    /// instruments. Lower values reject more noisy signals.
+    pub fn set_threshold(&mut self, threshold: f32) {
+        self.threshold = threshold;
+    }
+
+    /// The quietest level in dBFS that's still analyzed, to keep the pitch from jumping around
+    /// on background noise.
+    pub fn set_gate(&mut self, gate_db: f32) {
+        let gain = Sample::powf(10.0f32, gate_db / 20.0);
+        self.min_power = gain * gain;
+    }
+
+    /// The most recent estimate, or `None` if the input is silent or not periodic.
+    pub fn pitch(&self) -> Option<PitchEstimate> {
+        self.pitch
+    }
+
+    pub fn reset(&mut self) {
+        self.ring.fill(0.0);
+        self.write_pos = 0;
+        self.samples_until_detection = self.hop;
+        self.pitch = None;
+    }
+
+    /// Add a sample, returning true when a new estimate was made.
+    pub fn process(&mut self, sample: f32) -> bool {
+        self.ring[self.write_pos] = sample;
+        self.write_pos = (self.write_pos + 1) % self.ring.len();
+
+        self.samples_until_detection -= 1;
+        if self.samples_until_detection > 0 {
+            return false;
+        }
+
+        self.samples_until_detection = self.hop;
+        self.pitch = self.detect();
+        true
+    }
+
+    pub fn process_block(&mut self, block: &[f32]) {
+        for &sample in block {
+            self.process(sample);
+        }
+    }
+
+    fn detect(&mut self) -> Option<PitchEstimate> {
+        let (newest, oldest) = self.ring.split_at(self.write_pos);
+        self.frame[..oldest.len()].copy_from_slice(oldest);
+        self.frame[oldest.len()..].copy_from_slice(newest);
+
+        let window = &self.frame[..self.window_len];
+        let power = window.iter().map(|x| x * x).sum::<f32>() / self.window_len as f32;
+        if power < self.min_power {
+            return None;
+        }
+
+        // The squared difference between the window and itself shifted by each lag, normalized
+        // by its running mean so the search doesn't settle on lags near zero
+        self.difference[0] = 1.0;
+        let mut running_sum = 0.0;
+        for lag in 1..=self.max_lag {
+            let shifted = &self.frame[lag..lag + self.window_len];
+            let difference: f32 = window
+                .iter()
+                .zip(shifted)
+                .map(|(a, b)| (a - b) * (a - b))
+                .sum();
+            running_sum += difference;
+            self.difference[lag] = if running_sum > 0.0 {
+                difference * lag as f32 / running_sum
+            } else {
+                1.0
+            };
+        }
+
+        // Take the first dip below the threshold rather than the deepest one, which might be a
+        // multiple of the period
+        let mut lag =
+            (self.min_lag..self.max_lag).find(|&lag| self.difference[lag] < self.threshold)?;
+        while lag + 1 < self.max_lag && self.difference[lag + 1] < self.difference[lag] {
+            lag += 1;
+        }
+
+        // Fit a parabola through the dip for a lag between samples
+        let (left, center, right) = (
+            self.difference[lag - 1],
+            self.difference[lag],
+            self.difference[lag + 1],
+        );
+        let curvature = left - 2.0 * center + right;
+        let offset = if curvature > 0.0 {
+            (0.5 * (left - right) / curvature).clamp(-0.5, 0.5)
+        } else {
+            0.0
+        };
+
+        Some(PitchEstimate {
+            frequency: self.sample_rate / (lag as f32 + offset),
+            clarity: (1.0 - center).clamp(0.0, 1.0),
+        })
+    }
+}
+
+/// The correlation between two channels, averaged over a window. It's 1 when the channels are
+/// identical, around 0 when they're unrelated, and -1 when one is the other inverted. Below zero
+/// the channels partly cancel when summed to mono.
+#[derive(Debug, Clone)]
+pub struct StereoCorrelation {
+    window_seconds: f32,
+    /// The one-pole averaging coefficient.
+    weight: f32,
+    product: f32,
+    left_power: f32,
+    right_power: f32,
+}
+
+impl StereoCorrelation {
+    /// `window_seconds` is the averaging time constant, 0.3 seconds is typical for meters.
+    pub fn new(sample_rate: f32, window_seconds: f32) -> Self {
+        let mut correlation = Self {
+            window_seconds,
+            weight: 0.0,
+            product: 0.0,
+            left_power: 0.0,
+            right_power: 0.0,
+        };
+        correlation.set_sample_rate(sample_rate);
+        correlation
+    }
+
+    /// Keeps the window in seconds.
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.weight = 1.0 - Sample::exp(-1.0 / (self.window_seconds * sample_rate));
+    }
+
+    pub fn reset(&mut self) {
+        self.product = 0.0;
+        self.left_power = 0.0;
+        self.right_power = 0.0;
+    }
+
+    #[inline]
+    pub fn process(&mut self, left: f32, right: f32) {
+        self.product += (left * right - self.product) * self.weight;
+        self.left_power += (left * left - self.left_power) * self.weight;
+        self.right_power += (right * right - self.right_power) * self.weight;
+    }
+
+    pub fn process_block(&mut self, left: &[f32], right: &[f32]) {
+        for (&left, &right) in left.iter().zip(right) {
+            self.process(left, right);
+        }
+    }
+
+    /// Zero for silence, where there's nothing to correlate.
+    pub fn value(&self) -> f32 {
+        let power = Sample::sqrt(self.left_power * self.right_power);
+        if power > 1e-10 {
+            (self.product / power).clamp(-1.0, 1.0)
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Collects points for a goniometer, the Lissajous display of a stereo signal turned by 45° so
+/// mono sits on the vertical axis. Each point is `(x, y)` with the side signal on x, negated so
+/// the left channel leans left, and the mid signal on y. Only every `decimation`th sample is
+/// kept, since a display can't show more points than it has pixels anyway.
+#[derive(Debug, Clone)]
+pub struct GoniometerPoints {
+    points: Vec<(f32, f32)>,
+    capacity: usize,
+    decimation: usize,
+    counter: usize,
+}
+
+impl GoniometerPoints {
+    /// Holds up to `capacity` points between calls to [`clear()`][Self::clear()], dropping any
+    /// more than that.
+    pub fn new(capacity: usize, decimation: usize) -> Self {
+        Self {
+            points: Vec::with_capacity(capacity),
+            capacity,
+            decimation: decimation.max(1),
+            counter: 0,
+        }
+    }
+
+    #[inline]
+    pub fn process(&mut self, left: f32, right: f32) {
+        self.counter += 1;
+        if self.counter < self.decimation {
+            return;
+        }
+        self.counter = 0;
+
+        if self.points.len() < self.capacity {
+            let (mid, side) = crate::stereo::encode_mid_side(left, right);
+            self.points.push((-side, mid));
+        }
+    }
+
+    pub fn process_block(&mut self, left: &[f32], right: &[f32]) {
+        for (&left, &right) in left.iter().zip(right) {
+            self.process(left, right);
+        }
+    }
+
+    /// The points collected since the last [`clear()`][Self::clear()], oldest first.
+    pub fn points(&self) -> &[(f32, f32)] {
+        &self.points
+    }
+
+    /// Forget the collected points without freeing them, after they've been handed off.
+    pub fn clear(&mut self) {
+        self.points.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::TAU;
+
+    const SAMPLE_RATE: f32 = 48000.0;
+
+    fn sine(frequency: f32, amplitude: f32, len: usize) -> Vec<f32> {
+        (0..len)
+            .map(|n| amplitude * (TAU * frequency * n as f32 / SAMPLE_RATE).sin())
+            .collect()
+    }
+
+    #[test]
+    fn pure_tones_are_detected() {
+        for frequency in [55.0, 220.0, 440.0, 1000.0] {
+            let mut detector = PitchDetector::new(SAMPLE_RATE, 40.0, 2000.0, 512);
+            detector.process_block(&sine(frequency, 0.5, SAMPLE_RATE as usize / 4));
+
+            let pitch = detector.pitch().expect("a sine has a pitch");
+            assert!(
+                (pitch.frequency - frequency).abs() < frequency * 0.001,
+                "{frequency}: {}",
+                pitch.frequency
+            );
+            assert!(pitch.clarity > 0.95, "{frequency}: {}", pitch.clarity);
+        }
+    }
+
+    #[test]
+    fn signals_below_the_gate_have_no_pitch() {
+        let mut detector = PitchDetector::new(SAMPLE_RATE, 40.0, 2000.0, 512);
+        detector.set_gate(-40.0);
+        detector.process_block(&sine(220.0, 0.5, 8192));
+        assert!(detector.pitch().is_some());
+
+        // A mean square of -43 dB
+        detector.process_block(&sine(220.0, 0.01, 8192));
+        assert_eq!(detector.pitch(), None);
+
+        detector.process_block(&vec![0.0; 8192]);
+        assert_eq!(detector.pitch(), None);
+    }
+
+    #[test]
+    fn note_offsets_are_in_cents() {
+        let a4 = NoteOffset::from_frequency(440.0, 440.0);
+        assert_eq!(a4.note, 69);
+        assert!(a4.cents.abs() < 1e-3);
+
+        // 10 cents sharp of C4 and 20 cents flat of E4
+        let sharp_c4 = NoteOffset::from_frequency(261.6256 * 2f32.powf(10.0 / 1200.0), 440.0);
+        assert_eq!(sharp_c4.note, 60);
+        assert!((sharp_c4.cents - 10.0).abs() < 0.01, "{}", sharp_c4.cents);
+        let flat_e4 = NoteOffset::from_frequency(329.6276 * 2f32.powf(-20.0 / 1200.0), 440.0);
+        assert_eq!(flat_e4.note, 64);
+        assert!((flat_e4.cents + 20.0).abs() < 0.01, "{}", flat_e4.cents);
+
+        // Just past a quarter tone rounds to the next note, and the reference shifts every note
+        let above = NoteOffset::from_frequency(440.0 * 2f32.powf(0.51 / 12.0), 440.0);
+        assert_eq!(above.note, 70);
+        assert!((above.cents + 49.0).abs() < 0.01, "{}", above.cents);
+        let baroque = NoteOffset::from_frequency(415.0, 415.0);
+        assert_eq!(baroque.note, 69);
+    }
+}
@@ -0,0 +1,196 @@
+use crate::Sample;
+
+/// Default crossfade length when bypass is toggled.
+pub const DEFAULT_FADE_TIME: f32 = 0.01;
+
+/// Crossfades between the processed signal and the bypassed signal when bypass is toggled, so
+/// plugins don't click. Effects fade to their dry input, instruments fade to silence. The fade
+/// uses an equal-power curve so the level doesn't dip halfway through.
+#[derive(Debug, Clone)]
+pub struct SoftBypass<T: Sample = f32> {
+    bypassed: bool,
+    /// 0 is fully processed, 1 is fully bypassed.
+    position: T,
+    step: T,
+    sample_rate: T,
+    fade_time: T,
+}
+
+impl<T: Sample> SoftBypass<T> {
+    pub fn new(sample_rate: T) -> Self {
+        let mut bypass = Self {
+            bypassed: false,
+            position: T::ZERO,
+            step: T::ZERO,
+            sample_rate,
+            fade_time: T::from_f32(DEFAULT_FADE_TIME),
+        };
+        bypass.set_fade_time(sample_rate, bypass.fade_time);
+
+        bypass
+    }
+
+    /// Set the crossfade length in seconds.
+    pub fn set_fade_time(&mut self, sample_rate: T, fade_time: T) {
+        self.sample_rate = sample_rate;
+        self.fade_time = fade_time;
+        let fade_samples = (fade_time * sample_rate).max(T::ONE);
+        self.step = T::ONE / fade_samples;
+    }
+
+    /// Keeps the crossfade length in seconds.
+    pub fn set_sample_rate(&mut self, sample_rate: T) {
+        self.set_fade_time(sample_rate, self.fade_time);
+    }
+
+    /// Jump to the bypassed or processed state without fading, e.g. when the plugin is reset.
+    pub fn reset(&mut self, bypassed: bool) {
+        self.bypassed = bypassed;
+        self.position = if bypassed { T::ONE } else { T::ZERO };
+    }
+
+    /// Start fading towards the bypassed or processed state.
+    pub fn set_bypassed(&mut self, bypassed: bool) {
+        self.bypassed = bypassed;
+    }
+
+    pub fn is_bypassed(&self) -> bool {
+        self.bypassed
+    }
+
+    /// Whether the fade to bypass has finished, in which case the processing can be skipped.
+    pub fn is_fully_bypassed(&self) -> bool {
+        self.bypassed && self.position >= T::ONE
+    }
+
+    /// Whether the output is entirely the processed signal.
+    pub fn is_fully_processed(&self) -> bool {
+        !self.bypassed && self.position <= T::ZERO
+    }
+
+    /// Advance the fade by one sample, returning the gains for the processed and the bypassed
+    /// signal.
+    pub fn next_gains(&mut self) -> (T, T) {
+        if self.bypassed {
+            self.position = (self.position + self.step).min(T::ONE);
+        } else {
+            self.position = (self.position - self.step).max(T::ZERO);
+        }
+
+        // Exact gains at the ends, the trigonometric functions don't quite reach zero
+        if self.position >= T::ONE {
+            return (T::ZERO, T::ONE);
+        } else if self.position <= T::ZERO {
+            return (T::ONE, T::ZERO);
+        }
+
+        let angle = self.position * T::PI * T::HALF;
+        (angle.cos(), angle.sin())
+    }
+
+    /// Crossfade a block of processed audio in place with the dry input. `processed` and `dry`
+    /// hold one slice per channel, all channels share the same fade.
+    pub fn process<P, D>(&mut self, processed: &mut [P], dry: &[D])
+    where
+        P: AsMut<[T]>,
+        D: AsRef<[T]>,
+    {
+        if self.is_fully_processed() {
+            return;
+        }
+
+        let num_samples = processed
+            .iter_mut()
+            .map(|channel| channel.as_mut().len())
+            .min()
+            .unwrap_or(0);
+        for sample_idx in 0..num_samples {
+            let (processed_gain, dry_gain) = self.next_gains();
+            for (channel_idx, processed) in processed.iter_mut().enumerate() {
+                // Channels without a dry signal fade to silence
+                let dry = dry
+                    .get(channel_idx)
+                    .and_then(|dry| dry.as_ref().get(sample_idx))
+                    .copied()
+                    .unwrap_or(T::ZERO);
+                let processed = &mut processed.as_mut()[sample_idx];
+                *processed = *processed * processed_gain + dry * dry_gain;
+            }
+        }
+    }
+
+    /// Like [`process()`][Self::process()], but fading to silence for instruments.
+    pub fn process_to_silence<P: AsMut<[T]>>(&mut self, processed: &mut [P]) {
+        if self.is_fully_processed() {
+            return;
+        }
+
+        let num_samples = processed
+            .iter_mut()
+            .map(|channel| channel.as_mut().len())
+            .min()
+            .unwrap_or(0);
+        for sample_idx in 0..num_samples {
+            let (processed_gain, _) = self.next_gains();
+            for processed in processed.iter_mut() {
+                processed.as_mut()[sample_idx] *= processed_gain;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: f32 = 48000.0;
+
+    #[test]
+    fn fades_take_the_fade_time_and_end_exactly() {
+        let mut bypass = SoftBypass::new(SAMPLE_RATE);
+        let fade_samples = (DEFAULT_FADE_TIME * SAMPLE_RATE) as usize;
+        assert!(bypass.is_fully_processed());
+
+        bypass.set_bypassed(true);
+        for n in 1..fade_samples {
+            let (processed, dry) = bypass.next_gains();
+            assert!(processed > 0.0 && dry < 1.0, "{n}: {processed}, {dry}");
+            // Equal power throughout
+            assert!((processed * processed + dry * dry - 1.0).abs() < 1e-5);
+        }
+        assert_eq!(bypass.next_gains(), (0.0, 1.0));
+        assert!(bypass.is_fully_bypassed());
+
+        bypass.set_bypassed(false);
+        for _ in 1..fade_samples {
+            assert_ne!(bypass.next_gains(), (1.0, 0.0));
+        }
+        assert_eq!(bypass.next_gains(), (1.0, 0.0));
+        assert!(bypass.is_fully_processed());
+    }
+
+    #[test]
+    fn instruments_fade_to_silence() {
+        let mut bypass = SoftBypass::new(SAMPLE_RATE);
+        let fade_samples = (DEFAULT_FADE_TIME * SAMPLE_RATE) as usize;
+        let mut block = [vec![1.0; fade_samples + 10], vec![-1.0; fade_samples + 10]];
+        bypass.set_bypassed(true);
+        bypass.process_to_silence(&mut block);
+
+        for channel in &block {
+            assert!(channel[..fade_samples - 1].iter().all(|&s| s != 0.0));
+            assert!(channel[fade_samples - 1..].iter().all(|&s| s == 0.0));
+        }
+        assert!(bypass.is_fully_bypassed());
+    }
+
+    #[test]
+    fn effects_fade_to_their_dry_input() {
+        let mut bypass = SoftBypass::new(SAMPLE_RATE);
+        bypass.reset(true);
+        let dry = [vec![0.25; 16]];
+        let mut processed = [vec![1.0; 16]];
+        bypass.process(&mut processed, &dry);
+        assert_eq!(processed, dry);
+    }
+}
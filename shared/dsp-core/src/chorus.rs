@@ -0,0 +1,111 @@
+use crate::delay::DelayLine;
+use crate::lfo::{Lfo, LfoShape};
+use crate::Sample;
+
+/// A mono chorus and vibrato: an LFO sweeps a short delay around its center, and the swept
+/// signal is blended with the dry one. At full mix only the pitch wobble of the sweep is heard,
+/// which is a vibrato. At half mix the two beat against each other, which is the chorus.
+#[derive(Debug, Clone)]
+pub struct Chorus<T: Sample = f32> {
+    delay: DelayLine<T>,
+    lfo: Lfo<T>,
+    sample_rate: T,
+    /// The delay in the middle of the sweep, in samples.
+    center: T,
+    /// How far the delay swings either side of the center, in samples.
+    depth: T,
+    mix: T,
+}
+
+impl<T: Sample> Chorus<T> {
+    /// Allocates, so call this from `initialize()`. `max_delay` is the longest the center plus
+    /// the depth can be, in seconds.
+    pub fn new(sample_rate: T, max_delay: T) -> Self {
+        let max_delay = DelayLine::<T>::samples_for(sample_rate, max_delay) + 2;
+        Self {
+            delay: DelayLine::new(max_delay),
+            lfo: Lfo::new(sample_rate),
+            sample_rate,
+            center: T::ONE,
+            depth: T::ZERO,
+            mix: T::HALF,
+        }
+    }
+
+    pub fn set_rate(&mut self, rate: T) {
+        self.lfo.set_rate(rate);
+    }
+
+    pub fn set_shape(&mut self, shape: LfoShape) {
+        self.lfo.set_shape(shape);
+    }
+
+    /// Set the delay at the middle of the sweep and how far it swings either way, in seconds.
+    pub fn set_delay(&mut self, center: T, depth: T) {
+        self.center = center * self.sample_rate;
+        self.depth = depth * self.sample_rate;
+    }
+
+    /// From only the dry signal at 0 to only the swept one at 1.
+    pub fn set_mix(&mut self, mix: T) {
+        self.mix = mix.clamp(T::ZERO, T::ONE);
+    }
+
+    pub fn reset(&mut self) {
+        self.delay.reset();
+        self.lfo.reset();
+    }
+
+    #[inline]
+    pub fn process(&mut self, input: T) -> T {
+        let delay = self.center + self.depth * self.lfo.next_sample();
+        let wet = self.delay.process(input, delay);
+        input + (wet - input) * self.mix
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: f32 = 48000.0;
+
+    #[test]
+    fn without_depth_the_wet_signal_is_a_plain_delay() {
+        let mut chorus = Chorus::new(SAMPLE_RATE, 0.01);
+        chorus.set_delay(0.001, 0.0);
+        chorus.set_mix(1.0);
+
+        let output: Vec<f32> = (0..200).map(|n| chorus.process(n as f32)).collect();
+        for (n, &sample) in output.iter().enumerate().skip(48) {
+            assert!((sample - (n - 48) as f32).abs() < 1e-3, "{n}: {sample}");
+        }
+    }
+
+    #[test]
+    fn full_mix_bends_the_pitch() {
+        let mut chorus = Chorus::new(SAMPLE_RATE, 0.01);
+        chorus.set_rate(5.0);
+        chorus.set_delay(0.002, 0.001);
+        chorus.set_mix(1.0);
+
+        // The time between rising zero crossings of a 1 kHz sine
+        let crossings: Vec<f32> = (0..SAMPLE_RATE as usize)
+            .map(|n| {
+                chorus.process((std::f32::consts::TAU * 1000.0 * n as f32 / SAMPLE_RATE).sin())
+            })
+            .collect::<Vec<_>>()
+            .windows(2)
+            .enumerate()
+            .skip(1000)
+            .filter(|(_, pair)| pair[0] < 0.0 && pair[1] >= 0.0)
+            .map(|(i, pair)| i as f32 + pair[0] / (pair[0] - pair[1]))
+            .collect();
+        let periods: Vec<f32> = crossings.windows(2).map(|pair| pair[1] - pair[0]).collect();
+        let longest = periods.iter().cloned().fold(f32::MIN, f32::max);
+        let shortest = periods.iter().cloned().fold(f32::MAX, f32::min);
+
+        // A 1 ms swing at 5 Hz moves the pitch by up to 2π · 5 · 0.001, about 3% either way
+        assert!(longest / shortest > 1.05, "{longest} / {shortest}");
+    }
+}
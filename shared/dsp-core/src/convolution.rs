@@ -0,0 +1,215 @@
+//! Uniformly partitioned FFT convolution for long impulse responses like reverbs.
+//!
+//! The impulse response is cut into partitions of one block each and transformed up front.
+//! Every block of input is transformed once and multiplied with each partition against a
+//! history of earlier input spectra, so the cost per sample grows with the response's length
+//! divided by the block size rather than with its length. The output lags the input by one
+//! block, smaller blocks lower the latency at the cost of more work per sample.
+//!
+//! Like the rest of the FFT code everything allocates in the constructor, so build convolvers
+//! off the audio thread.
+
+use crate::fft::{Complex32, Fft};
+
+#[derive(Clone)]
+pub struct Convolver {
+    fft: Fft,
+    block_size: usize,
+    /// The impulse response's length in samples.
+    len: usize,
+
+    /// The transformed partitions, the first one holding the start of the response.
+    partitions: Vec<Vec<Complex32>>,
+    /// The spectra of recent input blocks, used as a ring buffer with the newest at `head`.
+    history: Vec<Vec<Complex32>>,
+    head: usize,
+
+    /// The previous and the current block of input.
+    input: Vec<f32>,
+    /// The finished block being played back.
+    output: Vec<f32>,
+    /// The position within the current block.
+    pos: usize,
+
+    frame: Vec<f32>,
+    accumulator: Vec<Complex32>,
+}
+
+impl Convolver {
+    /// Prepare to convolve with `impulse_response` in blocks of `block_size` samples, which
+    /// must be a power of two for the FFT to be fast. An empty response outputs silence.
+    pub fn new(impulse_response: &[f32], block_size: usize) -> Self {
+        assert!(block_size > 0);
+        let mut fft = Fft::new(block_size * 2);
+        let mut frame = vec![0.0; block_size * 2];
+
+        let partitions: Vec<_> = impulse_response
+            .chunks(block_size)
+            .map(|chunk| {
+                frame.fill(0.0);
+                frame[..chunk.len()].copy_from_slice(chunk);
+                let mut spectrum = fft.make_spectrum();
+                fft.forward(&mut frame, &mut spectrum);
+                spectrum
+            })
+            .collect();
+
+        Self {
+            history: vec![fft.make_spectrum(); partitions.len().max(1)],
+            accumulator: fft.make_spectrum(),
+            fft,
+            block_size,
+            len: impulse_response.len(),
+
+            partitions,
+            head: 0,
+
+            input: vec![0.0; block_size * 2],
+            output: vec![0.0; block_size],
+            pos: 0,
+
+            frame,
+        }
+    }
+
+    /// How far the output lags behind the input, in samples.
+    pub fn latency(&self) -> usize {
+        self.block_size
+    }
+
+    /// The impulse response's length in samples. After the input falls silent the output rings
+    /// on for this long, plus the latency.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn reset(&mut self) {
+        for spectrum in &mut self.history {
+            spectrum.fill(Complex32::ZERO);
+        }
+        self.input.fill(0.0);
+        self.output.fill(0.0);
+        self.pos = 0;
+    }
+
+    /// Process one sample, convolving a whole block whenever one has arrived.
+    #[inline]
+    pub fn process(&mut self, input: f32) -> f32 {
+        self.input[self.block_size + self.pos] = input;
+        let output = self.output[self.pos];
+
+        self.pos += 1;
+        if self.pos == self.block_size {
+            self.pos = 0;
+            self.convolve_block();
+        }
+
+        output
+    }
+
+    pub fn process_block(&mut self, block: &mut [f32]) {
+        for sample in block {
+            *sample = self.process(*sample);
+        }
+    }
+
+    /// Overlap-save: transform the last two blocks of input, multiply and accumulate against
+    /// every partition, and keep the second half of the inverse transform.
+    fn convolve_block(&mut self) {
+        let num_slots = self.history.len();
+        self.head = (self.head + 1) % num_slots;
+
+        self.frame.copy_from_slice(&self.input);
+        self.fft
+            .forward(&mut self.frame, &mut self.history[self.head]);
+        self.input.copy_within(self.block_size.., 0);
+
+        self.accumulator.fill(Complex32::ZERO);
+        for (age, partition) in self.partitions.iter().enumerate() {
+            let spectrum = &self.history[(self.head + num_slots - age) % num_slots];
+            for ((accumulator, x), h) in self.accumulator.iter_mut().zip(spectrum).zip(partition) {
+                *accumulator += *x * *h;
+            }
+        }
+
+        self.fft.inverse(&mut self.accumulator, &mut self.frame);
+        let scale = 1.0 / self.fft.size() as f32;
+        for (output, sample) in self.output.iter_mut().zip(&self.frame[self.block_size..]) {
+            *output = sample * scale;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLOCK_SIZE: usize = 64;
+
+    fn test_signal(len: usize) -> Vec<f32> {
+        (0..len)
+            .map(|n| (n as f32 * 0.07).sin() * 0.6 + (n as f32 * 0.91).cos() * 0.3)
+            .collect()
+    }
+
+    /// Convolve with an impulse response that is silent except for `gain` at `delay`, spread
+    /// over several partitions, and check the output is the input delayed by that much plus the
+    /// latency.
+    fn assert_delays_by(delay: usize, gain: f32) {
+        let mut impulse_response = vec![0.0; delay + 3 * BLOCK_SIZE + 5];
+        impulse_response[delay] = gain;
+        let mut convolver = Convolver::new(&impulse_response, BLOCK_SIZE);
+        let total_delay = convolver.latency() + delay;
+
+        let input = test_signal(total_delay + 10 * BLOCK_SIZE);
+        let mut output = input.clone();
+        // Blocks that don't line up with the partitions
+        for block in output.chunks_mut(37) {
+            convolver.process_block(block);
+        }
+
+        for (n, &sample) in output.iter().enumerate() {
+            let expected = match n.checked_sub(total_delay) {
+                Some(n) => input[n] * gain,
+                None => 0.0,
+            };
+            assert!(
+                (sample - expected).abs() < 1e-5,
+                "{delay}, {n}: {sample} vs {expected}"
+            );
+        }
+    }
+
+    #[test]
+    fn a_unit_impulse_passes_the_input_through() {
+        assert_delays_by(0, 1.0);
+    }
+
+    #[test]
+    fn delayed_impulses_delay_the_input() {
+        // Within the first partition, on and just past partition boundaries, and far into the
+        // response
+        for delay in [
+            5,
+            BLOCK_SIZE - 1,
+            BLOCK_SIZE,
+            BLOCK_SIZE + 1,
+            2 * BLOCK_SIZE + 17,
+        ] {
+            assert_delays_by(delay, 0.5);
+        }
+    }
+
+    #[test]
+    fn empty_responses_are_silent() {
+        let mut convolver = Convolver::new(&[], BLOCK_SIZE);
+        assert!(convolver.is_empty());
+        let mut block = test_signal(4 * BLOCK_SIZE);
+        convolver.process_block(&mut block);
+        assert!(block.iter().all(|&sample| sample == 0.0));
+    }
+}
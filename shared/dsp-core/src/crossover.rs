@@ -0,0 +1,322 @@
+//! Linkwitz-Riley crossovers for splitting a signal into frequency bands that sum back to the
+//! original magnitude response.
+
+#[cfg(not(feature = "std"))]
+use alloc::{vec, vec::Vec};
+
+use crate::Sample;
+
+/// A 12 dB/octave Butterworth section using the trapezoidal state variable topology, which puts
+/// out its low pass, high pass and all pass responses at once.
+#[derive(Debug, Clone)]
+struct Butterworth<T: Sample> {
+    a1: T,
+    a2: T,
+    a3: T,
+    ic1: T,
+    ic2: T,
+}
+
+/// `1 / Q` for a Butterworth response.
+const K: f32 = core::f32::consts::SQRT_2;
+
+impl<T: Sample> Butterworth<T> {
+    fn new(sample_rate: T, frequency: T) -> Self {
+        let mut section = Self {
+            a1: T::ZERO,
+            a2: T::ZERO,
+            a3: T::ZERO,
+            ic1: T::ZERO,
+            ic2: T::ZERO,
+        };
+        section.set_frequency(sample_rate, frequency);
+        section
+    }
+
+    fn set_frequency(&mut self, sample_rate: T, frequency: T) {
+        let frequency = frequency.clamp(T::from_f32(10.0), sample_rate * T::from_f32(0.49));
+        let g = (T::PI * frequency / sample_rate).tan();
+        self.a1 = T::ONE / (T::ONE + g * (g + T::from_f32(K)));
+        self.a2 = g * self.a1;
+        self.a3 = g * self.a2;
+    }
+
+    fn reset(&mut self) {
+        self.ic1 = T::ZERO;
+        self.ic2 = T::ZERO;
+    }
+
+    /// Returns the band pass and low pass outputs, the other responses are derived from these.
+    #[inline]
+    fn tick(&mut self, input: T) -> (T, T) {
+        let v3 = input - self.ic2;
+        let v1 = self.a1 * self.ic1 + self.a2 * v3;
+        let v2 = self.ic2 + self.a2 * self.ic1 + self.a3 * v3;
+        self.ic1 = T::from_f32(2.0) * v1 - self.ic1;
+        self.ic2 = T::from_f32(2.0) * v2 - self.ic2;
+        (v1, v2)
+    }
+
+    /// The low pass and high pass outputs.
+    #[inline]
+    fn split(&mut self, input: T) -> (T, T) {
+        let (band, low) = self.tick(input);
+        (low, input - T::from_f32(K) * band - low)
+    }
+
+    /// The all pass output, which matches the phase of a Linkwitz-Riley crossover at the same
+    /// frequency.
+    #[inline]
+    fn all_pass(&mut self, input: T) -> T {
+        let (band, _) = self.tick(input);
+        input - T::from_f32(2.0 * K) * band
+    }
+}
+
+/// A 24 dB/octave Linkwitz-Riley crossover. Both outputs are 6 dB down at the crossover
+/// frequency and in phase with each other, so they sum to an all pass response.
+#[derive(Debug, Clone)]
+pub struct LinkwitzRiley<T: Sample = f32> {
+    sample_rate: T,
+    frequency: T,
+    /// The first stage is shared since both outputs start from the same input.
+    first: Butterworth<T>,
+    low: Butterworth<T>,
+    high: Butterworth<T>,
+}
+
+impl<T: Sample> LinkwitzRiley<T> {
+    pub fn new(sample_rate: T, frequency: T) -> Self {
+        Self {
+            sample_rate,
+            frequency,
+            first: Butterworth::new(sample_rate, frequency),
+            low: Butterworth::new(sample_rate, frequency),
+            high: Butterworth::new(sample_rate, frequency),
+        }
+    }
+
+    /// Set the crossover frequency in Hz, kept below Nyquist.
+    pub fn set_frequency(&mut self, frequency: T) {
+        self.frequency = frequency;
+        for section in [&mut self.first, &mut self.low, &mut self.high] {
+            section.set_frequency(self.sample_rate, frequency);
+        }
+    }
+
+    pub fn frequency(&self) -> T {
+        self.frequency
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: T) {
+        self.sample_rate = sample_rate;
+        self.set_frequency(self.frequency);
+    }
+
+    pub fn reset(&mut self) {
+        self.first.reset();
+        self.low.reset();
+        self.high.reset();
+    }
+
+    /// Split `input` into its low and high bands.
+    #[inline]
+    pub fn process(&mut self, input: T) -> (T, T) {
+        let (low, high) = self.first.split(input);
+        (self.low.split(low).0, self.high.split(high).1)
+    }
+}
+
+/// Splits a signal into bands at a list of rising crossover frequencies. Each band below the
+/// top one goes through all pass filters matching the crossovers above it, so every band ends up
+/// with the same phase and the bands sum back to a flat magnitude response.
+#[derive(Debug, Clone)]
+pub struct BandSplitter<T: Sample = f32> {
+    sample_rate: T,
+    crossovers: Vec<LinkwitzRiley<T>>,
+    /// `compensation[band][i]` matches the phase of crossover `band + 1 + i`.
+    compensation: Vec<Vec<Butterworth<T>>>,
+}
+
+impl<T: Sample> BandSplitter<T> {
+    /// Create a splitter with one more band than there are `frequencies`, which should be in
+    /// rising order.
+    pub fn new(sample_rate: T, frequencies: &[T]) -> Self {
+        let crossovers = frequencies
+            .iter()
+            .map(|&frequency| LinkwitzRiley::new(sample_rate, frequency))
+            .collect();
+        let compensation = (0..frequencies.len())
+            .map(|band| {
+                frequencies[band + 1..]
+                    .iter()
+                    .map(|&frequency| Butterworth::new(sample_rate, frequency))
+                    .collect()
+            })
+            .collect();
+
+        Self {
+            sample_rate,
+            crossovers,
+            compensation,
+        }
+    }
+
+    pub fn num_bands(&self) -> usize {
+        self.crossovers.len() + 1
+    }
+
+    /// Move crossover `index`. Keeping the frequencies in rising order is up to the caller,
+    /// the sum stays flat either way but the bands stop meaning much.
+    pub fn set_frequency(&mut self, index: usize, frequency: T) {
+        self.crossovers[index].set_frequency(frequency);
+        for (band, filters) in self.compensation.iter_mut().enumerate().take(index) {
+            filters[index - band - 1].set_frequency(self.sample_rate, frequency);
+        }
+    }
+
+    pub fn frequency(&self, index: usize) -> T {
+        self.crossovers[index].frequency()
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: T) {
+        self.sample_rate = sample_rate;
+        for crossover in &mut self.crossovers {
+            crossover.set_sample_rate(sample_rate);
+        }
+        for (band, filters) in self.compensation.iter_mut().enumerate() {
+            for (i, filter) in filters.iter_mut().enumerate() {
+                filter.set_frequency(sample_rate, self.crossovers[band + 1 + i].frequency());
+            }
+        }
+    }
+
+    pub fn reset(&mut self) {
+        for crossover in &mut self.crossovers {
+            crossover.reset();
+        }
+        for filter in self.compensation.iter_mut().flatten() {
+            filter.reset();
+        }
+    }
+
+    /// Split `input` into [`num_bands()`][Self::num_bands] bands, lowest first.
+    #[inline]
+    pub fn process(&mut self, input: T, bands: &mut [T]) {
+        debug_assert_eq!(bands.len(), self.num_bands());
+
+        let mut rest = input;
+        for (band, (crossover, filters)) in self
+            .crossovers
+            .iter_mut()
+            .zip(&mut self.compensation)
+            .enumerate()
+        {
+            let (low, high) = crossover.process(rest);
+            bands[band] = filters
+                .iter_mut()
+                .fold(low, |sample, filter| filter.all_pass(sample));
+            rest = high;
+        }
+        bands[self.crossovers.len()] = rest;
+    }
+
+    /// Split a block of `input` into one output slice per band.
+    pub fn process_block(&mut self, input: &[T], bands: &mut [&mut [T]]) {
+        let mut frame = vec![T::ZERO; self.num_bands()];
+        for (i, &sample) in input.iter().enumerate() {
+            self.process(sample, &mut frame);
+            for (band, &value) in bands.iter_mut().zip(&frame) {
+                band[i] = value;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: f64 = 48000.0;
+    const LENGTH: usize = 1 << 15;
+
+    /// The magnitude response of an impulse response at `frequency`, in dB.
+    fn magnitude_db(impulse_response: &[f64], frequency: f64) -> f64 {
+        let omega = core::f64::consts::TAU * frequency / SAMPLE_RATE;
+        let (re, im) = impulse_response
+            .iter()
+            .enumerate()
+            .fold((0.0, 0.0), |(re, im), (n, &x)| {
+                let phase = omega * n as f64;
+                (re + x * phase.cos(), im - x * phase.sin())
+            });
+        10.0 * (re * re + im * im).log10()
+    }
+
+    fn probe_frequencies() -> impl Iterator<Item = f64> {
+        // Third octaves from 20 Hz to 20 kHz
+        (0..31).map(|i| 20.0 * 2f64.powf(i as f64 / 3.0))
+    }
+
+    #[test]
+    fn crossover_outputs_are_6_db_down_at_the_crossover() {
+        let mut crossover = LinkwitzRiley::new(SAMPLE_RATE, 1000.0);
+        let (low, high): (Vec<f64>, Vec<f64>) = (0..LENGTH)
+            .map(|i| crossover.process(if i == 0 { 1.0 } else { 0.0 }))
+            .unzip();
+
+        for response in [&low, &high] {
+            let db = magnitude_db(response, 1000.0);
+            assert!((db + 6.02).abs() < 0.05, "{db}");
+        }
+        assert!(magnitude_db(&low, 8000.0) < -70.0);
+        assert!(magnitude_db(&high, 125.0) < -70.0);
+    }
+
+    #[test]
+    fn resummed_bands_are_flat() {
+        for frequencies in [
+            &[1000.0][..],
+            &[200.0, 2000.0],
+            &[120.0, 1000.0, 6000.0],
+            &[80.0, 400.0, 2500.0, 10000.0],
+        ] {
+            let mut splitter = BandSplitter::new(SAMPLE_RATE, frequencies);
+            let mut bands = vec![0.0; splitter.num_bands()];
+            let sum: Vec<f64> = (0..LENGTH)
+                .map(|i| {
+                    splitter.process(if i == 0 { 1.0 } else { 0.0 }, &mut bands);
+                    bands.iter().sum()
+                })
+                .collect();
+
+            for frequency in probe_frequencies() {
+                let db = magnitude_db(&sum, frequency);
+                assert!(
+                    db.abs() < 0.01,
+                    "{db} dB at {frequency} Hz with crossovers at {frequencies:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn moving_a_crossover_keeps_the_sum_flat() {
+        let mut splitter = BandSplitter::new(SAMPLE_RATE, &[100.0, 1000.0, 5000.0]);
+        splitter.set_frequency(1, 300.0);
+        splitter.set_frequency(2, 12000.0);
+
+        let mut bands = [0.0; 4];
+        let sum: Vec<f64> = (0..LENGTH)
+            .map(|i| {
+                splitter.process(if i == 0 { 1.0 } else { 0.0 }, &mut bands);
+                bands.iter().sum()
+            })
+            .collect();
+
+        for frequency in probe_frequencies() {
+            let db = magnitude_db(&sum, frequency);
+            assert!(db.abs() < 0.01, "{db} dB at {frequency} Hz");
+        }
+    }
+}
@@ -0,0 +1,300 @@
+#[cfg(not(feature = "std"))]
+use alloc::{vec, vec::Vec};
+
+use crate::Sample;
+
+/// A mono delay line with fractional, modulatable read positions, for choruses, Doppler effects,
+/// and echoes. Writing and reading are separate so one line can feed several taps and the
+/// output can be fed back into the input:
+///
+/// ```ignore
+/// let echo = delay.read(delay_samples);
+/// delay.write(input + echo * feedback);
+/// ```
+///
+/// Reads use 4-point Hermite interpolation, which keeps the top octave clean while the delay
+/// time sweeps.
+#[derive(Debug, Clone)]
+pub struct DelayLine<T: Sample = f32> {
+    /// A ring buffer with room for the longest delay plus the interpolation's neighbors.
+    buffer: Vec<T>,
+    /// Where the next sample is written.
+    write_pos: usize,
+    max_delay: usize,
+}
+
+impl<T: Sample> DelayLine<T> {
+    /// Allocates, so call this from `initialize()`. `max_delay` is the longest delay in samples.
+    pub fn new(max_delay: usize) -> Self {
+        let max_delay = max_delay.max(1);
+        Self {
+            buffer: vec![T::ZERO; max_delay + 3],
+            write_pos: 0,
+            max_delay,
+        }
+    }
+
+    /// The number of samples in `seconds`, rounded up, for sizing the line.
+    pub fn samples_for(sample_rate: T, seconds: T) -> usize {
+        let samples = seconds * sample_rate;
+        -(-samples).floor().to_f32() as usize
+    }
+
+    pub fn max_delay(&self) -> usize {
+        self.max_delay
+    }
+
+    /// Clear the delayed audio.
+    pub fn reset(&mut self) {
+        self.buffer.fill(T::ZERO);
+        self.write_pos = 0;
+    }
+
+    /// Push the next sample.
+    #[inline]
+    pub fn write(&mut self, input: T) {
+        self.buffer[self.write_pos] = input;
+        self.write_pos = (self.write_pos + 1) % self.buffer.len();
+    }
+
+    /// The sample written `delay` samples before the next write, so reading before writing
+    /// delays by exactly `delay`. The delay is kept between one sample and the maximum delay.
+    #[inline]
+    pub fn read(&self, delay: T) -> T {
+        read_interpolated(&self.buffer, self.write_pos, self.max_delay, delay)
+    }
+
+    /// Delay `input` by `delay` samples.
+    #[inline]
+    pub fn process(&mut self, input: T, delay: T) -> T {
+        let output = self.read(delay);
+        self.write(input);
+        output
+    }
+}
+
+/// A [`DelayLine`] with its buffer stored inline instead of on the heap, for short delays that
+/// are embedded many times over, like the diffusers in every voice of a synth. `N` is the
+/// buffer's length, which leaves room for delays of up to `N - 3` samples.
+#[derive(Debug, Clone)]
+pub struct FixedDelayLine<T: Sample, const N: usize> {
+    buffer: [T; N],
+    write_pos: usize,
+}
+
+impl<T: Sample, const N: usize> Default for FixedDelayLine<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Sample, const N: usize> FixedDelayLine<T, N> {
+    /// The longest delay in samples.
+    pub const MAX_DELAY: usize = {
+        assert!(
+            N >= 4,
+            "a fixed delay line needs room for at least one sample of delay"
+        );
+        N - 3
+    };
+
+    /// Doesn't allocate, so this can be used anywhere.
+    pub fn new() -> Self {
+        Self {
+            buffer: [T::ZERO; N],
+            write_pos: 0,
+        }
+    }
+
+    pub fn max_delay(&self) -> usize {
+        Self::MAX_DELAY
+    }
+
+    /// Clear the delayed audio.
+    pub fn reset(&mut self) {
+        self.buffer.fill(T::ZERO);
+        self.write_pos = 0;
+    }
+
+    /// Push the next sample.
+    #[inline]
+    pub fn write(&mut self, input: T) {
+        self.buffer[self.write_pos] = input;
+        self.write_pos = (self.write_pos + 1) % N;
+    }
+
+    /// Works like [`DelayLine::read()`].
+    #[inline]
+    pub fn read(&self, delay: T) -> T {
+        read_interpolated(&self.buffer, self.write_pos, Self::MAX_DELAY, delay)
+    }
+
+    /// Delay `input` by `delay` samples.
+    #[inline]
+    pub fn process(&mut self, input: T, delay: T) -> T {
+        let output = self.read(delay);
+        self.write(input);
+        output
+    }
+}
+
+/// A Schroeder allpass filter over a fixed delay of `N` samples, stored inline. Passes every
+/// frequency at the same level while smearing the phase, which is what reverbs and string
+/// ensembles chain them for to diffuse a signal.
+#[derive(Debug, Clone)]
+pub struct Allpass<T: Sample, const N: usize> {
+    buffer: [T; N],
+    pos: usize,
+    gain: T,
+}
+
+impl<T: Sample, const N: usize> Default for Allpass<T, N> {
+    fn default() -> Self {
+        Self::new(T::HALF)
+    }
+}
+
+impl<T: Sample, const N: usize> Allpass<T, N> {
+    /// The delay in samples.
+    pub const DELAY: usize = {
+        assert!(N >= 1, "an allpass needs at least one sample of delay");
+        N
+    };
+
+    /// `gain` sets how much of the signal circulates, and how long the smearing lasts. Kept
+    /// below one so the filter stays stable.
+    pub fn new(gain: T) -> Self {
+        let mut allpass = Self {
+            buffer: [T::ZERO; N],
+            pos: 0,
+            gain: T::ZERO,
+        };
+        allpass.set_gain(gain);
+        allpass
+    }
+
+    pub fn set_gain(&mut self, gain: T) {
+        let limit = T::from_f32(0.99);
+        self.gain = gain.clamp(-limit, limit);
+    }
+
+    pub fn reset(&mut self) {
+        self.buffer.fill(T::ZERO);
+        self.pos = 0;
+    }
+
+    #[inline]
+    pub fn process(&mut self, input: T) -> T {
+        let delayed = self.buffer[self.pos];
+        let feedback = input - self.gain * delayed;
+        self.buffer[self.pos] = feedback;
+        self.pos = (self.pos + 1) % Self::DELAY;
+
+        delayed + self.gain * feedback
+    }
+}
+
+/// Read from a ring buffer `delay` samples before `write_pos`. Uses 4-point Hermite
+/// interpolation, the buffer needs three samples more than `max_delay`.
+#[inline]
+fn read_interpolated<T: Sample>(buffer: &[T], write_pos: usize, max_delay: usize, delay: T) -> T {
+    let delay = delay.clamp(T::ONE, T::from_f32(max_delay as f32));
+    let whole = delay.floor();
+    let frac = delay - whole;
+    let whole = whole.to_f32() as usize;
+
+    let len = buffer.len();
+    let at = |delay: usize| buffer[(write_pos + len - delay) % len];
+
+    // Newer to older. The newest sample is one sample back, there's nothing newer to
+    // interpolate from so it's repeated.
+    let y0 = at(whole);
+    let newer = if whole > 1 { at(whole - 1) } else { y0 };
+    let y1 = at(whole + 1);
+    let older = at(whole + 2);
+
+    let c1 = T::HALF * (y1 - newer);
+    let c2 = newer - T::from_f32(2.5) * y0 + T::from_f32(2.0) * y1 - T::HALF * older;
+    let c3 = T::HALF * (older - newer) + T::from_f32(1.5) * (y0 - y1);
+    ((c3 * frac + c2) * frac + c1) * frac + y0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn whole_sample_delays_are_exact() {
+        let mut delay = DelayLine::<f32>::new(16);
+        let output: Vec<f32> = (0..32).map(|i| delay.process(i as f32, 5.0)).collect();
+        for (i, &sample) in output.iter().enumerate() {
+            let expected = if i < 5 { 0.0 } else { (i - 5) as f32 };
+            assert_eq!(sample, expected, "{i}");
+        }
+    }
+
+    #[test]
+    fn fractional_delays_interpolate_smooth_signals() {
+        let mut delay = DelayLine::<f64>::new(64);
+        let signal = |n: f64| (n * 0.05).sin();
+        for n in 0..100 {
+            delay.write(signal(n as f64));
+        }
+
+        // The last write was sample 99, a delay of one reads it back
+        for delay_samples in [1.0, 2.25, 10.5, 33.75] {
+            let expected = signal(100.0 - delay_samples);
+            let read = delay.read(delay_samples);
+            assert!(
+                (read - expected).abs() < 1e-5,
+                "{delay_samples}: {read} vs {expected}"
+            );
+        }
+    }
+
+    #[test]
+    fn delays_are_kept_in_range() {
+        let mut delay = DelayLine::<f32>::new(8);
+        for i in 0..20 {
+            delay.write(i as f32);
+        }
+        assert_eq!(delay.read(0.0), 19.0);
+        assert_eq!(delay.read(100.0), 12.0);
+    }
+
+    #[test]
+    fn fixed_delay_lines_match_heap_delay_lines() {
+        let mut heap = DelayLine::<f32>::new(13);
+        let mut fixed = FixedDelayLine::<f32, 16>::new();
+        assert_eq!(fixed.max_delay(), heap.max_delay());
+
+        for n in 0..200 {
+            let input = (n as f32 * 0.1).sin();
+            let delay = 1.0 + (n as f32 * 0.03).sin().abs() * 14.0;
+            assert_eq!(
+                fixed.process(input, delay),
+                heap.process(input, delay),
+                "{n}"
+            );
+        }
+    }
+
+    #[test]
+    fn allpasses_keep_the_signals_energy() {
+        let mut allpass = Allpass::<f64, 7>::new(0.6);
+        let energy: f64 = (0..2000)
+            .map(|n| allpass.process(if n == 0 { 1.0 } else { 0.0 }))
+            .map(|sample| sample * sample)
+            .sum();
+        assert!((energy - 1.0).abs() < 1e-9, "{energy}");
+
+        // The first echo comes back after the full delay
+        allpass.reset();
+        let response: Vec<f64> = (0..8)
+            .map(|n| allpass.process(if n == 0 { 1.0 } else { 0.0 }))
+            .collect();
+        assert!((response[0] - 0.6).abs() < 1e-12);
+        assert!(response[1..7].iter().all(|&sample| sample == 0.0));
+        assert!((response[7] - 0.64).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,298 @@
+//! Spectral noise reduction. A [`NoiseProfile`] learns the average power of a stretch of noise
+//! in every frequency bin, and a [`Denoiser`] then turns down the bins of the running signal
+//! that don't rise far enough above it, with a Wiener filter's gain of `1 - noise / power`. The
+//! gains rise instantly so transients get through, and fall smoothly so the remaining noise
+//! doesn't break up into chirps. The output is delayed by [`LATENCY`] samples, which plugins
+//! should report to the host.
+
+use crate::fft::{Complex32, Stft, Window};
+
+pub const FFT_SIZE: usize = 2048;
+
+/// Frames overlap by 75%, so a gain changing between frames is spread over four of them.
+const OVERLAP: usize = 4;
+const HOP: usize = FFT_SIZE / OVERLAP;
+pub const NUM_BINS: usize = FFT_SIZE / 2 + 1;
+
+/// How far the output lags behind the input, in samples.
+pub const LATENCY: usize = FFT_SIZE;
+
+/// The average noise power in each bin. Several [`Denoiser`]s can learn into and reduce with
+/// the same profile, e.g. one per channel.
+#[derive(Debug, Clone)]
+pub struct NoiseProfile {
+    /// Silent until a profile has been learned.
+    power: Vec<f32>,
+    /// The summed power of the frames seen while learning.
+    sum: Vec<f32>,
+    frames: u32,
+}
+
+impl Default for NoiseProfile {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl NoiseProfile {
+    /// Allocates, so call this from `initialize()`.
+    pub fn new() -> Self {
+        Self {
+            power: vec![0.0; NUM_BINS],
+            sum: vec![0.0; NUM_BINS],
+            frames: 0,
+        }
+    }
+
+    /// The learned power per bin, [`NUM_BINS`] values for persisting.
+    pub fn power(&self) -> &[f32] {
+        &self.power
+    }
+
+    /// Restore a persisted profile. Returns `false` and keeps the current profile if `power`
+    /// doesn't have [`NUM_BINS`] values.
+    pub fn set_power(&mut self, power: &[f32]) -> bool {
+        if power.len() != NUM_BINS {
+            return false;
+        }
+
+        self.power.copy_from_slice(power);
+        true
+    }
+
+    /// Whether nothing has been learned yet, in which case a denoiser passes everything.
+    pub fn is_empty(&self) -> bool {
+        self.power.iter().all(|&power| power == 0.0)
+    }
+
+    pub fn clear(&mut self) {
+        self.power.fill(0.0);
+    }
+
+    /// Start averaging a new profile. The current one stays in place until
+    /// [`finish_learning()`][Self::finish_learning()].
+    pub fn start_learning(&mut self) {
+        self.sum.fill(0.0);
+        self.frames = 0;
+    }
+
+    fn learn(&mut self, spectrum: &[Complex32]) {
+        for (sum, bin) in self.sum.iter_mut().zip(spectrum) {
+            *sum += bin.norm_sqr();
+        }
+        self.frames += 1;
+    }
+
+    /// Replace the profile with the average of the frames learned since
+    /// [`start_learning()`][Self::start_learning()]. Returns `false` if no frames were learned,
+    /// leaving the profile as it was.
+    pub fn finish_learning(&mut self) -> bool {
+        if self.frames == 0 {
+            return false;
+        }
+
+        let scale = 1.0 / self.frames as f32;
+        for (power, sum) in self.power.iter_mut().zip(&self.sum) {
+            *power = sum * scale;
+        }
+        true
+    }
+}
+
+/// Reduces the noise in one channel.
+#[derive(Clone)]
+pub struct Denoiser {
+    stft: Stft,
+    gains: Gains,
+}
+
+#[derive(Clone)]
+struct Gains {
+    gains: Vec<f32>,
+    /// The lowest gain, set by the reduction.
+    floor: f32,
+    /// How far above the noise's power a bin has to be to pass untouched.
+    threshold: f32,
+    /// How much of the way a falling gain moves per frame.
+    release: f32,
+    release_time: f32,
+    sample_rate: f32,
+}
+
+impl Denoiser {
+    /// Plans the FFTs and allocates every buffer, so call this from `initialize()`.
+    pub fn new(sample_rate: f32) -> Self {
+        let mut denoiser = Self {
+            stft: Stft::new(FFT_SIZE, OVERLAP, Window::Hann),
+            gains: Gains {
+                gains: vec![1.0; NUM_BINS],
+                floor: 0.0,
+                threshold: 1.0,
+                release: 1.0,
+                release_time: 0.0,
+                sample_rate,
+            },
+        };
+        denoiser.set_reduction(12.0);
+        denoiser.set_threshold(6.0);
+        denoiser.set_release(0.1);
+        denoiser
+    }
+
+    /// How far the noise is turned down at most, in decibels.
+    pub fn set_reduction(&mut self, db: f32) {
+        self.gains.floor = crate::dynamics::db_to_gain(-db.max(0.0));
+    }
+
+    /// How far above the learned noise a bin has to be before it passes untouched, in decibels.
+    /// Higher values catch noise that peaks above its average, at the cost of quiet details.
+    pub fn set_threshold(&mut self, db: f32) {
+        let gain = crate::dynamics::db_to_gain(db);
+        self.gains.threshold = gain * gain;
+    }
+
+    /// How long a bin takes to fade down after its signal drops into the noise, in seconds.
+    pub fn set_release(&mut self, seconds: f32) {
+        self.gains.release_time = seconds;
+        let frames = seconds * self.gains.sample_rate / HOP as f32;
+        self.gains.release = if frames > 0.0 {
+            1.0 - (-1.0 / frames).exp()
+        } else {
+            1.0
+        };
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.gains.sample_rate = sample_rate;
+        self.set_release(self.gains.release_time);
+    }
+
+    pub fn reset(&mut self) {
+        self.stft.reset();
+        self.gains.gains.fill(1.0);
+    }
+
+    /// Reduce the noise in `block` with `profile`, or while `learning` add the block to the
+    /// profile being learned and pass it through unchanged. Either way the output is delayed by
+    /// [`LATENCY`].
+    pub fn process_block(&mut self, block: &mut [f32], profile: &mut NoiseProfile, learning: bool) {
+        let gains = &mut self.gains;
+        self.stft.process_block(block, |spectrum| {
+            if learning {
+                profile.learn(spectrum);
+            } else if !profile.is_empty() {
+                gains.apply(spectrum, profile.power());
+            }
+        });
+    }
+}
+
+impl Gains {
+    fn apply(&mut self, spectrum: &mut [Complex32], noise: &[f32]) {
+        for ((bin, gain), &noise) in spectrum.iter_mut().zip(&mut self.gains).zip(noise) {
+            let power = bin.norm_sqr();
+            let target = if power > 0.0 {
+                (1.0 - self.threshold * noise / power).max(self.floor)
+            } else {
+                self.floor
+            };
+
+            if target > *gain {
+                *gain = target;
+            } else {
+                *gain += (target - *gain) * self.release;
+            }
+            *bin *= *gain;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::random::Xorshift32;
+
+    const SAMPLE_RATE: f32 = 48000.0;
+
+    fn noise(rng: &mut Xorshift32, len: usize, level: f32) -> Vec<f32> {
+        (0..len).map(|_| rng.next_bipolar() * level).collect()
+    }
+
+    fn rms(samples: &[f32]) -> f32 {
+        (samples.iter().map(|s| s * s).sum::<f32>() / samples.len() as f32).sqrt()
+    }
+
+    fn learned_profile(denoiser: &mut Denoiser, rng: &mut Xorshift32) -> NoiseProfile {
+        let mut profile = NoiseProfile::new();
+        profile.start_learning();
+        denoiser.process_block(&mut noise(rng, 48000, 0.1), &mut profile, true);
+        assert!(profile.finish_learning());
+        denoiser.reset();
+        profile
+    }
+
+    #[test]
+    fn without_a_profile_the_input_passes_through_delayed() {
+        let mut denoiser = Denoiser::new(SAMPLE_RATE);
+        let mut profile = NoiseProfile::new();
+        let input = noise(&mut Xorshift32::new(1), 8192, 0.5);
+        let mut output = input.clone();
+        denoiser.process_block(&mut output, &mut profile, false);
+
+        for (input, output) in input.iter().zip(&output[LATENCY..]) {
+            assert!((input - output).abs() < 1e-4, "{input} != {output}");
+        }
+    }
+
+    #[test]
+    fn learned_noise_is_turned_down_by_the_reduction() {
+        let mut rng = Xorshift32::new(2);
+        let mut denoiser = Denoiser::new(SAMPLE_RATE);
+        denoiser.set_reduction(20.0);
+        let mut profile = learned_profile(&mut denoiser, &mut rng);
+
+        let input = noise(&mut rng, 48000, 0.1);
+        let mut output = input.clone();
+        denoiser.process_block(&mut output, &mut profile, false);
+
+        // Measured once the gains have settled
+        let reduction = rms(&output[24000..]) / rms(&input[24000..]);
+        assert!(reduction < 0.15, "{reduction}");
+    }
+
+    #[test]
+    fn signals_above_the_noise_pass() {
+        let mut rng = Xorshift32::new(3);
+        let mut denoiser = Denoiser::new(SAMPLE_RATE);
+        denoiser.set_reduction(30.0);
+        let mut profile = learned_profile(&mut denoiser, &mut rng);
+
+        let sine: Vec<f32> = (0..48000)
+            .map(|i| (std::f32::consts::TAU * 1000.0 * i as f32 / SAMPLE_RATE).sin() * 0.5)
+            .collect();
+        let mut output: Vec<f32> = noise(&mut rng, 48000, 0.1)
+            .iter()
+            .zip(&sine)
+            .map(|(noise, sine)| noise + sine)
+            .collect();
+        denoiser.process_block(&mut output, &mut profile, false);
+
+        // What's left is mostly the sine, with the noise around it gone
+        let error: Vec<f32> = output[24000..]
+            .iter()
+            .zip(&sine[24000 - LATENCY..])
+            .map(|(output, sine)| output - sine)
+            .collect();
+        assert!(rms(&error) < 0.02, "{}", rms(&error));
+    }
+
+    #[test]
+    fn learning_without_frames_keeps_the_profile() {
+        let mut profile = NoiseProfile::new();
+        profile.set_power(&[1.0; NUM_BINS]);
+        profile.start_learning();
+        assert!(!profile.finish_learning());
+        assert_eq!(profile.power()[0], 1.0);
+        assert!(!profile.set_power(&[0.0; 3]));
+    }
+}
@@ -0,0 +1,389 @@
+//! Building blocks for dynamics processors: envelope followers, gain reduction smoothing, auto
+//! makeup gain, and decibel conversions.
+
+use crate::Sample;
+
+/// Convert decibels to a linear gain.
+pub fn db_to_gain<T: Sample>(db: T) -> T {
+    T::from_f32(10.0).powf(db / T::from_f32(20.0))
+}
+
+/// Convert a linear gain to decibels, with silence floored at -120 dB.
+pub fn gain_to_db<T: Sample>(gain: T) -> T {
+    T::from_f32(20.0) * gain.max(T::from_f32(1e-6)).ln() / T::from_f32(core::f32::consts::LN_10)
+}
+
+/// The coefficient of a one-pole smoother that covers about 63% of the way to its target in
+/// `time` seconds.
+fn one_pole_coefficient<T: Sample>(time: T, sample_rate: T) -> T {
+    let samples = time * sample_rate;
+    if samples > T::ZERO {
+        (-T::ONE / samples).exp()
+    } else {
+        T::ZERO
+    }
+}
+
+/// Follows the level of a signal, rising with the attack time and falling with the release
+/// time. Both are the time to cover about 63% of a step.
+#[derive(Debug, Clone)]
+pub struct EnvelopeFollower<T: Sample = f32> {
+    sample_rate: T,
+    attack_time: T,
+    release_time: T,
+    /// The one-pole coefficients for the attack and release times.
+    attack: T,
+    release: T,
+    envelope: T,
+}
+
+impl<T: Sample> EnvelopeFollower<T> {
+    pub fn new(sample_rate: T) -> Self {
+        let mut follower = Self {
+            sample_rate,
+            attack_time: T::ZERO,
+            release_time: T::ZERO,
+            attack: T::ZERO,
+            release: T::ZERO,
+            envelope: T::ZERO,
+        };
+        follower.set_attack(T::from_f32(0.001));
+        follower.set_release(T::from_f32(0.1));
+        follower
+    }
+
+    /// Set the attack time in seconds, zero follows rises instantly.
+    pub fn set_attack(&mut self, seconds: T) {
+        self.attack_time = seconds;
+        self.attack = one_pole_coefficient(seconds, self.sample_rate);
+    }
+
+    /// Set the release time in seconds, zero follows falls instantly.
+    pub fn set_release(&mut self, seconds: T) {
+        self.release_time = seconds;
+        self.release = one_pole_coefficient(seconds, self.sample_rate);
+    }
+
+    /// Keeps the attack and release times in seconds.
+    pub fn set_sample_rate(&mut self, sample_rate: T) {
+        self.sample_rate = sample_rate;
+        self.set_attack(self.attack_time);
+        self.set_release(self.release_time);
+    }
+
+    pub fn reset(&mut self) {
+        self.envelope = T::ZERO;
+    }
+
+    /// The current level.
+    pub fn value(&self) -> T {
+        self.envelope
+    }
+
+    /// Follow the peak level of `input`, returning the new envelope.
+    #[inline]
+    pub fn process(&mut self, input: T) -> T {
+        let level = input.abs();
+        let coefficient = if level > self.envelope {
+            self.attack
+        } else {
+            self.release
+        };
+        self.envelope = level + coefficient * (self.envelope - level);
+        self.envelope
+    }
+}
+
+/// Smooths a compressor's gain reduction in dB with a program-dependent release. A fast stage
+/// follows the gain reduction with the attack and fast release times, while a slow stage charges
+/// and discharges from it with the slow release time, and the larger of the two is used.
+///
+/// Short peaks barely charge the slow stage so they recover with the fast release, while
+/// sustained compression on dense material charges it and recovers slowly instead of pumping.
+#[derive(Debug, Clone)]
+pub struct DualRelease<T: Sample = f32> {
+    sample_rate: T,
+    attack_time: T,
+    fast_release_time: T,
+    slow_release_time: T,
+    attack: T,
+    fast_release: T,
+    slow_release: T,
+    fast: T,
+    slow: T,
+}
+
+impl<T: Sample> DualRelease<T> {
+    pub fn new(sample_rate: T) -> Self {
+        let mut release = Self {
+            sample_rate,
+            attack_time: T::ZERO,
+            fast_release_time: T::ZERO,
+            slow_release_time: T::ZERO,
+            attack: T::ZERO,
+            fast_release: T::ZERO,
+            slow_release: T::ZERO,
+            fast: T::ZERO,
+            slow: T::ZERO,
+        };
+        release.set_attack(T::from_f32(0.005));
+        release.set_fast_release(T::from_f32(0.05));
+        release.set_slow_release(T::from_f32(1.0));
+        release
+    }
+
+    /// Set the attack time in seconds, zero follows rises instantly.
+    pub fn set_attack(&mut self, seconds: T) {
+        self.attack_time = seconds;
+        self.attack = one_pole_coefficient(seconds, self.sample_rate);
+    }
+
+    /// Set the release time after short peaks in seconds.
+    pub fn set_fast_release(&mut self, seconds: T) {
+        self.fast_release_time = seconds;
+        self.fast_release = one_pole_coefficient(seconds, self.sample_rate);
+    }
+
+    /// Set the release time after sustained compression in seconds. This is also how long the
+    /// compression has to last before the release slows down.
+    pub fn set_slow_release(&mut self, seconds: T) {
+        self.slow_release_time = seconds;
+        self.slow_release = one_pole_coefficient(seconds, self.sample_rate);
+    }
+
+    /// Keeps the times in seconds.
+    pub fn set_sample_rate(&mut self, sample_rate: T) {
+        self.sample_rate = sample_rate;
+        self.set_attack(self.attack_time);
+        self.set_fast_release(self.fast_release_time);
+        self.set_slow_release(self.slow_release_time);
+    }
+
+    pub fn reset(&mut self) {
+        self.fast = T::ZERO;
+        self.slow = T::ZERO;
+    }
+
+    /// The current gain reduction in dB.
+    pub fn value(&self) -> T {
+        self.fast.max(self.slow)
+    }
+
+    /// Smooth the target gain reduction in dB, returning the gain reduction to apply.
+    #[inline]
+    pub fn process(&mut self, reduction_db: T) -> T {
+        let coefficient = if reduction_db > self.fast {
+            self.attack
+        } else {
+            self.fast_release
+        };
+        self.fast = reduction_db + coefficient * (self.fast - reduction_db);
+        self.slow = self.fast + self.slow_release * (self.slow - self.fast);
+        self.value()
+    }
+}
+
+/// Measures how much quieter a compressor made the signal and works out the gain that brings it
+/// back to the input's level. The input and output levels are RMS over a sliding window, so the
+/// makeup follows the program's overall level rather than individual peaks.
+#[derive(Debug, Clone)]
+pub struct AutoMakeup<T: Sample = f32> {
+    sample_rate: T,
+    window_time: T,
+    /// The one-pole coefficient for the RMS window.
+    coefficient: T,
+    max_gain: T,
+    input_power: T,
+    output_power: T,
+    gain: T,
+}
+
+impl<T: Sample> AutoMakeup<T> {
+    /// Below this mean square, about -80 dBFS, the gain is held so silence isn't boosted.
+    const SILENCE: f32 = 1e-8;
+
+    /// `window` is the RMS window in seconds.
+    pub fn new(sample_rate: T, window: T) -> Self {
+        let mut makeup = Self {
+            sample_rate,
+            window_time: window,
+            coefficient: T::ZERO,
+            max_gain: db_to_gain(T::from_f32(24.0)),
+            input_power: T::ZERO,
+            output_power: T::ZERO,
+            gain: T::ONE,
+        };
+        makeup.set_window(window);
+        makeup
+    }
+
+    /// Set the RMS window in seconds. Longer windows react more slowly but don't wobble with
+    /// low frequencies.
+    pub fn set_window(&mut self, seconds: T) {
+        self.window_time = seconds;
+        self.coefficient = one_pole_coefficient(seconds, self.sample_rate);
+    }
+
+    /// Limit the makeup gain, 24 dB by default.
+    pub fn set_max_gain_db(&mut self, db: T) {
+        self.max_gain = db_to_gain(db);
+    }
+
+    /// Keeps the window in seconds.
+    pub fn set_sample_rate(&mut self, sample_rate: T) {
+        self.sample_rate = sample_rate;
+        self.set_window(self.window_time);
+    }
+
+    pub fn reset(&mut self) {
+        self.input_power = T::ZERO;
+        self.output_power = T::ZERO;
+        self.gain = T::ONE;
+    }
+
+    /// The current makeup gain.
+    pub fn gain(&self) -> T {
+        self.gain
+    }
+
+    /// Measure a sample before and after compression, returning the makeup gain for the
+    /// compressed sample. For stereo, pass the channels' sum or the louder channel.
+    #[inline]
+    pub fn process(&mut self, input: T, compressed: T) -> T {
+        self.input_power = input * input + self.coefficient * (self.input_power - input * input);
+        self.output_power = compressed * compressed
+            + self.coefficient * (self.output_power - compressed * compressed);
+
+        let silence = T::from_f32(Self::SILENCE);
+        if self.input_power > silence && self.output_power > silence * silence {
+            // A compressor only turns the signal down
+            self.gain = (self.input_power / self.output_power)
+                .sqrt()
+                .clamp(T::ONE, self.max_gain);
+        }
+        self.gain
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: f64 = 48000.0;
+
+    fn samples(seconds: f64) -> usize {
+        (seconds * SAMPLE_RATE) as usize
+    }
+
+    /// How long the gain reduction takes to recover to `db` after `held_for` seconds at 10 dB.
+    fn recovery_time(held_for: f64, db: f64) -> f64 {
+        let mut release = DualRelease::new(SAMPLE_RATE);
+        release.set_attack(0.0);
+        for _ in 0..samples(held_for) {
+            release.process(10.0);
+        }
+
+        let recovered = (0..).find(|_| release.process(0.0) < db).unwrap();
+        recovered as f64 / SAMPLE_RATE
+    }
+
+    #[test]
+    fn attack_covers_63_percent_of_a_step_in_the_attack_time() {
+        let mut release = DualRelease::new(SAMPLE_RATE);
+        release.set_attack(0.01);
+        let mut reduction = 0.0;
+        for _ in 0..samples(0.01) {
+            reduction = release.process(10.0);
+        }
+        assert!((reduction - 6.32).abs() < 0.05, "{reduction}");
+    }
+
+    #[test]
+    fn short_peaks_release_with_the_fast_release() {
+        // One time constant of the 50 ms fast release, barely slowed down by the slow stage
+        let time = recovery_time(0.002, 3.68);
+        assert!((time - 0.05).abs() < 0.005, "{time}");
+    }
+
+    #[test]
+    fn sustained_compression_releases_slowly() {
+        let short = recovery_time(0.002, 1.0);
+        let sustained = recovery_time(2.0, 1.0);
+        assert!(
+            sustained > short * 10.0,
+            "{short} s after a peak, {sustained} s sustained"
+        );
+
+        // Fully charged, the recovery follows the 1 s slow release, plus a little for the fast
+        // stage that feeds it
+        let time = recovery_time(10.0, 3.68);
+        assert!((time - 1.05).abs() < 0.01, "{time}");
+    }
+
+    #[test]
+    fn release_times_are_kept_across_sample_rates() {
+        let mut release = DualRelease::new(SAMPLE_RATE / 2.0);
+        release.set_attack(0.0);
+        release.set_sample_rate(SAMPLE_RATE);
+        for _ in 0..samples(0.002) {
+            release.process(10.0);
+        }
+        let recovered = (0..).find(|_| release.process(0.0) < 3.68).unwrap();
+        assert!((recovered as f64 / SAMPLE_RATE - 0.05).abs() < 0.005);
+    }
+
+    fn sine(i: usize) -> f64 {
+        (core::f64::consts::TAU * 440.0 * i as f64 / SAMPLE_RATE).sin()
+    }
+
+    #[test]
+    fn makeup_restores_the_input_level() {
+        let mut makeup = AutoMakeup::new(SAMPLE_RATE, 0.3);
+        let reduction = db_to_gain(-12.0);
+        for i in 0..samples(3.0) {
+            makeup.process(sine(i), sine(i) * reduction);
+        }
+        let db = gain_to_db(makeup.gain());
+        assert!((db - 12.0).abs() < 0.1, "{db}");
+    }
+
+    #[test]
+    fn makeup_follows_a_step_in_gain_reduction_within_the_window() {
+        let mut makeup = AutoMakeup::new(SAMPLE_RATE, 0.3);
+        for i in 0..samples(3.0) {
+            makeup.process(sine(i), sine(i));
+        }
+        assert!((makeup.gain() - 1.0).abs() < 1e-3);
+
+        // The output drops by 12 dB. The output's mean square covers 63% of its drop in one
+        // window, which is a third of the way in dB, and is within half a dB after five.
+        let reduction = db_to_gain(-12.0);
+        let mut i = samples(3.0);
+        let mut follow = |seconds| {
+            for _ in 0..samples(seconds) {
+                makeup.process(sine(i), sine(i) * reduction);
+                i += 1;
+            }
+            gain_to_db(makeup.gain())
+        };
+        let db = follow(0.3);
+        assert!(db > 3.5 && db < 4.5, "{db}");
+        let db = follow(1.2);
+        assert!(db > 11.5 && db < 12.0, "{db}");
+    }
+
+    #[test]
+    fn makeup_is_limited_and_holds_through_silence() {
+        let mut makeup = AutoMakeup::new(SAMPLE_RATE, 0.3);
+        makeup.set_max_gain_db(6.0);
+        for i in 0..samples(3.0) {
+            makeup.process(sine(i), sine(i) * 0.1);
+        }
+        assert!((makeup.gain() - db_to_gain(6.0)).abs() < 1e-9);
+
+        for _ in 0..samples(3.0) {
+            makeup.process(0.0, 0.0);
+        }
+        assert!((makeup.gain() - db_to_gain(6.0)).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,379 @@
+//! FFT building blocks for spectral processing: a real FFT wrapper that owns its plans and
+//! scratch space, analysis windows, magnitude and phase helpers, and a streaming short-time
+//! Fourier transform that resynthesizes through overlap-add.
+//!
+//! Everything allocates up front in its constructor, so build these in `initialize()` and only
+//! call the processing methods from the audio thread.
+
+pub use realfft::num_complex::Complex32;
+
+use realfft::{ComplexToReal, RealFftPlanner, RealToComplex};
+use std::f32::consts::{PI, TAU};
+use std::sync::Arc;
+
+/// Window functions for spectral analysis. The windows are periodic rather than symmetric, so
+/// overlapping frames sum to a constant.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Window {
+    Rectangular,
+    /// A good default with low leakage. Squared, as [`Stft`] applies it, it sums to a constant at
+    /// 75% overlap.
+    #[default]
+    Hann,
+    Hamming,
+    /// Lower sidelobes than Hann at the cost of a wider main lobe.
+    Blackman,
+    /// Four-term Blackman-Harris, with sidelobes below -90 dB for analyzers.
+    BlackmanHarris,
+}
+
+impl Window {
+    /// The window's value at `index` out of `len` samples.
+    pub fn coefficient(self, index: usize, len: usize) -> f32 {
+        let x = TAU * index as f32 / len as f32;
+        match self {
+            Window::Rectangular => 1.0,
+            Window::Hann => 0.5 - 0.5 * x.cos(),
+            Window::Hamming => 0.54 - 0.46 * x.cos(),
+            Window::Blackman => 0.42 - 0.5 * x.cos() + 0.08 * (2.0 * x).cos(),
+            Window::BlackmanHarris => {
+                0.35875 - 0.48829 * x.cos() + 0.14128 * (2.0 * x).cos() - 0.01168 * (3.0 * x).cos()
+            }
+        }
+    }
+
+    /// Fill `window` with the window's coefficients.
+    pub fn fill(self, window: &mut [f32]) {
+        let len = window.len();
+        for (index, coefficient) in window.iter_mut().enumerate() {
+            *coefficient = self.coefficient(index, len);
+        }
+    }
+
+    pub fn build(self, len: usize) -> Vec<f32> {
+        let mut window = vec![0.0; len];
+        self.fill(&mut window);
+        window
+    }
+}
+
+/// The average of a window's coefficients. A windowed sinusoid's peak magnitude is scaled by
+/// this, on top of the FFT's own `size / 2`.
+pub fn coherent_gain(window: &[f32]) -> f32 {
+    window.iter().sum::<f32>() / window.len() as f32
+}
+
+/// A real FFT of a fixed length, in both directions.
+#[derive(Clone)]
+pub struct Fft {
+    forward: Arc<dyn RealToComplex<f32>>,
+    inverse: Arc<dyn ComplexToReal<f32>>,
+    scratch: Vec<Complex32>,
+    size: usize,
+}
+
+impl Fft {
+    pub fn new(size: usize) -> Self {
+        let mut planner = RealFftPlanner::<f32>::new();
+        let forward = planner.plan_fft_forward(size);
+        let inverse = planner.plan_fft_inverse(size);
+        let scratch_len = forward.get_scratch_len().max(inverse.get_scratch_len());
+
+        Self {
+            forward,
+            inverse,
+            scratch: vec![Complex32::ZERO; scratch_len],
+            size,
+        }
+    }
+
+    /// The number of samples transformed at once.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// The number of bins in a spectrum, from DC up to and including Nyquist.
+    pub fn num_bins(&self) -> usize {
+        self.size / 2 + 1
+    }
+
+    /// A zeroed buffer for a spectrum.
+    pub fn make_spectrum(&self) -> Vec<Complex32> {
+        vec![Complex32::ZERO; self.num_bins()]
+    }
+
+    /// Transform `size` samples into `num_bins` bins. The input is used as scratch space and is
+    /// left with unspecified contents.
+    pub fn forward(&mut self, input: &mut [f32], spectrum: &mut [Complex32]) {
+        self.forward
+            .process_with_scratch(input, spectrum, &mut self.scratch)
+            .expect("buffers must match the FFT length");
+    }
+
+    /// Transform a spectrum back into `size` samples. Like most FFTs the output is scaled by
+    /// `size`, so a round trip needs dividing by [`size()`][Self::size()]. The DC and Nyquist bins
+    /// must be real, their imaginary parts are cleared first. The spectrum is used as scratch
+    /// space and is left with unspecified contents.
+    pub fn inverse(&mut self, spectrum: &mut [Complex32], output: &mut [f32]) {
+        if let Some(dc) = spectrum.first_mut() {
+            dc.im = 0.0;
+        }
+        if self.size.is_multiple_of(2) {
+            if let Some(nyquist) = spectrum.last_mut() {
+                nyquist.im = 0.0;
+            }
+        }
+
+        self.inverse
+            .process_with_scratch(spectrum, output, &mut self.scratch)
+            .expect("buffers must match the FFT length");
+    }
+}
+
+/// The frequency at the center of `bin`.
+pub fn bin_frequency(bin: usize, fft_size: usize, sample_rate: f32) -> f32 {
+    bin as f32 * sample_rate / fft_size as f32
+}
+
+/// Each bin's magnitude.
+pub fn magnitudes(spectrum: &[Complex32], magnitudes: &mut [f32]) {
+    for (magnitude, bin) in magnitudes.iter_mut().zip(spectrum) {
+        *magnitude = bin.norm();
+    }
+}
+
+/// Each bin's phase, in `[-PI, PI]`.
+pub fn phases(spectrum: &[Complex32], phases: &mut [f32]) {
+    for (phase, bin) in phases.iter_mut().zip(spectrum) {
+        *phase = bin.arg();
+    }
+}
+
+/// Rebuild a spectrum from magnitudes and phases.
+pub fn from_polar(magnitudes: &[f32], phases: &[f32], spectrum: &mut [Complex32]) {
+    for ((bin, &magnitude), &phase) in spectrum.iter_mut().zip(magnitudes).zip(phases) {
+        *bin = Complex32::from_polar(magnitude, phase);
+    }
+}
+
+/// Wrap a phase to `[-PI, PI]`.
+pub fn wrap_phase(phase: f32) -> f32 {
+    phase - TAU * ((phase + PI) / TAU).floor()
+}
+
+/// A streaming short-time Fourier transform. The input is cut into overlapping windowed frames,
+/// a callback processes each frame's spectrum in place, and the frames are windowed again and
+/// overlap-added into the output. The output lags the input by [`latency()`][Self::latency()]
+/// samples, and a callback that leaves the spectrum alone reproduces the input exactly.
+///
+/// ```ignore
+/// let mut stft = Stft::new(2048, 4, Window::Hann);
+/// stft.process_block(channel, |spectrum| {
+///     for bin in &mut spectrum[..64] {
+///         *bin = Complex32::ZERO;
+///     }
+/// });
+/// ```
+#[derive(Clone)]
+pub struct Stft {
+    fft: Fft,
+    window: Vec<f32>,
+    hop: usize,
+    /// Divided out of the overlap-added output, the FFT's scaling times the summed squared
+    /// windows.
+    output_scale: f32,
+
+    /// The last `fft_size` input samples, the newest hop at the end.
+    input: Vec<f32>,
+    /// The finished hop being played back.
+    output: Vec<f32>,
+    /// Overlap-added frames that aren't finished yet.
+    accumulator: Vec<f32>,
+    /// The position within the current hop.
+    pos: usize,
+
+    frame: Vec<f32>,
+    spectrum: Vec<Complex32>,
+}
+
+impl Stft {
+    /// `overlap` frames cover every sample, so the hop is `fft_size / overlap`. The window must
+    /// sum to a constant when squared and overlapped this much, e.g. Hann at an overlap of 4.
+    pub fn new(fft_size: usize, overlap: usize, window: Window) -> Self {
+        assert!(overlap > 0 && fft_size.is_multiple_of(overlap));
+        let hop = fft_size / overlap;
+        let fft = Fft::new(fft_size);
+        let window = window.build(fft_size);
+        let window_power = window.iter().map(|w| w * w).sum::<f32>() / hop as f32;
+        debug_assert!(
+            (0..hop).all(|offset| {
+                let sum: f32 = window[offset..].iter().step_by(hop).map(|w| w * w).sum();
+                (sum - window_power).abs() <= 1e-3 * window_power
+            }),
+            "the squared window doesn't overlap-add to a constant at an overlap of {overlap}"
+        );
+
+        Self {
+            spectrum: fft.make_spectrum(),
+            fft,
+            window,
+            hop,
+            output_scale: 1.0 / (fft_size as f32 * window_power),
+
+            input: vec![0.0; fft_size],
+            output: vec![0.0; hop],
+            accumulator: vec![0.0; fft_size],
+            pos: 0,
+
+            frame: vec![0.0; fft_size],
+        }
+    }
+
+    pub fn fft_size(&self) -> usize {
+        self.fft.size()
+    }
+
+    pub fn hop(&self) -> usize {
+        self.hop
+    }
+
+    /// How far the output lags behind the input, in samples.
+    pub fn latency(&self) -> usize {
+        self.fft.size()
+    }
+
+    pub fn reset(&mut self) {
+        self.input.fill(0.0);
+        self.output.fill(0.0);
+        self.accumulator.fill(0.0);
+        self.pos = 0;
+    }
+
+    /// Process one sample, calling `process_frame` whenever a hop's worth of input has arrived.
+    pub fn process(&mut self, input: f32, process_frame: impl FnMut(&mut [Complex32])) -> f32 {
+        let fft_size = self.fft.size();
+        self.input[fft_size - self.hop + self.pos] = input;
+        let output = self.output[self.pos];
+
+        self.pos += 1;
+        if self.pos == self.hop {
+            self.pos = 0;
+            self.process_frame(process_frame);
+        }
+
+        output
+    }
+
+    pub fn process_block(
+        &mut self,
+        block: &mut [f32],
+        mut process_frame: impl FnMut(&mut [Complex32]),
+    ) {
+        for sample in block {
+            *sample = self.process(*sample, &mut process_frame);
+        }
+    }
+
+    fn process_frame(&mut self, mut process_frame: impl FnMut(&mut [Complex32])) {
+        for ((frame, input), window) in self.frame.iter_mut().zip(&self.input).zip(&self.window) {
+            *frame = input * window;
+        }
+        self.fft.forward(&mut self.frame, &mut self.spectrum);
+        process_frame(&mut self.spectrum);
+        self.fft.inverse(&mut self.spectrum, &mut self.frame);
+
+        for ((accumulator, frame), window) in self
+            .accumulator
+            .iter_mut()
+            .zip(&self.frame)
+            .zip(&self.window)
+        {
+            *accumulator += frame * window * self.output_scale;
+        }
+
+        let fft_size = self.fft.size();
+        self.output.copy_from_slice(&self.accumulator[..self.hop]);
+        self.accumulator.copy_within(self.hop.., 0);
+        self.accumulator[fft_size - self.hop..].fill(0.0);
+        self.input.copy_within(self.hop.., 0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A few seconds' worth of deterministic noise-like test signal.
+    fn test_signal(len: usize) -> Vec<f32> {
+        (0..len)
+            .map(|n| {
+                let n = n as f32;
+                (n * 0.031).sin() * 0.5 + (n * 0.173).sin() * 0.3 + (n * 1.37).cos() * 0.2
+            })
+            .collect()
+    }
+
+    #[test]
+    fn fft_round_trips_are_scaled_by_the_size() {
+        for size in [64, 100, 1024] {
+            let mut fft = Fft::new(size);
+            let signal = test_signal(size);
+            let mut spectrum = fft.make_spectrum();
+            let mut output = vec![0.0; size];
+
+            fft.forward(&mut signal.clone(), &mut spectrum);
+            fft.inverse(&mut spectrum, &mut output);
+            for (n, (&output, &input)) in output.iter().zip(&signal).enumerate() {
+                let output = output / size as f32;
+                assert!(
+                    (output - input).abs() < 1e-5,
+                    "{size}, {n}: {output} vs {input}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn stft_pass_through_is_a_delay_by_the_latency() {
+        let mut stft = Stft::new(256, 4, Window::Hann);
+        let latency = stft.latency();
+        let signal = test_signal(latency + 2000);
+
+        let mut output = signal.clone();
+        for block in output.chunks_mut(97) {
+            stft.process_block(block, |_| {});
+        }
+
+        for (n, &sample) in output.iter().enumerate() {
+            let expected = if n < latency {
+                0.0
+            } else {
+                signal[n - latency]
+            };
+            assert!(
+                (sample - expected).abs() < 1e-5,
+                "{n}: {sample} vs {expected}"
+            );
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    #[cfg(debug_assertions)]
+    fn hann_at_50_percent_overlap_is_rejected() {
+        Stft::new(256, 2, Window::Hann);
+    }
+
+    #[test]
+    fn wrapped_phases_stay_within_pi() {
+        for i in -1000..=1000 {
+            let phase = i as f32 * 0.0731;
+            let wrapped = wrap_phase(phase);
+            assert!((-PI..=PI).contains(&wrapped), "{phase}: {wrapped}");
+
+            // Only whole turns are removed
+            let turns = (phase - wrapped) / TAU;
+            assert!((turns - turns.round()).abs() < 1e-3, "{phase}: {wrapped}");
+        }
+    }
+}
@@ -0,0 +1,254 @@
+use crate::Sample;
+
+/// The response a [`StateVariableFilter`] outputs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FilterMode {
+    #[default]
+    LowPass,
+    HighPass,
+    BandPass,
+    Notch,
+}
+
+/// A 12 dB/octave state variable filter, using the trapezoidal integrator topology so the cutoff
+/// and resonance can be modulated every sample without blowing up.
+#[derive(Debug, Clone)]
+pub struct StateVariableFilter<T: Sample = f32> {
+    mode: FilterMode,
+    sample_rate: T,
+    cutoff: T,
+    q: T,
+
+    /// `1 / Q`, lower values resonate more.
+    k: T,
+    a1: T,
+    a2: T,
+    a3: T,
+
+    /// The integrators' states.
+    ic1: T,
+    ic2: T,
+}
+
+impl<T: Sample> StateVariableFilter<T> {
+    pub fn new(sample_rate: T) -> Self {
+        let mut filter = Self {
+            mode: FilterMode::default(),
+            sample_rate,
+            cutoff: T::ZERO,
+            q: T::ZERO,
+            k: T::ZERO,
+            a1: T::ZERO,
+            a2: T::ZERO,
+            a3: T::ZERO,
+            ic1: T::ZERO,
+            ic2: T::ZERO,
+        };
+        filter.set_params(
+            T::from_f32(1000.0),
+            T::from_f32(core::f32::consts::FRAC_1_SQRT_2),
+        );
+
+        filter
+    }
+
+    pub fn set_mode(&mut self, mode: FilterMode) {
+        self.mode = mode;
+    }
+
+    pub fn mode(&self) -> FilterMode {
+        self.mode
+    }
+
+    /// Set the cutoff in Hz and the resonance as a Q factor. The cutoff is kept below Nyquist
+    /// and Q is kept above zero.
+    pub fn set_params(&mut self, cutoff: T, q: T) {
+        self.cutoff = cutoff;
+        self.q = q;
+        let cutoff = cutoff.clamp(T::from_f32(10.0), self.sample_rate * T::from_f32(0.49));
+        let g = (T::PI * cutoff / self.sample_rate).tan();
+        self.k = T::ONE / q.max(T::from_f32(0.025));
+        self.a1 = T::ONE / (T::ONE + g * (g + self.k));
+        self.a2 = g * self.a1;
+        self.a3 = g * self.a2;
+    }
+
+    /// Keeps the cutoff in Hz.
+    pub fn set_sample_rate(&mut self, sample_rate: T) {
+        self.sample_rate = sample_rate;
+        self.set_params(self.cutoff, self.q);
+    }
+
+    /// Clear the filter's state.
+    pub fn reset(&mut self) {
+        self.ic1 = T::ZERO;
+        self.ic2 = T::ZERO;
+    }
+
+    pub fn process(&mut self, input: T) -> T {
+        let v3 = input - self.ic2;
+        let v1 = self.a1 * self.ic1 + self.a2 * v3;
+        let v2 = self.ic2 + self.a2 * self.ic1 + self.a3 * v3;
+        self.ic1 = T::from_f32(2.0) * v1 - self.ic1;
+        self.ic2 = T::from_f32(2.0) * v2 - self.ic2;
+
+        let low = v2;
+        let band = v1;
+        let high = input - self.k * band - low;
+        match self.mode {
+            FilterMode::LowPass => low,
+            FilterMode::HighPass => high,
+            FilterMode::BandPass => band,
+            FilterMode::Notch => low + high,
+        }
+    }
+
+    /// Filter a block in place.
+    pub fn process_block(&mut self, samples: &mut [T]) {
+        for sample in samples {
+            *sample = self.process(*sample);
+        }
+    }
+}
+
+/// The number of formants a [`FormantFilter`] models.
+pub const NUM_FORMANTS: usize = 5;
+
+/// Vowels for a [`FormantFilter`], in the order its morph sweeps through them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Vowel {
+    A,
+    E,
+    I,
+    O,
+    U,
+}
+
+/// A formant's center frequency in Hz, gain in dB, and bandwidth in Hz.
+type Formant = [f32; 3];
+
+impl Vowel {
+    pub const ALL: [Vowel; 5] = [Vowel::A, Vowel::E, Vowel::I, Vowel::O, Vowel::U];
+
+    /// The formants of a bass voice singing the vowel.
+    fn formants(self) -> [Formant; NUM_FORMANTS] {
+        match self {
+            Vowel::A => [
+                [600.0, 0.0, 60.0],
+                [1040.0, -7.0, 70.0],
+                [2250.0, -9.0, 110.0],
+                [2450.0, -9.0, 120.0],
+                [2750.0, -20.0, 130.0],
+            ],
+            Vowel::E => [
+                [400.0, 0.0, 40.0],
+                [1620.0, -12.0, 80.0],
+                [2400.0, -9.0, 100.0],
+                [2800.0, -12.0, 120.0],
+                [3100.0, -18.0, 120.0],
+            ],
+            Vowel::I => [
+                [250.0, 0.0, 60.0],
+                [1750.0, -30.0, 90.0],
+                [2600.0, -16.0, 100.0],
+                [3050.0, -22.0, 120.0],
+                [3340.0, -28.0, 120.0],
+            ],
+            Vowel::O => [
+                [400.0, 0.0, 40.0],
+                [750.0, -11.0, 80.0],
+                [2400.0, -21.0, 100.0],
+                [2600.0, -20.0, 120.0],
+                [2900.0, -40.0, 120.0],
+            ],
+            Vowel::U => [
+                [350.0, 0.0, 40.0],
+                [600.0, -20.0, 80.0],
+                [2400.0, -32.0, 100.0],
+                [2675.0, -28.0, 120.0],
+                [2950.0, -36.0, 120.0],
+            ],
+        }
+    }
+}
+
+/// A vowel filter made from a parallel bank of band-pass filters, one per formant. The morph
+/// position sweeps smoothly through the vowels A, E, I, O, and U for talking and singing sounds.
+#[derive(Debug, Clone)]
+pub struct FormantFilter<T: Sample = f32> {
+    bands: [StateVariableFilter<T>; NUM_FORMANTS],
+    /// Each band's linear gain, including the `1 / Q` that normalizes its peak to unity.
+    gains: [T; NUM_FORMANTS],
+}
+
+impl<T: Sample> FormantFilter<T> {
+    pub fn new(sample_rate: T) -> Self {
+        let mut filter = Self {
+            bands: core::array::from_fn(|_| {
+                let mut band = StateVariableFilter::new(sample_rate);
+                band.set_mode(FilterMode::BandPass);
+                band
+            }),
+            gains: [T::ZERO; NUM_FORMANTS],
+        };
+        filter.set_morph(T::ZERO);
+
+        filter
+    }
+
+    pub fn set_vowel(&mut self, vowel: Vowel) {
+        self.set_formants(vowel.formants().map(|formant| formant.map(T::from_f32)));
+    }
+
+    /// Morph through the vowels, from 0 for A to 1 for U. Formant frequencies glide
+    /// exponentially so the sweep sounds even.
+    pub fn set_morph(&mut self, morph: T) {
+        let last = Vowel::ALL.len() - 1;
+        let position = morph.clamp(T::ZERO, T::ONE) * T::from_f32(last as f32);
+        let idx = (position.floor().to_f32() as usize).min(last - 1);
+        let t = position - T::from_f32(idx as f32);
+
+        let from = Vowel::ALL[idx].formants();
+        let to = Vowel::ALL[idx + 1].formants();
+        self.set_formants(core::array::from_fn(|band| {
+            let [from_freq, from_gain, from_bandwidth] = from[band].map(T::from_f32);
+            let [to_freq, to_gain, to_bandwidth] = to[band].map(T::from_f32);
+            [
+                from_freq * (to_freq / from_freq).powf(t),
+                from_gain + (to_gain - from_gain) * t,
+                from_bandwidth + (to_bandwidth - from_bandwidth) * t,
+            ]
+        }));
+    }
+
+    fn set_formants(&mut self, formants: [[T; 3]; NUM_FORMANTS]) {
+        for ((band, gain), [freq, gain_db, bandwidth]) in
+            self.bands.iter_mut().zip(&mut self.gains).zip(formants)
+        {
+            let q = freq / bandwidth;
+            band.set_params(freq, q);
+            *gain = T::from_f32(10.0).powf(gain_db / T::from_f32(20.0)) / q;
+        }
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: T) {
+        for band in &mut self.bands {
+            band.set_sample_rate(sample_rate);
+        }
+    }
+
+    pub fn reset(&mut self) {
+        for band in &mut self.bands {
+            band.reset();
+        }
+    }
+
+    pub fn process(&mut self, input: T) -> T {
+        self.bands
+            .iter_mut()
+            .zip(&self.gains)
+            .fold(T::ZERO, |sum, (band, &gain)| {
+                sum + band.process(input) * gain
+            })
+    }
+}
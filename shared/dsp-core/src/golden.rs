@@ -0,0 +1,396 @@
+//! Golden output vectors for the DSP blocks. Each block renders a fixed input, and its output is
+//! compared against a recorded fixture in `golden/`, so refactors like table lookup sines or SIMD
+//! can show they still produce the same audio. The fixtures are gzipped little-endian `f32`s.
+//!
+//! After a change that's meant to alter a block's output, record new fixtures with:
+//!
+//! ```text
+//! UPDATE_GOLDEN=1 cargo test -p dsp-core golden
+//! ```
+
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::io::{Read, Write};
+use std::path::PathBuf;
+
+use crate::random::Xorshift32;
+
+/// The length of every input signal, and so of every golden vector.
+pub const LEN: usize = 2048;
+pub const SAMPLE_RATE: f32 = 48000.0;
+
+/// Tolerance for refactors that should only change rounding, about 20 dB above `f32`'s
+/// resolution.
+pub const NEAR_EXACT_DB: f32 = -120.0;
+
+/// A unit impulse followed by silence.
+pub fn impulse() -> Vec<f32> {
+    let mut signal = vec![0.0; LEN];
+    signal[0] = 1.0;
+    signal
+}
+
+/// Uniform white noise between -0.5 and 0.5, the same every time.
+pub fn noise(seed: u32) -> Vec<f32> {
+    let mut rng = Xorshift32::new(seed);
+    (0..LEN).map(|_| rng.next_bipolar() * 0.5).collect()
+}
+
+/// An exponential sine sweep from 20 Hz to 20 kHz at half scale.
+pub fn sweep() -> Vec<f32> {
+    let (start, end) = (20.0f64, 20000.0f64);
+    let rate = (end / start).ln() / LEN as f64;
+    (0..LEN)
+        .map(|n| {
+            let phase = start / SAMPLE_RATE as f64 * ((rate * n as f64).exp() - 1.0) / rate;
+            ((phase * std::f64::consts::TAU).sin() * 0.5) as f32
+        })
+        .collect()
+}
+
+/// How far an output is from its golden vector, relative to the golden vector's level.
+#[derive(Debug, Clone, Copy)]
+pub struct Difference {
+    /// The error's RMS level in dB relative to the reference's RMS level.
+    pub rms_db: f32,
+    /// The largest error in dB relative to the reference's peak.
+    pub peak_db: f32,
+}
+
+pub fn difference(reference: &[f32], output: &[f32]) -> Difference {
+    let mut error_energy = 0.0f64;
+    let mut reference_energy = 0.0f64;
+    let mut error_peak = 0.0f64;
+    let mut reference_peak = 0.0f64;
+    for (&reference, &output) in reference.iter().zip(output) {
+        let error = output as f64 - reference as f64;
+        error_energy += error * error;
+        reference_energy += reference as f64 * reference as f64;
+        error_peak = error_peak.max(error.abs());
+        reference_peak = reference_peak.max((reference as f64).abs());
+    }
+
+    // A silent reference is compared in absolute terms
+    let ratio_db = |error: f64, reference: f64| {
+        let reference = if reference > 0.0 { reference } else { 1.0 };
+        (10.0 * (error / reference).log10()) as f32
+    };
+    Difference {
+        rms_db: ratio_db(error_energy, reference_energy),
+        peak_db: ratio_db(error_peak * error_peak, reference_peak * reference_peak),
+    }
+}
+
+/// Compare `output` against the golden vector `name`, failing if any sample is off by more than
+/// `tolerance_db` relative to the vector's peak. With `UPDATE_GOLDEN` set the vector is recorded
+/// instead.
+#[track_caller]
+pub fn assert_golden(name: &str, output: &[f32], tolerance_db: f32) {
+    let path = fixture_path(name);
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        write_fixture(&path, output);
+        return;
+    }
+
+    let Some(reference) = read_fixture(&path) else {
+        panic!("there is no golden vector for {name}, record one with UPDATE_GOLDEN=1");
+    };
+    assert_eq!(reference.len(), output.len(), "{name}: length changed");
+
+    let difference = difference(&reference, output);
+    assert!(
+        difference.peak_db <= tolerance_db,
+        "{name}: off by {:.1} dB peak and {:.1} dB RMS, the tolerance is {tolerance_db} dB",
+        difference.peak_db,
+        difference.rms_db
+    );
+}
+
+fn fixture_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("golden")
+        .join(format!("{name}.f32.gz"))
+}
+
+fn read_fixture(path: &PathBuf) -> Option<Vec<f32>> {
+    let file = std::fs::File::open(path).ok()?;
+    let mut bytes = Vec::new();
+    GzDecoder::new(file)
+        .read_to_end(&mut bytes)
+        .unwrap_or_else(|err| panic!("{} is corrupt: {err}", path.display()));
+
+    let samples = bytes
+        .chunks_exact(4)
+        .map(|chunk| f32::from_le_bytes(chunk.try_into().unwrap()))
+        .collect();
+    Some(samples)
+}
+
+fn write_fixture(path: &PathBuf, samples: &[f32]) {
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    let file = std::fs::File::create(path).unwrap();
+    let mut encoder = GzEncoder::new(file, Compression::best());
+    for sample in samples {
+        encoder.write_all(&sample.to_le_bytes()).unwrap();
+    }
+    encoder.finish().unwrap();
+}
+
+/// The golden vectors themselves, one test per block. Blocks built on the FFT are left out,
+/// their rounding depends on the FFT library's version.
+mod blocks {
+    use super::*;
+    use crate::additive::AdditiveOsc;
+    use crate::chorus::Chorus;
+    use crate::crossover::LinkwitzRiley;
+    use crate::delay::{Allpass, DelayLine};
+    use crate::dynamics::EnvelopeFollower;
+    use crate::envelopes::ADSREnvelope;
+    use crate::filters::{FilterMode, FormantFilter, StateVariableFilter};
+    use crate::generators::{LogSweep, PinkNoise, SawOsc, SquareOsc};
+    use crate::lfo::{Lfo, LfoShape};
+    use crate::modal::{ModalResonator, Mode, Strike};
+    use crate::oscillators::SineOsc;
+    use crate::oversampling::Oversampler;
+    use crate::voices::SineVoiceBank;
+    use crate::waveshaper::{Curve, Waveshaper};
+
+    fn render(mut next_sample: impl FnMut() -> f32) -> Vec<f32> {
+        (0..LEN).map(|_| next_sample()).collect()
+    }
+
+    fn process(input: &[f32], mut process: impl FnMut(f32) -> f32) -> Vec<f32> {
+        input.iter().map(|&sample| process(sample)).collect()
+    }
+
+    #[test]
+    fn sine_osc() {
+        let mut osc = SineOsc::new(SAMPLE_RATE);
+        osc.set_frequency(441.0);
+        assert_golden("sine-osc", &render(|| osc.next_sample()), NEAR_EXACT_DB);
+    }
+
+    #[test]
+    fn band_limited_oscillators() {
+        let mut square = SquareOsc::new(SAMPLE_RATE);
+        square.set_frequency(1234.5);
+        assert_golden(
+            "square-osc",
+            &render(|| square.next_sample()),
+            NEAR_EXACT_DB,
+        );
+
+        let mut saw = SawOsc::new(SAMPLE_RATE);
+        saw.set_frequency(1234.5);
+        assert_golden("saw-osc", &render(|| saw.next_sample()), NEAR_EXACT_DB);
+    }
+
+    #[test]
+    fn additive_osc() {
+        let mut osc = AdditiveOsc::new(SAMPLE_RATE, [1.0, 2.0, 3.0, 4.5]);
+        osc.set_frequency(220.0);
+        for (partial, level) in [1.0, 0.5, 0.25, 0.125].into_iter().enumerate() {
+            osc.set_level(partial, level);
+        }
+        assert_golden("additive-osc", &render(|| osc.next_sample()), NEAR_EXACT_DB);
+    }
+
+    #[test]
+    fn noise_and_sweep_generators() {
+        let mut pink = PinkNoise::new(1);
+        assert_golden("pink-noise", &render(|| pink.next_sample()), NEAR_EXACT_DB);
+
+        let mut log_sweep = LogSweep::new(SAMPLE_RATE, 20.0, 20000.0, 0.05);
+        assert_golden(
+            "log-sweep",
+            &render(|| log_sweep.next_sample()),
+            NEAR_EXACT_DB,
+        );
+    }
+
+    #[test]
+    fn lfo_shapes() {
+        for (shape, name) in [
+            (LfoShape::Sine, "lfo-sine"),
+            (LfoShape::Triangle, "lfo-triangle"),
+            (LfoShape::Saw, "lfo-saw"),
+            (LfoShape::Square, "lfo-square"),
+            (LfoShape::SampleAndHold, "lfo-sample-and-hold"),
+        ] {
+            let mut lfo = Lfo::new(SAMPLE_RATE);
+            lfo.set_shape(shape);
+            lfo.set_rate(75.0);
+            assert_golden(name, &render(|| lfo.next_sample()), NEAR_EXACT_DB);
+        }
+    }
+
+    #[test]
+    fn adsr_envelope() {
+        let mut env = ADSREnvelope::new(SAMPLE_RATE);
+        env.set_attack(0.005);
+        env.set_decay(0.01);
+        env.set_sustain(0.5);
+        env.set_release(0.01);
+        env.note_on();
+        let mut n = 0;
+        let output = render(|| {
+            n += 1;
+            if n == LEN / 2 {
+                env.note_off();
+            }
+            env.next_sample()
+        });
+        assert_golden("adsr-envelope", &output, NEAR_EXACT_DB);
+    }
+
+    #[test]
+    fn state_variable_filter() {
+        for (mode, name) in [
+            (FilterMode::LowPass, "svf-low-pass"),
+            (FilterMode::HighPass, "svf-high-pass"),
+            (FilterMode::BandPass, "svf-band-pass"),
+            (FilterMode::Notch, "svf-notch"),
+        ] {
+            let mut filter = StateVariableFilter::new(SAMPLE_RATE);
+            filter.set_mode(mode);
+            filter.set_params(1000.0, 4.0);
+            let output = process(&noise(1), |sample| filter.process(sample));
+            assert_golden(name, &output, NEAR_EXACT_DB);
+        }
+
+        // Modulating the cutoff every sample
+        let mut filter = StateVariableFilter::new(SAMPLE_RATE);
+        let mut n = 0;
+        let output = process(&noise(2), |sample| {
+            n += 1;
+            filter.set_params(100.0 + n as f32 * 5.0, 2.0);
+            filter.process(sample)
+        });
+        assert_golden("svf-modulated", &output, NEAR_EXACT_DB);
+    }
+
+    #[test]
+    fn formant_filter() {
+        let mut filter = FormantFilter::new(SAMPLE_RATE);
+        let mut n = 0;
+        let output = process(&noise(3), |sample| {
+            n += 1;
+            filter.set_morph(n as f32 / LEN as f32);
+            filter.process(sample)
+        });
+        assert_golden("formant-filter", &output, NEAR_EXACT_DB);
+    }
+
+    #[test]
+    fn linkwitz_riley_crossover() {
+        let mut crossover = LinkwitzRiley::new(SAMPLE_RATE, 1000.0);
+        let (low, high): (Vec<f32>, Vec<f32>) = sweep()
+            .into_iter()
+            .map(|sample| crossover.process(sample))
+            .unzip();
+        assert_golden("linkwitz-riley-low", &low, NEAR_EXACT_DB);
+        assert_golden("linkwitz-riley-high", &high, NEAR_EXACT_DB);
+    }
+
+    #[test]
+    fn delays() {
+        let mut delay = DelayLine::new(256);
+        let mut n = 0;
+        let output = process(&sweep(), |sample| {
+            n += 1;
+            delay.process(sample, 100.0 + (n as f32 * 0.01).sin() * 50.0)
+        });
+        assert_golden("delay-line", &output, NEAR_EXACT_DB);
+
+        let mut allpass = Allpass::<f32, 113>::new(0.7);
+        let output = process(&impulse(), |sample| allpass.process(sample));
+        assert_golden("allpass", &output, NEAR_EXACT_DB);
+
+        let mut chorus = Chorus::new(SAMPLE_RATE, 0.03);
+        chorus.set_rate(5.0);
+        chorus.set_delay(0.01, 0.005);
+        chorus.set_mix(0.5);
+        let output = process(&sweep(), |sample| chorus.process(sample));
+        assert_golden("chorus", &output, NEAR_EXACT_DB);
+    }
+
+    #[test]
+    fn waveshaper_curves() {
+        for (curve, name) in [
+            (Curve::Tanh, "waveshaper-tanh"),
+            (Curve::Algebraic, "waveshaper-algebraic"),
+            (Curve::Cubic, "waveshaper-cubic"),
+            (Curve::HardClip, "waveshaper-hard-clip"),
+        ] {
+            let mut shaper = Waveshaper::new(SAMPLE_RATE);
+            shaper.set_curve(curve);
+            shaper.set_drive(4.0);
+            shaper.set_bias(0.1);
+            let output = process(&sweep(), |sample| shaper.process(sample));
+            assert_golden(name, &output, NEAR_EXACT_DB);
+        }
+    }
+
+    #[test]
+    fn oversampled_waveshaper() {
+        let mut oversampler = Oversampler::new(8);
+        oversampler.set_factor(4);
+        let mut output = sweep();
+        oversampler.process_block(&mut output, |sample| Curve::Tanh.apply(sample * 8.0));
+        assert_golden("oversampler", &output, NEAR_EXACT_DB);
+    }
+
+    #[test]
+    fn envelope_follower() {
+        let mut follower = EnvelopeFollower::new(SAMPLE_RATE);
+        follower.set_attack(0.001);
+        follower.set_release(0.02);
+        let output = process(&sweep(), |sample| follower.process(sample));
+        assert_golden("envelope-follower", &output, NEAR_EXACT_DB);
+    }
+
+    #[test]
+    fn modal_resonator() {
+        let mut resonator = ModalResonator::new(SAMPLE_RATE, 3);
+        resonator.set_modes(&[
+            Mode {
+                frequency: 400.0,
+                decay: 0.5,
+                gain: 1.0,
+            },
+            Mode {
+                frequency: 1103.0,
+                decay: 0.2,
+                gain: 0.5,
+            },
+            Mode {
+                frequency: 2871.0,
+                decay: 0.05,
+                gain: 0.25,
+            },
+        ]);
+        let mut strike = Strike::new(SAMPLE_RATE);
+        strike.strike(1.0, 0.002);
+        let output = render(|| resonator.process(strike.next_sample()));
+        assert_golden("modal-resonator", &output, NEAR_EXACT_DB);
+    }
+
+    #[test]
+    fn sine_voice_bank() {
+        let mut bank = SineVoiceBank::<f32, 4>::new(SAMPLE_RATE);
+        bank.set_attack(0.002);
+        bank.set_release(0.005);
+        for (voice, frequency) in [220.0, 277.2, 329.6, 440.0].into_iter().enumerate() {
+            bank.note_on(voice, frequency, 0.25);
+        }
+        let mut output = vec![0.0; LEN];
+        let (held, released) = output.split_at_mut(LEN / 2);
+        bank.process_block(held);
+        for voice in 0..4 {
+            bank.note_off(voice);
+        }
+        bank.process_block(released);
+        assert_golden("sine-voice-bank", &output, NEAR_EXACT_DB);
+    }
+}
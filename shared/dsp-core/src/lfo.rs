@@ -0,0 +1,92 @@
+use crate::random::Xorshift32;
+use crate::Sample;
+
+/// The waveform of an [`Lfo`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LfoShape {
+    #[default]
+    Sine,
+    Triangle,
+    /// Rising saw.
+    Saw,
+    Square,
+    /// A new random value every cycle.
+    SampleAndHold,
+}
+
+/// A low frequency oscillator for modulation, with a bipolar output from -1 to 1.
+#[derive(Debug, Clone)]
+pub struct Lfo<T: Sample = f32> {
+    shape: LfoShape,
+    phase: T,
+    rate: T,
+    sample_rate: T,
+    rng: Xorshift32,
+    held: T,
+}
+
+impl<T: Sample> Lfo<T> {
+    pub fn new(sample_rate: T) -> Self {
+        Self {
+            shape: LfoShape::default(),
+            phase: T::ZERO,
+            rate: T::ONE,
+            sample_rate,
+            rng: Xorshift32::new(1),
+            held: T::ZERO,
+        }
+    }
+
+    pub fn set_shape(&mut self, shape: LfoShape) {
+        self.shape = shape;
+    }
+
+    /// Set the rate in Hz.
+    pub fn set_rate(&mut self, rate: T) {
+        self.rate = rate.max(T::ZERO);
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: T) {
+        self.sample_rate = sample_rate;
+    }
+
+    /// Restart the cycle, e.g. when a note starts in retriggered mode.
+    pub fn reset(&mut self) {
+        self.phase = T::ZERO;
+        self.held = T::from_f32(self.rng.next_bipolar());
+    }
+
+    pub fn next_sample(&mut self) -> T {
+        let two = T::from_f32(2.0);
+        let sample = match self.shape {
+            LfoShape::Sine => (self.phase * T::TAU).sin(),
+            LfoShape::Triangle => {
+                // Starts at zero and rises, like the sine
+                let shifted = self.phase + T::from_f32(0.25);
+                let shifted = if shifted >= T::ONE {
+                    shifted - T::ONE
+                } else {
+                    shifted
+                };
+                T::ONE - two * (two * shifted - T::ONE).abs()
+            }
+            LfoShape::Saw => two * self.phase - T::ONE,
+            LfoShape::Square => {
+                if self.phase < T::HALF {
+                    T::ONE
+                } else {
+                    -T::ONE
+                }
+            }
+            LfoShape::SampleAndHold => self.held,
+        };
+
+        self.phase += self.rate / self.sample_rate;
+        if self.phase >= T::ONE {
+            self.phase -= self.phase.floor();
+            self.held = T::from_f32(self.rng.next_bipolar());
+        }
+
+        sample
+    }
+}
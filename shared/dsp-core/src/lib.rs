@@ -1,55 +1,174 @@
-use std::f32::consts::TAU;
+//! Shared DSP building blocks. Builds without the standard library when the default `std`
+//! feature is disabled, in which case math functions come from `libm`. Modules that need buffers
+//! still use `alloc`.
+//!
+//! Types that depend on the sample rate have a `set_sample_rate()` that keeps their settings and
+//! doesn't allocate, so they can be reused when the host changes the rate. `reset()` clears their
+//! state without touching the settings. Types whose buffers are sized by the sample rate, like
+//! the loudness meter and the pitch detector, are constructed again instead.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(not(feature = "std"))]
+extern crate alloc;
+
+/// Generic sample type support
+pub mod sample;
+
+pub use sample::Sample;
 
 /// Common oscillator implementations
 pub mod oscillators {
-    use super::*;
+    use super::Sample;
 
     #[derive(Clone)]
-    pub struct SineOsc {
-        phase: f32,
-        frequency: f32,
-        sample_rate: f32,
+    pub struct SineOsc<T: Sample = f32> {
+        phase: T,
+        frequency: T,
+        sample_rate: T,
     }
 
-    impl SineOsc {
-        pub fn new(sample_rate: f32) -> Self {
+    impl<T: Sample> SineOsc<T> {
+        pub fn new(sample_rate: T) -> Self {
             Self {
-                phase: 0.0,
-                frequency: 440.0,
+                phase: T::ZERO,
+                frequency: T::from_f32(440.0),
                 sample_rate,
             }
         }
 
-        pub fn set_frequency(&mut self, freq: f32) {
+        pub fn set_sample_rate(&mut self, sample_rate: T) {
+            self.sample_rate = sample_rate;
+        }
+
+        /// Only the phase increment changes, so this can be called every sample for vibrato
+        /// and glides without discontinuities.
+        pub fn set_frequency(&mut self, freq: T) {
             self.frequency = freq;
         }
 
-        pub fn next_sample(&mut self) -> f32 {
-            let sample = (self.phase * TAU).sin();
+        /// The position in the cycle, from 0 to 1.
+        pub fn phase(&self) -> T {
+            self.phase
+        }
+
+        /// Jump to a position in the cycle, e.g. to hard sync to another oscillator. Wrapped
+        /// into 0 to 1.
+        pub fn set_phase(&mut self, phase: T) {
+            self.phase = phase - phase.floor();
+        }
+
+        pub fn next_sample(&mut self) -> T {
+            self.tick_with_fm(T::ZERO)
+        }
+
+        /// Like [`next_sample()`][Self::next_sample()], with the output's phase offset by
+        /// `modulation` cycles. This is phase modulation, which is what FM synths do in
+        /// practice: the offset doesn't accumulate, so the pitch doesn't drift when the
+        /// modulator has a DC offset.
+        pub fn tick_with_fm(&mut self, modulation: T) -> T {
+            let sample = ((self.phase + modulation) * T::TAU).sin();
             self.phase += self.frequency / self.sample_rate;
-            if self.phase >= 1.0 {
-                self.phase -= 1.0;
+            if self.phase >= T::ONE {
+                self.phase -= T::ONE;
+            } else if self.phase < T::ZERO {
+                self.phase += T::ONE;
             }
             sample
         }
 
         pub fn reset(&mut self) {
-            self.phase = 0.0;
+            self.phase = T::ZERO;
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        const SAMPLE_RATE: f32 = 48000.0;
+
+        /// The interpolated positions of the upward zero crossings.
+        fn rising_zero_crossings(samples: &[f32]) -> Vec<f32> {
+            samples
+                .windows(2)
+                .enumerate()
+                .filter(|(_, pair)| pair[0] < 0.0 && pair[1] >= 0.0)
+                .map(|(idx, pair)| idx as f32 + pair[0] / (pair[0] - pair[1]))
+                .collect()
+        }
+
+        #[test]
+        fn phases_wrap_into_one_cycle() {
+            let mut osc = SineOsc::new(SAMPLE_RATE);
+            for (phase, wrapped) in [
+                (0.25, 0.25),
+                (-0.25, 0.75),
+                (1.25, 0.25),
+                (3.0, 0.0),
+                (-2.5, 0.5),
+            ] {
+                osc.set_phase(phase);
+                assert_eq!(osc.phase(), wrapped, "{phase}");
+            }
+
+            osc.set_phase(-0.25);
+            assert!((osc.next_sample() + 1.0).abs() < 1e-6);
+        }
+
+        #[test]
+        fn constant_modulation_only_shifts_the_phase() {
+            let mut plain = SineOsc::new(SAMPLE_RATE);
+            let mut modulated = SineOsc::new(SAMPLE_RATE);
+            plain.set_frequency(440.0);
+            modulated.set_frequency(440.0);
+
+            let plain: Vec<f32> = (0..4800).map(|_| plain.next_sample()).collect();
+            let modulated: Vec<f32> = (0..4800).map(|_| modulated.tick_with_fm(0.3)).collect();
+            let plain = rising_zero_crossings(&plain);
+            let modulated = rising_zero_crossings(&modulated);
+            assert!(modulated.len() > 40);
+
+            let period = SAMPLE_RATE / 440.0;
+            for (plain, modulated) in plain.windows(2).zip(modulated.windows(2)) {
+                let (plain, modulated) = (plain[1] - plain[0], modulated[1] - modulated[0]);
+                assert!((plain - period).abs() < 0.01, "{plain} vs {period}");
+                assert!((modulated - period).abs() < 0.01, "{modulated} vs {period}");
+            }
+        }
+
+        #[test]
+        fn double_precision_matches_single_precision() {
+            let mut single = SineOsc::new(SAMPLE_RATE);
+            let mut double = SineOsc::<f64>::new(SAMPLE_RATE as f64);
+            single.set_frequency(1000.0);
+            double.set_frequency(1000.0);
+
+            // Only the rounding errors single precision accumulates in the phase may differ
+            for n in 0..4800 {
+                let (single, double) = (single.next_sample(), double.next_sample());
+                assert!(
+                    (single as f64 - double).abs() < 1e-3,
+                    "{n}: {single} vs {double}"
+                );
+            }
         }
     }
 }
 
 /// Common envelope generators
 pub mod envelopes {
+    use super::Sample;
+
     #[derive(Clone)]
-    pub struct ADSREnvelope {
-        attack: f32,
-        decay: f32,
-        sustain: f32,
-        release: f32,
+    pub struct ADSREnvelope<T: Sample = f32> {
+        attack: T,
+        decay: T,
+        sustain: T,
+        release: T,
         stage: EnvStage,
-        level: f32,
-        sample_rate: f32,
+        level: T,
+        sample_rate: T,
     }
 
     #[derive(Clone, PartialEq)]
@@ -61,19 +180,30 @@ pub mod envelopes {
         Release,
     }
 
-    impl ADSREnvelope {
-        pub fn new(sample_rate: f32) -> Self {
+    impl<T: Sample> ADSREnvelope<T> {
+        pub fn new(sample_rate: T) -> Self {
             Self {
-                attack: 0.01,
-                decay: 0.1,
-                sustain: 0.7,
-                release: 0.2,
+                attack: T::from_f32(0.01),
+                decay: T::from_f32(0.1),
+                sustain: T::from_f32(0.7),
+                release: T::from_f32(0.2),
                 stage: EnvStage::Idle,
-                level: 0.0,
+                level: T::ZERO,
                 sample_rate,
             }
         }
 
+        /// Stage lengths are kept in seconds, so they stay the same at the new rate.
+        pub fn set_sample_rate(&mut self, sample_rate: T) {
+            self.sample_rate = sample_rate;
+        }
+
+        /// Go silent immediately, without a release.
+        pub fn reset(&mut self) {
+            self.stage = EnvStage::Idle;
+            self.level = T::ZERO;
+        }
+
         pub fn note_on(&mut self) {
             self.stage = EnvStage::Attack;
         }
@@ -82,19 +212,19 @@ pub mod envelopes {
             self.stage = EnvStage::Release;
         }
 
-        pub fn next_sample(&mut self) -> f32 {
+        pub fn next_sample(&mut self) -> T {
             match self.stage {
-                EnvStage::Idle => 0.0,
+                EnvStage::Idle => T::ZERO,
                 EnvStage::Attack => {
-                    self.level += 1.0 / (self.attack * self.sample_rate);
-                    if self.level >= 1.0 {
-                        self.level = 1.0;
+                    self.level += T::ONE / (self.attack * self.sample_rate);
+                    if self.level >= T::ONE {
+                        self.level = T::ONE;
                         self.stage = EnvStage::Decay;
                     }
                     self.level
                 }
                 EnvStage::Decay => {
-                    self.level -= (1.0 - self.sustain) / (self.decay * self.sample_rate);
+                    self.level -= (T::ONE - self.sustain) / (self.decay * self.sample_rate);
                     if self.level <= self.sustain {
                         self.level = self.sustain;
                         self.stage = EnvStage::Sustain;
@@ -104,8 +234,8 @@ pub mod envelopes {
                 EnvStage::Sustain => self.sustain,
                 EnvStage::Release => {
                     self.level -= self.level / (self.release * self.sample_rate);
-                    if self.level <= 0.001 {
-                        self.level = 0.0;
+                    if self.level <= T::from_f32(0.001) {
+                        self.level = T::ZERO;
                         self.stage = EnvStage::Idle;
                     }
                     self.level
@@ -117,33 +247,325 @@ pub mod envelopes {
             self.stage != EnvStage::Idle
         }
 
-        pub fn set_attack(&mut self, attack: f32) {
+        /// The number of samples until a releasing envelope becomes idle, zero once it is idle,
+        /// or `None` while the note is still held.
+        pub fn remaining_release_samples(&self) -> Option<u32> {
+            match self.stage {
+                EnvStage::Idle => Some(0),
+                EnvStage::Release => {
+                    // The release decays by a constant factor per sample until it reaches -60 dB
+                    let factor = T::ONE - T::ONE / (self.release * self.sample_rate);
+                    if factor <= T::ZERO || self.level <= T::from_f32(0.001) {
+                        return Some(1);
+                    }
+
+                    let samples = (T::from_f32(0.001) / self.level).ln() / factor.ln();
+                    Some(samples.floor().to_f32() as u32 + 1)
+                }
+                _ => None,
+            }
+        }
+
+        pub fn set_attack(&mut self, attack: T) {
             self.attack = attack;
         }
 
-        pub fn set_decay(&mut self, decay: f32) {
+        pub fn set_decay(&mut self, decay: T) {
             self.decay = decay;
         }
 
-        pub fn set_sustain(&mut self, sustain: f32) {
+        pub fn set_sustain(&mut self, sustain: T) {
             self.sustain = sustain;
         }
 
-        pub fn set_release(&mut self, release: f32) {
+        pub fn set_release(&mut self, release: T) {
             self.release = release;
         }
     }
+
+    /// A linear fade to silence for sounds that have to stop right away, like a stolen voice,
+    /// without the click of cutting them off mid-waveform.
+    #[derive(Clone)]
+    pub struct FadeOut<T: Sample = f32> {
+        /// The fade's length in samples, and the samples left in it. Counting samples instead of
+        /// subtracting a step makes the fade end exactly on zero.
+        length: u32,
+        remaining: u32,
+        time: T,
+        sample_rate: T,
+    }
+
+    impl<T: Sample> FadeOut<T> {
+        /// `time` is the fade's length in seconds. Starts out silent.
+        pub fn new(sample_rate: T, time: T) -> Self {
+            let mut fade = Self {
+                length: 1,
+                remaining: 0,
+                time,
+                sample_rate,
+            };
+            fade.set_time(time);
+            fade
+        }
+
+        pub fn set_time(&mut self, time: T) {
+            self.time = time;
+            self.length = (time * self.sample_rate + T::HALF)
+                .floor()
+                .max(T::ONE)
+                .to_f32() as u32;
+            self.remaining = self.remaining.min(self.length);
+        }
+
+        pub fn set_sample_rate(&mut self, sample_rate: T) {
+            self.sample_rate = sample_rate;
+            self.set_time(self.time);
+        }
+
+        /// Start fading from full level.
+        pub fn start(&mut self) {
+            self.remaining = self.length;
+        }
+
+        /// Go silent immediately.
+        pub fn reset(&mut self) {
+            self.remaining = 0;
+        }
+
+        /// Whether the fade is still audible.
+        pub fn is_active(&self) -> bool {
+            self.remaining > 0
+        }
+
+        /// The gain for the next sample.
+        pub fn next_gain(&mut self) -> T {
+            let gain = T::from_f32(self.remaining as f32) / T::from_f32(self.length as f32);
+            self.remaining = self.remaining.saturating_sub(1);
+            gain
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        const SAMPLE_RATE: f32 = 48000.0;
+        const FADE_TIME: f32 = 0.005;
+
+        /// The number of gains a fade produces before it goes silent.
+        fn fade_length(fade: &mut FadeOut) -> usize {
+            let mut length = 0;
+            while fade.is_active() {
+                let gain = fade.next_gain();
+                assert!(gain > 0.0 && gain <= 1.0, "{length}: {gain}");
+                length += 1;
+            }
+            length
+        }
+
+        #[test]
+        fn fades_reach_zero_after_their_time() {
+            let mut fade = FadeOut::new(SAMPLE_RATE, FADE_TIME);
+            assert!(!fade.is_active());
+            assert_eq!(fade.next_gain(), 0.0);
+
+            fade.start();
+            assert_eq!(fade.next_gain(), 1.0);
+            let mut previous = 1.0;
+            for n in 1..240 {
+                assert!(fade.is_active(), "{n}");
+                let gain = fade.next_gain();
+                assert!(gain < previous, "{n}: {gain} vs {previous}");
+                previous = gain;
+            }
+
+            assert!(!fade.is_active());
+            assert_eq!(fade.next_gain(), 0.0);
+        }
+
+        #[test]
+        fn fade_lengths_stay_the_same_in_seconds() {
+            let mut fade = FadeOut::new(SAMPLE_RATE, FADE_TIME);
+            fade.set_sample_rate(96000.0);
+            fade.start();
+            assert_eq!(fade_length(&mut fade), 480);
+
+            fade.set_sample_rate(44100.0);
+            fade.start();
+            assert_eq!(fade_length(&mut fade), 221);
+        }
+
+        #[test]
+        fn double_precision_envelopes_match_single_precision() {
+            let mut single = ADSREnvelope::new(SAMPLE_RATE);
+            let mut double = ADSREnvelope::<f64>::new(SAMPLE_RATE as f64);
+            single.set_release(0.05);
+            double.set_release(0.05);
+            single.note_on();
+            double.note_on();
+
+            // Through the attack, decay, and sustain, then the release to idle
+            for n in 0..30000 {
+                if n == 12000 {
+                    single.note_off();
+                    double.note_off();
+                }
+                let (single, double) = (single.next_sample(), double.next_sample());
+                assert!(
+                    (single as f64 - double).abs() < 1e-3,
+                    "{n}: {single} vs {double}"
+                );
+            }
+            assert!(!single.is_active() && !double.is_active());
+        }
+
+        #[test]
+        fn resetting_silences_the_fade() {
+            let mut fade = FadeOut::new(SAMPLE_RATE, FADE_TIME);
+            fade.start();
+            fade.next_gain();
+            fade.reset();
+            assert!(!fade.is_active());
+            assert_eq!(fade.next_gain(), 0.0);
+        }
+    }
 }
 
+/// MIDI note processing
+pub mod midi;
+
+/// Deterministic random number generation
+pub mod random;
+
+/// Click-free bypass crossfading
+pub mod bypass;
+
+/// Dry/wet mixing with latency compensation
+pub mod mix;
+
+/// Resonant filters
+pub mod filters;
+
+/// Linkwitz-Riley crossovers and multiband splitting
+pub mod crossover;
+
+/// Mid/side conversion, stereo width, rotation, and balance
+pub mod stereo;
+
+/// Mipmapped wavetables and a morphing wavetable oscillator
+pub mod wavetable;
+
+/// Stacks of sine partials for additive and drawbar organ tones
+pub mod additive;
+
+/// Structure-of-arrays voice banks for running many voices at once
+pub mod voices;
+
+/// Low frequency oscillators for modulation
+pub mod lfo;
+
+/// Noise, sweeps, impulses, and band-limited square and saw waves
+pub mod generators;
+
+/// Modal synthesis with banks of decaying resonators and a mallet exciter
+pub mod modal;
+
+/// Routing modulation sources to destinations
+pub mod modulation;
+
+/// Envelope followers, gain reduction smoothing, and decibel conversions for dynamics processors
+pub mod dynamics;
+
+/// Lookahead delays for dynamics processors
+pub mod lookahead;
+
+/// Modulatable delay lines with fractional reads, and inline fixed-size delays and allpasses
+pub mod delay;
+
+/// Chorus and vibrato from an LFO-swept delay
+pub mod chorus;
+
+/// Waveshaping curves for saturation
+pub mod waveshaper;
+
+/// Half-band oversampling for nonlinear processing
+pub mod oversampling;
+
+/// Polyphase windowed-sinc sample rate conversion
+pub mod resampler;
+
+/// Pitch detection, stereo correlation, and other signal analysis
+pub mod analysis;
+
+/// BS.1770 loudness and true peak metering
+pub mod loudness;
+
+/// FFT wrapper, windows, and streaming STFT
+#[cfg(feature = "std")]
+pub mod fft;
+
+/// Phase vocoder pitch shifting
+#[cfg(feature = "std")]
+pub mod pitch_shift;
+
+/// Spectral noise reduction with learned noise profiles
+#[cfg(feature = "std")]
+pub mod denoise;
+
+/// Offline phase vocoder time stretching and pitch shifting of whole buffers
+#[cfg(feature = "std")]
+pub mod time_stretch;
+
+/// Partitioned FFT convolution with impulse responses
+#[cfg(feature = "std")]
+pub mod convolution;
+
+/// Golden output vectors for regression testing the DSP blocks
+#[cfg(all(test, feature = "std"))]
+mod golden;
+
 /// Common utility functions
 pub mod utils {
+    use super::Sample;
+
+    /// One-pole, linear, and per-block parameter smoothing
+    mod smoothing;
+
+    pub use smoothing::{BlockSmoother, LinearSmoother, OnePoleSmoother, Smoother};
+
     /// Convert MIDI note number to frequency
     pub fn midi_to_freq(note: u8) -> f32 {
         440.0 * 2.0f32.powf((note as f32 - 69.0) / 12.0)
     }
 
     /// Linear interpolation
-    pub fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    pub fn lerp<T: Sample>(a: T, b: T, t: T) -> T {
         a + (b - a) * t
     }
 }
+
+/// Debug-build validation of processed audio
+pub mod guard {
+    /// Panic if `sample` is NaN, infinite, or denormal, naming the `module` that produced it.
+    /// Compiles to nothing in release builds.
+    #[inline]
+    pub fn check_sample(module: &str, sample: f32) {
+        if cfg!(debug_assertions) && (!sample.is_finite() || sample.is_subnormal()) {
+            panic!("{module} produced an invalid sample: {sample:e}");
+        }
+    }
+
+    /// Run [`check_sample`] over a whole block, reporting the offending index.
+    #[inline]
+    pub fn check_block(module: &str, samples: &[f32]) {
+        if cfg!(debug_assertions) {
+            if let Some((idx, sample)) = samples
+                .iter()
+                .enumerate()
+                .find(|(_, s)| !s.is_finite() || s.is_subnormal())
+            {
+                panic!("{module} produced an invalid sample at index {idx}: {sample:e}");
+            }
+        }
+    }
+}
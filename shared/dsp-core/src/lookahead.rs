@@ -0,0 +1,173 @@
+#[cfg(not(feature = "std"))]
+use alloc::{vec, vec::Vec};
+
+use crate::Sample;
+
+/// Delays audio so a dynamics processor can see it coming. The detector runs on the input as it
+/// arrives while the gain is applied to the delayed audio, so attacks can be caught before they
+/// happen. The delay is the plugin's latency, which the host compensates for.
+///
+/// ```ignore
+/// context.set_latency_samples(lookahead.latency_samples());
+///
+/// for (channel, sample) in frame.iter_mut().enumerate() {
+///     *sample = lookahead.process_sample(channel, *sample) * gain;
+/// }
+/// lookahead.advance();
+/// ```
+#[derive(Debug, Clone)]
+pub struct Lookahead<T: Sample = f32> {
+    /// One ring buffer per channel, with room for the maximum delay plus the current sample.
+    buffers: Vec<Vec<T>>,
+    write_pos: usize,
+    delay: usize,
+}
+
+impl<T: Sample> Lookahead<T> {
+    /// Allocates, so call this from `initialize()`. Starts without a delay.
+    pub fn new(num_channels: usize, max_delay: usize) -> Self {
+        Self {
+            buffers: vec![vec![T::ZERO; max_delay + 1]; num_channels],
+            write_pos: 0,
+            delay: 0,
+        }
+    }
+
+    /// The number of samples needed to look `seconds` ahead, for sizing the buffers.
+    pub fn samples_for(sample_rate: T, seconds: T) -> usize {
+        (seconds * sample_rate + T::from_f32(0.5)).floor().to_f32() as usize
+    }
+
+    /// Set the delay in samples, up to the maximum delay. The output jumps, so only change it
+    /// together with the plugin's latency.
+    pub fn set_delay(&mut self, samples: usize) {
+        self.delay = samples.min(self.max_delay());
+    }
+
+    pub fn delay(&self) -> usize {
+        self.delay
+    }
+
+    pub fn max_delay(&self) -> usize {
+        self.buffers.first().map_or(0, |buffer| buffer.len() - 1)
+    }
+
+    /// The delay in the form `set_latency_samples()` takes.
+    pub fn latency_samples(&self) -> u32 {
+        self.delay as u32
+    }
+
+    /// Clear the delayed audio.
+    pub fn reset(&mut self) {
+        for buffer in &mut self.buffers {
+            buffer.fill(T::ZERO);
+        }
+        self.write_pos = 0;
+    }
+
+    /// Write the channel's next sample and return the sample from `delay` samples ago. Call
+    /// [`advance()`][Self::advance()] after every channel of the frame is processed.
+    #[inline]
+    pub fn process_sample(&mut self, channel: usize, input: T) -> T {
+        let buffer = &mut self.buffers[channel];
+        let len = buffer.len();
+        buffer[self.write_pos] = input;
+        buffer[(self.write_pos + len - self.delay) % len]
+    }
+
+    /// Move on to the next frame.
+    #[inline]
+    pub fn advance(&mut self) {
+        if let Some(buffer) = self.buffers.first() {
+            self.write_pos = (self.write_pos + 1) % buffer.len();
+        }
+    }
+
+    /// Delay whole channels in place, for when the detector has already seen the block.
+    pub fn process_block(&mut self, channels: &mut [&mut [T]]) {
+        let num_samples = channels.first().map_or(0, |channel| channel.len());
+        for i in 0..num_samples {
+            for (channel, samples) in channels.iter_mut().enumerate() {
+                samples[i] = self.process_sample(channel, samples[i]);
+            }
+            self.advance();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: f32 = 48000.0;
+    const CEILING: f32 = 0.5;
+
+    /// A brickwall limiter: the gain is held at the lowest gain any sample within the lookahead
+    /// needs, so it's already down when a peak reaches the delayed output, then released slowly.
+    fn limit(input: &[f32], lookahead_seconds: f32) -> (Vec<f32>, usize) {
+        let delay = Lookahead::samples_for(SAMPLE_RATE, lookahead_seconds);
+        let mut lookahead = Lookahead::new(1, delay);
+        lookahead.set_delay(delay);
+
+        let mut needed = vec![1.0; delay + 1];
+        let mut gain = 1.0f32;
+        let output = input
+            .iter()
+            .enumerate()
+            .map(|(n, &sample)| {
+                needed[n % (delay + 1)] = (CEILING / sample.abs()).min(1.0);
+                let target = needed.iter().cloned().fold(1.0, f32::min);
+                gain = (gain + 0.001).min(target);
+
+                let output = lookahead.process_sample(0, sample) * gain;
+                lookahead.advance();
+                output
+            })
+            .collect();
+
+        (output, lookahead.latency_samples() as usize)
+    }
+
+    #[test]
+    fn limited_steps_never_exceed_the_ceiling() {
+        let input: Vec<f32> = (0..4800)
+            .map(|n| if (1000..3000).contains(&n) { 2.0 } else { 0.1 })
+            .collect();
+        let (output, latency) = limit(&input, 0.005);
+        assert_eq!(latency, 240);
+
+        for (n, &sample) in output.iter().enumerate() {
+            assert!(sample.abs() <= CEILING, "{n}: {sample}");
+        }
+        // The step arrives at the output a latency later, already turned down to the ceiling
+        assert_eq!(output[1000 + latency - 1], 0.1 * CEILING / 2.0);
+        assert_eq!(output[1000 + latency], CEILING);
+    }
+
+    #[test]
+    fn the_delay_is_the_reported_latency() {
+        let input: Vec<f32> = (0..2000).map(|n| (n as f32 * 0.01).sin() * 0.4).collect();
+        let (output, latency) = limit(&input, 0.003);
+        assert_eq!(latency, 144);
+
+        for (n, &sample) in output.iter().enumerate() {
+            let expected = if n < latency { 0.0 } else { input[n - latency] };
+            assert_eq!(sample, expected, "{n}");
+        }
+    }
+
+    #[test]
+    fn block_processing_delays_every_channel() {
+        let mut lookahead = Lookahead::<f32>::new(2, 16);
+        lookahead.set_delay(100);
+        assert_eq!(lookahead.delay(), 16);
+
+        let mut left: Vec<f32> = (1..=40).map(|n| n as f32).collect();
+        let mut right: Vec<f32> = left.iter().map(|x| -x).collect();
+        lookahead.process_block(&mut [&mut left, &mut right]);
+        for n in 0..40 {
+            let expected = if n < 16 { 0.0 } else { (n - 15) as f32 };
+            assert_eq!((left[n], right[n]), (expected, -expected), "{n}");
+        }
+    }
+}
@@ -0,0 +1,430 @@
+//! Loudness measurement following ITU-R BS.1770 and EBU R 128: K-weighted momentary,
+//! short-term and integrated loudness in LUFS, and the true peak level in dBTP.
+
+#[cfg(not(feature = "std"))]
+use alloc::{vec, vec::Vec};
+
+use crate::Sample;
+
+/// The loudness of a full scale 997 Hz sine in a single channel is -3.01 LUFS.
+const LOUDNESS_OFFSET: f64 = -0.691;
+
+/// Loudness is measured in 100 ms steps.
+const STEP_SECONDS: f32 = 0.1;
+/// Momentary loudness covers 400 ms, which is also the length of the gating blocks.
+const MOMENTARY_STEPS: usize = 4;
+/// Short-term loudness covers 3 s.
+const SHORT_TERM_STEPS: usize = 30;
+
+/// Gating blocks quieter than this don't count towards the integrated loudness.
+const ABSOLUTE_GATE: f64 = -70.0;
+/// Nor do blocks this far below the loudness of the blocks above the absolute gate.
+const RELATIVE_GATE: f64 = -10.0;
+
+/// The integrated loudness keeps a histogram of the gating blocks rather than every block, so
+/// it never allocates however long the measurement runs. The gates are applied with this
+/// resolution, in LU.
+const HISTOGRAM_RESOLUTION: f64 = 0.1;
+const HISTOGRAM_MAX: f64 = 10.0;
+const HISTOGRAM_BINS: usize = ((HISTOGRAM_MAX - ABSOLUTE_GATE) / HISTOGRAM_RESOLUTION) as usize;
+
+/// True peaks are measured at a sample rate of at least this much.
+const TRUE_PEAK_SAMPLE_RATE: f32 = 192_000.0;
+const MAX_OVERSAMPLING: usize = 4;
+/// The length of each phase of the interpolation filter.
+const TAPS_PER_PHASE: usize = 12;
+
+/// Measures the loudness of a multichannel signal. Every channel is weighted equally, which is
+/// right for mono and stereo, surround channels can be weighted with
+/// [`set_channel_weight()`][Self::set_channel_weight()].
+///
+/// ```ignore
+/// meter.process(buffer.as_slice_immutable());
+/// let short_term = meter.short_term();
+/// ```
+#[derive(Debug, Clone)]
+pub struct LoudnessMeter {
+    channels: Vec<ChannelState>,
+    /// The samples in each 100 ms step.
+    step_len: usize,
+    step_pos: usize,
+    /// The weighted sum of squares over the current step.
+    step_energy: f64,
+
+    /// The mean squares of the most recent steps, as a ring buffer.
+    steps: [f64; SHORT_TERM_STEPS],
+    step_index: usize,
+    /// The number of steps measured so far, up to a full ring.
+    num_steps: usize,
+
+    /// The number of gating blocks and the sum of their mean squares, binned by loudness.
+    histogram: Vec<(u64, f64)>,
+    integrated: f64,
+}
+
+#[derive(Debug, Clone)]
+struct ChannelState {
+    weight: f64,
+    pre_filter: Biquad,
+    rlb_filter: Biquad,
+    true_peak: TruePeak,
+}
+
+impl LoudnessMeter {
+    /// Allocates, so call this from `initialize()`.
+    pub fn new(sample_rate: f32, num_channels: usize) -> Self {
+        let channel = ChannelState {
+            weight: 1.0,
+            pre_filter: Biquad::k_weighting_shelf(sample_rate as f64),
+            rlb_filter: Biquad::k_weighting_high_pass(sample_rate as f64),
+            true_peak: TruePeak::new(sample_rate),
+        };
+
+        Self {
+            channels: vec![channel; num_channels],
+            step_len: ((sample_rate * STEP_SECONDS) as usize).max(1),
+            step_pos: 0,
+            step_energy: 0.0,
+
+            steps: [0.0; SHORT_TERM_STEPS],
+            step_index: 0,
+            num_steps: 0,
+
+            histogram: vec![(0, 0.0); HISTOGRAM_BINS],
+            integrated: f64::NEG_INFINITY,
+        }
+    }
+
+    /// Weight a channel's contribution, 1.41 for the surround channels of a 5.1 signal and 0 for
+    /// the LFE channel.
+    pub fn set_channel_weight(&mut self, channel: usize, weight: f32) {
+        if let Some(channel) = self.channels.get_mut(channel) {
+            channel.weight = weight as f64;
+        }
+    }
+
+    /// Start a new measurement.
+    pub fn reset(&mut self) {
+        for channel in &mut self.channels {
+            channel.pre_filter.reset();
+            channel.rlb_filter.reset();
+            channel.true_peak.reset();
+        }
+        self.step_pos = 0;
+        self.step_energy = 0.0;
+        self.steps = [0.0; SHORT_TERM_STEPS];
+        self.step_index = 0;
+        self.num_steps = 0;
+        self.histogram.fill((0, 0.0));
+        self.integrated = f64::NEG_INFINITY;
+    }
+
+    /// Measure a block, one slice per channel.
+    pub fn process<C: AsRef<[f32]>>(&mut self, channels: &[C]) {
+        let num_samples = channels
+            .iter()
+            .map(|channel| channel.as_ref().len())
+            .min()
+            .unwrap_or(0);
+
+        for i in 0..num_samples {
+            for (state, channel) in self.channels.iter_mut().zip(channels) {
+                let sample = channel.as_ref()[i];
+                state.true_peak.process(sample);
+
+                let weighted = state
+                    .rlb_filter
+                    .process(state.pre_filter.process(sample as f64));
+                self.step_energy += state.weight * weighted * weighted;
+            }
+
+            self.step_pos += 1;
+            if self.step_pos == self.step_len {
+                self.finish_step();
+            }
+        }
+    }
+
+    /// The loudness over the last 400 ms, in LUFS.
+    pub fn momentary(&self) -> f32 {
+        loudness(self.mean_square(MOMENTARY_STEPS)) as f32
+    }
+
+    /// The loudness over the last 3 s, in LUFS.
+    pub fn short_term(&self) -> f32 {
+        loudness(self.mean_square(SHORT_TERM_STEPS)) as f32
+    }
+
+    /// The gated loudness since the measurement started, in LUFS. Negative infinity until a
+    /// 400 ms block has been louder than -70 LUFS.
+    pub fn integrated(&self) -> f32 {
+        self.integrated as f32
+    }
+
+    /// The highest true peak across all channels since the measurement started, in dBTP.
+    pub fn true_peak(&self) -> f32 {
+        let peak = self
+            .channels
+            .iter()
+            .map(|channel| channel.true_peak.peak)
+            .fold(0.0, f32::max);
+        20.0 * Sample::ln(peak) / core::f32::consts::LN_10
+    }
+
+    /// The mean square of the last `num_steps` steps.
+    fn mean_square(&self, num_steps: usize) -> f64 {
+        let energy: f64 = (1..=num_steps)
+            .map(|age| self.steps[(self.step_index + SHORT_TERM_STEPS - age) % SHORT_TERM_STEPS])
+            .sum();
+        energy / num_steps as f64
+    }
+
+    fn finish_step(&mut self) {
+        self.steps[self.step_index] = self.step_energy / self.step_len as f64;
+        self.step_index = (self.step_index + 1) % SHORT_TERM_STEPS;
+        self.num_steps = (self.num_steps + 1).min(SHORT_TERM_STEPS);
+        self.step_pos = 0;
+        self.step_energy = 0.0;
+
+        // A new gating block finishes every step, overlapping the previous one by 75%
+        if self.num_steps >= MOMENTARY_STEPS {
+            let mean_square = self.mean_square(MOMENTARY_STEPS);
+            let block_loudness = loudness(mean_square);
+            if block_loudness >= ABSOLUTE_GATE {
+                let bin = (((block_loudness - ABSOLUTE_GATE) / HISTOGRAM_RESOLUTION) as usize)
+                    .min(HISTOGRAM_BINS - 1);
+                self.histogram[bin].0 += 1;
+                self.histogram[bin].1 += mean_square;
+                self.integrated = self.gated_loudness();
+            }
+        }
+    }
+
+    fn gated_loudness(&self) -> f64 {
+        let gated_mean = |first_bin: usize| {
+            let (count, energy) = self.histogram[first_bin..].iter().fold(
+                (0, 0.0),
+                |(count, energy), &(bin_count, bin_energy)| {
+                    (count + bin_count, energy + bin_energy)
+                },
+            );
+            if count > 0 {
+                energy / count as f64
+            } else {
+                0.0
+            }
+        };
+
+        let relative_gate = loudness(gated_mean(0)) + RELATIVE_GATE;
+        let first_bin =
+            Sample::floor((relative_gate - ABSOLUTE_GATE) / HISTOGRAM_RESOLUTION).max(0.0) as usize;
+        loudness(gated_mean(first_bin.min(HISTOGRAM_BINS - 1)))
+    }
+}
+
+/// The loudness of a weighted mean square, in LUFS.
+fn loudness(mean_square: f64) -> f64 {
+    LOUDNESS_OFFSET + 10.0 * Sample::ln(mean_square) / core::f64::consts::LN_10
+}
+
+/// A second order filter for the K-weighting curve.
+#[derive(Debug, Clone)]
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    state: [f64; 2],
+}
+
+impl Biquad {
+    /// The first stage, a high shelf modelling the acoustic effect of the head.
+    fn k_weighting_shelf(sample_rate: f64) -> Self {
+        let frequency = 1681.97445095553;
+        let gain_db = 3.99984385397335;
+        let q = 0.70717523695542;
+
+        let k = Sample::tan(core::f64::consts::PI * frequency / sample_rate);
+        let vh = Sample::powf(10.0f64, gain_db / 20.0);
+        let vb = Sample::powf(vh, 0.499666774154542);
+        let a0 = 1.0 + k / q + k * k;
+
+        Self {
+            b: [
+                (vh + vb * k / q + k * k) / a0,
+                2.0 * (k * k - vh) / a0,
+                (vh - vb * k / q + k * k) / a0,
+            ],
+            a: [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+            state: [0.0; 2],
+        }
+    }
+
+    /// The second stage, the revised low-frequency B-weighting high-pass.
+    fn k_weighting_high_pass(sample_rate: f64) -> Self {
+        let frequency = 38.1354708760244;
+        let q = 0.500327037323877;
+
+        let k = Sample::tan(core::f64::consts::PI * frequency / sample_rate);
+        let a0 = 1.0 + k / q + k * k;
+
+        Self {
+            b: [1.0, -2.0, 1.0],
+            a: [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+            state: [0.0; 2],
+        }
+    }
+
+    fn reset(&mut self) {
+        self.state = [0.0; 2];
+    }
+
+    /// Transposed direct form II.
+    fn process(&mut self, input: f64) -> f64 {
+        let output = self.b[0] * input + self.state[0];
+        self.state[0] = self.b[1] * input - self.a[0] * output + self.state[1];
+        self.state[1] = self.b[2] * input - self.a[1] * output;
+        output
+    }
+}
+
+/// Finds the peaks between samples by oversampling with a windowed sinc interpolator, as in
+/// BS.1770 Annex 2.
+#[derive(Debug, Clone)]
+struct TruePeak {
+    /// One phase of `TAPS_PER_PHASE` coefficients per oversampled point, one after the other.
+    coefficients: Vec<f32>,
+    /// The last `TAPS_PER_PHASE` samples, the newest first.
+    history: [f32; TAPS_PER_PHASE],
+    peak: f32,
+}
+
+impl TruePeak {
+    fn new(sample_rate: f32) -> Self {
+        let oversampling =
+            ((TRUE_PEAK_SAMPLE_RATE / sample_rate - 0.001) as usize + 1).clamp(1, MAX_OVERSAMPLING);
+
+        // Phase `p` interpolates the point `p / oversampling` samples after the centre tap
+        let center = (TAPS_PER_PHASE / 2) as f32 - 1.0;
+        let mut coefficients = vec![0.0; oversampling * TAPS_PER_PHASE];
+        for phase in 0..oversampling {
+            let offset = phase as f32 / oversampling as f32;
+            let taps = &mut coefficients[phase * TAPS_PER_PHASE..(phase + 1) * TAPS_PER_PHASE];
+            for (tap, coefficient) in taps.iter_mut().enumerate() {
+                // Taps reach back in time from the newest sample
+                let t = center + offset - tap as f32;
+                let window_pos = 0.5 + t / (TAPS_PER_PHASE as f32 + 1.0);
+                let window = 0.42 - 0.5 * Sample::cos(core::f32::consts::TAU * window_pos)
+                    + 0.08 * Sample::cos(2.0 * core::f32::consts::TAU * window_pos);
+                *coefficient = sinc(t) * window;
+            }
+
+            // Each phase passes DC at unity gain
+            let sum: f32 = taps.iter().sum();
+            for coefficient in taps {
+                *coefficient /= sum;
+            }
+        }
+
+        Self {
+            coefficients,
+            history: [0.0; TAPS_PER_PHASE],
+            peak: 0.0,
+        }
+    }
+
+    fn reset(&mut self) {
+        self.history = [0.0; TAPS_PER_PHASE];
+        self.peak = 0.0;
+    }
+
+    fn process(&mut self, sample: f32) {
+        self.history.copy_within(..TAPS_PER_PHASE - 1, 1);
+        self.history[0] = sample;
+
+        for taps in self.coefficients.chunks_exact(TAPS_PER_PHASE) {
+            let interpolated: f32 = taps
+                .iter()
+                .zip(&self.history)
+                .map(|(coefficient, sample)| coefficient * sample)
+                .sum();
+            self.peak = self.peak.max(interpolated.abs());
+        }
+    }
+}
+
+fn sinc(x: f32) -> f32 {
+    if x.abs() < 1e-6 {
+        1.0
+    } else {
+        let x = core::f32::consts::PI * x;
+        Sample::sin(x) / x
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::{FRAC_PI_4, TAU};
+
+    const SAMPLE_RATE: f32 = 48000.0;
+
+    fn sine(frequency: f32, amplitude: f32, phase: f32, seconds: f32) -> Vec<f32> {
+        (0..(seconds * SAMPLE_RATE) as usize)
+            .map(|n| amplitude * (TAU * frequency * n as f32 / SAMPLE_RATE + phase).sin())
+            .collect()
+    }
+
+    fn db_to_gain(db: f32) -> f32 {
+        10.0f32.powf(db / 20.0)
+    }
+
+    #[test]
+    fn a_stereo_sine_at_minus_23_dbfs_reads_minus_23_lufs() {
+        // The EBU Tech 3341 reference signal
+        let signal = sine(1000.0, db_to_gain(-23.0), 0.0, 20.0);
+        let mut meter = LoudnessMeter::new(SAMPLE_RATE, 2);
+        meter.process(&[&signal, &signal]);
+
+        for (name, lufs) in [
+            ("momentary", meter.momentary()),
+            ("short-term", meter.short_term()),
+            ("integrated", meter.integrated()),
+        ] {
+            assert!((lufs + 23.0).abs() < 0.1, "{name}: {lufs}");
+        }
+    }
+
+    #[test]
+    fn blocks_below_the_absolute_gate_are_ignored() {
+        let mut meter = LoudnessMeter::new(SAMPLE_RATE, 2);
+        let quiet = sine(1000.0, db_to_gain(-75.0), 0.0, 5.0);
+        meter.process(&[&quiet, &quiet]);
+        assert_eq!(meter.integrated(), f32::NEG_INFINITY);
+
+        // Silence after a loud passage doesn't drag the integrated loudness down. The blocks
+        // straddling either end of the passage count, so it reads a little below -20 LUFS.
+        let loud = sine(1000.0, db_to_gain(-20.0), 0.0, 10.0);
+        let silence = vec![0.0; loud.len()];
+        meter.process(&[&loud, &loud]);
+        meter.process(&[&silence, &silence]);
+        let integrated = meter.integrated();
+        assert!((integrated + 20.0).abs() < 0.2, "{integrated}");
+        assert!(meter.momentary() < -70.0);
+    }
+
+    #[test]
+    fn true_peaks_are_found_between_samples() {
+        // A quarter of the sample rate, sampled 45 degrees off its peaks, never has a sample
+        // above 0.707 of its amplitude
+        let signal = sine(SAMPLE_RATE / 4.0, 0.5, FRAC_PI_4, 1.0);
+        let sample_peak = signal.iter().fold(0.0f32, |peak, s| peak.max(s.abs()));
+        assert!(sample_peak < 0.36);
+
+        let mut meter = LoudnessMeter::new(SAMPLE_RATE, 1);
+        meter.process(&[&signal]);
+        let true_peak = meter.true_peak();
+        assert!((true_peak + 6.02).abs() < 0.5, "{true_peak}");
+
+        meter.reset();
+        assert_eq!(meter.true_peak(), f32::NEG_INFINITY);
+    }
+}
@@ -0,0 +1,136 @@
+/// Musical scales, described by the semitone offsets of their degrees from the root.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scale {
+    Major,
+    NaturalMinor,
+    HarmonicMinor,
+    MelodicMinor,
+    Dorian,
+    Phrygian,
+    Lydian,
+    Mixolydian,
+    Locrian,
+    MajorPentatonic,
+    MinorPentatonic,
+    Blues,
+    /// Every semitone, quantizing leaves notes as they are.
+    Chromatic,
+}
+
+impl Scale {
+    pub const fn intervals(self) -> &'static [u8] {
+        match self {
+            Scale::Major => &[0, 2, 4, 5, 7, 9, 11],
+            Scale::NaturalMinor => &[0, 2, 3, 5, 7, 8, 10],
+            Scale::HarmonicMinor => &[0, 2, 3, 5, 7, 8, 11],
+            Scale::MelodicMinor => &[0, 2, 3, 5, 7, 9, 11],
+            Scale::Dorian => &[0, 2, 3, 5, 7, 9, 10],
+            Scale::Phrygian => &[0, 1, 3, 5, 7, 8, 10],
+            Scale::Lydian => &[0, 2, 4, 6, 7, 9, 11],
+            Scale::Mixolydian => &[0, 2, 4, 5, 7, 9, 10],
+            Scale::Locrian => &[0, 1, 3, 5, 6, 8, 10],
+            Scale::MajorPentatonic => &[0, 2, 4, 7, 9],
+            Scale::MinorPentatonic => &[0, 3, 5, 7, 10],
+            Scale::Blues => &[0, 3, 5, 6, 7, 10],
+            Scale::Chromatic => &[0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11],
+        }
+    }
+}
+
+/// Snaps MIDI notes to the nearest degree of a scale, for example so every pad on a pad
+/// controller plays something in key. A note exactly between two degrees goes down.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScaleQuantizer {
+    /// Bit `n` is set when pitch class `n` (C = 0) is in the scale.
+    pitch_classes: u16,
+}
+
+impl ScaleQuantizer {
+    /// `root` is a pitch class from 0 (C) to 11 (B), larger values wrap around.
+    pub fn new(scale: Scale, root: u8) -> Self {
+        let root = root % 12;
+        let pitch_classes = scale
+            .intervals()
+            .iter()
+            .fold(0, |mask, interval| mask | 1 << ((root + interval) % 12));
+
+        Self { pitch_classes }
+    }
+
+    pub fn contains(&self, note: u8) -> bool {
+        self.pitch_classes & (1 << (note % 12)) != 0
+    }
+
+    /// The scale degree closest to `note`. Notes at the edges of the MIDI range may snap inwards
+    /// when the nearest degree would be outside of it.
+    pub fn quantize(&self, note: u8) -> u8 {
+        let note = note.min(127);
+        for distance in 0..12 {
+            if let Some(lower) = note.checked_sub(distance) {
+                if self.contains(lower) {
+                    return lower;
+                }
+            }
+            let upper = note + distance;
+            if upper <= 127 && self.contains(upper) {
+                return upper;
+            }
+        }
+
+        note
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn notes_snap_to_the_nearest_degree() {
+        let c_major_pentatonic = ScaleQuantizer::new(Scale::MajorPentatonic, 0);
+        // C D E G A
+        for (note, expected) in [(60, 60), (62, 62), (65, 64), (66, 67), (68, 67), (70, 69)] {
+            assert_eq!(c_major_pentatonic.quantize(note), expected, "{note}");
+        }
+    }
+
+    #[test]
+    fn ties_go_down() {
+        let c_major = ScaleQuantizer::new(Scale::Major, 0);
+        for (note, expected) in [(61, 60), (63, 62), (66, 65), (68, 67), (70, 69)] {
+            assert_eq!(c_major.quantize(note), expected, "{note}");
+        }
+
+        // In D major C sits between B and C#, and D# between D and E
+        let d_major = ScaleQuantizer::new(Scale::Major, 2);
+        assert_eq!(d_major.quantize(60), 59);
+        assert_eq!(d_major.quantize(63), 62);
+    }
+
+    #[test]
+    fn scales_wrap_around_the_octave() {
+        // B is closer to the C above it than to the A below
+        let c_major_pentatonic = ScaleQuantizer::new(Scale::MajorPentatonic, 0);
+        assert_eq!(c_major_pentatonic.quantize(71), 72);
+        assert_eq!(c_major_pentatonic.quantize(11), 12);
+
+        // Roots wrap too, A minor pentatonic starting on the A below C
+        let a_minor_pentatonic = ScaleQuantizer::new(Scale::MinorPentatonic, 21);
+        assert_eq!(a_minor_pentatonic, c_major_pentatonic);
+
+        // The nearest degree above the MIDI range is out of reach, so the top note snaps down
+        let c_sharp_major_pentatonic = ScaleQuantizer::new(Scale::MajorPentatonic, 1);
+        assert_eq!(c_sharp_major_pentatonic.quantize(127), 125);
+        assert_eq!(c_sharp_major_pentatonic.quantize(0), 1);
+    }
+
+    #[test]
+    fn the_chromatic_scale_keeps_every_note() {
+        for root in 0..12 {
+            let chromatic = ScaleQuantizer::new(Scale::Chromatic, root);
+            for note in 0..=127 {
+                assert_eq!(chromatic.quantize(note), note);
+            }
+        }
+    }
+}
@@ -0,0 +1,239 @@
+#[cfg(not(feature = "std"))]
+use alloc::{vec, vec::Vec};
+
+use crate::Sample;
+
+/// How long a change of the mix amount takes, to avoid zipper noise.
+const MIX_SMOOTHING_TIME: f32 = 0.01;
+
+/// How far the mix amount moves per sample while it's changing.
+fn mix_step<T: Sample>(sample_rate: T) -> T {
+    T::ONE / (T::from_f32(MIX_SMOOTHING_TIME) * sample_rate).max(T::ONE)
+}
+
+/// How the dry and wet gains follow the mix amount.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MixLaw {
+    /// Gains sum to one. Keeps the level constant when the wet signal is correlated with the dry
+    /// signal, which is the case for most effects.
+    #[default]
+    Linear,
+    /// Powers sum to one. Keeps the level constant for uncorrelated signals like reverb tails.
+    EqualPower,
+}
+
+/// The dry/wet stage for effect plugins. The dry input is captured before the effect processes
+/// the buffer in place, and mixed back in afterwards. When the wet path adds latency, e.g. from
+/// oversampling or lookahead, the dry path is delayed by the same amount so the two stay aligned.
+///
+/// ```ignore
+/// mix_stage.set_mix(params.mix.value());
+/// mix_stage.capture_dry(buffer.as_slice_immutable());
+/// effect.process(buffer);
+/// mix_stage.mix_into(buffer.as_slice());
+/// ```
+#[derive(Debug, Clone)]
+pub struct MixStage<T: Sample = f32> {
+    /// One ring buffer per channel, with room for a block plus the maximum latency.
+    dry: Vec<Vec<T>>,
+    write_pos: usize,
+    /// The length of the most recently captured block.
+    block_len: usize,
+    latency: usize,
+    max_latency: usize,
+
+    mix: T,
+    target_mix: T,
+    mix_step: T,
+    law: MixLaw,
+}
+
+impl<T: Sample> MixStage<T> {
+    /// Blocks passed to [`capture_dry()`][Self::capture_dry()] must not be longer than
+    /// `max_block_size`. Allocates, so call this from `initialize()`.
+    pub fn new(
+        sample_rate: T,
+        num_channels: usize,
+        max_block_size: usize,
+        max_latency: usize,
+    ) -> Self {
+        Self {
+            dry: vec![vec![T::ZERO; max_block_size + max_latency]; num_channels],
+            write_pos: 0,
+            block_len: 0,
+            latency: 0,
+            max_latency,
+
+            mix: T::ONE,
+            target_mix: T::ONE,
+            mix_step: mix_step(sample_rate),
+            law: MixLaw::default(),
+        }
+    }
+
+    /// Keeps the buffers, which are sized in samples rather than by the sample rate.
+    pub fn set_sample_rate(&mut self, sample_rate: T) {
+        self.mix_step = mix_step(sample_rate);
+    }
+
+    /// Delay the dry path by the wet path's latency in samples, up to the maximum latency.
+    pub fn set_latency(&mut self, samples: usize) {
+        self.latency = samples.min(self.max_latency);
+    }
+
+    pub fn latency(&self) -> usize {
+        self.latency
+    }
+
+    /// Set the mix amount, from 0 for only the dry signal to 1 for only the wet signal. Changes
+    /// are smoothed.
+    pub fn set_mix(&mut self, mix: T) {
+        self.target_mix = mix.clamp(T::ZERO, T::ONE);
+    }
+
+    pub fn set_law(&mut self, law: MixLaw) {
+        self.law = law;
+    }
+
+    /// Clear the dry delay and jump to the target mix amount.
+    pub fn reset(&mut self) {
+        for channel in &mut self.dry {
+            channel.fill(T::ZERO);
+        }
+        self.write_pos = 0;
+        self.block_len = 0;
+        self.mix = self.target_mix;
+    }
+
+    /// Store the dry input, one slice per channel. Call this before the effect overwrites the
+    /// buffer.
+    pub fn capture_dry<D: AsRef<[T]>>(&mut self, input: &[D]) {
+        let capacity = self.capacity();
+        if capacity == 0 {
+            return;
+        }
+
+        let block_len = input
+            .iter()
+            .map(|channel| channel.as_ref().len())
+            .max()
+            .unwrap_or(0);
+        debug_assert!(block_len <= capacity - self.max_latency);
+        self.block_len = block_len.min(capacity - self.max_latency);
+
+        for (ring, channel) in self.dry.iter_mut().zip(input) {
+            for (offset, &sample) in channel.as_ref()[..self.block_len].iter().enumerate() {
+                ring[(self.write_pos + offset) % capacity] = sample;
+            }
+        }
+        self.write_pos = (self.write_pos + self.block_len) % capacity;
+    }
+
+    /// Mix the delayed dry signal into the processed buffer, one slice per channel.
+    pub fn mix_into<P: AsMut<[T]>>(&mut self, wet: &mut [P]) {
+        let capacity = self.capacity();
+        if capacity == 0 {
+            return;
+        }
+
+        // Fully wet, nothing to mix in
+        if self.mix >= T::ONE && self.target_mix >= T::ONE {
+            return;
+        }
+
+        let read_start = (self.write_pos + 2 * capacity - self.block_len - self.latency) % capacity;
+        for sample_idx in 0..self.block_len {
+            let (wet_gain, dry_gain) = self.next_gains();
+            let read_pos = (read_start + sample_idx) % capacity;
+            for (channel, ring) in wet.iter_mut().zip(&self.dry) {
+                if let Some(sample) = channel.as_mut().get_mut(sample_idx) {
+                    *sample = *sample * wet_gain + ring[read_pos] * dry_gain;
+                }
+            }
+        }
+    }
+
+    fn capacity(&self) -> usize {
+        self.dry.first().map_or(0, Vec::len)
+    }
+
+    fn next_gains(&mut self) -> (T, T) {
+        if self.mix < self.target_mix {
+            self.mix = (self.mix + self.mix_step).min(self.target_mix);
+        } else if self.mix > self.target_mix {
+            self.mix = (self.mix - self.mix_step).max(self.target_mix);
+        }
+
+        match self.law {
+            MixLaw::Linear => (self.mix, T::ONE - self.mix),
+            MixLaw::EqualPower => {
+                let angle = self.mix * T::PI * T::HALF;
+                (angle.sin(), angle.cos())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAX_BLOCK_SIZE: usize = 64;
+    const LATENCY: usize = 37;
+
+    /// Run numbered input through the stage in blocks of varying length, with the wet signal
+    /// standing in for an effect that replaces every sample with its negation.
+    fn run(mix: f32) -> (Vec<f32>, Vec<f32>) {
+        let mut stage = MixStage::new(48000.0, 2, MAX_BLOCK_SIZE, LATENCY + 10);
+        stage.set_latency(LATENCY);
+        stage.set_mix(mix);
+        stage.reset();
+
+        let input: Vec<f32> = (1..=2000).map(|n| n as f32).collect();
+        let mut output = Vec::new();
+        let mut start = 0;
+        // Block lengths that don't divide the ring buffer, so reads wrap at every offset
+        for block_len in [64, 17, 1, 50, 64, 33].into_iter().cycle() {
+            let end = (start + block_len).min(input.len());
+            if start == end {
+                break;
+            }
+
+            let block = &input[start..end];
+            let mut channels = [block.to_vec(), block.iter().map(|x| x * 2.0).collect()];
+            stage.capture_dry(&channels);
+            for channel in &mut channels {
+                for sample in channel.iter_mut() {
+                    *sample = -*sample;
+                }
+            }
+            stage.mix_into(&mut channels);
+
+            assert_eq!(
+                channels[1],
+                channels[0].iter().map(|x| x * 2.0).collect::<Vec<_>>()
+            );
+            output.extend_from_slice(&channels[0]);
+            start = end;
+        }
+
+        (input, output)
+    }
+
+    #[test]
+    fn fully_dry_is_the_input_delayed_by_the_latency() {
+        let (input, output) = run(0.0);
+        for (n, &sample) in output.iter().enumerate() {
+            let expected = if n < LATENCY { 0.0 } else { input[n - LATENCY] };
+            assert_eq!(sample, expected, "{n}");
+        }
+    }
+
+    #[test]
+    fn fully_wet_is_the_processed_signal() {
+        let (input, output) = run(1.0);
+        for (n, (&sample, &input)) in output.iter().zip(&input).enumerate() {
+            assert_eq!(sample, -input, "{n}");
+        }
+    }
+}
@@ -0,0 +1,280 @@
+#[cfg(not(feature = "std"))]
+use alloc::{vec, vec::Vec};
+
+use crate::Sample;
+
+/// `ln(1000)`, for decay times measured to -60 dB.
+const LN_1000: f32 = 6.907_755;
+
+/// One resonant mode of a struck object.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Mode<T: Sample = f32> {
+    /// The mode's frequency in Hz.
+    pub frequency: T,
+    /// How long the mode takes to fall by 60 dB, in seconds.
+    pub decay: T,
+    /// The mode's amplitude for a unit strike.
+    pub gain: T,
+}
+
+/// A complex one-pole whose impulse response is an exponentially decaying sine. Rotating the
+/// state instead of running a biquad keeps the pole exactly where it was put even at very long
+/// decays.
+#[derive(Debug, Clone, Copy)]
+struct Resonator<T: Sample> {
+    re: T,
+    im: T,
+    pole_re: T,
+    pole_im: T,
+    gain: T,
+}
+
+impl<T: Sample> Default for Resonator<T> {
+    fn default() -> Self {
+        Self {
+            re: T::ZERO,
+            im: T::ZERO,
+            pole_re: T::ZERO,
+            pole_im: T::ZERO,
+            gain: T::ZERO,
+        }
+    }
+}
+
+/// A bank of exponentially decaying resonators, one per mode, for bells, bars, and other struck
+/// objects. Excite it with a [`Strike`]:
+///
+/// ```ignore
+/// strike.strike(velocity, contact_time);
+/// let sample = modes.process(strike.next_sample());
+/// ```
+///
+/// A unit impulse makes every mode ring at its gain, so the modes' gains set the timbre and
+/// their sum bounds the loudest output for a unit strike.
+#[derive(Debug, Clone)]
+pub struct ModalResonator<T: Sample = f32> {
+    sample_rate: T,
+    resonators: Vec<Resonator<T>>,
+    num_modes: usize,
+}
+
+impl<T: Sample> ModalResonator<T> {
+    /// Allocates, so call this from `initialize()`.
+    pub fn new(sample_rate: T, max_modes: usize) -> Self {
+        Self {
+            sample_rate,
+            resonators: vec![Resonator::default(); max_modes],
+            num_modes: 0,
+        }
+    }
+
+    pub fn max_modes(&self) -> usize {
+        self.resonators.len()
+    }
+
+    /// Tune the bank. Modes beyond [`max_modes()`](Self::max_modes) are ignored, and modes at or
+    /// above Nyquist are silenced. The modes keep ringing, so retuning mid-note bends them.
+    pub fn set_modes(&mut self, modes: &[Mode<T>]) {
+        self.num_modes = modes.len().min(self.resonators.len());
+        let nyquist = self.sample_rate * T::HALF;
+        for (resonator, mode) in self.resonators.iter_mut().zip(modes) {
+            let omega = T::TAU * mode.frequency / self.sample_rate;
+            let decay_samples = (mode.decay * self.sample_rate).max(T::ONE);
+            let radius = (-T::from_f32(LN_1000) / decay_samples).exp();
+            resonator.pole_re = radius * omega.cos();
+            resonator.pole_im = radius * omega.sin();
+            resonator.gain = if mode.frequency < nyquist {
+                mode.gain
+            } else {
+                T::ZERO
+            };
+        }
+    }
+
+    /// Silence every mode.
+    pub fn reset(&mut self) {
+        for resonator in &mut self.resonators {
+            resonator.re = T::ZERO;
+            resonator.im = T::ZERO;
+        }
+    }
+
+    #[inline]
+    pub fn process(&mut self, excitation: T) -> T {
+        let mut output = T::ZERO;
+        for resonator in &mut self.resonators[..self.num_modes] {
+            let re = resonator.re * resonator.pole_re - resonator.im * resonator.pole_im;
+            let im = resonator.re * resonator.pole_im + resonator.im * resonator.pole_re;
+            resonator.re = re + excitation;
+            resonator.im = im;
+            output += im * resonator.gain;
+        }
+
+        output
+    }
+
+    /// The loudest the output can get from here on without another strike. It only falls, so a
+    /// voice can be freed once it drops below the noise floor.
+    pub fn envelope(&self) -> T {
+        self.resonators[..self.num_modes]
+            .iter()
+            .fold(T::ZERO, |sum, resonator| {
+                let magnitude = (resonator.re * resonator.re + resonator.im * resonator.im).sqrt();
+                sum + magnitude * resonator.gain.abs()
+            })
+    }
+}
+
+/// A mallet hitting a [`ModalResonator`], as a half-sine force pulse. The longer the mallet
+/// stays in contact, the softer it sounds: a pulse `contact_time` long barely excites modes
+/// above about `1.5 / contact_time` Hz.
+#[derive(Debug, Clone)]
+pub struct Strike<T: Sample = f32> {
+    sample_rate: T,
+    /// From 0 to 1 over the contact time, 1 once the mallet has left.
+    phase: T,
+    increment: T,
+    amplitude: T,
+}
+
+impl<T: Sample> Strike<T> {
+    pub fn new(sample_rate: T) -> Self {
+        Self {
+            sample_rate,
+            phase: T::ONE,
+            increment: T::ZERO,
+            amplitude: T::ZERO,
+        }
+    }
+
+    /// Start a strike. The pulse's area is `velocity` whatever the contact time, so the lowest
+    /// modes ring at the same level for hard and soft mallets.
+    pub fn strike(&mut self, velocity: T, contact_time: T) {
+        // A whole number of samples, sampled at their midpoints so the area comes out exact
+        let length = (contact_time * self.sample_rate + T::HALF)
+            .floor()
+            .max(T::ONE);
+        self.phase = T::ZERO;
+        self.increment = T::ONE / length;
+        self.amplitude = velocity * (T::PI * T::HALF / length).sin();
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.phase < T::ONE
+    }
+
+    pub fn reset(&mut self) {
+        self.phase = T::ONE;
+    }
+
+    #[inline]
+    pub fn next_sample(&mut self) -> T {
+        if !self.is_active() {
+            return T::ZERO;
+        }
+
+        let sample = self.amplitude * (T::PI * (self.phase + self.increment * T::HALF)).sin();
+        self.phase += self.increment;
+        sample
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: f32 = 48000.0;
+
+    fn peak(samples: &[f32]) -> f32 {
+        samples
+            .iter()
+            .fold(0.0, |peak, sample| f32::max(peak, sample.abs()))
+    }
+
+    fn ring(modes: &[Mode], contact_time: f32, seconds: f32) -> Vec<f32> {
+        let mut resonator = ModalResonator::new(SAMPLE_RATE, 8);
+        resonator.set_modes(modes);
+        let mut strike = Strike::new(SAMPLE_RATE);
+        strike.strike(1.0, contact_time);
+        (0..(seconds * SAMPLE_RATE) as usize)
+            .map(|_| resonator.process(strike.next_sample()))
+            .collect()
+    }
+
+    #[test]
+    fn modes_ring_at_their_frequency_and_decay_time() {
+        let mode = Mode {
+            frequency: 1000.0,
+            decay: 0.5,
+            gain: 1.0,
+        };
+        let output = ring(&[mode], 0.0, 0.6);
+
+        let crossings = output
+            .windows(2)
+            .take(SAMPLE_RATE as usize / 10)
+            .filter(|pair| pair[0] < 0.0 && pair[1] >= 0.0)
+            .count();
+        assert!(crossings.abs_diff(100) <= 1, "{crossings}");
+
+        let start = peak(&output[..480]);
+        let at_decay = peak(&output[24000..24480]);
+        assert!((start - 1.0).abs() < 0.01, "{start}");
+        assert!((at_decay / start * 1000.0 - 1.0).abs() < 0.05, "{at_decay}");
+    }
+
+    #[test]
+    fn soft_strikes_barely_excite_high_modes() {
+        let mode = |frequency| Mode {
+            frequency,
+            decay: 1.0,
+            gain: 1.0,
+        };
+
+        let hard = peak(&ring(&[mode(5000.0)], 0.0001, 0.1));
+        let soft = peak(&ring(&[mode(5000.0)], 0.002, 0.1));
+        assert!(soft < hard * 0.05, "{soft} vs {hard}");
+
+        let hard = peak(&ring(&[mode(50.0)], 0.0001, 0.1));
+        let soft = peak(&ring(&[mode(50.0)], 0.002, 0.1));
+        assert!((soft / hard - 1.0).abs() < 0.05, "{soft} vs {hard}");
+    }
+
+    #[test]
+    fn modes_above_nyquist_are_silent() {
+        let mode = Mode {
+            frequency: 30000.0,
+            decay: 1.0,
+            gain: 1.0,
+        };
+        assert_eq!(peak(&ring(&[mode], 0.0, 0.1)), 0.0);
+    }
+
+    #[test]
+    fn the_envelope_bounds_the_output() {
+        let modes = [
+            Mode {
+                frequency: 220.0,
+                decay: 1.0,
+                gain: 0.5,
+            },
+            Mode {
+                frequency: 607.0,
+                decay: 0.3,
+                gain: 0.3,
+            },
+        ];
+        let mut resonator = ModalResonator::new(SAMPLE_RATE, 8);
+        resonator.set_modes(&modes);
+        resonator.process(1.0);
+
+        let mut previous = resonator.envelope();
+        for _ in 0..SAMPLE_RATE as usize {
+            let sample = resonator.process(0.0);
+            let envelope = resonator.envelope();
+            assert!(sample.abs() <= previous + 1e-6 && envelope <= previous + 1e-6);
+            previous = envelope;
+        }
+        assert!(previous < 1e-3);
+    }
+}
@@ -0,0 +1,60 @@
+use crate::Sample;
+
+/// One routing in a [`ModMatrix`]. Sources and destinations are indices the plugin defines.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ModSlot<T: Sample = f32> {
+    pub source: usize,
+    pub destination: usize,
+    /// Scales the source, negative amounts invert it.
+    pub amount: T,
+}
+
+/// A fixed number of source to destination routings. Each destination receives the sum of every
+/// slot routed to it, scaling that sum to the destination's range is left to the plugin.
+#[derive(Debug, Clone)]
+pub struct ModMatrix<T: Sample = f32, const SLOTS: usize = 4> {
+    slots: [Option<ModSlot<T>>; SLOTS],
+}
+
+impl<T: Sample, const SLOTS: usize> Default for ModMatrix<T, SLOTS> {
+    fn default() -> Self {
+        Self {
+            slots: [None; SLOTS],
+        }
+    }
+}
+
+impl<T: Sample, const SLOTS: usize> ModMatrix<T, SLOTS> {
+    /// Set or clear a slot. Out of range slots are ignored.
+    pub fn set_slot(&mut self, idx: usize, slot: Option<ModSlot<T>>) {
+        if let Some(existing) = self.slots.get_mut(idx) {
+            *existing = slot;
+        }
+    }
+
+    pub fn slot(&self, idx: usize) -> Option<ModSlot<T>> {
+        self.slots.get(idx).copied().flatten()
+    }
+
+    /// Whether any slot routes to `destination`, so unmodulated destinations can be skipped.
+    pub fn is_routed(&self, destination: usize) -> bool {
+        self.slots
+            .iter()
+            .flatten()
+            .any(|slot| slot.destination == destination && slot.amount != T::ZERO)
+    }
+
+    /// Overwrite `destinations` with the modulation from the current `sources` values. Slots that
+    /// refer to a source or destination outside the slices are skipped.
+    pub fn process(&self, sources: &[T], destinations: &mut [T]) {
+        destinations.fill(T::ZERO);
+        for slot in self.slots.iter().flatten() {
+            if let (Some(&source), Some(destination)) = (
+                sources.get(slot.source),
+                destinations.get_mut(slot.destination),
+            ) {
+                *destination += source * slot.amount;
+            }
+        }
+    }
+}
@@ -0,0 +1,294 @@
+//! Oversampling for nonlinear processing, so the harmonics it adds above the original Nyquist
+//! frequency are filtered out instead of folding back down as aliasing.
+//!
+//! Each doubling of the sample rate is a stage with a pair of linear phase half-band filters,
+//! one to remove the images when upsampling and one to band-limit before downsampling. Half of a
+//! half-band filter's taps are zero, which the polyphase implementation skips.
+
+#[cfg(not(feature = "std"))]
+use alloc::{vec, vec::Vec};
+
+use crate::Sample;
+use core::f64::consts::{PI, TAU};
+
+/// The half-band filters' length. The taps either side of the center sit an odd number of
+/// samples away from it, so every other tap is zero.
+const TAPS: usize = 47;
+const CENTER: usize = TAPS / 2;
+/// The number of nonzero taps off the center, which all have even indices.
+const EVEN_TAPS: usize = TAPS / 2 + 1;
+
+/// The even indexed taps of a Blackman windowed half-band filter. The odd indexed taps are all
+/// zero apart from the center one, which is one half.
+fn half_band_taps<T: Sample>() -> Vec<T> {
+    let taps: Vec<f64> = (0..EVEN_TAPS)
+        .map(|k| {
+            let n = 2 * k;
+            let offset = (n as f64 - CENTER as f64) / 2.0;
+            let sinc = Sample::sin(PI * offset) / (PI * offset);
+            let x = TAU * n as f64 / (TAPS - 1) as f64;
+            let window = 0.42 - 0.5 * Sample::cos(x) + 0.08 * Sample::cos(2.0 * x);
+            0.5 * sinc * window
+        })
+        .collect();
+
+    // Normalize so each polyphase branch has a DC gain of exactly one half
+    let sum: f64 = taps.iter().sum();
+    taps.iter()
+        .map(|tap| T::from_f64(tap * 0.5 / sum))
+        .collect()
+}
+
+/// A history of recent samples, written twice so the newest `len` samples can always be read
+/// as one contiguous slice, oldest first.
+#[derive(Debug, Clone)]
+struct History<T: Sample> {
+    samples: Vec<T>,
+    pos: usize,
+}
+
+impl<T: Sample> History<T> {
+    fn new(len: usize) -> Self {
+        Self {
+            samples: vec![T::ZERO; len * 2],
+            pos: 0,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.samples.len() / 2
+    }
+
+    fn reset(&mut self) {
+        self.samples.fill(T::ZERO);
+        self.pos = 0;
+    }
+
+    #[inline]
+    fn push(&mut self, sample: T) {
+        let len = self.len();
+        self.samples[self.pos] = sample;
+        self.samples[self.pos + len] = sample;
+        self.pos = (self.pos + 1) % len;
+    }
+
+    /// The newest `len` samples, oldest first.
+    #[inline]
+    fn window(&self) -> &[T] {
+        &self.samples[self.pos..self.pos + self.len()]
+    }
+
+    /// The sample `age` samples before the newest one.
+    #[inline]
+    fn get(&self, age: usize) -> T {
+        self.window()[self.len() - 1 - age]
+    }
+}
+
+/// The dot product of the even taps with a window of samples in the same order as the taps.
+#[inline]
+fn convolve<T: Sample>(taps: &[T], window: &[T]) -> T {
+    taps.iter()
+        .zip(window)
+        .fold(T::ZERO, |sum, (&tap, &sample)| sum + tap * sample)
+}
+
+/// One doubling of the sample rate.
+#[derive(Debug, Clone)]
+struct Stage<T: Sample> {
+    /// The upsampler's input.
+    up: History<T>,
+    /// The downsampler's input, split into even and odd samples.
+    down_even: History<T>,
+    down_odd: History<T>,
+}
+
+impl<T: Sample> Stage<T> {
+    fn new() -> Self {
+        Self {
+            up: History::new(EVEN_TAPS),
+            down_even: History::new(EVEN_TAPS),
+            down_odd: History::new(EVEN_TAPS),
+        }
+    }
+
+    fn reset(&mut self) {
+        self.up.reset();
+        self.down_even.reset();
+        self.down_odd.reset();
+    }
+
+    /// Turn one sample into two at twice the rate.
+    #[inline]
+    fn upsample(&mut self, taps: &[T], input: T) -> (T, T) {
+        self.up.push(input);
+        // Zero stuffing halves the level, so both branches are doubled. The taps are symmetric,
+        // so the window doesn't need reversing.
+        let first = convolve(taps, self.up.window()) * T::from_f32(2.0);
+        let second = self.up.get(CENTER / 2);
+        (first, second)
+    }
+
+    /// Turn two samples into one at half the rate.
+    #[inline]
+    fn downsample(&mut self, taps: &[T], first: T, second: T) -> T {
+        self.down_even.push(first);
+        self.down_odd.push(second);
+        convolve(taps, self.down_odd.window()) + self.down_even.get(CENTER / 2) * T::HALF
+    }
+}
+
+/// Runs a per-sample process at 2, 4, 8... times the sample rate.
+///
+/// ```
+/// use dsp_core::oversampling::Oversampler;
+///
+/// let mut oversampler = Oversampler::<f32>::new(4);
+/// let output = oversampler.process(0.5, |sample| (sample * 4.0).tanh());
+/// ```
+#[derive(Debug, Clone)]
+pub struct Oversampler<T: Sample = f32> {
+    taps: Vec<T>,
+    stages: Vec<Stage<T>>,
+    /// The number of stages in use.
+    active: usize,
+    /// Ping-pong buffers for one input sample's worth of oversampled samples.
+    buffer: Vec<T>,
+    scratch: Vec<T>,
+}
+
+impl<T: Sample> Oversampler<T> {
+    /// Allocate for factors up to `max_factor`, rounded up to a power of two. Starts out using
+    /// the maximum factor.
+    pub fn new(max_factor: usize) -> Self {
+        let max_factor = max_factor.max(1).next_power_of_two();
+        let num_stages = max_factor.trailing_zeros() as usize;
+        Self {
+            taps: half_band_taps(),
+            stages: (0..num_stages).map(|_| Stage::new()).collect(),
+            active: num_stages,
+            buffer: vec![T::ZERO; max_factor],
+            scratch: vec![T::ZERO; max_factor],
+        }
+    }
+
+    /// Change the factor without allocating, rounded up to a power of two and limited to the
+    /// maximum factor. A factor of one processes at the original rate. Resets the filters when
+    /// the factor changes.
+    pub fn set_factor(&mut self, factor: usize) {
+        let active =
+            (factor.max(1).next_power_of_two().trailing_zeros() as usize).min(self.stages.len());
+        if active != self.active {
+            self.active = active;
+            self.reset();
+        }
+    }
+
+    pub fn factor(&self) -> usize {
+        1 << self.active
+    }
+
+    /// How far the output lags behind the input, in samples at the original rate and rounded
+    /// to the nearest sample. Each stage's pair of filters delays by `TAPS - 2` samples at its
+    /// higher rate.
+    pub fn latency(&self) -> usize {
+        // Summed in units of the highest rate's samples to stay in integers
+        let latency: usize = (1..=self.active)
+            .map(|stage| (TAPS - 2) << (self.active - stage))
+            .sum();
+        (latency + (1 << self.active) / 2) >> self.active
+    }
+
+    pub fn reset(&mut self) {
+        for stage in &mut self.stages {
+            stage.reset();
+        }
+    }
+
+    /// Upsample `input`, run `process` on each oversampled sample, and downsample the result.
+    #[inline]
+    pub fn process(&mut self, input: T, mut process: impl FnMut(T) -> T) -> T {
+        let stages = &mut self.stages[..self.active];
+        self.buffer[0] = input;
+
+        let mut len = 1;
+        for stage in stages.iter_mut() {
+            for i in 0..len {
+                let (first, second) = stage.upsample(&self.taps, self.buffer[i]);
+                self.scratch[2 * i] = first;
+                self.scratch[2 * i + 1] = second;
+            }
+            len *= 2;
+            self.buffer[..len].copy_from_slice(&self.scratch[..len]);
+        }
+
+        for sample in &mut self.buffer[..len] {
+            *sample = process(*sample);
+        }
+
+        for stage in stages.iter_mut().rev() {
+            len /= 2;
+            for i in 0..len {
+                self.scratch[i] =
+                    stage.downsample(&self.taps, self.buffer[2 * i], self.buffer[2 * i + 1]);
+            }
+            self.buffer[..len].copy_from_slice(&self.scratch[..len]);
+        }
+
+        self.buffer[0]
+    }
+
+    pub fn process_block(&mut self, block: &mut [T], mut process: impl FnMut(T) -> T) {
+        for sample in block {
+            *sample = self.process(*sample, &mut process);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn in_band_sines_pass_at_unity_gain_with_the_reported_latency() {
+        let frequency = 1000.0 / 48000.0;
+        let signal = |n: f64| Sample::sin(TAU * frequency * n);
+
+        for factor in [1, 2, 4, 8] {
+            let mut oversampler = Oversampler::<f64>::new(8);
+            oversampler.set_factor(factor);
+            assert_eq!(oversampler.factor(), factor);
+
+            // The 2x stage's delay is a whole number of samples at its own rate but not at the
+            // original rate, so the reported latency is rounded
+            let exact_latency: f64 = (1..=factor.trailing_zeros())
+                .map(|stage| (TAPS - 2) as f64 / (1 << stage) as f64)
+                .sum();
+            assert_eq!(oversampler.latency(), (exact_latency + 0.5) as usize);
+
+            for n in 0..4800 {
+                let output = oversampler.process(signal(n as f64), |sample| sample);
+                if n > 200 {
+                    let expected = signal(n as f64 - exact_latency);
+                    assert!(
+                        (output - expected).abs() < 1e-3,
+                        "{factor}x, {n}: {output} vs {expected}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn changing_the_factor_rounds_to_a_power_of_two_within_the_maximum() {
+        let mut oversampler = Oversampler::<f32>::new(6);
+        assert_eq!(oversampler.factor(), 8);
+        oversampler.set_factor(3);
+        assert_eq!(oversampler.factor(), 4);
+        oversampler.set_factor(64);
+        assert_eq!(oversampler.factor(), 8);
+        oversampler.set_factor(0);
+        assert_eq!(oversampler.factor(), 1);
+        assert_eq!(oversampler.latency(), 0);
+    }
+}
@@ -0,0 +1,337 @@
+//! A phase vocoder pitch shifter. The input is cut into overlapping Hann windowed frames and every
+//! spectral peak is moved to its shifted frequency together with the bins around it, rotating
+//! their phases so they stay coherent from frame to frame. Moving whole peaks keeps each partial's
+//! shape and level intact, unlike shifting bins one by one. The frames are then overlap-added
+//! back together. The output is delayed by [`LATENCY`] samples, which plugins should report to
+//! the host.
+//!
+//! With formant preservation the spectral envelope is estimated from the cepstrum and kept in
+//! place while the harmonics move, so voices keep their character instead of sounding like
+//! chipmunks or giants.
+
+use crate::fft::{wrap_phase, Complex32, Fft, Stft, Window};
+use std::f32::consts::TAU;
+
+pub const FFT_SIZE: usize = 2048;
+
+/// Frames overlap by 75%, the least that keeps the phase estimates reliable.
+const OVERLAP: usize = 4;
+const HOP: usize = FFT_SIZE / OVERLAP;
+const NUM_BINS: usize = FFT_SIZE / 2 + 1;
+
+/// How far the output lags behind the input, in samples.
+pub const LATENCY: usize = FFT_SIZE;
+
+/// The highest quefrency kept in the spectral envelope, in seconds. Harmonics of notes below
+/// about 650 Hz sit above this and are smoothed away, leaving only the formants.
+const ENVELOPE_QUEFRENCY: f32 = 0.0015;
+
+/// The shift ratio is limited to two octaves either way.
+const MIN_RATIO: f32 = 0.25;
+const MAX_RATIO: f32 = 4.0;
+
+#[derive(Clone)]
+pub struct PitchShifter {
+    stft: Stft,
+    shift: SpectralShift,
+}
+
+impl PitchShifter {
+    /// Plans the FFTs and allocates every buffer, so call this from `initialize()`.
+    pub fn new(sample_rate: f32) -> Self {
+        Self {
+            stft: Stft::new(FFT_SIZE, OVERLAP, Window::Hann),
+            shift: SpectralShift::new(sample_rate),
+        }
+    }
+
+    /// Set the shift as a frequency ratio, clamped to two octaves either way.
+    pub fn set_ratio(&mut self, ratio: f32) {
+        self.shift.ratio = ratio.clamp(MIN_RATIO, MAX_RATIO);
+    }
+
+    /// Set the shift in semitones.
+    pub fn set_semitones(&mut self, semitones: f32) {
+        self.set_ratio(2.0f32.powf(semitones / 12.0));
+    }
+
+    pub fn ratio(&self) -> f32 {
+        self.shift.ratio
+    }
+
+    /// Keep the spectral envelope in place while the pitch moves.
+    pub fn set_preserve_formants(&mut self, preserve_formants: bool) {
+        self.shift.preserve_formants = preserve_formants;
+    }
+
+    pub fn reset(&mut self) {
+        self.stft.reset();
+        self.shift.last_phase.fill(0.0);
+        self.shift.rotation.fill(0.0);
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        self.stft
+            .process(input, |spectrum| self.shift.shift(spectrum))
+    }
+
+    pub fn process_block(&mut self, block: &mut [f32]) {
+        self.stft
+            .process_block(block, |spectrum| self.shift.shift(spectrum));
+    }
+}
+
+/// The per-frame state for moving a spectrum's peaks.
+#[derive(Clone)]
+struct SpectralShift {
+    ratio: f32,
+    preserve_formants: bool,
+    /// The number of cepstral coefficients kept for the spectral envelope.
+    lifter_len: usize,
+    /// For the cepstrum.
+    fft: Fft,
+
+    shifted: Vec<Complex32>,
+    envelope_spectrum: Vec<Complex32>,
+    cepstrum: Vec<f32>,
+    magnitude: Vec<f32>,
+    phase: Vec<f32>,
+    /// Each bin's phase in the previous frame.
+    last_phase: Vec<f32>,
+    /// Each bin's measured frequency, in bins.
+    frequency: Vec<f32>,
+    /// The phase rotation applied to the peak that landed on each output bin, carried over to
+    /// the next frame.
+    rotation: Vec<f32>,
+    last_rotation: Vec<f32>,
+    /// The bins holding spectral peaks, with room for every bin so finding them never allocates.
+    peaks: Vec<usize>,
+    envelope: Vec<f32>,
+}
+
+impl SpectralShift {
+    fn new(sample_rate: f32) -> Self {
+        Self {
+            ratio: 1.0,
+            preserve_formants: false,
+            lifter_len: ((ENVELOPE_QUEFRENCY * sample_rate) as usize).clamp(1, FFT_SIZE / 2),
+            fft: Fft::new(FFT_SIZE),
+
+            shifted: vec![Complex32::ZERO; NUM_BINS],
+            envelope_spectrum: vec![Complex32::ZERO; NUM_BINS],
+            cepstrum: vec![0.0; FFT_SIZE],
+            magnitude: vec![0.0; NUM_BINS],
+            phase: vec![0.0; NUM_BINS],
+            last_phase: vec![0.0; NUM_BINS],
+            frequency: vec![0.0; NUM_BINS],
+            rotation: vec![0.0; NUM_BINS],
+            last_rotation: vec![0.0; NUM_BINS],
+            peaks: Vec::with_capacity(NUM_BINS),
+            envelope: vec![1.0; NUM_BINS],
+        }
+    }
+
+    fn shift(&mut self, spectrum: &mut [Complex32]) {
+        if self.ratio == 1.0 {
+            // Unshifted frames pass through untouched, the phases are still tracked so shifting
+            // can resume without a jump
+            for (bin, last_phase) in spectrum.iter().zip(&mut self.last_phase) {
+                *last_phase = bin.arg();
+            }
+            self.rotation.fill(0.0);
+            return;
+        }
+
+        // The phase a bin's center frequency advances by in one hop
+        let expected_advance = TAU * HOP as f32 / FFT_SIZE as f32;
+
+        for (k, bin) in spectrum.iter().enumerate() {
+            let (magnitude, phase) = bin.to_polar();
+            let deviation = wrap_phase(phase - self.last_phase[k] - k as f32 * expected_advance);
+            self.last_phase[k] = phase;
+
+            self.magnitude[k] = magnitude;
+            self.phase[k] = phase;
+            self.frequency[k] = k as f32 + deviation / expected_advance;
+        }
+
+        if self.preserve_formants {
+            self.estimate_envelope();
+        }
+
+        self.peaks.clear();
+        for k in 1..NUM_BINS - 1 {
+            let magnitude = self.magnitude[k];
+            if magnitude > self.magnitude[k - 1] && magnitude >= self.magnitude[k + 1] {
+                self.peaks.push(k);
+            }
+        }
+
+        std::mem::swap(&mut self.rotation, &mut self.last_rotation);
+        self.rotation.fill(0.0);
+        self.shifted.fill(Complex32::ZERO);
+        for (idx, &peak) in self.peaks.iter().enumerate() {
+            // Each peak owns the bins down to the quietest bin between it and its neighbours
+            let start = match idx {
+                0 => 0,
+                _ => self.quietest_bin(self.peaks[idx - 1], peak),
+            };
+            let end = match self.peaks.get(idx + 1) {
+                Some(&next) => self.quietest_bin(peak, next),
+                None => NUM_BINS,
+            };
+
+            // The region moves by whole bins, the rest of the shift is made up by rotating the
+            // phases a little further every frame
+            let shift = self.frequency[peak] * (self.ratio - 1.0);
+            let offset = shift.round() as isize;
+            let Some(&last_rotation) = peak
+                .checked_add_signed(offset)
+                .and_then(|target| self.last_rotation.get(target))
+            else {
+                continue;
+            };
+            let rotation = wrap_phase(last_rotation + expected_advance * shift);
+
+            for k in start..end {
+                let Some(output) = k
+                    .checked_add_signed(offset)
+                    .filter(|&output| output < NUM_BINS)
+                else {
+                    continue;
+                };
+
+                let mut magnitude = self.magnitude[k];
+                if self.preserve_formants {
+                    magnitude *= self.envelope[output] / self.envelope[k];
+                }
+                self.shifted[output] += Complex32::from_polar(magnitude, self.phase[k] + rotation);
+                self.rotation[output] = rotation;
+            }
+        }
+
+        spectrum.copy_from_slice(&self.shifted);
+    }
+
+    /// The quietest bin from `start` up to `end`.
+    fn quietest_bin(&self, start: usize, end: usize) -> usize {
+        (start + 1..end)
+            .min_by(|&a, &b| self.magnitude[a].total_cmp(&self.magnitude[b]))
+            .unwrap_or(end)
+    }
+
+    /// Smooth the log magnitude spectrum by keeping only the low quefrencies of its cepstrum.
+    fn estimate_envelope(&mut self) {
+        for (bin, magnitude) in self.envelope_spectrum.iter_mut().zip(&self.magnitude) {
+            *bin = Complex32::new(magnitude.max(1e-9).ln(), 0.0);
+        }
+        self.fft
+            .inverse(&mut self.envelope_spectrum, &mut self.cepstrum);
+
+        // The cepstrum is symmetric, so the low quefrencies are at both ends
+        let scale = 1.0 / FFT_SIZE as f32;
+        for (i, coefficient) in self.cepstrum.iter_mut().enumerate() {
+            let quefrency = i.min(FFT_SIZE - i);
+            *coefficient = if quefrency < self.lifter_len {
+                *coefficient * scale
+            } else {
+                0.0
+            };
+        }
+
+        self.fft
+            .forward(&mut self.cepstrum, &mut self.envelope_spectrum);
+        for (envelope, bin) in self.envelope.iter_mut().zip(&self.envelope_spectrum) {
+            *envelope = bin.re.exp();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: f32 = 48000.0;
+
+    fn shift(shifter: &mut PitchShifter, input: &[f32]) -> Vec<f32> {
+        let mut output = input.to_vec();
+        for block in output.chunks_mut(256) {
+            shifter.process_block(block);
+        }
+        output
+    }
+
+    /// A tone with 20 equally loud harmonics, so its spectral envelope is roughly flat.
+    fn harmonic_tone(frequency: f32, len: usize) -> Vec<f32> {
+        (0..len)
+            .map(|n| {
+                (1..=20)
+                    .map(|harmonic| {
+                        let phase = TAU * frequency * harmonic as f32 * n as f32 / SAMPLE_RATE;
+                        0.05 * phase.sin()
+                    })
+                    .sum()
+            })
+            .collect()
+    }
+
+    /// The average frequency over the signal's rising zero crossings.
+    fn zero_crossing_frequency(signal: &[f32]) -> f32 {
+        let crossings: Vec<f32> = signal
+            .windows(2)
+            .enumerate()
+            .filter(|(_, pair)| pair[0] < 0.0 && pair[1] >= 0.0)
+            .map(|(i, pair)| i as f32 + pair[0] / (pair[0] - pair[1]))
+            .collect();
+        let periods = crossings.len() - 1;
+        SAMPLE_RATE * periods as f32 / (crossings[periods] - crossings[0])
+    }
+
+    fn rms(signal: &[f32]) -> f32 {
+        (signal.iter().map(|x| x * x).sum::<f32>() / signal.len() as f32).sqrt()
+    }
+
+    #[test]
+    fn a_fifth_up_moves_a_sine_by_a_fifth() {
+        let input: Vec<f32> = (0..SAMPLE_RATE as usize)
+            .map(|n| 0.5 * (TAU * 220.0 * n as f32 / SAMPLE_RATE).sin())
+            .collect();
+        let mut shifter = PitchShifter::new(SAMPLE_RATE);
+        shifter.set_semitones(7.0);
+
+        let output = shift(&mut shifter, &input);
+        let frequency = zero_crossing_frequency(&output[LATENCY * 2..]);
+        let expected = 220.0 * 2f32.powf(7.0 / 12.0);
+        assert!((frequency - expected).abs() < 1.0, "{frequency}");
+    }
+
+    #[test]
+    fn no_shift_passes_the_input_through_delayed() {
+        let input = harmonic_tone(220.0, LATENCY * 3);
+        let mut shifter = PitchShifter::new(SAMPLE_RATE);
+        shifter.set_ratio(1.0);
+
+        let output = shift(&mut shifter, &input);
+        for (n, &sample) in output.iter().enumerate() {
+            let expected = if n < LATENCY { 0.0 } else { input[n - LATENCY] };
+            assert!(
+                (sample - expected).abs() < 1e-5,
+                "{n}: {sample} vs {expected}"
+            );
+        }
+    }
+
+    #[test]
+    fn formant_preservation_keeps_the_level() {
+        let input = harmonic_tone(220.0, SAMPLE_RATE as usize);
+        for semitones in [-5.0, 5.0] {
+            let mut shifter = PitchShifter::new(SAMPLE_RATE);
+            shifter.set_semitones(semitones);
+            shifter.set_preserve_formants(true);
+
+            let output = shift(&mut shifter, &input);
+            let gain_db = 20.0 * (rms(&output[LATENCY * 2..]) / rms(&input)).log10();
+            assert!(gain_db.abs() < 1.0, "{semitones}: {gain_db} dB");
+        }
+    }
+}
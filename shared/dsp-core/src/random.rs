@@ -0,0 +1,33 @@
+/// A small, fast xorshift generator for audio-rate randomness. Not suitable for anything that
+/// needs statistically strong or unpredictable numbers, but the same seed always produces the
+/// same sequence, which keeps renders reproducible.
+#[derive(Debug, Clone)]
+pub struct Xorshift32 {
+    state: u32,
+}
+
+impl Xorshift32 {
+    /// A zero seed would get stuck at zero, so it's replaced by a fixed non-zero value.
+    pub fn new(seed: u32) -> Self {
+        Self {
+            state: if seed == 0 { 0x9E37_79B9 } else { seed },
+        }
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 17;
+        self.state ^= self.state << 5;
+        self.state
+    }
+
+    /// A value in `[0, 1)`.
+    pub fn next_unipolar(&mut self) -> f32 {
+        (self.next_u32() >> 8) as f32 / (1u32 << 24) as f32
+    }
+
+    /// A value in `[-1, 1)`.
+    pub fn next_bipolar(&mut self) -> f32 {
+        self.next_unipolar() * 2.0 - 1.0
+    }
+}
@@ -0,0 +1,412 @@
+//! Sample rate conversion by arbitrary ratios, for playing files recorded at another rate,
+//! pitching samples, and rendering offline.
+//!
+//! The resampler evaluates a Kaiser windowed sinc at the fractional position of each output
+//! sample. The filter is tabulated at a fixed number of phases per input sample, and outputs
+//! between two phases mix the taps of both, so any ratio works without a table per ratio. When
+//! converting down, the sinc is stretched to cut off below the output's Nyquist frequency.
+
+#[cfg(not(feature = "std"))]
+use alloc::{vec, vec::Vec};
+
+use crate::Sample;
+use core::f64::consts::PI;
+
+/// Ratios are limited to this range, which keeps the filter a reasonable length.
+pub const MIN_RATIO: f64 = 1.0 / 16.0;
+pub const MAX_RATIO: f64 = 16.0;
+
+/// Trades the filter's length and accuracy against CPU use.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ResamplerQuality {
+    /// Short filters for realtime pitching of many voices, with errors around -70 dB.
+    Low,
+    /// Transparent for playback, with errors below -100 dB.
+    #[default]
+    Medium,
+    /// For offline rendering, with errors below -140 dB.
+    High,
+}
+
+impl ResamplerQuality {
+    /// The sinc's zero crossings on either side of the center.
+    fn zero_crossings(self) -> usize {
+        match self {
+            Self::Low => 12,
+            Self::Medium => 32,
+            Self::High => 64,
+        }
+    }
+
+    /// The filter is tabulated at this many phases per input sample.
+    fn phases(self) -> usize {
+        match self {
+            Self::Low => 64,
+            Self::Medium => 512,
+            Self::High => 2048,
+        }
+    }
+
+    /// The Kaiser window's shape. Higher values have lower sidelobes and a wider transition.
+    fn beta(self) -> f64 {
+        match self {
+            Self::Low => 6.0,
+            Self::Medium => 10.0,
+            Self::High => 14.0,
+        }
+    }
+
+    /// The cutoff as a fraction of the lower of the two Nyquist frequencies. The transition
+    /// band sits above it, so the top of the audio band is kept at the usual rates.
+    fn rolloff(self) -> f64 {
+        match self {
+            Self::Low => 0.8,
+            Self::Medium => 0.88,
+            Self::High => 0.92,
+        }
+    }
+}
+
+/// `Sample` has no `ceil()`, and `f64` doesn't either without the standard library.
+fn ceil(x: f64) -> f64 {
+    -Sample::floor(-x)
+}
+
+/// The modified Bessel function of the first kind of order zero, for the Kaiser window.
+fn bessel_i0(x: f64) -> f64 {
+    let half = x * 0.5;
+    let mut sum = 1.0;
+    let mut term = 1.0;
+    let mut k = 1.0;
+    while term > sum * 1e-17 {
+        term *= (half / k) * (half / k);
+        sum += term;
+        k += 1.0;
+    }
+    sum
+}
+
+/// Converts a stream of samples from one rate to another.
+///
+/// Output sample `k` is the input evaluated at `k / ratio` input samples, so the two streams
+/// line up exactly apart from the latency, which is [`latency()`][Self::latency()] input samples
+/// of waiting before each output has the inputs it needs.
+///
+/// ```
+/// use dsp_core::resampler::{Resampler, ResamplerQuality};
+///
+/// let mut resampler = Resampler::<f32>::from_rates(44100.0, 48000.0, ResamplerQuality::Medium);
+/// let input = [0.0; 441];
+/// let mut output = [0.0; 512];
+/// let (consumed, produced) = resampler.process(&input, &mut output);
+/// assert_eq!(consumed, 441);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Resampler<T: Sample = f32> {
+    /// Output samples per input sample.
+    ratio: f64,
+    /// Input samples per output sample.
+    step: f64,
+    /// The filter's reach on either side of the center, in input samples.
+    half_width: usize,
+    phases: usize,
+    /// `phases + 1` rows of `2 * half_width` taps, one row per phase, ordered to line up with
+    /// the history's oldest first window.
+    kernel: Vec<T>,
+    /// The last `2 * half_width` inputs, written twice so they can be read as one slice.
+    history: Vec<T>,
+    pos: usize,
+    /// The next output's position in input samples, relative to the newest input.
+    time: f64,
+}
+
+impl<T: Sample> Resampler<T> {
+    /// Allocates the filter table, so call this from `initialize()`. The ratio is output samples
+    /// per input sample, limited to [`MIN_RATIO`] to [`MAX_RATIO`].
+    pub fn new(ratio: f64, quality: ResamplerQuality) -> Self {
+        let ratio = ratio.clamp(MIN_RATIO, MAX_RATIO);
+        let cutoff = 0.5 * ratio.min(1.0) * quality.rolloff();
+        let half_width = ceil(quality.zero_crossings() as f64 / (2.0 * cutoff)) as usize;
+        let phases = quality.phases();
+        let taps = 2 * half_width;
+
+        let beta = quality.beta();
+        let window_scale = 1.0 / bessel_i0(beta);
+        let mut kernel = Vec::with_capacity((phases + 1) * taps);
+        for phase in 0..=phases {
+            let frac = phase as f64 / phases as f64;
+            for i in 0..taps {
+                // The distance from the output to the input this tap weights
+                let x = frac + half_width as f64 - 1.0 - i as f64;
+                let edge = x / half_width as f64;
+                let tap = if edge.abs() >= 1.0 {
+                    0.0
+                } else {
+                    let arg = 2.0 * PI * cutoff * x;
+                    let sinc = if arg == 0.0 {
+                        1.0
+                    } else {
+                        Sample::sin(arg) / arg
+                    };
+                    let window = bessel_i0(beta * Sample::sqrt(1.0 - edge * edge)) * window_scale;
+                    2.0 * cutoff * sinc * window
+                };
+                kernel.push(T::from_f64(tap));
+            }
+        }
+
+        Self {
+            ratio,
+            step: 1.0 / ratio,
+            half_width,
+            phases,
+            kernel,
+            history: vec![T::ZERO; taps * 2],
+            pos: 0,
+            time: 1.0,
+        }
+    }
+
+    /// Converts from `input_rate` to `output_rate`.
+    pub fn from_rates(input_rate: f64, output_rate: f64, quality: ResamplerQuality) -> Self {
+        Self::new(output_rate / input_rate, quality)
+    }
+
+    /// Change the ratio without allocating, for pitching. The filter keeps the cutoff it was
+    /// built with, so construct it with the lowest ratio that will be used or higher ratios
+    /// alias when converting down.
+    pub fn set_ratio(&mut self, ratio: f64) {
+        self.ratio = ratio.clamp(MIN_RATIO, MAX_RATIO);
+        self.step = 1.0 / self.ratio;
+    }
+
+    pub fn ratio(&self) -> f64 {
+        self.ratio
+    }
+
+    /// How long each output waits for the inputs after it, in input samples.
+    pub fn latency(&self) -> usize {
+        self.half_width
+    }
+
+    /// Clear the history and line the next input up with the next output again.
+    pub fn reset(&mut self) {
+        self.history.fill(T::ZERO);
+        self.pos = 0;
+        self.time = 1.0;
+    }
+
+    /// The number of outputs `num_inputs` more inputs will produce, give or take one.
+    pub fn output_len(&self, num_inputs: usize) -> usize {
+        ceil((num_inputs as f64 - self.time) * self.ratio).max(0.0) as usize
+    }
+
+    #[inline]
+    fn push(&mut self, sample: T) {
+        let len = self.history.len() / 2;
+        self.history[self.pos] = sample;
+        self.history[self.pos + len] = sample;
+        self.pos = (self.pos + 1) % len;
+        self.time -= 1.0;
+    }
+
+    /// Whether the history holds every input the next output needs.
+    #[inline]
+    fn output_ready(&self) -> bool {
+        self.time < 1.0 - self.half_width as f64
+    }
+
+    /// The next output, which must be [ready][Self::output_ready()].
+    #[inline]
+    fn next_output(&mut self) -> T {
+        let taps = self.history.len() / 2;
+        let window = &self.history[self.pos..self.pos + taps];
+
+        // The output sits between the newest input `half_width` samples back and the next one
+        let frac = (self.time + self.half_width as f64) * self.phases as f64;
+        let phase = (frac as usize).min(self.phases - 1);
+        let mix = T::from_f64(frac - phase as f64);
+        let current = &self.kernel[phase * taps..(phase + 1) * taps];
+        let next = &self.kernel[(phase + 1) * taps..(phase + 2) * taps];
+
+        let (a, b) = window.iter().zip(current).zip(next).fold(
+            (T::ZERO, T::ZERO),
+            |(a, b), ((&sample, &current), &next)| (a + sample * current, b + sample * next),
+        );
+        self.time += self.step;
+        a + (b - a) * mix
+    }
+
+    /// Resample as much of `input` as fits in `output`. Returns how many input samples were
+    /// consumed and how many output samples were written. Stops when the input runs out or the
+    /// output is full, and carries on where it left off with the next call.
+    pub fn process(&mut self, input: &[T], output: &mut [T]) -> (usize, usize) {
+        let mut consumed = 0;
+        let mut produced = 0;
+        loop {
+            while produced < output.len() && self.output_ready() {
+                output[produced] = self.next_output();
+                produced += 1;
+            }
+            if consumed == input.len() || produced == output.len() {
+                return (consumed, produced);
+            }
+            self.push(input[consumed]);
+            consumed += 1;
+        }
+    }
+}
+
+/// Resample a whole signal from `input_rate` to `output_rate`, flushing the filter at the end so
+/// nothing is cut off. The output starts at the same time as the input.
+pub fn resample<T: Sample>(
+    input: &[T],
+    input_rate: f64,
+    output_rate: f64,
+    quality: ResamplerQuality,
+) -> Vec<T> {
+    let mut resampler = Resampler::from_rates(input_rate, output_rate, quality);
+    let flush = vec![T::ZERO; resampler.latency()];
+    let mut output = vec![T::ZERO; resampler.output_len(input.len() + flush.len()) + 1];
+
+    let (_, produced) = resampler.process(input, &mut output);
+    let (_, flushed) = resampler.process(&flush, &mut output[produced..]);
+
+    // Everything from the end of the input on is only the filter ringing out
+    let len = ceil(input.len() as f64 * resampler.ratio()) as usize;
+    output.truncate(len.min(produced + flushed));
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Resample a sum of sines at a few in-band frequencies and compare it to the same sines
+    /// computed at the output rate. The highest frequency is `top` times the lower of the two
+    /// Nyquist frequencies. The edges, where the input starts and stops abruptly, are left out.
+    fn snr_db(input_rate: f64, output_rate: f64, quality: ResamplerQuality, top: f64) -> f64 {
+        let nyquist = 0.5 * input_rate.min(output_rate);
+        let frequencies = [0.01, 0.3, top];
+        let signal = |time: f64| {
+            frequencies
+                .iter()
+                .enumerate()
+                .map(|(i, fraction)| {
+                    let phase = 2.0 * PI * fraction * nyquist * time + i as f64;
+                    Sample::sin(phase) / frequencies.len() as f64
+                })
+                .sum::<f64>()
+        };
+
+        let length = 16384;
+        let input: Vec<f64> = (0..length).map(|n| signal(n as f64 / input_rate)).collect();
+        let output = resample(&input, input_rate, output_rate, quality);
+        assert_eq!(
+            output.len(),
+            (length as f64 * output_rate / input_rate).ceil() as usize
+        );
+
+        let edge = output.len() / 8;
+        let (signal_power, noise_power) = output[edge..output.len() - edge]
+            .iter()
+            .enumerate()
+            .fold((0.0, 0.0), |(signal_power, noise_power), (k, &sample)| {
+                let reference = signal((k + edge) as f64 / output_rate);
+                let error = sample - reference;
+                (
+                    signal_power + reference * reference,
+                    noise_power + error * error,
+                )
+            });
+        10.0 * Sample::ln(signal_power / noise_power) / core::f64::consts::LN_10
+    }
+
+    #[test]
+    fn low_quality_snr() {
+        for (from, to) in [(44100.0, 48000.0), (48000.0, 44100.0), (96000.0, 44100.0)] {
+            let snr = snr_db(from, to, ResamplerQuality::Low, 0.5);
+            assert!(snr > 65.0, "{from} Hz to {to} Hz: {snr} dB");
+        }
+    }
+
+    #[test]
+    fn medium_quality_snr() {
+        for (from, to) in [(44100.0, 48000.0), (48000.0, 44100.0), (96000.0, 44100.0)] {
+            let snr = snr_db(from, to, ResamplerQuality::Medium, 0.7);
+            assert!(snr > 100.0, "{from} Hz to {to} Hz: {snr} dB");
+        }
+    }
+
+    #[test]
+    fn high_quality_snr() {
+        for (from, to) in [(44100.0, 48000.0), (48000.0, 44100.0), (96000.0, 44100.0)] {
+            let snr = snr_db(from, to, ResamplerQuality::High, 0.7);
+            assert!(snr > 140.0, "{from} Hz to {to} Hz: {snr} dB");
+        }
+    }
+
+    #[test]
+    fn same_rate_passes_the_signal_through() {
+        let input: Vec<f64> = (0..1000).map(|n| Sample::sin(n as f64 * 0.1)).collect();
+        let output = resample(&input, 48000.0, 48000.0, ResamplerQuality::Medium);
+        assert_eq!(output.len(), input.len());
+        // The filter still rings where the sine starts and stops
+        let edge = 100;
+        for (&a, &b) in input
+            .iter()
+            .zip(&output)
+            .skip(edge)
+            .take(input.len() - 2 * edge)
+        {
+            assert!((a - b).abs() < 1e-5, "{a} {b}");
+        }
+    }
+
+    #[test]
+    fn removes_content_above_the_output_nyquist_frequency() {
+        // 30 kHz can't be represented at 44.1 kHz, so it has to go instead of folding down
+        let input: Vec<f64> = (0..16384)
+            .map(|n| Sample::sin(2.0 * PI * 30000.0 * n as f64 / 96000.0))
+            .collect();
+        let output = resample(&input, 96000.0, 44100.0, ResamplerQuality::Medium);
+        let edge = output.len() / 8;
+        let peak = output[edge..output.len() - edge]
+            .iter()
+            .fold(0.0f64, |peak, &sample| peak.max(sample.abs()));
+        assert!(peak < 1e-5, "{peak}");
+    }
+
+    #[test]
+    fn streaming_matches_offline() {
+        let input: Vec<f64> = (0..4000).map(|n| Sample::sin(n as f64 * 0.05)).collect();
+        let offline = resample(&input, 44100.0, 48000.0, ResamplerQuality::Low);
+
+        let mut resampler = Resampler::from_rates(44100.0, 48000.0, ResamplerQuality::Low);
+        let mut streamed = Vec::new();
+        let mut output = [0.0; 64];
+        let mut remaining = &input[..];
+        // Blocks of awkward sizes, with the output filling up before the input runs out
+        for size in (1..).map(|i| (i * 37) % 100 + 1) {
+            let block = &remaining[..size.min(remaining.len())];
+            let mut offset = 0;
+            while offset < block.len() {
+                let (consumed, produced) = resampler.process(&block[offset..], &mut output);
+                streamed.extend_from_slice(&output[..produced]);
+                offset += consumed;
+            }
+            remaining = &remaining[block.len()..];
+            if remaining.is_empty() {
+                break;
+            }
+        }
+
+        let latency = resampler.latency();
+        let ready = streamed.len();
+        assert!(ready <= offline.len());
+        assert!(ready + 2 * latency >= offline.len());
+        for (&a, &b) in streamed.iter().zip(&offline) {
+            assert!((a - b).abs() < 1e-12);
+        }
+    }
+}
@@ -0,0 +1,169 @@
+use core::fmt::Debug;
+use core::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub, SubAssign};
+
+/// A floating point type the DSP building blocks can run at. Implemented for `f32` and `f64` so
+/// feedback-heavy stages can run in double precision while plugin I/O stays `f32`.
+pub trait Sample:
+    Copy
+    + Default
+    + Debug
+    + PartialEq
+    + PartialOrd
+    + Send
+    + Sync
+    + 'static
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + Mul<Output = Self>
+    + Div<Output = Self>
+    + Neg<Output = Self>
+    + AddAssign
+    + SubAssign
+    + MulAssign
+    + DivAssign
+{
+    const ZERO: Self;
+    const ONE: Self;
+    const HALF: Self;
+    const PI: Self;
+    const TAU: Self;
+
+    fn from_f32(value: f32) -> Self;
+    fn from_f64(value: f64) -> Self;
+    fn to_f32(self) -> f32;
+    fn to_f64(self) -> f64;
+
+    fn abs(self) -> Self;
+    fn sqrt(self) -> Self;
+    fn sin(self) -> Self;
+    fn cos(self) -> Self;
+    fn tan(self) -> Self;
+    fn tanh(self) -> Self;
+    fn exp(self) -> Self;
+    fn ln(self) -> Self;
+    fn powf(self, exponent: Self) -> Self;
+    fn floor(self) -> Self;
+    fn min(self, other: Self) -> Self;
+    fn max(self, other: Self) -> Self;
+    fn clamp(self, min: Self, max: Self) -> Self;
+    fn is_finite(self) -> bool;
+}
+
+/// Call the inherent `std` float method, or the `libm` equivalent in `no_std` builds.
+macro_rules! math {
+    ($ty:ident, $std_fn:ident, $libm_fn:ident($($arg:expr),*)) => {{
+        #[cfg(feature = "std")]
+        {
+            $ty::$std_fn($($arg),*)
+        }
+        #[cfg(not(feature = "std"))]
+        {
+            libm::Libm::<$ty>::$libm_fn($($arg),*)
+        }
+    }};
+}
+
+macro_rules! impl_sample {
+    ($ty:ident) => {
+        impl Sample for $ty {
+            const ZERO: Self = 0.0;
+            const ONE: Self = 1.0;
+            const HALF: Self = 0.5;
+            const PI: Self = core::$ty::consts::PI;
+            const TAU: Self = core::$ty::consts::TAU;
+
+            #[inline]
+            fn from_f32(value: f32) -> Self {
+                value as $ty
+            }
+
+            #[inline]
+            fn from_f64(value: f64) -> Self {
+                value as $ty
+            }
+
+            #[inline]
+            fn to_f32(self) -> f32 {
+                self as f32
+            }
+
+            #[inline]
+            fn to_f64(self) -> f64 {
+                self as f64
+            }
+
+            #[inline]
+            fn abs(self) -> Self {
+                math!($ty, abs, fabs(self))
+            }
+
+            #[inline]
+            fn sqrt(self) -> Self {
+                math!($ty, sqrt, sqrt(self))
+            }
+
+            #[inline]
+            fn sin(self) -> Self {
+                math!($ty, sin, sin(self))
+            }
+
+            #[inline]
+            fn cos(self) -> Self {
+                math!($ty, cos, cos(self))
+            }
+
+            #[inline]
+            fn tan(self) -> Self {
+                math!($ty, tan, tan(self))
+            }
+
+            #[inline]
+            fn tanh(self) -> Self {
+                math!($ty, tanh, tanh(self))
+            }
+
+            #[inline]
+            fn exp(self) -> Self {
+                math!($ty, exp, exp(self))
+            }
+
+            #[inline]
+            fn ln(self) -> Self {
+                math!($ty, ln, log(self))
+            }
+
+            #[inline]
+            fn powf(self, exponent: Self) -> Self {
+                math!($ty, powf, pow(self, exponent))
+            }
+
+            #[inline]
+            fn floor(self) -> Self {
+                math!($ty, floor, floor(self))
+            }
+
+            #[inline]
+            fn min(self, other: Self) -> Self {
+                $ty::min(self, other)
+            }
+
+            #[inline]
+            fn max(self, other: Self) -> Self {
+                $ty::max(self, other)
+            }
+
+            #[inline]
+            fn clamp(self, min: Self, max: Self) -> Self {
+                $ty::clamp(self, min, max)
+            }
+
+            #[inline]
+            fn is_finite(self) -> bool {
+                $ty::is_finite(self)
+            }
+        }
+    };
+}
+
+impl_sample!(f32);
+impl_sample!(f64);
@@ -0,0 +1,168 @@
+//! Mid/side conversion and stereo image adjustments.
+//!
+//! The mid signal is what the two channels have in common and the side signal is what differs
+//! between them. Both are scaled by one half, so decoding an encoded pair gives back the input.
+
+use crate::crossover::LinkwitzRiley;
+use crate::Sample;
+
+/// Convert a left/right pair to mid/side.
+#[inline]
+pub fn encode_mid_side<T: Sample>(left: T, right: T) -> (T, T) {
+    ((left + right) * T::HALF, (left - right) * T::HALF)
+}
+
+/// Convert a mid/side pair back to left/right.
+#[inline]
+pub fn decode_mid_side<T: Sample>(mid: T, side: T) -> (T, T) {
+    (mid + side, mid - side)
+}
+
+/// Scale the difference between the channels. A width of 0 folds the pair to mono, 1 leaves it
+/// unchanged, and 2 doubles the side signal.
+#[inline]
+pub fn set_width<T: Sample>(left: T, right: T, width: T) -> (T, T) {
+    let (mid, side) = encode_mid_side(left, right);
+    decode_mid_side(mid, side * width)
+}
+
+/// Rotate the stereo image by `angle` radians, like turning a coincident microphone pair.
+/// Positive angles move sources towards the right. A quarter turn swaps the channels with one of
+/// them inverted, so useful angles stay well within that.
+#[inline]
+pub fn rotate<T: Sample>(left: T, right: T, angle: T) -> (T, T) {
+    let (sin, cos) = (angle.sin(), angle.cos());
+    (left * cos - right * sin, left * sin + right * cos)
+}
+
+/// The left and right gains for a balance from -1 (left only) to 1 (right only). Unlike a pan
+/// law, balance only ever turns one side down, so the center is unity gain.
+#[inline]
+pub fn balance_gains<T: Sample>(balance: T) -> (T, T) {
+    let balance = balance.clamp(-T::ONE, T::ONE);
+    (
+        (T::ONE - balance).min(T::ONE),
+        (T::ONE + balance).min(T::ONE),
+    )
+}
+
+/// Folds everything below a frequency to mono by high passing the side signal, like the
+/// elliptic filter used when cutting vinyl. Keeps low end centered and mono compatible while
+/// leaving the width above it alone.
+#[derive(Debug, Clone)]
+pub struct MonoBass<T: Sample = f32> {
+    filter: LinkwitzRiley<T>,
+}
+
+impl<T: Sample> MonoBass<T> {
+    pub fn new(sample_rate: T, frequency: T) -> Self {
+        Self {
+            filter: LinkwitzRiley::new(sample_rate, frequency),
+        }
+    }
+
+    pub fn set_frequency(&mut self, frequency: T) {
+        self.filter.set_frequency(frequency);
+    }
+
+    pub fn frequency(&self) -> T {
+        self.filter.frequency()
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: T) {
+        self.filter.set_sample_rate(sample_rate);
+    }
+
+    pub fn reset(&mut self) {
+        self.filter.reset();
+    }
+
+    #[inline]
+    pub fn process(&mut self, left: T, right: T) -> (T, T) {
+        let (mid, side) = encode_mid_side(left, right);
+        let (_, side) = self.filter.process(side);
+        decode_mid_side(mid, side)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: f64 = 48000.0;
+    const PAIRS: [(f64, f64); 5] = [
+        (1.0, 0.0),
+        (0.0, 1.0),
+        (0.5, -0.25),
+        (-0.75, -0.75),
+        (0.3, 0.9),
+    ];
+
+    fn assert_close(actual: (f64, f64), expected: (f64, f64)) {
+        assert!(
+            (actual.0 - expected.0).abs() < 1e-12 && (actual.1 - expected.1).abs() < 1e-12,
+            "{actual:?} vs {expected:?}"
+        );
+    }
+
+    #[test]
+    fn decoding_undoes_encoding() {
+        for (left, right) in PAIRS {
+            let (mid, side) = encode_mid_side(left, right);
+            assert_close(decode_mid_side(mid, side), (left, right));
+        }
+    }
+
+    #[test]
+    fn widths_fold_keep_and_widen() {
+        for (left, right) in PAIRS {
+            let (mono_left, mono_right) = set_width(left, right, 0.0);
+            assert_eq!(mono_left, mono_right);
+            let mid = (left + right) / 2.0;
+            assert_close((mono_left, mono_right), (mid, mid));
+
+            assert_close(set_width(left, right, 1.0), (left, right));
+            let (_, side) = encode_mid_side(left, right);
+            let (wide_left, wide_right) = set_width(left, right, 2.0);
+            assert!((encode_mid_side(wide_left, wide_right).1 - 2.0 * side).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn balance_only_turns_one_side_down() {
+        assert_eq!(balance_gains(-1.0), (1.0, 0.0));
+        assert_eq!(balance_gains(0.0), (1.0, 1.0));
+        assert_eq!(balance_gains(1.0), (0.0, 1.0));
+        assert_eq!(balance_gains(0.5), (0.5, 1.0));
+        assert_eq!(balance_gains(-3.0), (1.0, 0.0));
+    }
+
+    /// The peak levels of the mid and side signals after settling.
+    fn mid_side_peaks(mono_bass: &mut MonoBass<f64>, frequency: f64) -> (f64, f64) {
+        let omega = core::f64::consts::TAU * frequency / SAMPLE_RATE;
+        let (mut mid_peak, mut side_peak) = (0.0f64, 0.0f64);
+        for n in 0..SAMPLE_RATE as usize {
+            // Only in the left channel, so half of it is side
+            let (left, right) = mono_bass.process((omega * n as f64).sin(), 0.0);
+            if n > SAMPLE_RATE as usize / 2 {
+                let (mid, side) = encode_mid_side(left, right);
+                mid_peak = mid_peak.max(mid.abs());
+                side_peak = side_peak.max(side.abs());
+            }
+        }
+        (mid_peak, side_peak)
+    }
+
+    #[test]
+    fn mono_bass_removes_low_side_content_only() {
+        let mut mono_bass = MonoBass::new(SAMPLE_RATE, 150.0);
+        let (mid, side) = mid_side_peaks(&mut mono_bass, 30.0);
+        assert!((mid - 0.5).abs() < 1e-3, "{mid}");
+        assert!(side < 0.01, "{side}");
+
+        mono_bass.reset();
+        let (mid, side) = mid_side_peaks(&mut mono_bass, 3000.0);
+        assert!((mid - 0.5).abs() < 1e-3, "{mid}");
+        assert!((side - 0.5).abs() < 0.01, "{side}");
+    }
+}
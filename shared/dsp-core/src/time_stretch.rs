@@ -0,0 +1,495 @@
+//! Offline time stretching and pitch shifting of whole buffers, for fitting samples to a tempo
+//! and for rendering. These aren't realtime: they allocate, and they look at the whole signal
+//! before producing any output.
+//!
+//! The stretch is a phase vocoder with identity phase locking. Spectral peaks have their phases
+//! advanced at their measured frequencies, and the bins around each peak keep their phase
+//! relative to it, which avoids most of the phasiness of a plain phase vocoder.
+//!
+//! A plain phase vocoder also smears transients. Every frame that overlaps a drum hit carries a
+//! copy of it, and stretching moves those copies apart. So the onsets are found first, and the
+//! frames around each one are taken at the original spacing with their phases reset to the
+//! input's. That copies every attack through unchanged, and the stretching is made up in between
+//! the hits.
+//!
+//! Pitch shifting stretches by the shift ratio and resamples back to the original length.
+
+use crate::fft::{wrap_phase, Complex32, Fft, Window};
+use crate::resampler::{resample, ResamplerQuality};
+use std::f32::consts::TAU;
+
+const FFT_SIZE: usize = 2048;
+const HALF_FRAME: usize = FFT_SIZE / 2;
+/// Frames overlap by 75%, the least that keeps the phase estimates reliable.
+const OVERLAP: usize = 4;
+/// The spacing of the output frames. The input frames' spacing varies.
+const HOP: usize = FFT_SIZE / OVERLAP;
+const NUM_BINS: usize = FFT_SIZE / 2 + 1;
+
+/// The onsets are found with shorter frames, which pin them down to within half a frame.
+const ONSET_FFT_SIZE: usize = 512;
+const ONSET_HOP: usize = ONSET_FFT_SIZE / 4;
+/// How much of a frame's spectrum has to be new for it to count as an onset.
+const ONSET_THRESHOLD: f32 = 0.5;
+/// Frames quieter than this relative to the loudest frame are never onsets, so noise in the
+/// fades doesn't count.
+const ONSET_FLOOR: f32 = 1e-3;
+
+/// Onsets are pinned down to the sharpest rise in level between blocks this long.
+const ONSET_BLOCK: usize = 16;
+
+/// How far either side of an onset the input is copied at its original speed. It covers every
+/// frame that could contain the onset, with some room for finding it a little late.
+const RIGID_RADIUS: usize = HALF_FRAME + ONSET_HOP;
+
+/// Ratios are limited to two octaves or two tempo doublings either way.
+pub const MIN_RATIO: f32 = 0.25;
+pub const MAX_RATIO: f32 = 4.0;
+
+/// Stretch `input` to `ratio` times its length without changing its pitch, e.g. 2 for half
+/// speed. The ratio is limited to [`MIN_RATIO`] to [`MAX_RATIO`].
+pub fn stretch(input: &[f32], ratio: f32) -> Vec<f32> {
+    let ratio = ratio.clamp(MIN_RATIO, MAX_RATIO);
+    let output_len = (input.len() as f64 * ratio as f64).round() as usize;
+    stretch_to_length(input, output_len)
+}
+
+/// Stretch `input` to exactly `output_len` samples without changing its pitch, e.g. to fit a
+/// loop to a number of bars at the project's tempo.
+pub fn stretch_to_length(input: &[f32], output_len: usize) -> Vec<f32> {
+    if input.is_empty() || output_len == 0 {
+        return vec![0.0; output_len];
+    }
+
+    let onsets = find_onsets(input);
+    let map = TimeMap::new(&onsets, input.len(), output_len);
+    Vocoder::new().render(input, &map, output_len)
+}
+
+/// Shift the pitch of `input` by `ratio` as a frequency ratio, keeping its length. The ratio is
+/// limited to [`MIN_RATIO`] to [`MAX_RATIO`].
+pub fn shift_pitch(input: &[f32], ratio: f32) -> Vec<f32> {
+    let ratio = ratio.clamp(MIN_RATIO, MAX_RATIO);
+    let stretched = stretch(input, ratio);
+
+    // Playing the stretched signal back faster by the same ratio brings it back to length
+    let mut output = resample(&stretched, ratio as f64, 1.0, ResamplerQuality::High);
+    output.resize(input.len(), 0.0);
+    output
+}
+
+/// Shift the pitch of `input` by `semitones`, keeping its length.
+pub fn shift_semitones(input: &[f32], semitones: f32) -> Vec<f32> {
+    shift_pitch(input, 2.0f32.powf(semitones / 12.0))
+}
+
+/// The sample positions where new sounds start in `input`, in order. A frame is an onset when
+/// most of its spectrum's magnitude is new compared to the frame before, and it's newer than the
+/// frames on either side.
+fn find_onsets(input: &[f32]) -> Vec<usize> {
+    let mut fft = Fft::new(ONSET_FFT_SIZE);
+    let window = Window::Hann.build(ONSET_FFT_SIZE);
+    let mut frame = vec![0.0; ONSET_FFT_SIZE];
+    let mut spectrum = fft.make_spectrum();
+    let mut magnitudes = vec![0.0; fft.num_bins()];
+    let mut last_magnitudes = vec![0.0; fft.num_bins()];
+
+    // Each frame's new magnitude and its total magnitude, frames centered every hop
+    let num_frames = input.len() / ONSET_HOP + 1;
+    let mut flux = Vec::with_capacity(num_frames);
+    let mut totals = Vec::with_capacity(num_frames);
+    for idx in 0..num_frames {
+        read_frame(input, idx * ONSET_HOP, &window, &mut frame);
+        fft.forward(&mut frame, &mut spectrum);
+        for (magnitude, bin) in magnitudes.iter_mut().zip(&spectrum) {
+            *magnitude = bin.norm();
+        }
+
+        let rise: f32 = magnitudes
+            .iter()
+            .zip(&last_magnitudes)
+            .map(|(magnitude, last)| (magnitude - last).max(0.0))
+            .sum();
+        flux.push(rise);
+        totals.push(magnitudes.iter().sum::<f32>());
+        std::mem::swap(&mut magnitudes, &mut last_magnitudes);
+    }
+
+    let floor = totals.iter().cloned().fold(0.0, f32::max) * ONSET_FLOOR;
+    let novelty: Vec<f32> = flux
+        .iter()
+        .zip(&totals)
+        .map(|(flux, total)| flux / (total + floor))
+        .collect();
+
+    (0..num_frames)
+        .filter(|&idx| {
+            let before = idx.checked_sub(1).map_or(0.0, |before| novelty[before]);
+            let after = novelty.get(idx + 1).copied().unwrap_or(0.0);
+            novelty[idx] > ONSET_THRESHOLD && novelty[idx] > before && novelty[idx] >= after
+        })
+        .map(|idx| refine_onset(input, idx * ONSET_HOP))
+        .collect()
+}
+
+/// Pin down an onset found in the frame centered on `center` to the block with the sharpest rise
+/// in level within that frame.
+fn refine_onset(input: &[f32], center: usize) -> usize {
+    let start = center.saturating_sub(ONSET_FFT_SIZE / 2);
+    let end = (center + ONSET_FFT_SIZE / 2).min(input.len());
+    let energy = |block: usize| -> f32 {
+        let from = block.min(input.len());
+        let to = (block + ONSET_BLOCK).min(input.len());
+        input[from..to].iter().map(|s| s * s).sum()
+    };
+
+    let loudest = (start..end)
+        .step_by(ONSET_BLOCK)
+        .map(energy)
+        .fold(0.0, f32::max);
+    let floor = loudest * ONSET_FLOOR;
+    (start..end)
+        .step_by(ONSET_BLOCK)
+        .max_by(|&a, &b| {
+            let rise = |block: usize| {
+                energy(block)
+                    / (energy(block.saturating_sub(ONSET_BLOCK)) + floor + f32::MIN_POSITIVE)
+            };
+            rise(a).total_cmp(&rise(b))
+        })
+        .unwrap_or(center)
+}
+
+/// Copy the windowed frame of `input` centered on `center` into `frame`, with silence past the
+/// ends.
+fn read_frame(input: &[f32], center: usize, window: &[f32], frame: &mut [f32]) {
+    let half = frame.len() / 2;
+    for (offset, (sample, window)) in frame.iter_mut().zip(window).enumerate() {
+        *sample = match (center + offset).checked_sub(half) {
+            Some(idx) if idx < input.len() => input[idx] * window,
+            _ => 0.0,
+        };
+    }
+}
+
+/// Where each point of the output is read from in the input. Piecewise linear, at the original
+/// speed around the onsets and stretched evenly in between.
+struct TimeMap {
+    /// Output and input positions, in order.
+    points: Vec<(f64, f64)>,
+    /// The start of each stretch of input that's copied at its original speed.
+    rigid_starts: Vec<f64>,
+}
+
+impl TimeMap {
+    fn new(onsets: &[usize], input_len: usize, output_len: usize) -> Self {
+        let ratio = output_len as f64 / input_len as f64;
+        let radius = RIGID_RADIUS as f64;
+        let mut points = vec![(0.0, 0.0)];
+        let mut rigid_starts = Vec::new();
+
+        for &onset in onsets {
+            let onset = onset as f64;
+            let &(last_output, last_input) = points.last().unwrap();
+            let (start, end) = (
+                (onset * ratio - radius, onset - radius),
+                (onset * ratio + radius, onset + radius),
+            );
+
+            if start.0 > last_output && start.1 > last_input {
+                if end.0 < output_len as f64 && end.1 < input_len as f64 {
+                    points.extend([start, end]);
+                    rigid_starts.push(start.1);
+                }
+            } else {
+                // Too close to the start or to the last onset to stretch anything in between,
+                // so the copy at the original speed carries on past this onset
+                let end = (last_output + end.1 - last_input, end.1);
+                if end.0 < output_len as f64 && end.1 < input_len as f64 {
+                    if points.len() == 1 {
+                        rigid_starts.push(0.0);
+                    }
+                    points.push(end);
+                }
+            }
+        }
+
+        points.push((output_len as f64, input_len as f64));
+        Self {
+            points,
+            rigid_starts,
+        }
+    }
+
+    /// The input position for an output position. Before the start and after the end the first
+    /// and last segments carry on.
+    fn input_position(&self, output: f64) -> f64 {
+        let segment = self
+            .points
+            .windows(2)
+            .position(|pair| output < pair[1].0)
+            .unwrap_or(self.points.len() - 2);
+        let (from, to) = (self.points[segment], self.points[segment + 1]);
+        from.1 + (output - from.0) * (to.1 - from.1) / (to.0 - from.0)
+    }
+}
+
+/// The phase vocoder's buffers and the phases carried from frame to frame.
+struct Vocoder {
+    fft: Fft,
+    window: Vec<f32>,
+    frame: Vec<f32>,
+    spectrum: Vec<Complex32>,
+    magnitude: Vec<f32>,
+    phase: Vec<f32>,
+    last_phase: Vec<f32>,
+    /// The phases written to the output.
+    synthesis_phase: Vec<f32>,
+    peaks: Vec<usize>,
+}
+
+impl Vocoder {
+    fn new() -> Self {
+        let fft = Fft::new(FFT_SIZE);
+        Self {
+            spectrum: fft.make_spectrum(),
+            fft,
+            window: Window::Hann.build(FFT_SIZE),
+            frame: vec![0.0; FFT_SIZE],
+            magnitude: vec![0.0; NUM_BINS],
+            phase: vec![0.0; NUM_BINS],
+            last_phase: vec![0.0; NUM_BINS],
+            synthesis_phase: vec![0.0; NUM_BINS],
+            peaks: Vec::with_capacity(NUM_BINS),
+        }
+    }
+
+    fn render(&mut self, input: &[f32], map: &TimeMap, output_len: usize) -> Vec<f32> {
+        // Room for the frames hanging over either end
+        let mut output = vec![0.0; output_len + 2 * FFT_SIZE];
+        let window_power = self.window.iter().map(|w| w * w).sum::<f32>() / HOP as f32;
+        let output_scale = 1.0 / (FFT_SIZE as f32 * window_power);
+
+        // Starting one hop before the output, so the first samples are covered by every frame
+        // that overlaps them like the rest are
+        let frames_before = (HALF_FRAME / HOP) as isize - 1;
+        // Output index 0 is where the first frame starts
+        let offset = HALF_FRAME + frames_before as usize * HOP;
+        let mut last_center: Option<isize> = None;
+        let mut rigid_starts = map.rigid_starts.iter().peekable();
+        for idx in -frames_before.. {
+            let output_center = idx * HOP as isize;
+            if output_center - HALF_FRAME as isize >= output_len as isize {
+                break;
+            }
+
+            let center = map.input_position(output_center as f64).round() as isize;
+            let mut reset = last_center.is_none();
+            while rigid_starts
+                .next_if(|&&start| center as f64 > start)
+                .is_some()
+            {
+                reset = true;
+            }
+
+            self.analyze(input, center);
+            match last_center {
+                Some(last_center) if !reset => self.advance_phases((center - last_center) as f32),
+                _ => self.synthesis_phase.copy_from_slice(&self.phase),
+            }
+            std::mem::swap(&mut self.phase, &mut self.last_phase);
+            last_center = Some(center);
+
+            for (bin, (&magnitude, &phase)) in self
+                .spectrum
+                .iter_mut()
+                .zip(self.magnitude.iter().zip(&self.synthesis_phase))
+            {
+                *bin = Complex32::from_polar(magnitude, phase);
+            }
+            self.fft.inverse(&mut self.spectrum, &mut self.frame);
+
+            let start = (output_center + (offset - HALF_FRAME) as isize) as usize;
+            for ((output, sample), window) in output[start..start + FFT_SIZE]
+                .iter_mut()
+                .zip(&self.frame)
+                .zip(&self.window)
+            {
+                *output += sample * window * output_scale;
+            }
+        }
+
+        output.drain(..offset);
+        output.truncate(output_len);
+        output
+    }
+
+    /// Window the input frame centered on `center` and split its spectrum into magnitudes and
+    /// phases.
+    fn analyze(&mut self, input: &[f32], center: isize) {
+        let half = HALF_FRAME as isize;
+        for (offset, (sample, window)) in self.frame.iter_mut().zip(&self.window).enumerate() {
+            let idx = center + offset as isize - half;
+            *sample = if idx >= 0 && (idx as usize) < input.len() {
+                input[idx as usize] * window
+            } else {
+                0.0
+            };
+        }
+        self.fft.forward(&mut self.frame, &mut self.spectrum);
+        for (k, bin) in self.spectrum.iter().enumerate() {
+            let (magnitude, phase) = bin.to_polar();
+            self.magnitude[k] = magnitude;
+            self.phase[k] = phase;
+        }
+    }
+
+    /// Move the synthesis phases on by one output hop, given that the input moved `advance`
+    /// samples since the last frame. The peaks advance at their measured frequencies and the
+    /// bins around them keep their phases relative to the peak.
+    fn advance_phases(&mut self, advance: f32) {
+        self.peaks.clear();
+        for k in 1..NUM_BINS - 1 {
+            let magnitude = self.magnitude[k];
+            if magnitude > self.magnitude[k - 1] && magnitude >= self.magnitude[k + 1] {
+                self.peaks.push(k);
+            }
+        }
+
+        for (idx, &peak) in self.peaks.iter().enumerate() {
+            let bin_frequency = TAU * peak as f32 / FFT_SIZE as f32;
+            let frequency = if advance > 0.0 {
+                let expected = bin_frequency * advance;
+                let deviation = wrap_phase(self.phase[peak] - self.last_phase[peak] - expected);
+                bin_frequency + deviation / advance
+            } else {
+                bin_frequency
+            };
+            let peak_phase = wrap_phase(self.synthesis_phase[peak] + frequency * HOP as f32);
+
+            // Each peak owns the bins down to the quietest bin between it and its neighbours
+            let start = match idx {
+                0 => 0,
+                _ => self.quietest_bin(self.peaks[idx - 1], peak),
+            };
+            let end = match self.peaks.get(idx + 1) {
+                Some(&next) => self.quietest_bin(peak, next),
+                None => NUM_BINS,
+            };
+            for k in start..end {
+                self.synthesis_phase[k] = peak_phase + self.phase[k] - self.phase[peak];
+            }
+        }
+    }
+
+    /// The quietest bin from `start` up to `end`.
+    fn quietest_bin(&self, start: usize, end: usize) -> usize {
+        (start + 1..end)
+            .min_by(|&a, &b| self.magnitude[a].total_cmp(&self.magnitude[b]))
+            .unwrap_or(end)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: f32 = 48000.0;
+
+    fn sine(frequency: f32, len: usize) -> Vec<f32> {
+        (0..len)
+            .map(|n| 0.5 * (TAU * frequency * n as f32 / SAMPLE_RATE).sin())
+            .collect()
+    }
+
+    fn rms(samples: &[f32]) -> f32 {
+        (samples.iter().map(|s| s * s).sum::<f32>() / samples.len() as f32).sqrt()
+    }
+
+    /// The frequency from the rising zero crossings.
+    fn frequency(samples: &[f32]) -> f32 {
+        let crossings: Vec<f32> = samples
+            .windows(2)
+            .enumerate()
+            .filter(|(_, pair)| pair[0] < 0.0 && pair[1] >= 0.0)
+            .map(|(i, pair)| i as f32 + pair[0] / (pair[0] - pair[1]))
+            .collect();
+        let cycles = (crossings.len() - 1) as f32;
+        SAMPLE_RATE * cycles / (crossings[crossings.len() - 1] - crossings[0])
+    }
+
+    /// Decaying noise bursts with instant attacks at `hits`, like a drum loop.
+    fn drum_loop(hits: &[usize], len: usize) -> Vec<f32> {
+        let mut rng = crate::random::Xorshift32::new(7);
+        let mut output = vec![0.0; len];
+        for &hit in hits {
+            for (n, sample) in output[hit..].iter_mut().enumerate().take(len / 8) {
+                let decay = (-(n as f32) / (0.03 * SAMPLE_RATE)).exp();
+                *sample += rng.next_bipolar() * decay * 0.5;
+            }
+        }
+        output
+    }
+
+    #[test]
+    fn no_stretch_gives_back_the_input() {
+        let input = drum_loop(&[4800, 30000], 48000);
+        let output = stretch(&input, 1.0);
+        assert_eq!(output.len(), input.len());
+        for (n, (output, input)) in output.iter().zip(&input).enumerate() {
+            assert!((output - input).abs() < 1e-4, "{n}: {output} vs {input}");
+        }
+    }
+
+    #[test]
+    fn stretching_keeps_the_pitch_and_level_of_a_tone() {
+        let input = sine(440.0, 48000);
+        for ratio in [0.5, 1.5, 3.0] {
+            let output = stretch(&input, ratio);
+            assert_eq!(output.len(), (48000.0 * ratio) as usize);
+
+            let middle = &output[output.len() / 4..output.len() * 3 / 4];
+            let frequency = frequency(middle);
+            assert!((frequency - 440.0).abs() < 0.5, "{ratio}: {frequency}");
+            let level = rms(middle) / rms(&input);
+            assert!((level - 1.0).abs() < 0.06, "{ratio}: {level}");
+        }
+    }
+
+    #[test]
+    fn hits_keep_their_attacks_when_stretched() {
+        let hits = [6000, 30000, 54000, 66000];
+        let input = drum_loop(&hits, 96000);
+        for ratio in [0.7, 2.0] {
+            let output = stretch(&input, ratio);
+
+            for &hit in &hits {
+                let stretched = (hit as f32 * ratio) as usize;
+                let peak = |samples: &[f32]| samples.iter().fold(0.0f32, |m, s| m.max(s.abs()));
+
+                // The attack comes through as sharp as it went in, at its stretched time
+                let attack = peak(&output[stretched..stretched + 48]);
+                assert!(attack > 0.3, "{ratio} {hit}: {attack}");
+
+                // And there are no smeared copies of it in the silence before
+                let before = &output[stretched - 2400..stretched - 48];
+                let pre_echo = peak(before);
+                assert!(pre_echo < 0.01, "{ratio} {hit}: {pre_echo}");
+            }
+        }
+    }
+
+    #[test]
+    fn shifting_the_pitch_keeps_the_length() {
+        let input = sine(440.0, 48000);
+        let output = shift_semitones(&input, 7.0);
+        assert_eq!(output.len(), input.len());
+
+        let middle = &output[12000..36000];
+        let expected = 440.0 * 2.0f32.powf(7.0 / 12.0);
+        let frequency = frequency(middle);
+        assert!((frequency - expected).abs() < 1.0, "{frequency}");
+        let level = rms(middle) / rms(&input);
+        assert!((level - 1.0).abs() < 0.06, "{level}");
+    }
+}
@@ -0,0 +1,380 @@
+//! Parameter smoothing without nih_plug, so DSP types can glide towards their own targets and
+//! still be used outside of plugins.
+
+use crate::Sample;
+
+/// Glides a value towards a target to avoid zipper noise when it changes in steps.
+pub trait Smoother<T: Sample> {
+    /// Start gliding towards `target`.
+    fn set_target(&mut self, target: T);
+
+    fn target(&self) -> T;
+
+    /// Jump straight to `value` without gliding.
+    fn reset(&mut self, value: T);
+
+    /// Advance by one sample and return the new value.
+    fn next(&mut self) -> T;
+
+    /// The most recent value, without advancing.
+    fn current(&self) -> T;
+
+    /// Whether the value hasn't reached the target yet. Callers can skip per-sample work for
+    /// values that have settled.
+    fn is_smoothing(&self) -> bool;
+
+    /// Fill a block with the next values.
+    fn fill(&mut self, block: &mut [T]) {
+        for value in block {
+            *value = self.next();
+        }
+    }
+}
+
+/// An exponential glide, the shape of a one-pole low pass. Fast at first and slow at the end,
+/// which sounds natural for gains and cutoff frequencies.
+#[derive(Debug, Clone)]
+pub struct OnePoleSmoother<T: Sample = f32> {
+    current: T,
+    target: T,
+    coefficient: T,
+    sample_rate: T,
+    time: T,
+}
+
+impl<T: Sample> OnePoleSmoother<T> {
+    /// `time` is the time constant in seconds, after which the value has covered about 63% of
+    /// the distance to the target.
+    pub fn new(sample_rate: T, time: T) -> Self {
+        let mut smoother = Self {
+            current: T::ZERO,
+            target: T::ZERO,
+            coefficient: T::ONE,
+            sample_rate,
+            time,
+        };
+        smoother.update_coefficient();
+        smoother
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: T) {
+        self.sample_rate = sample_rate;
+        self.update_coefficient();
+    }
+
+    pub fn set_time(&mut self, time: T) {
+        self.time = time;
+        self.update_coefficient();
+    }
+
+    fn update_coefficient(&mut self) {
+        let samples = self.time * self.sample_rate;
+        self.coefficient = if samples > T::ZERO {
+            T::ONE - (-T::ONE / samples).exp()
+        } else {
+            T::ONE
+        };
+    }
+}
+
+impl<T: Sample> Smoother<T> for OnePoleSmoother<T> {
+    fn set_target(&mut self, target: T) {
+        self.target = target;
+    }
+
+    fn target(&self) -> T {
+        self.target
+    }
+
+    fn reset(&mut self, value: T) {
+        self.current = value;
+        self.target = value;
+    }
+
+    #[inline]
+    fn next(&mut self) -> T {
+        if self.is_smoothing() {
+            let next = self.current + (self.target - self.current) * self.coefficient;
+            // An exponential never quite arrives, so snap once the difference is inaudible or
+            // too small for the step to register at all
+            self.current = if next == self.current || (self.target - next).abs() < T::from_f32(1e-6)
+            {
+                self.target
+            } else {
+                next
+            };
+        }
+        self.current
+    }
+
+    fn current(&self) -> T {
+        self.current
+    }
+
+    fn is_smoothing(&self) -> bool {
+        self.current != self.target
+    }
+}
+
+/// A straight line to the target over a fixed number of samples, however far away it is. Every
+/// change takes exactly as long, which keeps crossfades and modulated delay times predictable.
+#[derive(Debug, Clone)]
+pub struct LinearSmoother<T: Sample = f32> {
+    current: T,
+    target: T,
+    step: T,
+    ramp_length: usize,
+    remaining: usize,
+}
+
+impl<T: Sample> LinearSmoother<T> {
+    /// Every change takes `ramp_length` samples.
+    pub fn new(ramp_length: usize) -> Self {
+        Self {
+            current: T::ZERO,
+            target: T::ZERO,
+            step: T::ZERO,
+            ramp_length,
+            remaining: 0,
+        }
+    }
+
+    /// Changes take `time` seconds.
+    pub fn with_time(sample_rate: T, time: T) -> Self {
+        Self::new((time * sample_rate).to_f32() as usize)
+    }
+
+    /// Takes effect from the next target change.
+    pub fn set_ramp_length(&mut self, ramp_length: usize) {
+        self.ramp_length = ramp_length;
+    }
+}
+
+impl<T: Sample> Smoother<T> for LinearSmoother<T> {
+    fn set_target(&mut self, target: T) {
+        if target == self.target {
+            return;
+        }
+
+        self.target = target;
+        if self.ramp_length == 0 {
+            self.current = target;
+            self.remaining = 0;
+        } else {
+            self.step = (target - self.current) / T::from_f32(self.ramp_length as f32);
+            self.remaining = self.ramp_length;
+        }
+    }
+
+    fn target(&self) -> T {
+        self.target
+    }
+
+    fn reset(&mut self, value: T) {
+        self.current = value;
+        self.target = value;
+        self.remaining = 0;
+    }
+
+    #[inline]
+    fn next(&mut self) -> T {
+        if self.remaining > 0 {
+            self.remaining -= 1;
+            // Land exactly on the target rather than wherever the rounding errors lead
+            self.current = if self.remaining == 0 {
+                self.target
+            } else {
+                self.current + self.step
+            };
+        }
+        self.current
+    }
+
+    fn current(&self) -> T {
+        self.current
+    }
+
+    fn is_smoothing(&self) -> bool {
+        self.remaining > 0
+    }
+}
+
+/// Ramps linearly across each block, arriving at the target by the block's last sample. For
+/// values that are only updated once per block, like parameters read at the start of
+/// `process()`, this is the smoothest path that never lags more than a block behind.
+///
+/// Call [`start_block()`][Self::start_block()] with the block length before taking that many
+/// values with [`next()`][Smoother::next()], or use [`fill()`][Smoother::fill()] which does
+/// both.
+#[derive(Debug, Clone)]
+pub struct BlockSmoother<T: Sample = f32> {
+    current: T,
+    target: T,
+    step: T,
+    remaining: usize,
+}
+
+impl<T: Sample> BlockSmoother<T> {
+    pub fn new() -> Self {
+        Self {
+            current: T::ZERO,
+            target: T::ZERO,
+            step: T::ZERO,
+            remaining: 0,
+        }
+    }
+
+    /// Plan a ramp from the current value to the target over the next `len` samples.
+    pub fn start_block(&mut self, len: usize) {
+        if len == 0 || self.current == self.target {
+            self.remaining = 0;
+        } else {
+            self.step = (self.target - self.current) / T::from_f32(len as f32);
+            self.remaining = len;
+        }
+    }
+}
+
+impl<T: Sample> Default for BlockSmoother<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Sample> Smoother<T> for BlockSmoother<T> {
+    fn set_target(&mut self, target: T) {
+        self.target = target;
+    }
+
+    fn target(&self) -> T {
+        self.target
+    }
+
+    fn reset(&mut self, value: T) {
+        self.current = value;
+        self.target = value;
+        self.remaining = 0;
+    }
+
+    #[inline]
+    fn next(&mut self) -> T {
+        if self.remaining > 0 {
+            self.remaining -= 1;
+            self.current = if self.remaining == 0 {
+                self.target
+            } else {
+                self.current + self.step
+            };
+        }
+        self.current
+    }
+
+    fn current(&self) -> T {
+        self.current
+    }
+
+    fn is_smoothing(&self) -> bool {
+        self.current != self.target
+    }
+
+    fn fill(&mut self, block: &mut [T]) {
+        self.start_block(block.len());
+        for value in block {
+            *value = self.next();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: f32 = 48000.0;
+
+    /// Take values until the smoother settles, checking that every one moves towards the target
+    /// without passing it. Returns the number of samples it took.
+    fn glide(smoother: &mut impl Smoother<f32>, target: f32, max_samples: usize) -> usize {
+        let start = smoother.current();
+        smoother.set_target(target);
+        let mut previous = start;
+        for n in 1..=max_samples {
+            let value = smoother.next();
+            let (low, high) = if start < target {
+                (previous, target)
+            } else {
+                (target, previous)
+            };
+            assert!(
+                (low..=high).contains(&value),
+                "{n}: {value} outside {low}..={high}"
+            );
+            previous = value;
+
+            if !smoother.is_smoothing() {
+                assert_eq!(value, target);
+                return n;
+            }
+        }
+        panic!("didn't reach {target} from {start} in {max_samples} samples");
+    }
+
+    #[test]
+    fn one_pole_smoothers_glide_exponentially_and_settle() {
+        let time = 0.01;
+        let time_constant = (time * SAMPLE_RATE) as usize;
+        let mut smoother = OnePoleSmoother::new(SAMPLE_RATE, time);
+        smoother.reset(0.0);
+        smoother.set_target(1.0);
+        for _ in 0..time_constant {
+            smoother.next();
+        }
+        assert!(
+            (smoother.current() - 0.632).abs() < 0.01,
+            "{}",
+            smoother.current()
+        );
+
+        // Snapping at a millionth of the distance takes about ln(10⁶), 14 time constants
+        smoother.reset(0.0);
+        for target in [1.0, -0.5, 0.25, 1000.0] {
+            let samples = glide(&mut smoother, target, 20 * time_constant);
+            assert!(samples > 5 * time_constant, "{target}: {samples}");
+        }
+    }
+
+    #[test]
+    fn linear_smoothers_take_exactly_the_ramp_length() {
+        let mut smoother = LinearSmoother::with_time(SAMPLE_RATE, 0.005);
+        for target in [1.0, -0.3, 0.7, 0.7000001, 123.0] {
+            assert_eq!(glide(&mut smoother, target, 1000), 240, "{target}");
+        }
+
+        // A new target mid-ramp starts a full ramp from wherever the value got to
+        smoother.reset(0.0);
+        smoother.set_target(1.0);
+        for _ in 0..100 {
+            smoother.next();
+        }
+        assert_eq!(glide(&mut smoother, -1.0, 1000), 240);
+    }
+
+    #[test]
+    fn block_smoothers_arrive_on_the_blocks_last_sample() {
+        let mut smoother = BlockSmoother::new();
+        smoother.reset(0.0);
+        for (target, len) in [(1.0, 64), (0.3, 7), (-2.0, 1), (5.0, 333)] {
+            let start = smoother.current();
+            smoother.set_target(target);
+            let mut block = vec![0.0; len];
+            smoother.fill(&mut block);
+
+            assert_eq!(block[len - 1], target, "{target}");
+            let (low, high) = (start.min(target), start.max(target));
+            assert!(block.iter().all(|value| (low..=high).contains(value)));
+            assert!(block
+                .windows(2)
+                .all(|pair| (pair[1] - pair[0]) * (target - start) >= 0.0));
+            assert!(!smoother.is_smoothing());
+        }
+    }
+}
@@ -0,0 +1,248 @@
+use crate::Sample;
+
+/// How far past full level the attack aims, so it reaches full level in finite time.
+const ATTACK_TARGET: f32 = 1.2;
+/// Envelope levels below -80 dB snap to silence, which keeps them out of denormal range.
+const SILENCE: f32 = 1e-4;
+
+/// A bank of `VOICES` sine voices with one-pole ADSR envelopes, laid out as a structure of
+/// arrays: every field holds one value per voice, instead of the bank being an array of voice
+/// structs. Each step of the per-sample loop runs over one field for all voices at once, which
+/// the compiler can vectorize, and the voices' state stays packed into a few cache lines. The
+/// oscillators rotate a phasor by a fixed angle every sample instead of calling `sin()`, so the
+/// whole loop is multiplies and adds.
+///
+/// The envelopes approach their targets exponentially like an analog ADSR's, so the attack, decay
+/// and release times are time constants. Every voice is processed every sample, whether it's
+/// playing or not, since skipping the silent ones would need a branch per voice.
+#[derive(Debug, Clone)]
+pub struct SineVoiceBank<T: Sample = f32, const VOICES: usize = 16> {
+    /// Each oscillator's phasor, whose imaginary part is the output.
+    re: [T; VOICES],
+    im: [T; VOICES],
+    /// The rotation per sample, the cosine and sine of the phase increment.
+    cos: [T; VOICES],
+    sin: [T; VOICES],
+    velocities: [T; VOICES],
+    levels: [T; VOICES],
+    /// The level each envelope is heading for, and the fraction of the way it moves per sample.
+    targets: [T; VOICES],
+    coefficients: [T; VOICES],
+    attacking: [bool; VOICES],
+
+    attack: T,
+    decay: T,
+    sustain: T,
+    release: T,
+    sample_rate: T,
+}
+
+impl<T: Sample, const VOICES: usize> SineVoiceBank<T, VOICES> {
+    /// All voices start silent.
+    pub fn new(sample_rate: T) -> Self {
+        Self {
+            re: [T::ONE; VOICES],
+            im: [T::ZERO; VOICES],
+            cos: [T::ONE; VOICES],
+            sin: [T::ZERO; VOICES],
+            velocities: [T::ZERO; VOICES],
+            levels: [T::ZERO; VOICES],
+            targets: [T::ZERO; VOICES],
+            coefficients: [T::ZERO; VOICES],
+            attacking: [false; VOICES],
+            attack: T::from_f32(0.01),
+            decay: T::from_f32(0.1),
+            sustain: T::from_f32(0.7),
+            release: T::from_f32(0.2),
+            sample_rate,
+        }
+    }
+
+    /// Envelope times are kept in seconds, so they stay the same at the new rate. Voices that
+    /// are already playing are only retuned by their next note.
+    pub fn set_sample_rate(&mut self, sample_rate: T) {
+        self.sample_rate = sample_rate;
+    }
+
+    /// Silence every voice immediately.
+    pub fn reset(&mut self) {
+        self.re = [T::ONE; VOICES];
+        self.im = [T::ZERO; VOICES];
+        self.levels = [T::ZERO; VOICES];
+        self.targets = [T::ZERO; VOICES];
+        self.attacking = [false; VOICES];
+    }
+
+    pub fn set_attack(&mut self, attack: T) {
+        self.attack = attack;
+    }
+
+    pub fn set_decay(&mut self, decay: T) {
+        self.decay = decay;
+    }
+
+    /// Voices that are already sustaining move to the new level at the decay's speed.
+    pub fn set_sustain(&mut self, sustain: T) {
+        self.sustain = sustain;
+        for voice in 0..VOICES {
+            if !self.attacking[voice] && self.targets[voice] > T::ZERO {
+                self.targets[voice] = sustain;
+            }
+        }
+    }
+
+    pub fn set_release(&mut self, release: T) {
+        self.release = release;
+    }
+
+    /// Start `voice` at `frequency` Hz, from the level it's currently at so retriggering
+    /// doesn't click.
+    pub fn note_on(&mut self, voice: usize, frequency: T, velocity: T) {
+        let increment = T::TAU * frequency / self.sample_rate;
+        self.cos[voice] = increment.cos();
+        self.sin[voice] = increment.sin();
+        self.velocities[voice] = velocity;
+        self.targets[voice] = T::from_f32(ATTACK_TARGET);
+        self.coefficients[voice] = self.coefficient(self.attack);
+        self.attacking[voice] = true;
+    }
+
+    pub fn note_off(&mut self, voice: usize) {
+        self.targets[voice] = T::ZERO;
+        self.coefficients[voice] = self.coefficient(self.release);
+        self.attacking[voice] = false;
+    }
+
+    /// Whether `voice` is still audible.
+    pub fn is_active(&self, voice: usize) -> bool {
+        self.levels[voice] > T::ZERO || self.targets[voice] > T::ZERO
+    }
+
+    /// Write the sum of all voices to `output`.
+    pub fn process_block(&mut self, output: &mut [T]) {
+        let sustain = self.sustain;
+        let decay = self.coefficient(self.decay);
+        let silence = T::from_f32(SILENCE);
+
+        for sample in output.iter_mut() {
+            let mut sum = T::ZERO;
+            for voice in 0..VOICES {
+                sum += self.im[voice] * self.levels[voice] * self.velocities[voice];
+            }
+            *sample = sum;
+
+            for voice in 0..VOICES {
+                let (re, im) = (self.re[voice], self.im[voice]);
+                self.re[voice] = re * self.cos[voice] - im * self.sin[voice];
+                self.im[voice] = re * self.sin[voice] + im * self.cos[voice];
+            }
+
+            for voice in 0..VOICES {
+                let level = self.levels[voice];
+                self.levels[voice] =
+                    level + (self.targets[voice] - level) * self.coefficients[voice];
+            }
+
+            // Attacks that reached full level turn into decays, releases that faded out stop
+            for voice in 0..VOICES {
+                let level = self.levels[voice];
+                let peaked = self.attacking[voice] && level >= T::ONE;
+                let faded = self.targets[voice] == T::ZERO && level < silence;
+
+                self.levels[voice] = if peaked {
+                    T::ONE
+                } else if faded {
+                    T::ZERO
+                } else {
+                    level
+                };
+                self.targets[voice] = if peaked { sustain } else { self.targets[voice] };
+                self.coefficients[voice] = if peaked {
+                    decay
+                } else {
+                    self.coefficients[voice]
+                };
+                self.attacking[voice] &= !peaked;
+            }
+        }
+
+        // Rounding errors make the phasors' lengths drift, pull them back to one. The drift over
+        // a block is tiny, so a first order correction is enough.
+        for voice in 0..VOICES {
+            let (re, im) = (self.re[voice], self.im[voice]);
+            let correction = T::from_f32(1.5) - T::HALF * (re * re + im * im);
+            self.re[voice] = re * correction;
+            self.im[voice] = im * correction;
+        }
+    }
+
+    /// The one-pole coefficient for a time constant of `time` seconds.
+    fn coefficient(&self, time: T) -> T {
+        let samples = (time * self.sample_rate).max(T::ONE);
+        T::ONE - (-T::ONE / samples).exp()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: f32 = 48000.0;
+
+    #[test]
+    fn held_voices_settle_at_the_sustain_level() {
+        let mut bank = SineVoiceBank::<f32, 4>::new(SAMPLE_RATE);
+        bank.set_sustain(0.5);
+        bank.note_on(2, 375.0, 0.8);
+
+        // A second is plenty for the attack and decay, after which a cycle of 128 samples peaks
+        // at the sustain level times the velocity
+        let mut output = vec![0.0; SAMPLE_RATE as usize];
+        bank.process_block(&mut output);
+        let mut cycle = [0.0; 128];
+        bank.process_block(&mut cycle);
+        let peak = cycle
+            .iter()
+            .fold(0.0f32, |peak, sample| peak.max(sample.abs()));
+        assert!((peak - 0.4).abs() < 1e-3, "{peak}");
+
+        for voice in 0..4 {
+            assert_eq!(bank.is_active(voice), voice == 2);
+        }
+    }
+
+    #[test]
+    fn released_voices_fade_to_silence() {
+        let mut bank = SineVoiceBank::<f32, 4>::new(SAMPLE_RATE);
+        bank.set_release(0.05);
+        bank.note_on(0, 440.0, 1.0);
+        bank.note_on(1, 660.0, 1.0);
+        let mut output = vec![0.0; 4800];
+        bank.process_block(&mut output);
+
+        bank.note_off(0);
+        bank.note_off(1);
+        let mut output = vec![0.0; SAMPLE_RATE as usize];
+        bank.process_block(&mut output);
+        assert!(!bank.is_active(0) && !bank.is_active(1));
+        assert!(output[output.len() - 100..]
+            .iter()
+            .all(|&sample| sample == 0.0));
+    }
+
+    #[test]
+    fn attacks_reach_full_level() {
+        let mut bank = SineVoiceBank::<f64, 2>::new(SAMPLE_RATE as f64);
+        bank.set_attack(0.01);
+        bank.set_decay(10.0);
+        bank.note_on(0, 1000.0, 1.0);
+
+        // The attack aims past full level, so it gets there in about two time constants
+        let mut output = vec![0.0; 1200];
+        bank.process_block(&mut output);
+        let peak = output
+            .iter()
+            .fold(0.0f64, |peak, sample| peak.max(sample.abs()));
+        assert!(peak > 0.99, "{peak}");
+    }
+}
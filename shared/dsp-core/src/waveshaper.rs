@@ -0,0 +1,209 @@
+//! Static waveshaping curves for saturation and distortion.
+//!
+//! Shaping adds harmonics above the input's bandwidth which fold back down as aliasing, so run
+//! shapers inside an [`Oversampler`](crate::oversampling::Oversampler) when the drive is high.
+
+use crate::Sample;
+
+/// The transfer curve of a [`Waveshaper`]. All curves pass small signals through unchanged and
+/// limit to ±1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Curve {
+    /// Smooth and symmetric, the classic soft clipper.
+    #[default]
+    Tanh,
+    /// `x / sqrt(1 + x²)`, with a softer knee than `tanh` that approaches the limit slowly.
+    Algebraic,
+    /// A cubic that's clean at low levels and flat above 1.5, for a harder knee.
+    Cubic,
+    /// Clips at ±1, harsh and full of high harmonics.
+    HardClip,
+}
+
+impl Curve {
+    #[inline]
+    pub fn apply<T: Sample>(self, x: T) -> T {
+        match self {
+            Curve::Tanh => x.tanh(),
+            Curve::Algebraic => x / (T::ONE + x * x).sqrt(),
+            Curve::Cubic => {
+                let limit = T::from_f32(1.5);
+                let x = x.clamp(-limit, limit);
+                x - x * x * x * T::from_f32(4.0 / 27.0)
+            }
+            Curve::HardClip => x.clamp(-T::ONE, T::ONE),
+        }
+    }
+}
+
+/// A one-pole DC blocking high pass at about 10 Hz.
+#[derive(Debug, Clone)]
+pub struct DcBlocker<T: Sample = f32> {
+    coefficient: T,
+    previous_input: T,
+    previous_output: T,
+}
+
+impl<T: Sample> DcBlocker<T> {
+    pub fn new(sample_rate: T) -> Self {
+        let mut blocker = Self {
+            coefficient: T::ZERO,
+            previous_input: T::ZERO,
+            previous_output: T::ZERO,
+        };
+        blocker.set_sample_rate(sample_rate);
+        blocker
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: T) {
+        self.coefficient = (-T::TAU * T::from_f32(10.0) / sample_rate).exp();
+    }
+
+    pub fn reset(&mut self) {
+        self.previous_input = T::ZERO;
+        self.previous_output = T::ZERO;
+    }
+
+    #[inline]
+    pub fn process(&mut self, input: T) -> T {
+        self.previous_output =
+            input - self.previous_input + self.coefficient * self.previous_output;
+        self.previous_input = input;
+        self.previous_output
+    }
+}
+
+/// Drives a signal into a [`Curve`]. A bias shifts the signal along the curve before shaping,
+/// which makes it asymmetric and adds even harmonics like a single-ended tube stage. The offset
+/// this leaves at silence is subtracted and the remaining DC is filtered out.
+#[derive(Debug, Clone)]
+pub struct Waveshaper<T: Sample = f32> {
+    curve: Curve,
+    drive: T,
+    bias: T,
+    /// The curve's output at silence, subtracted so the bias doesn't add a DC step.
+    bias_offset: T,
+    dc_blocker: DcBlocker<T>,
+}
+
+impl<T: Sample> Waveshaper<T> {
+    /// The sample rate is the one the shaper runs at, so the oversampled rate inside an
+    /// oversampler.
+    pub fn new(sample_rate: T) -> Self {
+        Self {
+            curve: Curve::default(),
+            drive: T::ONE,
+            bias: T::ZERO,
+            bias_offset: T::ZERO,
+            dc_blocker: DcBlocker::new(sample_rate),
+        }
+    }
+
+    pub fn set_curve(&mut self, curve: Curve) {
+        self.curve = curve;
+        self.bias_offset = curve.apply(self.bias);
+    }
+
+    /// Set the linear gain into the curve.
+    pub fn set_drive(&mut self, drive: T) {
+        self.drive = drive;
+    }
+
+    pub fn set_bias(&mut self, bias: T) {
+        self.bias = bias;
+        self.bias_offset = self.curve.apply(bias);
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: T) {
+        self.dc_blocker.set_sample_rate(sample_rate);
+    }
+
+    pub fn reset(&mut self) {
+        self.dc_blocker.reset();
+    }
+
+    #[inline]
+    pub fn process(&mut self, input: T) -> T {
+        let shaped = self.curve.apply(input * self.drive + self.bias) - self.bias_offset;
+        if self.bias == T::ZERO {
+            shaped
+        } else {
+            self.dc_blocker.process(shaped)
+        }
+    }
+
+    /// The gain that brings a sine at `amplitude` back to its own RMS level after shaping.
+    /// Multiplying the output by this keeps the loudness roughly constant as the drive changes,
+    /// for level matched comparisons. Cheap enough to call once per block.
+    pub fn level_compensation(&self, amplitude: T) -> T {
+        const POINTS: usize = 64;
+
+        let mut sum = T::ZERO;
+        let mut sum_squares = T::ZERO;
+        for i in 0..POINTS {
+            let phase = T::TAU * T::from_f32(i as f32 / POINTS as f32);
+            let shaped = self
+                .curve
+                .apply(phase.sin() * amplitude * self.drive + self.bias);
+            sum += shaped;
+            sum_squares += shaped * shaped;
+        }
+
+        // The mean is DC, which the DC blocker removes
+        let points = T::from_f32(POINTS as f32);
+        let mean = sum / points;
+        let output_rms = (sum_squares / points - mean * mean).max(T::ZERO).sqrt();
+        let input_rms = amplitude * T::from_f32(core::f32::consts::FRAC_1_SQRT_2);
+        if output_rms > T::ZERO {
+            input_rms / output_rms
+        } else {
+            T::ONE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CURVES: [Curve; 4] = [Curve::Tanh, Curve::Algebraic, Curve::Cubic, Curve::HardClip];
+
+    #[test]
+    fn curves_are_bounded_odd_and_transparent_for_small_signals() {
+        for curve in CURVES {
+            for i in -10000..=10000 {
+                let x = i as f32 * 0.01;
+                let y = curve.apply(x);
+                assert!(y.abs() <= 1.0, "{curve:?}({x}) = {y}");
+                assert_eq!(curve.apply(-x), -y, "{curve:?}({x})");
+            }
+
+            let small = curve.apply(0.001f32);
+            assert!((small - 0.001).abs() < 1e-6, "{curve:?}: {small}");
+        }
+    }
+
+    #[test]
+    fn driven_and_biased_shapers_stay_bounded() {
+        for curve in CURVES {
+            for bias in [0.0, 0.3, -0.7] {
+                let mut shaper = Waveshaper::new(48000.0);
+                shaper.set_curve(curve);
+                shaper.set_drive(50.0);
+                shaper.set_bias(bias);
+
+                // The offset at silence is removed, the DC blocker only takes out what's left
+                let limit = if bias == 0.0 { 1.0 } else { 2.0 };
+                for n in 0..48000 {
+                    let input = (n as f32 * 0.05).sin() * (n as f32 * 0.0003).cos();
+                    let output = shaper.process(input);
+                    assert!(
+                        output.is_finite() && output.abs() <= limit,
+                        "{curve:?}, {bias}"
+                    );
+                }
+                assert_eq!(Waveshaper::new(48000.0).process(0.0), 0.0);
+            }
+        }
+    }
+}
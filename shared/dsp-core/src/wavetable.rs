@@ -0,0 +1,156 @@
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+use crate::Sample;
+
+/// Importing `.wav` wavetables and building tables from frames
+#[cfg(feature = "std")]
+pub mod import;
+
+/// Built-in tables
+#[cfg(feature = "std")]
+pub mod factory;
+
+/// The number of samples in a single frame.
+pub const FRAME_SIZE: usize = 2048;
+
+/// The number of band-limited copies stored for each frame. Level `n` holds the harmonics up to
+/// [`max_harmonic(n)`][max_harmonic()], one octave fewer than the level before it.
+pub const NUM_LEVELS: usize = 11;
+
+/// The highest harmonic kept at a mipmap level.
+pub const fn max_harmonic(level: usize) -> usize {
+    (FRAME_SIZE / 2) >> level
+}
+
+/// A table of single cycle frames, each stored at [`NUM_LEVELS`] band-limited mipmap levels so
+/// high notes don't alias. With the `std` feature tables can be built from frames or imported from
+/// `.wav` files, see [`import`].
+///
+/// Tables are read-only once built and can be shared between voices, e.g. behind an `Arc`.
+#[derive(Debug, Clone)]
+pub struct Wavetable<T: Sample = f32> {
+    /// Frame-major, so frame `f` at level `l` starts at `(f * NUM_LEVELS + l) * FRAME_SIZE`.
+    data: Vec<T>,
+    num_frames: usize,
+}
+
+impl<T: Sample> Wavetable<T> {
+    /// Wrap mipmapped frames laid out as described on [`Wavetable`]. Returns `None` unless
+    /// `data` holds at least one whole frame with all of its levels.
+    pub fn from_mipmaps(data: Vec<T>) -> Option<Self> {
+        let frame_len = NUM_LEVELS * FRAME_SIZE;
+        if data.is_empty() || !data.len().is_multiple_of(frame_len) {
+            return None;
+        }
+
+        Some(Self {
+            num_frames: data.len() / frame_len,
+            data,
+        })
+    }
+
+    pub fn num_frames(&self) -> usize {
+        self.num_frames
+    }
+
+    /// One frame at one mipmap level.
+    pub fn frame(&self, frame: usize, level: usize) -> &[T] {
+        let start = (frame * NUM_LEVELS + level) * FRAME_SIZE;
+        &self.data[start..start + FRAME_SIZE]
+    }
+
+    /// Read a frame at `phase` in `[0, 1)`, interpolating linearly between samples.
+    fn read(&self, frame: usize, level: usize, phase: T) -> T {
+        let frame = self.frame(frame, level);
+        let position = phase * T::from_f32(FRAME_SIZE as f32);
+        let idx = position.floor();
+        let frac = position - idx;
+        let idx = (idx.to_f32() as usize) % FRAME_SIZE;
+
+        let a = frame[idx];
+        let b = frame[(idx + 1) % FRAME_SIZE];
+        a + (b - a) * frac
+    }
+}
+
+/// An oscillator reading from a [`Wavetable`], morphing between neighbouring frames based on its
+/// position. The table is passed in for every sample so one table can serve all voices.
+#[derive(Debug, Clone)]
+pub struct WavetableOsc<T: Sample = f32> {
+    phase: T,
+    frequency: T,
+    sample_rate: T,
+    /// Where in the table to read, from 0 for the first frame to 1 for the last.
+    position: T,
+    /// The mipmap level that keeps the frequency's harmonics below Nyquist.
+    level: usize,
+}
+
+impl<T: Sample> WavetableOsc<T> {
+    pub fn new(sample_rate: T) -> Self {
+        let mut osc = Self {
+            phase: T::ZERO,
+            frequency: T::ZERO,
+            sample_rate,
+            position: T::ZERO,
+            level: 0,
+        };
+        osc.set_frequency(T::from_f32(440.0));
+
+        osc
+    }
+
+    /// Also picks the mipmap level again, since the new Nyquist frequency may fit more or fewer
+    /// harmonics.
+    pub fn set_sample_rate(&mut self, sample_rate: T) {
+        self.sample_rate = sample_rate;
+        self.set_frequency(self.frequency);
+    }
+
+    pub fn set_frequency(&mut self, freq: T) {
+        self.frequency = freq;
+
+        // The first level whose highest harmonic still fits below Nyquist
+        let nyquist = self.sample_rate * T::HALF;
+        let mut level = 0;
+        while level < NUM_LEVELS - 1
+            && T::from_f32(max_harmonic(level) as f32) * freq.abs() > nyquist
+        {
+            level += 1;
+        }
+        self.level = level;
+    }
+
+    /// Set the morph position, from 0 for the first frame to 1 for the last.
+    pub fn set_position(&mut self, position: T) {
+        self.position = position.clamp(T::ZERO, T::ONE);
+    }
+
+    pub fn next_sample(&mut self, table: &Wavetable<T>) -> T {
+        let last_frame = table.num_frames() - 1;
+        let frame_position = self.position * T::from_f32(last_frame as f32);
+        let frame = frame_position.floor();
+        let morph = frame_position - frame;
+        let frame = (frame.to_f32() as usize).min(last_frame);
+
+        let mut sample = table.read(frame, self.level, self.phase);
+        if frame < last_frame && morph > T::ZERO {
+            let next = table.read(frame + 1, self.level, self.phase);
+            sample += (next - sample) * morph;
+        }
+
+        self.phase += self.frequency / self.sample_rate;
+        if self.phase >= T::ONE {
+            self.phase -= T::ONE;
+        } else if self.phase < T::ZERO {
+            self.phase += T::ONE;
+        }
+
+        sample
+    }
+
+    pub fn reset(&mut self) {
+        self.phase = T::ZERO;
+    }
+}
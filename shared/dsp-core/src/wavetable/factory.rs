@@ -0,0 +1,90 @@
+//! Factory wavetables, generated at load time so they're part of the binary without shipping any
+//! files next to it.
+
+use super::Wavetable;
+use std::f32::consts::TAU;
+
+/// The length the factory frames are generated at, they're resampled like imported frames.
+const FRAME_LENGTH: usize = 2048;
+
+/// Frames in the sweeping factory tables.
+const SWEEP_FRAMES: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FactoryTable {
+    /// Sine, triangle, saw, and square.
+    BasicShapes,
+    /// A pulse narrowing from a square to a thin spike.
+    PulseWidth,
+    /// A sine gaining harmonics until it's a bright saw.
+    Harmonics,
+    /// A hard synced saw sweeping the slave oscillator up four octaves.
+    SyncSweep,
+}
+
+impl FactoryTable {
+    pub const ALL: [FactoryTable; 4] = [
+        FactoryTable::BasicShapes,
+        FactoryTable::PulseWidth,
+        FactoryTable::Harmonics,
+        FactoryTable::SyncSweep,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            FactoryTable::BasicShapes => "Basic Shapes",
+            FactoryTable::PulseWidth => "Pulse Width",
+            FactoryTable::Harmonics => "Harmonics",
+            FactoryTable::SyncSweep => "Sync Sweep",
+        }
+    }
+
+    /// Generate the table. This runs an FFT per frame, so build the tables once up front.
+    pub fn build(self) -> Wavetable {
+        let frames: Vec<Vec<f32>> = match self {
+            FactoryTable::BasicShapes => vec![
+                frame(|phase| (TAU * phase).sin()),
+                frame(|phase| 1.0 - 4.0 * (phase - 0.25 - (phase - 0.25).round()).abs()),
+                frame(|phase| 2.0 * phase - 1.0),
+                frame(|phase| if phase < 0.5 { 1.0 } else { -1.0 }),
+            ],
+            FactoryTable::PulseWidth => sweep(|amount, phase| {
+                let width = 0.5 - amount * 0.47;
+                if phase < width {
+                    1.0
+                } else {
+                    -1.0
+                }
+            }),
+            FactoryTable::Harmonics => sweep(|amount, phase| {
+                let num_harmonics = 1 + (amount * 63.0).round() as usize;
+                (1..=num_harmonics)
+                    .map(|harmonic| (TAU * phase * harmonic as f32).sin() / harmonic as f32)
+                    .sum()
+            }),
+            FactoryTable::SyncSweep => sweep(|amount, phase| {
+                let ratio = 2.0f32.powf(amount * 4.0);
+                let slave_phase = (phase * ratio).fract();
+                2.0 * slave_phase - 1.0
+            }),
+        };
+
+        Wavetable::from_frames(&frames).expect("factory frames are never empty")
+    }
+}
+
+fn frame(f: impl Fn(f32) -> f32) -> Vec<f32> {
+    (0..FRAME_LENGTH)
+        .map(|i| f(i as f32 / FRAME_LENGTH as f32))
+        .collect()
+}
+
+/// Frames for a sweep from `amount` 0 to 1.
+fn sweep(f: impl Fn(f32, f32) -> f32) -> Vec<Vec<f32>> {
+    (0..SWEEP_FRAMES)
+        .map(|idx| {
+            let amount = idx as f32 / (SWEEP_FRAMES - 1) as f32;
+            frame(|phase| f(amount, phase))
+        })
+        .collect()
+}
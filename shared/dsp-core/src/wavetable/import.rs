@@ -0,0 +1,413 @@
+//! Building wavetables from single cycle frames and importing them from `.wav` files.
+//!
+//! Wavetable `.wav` files are a mono or multichannel sample stream cut into frames of a fixed
+//! length, 2048 samples unless the file says otherwise. Serum-style banks store their frame length
+//! in a `clm ` chunk, which is honoured when present. Frames of any length are resampled to
+//! [`FRAME_SIZE`] and band-limited into the mipmap levels with an FFT, so importing belongs on a
+//! background thread, never the audio thread.
+//!
+//! [`decode_wav()`] exposes the decoder itself for other audio files, like impulse responses.
+
+use super::{max_harmonic, Wavetable, FRAME_SIZE, NUM_LEVELS};
+use realfft::num_complex::Complex32;
+use realfft::RealFftPlanner;
+use std::fmt;
+use std::path::Path;
+
+/// The frame length assumed when a file doesn't specify one.
+pub const DEFAULT_FRAME_LENGTH: usize = 2048;
+
+/// The most frames a table may have, matching common wavetable editors.
+pub const MAX_FRAMES: usize = 256;
+
+#[derive(Debug)]
+pub enum ImportError {
+    Io(std::io::Error),
+    /// The data isn't a RIFF/WAVE file.
+    NotWav,
+    /// The sample format isn't PCM or IEEE float, or has an unsupported bit depth.
+    UnsupportedFormat {
+        format: u16,
+        bits_per_sample: u16,
+    },
+    /// The file has fewer samples than one frame.
+    TooShort {
+        samples: usize,
+        frame_length: usize,
+    },
+    /// A frame had fewer than two samples.
+    InvalidFrameLength(usize),
+    NoFrames,
+}
+
+impl fmt::Display for ImportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImportError::Io(err) => write!(f, "could not read the file: {err}"),
+            ImportError::NotWav => write!(f, "not a WAV file"),
+            ImportError::UnsupportedFormat {
+                format,
+                bits_per_sample,
+            } => write!(
+                f,
+                "unsupported sample format {format:#06x} at {bits_per_sample} bits"
+            ),
+            ImportError::TooShort {
+                samples,
+                frame_length,
+            } => write!(
+                f,
+                "{samples} samples is shorter than one {frame_length} sample frame"
+            ),
+            ImportError::InvalidFrameLength(length) => {
+                write!(f, "frames of {length} samples are too short")
+            }
+            ImportError::NoFrames => write!(f, "the wavetable has no frames"),
+        }
+    }
+}
+
+impl std::error::Error for ImportError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ImportError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for ImportError {
+    fn from(err: std::io::Error) -> Self {
+        ImportError::Io(err)
+    }
+}
+
+impl Wavetable<f32> {
+    /// Build a table from single cycle frames of any length. Each frame is resampled to
+    /// [`FRAME_SIZE`], has its DC offset removed, and is normalized to a peak of one. At most
+    /// [`MAX_FRAMES`] frames are used.
+    pub fn from_frames<F: AsRef<[f32]>>(frames: &[F]) -> Result<Self, ImportError> {
+        let frames = &frames[..frames.len().min(MAX_FRAMES)];
+        let mut planner = RealFftPlanner::<f32>::new();
+        let inverse = planner.plan_fft_inverse(FRAME_SIZE);
+        let mut spectrum = inverse.make_input_vec();
+        let mut level_spectrum = inverse.make_input_vec();
+        let mut output = inverse.make_output_vec();
+
+        let mut data = Vec::with_capacity(frames.len() * NUM_LEVELS * FRAME_SIZE);
+        for frame in frames {
+            let frame = frame.as_ref();
+            if frame.len() < 2 {
+                return Err(ImportError::InvalidFrameLength(frame.len()));
+            }
+
+            // Analysing the frame at its own length and resynthesizing at FRAME_SIZE resamples it
+            // without adding harmonics the frame didn't have
+            let forward = planner.plan_fft_forward(frame.len());
+            let mut input = frame.to_vec();
+            let mut frame_spectrum = forward.make_output_vec();
+            forward
+                .process(&mut input, &mut frame_spectrum)
+                .expect("buffers come from the planner");
+            let scale = FRAME_SIZE as f32 / frame.len() as f32;
+            spectrum.fill(Complex32::ZERO);
+            for (bin, harmonic) in spectrum.iter_mut().zip(&frame_spectrum) {
+                *bin = harmonic * scale;
+            }
+            // A short frame's Nyquist bin stands for both halves of the spectrum, at the new
+            // length it becomes an ordinary bin that only stands for one
+            if frame.len().is_multiple_of(2) && frame.len() < FRAME_SIZE {
+                spectrum[frame.len() / 2] *= 0.5;
+            }
+
+            let frame_start = data.len();
+            for level in 0..NUM_LEVELS {
+                level_spectrum.copy_from_slice(&spectrum);
+                level_spectrum[0] = Complex32::ZERO;
+                for bin in &mut level_spectrum[max_harmonic(level) + 1..] {
+                    *bin = Complex32::ZERO;
+                }
+                // The inverse transform needs a real Nyquist bin
+                if let Some(nyquist) = level_spectrum.last_mut() {
+                    nyquist.im = 0.0;
+                }
+
+                inverse
+                    .process(&mut level_spectrum, &mut output)
+                    .expect("buffers come from the planner");
+                data.extend(output.iter().map(|sample| sample / FRAME_SIZE as f32));
+            }
+
+            // The fullest level has the highest peak, the others are scaled by the same amount so
+            // the level doesn't jump between notes
+            let peak = data[frame_start..frame_start + FRAME_SIZE]
+                .iter()
+                .fold(0.0f32, |peak, sample| peak.max(sample.abs()));
+            if peak > 0.0 {
+                for sample in &mut data[frame_start..] {
+                    *sample /= peak;
+                }
+            }
+        }
+
+        Wavetable::from_mipmaps(data).ok_or(ImportError::NoFrames)
+    }
+
+    /// Import a wavetable `.wav` file's contents. Multichannel files use their first channel.
+    pub fn from_wav(bytes: &[u8]) -> Result<Self, ImportError> {
+        let wav = parse_wav(bytes)?;
+        let frame_length = wav.frame_length.unwrap_or(DEFAULT_FRAME_LENGTH);
+        if frame_length < 2 {
+            return Err(ImportError::InvalidFrameLength(frame_length));
+        }
+        let samples = &wav.audio.channels[0];
+        if samples.len() < frame_length {
+            return Err(ImportError::TooShort {
+                samples: samples.len(),
+                frame_length,
+            });
+        }
+
+        // A trailing partial frame is dropped
+        let frames: Vec<&[f32]> = samples.chunks_exact(frame_length).collect();
+        Self::from_frames(&frames)
+    }
+
+    /// Read and import a wavetable `.wav` file.
+    pub fn load_wav(path: impl AsRef<Path>) -> Result<Self, ImportError> {
+        Self::from_wav(&std::fs::read(path)?)
+    }
+}
+
+const FORMAT_PCM: u16 = 0x0001;
+const FORMAT_IEEE_FLOAT: u16 = 0x0003;
+const FORMAT_EXTENSIBLE: u16 = 0xfffe;
+
+/// A decoded `.wav` file.
+#[derive(Debug, Clone)]
+pub struct WavAudio {
+    pub sample_rate: u32,
+    /// The samples of each channel, there's always at least one.
+    pub channels: Vec<Vec<f32>>,
+}
+
+/// Decode every channel of a PCM or IEEE float `.wav` file.
+pub fn decode_wav(bytes: &[u8]) -> Result<WavAudio, ImportError> {
+    Ok(parse_wav(bytes)?.audio)
+}
+
+/// Read and decode a `.wav` file.
+pub fn read_wav(path: impl AsRef<Path>) -> Result<WavAudio, ImportError> {
+    decode_wav(&std::fs::read(path)?)
+}
+
+struct WavData {
+    audio: WavAudio,
+    /// The frame length from a Serum `clm ` chunk.
+    frame_length: Option<usize>,
+}
+
+fn parse_wav(bytes: &[u8]) -> Result<WavData, ImportError> {
+    if bytes.len() < 12 || &bytes[0..4] != b"RIFF" || &bytes[8..12] != b"WAVE" {
+        return Err(ImportError::NotWav);
+    }
+
+    let mut format = None;
+    let mut data = None;
+    let mut frame_length = None;
+
+    let mut pos = 12;
+    while pos + 8 <= bytes.len() {
+        let id = &bytes[pos..pos + 4];
+        let len = u32::from_le_bytes(bytes[pos + 4..pos + 8].try_into().unwrap()) as usize;
+        let body = &bytes[pos + 8..(pos + 8).saturating_add(len).min(bytes.len())];
+        match id {
+            b"fmt " => format = Some(parse_format(body)?),
+            b"data" => data = Some(body),
+            b"clm " => frame_length = parse_clm(body),
+            _ => {}
+        }
+
+        // Chunks are padded to an even length
+        pos = pos.saturating_add(8 + len + (len & 1));
+    }
+
+    let (Some(format), Some(data)) = (format, data) else {
+        return Err(ImportError::NotWav);
+    };
+
+    Ok(WavData {
+        audio: WavAudio {
+            sample_rate: format.sample_rate,
+            channels: decode_channels(data, &format),
+        },
+        frame_length,
+    })
+}
+
+struct WavFormat {
+    format: u16,
+    channels: usize,
+    sample_rate: u32,
+    bits_per_sample: u16,
+}
+
+fn parse_format(body: &[u8]) -> Result<WavFormat, ImportError> {
+    if body.len() < 16 {
+        return Err(ImportError::NotWav);
+    }
+
+    let read_u16 = |offset: usize| u16::from_le_bytes([body[offset], body[offset + 1]]);
+    let mut format = read_u16(0);
+    let channels = read_u16(2) as usize;
+    let sample_rate = u32::from_le_bytes(body[4..8].try_into().unwrap());
+    let bits_per_sample = read_u16(14);
+    // The actual format is the first two bytes of the extensible format's subformat GUID
+    if format == FORMAT_EXTENSIBLE && body.len() >= 26 {
+        format = read_u16(24);
+    }
+
+    let supported = matches!(
+        (format, bits_per_sample),
+        (FORMAT_PCM, 8 | 16 | 24 | 32) | (FORMAT_IEEE_FLOAT, 32 | 64)
+    );
+    if !supported || channels == 0 {
+        return Err(ImportError::UnsupportedFormat {
+            format,
+            bits_per_sample,
+        });
+    }
+
+    Ok(WavFormat {
+        format,
+        channels,
+        sample_rate,
+        bits_per_sample,
+    })
+}
+
+/// Serum's `clm ` chunk starts with `<!>` followed by the frame length, e.g. `<!>2048 01000000`.
+fn parse_clm(body: &[u8]) -> Option<usize> {
+    let text = body.strip_prefix(b"<!>")?;
+    let digits = text.iter().take_while(|byte| byte.is_ascii_digit()).count();
+    std::str::from_utf8(&text[..digits]).ok()?.parse().ok()
+}
+
+fn decode_channels(data: &[u8], format: &WavFormat) -> Vec<Vec<f32>> {
+    let bytes_per_sample = format.bits_per_sample as usize / 8;
+    let num_frames = data.len() / (bytes_per_sample * format.channels);
+    let mut channels: Vec<Vec<f32>> = (0..format.channels)
+        .map(|_| Vec::with_capacity(num_frames))
+        .collect();
+    for frame in data.chunks_exact(bytes_per_sample * format.channels) {
+        for (channel, sample) in channels
+            .iter_mut()
+            .zip(frame.chunks_exact(bytes_per_sample))
+        {
+            channel.push(decode_sample(sample, format));
+        }
+    }
+
+    channels
+}
+
+fn decode_sample(sample: &[u8], format: &WavFormat) -> f32 {
+    match (format.format, format.bits_per_sample) {
+        // 8-bit PCM is unsigned
+        (FORMAT_PCM, 8) => (sample[0] as f32 - 128.0) / 128.0,
+        (FORMAT_PCM, 16) => i16::from_le_bytes([sample[0], sample[1]]) as f32 / 32768.0,
+        (FORMAT_PCM, 24) => {
+            // Shift into the top of an i32 to sign extend
+            let value = i32::from_le_bytes([0, sample[0], sample[1], sample[2]]) >> 8;
+            value as f32 / 8_388_608.0
+        }
+        (FORMAT_PCM, _) => i32::from_le_bytes(sample.try_into().unwrap()) as f32 / 2_147_483_648.0,
+        (_, 32) => f32::from_le_bytes(sample.try_into().unwrap()),
+        (_, _) => f64::from_le_bytes(sample.try_into().unwrap()) as f32,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::TAU;
+
+    /// A 16-bit mono `.wav` file with a Serum `clm ` chunk giving its frame length.
+    fn wavetable_wav(samples: &[f32], frame_length: usize) -> Vec<u8> {
+        let clm = format!("<!>{frame_length} 00000000");
+        let data_len = samples.len() * 2;
+
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(b"RIFF");
+        let clm_padding = clm.len() & 1;
+        let riff_len = 4 + (8 + 16) + (8 + clm.len() + clm_padding) + (8 + data_len);
+        bytes.extend_from_slice(&(riff_len as u32).to_le_bytes());
+        bytes.extend_from_slice(b"WAVE");
+
+        bytes.extend_from_slice(b"fmt ");
+        bytes.extend_from_slice(&16u32.to_le_bytes());
+        bytes.extend_from_slice(&FORMAT_PCM.to_le_bytes());
+        bytes.extend_from_slice(&1u16.to_le_bytes());
+        bytes.extend_from_slice(&48000u32.to_le_bytes());
+        bytes.extend_from_slice(&(48000u32 * 2).to_le_bytes());
+        bytes.extend_from_slice(&2u16.to_le_bytes());
+        bytes.extend_from_slice(&16u16.to_le_bytes());
+
+        bytes.extend_from_slice(b"clm ");
+        bytes.extend_from_slice(&(clm.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(clm.as_bytes());
+        bytes.resize(bytes.len() + clm_padding, 0);
+
+        bytes.extend_from_slice(b"data");
+        bytes.extend_from_slice(&(data_len as u32).to_le_bytes());
+        for sample in samples {
+            bytes.extend_from_slice(&((sample * 32767.0).round() as i16).to_le_bytes());
+        }
+
+        bytes
+    }
+
+    #[test]
+    fn single_cycle_tables_are_split_and_normalized() {
+        // Three 256 sample frames of a quiet sine with a DC offset, plus a partial frame
+        let frame_length = 256;
+        let samples: Vec<f32> = (0..frame_length * 3 + 100)
+            .map(|n| 0.2 + 0.4 * (TAU * n as f32 / frame_length as f32).sin())
+            .collect();
+        let table = Wavetable::from_wav(&wavetable_wav(&samples, frame_length)).unwrap();
+        assert_eq!(table.num_frames(), 3);
+
+        for frame in 0..table.num_frames() {
+            for level in 0..NUM_LEVELS {
+                let samples = table.frame(frame, level);
+                let dc = samples.iter().sum::<f32>() / FRAME_SIZE as f32;
+                assert!(dc.abs() < 1e-4, "{frame}, {level}: {dc}");
+
+                // A sine survives every level and is resampled to a full-scale sine
+                for (n, &sample) in samples.iter().enumerate() {
+                    let expected = (TAU * n as f32 / FRAME_SIZE as f32).sin();
+                    assert!(
+                        (sample - expected).abs() < 1e-3,
+                        "{frame}, {level}, {n}: {sample} vs {expected}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn files_shorter_than_a_frame_are_rejected() {
+        let bytes = wavetable_wav(&[0.5; 100], 256);
+        assert!(matches!(
+            Wavetable::from_wav(&bytes),
+            Err(ImportError::TooShort {
+                samples: 100,
+                frame_length: 256
+            })
+        ));
+        assert!(matches!(
+            Wavetable::from_wav(b"not a wav file"),
+            Err(ImportError::NotWav)
+        ));
+    }
+}
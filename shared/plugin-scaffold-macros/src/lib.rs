@@ -0,0 +1,173 @@
+//! Procedural macros for plugin-scaffold. These expand to paths in plugin-scaffold, so use them
+//! through its re-exports rather than depending on this crate directly.
+//!
+//! There are no dependencies on purpose: the input is a single string literal, which is easy
+//! enough to take apart by hand, and the output is built as source text.
+
+use proc_macro::{TokenStream, TokenTree};
+use std::env;
+use std::path::{Path, PathBuf};
+
+/// Embed every preset file matching a pattern relative to the plugin's `Cargo.toml` into the
+/// binary, as a `&'static [plugin_scaffold::presets::FactoryPreset]` sorted by file name. Each
+/// preset is named after its file, without the extension.
+///
+/// The pattern can have a single `*` in its file name, e.g. `"presets/*.json"`. The directory is
+/// read when the plugin is compiled, so adding a preset file needs a rebuild of the plugin crate
+/// to be picked up. Changes to files that were already embedded are picked up automatically.
+///
+/// ```ignore
+/// impl FactoryPresets for MyPlugin {
+///     const FACTORY_PRESETS: &'static [FactoryPreset] = embed_presets!("presets/*.json");
+/// }
+/// ```
+#[proc_macro]
+pub fn embed_presets(input: TokenStream) -> TokenStream {
+    let source = match expand_embed_presets(input) {
+        Ok(source) => source,
+        Err(message) => format!("compile_error!({message:?})"),
+    };
+    source.parse().expect("the expansion is valid Rust")
+}
+
+fn expand_embed_presets(input: TokenStream) -> Result<String, String> {
+    let mut tokens = input.into_iter();
+    let pattern = match (tokens.next(), tokens.next()) {
+        (Some(TokenTree::Literal(literal)), None) => string_literal(&literal.to_string())?,
+        _ => return Err("expected a file pattern like \"presets/*.json\"".to_owned()),
+    };
+
+    let manifest_dir = env::var("CARGO_MANIFEST_DIR")
+        .map_err(|_| "CARGO_MANIFEST_DIR isn't set, build the plugin with Cargo".to_owned())?;
+    let files = matching_files(Path::new(&manifest_dir), &pattern)?;
+
+    let mut source = String::from("&[");
+    for (name, path) in files {
+        let path = path
+            .to_str()
+            .ok_or_else(|| format!("{} isn't a valid UTF-8 path", path.display()))?;
+        // Including the file also makes Cargo rebuild the plugin when it changes
+        source.push_str(&format!(
+            "::plugin_scaffold::presets::FactoryPreset {{ name: {name:?}, json: \
+             ::std::include_str!({path:?}) }},"
+        ));
+    }
+    source.push(']');
+
+    Ok(source)
+}
+
+/// The contents of a plain string literal.
+fn string_literal(literal: &str) -> Result<String, String> {
+    literal
+        .strip_prefix('"')
+        .and_then(|literal| literal.strip_suffix('"'))
+        .filter(|contents| !contents.contains('\\'))
+        .map(str::to_owned)
+        .ok_or_else(|| format!("expected a plain string literal, got {literal}"))
+}
+
+/// The files matching `pattern` relative to `root`, sorted by name, with the names they'll be
+/// listed under.
+fn matching_files(root: &Path, pattern: &str) -> Result<Vec<(String, PathBuf)>, String> {
+    let (dir, file_pattern) = pattern.rsplit_once('/').unwrap_or(("", pattern));
+    if dir.contains('*') {
+        return Err(format!(
+            "{pattern}: only the file name can have a `*`, not the directory"
+        ));
+    }
+    let (prefix, suffix) = match file_pattern.split_once('*') {
+        Some((_, suffix)) if suffix.contains('*') => {
+            return Err(format!("{pattern}: only one `*` is supported"))
+        }
+        Some((prefix, suffix)) => (prefix, suffix),
+        None => (file_pattern, ""),
+    };
+
+    let dir = root.join(dir);
+    let entries = dir
+        .read_dir()
+        .map_err(|err| format!("could not read {}: {err}", dir.display()))?;
+
+    let mut files = Vec::new();
+    for entry in entries {
+        let path = entry
+            .map_err(|err| format!("could not read {}: {err}", dir.display()))?
+            .path();
+        let Some(file_name) = path.file_name().and_then(|name| name.to_str()) else {
+            continue;
+        };
+
+        let matches = if file_pattern.contains('*') {
+            file_name.len() >= prefix.len() + suffix.len()
+                && file_name.starts_with(prefix)
+                && file_name.ends_with(suffix)
+        } else {
+            file_name == file_pattern
+        };
+        if matches && path.is_file() {
+            let name = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .unwrap_or(file_name)
+                .to_owned();
+            files.push((name, path));
+        }
+    }
+
+    files.sort();
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The fixture plugin-scaffold embeds in its own tests.
+    fn fixtures() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("../plugin-scaffold/fixtures")
+    }
+
+    fn names(files: Vec<(String, PathBuf)>) -> Vec<String> {
+        files.into_iter().map(|(name, _)| name).collect()
+    }
+
+    #[test]
+    fn only_plain_string_literals_are_accepted() {
+        assert_eq!(
+            string_literal("\"presets/*.json\"").as_deref(),
+            Ok("presets/*.json")
+        );
+        assert!(string_literal("r\"presets/*.json\"").is_err());
+        assert!(string_literal("\"presets\\\\*.json\"").is_err());
+        assert!(string_literal("presets").is_err());
+    }
+
+    #[test]
+    fn wildcards_match_by_prefix_and_suffix() {
+        let files = matching_files(&fixtures(), "presets/*.json").unwrap();
+        assert!(files.iter().all(|(_, path)| path.is_file()));
+        assert_eq!(names(files), ["Bright", "Dark"]);
+
+        let files = matching_files(&fixtures(), "presets/B*").unwrap();
+        assert_eq!(names(files), ["Bright"]);
+        let files = matching_files(&fixtures(), "presets/*.txt").unwrap();
+        assert_eq!(names(files), ["README"]);
+    }
+
+    #[test]
+    fn patterns_without_a_wildcard_match_one_file() {
+        let files = matching_files(&fixtures(), "presets/Dark.json").unwrap();
+        assert_eq!(names(files), ["Dark"]);
+        assert!(matching_files(&fixtures(), "presets/Dark")
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn bad_patterns_are_errors() {
+        assert!(matching_files(&fixtures(), "presets/*.*").is_err());
+        assert!(matching_files(&fixtures(), "*/Bright.json").is_err());
+        assert!(matching_files(&fixtures(), "missing/*.json").is_err());
+    }
+}
@@ -0,0 +1,222 @@
+//! Value formatters for units nih_plug doesn't cover, in the style of
+//! `nih_plug::formatters`: times that switch between milliseconds and seconds, and tempo synced
+//! note divisions. The formatters include the unit, so don't add one with `.with_unit()`.
+
+use nih_plug::prelude::*;
+use std::sync::Arc;
+
+/// Format a time stored in seconds, in milliseconds below one second.
+pub fn v2s_f32_s_then_ms(digits: usize) -> Arc<dyn Fn(f32) -> String + Send + Sync> {
+    Arc::new(move |seconds| format_time(seconds * 1000.0, digits))
+}
+
+/// Parse a time in seconds, or in milliseconds when it ends in `ms`.
+pub fn s2v_f32_s_then_ms() -> Arc<dyn Fn(&str) -> Option<f32> + Send + Sync> {
+    Arc::new(|string| {
+        parse_time(string).map(|(value, is_ms)| if is_ms { value / 1000.0 } else { value })
+    })
+}
+
+/// Format a time stored in milliseconds, in seconds from one second up.
+pub fn v2s_f32_ms_then_s(digits: usize) -> Arc<dyn Fn(f32) -> String + Send + Sync> {
+    Arc::new(move |ms| format_time(ms, digits))
+}
+
+/// Parse a time in milliseconds, or in seconds when it ends in `s`.
+pub fn s2v_f32_ms_then_s() -> Arc<dyn Fn(&str) -> Option<f32> + Send + Sync> {
+    Arc::new(|string| {
+        let (value, is_ms) = parse_time(string)?;
+        let bare = !string.trim().to_lowercase().ends_with('s');
+        Some(if is_ms || bare { value } else { value * 1000.0 })
+    })
+}
+
+/// Milliseconds with `digits` decimals, or seconds with at least one once they reach one second.
+fn format_time(ms: f32, digits: usize) -> String {
+    if ms.abs() < 1000.0 {
+        format!("{ms:.digits$} ms")
+    } else {
+        format!("{:.*} s", digits.max(1), ms / 1000.0)
+    }
+}
+
+/// The number in a time, and whether it was given in milliseconds.
+fn parse_time(string: &str) -> Option<(f32, bool)> {
+    let string = string.trim().to_lowercase();
+    let (number, is_ms) = match string.strip_suffix("ms") {
+        Some(number) => (number, true),
+        None => (string.strip_suffix('s').unwrap_or(&string), false),
+    };
+    number.trim().parse().ok().map(|value| (value, is_ms))
+}
+
+/// Note lengths for tempo synced rates and times, from a whole note down to a 64th, with
+/// dotted and triplet variants.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+pub enum NoteDivision {
+    #[name = "1/1"]
+    Whole,
+    #[name = "1/2 D"]
+    HalfDotted,
+    #[name = "1/1 T"]
+    WholeTriplet,
+    #[name = "1/2"]
+    Half,
+    #[name = "1/4 D"]
+    QuarterDotted,
+    #[name = "1/2 T"]
+    HalfTriplet,
+    #[name = "1/4"]
+    Quarter,
+    #[name = "1/8 D"]
+    EighthDotted,
+    #[name = "1/4 T"]
+    QuarterTriplet,
+    #[name = "1/8"]
+    Eighth,
+    #[name = "1/16 D"]
+    SixteenthDotted,
+    #[name = "1/8 T"]
+    EighthTriplet,
+    #[name = "1/16"]
+    Sixteenth,
+    #[name = "1/32 D"]
+    ThirtySecondDotted,
+    #[name = "1/16 T"]
+    SixteenthTriplet,
+    #[name = "1/32"]
+    ThirtySecond,
+    #[name = "1/32 T"]
+    ThirtySecondTriplet,
+    #[name = "1/64"]
+    SixtyFourth,
+}
+
+impl NoteDivision {
+    /// The length in quarter note beats.
+    pub fn beats(self) -> f32 {
+        match self {
+            NoteDivision::Whole => 4.0,
+            NoteDivision::HalfDotted => 3.0,
+            NoteDivision::WholeTriplet => 8.0 / 3.0,
+            NoteDivision::Half => 2.0,
+            NoteDivision::QuarterDotted => 1.5,
+            NoteDivision::HalfTriplet => 4.0 / 3.0,
+            NoteDivision::Quarter => 1.0,
+            NoteDivision::EighthDotted => 0.75,
+            NoteDivision::QuarterTriplet => 2.0 / 3.0,
+            NoteDivision::Eighth => 0.5,
+            NoteDivision::SixteenthDotted => 0.375,
+            NoteDivision::EighthTriplet => 1.0 / 3.0,
+            NoteDivision::Sixteenth => 0.25,
+            NoteDivision::ThirtySecondDotted => 0.1875,
+            NoteDivision::SixteenthTriplet => 1.0 / 6.0,
+            NoteDivision::ThirtySecond => 0.125,
+            NoteDivision::ThirtySecondTriplet => 1.0 / 12.0,
+            NoteDivision::SixtyFourth => 0.0625,
+        }
+    }
+
+    /// The length in seconds at `tempo` beats per minute.
+    pub fn seconds(self, tempo: f64) -> f32 {
+        (self.beats() as f64 * 60.0 / tempo) as f32
+    }
+
+    /// The rate in Hz of something repeating once per division at `tempo` beats per minute.
+    pub fn hz(self, tempo: f64) -> f32 {
+        self.seconds(tempo).recip()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn milliseconds_switch_to_seconds_at_one_second() {
+        let to_string = v2s_f32_ms_then_s(0);
+        assert_eq!(to_string(350.0), "350 ms");
+        assert_eq!(to_string(1000.0), "1.0 s");
+        assert_eq!(to_string(2500.0), "2.5 s");
+
+        let to_string = v2s_f32_s_then_ms(1);
+        assert_eq!(to_string(0.0125), "12.5 ms");
+        assert_eq!(to_string(1.5), "1.5 s");
+    }
+
+    #[test]
+    fn times_parse_in_either_unit() {
+        let from_string = s2v_f32_ms_then_s();
+        assert_eq!(from_string("350"), Some(350.0));
+        assert_eq!(from_string(" 350 ms "), Some(350.0));
+        assert_eq!(from_string("1.5 s"), Some(1500.0));
+        assert_eq!(from_string("1.5S"), Some(1500.0));
+
+        let from_string = s2v_f32_s_then_ms();
+        assert_eq!(from_string("1.5"), Some(1.5));
+        assert_eq!(from_string("1.5 s"), Some(1.5));
+        assert_eq!(from_string("250 ms"), Some(0.25));
+        assert_eq!(from_string("250MS"), Some(0.25));
+
+        for string in ["", "ms", "fast", "1.5 min"] {
+            assert_eq!(s2v_f32_ms_then_s()(string), None, "{string}");
+            assert_eq!(s2v_f32_s_then_ms()(string), None, "{string}");
+        }
+    }
+
+    #[test]
+    fn formatted_times_parse_back_to_their_value() {
+        let (to_string, from_string) = (v2s_f32_ms_then_s(1), s2v_f32_ms_then_s());
+        for ms in [0.5, 20.0, 350.0, 999.5, 1000.0, 1500.0, 12500.0] {
+            assert_eq!(from_string(&to_string(ms)), Some(ms), "{ms}");
+        }
+
+        let (to_string, from_string) = (v2s_f32_s_then_ms(1), s2v_f32_s_then_ms());
+        for seconds in [0.0005, 0.02, 0.35, 1.0, 1.5, 12.5] {
+            let parsed = from_string(&to_string(seconds)).unwrap();
+            assert!((parsed - seconds).abs() < 1e-6, "{parsed} vs {seconds}");
+        }
+    }
+
+    #[test]
+    fn dotted_and_triplet_divisions_scale_the_plain_ones() {
+        use NoteDivision::*;
+
+        for (dotted, plain) in [
+            (HalfDotted, Half),
+            (QuarterDotted, Quarter),
+            (EighthDotted, Eighth),
+            (SixteenthDotted, Sixteenth),
+            (ThirtySecondDotted, ThirtySecond),
+        ] {
+            assert_eq!(dotted.beats(), plain.beats() * 1.5, "{dotted:?}");
+        }
+        for (triplet, plain) in [
+            (WholeTriplet, Whole),
+            (HalfTriplet, Half),
+            (QuarterTriplet, Quarter),
+            (EighthTriplet, Eighth),
+            (SixteenthTriplet, Sixteenth),
+            (ThirtySecondTriplet, ThirtySecond),
+        ] {
+            let ratio = triplet.beats() / plain.beats();
+            assert!((ratio - 2.0 / 3.0).abs() < 1e-6, "{triplet:?}: {ratio}");
+        }
+    }
+
+    #[test]
+    fn divisions_are_listed_from_longest_to_shortest() {
+        let beats: Vec<f32> = (0..NoteDivision::variants().len())
+            .map(|index| NoteDivision::from_index(index).beats())
+            .collect();
+        assert!(beats.windows(2).all(|pair| pair[0] > pair[1]), "{beats:?}");
+    }
+
+    #[test]
+    fn divisions_convert_to_seconds_and_hz_at_a_tempo() {
+        assert_eq!(NoteDivision::Quarter.seconds(120.0), 0.5);
+        assert_eq!(NoteDivision::Quarter.hz(120.0), 2.0);
+        assert_eq!(NoteDivision::EighthDotted.seconds(150.0), 0.3);
+        assert_eq!(NoteDivision::Whole.hz(60.0), 0.25);
+    }
+}
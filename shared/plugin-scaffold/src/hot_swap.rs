@@ -0,0 +1,160 @@
+use arc_swap::ArcSwapOption;
+use std::sync::{Arc, Mutex};
+
+/// Large non-parameter state, like a sample, wavetable, or impulse response, that the GUI or
+/// background thread replaces while the audio thread plays it.
+///
+/// The audio thread reads the latest value without locking and keeps its own handle on it. The
+/// values that get replaced are kept in a retired list until every other handle is gone, and are
+/// freed by the next [`store()`][Self::store()] or [`collect()`][Self::collect()], so the audio
+/// thread never frees a buffer.
+///
+/// ```ignore
+/// // On the background thread
+/// bank.sample.store(Arc::new(decode(&path)?));
+///
+/// // At the start of each block on the audio thread
+/// bank.sample.sync(&mut self.sample);
+/// if let Some(sample) = &self.sample { ... }
+/// ```
+pub struct HotSwap<T> {
+    current: ArcSwapOption<T>,
+    retired: Mutex<Vec<Arc<T>>>,
+}
+
+impl<T> Default for HotSwap<T> {
+    fn default() -> Self {
+        Self {
+            current: ArcSwapOption::empty(),
+            retired: Mutex::new(Vec::new()),
+        }
+    }
+}
+
+impl<T> HotSwap<T> {
+    pub fn new(value: Arc<T>) -> Self {
+        let hot_swap = Self::default();
+        hot_swap.current.store(Some(value));
+        hot_swap
+    }
+
+    /// Publish a new value. Locks and frees memory, so never call this from the audio thread.
+    pub fn store(&self, value: Arc<T>) {
+        self.replace(Some(value));
+    }
+
+    /// Go back to having no value. Never call this from the audio thread either.
+    pub fn clear(&self) {
+        self.replace(None);
+    }
+
+    fn replace(&self, value: Option<Arc<T>>) {
+        let previous = self.current.swap(value);
+        let mut retired = self.retired.lock().unwrap();
+        retired.extend(previous);
+        Self::collect_retired(&mut retired);
+    }
+
+    /// Free the replaced values nothing else holds anymore. Storing a value already does this,
+    /// calling it from the editor as well frees the last value sooner.
+    pub fn collect(&self) {
+        Self::collect_retired(&mut self.retired.lock().unwrap());
+    }
+
+    fn collect_retired(retired: &mut Vec<Arc<T>>) {
+        // A retired value can't be loaded again, so once the list holds the only handle nothing
+        // else can pick it up
+        retired.retain(|value| Arc::strong_count(value) > 1);
+    }
+
+    /// A handle on the latest value, for the editor and background threads.
+    pub fn load(&self) -> Option<Arc<T>> {
+        self.current.load_full()
+    }
+
+    /// Point the audio thread's handle at the latest value. Doesn't lock or allocate, and the
+    /// handle it replaces is still retired, so dropping it doesn't free anything. Returns whether
+    /// the handle changed.
+    pub fn sync(&self, handle: &mut Option<Arc<T>>) -> bool {
+        let latest = self.current.load();
+        let changed = match (&*latest, &*handle) {
+            (Some(latest), Some(handle)) => !Arc::ptr_eq(latest, handle),
+            (latest, handle) => latest.is_some() != handle.is_some(),
+        };
+        if changed {
+            *handle = (*latest).clone();
+        }
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::assert_no_alloc;
+    use std::sync::Weak;
+
+    crate::install_alloc_disabler!();
+
+    /// A synced audio thread handle, and a weak handle to watch when the value is freed.
+    fn synced(hot_swap: &HotSwap<Vec<f32>>) -> (Option<Arc<Vec<f32>>>, Weak<Vec<f32>>) {
+        let mut handle = None;
+        hot_swap.sync(&mut handle);
+        let weak = Arc::downgrade(handle.as_ref().unwrap());
+        (handle, weak)
+    }
+
+    #[test]
+    fn syncing_only_moves_the_old_value_to_the_retired_list() {
+        let hot_swap = HotSwap::new(Arc::new(vec![1.0; 512]));
+        // Also sets up arc-swap's per-thread state, which allocates once
+        let (mut handle, first) = synced(&hot_swap);
+
+        hot_swap.store(Arc::new(vec![2.0; 512]));
+        assert_eq!(first.strong_count(), 2);
+
+        let changed = assert_no_alloc(|| hot_swap.sync(&mut handle));
+        assert!(changed);
+        assert_eq!(handle.as_ref().unwrap()[0], 2.0);
+        // The retired list holds the last handle
+        assert_eq!(first.strong_count(), 1);
+
+        assert!(!assert_no_alloc(|| hot_swap.sync(&mut handle)));
+    }
+
+    #[test]
+    fn storing_or_collecting_frees_the_retired_values() {
+        let hot_swap = HotSwap::new(Arc::new(vec![1.0; 512]));
+        let (mut handle, first) = synced(&hot_swap);
+
+        hot_swap.store(Arc::new(vec![2.0; 512]));
+        // Still playing on the audio thread
+        hot_swap.collect();
+        assert_eq!(first.strong_count(), 2);
+
+        assert_no_alloc(|| hot_swap.sync(&mut handle));
+        hot_swap.collect();
+        assert_eq!(first.strong_count(), 0);
+
+        let second = Arc::downgrade(handle.as_ref().unwrap());
+        hot_swap.store(Arc::new(vec![3.0; 512]));
+        assert_no_alloc(|| hot_swap.sync(&mut handle));
+        assert_eq!(second.strong_count(), 1);
+        hot_swap.store(Arc::new(vec![4.0; 512]));
+        assert_eq!(second.strong_count(), 0);
+    }
+
+    #[test]
+    fn clearing_is_also_freed_off_the_audio_thread() {
+        let hot_swap = HotSwap::new(Arc::new(vec![1.0; 512]));
+        let (mut handle, first) = synced(&hot_swap);
+
+        hot_swap.clear();
+        assert!(assert_no_alloc(|| hot_swap.sync(&mut handle)));
+        assert!(handle.is_none());
+        assert_eq!(first.strong_count(), 1);
+
+        hot_swap.collect();
+        assert_eq!(first.strong_count(), 0);
+    }
+}
@@ -0,0 +1,61 @@
+use nih_plug::prelude::*;
+
+/// Port names for layouts with a sidechain input.
+const SIDECHAIN_PORT_NAMES: PortNames = PortNames {
+    aux_inputs: &["Sidechain"],
+    ..PortNames::const_default()
+};
+
+pub const MONO: AudioIOLayout = AudioIOLayout {
+    main_input_channels: NonZeroU32::new(1),
+    main_output_channels: NonZeroU32::new(1),
+    ..AudioIOLayout::const_default()
+};
+
+pub const STEREO: AudioIOLayout = AudioIOLayout {
+    main_input_channels: NonZeroU32::new(2),
+    main_output_channels: NonZeroU32::new(2),
+    ..AudioIOLayout::const_default()
+};
+
+/// A mono input widened to a stereo output, for effects like stereo delays and wideners.
+pub const MONO_TO_STEREO: AudioIOLayout = AudioIOLayout {
+    main_input_channels: NonZeroU32::new(1),
+    main_output_channels: NonZeroU32::new(2),
+    ..AudioIOLayout::const_default()
+};
+
+/// Stereo with a stereo sidechain input, for compressors, gates, and duckers.
+pub const STEREO_SIDECHAIN: AudioIOLayout = AudioIOLayout {
+    main_input_channels: NonZeroU32::new(2),
+    main_output_channels: NonZeroU32::new(2),
+    aux_input_ports: &[new_nonzero_u32(2)],
+    names: SIDECHAIN_PORT_NAMES,
+    ..AudioIOLayout::const_default()
+};
+
+/// Mono with a mono sidechain input.
+pub const MONO_SIDECHAIN: AudioIOLayout = AudioIOLayout {
+    main_input_channels: NonZeroU32::new(1),
+    main_output_channels: NonZeroU32::new(1),
+    aux_input_ports: &[new_nonzero_u32(1)],
+    names: SIDECHAIN_PORT_NAMES,
+    ..AudioIOLayout::const_default()
+};
+
+/// A stereo instrument without audio inputs.
+pub const INSTRUMENT_STEREO: AudioIOLayout = AudioIOLayout {
+    main_input_channels: None,
+    main_output_channels: NonZeroU32::new(2),
+    ..AudioIOLayout::const_default()
+};
+
+/// Layouts for a typical effect, the first one is the host's default.
+pub const EFFECT_LAYOUTS: &[AudioIOLayout] = &[STEREO, MONO, MONO_TO_STEREO];
+
+/// Layouts for an effect with a sidechain input. Hosts that don't support sidechains pick the
+/// layouts without one, so the plugin must work when the sidechain is missing.
+pub const SIDECHAIN_EFFECT_LAYOUTS: &[AudioIOLayout] =
+    &[STEREO_SIDECHAIN, MONO_SIDECHAIN, STEREO, MONO];
+
+pub const INSTRUMENT_LAYOUTS: &[AudioIOLayout] = &[INSTRUMENT_STEREO];
@@ -0,0 +1,28 @@
+//! Boilerplate shared by the workspace's plugins, so new plugins don't copy-paste audio layouts,
+//! sidechain handling, and factory preset loading from each other.
+
+/// Time and note division formatters for parameters
+pub mod formatters;
+
+/// Lock-free swapping of samples, wavetables, and other large state with the audio thread
+pub mod hot_swap;
+
+/// Canonical `AUDIO_IO_LAYOUTS` building blocks
+pub mod layouts;
+
+/// Note event handling shared by the instruments
+pub mod notes;
+
+/// Factory presets embedded into the plugin binaries
+pub mod presets;
+
+/// Reading sidechain inputs from the auxiliary buffers
+pub mod sidechain;
+
+// Lets `embed_presets!()`, which expands to `::plugin_scaffold` paths, be tested in this crate
+#[cfg(test)]
+extern crate self as plugin_scaffold;
+
+/// Allocation checks for the plugins' tests
+#[cfg(any(test, feature = "test-util"))]
+pub mod test_util;
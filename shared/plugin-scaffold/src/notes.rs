@@ -0,0 +1,305 @@
+use nih_plug::prelude::{NoteEvent, Plugin, ProcessContext, SysExMessage};
+use std::ops::Deref;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// An instrument that plays note events. Implementing [`handle_event()`][Self::handle_event]
+/// gives it the sample accurate event loop the workspace's instruments share.
+pub trait NoteHandler<S: SysExMessage> {
+    fn handle_event(&mut self, event: NoteEvent<S>);
+
+    /// Handle every pending event that is due at or before `sample_id`. Events that arrive with a
+    /// timing earlier than the current sample are handled immediately instead of blocking the
+    /// queue.
+    fn handle_due_events(
+        &mut self,
+        sample_id: u32,
+        next_event: &mut Option<NoteEvent<S>>,
+        mut pull_event: impl FnMut() -> Option<NoteEvent<S>>,
+    ) {
+        while let Some(event) = next_event.take() {
+            if event.timing() > sample_id {
+                *next_event = Some(event);
+                break;
+            }
+
+            self.handle_event(event);
+            *next_event = pull_event();
+        }
+    }
+
+    /// Handle all of `events` right away, e.g. the notes played on the editor's keyboard since
+    /// the last block.
+    fn handle_events(&mut self, events: impl IntoIterator<Item = NoteEvent<S>>) {
+        for event in events {
+            self.handle_event(event);
+        }
+    }
+}
+
+/// Events waiting to be sent to the host at the end of the block, mostly `VoiceTerminated`. The
+/// capacity is reserved up front and never exceeded so queuing doesn't allocate, events queued
+/// while it's full are dropped.
+#[derive(Debug)]
+pub struct PendingEvents<S: SysExMessage> {
+    events: Vec<NoteEvent<S>>,
+}
+
+impl<S: SysExMessage> PendingEvents<S> {
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            events: Vec::with_capacity(capacity),
+        }
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.events.len() >= self.events.capacity()
+    }
+
+    pub fn push(&mut self, event: NoteEvent<S>) {
+        if !self.is_full() {
+            self.events.push(event);
+        }
+    }
+
+    /// Queue a `VoiceTerminated` event for a voice if the host still thinks it's playing. Pass
+    /// the voice's ID with `Option::take()`, the host must only be told once.
+    pub fn voice_terminated(
+        &mut self,
+        timing: u32,
+        voice_id: Option<i32>,
+        channel: u8,
+        note: Option<u8>,
+    ) {
+        let (Some(voice_id), Some(note)) = (voice_id, note) else {
+            return;
+        };
+
+        self.push(NoteEvent::VoiceTerminated {
+            timing,
+            voice_id: Some(voice_id),
+            channel,
+            note,
+        });
+    }
+
+    /// Send the queued events to the host, at the end of `process()`.
+    pub fn send<P: Plugin<SysExMessage = S>>(&mut self, context: &mut impl ProcessContext<P>) {
+        for event in self.events.drain(..) {
+            context.send_event(event);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.events.clear();
+    }
+}
+
+impl<S: SysExMessage> Deref for PendingEvents<S> {
+    type Target = [NoteEvent<S>];
+
+    fn deref(&self) -> &Self::Target {
+        &self.events
+    }
+}
+
+/// A set of held MIDI notes that the audio thread and an editor can both update without locking,
+/// one bit per note. Notes above 127 wrap around.
+#[derive(Debug, Default)]
+pub struct NoteMask {
+    words: [AtomicU64; 2],
+}
+
+impl NoteMask {
+    pub fn set(&self, note: u8, held: bool) {
+        let note = note & 127;
+        let word = &self.words[note as usize / 64];
+        let bit = 1 << (note % 64);
+        if held {
+            word.fetch_or(bit, Ordering::Relaxed);
+        } else {
+            word.fetch_and(!bit, Ordering::Relaxed);
+        }
+    }
+
+    pub fn contains(&self, note: u8) -> bool {
+        self.bits() & (1 << (note & 127)) != 0
+    }
+
+    /// The whole set, with note `n` in bit `n`.
+    pub fn bits(&self) -> u128 {
+        self.words[0].load(Ordering::Relaxed) as u128
+            | (self.words[1].load(Ordering::Relaxed) as u128) << 64
+    }
+
+    /// The held notes, lowest first.
+    pub fn notes(&self) -> impl Iterator<Item = u8> {
+        let mut bits = self.bits();
+        std::iter::from_fn(move || {
+            if bits == 0 {
+                return None;
+            }
+            let note = bits.trailing_zeros() as u8;
+            bits &= bits - 1;
+            Some(note)
+        })
+    }
+
+    /// Keep track of the notes held by note events, passing the events through unchanged. Wrap
+    /// the process loop's calls to `next_event()` in this to show the host's notes in the editor.
+    pub fn echo<S: SysExMessage>(&self, event: Option<NoteEvent<S>>) -> Option<NoteEvent<S>> {
+        match &event {
+            Some(NoteEvent::NoteOn { note, velocity, .. }) => self.set(*note, *velocity > 0.0),
+            Some(NoteEvent::NoteOff { note, .. } | NoteEvent::Choke { note, .. }) => {
+                self.set(*note, false)
+            }
+            _ => {}
+        }
+        event
+    }
+
+    pub fn clear(&self) {
+        for word in &self.words {
+            word.store(0, Ordering::Relaxed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_no_alloc::assert_no_alloc;
+
+    /// Records the timings of the events it handled.
+    #[derive(Default)]
+    struct Recorder {
+        handled: Vec<u32>,
+    }
+
+    impl NoteHandler<()> for Recorder {
+        fn handle_event(&mut self, event: NoteEvent<()>) {
+            self.handled.push(event.timing());
+        }
+    }
+
+    fn note_on(timing: u32, note: u8, velocity: f32) -> NoteEvent<()> {
+        NoteEvent::NoteOn {
+            timing,
+            voice_id: None,
+            channel: 0,
+            note,
+            velocity,
+        }
+    }
+
+    fn note_off(timing: u32, note: u8) -> NoteEvent<()> {
+        NoteEvent::NoteOff {
+            timing,
+            voice_id: None,
+            channel: 0,
+            note,
+            velocity: 0.0,
+        }
+    }
+
+    #[test]
+    fn events_are_handled_once_they_are_due() {
+        let mut events = [0, 0, 5, 3, 10]
+            .map(|timing| note_on(timing, 60, 1.0))
+            .into_iter();
+        let mut next_event = events.next();
+        let mut recorder = Recorder::default();
+
+        recorder.handle_due_events(0, &mut next_event, || events.next());
+        assert_eq!(recorder.handled, [0, 0]);
+
+        recorder.handle_due_events(4, &mut next_event, || events.next());
+        assert_eq!(recorder.handled, [0, 0]);
+        assert_eq!(next_event.as_ref().map(NoteEvent::timing), Some(5));
+
+        // The late event comes right after the one at sample 5 and doesn't hold up the queue
+        recorder.handle_due_events(5, &mut next_event, || events.next());
+        assert_eq!(recorder.handled, [0, 0, 5, 3]);
+        assert_eq!(next_event.as_ref().map(NoteEvent::timing), Some(10));
+
+        recorder.handle_due_events(10, &mut next_event, || events.next());
+        assert_eq!(recorder.handled, [0, 0, 5, 3, 10]);
+        assert!(next_event.is_none());
+    }
+
+    #[test]
+    fn batches_of_events_are_handled_in_order() {
+        let mut recorder = Recorder::default();
+        recorder.handle_events([note_off(2, 60), note_on(1, 64, 1.0)]);
+        assert_eq!(recorder.handled, [2, 1]);
+    }
+
+    #[test]
+    fn voices_are_only_terminated_with_an_id_and_a_note() {
+        let mut pending = PendingEvents::<()>::with_capacity(4);
+        pending.voice_terminated(0, None, 0, Some(60));
+        pending.voice_terminated(0, Some(1), 0, None);
+        assert!(pending.is_empty());
+
+        pending.voice_terminated(7, Some(1), 2, Some(60));
+        assert_eq!(
+            pending[..],
+            [NoteEvent::VoiceTerminated {
+                timing: 7,
+                voice_id: Some(1),
+                channel: 2,
+                note: 60,
+            }]
+        );
+    }
+
+    #[test]
+    fn pending_events_never_grow_past_their_capacity() {
+        let mut pending = PendingEvents::<()>::with_capacity(2);
+        let capacity = pending.events.capacity();
+        assert_no_alloc(|| {
+            for voice_id in 0..capacity as i32 + 3 {
+                pending.voice_terminated(0, Some(voice_id), 0, Some(60));
+            }
+        });
+
+        assert!(pending.is_full());
+        assert_eq!(pending.len(), capacity);
+        assert_eq!(pending.events.capacity(), capacity);
+
+        pending.clear();
+        assert!(!pending.is_full() && pending.is_empty());
+    }
+
+    #[test]
+    fn note_masks_hold_notes_in_both_words() {
+        let mask = NoteMask::default();
+        for note in [0, 63, 64, 127] {
+            mask.set(note, true);
+        }
+        mask.set(60, true);
+        mask.set(60, false);
+
+        assert!(mask.contains(63) && mask.contains(64));
+        assert!(!mask.contains(60));
+        assert_eq!(mask.notes().collect::<Vec<_>>(), [0, 63, 64, 127]);
+        assert_eq!(mask.bits(), 1 | 1 << 63 | 1 << 64 | 1 << 127);
+
+        mask.clear();
+        assert_eq!(mask.notes().next(), None);
+    }
+
+    #[test]
+    fn echoing_tracks_held_notes() {
+        let mask = NoteMask::default();
+        let event = mask.echo(Some(note_on(3, 60, 1.0)));
+        assert_eq!(event, Some(note_on(3, 60, 1.0)));
+        mask.echo(Some(note_on(0, 64, 1.0)));
+        assert_eq!(mask.notes().collect::<Vec<_>>(), [60, 64]);
+
+        // Zero velocity note ons are note offs
+        mask.echo(Some(note_on(0, 64, 0.0)));
+        mask.echo(Some(note_off(0, 60)));
+        assert_eq!(mask.bits(), 0);
+        assert_eq!(mask.echo::<()>(None), None);
+    }
+}
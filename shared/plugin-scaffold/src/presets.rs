@@ -0,0 +1,231 @@
+//! Factory presets, embedded into the plugin binary with [`embed_presets!`] so every plugin ships
+//! and loads its factory content the same way.
+//!
+//! A preset file is JSON with the values of the parameters it sets, by parameter ID:
+//!
+//! ```json
+//! {
+//!     "params": {
+//!         "level_1": 8,
+//!         "percussion": true,
+//!         "scanner": "C3"
+//!     }
+//! }
+//! ```
+//!
+//! Numbers are plain values in the parameter's own range, booleans set bool parameters, and
+//! strings are parsed like text typed into the host, e.g. an enum variant's name or `"-6 dB"`.
+//! Parameters a preset leaves out keep their current values.
+
+use nih_plug::prelude::{ParamPtr, Params};
+use serde_json::Value;
+use std::fmt;
+
+pub use plugin_scaffold_macros::embed_presets;
+
+/// A preset file embedded with [`embed_presets!`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FactoryPreset {
+    /// The file name without its extension.
+    pub name: &'static str,
+    pub json: &'static str,
+}
+
+/// Implemented by plugins that ship factory presets, so editors and tests find them the same way
+/// in every plugin.
+///
+/// ```ignore
+/// impl FactoryPresets for MyPlugin {
+///     const FACTORY_PRESETS: &'static [FactoryPreset] = embed_presets!("presets/*.json");
+/// }
+/// ```
+pub trait FactoryPresets {
+    const FACTORY_PRESETS: &'static [FactoryPreset];
+}
+
+#[derive(Debug)]
+pub enum Error {
+    /// The file isn't valid JSON, or has no `params` object.
+    Parse(String),
+    /// The preset sets a parameter the plugin doesn't have.
+    UnknownParam(String),
+    /// The value can't be set on the parameter.
+    InvalidValue { id: String, value: Value },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Parse(err) => write!(f, "could not read the preset: {err}"),
+            Error::UnknownParam(id) => write!(f, "the preset sets an unknown parameter '{id}'"),
+            Error::InvalidValue { id, value } => {
+                write!(
+                    f,
+                    "the preset sets '{id}' to {value}, which it can't be set to"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl FactoryPreset {
+    /// The parameters this preset sets, with their new normalized values, ready to be set
+    /// through a `ParamSetter`'s context.
+    pub fn resolve(&self, params: &dyn Params) -> Result<Vec<(ParamPtr, f32)>, Error> {
+        let file: Value =
+            serde_json::from_str(self.json).map_err(|err| Error::Parse(err.to_string()))?;
+        let Some(values) = file.get("params").and_then(Value::as_object) else {
+            return Err(Error::Parse("there is no `params` object".to_owned()));
+        };
+
+        let param_map = params.param_map();
+        values
+            .iter()
+            .map(|(id, value)| {
+                let param = param_map
+                    .iter()
+                    .find(|(param_id, _, _)| param_id == id)
+                    .map(|(_, param, _)| *param)
+                    .ok_or_else(|| Error::UnknownParam(id.clone()))?;
+
+                // SAFETY: The pointers come from `params`, which outlives this call
+                let normalized = unsafe {
+                    match value {
+                        Value::Number(plain) => plain
+                            .as_f64()
+                            .map(|plain| param.preview_normalized(plain as f32)),
+                        Value::Bool(on) => Some(if *on { 1.0 } else { 0.0 }),
+                        Value::String(text) => param.string_to_normalized_value(text),
+                        _ => None,
+                    }
+                };
+                normalized
+                    .filter(|normalized| normalized.is_finite())
+                    .map(|normalized| (param, normalized))
+                    .ok_or_else(|| Error::InvalidValue {
+                        id: id.clone(),
+                        value: value.clone(),
+                    })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nih_plug::prelude::*;
+
+    #[derive(Enum)]
+    enum Mode {
+        Soft,
+        Hard,
+    }
+
+    #[derive(Params)]
+    struct TestParams {
+        #[id = "gain"]
+        gain: FloatParam,
+        #[id = "on"]
+        on: BoolParam,
+        #[id = "mode"]
+        mode: EnumParam<Mode>,
+    }
+
+    impl Default for TestParams {
+        fn default() -> Self {
+            Self {
+                gain: FloatParam::new("Gain", 0.5, FloatRange::Linear { min: 0.0, max: 1.0 }),
+                on: BoolParam::new("On", false),
+                mode: EnumParam::new("Mode", Mode::Soft),
+            }
+        }
+    }
+
+    const FIXTURES: &[FactoryPreset] = embed_presets!("fixtures/presets/*.json");
+
+    fn preset(json: &'static str) -> FactoryPreset {
+        FactoryPreset { name: "Test", json }
+    }
+
+    /// The resolved value for `param`, if the preset sets it.
+    fn value_of(values: &[(ParamPtr, f32)], param: &impl Param) -> Option<f32> {
+        values
+            .iter()
+            .find(|(ptr, _)| *ptr == param.as_ptr())
+            .map(|(_, value)| *value)
+    }
+
+    #[test]
+    fn matching_files_are_embedded_in_name_order() {
+        let names: Vec<_> = FIXTURES.iter().map(|preset| preset.name).collect();
+        assert_eq!(names, ["Bright", "Dark"]);
+        assert_eq!(
+            FIXTURES[0].json,
+            include_str!("../fixtures/presets/Bright.json")
+        );
+    }
+
+    #[test]
+    fn presets_resolve_to_normalized_values() {
+        let params = TestParams::default();
+        let values = FIXTURES[0].resolve(&params).unwrap();
+        assert_eq!(values.len(), 3);
+        assert_eq!(value_of(&values, &params.gain), Some(0.75));
+        assert_eq!(value_of(&values, &params.on), Some(1.0));
+        assert_eq!(value_of(&values, &params.mode), Some(1.0));
+
+        // Parameters the preset leaves out aren't touched
+        let values = FIXTURES[1].resolve(&params).unwrap();
+        assert_eq!(value_of(&values, &params.mode), None);
+    }
+
+    #[test]
+    fn malformed_files_are_parse_errors() {
+        let params = TestParams::default();
+        for json in [
+            "",
+            "{ \"params\": { \"gain\": 0.5 }",
+            "{ \"values\": { \"gain\": 0.5 } }",
+            "{ \"params\": [0.5] }",
+        ] {
+            assert!(
+                matches!(preset(json).resolve(&params), Err(Error::Parse(_))),
+                "{json}"
+            );
+        }
+    }
+
+    #[test]
+    fn unknown_parameters_are_named() {
+        let params = TestParams::default();
+        let result = preset(r#"{ "params": { "gain": 0.5, "volume": 1.0 } }"#).resolve(&params);
+        let Err(err @ Error::UnknownParam(_)) = &result else {
+            panic!("{result:?}");
+        };
+        assert_eq!(
+            err.to_string(),
+            "the preset sets an unknown parameter 'volume'"
+        );
+    }
+
+    #[test]
+    fn values_the_parameter_cant_take_are_rejected() {
+        let params = TestParams::default();
+        for (id, value) in [
+            ("gain", "null"),
+            ("gain", "[0.5]"),
+            ("gain", r#""loud""#),
+            ("mode", r#""Medium""#),
+        ] {
+            let json = format!(r#"{{ "params": {{ "{id}": {value} }} }}"#);
+            let result = preset(json.leak()).resolve(&params);
+            assert!(
+                matches!(&result, Err(Error::InvalidValue { id: invalid, .. }) if invalid == id),
+                "{id}: {value} gave {result:?}"
+            );
+        }
+    }
+}
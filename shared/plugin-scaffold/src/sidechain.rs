@@ -0,0 +1,37 @@
+use nih_plug::prelude::*;
+
+/// How a multichannel signal is reduced to the single key signal dynamics processors detect on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum KeyMix {
+    /// The loudest channel's absolute value, so a hard panned signal still triggers the
+    /// detector.
+    #[default]
+    Max,
+    /// The average of the channels, keeping the sign.
+    Average,
+}
+
+/// The sidechain input, if the current layout has one. Hosts may still pass silence when nothing
+/// is routed to it.
+pub fn sidechain_input<'a, 'b>(aux: &'b mut AuxiliaryBuffers<'a>) -> Option<&'b mut Buffer<'a>> {
+    aux.inputs.first_mut()
+}
+
+/// Fill `key` with one detector sample per frame, taken from the sidechain when there is one and
+/// from the main input otherwise. Call this before processing the main buffer in place. `key`
+/// should be at least as long as the block, any extra samples are left untouched.
+pub fn fill_key(key: &mut [f32], main: &Buffer, sidechain: Option<&Buffer>, mix: KeyMix) {
+    let source = sidechain
+        .filter(|sidechain| sidechain.channels() > 0 && sidechain.samples() == main.samples())
+        .unwrap_or(main);
+    let channels = source.as_slice_immutable();
+    let num_samples = source.samples().min(key.len());
+
+    for (sample_idx, key) in key[..num_samples].iter_mut().enumerate() {
+        let samples = channels.iter().map(|channel| channel[sample_idx]);
+        *key = match mix {
+            KeyMix::Max => samples.fold(0.0, |max, sample| sample.abs().max(max)),
+            KeyMix::Average => samples.sum::<f32>() / channels.len().max(1) as f32,
+        };
+    }
+}
@@ -0,0 +1,16 @@
+//! Checks that the plugins' process paths don't allocate. A test module installs the checking
+//! allocator with [`install_alloc_disabler!()`][crate::install_alloc_disabler], then runs the
+//! process path inside [`assert_no_alloc()`].
+
+pub use assert_no_alloc::{assert_no_alloc, AllocDisabler};
+
+/// Make [`AllocDisabler`] the global allocator, so allocating or freeing memory inside
+/// [`assert_no_alloc()`] aborts the test run. Use it once per test binary, in the plugin's test
+/// module.
+#[macro_export]
+macro_rules! install_alloc_disabler {
+    () => {
+        #[global_allocator]
+        static ALLOCATOR: $crate::test_util::AllocDisabler = $crate::test_util::AllocDisabler;
+    };
+}
@@ -0,0 +1,159 @@
+use crate::undo;
+use nih_plug::prelude::{FloatParam, Param, ParamSetter};
+use nih_plug_egui::egui::{
+    self, Color32, Pos2, Rect, Response, Sense, Shape, Stroke, Ui, Vec2, Widget,
+};
+
+const HANDLE_RADIUS: f32 = 4.0;
+
+/// An ADSR curve drawn from four envelope parameters, with draggable handles for the attack
+/// peak, the decay/sustain corner, and the release end point. Each stage gets a quarter of the
+/// width, scaled by its normalized parameter value.
+pub struct AdsrEditor<'a> {
+    attack: &'a FloatParam,
+    decay: &'a FloatParam,
+    sustain: &'a FloatParam,
+    release: &'a FloatParam,
+    setter: &'a ParamSetter<'a>,
+    size: Vec2,
+}
+
+impl<'a> AdsrEditor<'a> {
+    pub fn new(
+        attack: &'a FloatParam,
+        decay: &'a FloatParam,
+        sustain: &'a FloatParam,
+        release: &'a FloatParam,
+        setter: &'a ParamSetter<'a>,
+    ) -> Self {
+        Self {
+            attack,
+            decay,
+            sustain,
+            release,
+            setter,
+            size: Vec2::new(240.0, 100.0),
+        }
+    }
+
+    pub fn with_size(mut self, size: Vec2) -> Self {
+        self.size = size;
+        self
+    }
+
+    /// Wrap a handle drag in a parameter gesture, nudging the normalized value by `delta`.
+    fn drag_param(&self, response: &Response, param: &FloatParam, delta: f32) {
+        if response.drag_started() {
+            self.setter.begin_set_parameter(param);
+            undo::begin_gesture(
+                &response.ctx,
+                param.as_ptr(),
+                param.unmodulated_normalized_value(),
+            );
+        }
+        if response.dragged() && delta != 0.0 {
+            let value = (param.unmodulated_normalized_value() + delta).clamp(0.0, 1.0);
+            self.setter.set_parameter_normalized(param, value);
+        }
+        if response.drag_stopped() {
+            self.setter.end_set_parameter(param);
+            undo::end_gesture(
+                &response.ctx,
+                param.as_ptr(),
+                param.unmodulated_normalized_value(),
+            );
+        }
+    }
+}
+
+impl Widget for AdsrEditor<'_> {
+    fn ui(self, ui: &mut Ui) -> Response {
+        let (rect, response) = ui.allocate_exact_size(self.size, Sense::hover());
+        let rect = rect.shrink(HANDLE_RADIUS);
+        let stage_width = rect.width() / 4.0;
+
+        let points = envelope_points(
+            rect,
+            self.attack.unmodulated_normalized_value(),
+            self.decay.unmodulated_normalized_value(),
+            self.sustain.unmodulated_normalized_value(),
+            self.release.unmodulated_normalized_value(),
+        );
+        let [_, peak, decay_end, sustain_end, release_end] = points;
+
+        // Handles are interacted with before drawing so the curve reflects this frame's drags
+        let handle = |index: usize, center: Pos2| {
+            ui.interact(
+                Rect::from_center_size(center, Vec2::splat(HANDLE_RADIUS * 4.0)),
+                response.id.with(index),
+                Sense::drag(),
+            )
+        };
+        let peak_handle = handle(0, peak);
+        let decay_handle = handle(1, decay_end);
+        let release_handle = handle(2, release_end);
+
+        self.drag_param(
+            &peak_handle,
+            self.attack,
+            peak_handle.drag_delta().x / stage_width,
+        );
+        self.drag_param(
+            &decay_handle,
+            self.decay,
+            decay_handle.drag_delta().x / stage_width,
+        );
+        self.drag_param(
+            &decay_handle,
+            self.sustain,
+            -decay_handle.drag_delta().y / rect.height(),
+        );
+        self.drag_param(
+            &release_handle,
+            self.release,
+            release_handle.drag_delta().x / stage_width,
+        );
+
+        if ui.is_rect_visible(rect) {
+            let visuals = ui.visuals();
+            let painter = ui.painter();
+            let line_color = visuals.selection.bg_fill;
+
+            painter.rect_filled(rect.expand(HANDLE_RADIUS), 0.0, visuals.extreme_bg_color);
+            painter.add(Shape::line(points.to_vec(), Stroke::new(2.0, line_color)));
+            painter.line_segment(
+                [sustain_end, egui::pos2(sustain_end.x, rect.bottom())],
+                Stroke::new(1.0, visuals.weak_text_color()),
+            );
+
+            for (handle, center) in [
+                (&peak_handle, peak),
+                (&decay_handle, decay_end),
+                (&release_handle, release_end),
+            ] {
+                let fill = if handle.hovered() || handle.dragged() {
+                    Color32::WHITE
+                } else {
+                    line_color
+                };
+                painter.circle_filled(center, HANDLE_RADIUS, fill);
+            }
+        }
+
+        response | peak_handle | decay_handle | release_handle
+    }
+}
+
+/// The start, attack peak, decay end, sustain end, and release end points of the curve.
+fn envelope_points(rect: Rect, attack: f32, decay: f32, sustain: f32, release: f32) -> [Pos2; 5] {
+    let stage_width = rect.width() / 4.0;
+    let sustain_y = rect.bottom() - sustain * rect.height();
+
+    let start = rect.left_bottom();
+    let peak = egui::pos2(start.x + attack * stage_width, rect.top());
+    let decay_end = egui::pos2(peak.x + decay * stage_width, sustain_y);
+    let sustain_end = egui::pos2(decay_end.x + stage_width, sustain_y);
+    let release_end = egui::pos2(sustain_end.x + release * stage_width, rect.bottom());
+
+    [start, peak, decay_end, sustain_end, release_end]
+}
@@ -0,0 +1,36 @@
+use crate::undo;
+use nih_plug::prelude::{Enum, EnumParam, Param, ParamSetter};
+use nih_plug_egui::egui::{self, Response, Ui};
+
+/// A drop-down for an enum parameter, labelled with the parameter's name. Changes are recorded
+/// in the undo history.
+///
+/// The combo box's ID comes from the label, so wrap it in `ui.push_id()` when several parameters
+/// share a name, e.g. in nested parameter arrays.
+pub fn param_combo<T: Enum + PartialEq + 'static>(
+    ui: &mut Ui,
+    param: &EnumParam<T>,
+    setter: &ParamSetter,
+) -> Response {
+    let current = param.value().to_index();
+    let variants = T::variants();
+    egui::ComboBox::from_label(param.name())
+        .selected_text(variants[current])
+        .show_ui(ui, |ui| {
+            for (idx, name) in variants.iter().enumerate() {
+                if ui.selectable_label(idx == current, *name).clicked() {
+                    let before = param.unmodulated_normalized_value();
+                    setter.begin_set_parameter(param);
+                    setter.set_parameter(param, T::from_index(idx));
+                    setter.end_set_parameter(param);
+                    undo::record_change(
+                        ui.ctx(),
+                        param.as_ptr(),
+                        before,
+                        param.preview_normalized(T::from_index(idx)),
+                    );
+                }
+            }
+        })
+        .response
+}
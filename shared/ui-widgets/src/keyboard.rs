@@ -0,0 +1,279 @@
+use nih_plug::prelude::{NoteEvent, SysExMessage};
+use nih_plug_egui::egui::{self, Color32, Pos2, Rect, Response, Sense, Stroke, Ui, Vec2};
+use plugin_scaffold::notes::NoteMask;
+use std::marker::PhantomData;
+
+const BLACK_KEY_WIDTH: f32 = 0.6;
+const BLACK_KEY_HEIGHT: f32 = 0.6;
+
+/// The velocity of notes played on the on-screen keyboard.
+const VELOCITY: f32 = 0.8;
+
+/// An on-screen piano keyboard. Notes in `highlighted` are drawn as held, and clicking or
+/// dragging across the keys produces note on/off pairs in the [`KeyboardResponse`]. With a
+/// [`KeyboardState`] the keys also play the plugin, and the notes it receives light up.
+pub struct Keyboard<'a> {
+    low: u8,
+    high: u8,
+    highlighted: Option<&'a [bool; 128]>,
+    state: Option<&'a KeyboardState>,
+    size: Vec2,
+}
+
+pub struct KeyboardResponse {
+    pub response: Response,
+    /// A key was pressed, or the pointer slid onto a new key.
+    pub note_on: Option<u8>,
+    /// The previously pressed key was released.
+    pub note_off: Option<u8>,
+}
+
+impl<'a> Keyboard<'a> {
+    /// A keyboard covering the notes from `low` through `high`.
+    pub fn new(low: u8, high: u8) -> Self {
+        Self {
+            low: low.min(high),
+            high: high.max(low).min(127),
+            highlighted: None,
+            state: None,
+            size: Vec2::new(420.0, 60.0),
+        }
+    }
+
+    pub fn with_highlighted(mut self, notes: &'a [bool; 128]) -> Self {
+        self.highlighted = Some(notes);
+        self
+    }
+
+    pub fn with_state(mut self, state: &'a KeyboardState) -> Self {
+        self.state = Some(state);
+        self
+    }
+
+    pub fn with_size(mut self, size: Vec2) -> Self {
+        self.size = size;
+        self
+    }
+
+    pub fn show(self, ui: &mut Ui) -> KeyboardResponse {
+        let (rect, response) = ui.allocate_exact_size(self.size, Sense::click_and_drag());
+        let (white_keys, black_keys) = self.key_rects(rect);
+
+        // Black keys sit on top of the white keys, so they get hit tested first
+        let key_at = |pos: Pos2| {
+            black_keys
+                .iter()
+                .chain(white_keys.iter())
+                .find(|(_, key_rect)| key_rect.contains(pos))
+                .map(|&(note, _)| note)
+        };
+        let pressed = if response.is_pointer_button_down_on() {
+            response.interact_pointer_pos().and_then(key_at)
+        } else {
+            None
+        };
+        let previous = ui
+            .data(|data| data.get_temp::<Option<u8>>(response.id))
+            .flatten();
+        ui.data_mut(|data| data.insert_temp(response.id, pressed));
+
+        if ui.is_rect_visible(rect) {
+            let painter = ui.painter_at(rect);
+            let highlight = ui.visuals().selection.bg_fill;
+            let is_lit = |note: u8| {
+                pressed == Some(note)
+                    || self.highlighted.is_some_and(|notes| notes[note as usize])
+                    || self.state.is_some_and(|state| state.is_received(note))
+            };
+
+            for &(note, key_rect) in &white_keys {
+                let fill = if is_lit(note) {
+                    highlight
+                } else {
+                    Color32::WHITE
+                };
+                painter.rect_filled(key_rect, 0.0, fill);
+                painter.line_segment(
+                    [key_rect.right_top(), key_rect.right_bottom()],
+                    Stroke::new(1.0, Color32::DARK_GRAY),
+                );
+            }
+            for &(note, key_rect) in &black_keys {
+                let fill = if is_lit(note) {
+                    highlight
+                } else {
+                    Color32::BLACK
+                };
+                painter.rect_filled(key_rect, 0.0, fill);
+            }
+        }
+
+        let (note_on, note_off) = if pressed != previous {
+            (pressed, previous)
+        } else {
+            (None, None)
+        };
+        if let Some(state) = self.state {
+            if let Some(note) = note_off {
+                state.press(note, false);
+            }
+            if let Some(note) = note_on {
+                state.press(note, true);
+            }
+        }
+
+        KeyboardResponse {
+            response,
+            note_on,
+            note_off,
+        }
+    }
+
+    fn key_rects(&self, rect: Rect) -> (Vec<(u8, Rect)>, Vec<(u8, Rect)>) {
+        let num_white = (self.low..=self.high)
+            .filter(|&n| !is_black(n))
+            .count()
+            .max(1);
+        let white_width = rect.width() / num_white as f32;
+
+        let mut white_keys = Vec::with_capacity(num_white);
+        let mut black_keys = Vec::new();
+        for note in self.low..=self.high {
+            // Black keys straddle the boundary after the last white key
+            let x = rect.left() + white_keys.len() as f32 * white_width;
+            if is_black(note) {
+                let width = white_width * BLACK_KEY_WIDTH;
+                black_keys.push((
+                    note,
+                    Rect::from_min_size(
+                        egui::pos2(x - width / 2.0, rect.top()),
+                        Vec2::new(width, rect.height() * BLACK_KEY_HEIGHT),
+                    ),
+                ));
+            } else {
+                white_keys.push((
+                    note,
+                    Rect::from_min_size(
+                        egui::pos2(x, rect.top()),
+                        Vec2::new(white_width, rect.height()),
+                    ),
+                ));
+            }
+        }
+
+        (white_keys, black_keys)
+    }
+}
+
+/// Whether a MIDI note is a black key.
+pub fn is_black(note: u8) -> bool {
+    matches!(note % 12, 1 | 3 | 6 | 8 | 10)
+}
+
+/// Notes shared between an editor's [`Keyboard`] and the audio thread. The keyboard presses keys
+/// into it, which the audio thread plays with a [`KeyboardInput`], and the audio thread echoes
+/// the notes it receives from the host back for the keyboard to light up. Both are bitmasks of
+/// atomics so neither side ever blocks.
+#[derive(Default)]
+pub struct KeyboardState {
+    /// The keys held down on the on-screen keyboard.
+    pressed: NoteMask,
+    /// The notes held by the host's note events.
+    received: NoteMask,
+}
+
+impl KeyboardState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Press or release a key on the on-screen keyboard.
+    pub fn press(&self, note: u8, held: bool) {
+        self.pressed.set(note, held);
+    }
+
+    pub fn is_pressed(&self, note: u8) -> bool {
+        self.pressed.contains(note)
+    }
+
+    pub fn is_received(&self, note: u8) -> bool {
+        self.received.contains(note)
+    }
+
+    /// Keep track of the notes in the host's note events, passing the events through unchanged.
+    /// Wrap the process loop's calls to `next_event()` in this.
+    pub fn echo<S: SysExMessage>(&self, event: Option<NoteEvent<S>>) -> Option<NoteEvent<S>> {
+        self.received.echo(event)
+    }
+
+    /// Call from `reset()`, the host won't send note offs for the notes it was holding.
+    pub fn clear_received(&self) {
+        self.received.clear();
+    }
+}
+
+/// The audio thread's side of a [`KeyboardState`], remembering which of the editor's keys it
+/// already played.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct KeyboardInput {
+    playing: u128,
+}
+
+impl KeyboardInput {
+    /// Note events for the keys pressed and released on the editor's keyboard since the last
+    /// call, all timed at the start of the block. Releases come first, so sliding across the keys
+    /// plays legato. A key tapped and released between two calls is missed, which would take a
+    /// tap shorter than a block.
+    pub fn poll<S: SysExMessage>(&mut self, state: &KeyboardState) -> KeyboardEvents<S> {
+        let pressed = state.pressed.bits();
+        let changed = pressed ^ self.playing;
+        self.playing = pressed;
+
+        KeyboardEvents {
+            note_offs: changed & !pressed,
+            note_ons: changed & pressed,
+            _sysex: PhantomData,
+        }
+    }
+}
+
+/// The note events from [`KeyboardInput::poll()`].
+pub struct KeyboardEvents<S> {
+    note_offs: u128,
+    note_ons: u128,
+    _sysex: PhantomData<S>,
+}
+
+impl<S: SysExMessage> Iterator for KeyboardEvents<S> {
+    type Item = NoteEvent<S>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(note) = pop_lowest(&mut self.note_offs) {
+            return Some(NoteEvent::NoteOff {
+                timing: 0,
+                voice_id: None,
+                channel: 0,
+                note,
+                velocity: 0.0,
+            });
+        }
+
+        pop_lowest(&mut self.note_ons).map(|note| NoteEvent::NoteOn {
+            timing: 0,
+            voice_id: None,
+            channel: 0,
+            note,
+            velocity: VELOCITY,
+        })
+    }
+}
+
+/// Remove and return the lowest note in a bitmask.
+fn pop_lowest(notes: &mut u128) -> Option<u8> {
+    if *notes == 0 {
+        return None;
+    }
+    let note = notes.trailing_zeros() as u8;
+    *notes &= *notes - 1;
+    Some(note)
+}
@@ -0,0 +1,177 @@
+use crate::arc_points;
+use crate::undo;
+use nih_plug::prelude::{Param, ParamSetter};
+use nih_plug_egui::egui::{
+    self, Align2, Color32, FontId, Response, Sense, Shape, Stroke, Ui, Vec2, Widget,
+};
+use std::f32::consts::PI;
+
+/// The knob sweeps 270 degrees, starting at the bottom left.
+const START_ANGLE: f32 = 0.75 * PI;
+const SWEEP: f32 = 1.5 * PI;
+
+/// Normalized change per pixel of vertical drag.
+const DRAG_SPEED: f32 = 0.005;
+const FINE_DRAG_SPEED: f32 = 0.0005;
+
+const MODULATION_COLOR: Color32 = Color32::from_rgb(255, 170, 0);
+
+/// A rotary knob for a nih_plug parameter. Drag vertically to change the value, hold Shift for
+/// fine adjustments, and double click to reset to the default. An outer ring shows how far the
+/// host's modulation, or the plugin's own modulation when given with
+/// [`with_modulation()`][Self::with_modulation()], moves the parameter away from its base value.
+pub struct ParamKnob<'a, P: Param> {
+    param: &'a P,
+    setter: &'a ParamSetter<'a>,
+    diameter: f32,
+    /// The normalized value after the plugin's own modulation, from a [`ModulationState`].
+    ///
+    /// [`ModulationState`]: crate::ModulationState
+    modulated: Option<f32>,
+}
+
+impl<'a, P: Param> ParamKnob<'a, P> {
+    pub fn for_param(param: &'a P, setter: &'a ParamSetter<'a>) -> Self {
+        Self {
+            param,
+            setter,
+            diameter: 48.0,
+            modulated: None,
+        }
+    }
+
+    pub fn with_diameter(mut self, diameter: f32) -> Self {
+        self.diameter = diameter;
+        self
+    }
+
+    /// Show the plugin's own modulation instead of the host's, usually
+    /// [`ModulationState::value()`][crate::ModulationState::value()] for the parameter's
+    /// destination. `None` falls back to the host's modulation.
+    pub fn with_modulation(mut self, modulated: Option<f32>) -> Self {
+        self.modulated = modulated;
+        self
+    }
+
+    fn handle_input(&self, ui: &Ui, response: &mut Response) {
+        // Stepped parameters would never move if every small drag delta was snapped, so the
+        // unsnapped value is kept in egui's memory for the duration of the drag
+        if response.drag_started() {
+            self.setter.begin_set_parameter(self.param);
+            let value = self.param.unmodulated_normalized_value();
+            ui.data_mut(|data| data.insert_temp(response.id, value));
+            undo::begin_gesture(ui.ctx(), self.param.as_ptr(), value);
+        }
+
+        if response.dragged() {
+            let speed = if ui.input(|input| input.modifiers.shift) {
+                FINE_DRAG_SPEED
+            } else {
+                DRAG_SPEED
+            };
+            let current = ui
+                .data(|data| data.get_temp::<f32>(response.id))
+                .unwrap_or_else(|| self.param.unmodulated_normalized_value());
+            let value = (current - response.drag_delta().y * speed).clamp(0.0, 1.0);
+            ui.data_mut(|data| data.insert_temp(response.id, value));
+
+            self.setter.set_parameter_normalized(self.param, value);
+            response.mark_changed();
+        }
+
+        if response.drag_stopped() {
+            self.setter.end_set_parameter(self.param);
+            // The drag value is what was last sent to the host, which may not have applied it yet
+            let value = ui
+                .data(|data| data.get_temp::<f32>(response.id))
+                .unwrap_or_else(|| self.param.unmodulated_normalized_value());
+            undo::end_gesture(ui.ctx(), self.param.as_ptr(), value);
+        }
+
+        if response.double_clicked() {
+            let before = self.param.unmodulated_normalized_value();
+            self.setter.begin_set_parameter(self.param);
+            self.setter
+                .set_parameter(self.param, self.param.default_plain_value());
+            self.setter.end_set_parameter(self.param);
+            undo::record_change(
+                ui.ctx(),
+                self.param.as_ptr(),
+                before,
+                self.param.default_normalized_value(),
+            );
+            response.mark_changed();
+        }
+    }
+}
+
+impl<P: Param> Widget for ParamKnob<'_, P> {
+    fn ui(self, ui: &mut Ui) -> Response {
+        let font = FontId::proportional(11.0);
+        let text_height = ui.fonts(|fonts| fonts.row_height(&font));
+        let size = Vec2::new(self.diameter, self.diameter + 2.0 * text_height);
+        let (rect, mut response) = ui.allocate_exact_size(size, Sense::click_and_drag());
+
+        self.handle_input(ui, &mut response);
+
+        if ui.is_rect_visible(rect) {
+            let visuals = ui.style().interact(&response);
+            let painter = ui.painter_at(rect);
+            let center = egui::pos2(
+                rect.center().x,
+                rect.top() + text_height + self.diameter / 2.0,
+            );
+            let radius = self.diameter / 2.0 - 4.0;
+
+            let base = self.param.unmodulated_normalized_value();
+            let modulated = self
+                .modulated
+                .unwrap_or_else(|| self.param.modulated_normalized_value());
+            let value_angle = START_ANGLE + base * SWEEP;
+
+            painter.add(Shape::line(
+                arc_points(center, radius, START_ANGLE, START_ANGLE + SWEEP),
+                Stroke::new(3.0, ui.visuals().widgets.inactive.bg_fill),
+            ));
+            painter.add(Shape::line(
+                arc_points(center, radius, START_ANGLE, value_angle),
+                Stroke::new(3.0, ui.visuals().selection.bg_fill),
+            ));
+            if (modulated - base).abs() > f32::EPSILON {
+                painter.add(Shape::line(
+                    arc_points(
+                        center,
+                        radius + 3.5,
+                        value_angle,
+                        START_ANGLE + modulated * SWEEP,
+                    ),
+                    Stroke::new(2.0, MODULATION_COLOR),
+                ));
+            }
+            painter.line_segment(
+                [
+                    center + (radius * 0.3) * Vec2::angled(value_angle),
+                    center + radius * Vec2::angled(value_angle),
+                ],
+                Stroke::new(2.0, visuals.fg_stroke.color),
+            );
+
+            painter.text(
+                egui::pos2(rect.center().x, rect.top()),
+                Align2::CENTER_TOP,
+                self.param.name(),
+                font.clone(),
+                visuals.text_color(),
+            );
+            painter.text(
+                egui::pos2(rect.center().x, rect.bottom()),
+                Align2::CENTER_BOTTOM,
+                self.param.normalized_value_to_string(modulated, true),
+                font,
+                visuals.text_color(),
+            );
+        }
+
+        response
+    }
+}
@@ -0,0 +1,72 @@
+//! Reusable egui widgets for the plugin editors, so every editor doesn't rebuild knobs, envelope
+//! editors, and displays from scratch.
+
+pub use nih_plug_egui::egui;
+
+/// Rotary parameter knob with a modulation ring
+pub mod knob;
+
+/// Draggable ADSR envelope curve
+pub mod adsr;
+
+/// Waveform/oscilloscope display
+pub mod scope;
+
+/// Log-frequency magnitude spectrum display
+pub mod spectrum;
+
+/// On-screen piano keyboard
+pub mod keyboard;
+
+/// Peak/RMS level metering shared between the audio thread and editors
+pub mod meter;
+
+/// Modulated parameter values shared between the audio thread and editors
+pub mod modulation;
+
+/// Stereo correlation meter and goniometer shared between the audio thread and editors
+pub mod phase;
+
+/// Undo/redo history for editor-driven parameter changes
+pub mod undo;
+
+/// Drop-down for enum parameters
+pub mod combo;
+
+/// Checkbox for boolean parameters
+pub mod toggle;
+
+/// Drop-down for loading factory presets
+pub mod presets;
+
+/// Resizable, zoomable editor frame with a shared header bar
+pub mod shell;
+
+pub use adsr::AdsrEditor;
+pub use combo::param_combo;
+pub use keyboard::{Keyboard, KeyboardInput, KeyboardResponse, KeyboardState};
+pub use knob::ParamKnob;
+pub use meter::{LevelMeter, MeterState};
+pub use modulation::ModulationState;
+pub use phase::{CorrelationMeter, Goniometer, PhaseState};
+pub use presets::preset_combo;
+pub use scope::Scope;
+pub use shell::{EditorParams, EditorShell};
+pub use spectrum::SpectrumPanel;
+pub use toggle::param_toggle;
+
+use egui::{Pos2, Vec2};
+
+/// Points along a circular arc from `start` to `end` radians, in screen space where angles grow
+/// clockwise.
+pub(crate) fn arc_points(center: Pos2, radius: f32, start: f32, end: f32) -> Vec<Pos2> {
+    const SEGMENTS_PER_RADIAN: f32 = 12.0;
+
+    let segments = (((end - start).abs() * SEGMENTS_PER_RADIAN).ceil() as usize).max(1);
+    (0..=segments)
+        .map(|i| {
+            let angle = start + (end - start) * (i as f32 / segments as f32);
+            center + radius * Vec2::angled(angle)
+        })
+        .collect()
+}
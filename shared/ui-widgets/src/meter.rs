@@ -0,0 +1,216 @@
+use atomic_float::AtomicF32;
+use nih_plug::util;
+use nih_plug_egui::egui::{self, Color32, Rect, Response, Sense, Stroke, Ui, Vec2, Widget};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Time for the peak level to fall by about 63%.
+const PEAK_DECAY_SECONDS: f32 = 0.3;
+/// Length of the exponential RMS averaging window.
+const RMS_WINDOW_SECONDS: f32 = 0.3;
+
+const METER_MIN_DB: f32 = -60.0;
+const METER_MAX_DB: f32 = 6.0;
+
+/// Level metering data shared between the audio thread and an editor. The audio thread calls
+/// [`update()`][Self::update()] with every processed block, and the editor reads the levels and
+/// clip flags. Everything is atomic so neither side ever blocks.
+pub struct MeterState {
+    sample_rate: AtomicF32,
+    channels: Box<[ChannelLevels]>,
+}
+
+#[derive(Default)]
+struct ChannelLevels {
+    peak: AtomicF32,
+    /// Mean square, the meter shows its square root.
+    mean_square: AtomicF32,
+    clipped: AtomicBool,
+}
+
+impl MeterState {
+    pub fn new(num_channels: usize) -> Self {
+        Self {
+            sample_rate: AtomicF32::new(44100.0),
+            channels: (0..num_channels)
+                .map(|_| ChannelLevels::default())
+                .collect(),
+        }
+    }
+
+    /// Call from `initialize()` so the meter ballistics don't depend on the sample rate.
+    pub fn set_sample_rate(&self, sample_rate: f32) {
+        self.sample_rate.store(sample_rate, Ordering::Relaxed);
+    }
+
+    pub fn num_channels(&self) -> usize {
+        self.channels.len()
+    }
+
+    /// Feed a block of samples for `channel`. Channels past the ones the meter was created with
+    /// are ignored.
+    pub fn update(&self, channel: usize, samples: &[f32]) {
+        let Some(levels) = self.channels.get(channel) else {
+            return;
+        };
+        if samples.is_empty() {
+            return;
+        }
+
+        let sample_rate = self.sample_rate.load(Ordering::Relaxed);
+        let block_seconds = samples.len() as f32 / sample_rate;
+        let peak_decay = (-block_seconds / PEAK_DECAY_SECONDS).exp();
+        let rms_weight = (-block_seconds / RMS_WINDOW_SECONDS).exp();
+
+        let mut block_peak = 0.0f32;
+        let mut block_square_sum = 0.0f32;
+        for sample in samples {
+            block_peak = block_peak.max(sample.abs());
+            block_square_sum += sample * sample;
+        }
+
+        let peak = levels.peak.load(Ordering::Relaxed);
+        let new_peak = if block_peak > peak {
+            block_peak
+        } else {
+            peak * peak_decay
+        };
+        levels.peak.store(new_peak, Ordering::Relaxed);
+
+        let mean_square = levels.mean_square.load(Ordering::Relaxed);
+        let block_mean_square = block_square_sum / samples.len() as f32;
+        levels.mean_square.store(
+            mean_square * rms_weight + block_mean_square * (1.0 - rms_weight),
+            Ordering::Relaxed,
+        );
+
+        if block_peak >= 1.0 {
+            levels.clipped.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// The decaying peak level as a linear gain.
+    pub fn peak(&self, channel: usize) -> f32 {
+        self.channels
+            .get(channel)
+            .map_or(0.0, |levels| levels.peak.load(Ordering::Relaxed))
+    }
+
+    /// The RMS level as a linear gain.
+    pub fn rms(&self, channel: usize) -> f32 {
+        self.channels.get(channel).map_or(0.0, |levels| {
+            levels.mean_square.load(Ordering::Relaxed).sqrt()
+        })
+    }
+
+    /// Whether the channel has hit 0 dBFS since the clip indicator was last reset.
+    pub fn clipped(&self, channel: usize) -> bool {
+        self.channels
+            .get(channel)
+            .is_some_and(|levels| levels.clipped.load(Ordering::Relaxed))
+    }
+
+    pub fn reset_clip(&self) {
+        for levels in self.channels.iter() {
+            levels.clipped.store(false, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Vertical peak/RMS bars for every channel in a [`MeterState`], with clip indicators on top.
+/// Clicking the meter resets the clip indicators.
+pub struct LevelMeter<'a> {
+    state: &'a MeterState,
+    size: Vec2,
+}
+
+impl<'a> LevelMeter<'a> {
+    pub fn new(state: &'a MeterState) -> Self {
+        Self {
+            state,
+            size: Vec2::new(24.0, 140.0),
+        }
+    }
+
+    pub fn with_size(mut self, size: Vec2) -> Self {
+        self.size = size;
+        self
+    }
+}
+
+impl Widget for LevelMeter<'_> {
+    fn ui(self, ui: &mut Ui) -> Response {
+        let (rect, response) = ui.allocate_exact_size(self.size, Sense::click());
+        if response.clicked() {
+            self.state.reset_clip();
+        }
+
+        let num_channels = self.state.num_channels();
+        if ui.is_rect_visible(rect) && num_channels > 0 {
+            let visuals = ui.visuals();
+            let painter = ui.painter_at(rect);
+            let clip_height = 6.0;
+            let bar_width = rect.width() / num_channels as f32;
+            let meter_color = visuals.selection.bg_fill;
+
+            painter.rect_filled(rect, 0.0, visuals.extreme_bg_color);
+
+            for channel in 0..num_channels {
+                let left = rect.left() + channel as f32 * bar_width;
+                let bar = Rect::from_min_max(
+                    egui::pos2(left + 1.0, rect.top() + clip_height + 1.0),
+                    egui::pos2(left + bar_width - 1.0, rect.bottom()),
+                );
+                let level_to_y = |gain: f32| {
+                    let db = util::gain_to_db(gain).clamp(METER_MIN_DB, METER_MAX_DB);
+                    let t = (db - METER_MIN_DB) / (METER_MAX_DB - METER_MIN_DB);
+                    bar.bottom() - t * bar.height()
+                };
+
+                let rms_top = level_to_y(self.state.rms(channel));
+                painter.rect_filled(
+                    Rect::from_min_max(egui::pos2(bar.left(), rms_top), bar.right_bottom()),
+                    0.0,
+                    meter_color,
+                );
+
+                let peak_y = level_to_y(self.state.peak(channel));
+                painter.line_segment(
+                    [
+                        egui::pos2(bar.left(), peak_y),
+                        egui::pos2(bar.right(), peak_y),
+                    ],
+                    Stroke::new(2.0, visuals.strong_text_color()),
+                );
+
+                let clip_color = if self.state.clipped(channel) {
+                    Color32::RED
+                } else {
+                    visuals.faint_bg_color
+                };
+                painter.rect_filled(
+                    Rect::from_min_size(
+                        egui::pos2(bar.left(), rect.top()),
+                        Vec2::new(bar.width(), clip_height),
+                    ),
+                    0.0,
+                    clip_color,
+                );
+            }
+
+            let zero_db_y = rect.top()
+                + clip_height
+                + 1.0
+                + (METER_MAX_DB / (METER_MAX_DB - METER_MIN_DB))
+                    * (rect.height() - clip_height - 1.0);
+            painter.line_segment(
+                [
+                    egui::pos2(rect.left(), zero_db_y),
+                    egui::pos2(rect.right(), zero_db_y),
+                ],
+                Stroke::new(1.0, visuals.weak_text_color()),
+            );
+        }
+
+        response
+    }
+}
@@ -0,0 +1,58 @@
+use atomic_float::AtomicF32;
+use std::sync::atomic::Ordering;
+
+/// Modulated parameter values shared between the audio thread and an editor, so knobs can show
+/// where the plugin's own LFOs and envelopes are moving them. The plugin defines one destination
+/// per modulatable parameter. After every processed block the audio thread publishes the current
+/// modulated value of each routed destination, or clears the ones that aren't modulated, and the
+/// editor hands [`value()`][Self::value()] to [`ParamKnob::with_modulation()`]. Everything is
+/// atomic so neither side ever blocks.
+///
+/// [`ParamKnob::with_modulation()`]: crate::ParamKnob::with_modulation()
+pub struct ModulationState {
+    /// Normalized values, NaN when the destination isn't modulated.
+    values: Box<[AtomicF32]>,
+}
+
+impl ModulationState {
+    pub fn new(num_destinations: usize) -> Self {
+        Self {
+            values: (0..num_destinations)
+                .map(|_| AtomicF32::new(f32::NAN))
+                .collect(),
+        }
+    }
+
+    pub fn num_destinations(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Publish a destination's modulated value, normalized like its parameter. Destinations past
+    /// the ones the state was created with are ignored.
+    pub fn publish(&self, destination: usize, normalized: f32) {
+        if let Some(value) = self.values.get(destination) {
+            value.store(normalized.clamp(0.0, 1.0), Ordering::Relaxed);
+        }
+    }
+
+    /// Mark a destination as unmodulated, so its knob goes back to showing the host's modulation.
+    pub fn clear(&self, destination: usize) {
+        if let Some(value) = self.values.get(destination) {
+            value.store(f32::NAN, Ordering::Relaxed);
+        }
+    }
+
+    pub fn clear_all(&self) {
+        for value in self.values.iter() {
+            value.store(f32::NAN, Ordering::Relaxed);
+        }
+    }
+
+    /// The destination's last published value, or `None` if it isn't modulated.
+    pub fn value(&self, destination: usize) -> Option<f32> {
+        self.values
+            .get(destination)
+            .map(|value| value.load(Ordering::Relaxed))
+            .filter(|value| !value.is_nan())
+    }
+}
@@ -0,0 +1,210 @@
+use atomic_float::AtomicF32;
+use nih_plug_egui::egui::{
+    self, Align2, Color32, FontId, Rect, Response, Sense, Stroke, Ui, Vec2, Widget,
+};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Negative correlation means the channels partly cancel in mono.
+const WARNING_COLOR: Color32 = Color32::from_rgb(230, 90, 70);
+
+/// Stereo phase data shared between the audio thread and an editor: the correlation, and a ring
+/// of recent goniometer points. The audio thread publishes what it measured with `dsp_core`'s
+/// `StereoCorrelation` and `GoniometerPoints` after every block. Everything is atomic so neither
+/// side ever blocks. A point can be half overwritten while the editor reads it, which only ever
+/// misplaces a single dot for a frame.
+pub struct PhaseState {
+    correlation: AtomicF32,
+    x: Box<[AtomicF32]>,
+    y: Box<[AtomicF32]>,
+    /// Where the next point goes in the ring.
+    next: AtomicUsize,
+}
+
+impl PhaseState {
+    /// Keeps the most recent `num_points` goniometer points.
+    pub fn new(num_points: usize) -> Self {
+        let ring = || {
+            (0..num_points.max(1))
+                .map(|_| AtomicF32::new(0.0))
+                .collect()
+        };
+        Self {
+            correlation: AtomicF32::new(0.0),
+            x: ring(),
+            y: ring(),
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn set_correlation(&self, correlation: f32) {
+        self.correlation.store(correlation, Ordering::Relaxed);
+    }
+
+    pub fn correlation(&self) -> f32 {
+        self.correlation.load(Ordering::Relaxed)
+    }
+
+    /// Add goniometer points as `(x, y)`, overwriting the oldest ones.
+    pub fn push_points(&self, points: &[(f32, f32)]) {
+        let len = self.x.len();
+        let mut next = self.next.load(Ordering::Relaxed);
+        for &(x, y) in points {
+            self.x[next].store(x, Ordering::Relaxed);
+            self.y[next].store(y, Ordering::Relaxed);
+            next = (next + 1) % len;
+        }
+        self.next.store(next, Ordering::Relaxed);
+    }
+
+    /// Every point in the ring, oldest first.
+    pub fn points(&self) -> impl Iterator<Item = (f32, f32)> + '_ {
+        let len = self.x.len();
+        let next = self.next.load(Ordering::Relaxed);
+        (0..len).map(move |i| {
+            let index = (next + i) % len;
+            (
+                self.x[index].load(Ordering::Relaxed),
+                self.y[index].load(Ordering::Relaxed),
+            )
+        })
+    }
+
+    /// Clear the display, e.g. when the plugin is reset.
+    pub fn reset(&self) {
+        self.correlation.store(0.0, Ordering::Relaxed);
+        for (x, y) in self.x.iter().zip(self.y.iter()) {
+            x.store(0.0, Ordering::Relaxed);
+            y.store(0.0, Ordering::Relaxed);
+        }
+    }
+}
+
+/// A horizontal bar from -1 to 1 with a needle at the [`PhaseState`]'s correlation.
+pub struct CorrelationMeter<'a> {
+    state: &'a PhaseState,
+    size: Vec2,
+}
+
+impl<'a> CorrelationMeter<'a> {
+    pub fn new(state: &'a PhaseState) -> Self {
+        Self {
+            state,
+            size: Vec2::new(240.0, 28.0),
+        }
+    }
+
+    pub fn with_size(mut self, size: Vec2) -> Self {
+        self.size = size;
+        self
+    }
+}
+
+impl Widget for CorrelationMeter<'_> {
+    fn ui(self, ui: &mut Ui) -> Response {
+        let (rect, response) = ui.allocate_exact_size(self.size, Sense::hover());
+
+        if ui.is_rect_visible(rect) {
+            let visuals = ui.visuals();
+            let painter = ui.painter_at(rect);
+            painter.rect_filled(rect, 0.0, visuals.extreme_bg_color);
+
+            let x = |value: f32| rect.left() + (value + 1.0) / 2.0 * rect.width();
+            painter.rect_filled(
+                Rect::from_x_y_ranges(rect.left()..=x(0.0), rect.y_range()),
+                0.0,
+                WARNING_COLOR.linear_multiply(0.15),
+            );
+            for (value, label, align) in [
+                (-1.0, "-1", Align2::LEFT_BOTTOM),
+                (0.0, "0", Align2::CENTER_BOTTOM),
+                (1.0, "+1", Align2::RIGHT_BOTTOM),
+            ] {
+                painter.text(
+                    egui::pos2(x(value), rect.bottom() - 2.0),
+                    align,
+                    label,
+                    FontId::proportional(10.0),
+                    visuals.weak_text_color(),
+                );
+            }
+
+            let correlation = self.state.correlation();
+            let needle_x = x(correlation.clamp(-1.0, 1.0));
+            let needle_color = if correlation < 0.0 {
+                WARNING_COLOR
+            } else {
+                visuals.strong_text_color()
+            };
+            painter.line_segment(
+                [
+                    egui::pos2(needle_x, rect.top()),
+                    egui::pos2(needle_x, rect.bottom()),
+                ],
+                Stroke::new(3.0, needle_color),
+            );
+        }
+
+        response.on_hover_text("Below zero the channels partly cancel out when played in mono")
+    }
+}
+
+/// Draws a [`PhaseState`]'s points as a goniometer, with mono on the vertical axis and the left
+/// and right channels on the diagonals. Wide signals spread out sideways, and out of phase
+/// signals lean towards the horizontal axis.
+pub struct Goniometer<'a> {
+    state: &'a PhaseState,
+    size: f32,
+}
+
+impl<'a> Goniometer<'a> {
+    pub fn new(state: &'a PhaseState) -> Self {
+        Self { state, size: 160.0 }
+    }
+
+    /// The goniometer is always square.
+    pub fn with_size(mut self, size: f32) -> Self {
+        self.size = size;
+        self
+    }
+}
+
+impl Widget for Goniometer<'_> {
+    fn ui(self, ui: &mut Ui) -> Response {
+        let (rect, response) = ui.allocate_exact_size(Vec2::splat(self.size), Sense::hover());
+
+        if ui.is_rect_visible(rect) {
+            let visuals = ui.visuals();
+            let painter = ui.painter_at(rect);
+            painter.rect_filled(rect, 0.0, visuals.extreme_bg_color);
+
+            let center = rect.center();
+            let radius = rect.width() / 2.0;
+            let axis_stroke = Stroke::new(1.0, visuals.faint_bg_color);
+            painter.line_segment([rect.center_top(), rect.center_bottom()], axis_stroke);
+            painter.line_segment([rect.left_top(), rect.right_bottom()], axis_stroke);
+            painter.line_segment([rect.right_top(), rect.left_bottom()], axis_stroke);
+            for (label, position, align) in [
+                ("M", rect.center_top(), Align2::CENTER_TOP),
+                ("L", rect.left_top(), Align2::LEFT_TOP),
+                ("R", rect.right_top(), Align2::RIGHT_TOP),
+            ] {
+                painter.text(
+                    position + Vec2::new(0.0, 2.0),
+                    align,
+                    label,
+                    FontId::proportional(10.0),
+                    visuals.weak_text_color(),
+                );
+            }
+
+            let dot_color = visuals.selection.bg_fill;
+            for (x, y) in self.state.points() {
+                let position =
+                    center + Vec2::new(x, -y).clamp(-Vec2::splat(1.0), Vec2::splat(1.0)) * radius;
+                painter.circle_filled(position, 1.0, dot_color);
+            }
+        }
+
+        response
+    }
+}
@@ -0,0 +1,58 @@
+use crate::undo::{self, ParamChange};
+use nih_plug::prelude::{nih_warn, ParamPtr, ParamSetter, Params};
+use nih_plug_egui::egui::{self, Id, Response, Ui};
+use plugin_scaffold::presets::FactoryPreset;
+
+/// A drop-down that loads one of the plugin's factory presets, showing the last one loaded. A
+/// preset load is recorded in the undo history as a single step.
+pub fn preset_combo(
+    ui: &mut Ui,
+    presets: &[FactoryPreset],
+    params: &dyn Params,
+    setter: &ParamSetter,
+) -> Response {
+    let id = Id::new("factory-preset");
+    let loaded = ui.data(|data| data.get_temp::<usize>(id));
+    let selected_text = loaded
+        .and_then(|idx| presets.get(idx))
+        .map_or("-", |preset| preset.name);
+
+    egui::ComboBox::from_label("Preset")
+        .selected_text(selected_text)
+        .show_ui(ui, |ui| {
+            for (idx, preset) in presets.iter().enumerate() {
+                if !ui
+                    .selectable_label(loaded == Some(idx), preset.name)
+                    .clicked()
+                {
+                    continue;
+                }
+
+                match preset.resolve(params) {
+                    Ok(values) => {
+                        load(ui, setter, &values);
+                        ui.data_mut(|data| data.insert_temp(id, idx));
+                    }
+                    Err(err) => nih_warn!("Could not load the preset '{}': {err}", preset.name),
+                }
+            }
+        })
+        .response
+}
+
+fn load(ui: &Ui, setter: &ParamSetter, values: &[(ParamPtr, f32)]) {
+    let changes: Vec<ParamChange> = values
+        .iter()
+        .map(|&(param, after)| ParamChange {
+            param,
+            // SAFETY: The parameters outlive the editor
+            before: unsafe { param.unmodulated_normalized_value() },
+            after,
+        })
+        .collect();
+
+    for change in &changes {
+        undo::apply(setter, change.param, change.after);
+    }
+    undo::record_changes(ui.ctx(), changes);
+}
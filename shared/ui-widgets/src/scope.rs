@@ -0,0 +1,56 @@
+use nih_plug_egui::egui::{self, Response, Sense, Shape, Stroke, Ui, Vec2, Widget};
+
+/// Draws a block of samples as a waveform, with -1..1 mapped to the full height.
+pub struct Scope<'a> {
+    samples: &'a [f32],
+    size: Vec2,
+}
+
+impl<'a> Scope<'a> {
+    pub fn new(samples: &'a [f32]) -> Self {
+        Self {
+            samples,
+            size: Vec2::new(240.0, 80.0),
+        }
+    }
+
+    pub fn with_size(mut self, size: Vec2) -> Self {
+        self.size = size;
+        self
+    }
+}
+
+impl Widget for Scope<'_> {
+    fn ui(self, ui: &mut Ui) -> Response {
+        let (rect, response) = ui.allocate_exact_size(self.size, Sense::hover());
+
+        if ui.is_rect_visible(rect) {
+            let visuals = ui.visuals();
+            let painter = ui.painter_at(rect);
+            painter.rect_filled(rect, 0.0, visuals.extreme_bg_color);
+            painter.line_segment(
+                [rect.left_center(), rect.right_center()],
+                Stroke::new(1.0, visuals.weak_text_color()),
+            );
+
+            if self.samples.len() > 1 {
+                let x_step = rect.width() / (self.samples.len() - 1) as f32;
+                let points = self
+                    .samples
+                    .iter()
+                    .enumerate()
+                    .map(|(i, sample)| {
+                        let y = rect.center().y - sample.clamp(-1.0, 1.0) * rect.height() / 2.0;
+                        egui::pos2(rect.left() + i as f32 * x_step, y)
+                    })
+                    .collect();
+                painter.add(Shape::line(
+                    points,
+                    Stroke::new(1.5, visuals.selection.bg_fill),
+                ));
+            }
+        }
+
+        response
+    }
+}
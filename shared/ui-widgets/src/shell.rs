@@ -0,0 +1,239 @@
+use crate::keyboard::{Keyboard, KeyboardState};
+use crate::meter::{LevelMeter, MeterState};
+use crate::presets::preset_combo;
+use crate::toggle::param_toggle;
+use crate::undo;
+use nih_plug::prelude::{BoolParam, ParamSetter, Params};
+use nih_plug_egui::egui::{
+    self, Align, Align2, Context, CursorIcon, Id, Layout, Sense, Stroke, Ui, Vec2,
+};
+use nih_plug_egui::EguiState;
+use plugin_scaffold::presets::FactoryPreset;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+
+/// The zoom levels offered in the header bar, in percent.
+const SCALES: [u32; 6] = [75, 100, 125, 150, 175, 200];
+const DEFAULT_SCALE: u32 = 100;
+
+const HEADER_METER_SIZE: Vec2 = Vec2::new(24.0, 28.0);
+const RESIZE_CORNER_SIZE: f32 = 14.0;
+
+/// The footer keyboard's range, five octaves up from MIDI note 36.
+const KEYBOARD_LOW: u8 = 36;
+const KEYBOARD_HIGH: u8 = 96;
+const KEYBOARD_HEIGHT: f32 = 56.0;
+
+/// A plugin's editor window, saved with the session: its size, and the zoom in percent picked in
+/// the [`EditorShell`]'s header bar. Nest it in the plugin's `Params`:
+///
+/// ```ignore
+/// #[nested]
+/// editor: EditorParams,
+/// ```
+#[derive(Params)]
+pub struct EditorParams {
+    #[persist = "editor-state"]
+    pub state: Arc<EguiState>,
+    #[persist = "ui-scale"]
+    scale: AtomicU32,
+    /// The default and smallest window size at 100% zoom, in logical pixels.
+    min_size: (u32, u32),
+}
+
+impl EditorParams {
+    pub fn new(size: (u32, u32)) -> Self {
+        Self {
+            state: EguiState::from_size(size.0, size.1),
+            scale: AtomicU32::new(DEFAULT_SCALE),
+            min_size: size,
+        }
+    }
+}
+
+/// The frame every plugin editor embeds its panels into. It has a header bar with the plugin's
+/// factory presets, bypass switch, and output meter, whichever the plugin has, and a zoom picker.
+/// Instruments can add a keyboard along the bottom to play them from the editor. The window can
+/// be resized from its bottom right corner, down to the [`EditorParams`]' size. The size and
+/// the zoom are kept in the `EditorParams`, so both come back with the session. The zoom applies
+/// on top of the host's HiDPI scale factor.
+///
+/// The shell also handles the undo shortcuts, so editors using it don't need to.
+pub struct EditorShell<'a> {
+    egui_state: &'a EguiState,
+    scale: &'a AtomicU32,
+    /// The smallest the window can be resized to at 100% zoom, in logical pixels.
+    min_size: Vec2,
+    bypass: Option<&'a BoolParam>,
+    presets: Option<(&'a [FactoryPreset], &'a dyn Params)>,
+    meter: Option<&'a MeterState>,
+    keyboard: Option<&'a KeyboardState>,
+}
+
+impl<'a> EditorShell<'a> {
+    pub fn new(editor: &'a EditorParams) -> Self {
+        Self {
+            egui_state: &editor.state,
+            scale: &editor.scale,
+            min_size: Vec2::new(editor.min_size.0 as f32, editor.min_size.1 as f32),
+            bypass: None,
+            presets: None,
+            meter: None,
+            keyboard: None,
+        }
+    }
+
+    pub fn with_bypass(mut self, bypass: &'a BoolParam) -> Self {
+        self.bypass = Some(bypass);
+        self
+    }
+
+    pub fn with_presets(mut self, presets: &'a [FactoryPreset], params: &'a dyn Params) -> Self {
+        self.presets = Some((presets, params));
+        self
+    }
+
+    pub fn with_meter(mut self, meter: &'a MeterState) -> Self {
+        self.meter = Some(meter);
+        self
+    }
+
+    /// A keyboard along the bottom of the editor that plays the plugin, and lights up the notes
+    /// it receives.
+    pub fn with_keyboard(mut self, keyboard: &'a KeyboardState) -> Self {
+        self.keyboard = Some(keyboard);
+        self
+    }
+
+    /// Draw the header bar and the plugin's panels below it. Call this once per frame in place of
+    /// an `egui::CentralPanel`.
+    pub fn show<R>(
+        self,
+        ctx: &Context,
+        setter: &ParamSetter,
+        add_contents: impl FnOnce(&mut Ui) -> R,
+    ) -> R {
+        undo::handle_shortcuts(ctx, setter);
+
+        let zoom = self.scale.load(Ordering::Relaxed) as f32 / 100.0;
+        if ctx.zoom_factor() != zoom {
+            ctx.set_zoom_factor(zoom);
+        }
+
+        egui::TopBottomPanel::top("editor-shell-header").show(ctx, |ui| {
+            ui.horizontal(|ui| self.header(ui, setter));
+        });
+        if let Some(keyboard) = self.keyboard {
+            egui::TopBottomPanel::bottom("editor-shell-keyboard").show(ctx, |ui| {
+                // Leave room for the resize handle
+                let width = ui.available_width() - RESIZE_CORNER_SIZE;
+                Keyboard::new(KEYBOARD_LOW, KEYBOARD_HIGH)
+                    .with_state(keyboard)
+                    .with_size(Vec2::new(width, KEYBOARD_HEIGHT))
+                    .show(ui);
+            });
+
+            // Notes can arrive at any time, so keep repainting while the editor is open
+            ctx.request_repaint();
+        }
+        let inner = egui::CentralPanel::default().show(ctx, add_contents).inner;
+        self.resize_corner(ctx, zoom);
+
+        inner
+    }
+
+    fn header(&self, ui: &mut Ui, setter: &ParamSetter) {
+        if let Some((presets, params)) = self.presets {
+            preset_combo(ui, presets, params, setter);
+        }
+        if let Some(bypass) = self.bypass {
+            param_toggle(ui, bypass, setter);
+        }
+
+        ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
+            if let Some(meter) = self.meter {
+                ui.add(LevelMeter::new(meter).with_size(HEADER_METER_SIZE))
+                    .on_hover_text("Output level, click to reset the clip indicators");
+            }
+            self.scale_combo(ui);
+        });
+    }
+
+    /// Changing the zoom resizes the window by the same amount, so the layout stays the same.
+    fn scale_combo(&self, ui: &mut Ui) {
+        let current = self.scale.load(Ordering::Relaxed);
+        let mut selected = current;
+        egui::ComboBox::from_label("Zoom")
+            .selected_text(format!("{current}%"))
+            .width(60.0)
+            .show_ui(ui, |ui| {
+                for scale in SCALES {
+                    ui.selectable_value(&mut selected, scale, format!("{scale}%"));
+                }
+            });
+
+        if selected != current {
+            self.scale.store(selected, Ordering::Relaxed);
+            let ratio = selected as f32 / current as f32;
+            let (width, height) = self.egui_state.size();
+            self.egui_state.set_requested_size((
+                (width as f32 * ratio).round() as u32,
+                (height as f32 * ratio).round() as u32,
+            ));
+        }
+    }
+
+    /// A drag handle in the bottom right corner. The pointer is in zoomed points, while the
+    /// window's size is in logical pixels.
+    fn resize_corner(&self, ctx: &Context, zoom: f32) {
+        egui::Area::new(Id::new("editor-shell-resize"))
+            .anchor(Align2::RIGHT_BOTTOM, Vec2::ZERO)
+            .show(ctx, |ui| {
+                let (rect, response) =
+                    ui.allocate_exact_size(Vec2::splat(RESIZE_CORNER_SIZE), Sense::drag());
+                let response = response.on_hover_cursor(CursorIcon::ResizeNwSe);
+
+                let stroke = Stroke::new(1.0, ui.visuals().weak_text_color());
+                for offset in [0.3, 0.65] {
+                    let inset = rect.width() * offset;
+                    ui.painter().line_segment(
+                        [
+                            egui::pos2(rect.right() - inset, rect.bottom()),
+                            egui::pos2(rect.right(), rect.bottom() - inset),
+                        ],
+                        stroke,
+                    );
+                }
+
+                let dragged = response.dragged();
+                if let Some(pointer) = response.interact_pointer_pos().filter(|_| dragged) {
+                    let size =
+                        ((pointer.to_vec2() + rect.size() / 2.0) * zoom).max(self.min_size * zoom);
+                    self.egui_state
+                        .set_requested_size((size.x.round() as u32, size.y.round() as u32));
+                }
+            });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn editor_params_persist_the_size_and_zoom_under_their_keys() {
+        let editor = EditorParams::new((300, 200));
+        assert_eq!(editor.state.size(), (300, 200));
+
+        let fields = editor.serialize_fields();
+        assert_eq!(
+            fields.keys().collect::<Vec<_>>(),
+            ["editor-state", "ui-scale"]
+        );
+        assert_eq!(fields["ui-scale"], "100");
+
+        editor.deserialize_fields(&BTreeMap::from([("ui-scale".to_owned(), "150".to_owned())]));
+        assert_eq!(editor.scale.load(Ordering::Relaxed), 150);
+    }
+}
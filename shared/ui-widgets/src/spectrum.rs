@@ -0,0 +1,155 @@
+use nih_plug_egui::egui::{
+    self, Align2, FontId, Pos2, Rect, Response, Sense, Shape, Stroke, Ui, Vec2, Widget,
+};
+
+const MIN_FREQUENCY: f32 = 20.0;
+const MAX_FREQUENCY: f32 = 20_000.0;
+const GRID_FREQUENCIES: [(f32, &str); 3] = [(100.0, "100"), (1_000.0, "1k"), (10_000.0, "10k")];
+
+/// Draws a magnitude spectrum on a logarithmic frequency axis. `magnitudes_db` holds one value
+/// in decibels per FFT bin, evenly spaced from 0 Hz to Nyquist.
+pub struct SpectrumPanel<'a> {
+    magnitudes_db: &'a [f32],
+    /// A second, fainter curve drawn behind the spectrum, like a peak hold.
+    peaks_db: Option<&'a [f32]>,
+    sample_rate: f32,
+    db_range: (f32, f32),
+    /// The spacing of labelled horizontal grid lines, if any.
+    db_grid_step: Option<f32>,
+    size: Vec2,
+}
+
+impl<'a> SpectrumPanel<'a> {
+    pub fn new(magnitudes_db: &'a [f32], sample_rate: f32) -> Self {
+        Self {
+            magnitudes_db,
+            peaks_db: None,
+            sample_rate,
+            db_range: (-90.0, 0.0),
+            db_grid_step: None,
+            size: Vec2::new(320.0, 120.0),
+        }
+    }
+
+    /// The decibel values at the bottom and top of the panel.
+    pub fn with_db_range(mut self, min_db: f32, max_db: f32) -> Self {
+        self.db_range = (min_db, max_db);
+        self
+    }
+
+    /// Also draw held peaks, one value in decibels per bin like the magnitudes.
+    pub fn with_peaks(mut self, peaks_db: &'a [f32]) -> Self {
+        self.peaks_db = Some(peaks_db);
+        self
+    }
+
+    /// Draw a labelled grid line every `step` decibels, counting down from the top of the range.
+    pub fn with_db_grid(mut self, step: f32) -> Self {
+        self.db_grid_step = Some(step);
+        self
+    }
+
+    pub fn with_size(mut self, size: Vec2) -> Self {
+        self.size = size;
+        self
+    }
+}
+
+/// Map a frequency to an x coordinate on a log scale from 20 Hz to 20 kHz.
+pub fn frequency_to_x(rect: Rect, frequency: f32) -> f32 {
+    let t = (frequency / MIN_FREQUENCY).ln() / (MAX_FREQUENCY / MIN_FREQUENCY).ln();
+    rect.left() + t * rect.width()
+}
+
+/// Map a decibel value to a y coordinate, clamped to the panel.
+pub fn db_to_y(rect: Rect, db: f32, (min_db, max_db): (f32, f32)) -> f32 {
+    let t = ((db - min_db) / (max_db - min_db)).clamp(0.0, 1.0);
+    rect.bottom() - t * rect.height()
+}
+
+impl SpectrumPanel<'_> {
+    /// The curve through `values_db`, skipping DC and bins outside the frequency axis.
+    fn curve_points(&self, rect: Rect, values_db: &[f32]) -> Vec<Pos2> {
+        let num_bins = values_db.len();
+        if num_bins < 2 {
+            return Vec::new();
+        }
+
+        let bin_width = self.sample_rate / 2.0 / (num_bins - 1) as f32;
+        values_db
+            .iter()
+            .enumerate()
+            .skip(1)
+            .map(|(bin, &db)| (bin as f32 * bin_width, db))
+            .filter(|&(frequency, _)| (MIN_FREQUENCY..=MAX_FREQUENCY).contains(&frequency))
+            .map(|(frequency, db)| {
+                egui::pos2(
+                    frequency_to_x(rect, frequency),
+                    db_to_y(rect, db, self.db_range),
+                )
+            })
+            .collect()
+    }
+}
+
+impl Widget for SpectrumPanel<'_> {
+    fn ui(self, ui: &mut Ui) -> Response {
+        let (rect, response) = ui.allocate_exact_size(self.size, Sense::hover());
+
+        if ui.is_rect_visible(rect) {
+            let visuals = ui.visuals();
+            let painter = ui.painter_at(rect);
+            let grid_stroke = Stroke::new(1.0, visuals.faint_bg_color);
+            painter.rect_filled(rect, 0.0, visuals.extreme_bg_color);
+
+            for (frequency, label) in GRID_FREQUENCIES {
+                let x = frequency_to_x(rect, frequency);
+                painter.line_segment(
+                    [egui::pos2(x, rect.top()), egui::pos2(x, rect.bottom())],
+                    grid_stroke,
+                );
+                painter.text(
+                    egui::pos2(x + 2.0, rect.bottom() - 2.0),
+                    Align2::LEFT_BOTTOM,
+                    label,
+                    FontId::proportional(10.0),
+                    visuals.weak_text_color(),
+                );
+            }
+
+            if let Some(step) = self.db_grid_step.filter(|&step| step > 0.0) {
+                let (min_db, max_db) = self.db_range;
+                let mut db = max_db - step;
+                while db > min_db {
+                    let y = db_to_y(rect, db, self.db_range);
+                    painter.line_segment(
+                        [egui::pos2(rect.left(), y), egui::pos2(rect.right(), y)],
+                        grid_stroke,
+                    );
+                    painter.text(
+                        egui::pos2(rect.left() + 2.0, y - 1.0),
+                        Align2::LEFT_BOTTOM,
+                        format!("{db:.0}"),
+                        FontId::proportional(10.0),
+                        visuals.weak_text_color(),
+                    );
+                    db -= step;
+                }
+            }
+
+            let line_color = visuals.selection.bg_fill;
+            if let Some(peaks_db) = self.peaks_db {
+                painter.add(Shape::line(
+                    self.curve_points(rect, peaks_db),
+                    Stroke::new(1.0, line_color.gamma_multiply(0.5)),
+                ));
+            }
+            painter.add(Shape::line(
+                self.curve_points(rect, self.magnitudes_db),
+                Stroke::new(1.5, line_color),
+            ));
+        }
+
+        response
+    }
+}
@@ -0,0 +1,24 @@
+use crate::undo;
+use nih_plug::prelude::{BoolParam, Param, ParamSetter};
+use nih_plug_egui::egui::{Response, Ui};
+
+/// A checkbox for a boolean parameter, labelled with the parameter's name. Changes are recorded
+/// in the undo history.
+pub fn param_toggle(ui: &mut Ui, param: &BoolParam, setter: &ParamSetter) -> Response {
+    let mut value = param.value();
+    let response = ui.checkbox(&mut value, param.name());
+    if response.changed() {
+        let before = param.unmodulated_normalized_value();
+        setter.begin_set_parameter(param);
+        setter.set_parameter(param, value);
+        setter.end_set_parameter(param);
+        undo::record_change(
+            ui.ctx(),
+            param.as_ptr(),
+            before,
+            param.preview_normalized(value),
+        );
+    }
+
+    response
+}
@@ -0,0 +1,269 @@
+//! An undo history for parameter changes made in the editor. Hosts rarely undo changes a plugin
+//! makes on its own, like loading a preset, so the editor keeps its own history.
+//!
+//! The history lives in egui's memory. The widgets in this crate record their gestures
+//! automatically, editors only need to call [`handle_shortcuts()`] every frame and record
+//! changes they make themselves with [`record_change()`] or [`record_changes()`].
+
+use nih_plug::prelude::{ParamPtr, ParamSetter};
+use nih_plug_egui::egui::{Context, Id, Key, KeyboardShortcut, Modifiers};
+
+/// Older entries are dropped beyond this.
+const MAX_HISTORY: usize = 100;
+
+/// A parameter change as normalized values.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ParamChange {
+    pub param: ParamPtr,
+    pub before: f32,
+    pub after: f32,
+}
+
+#[derive(Debug, Clone, Default)]
+struct UndoHistory {
+    /// Each entry is undone in one step, e.g. a whole preset load.
+    undo: Vec<Vec<ParamChange>>,
+    redo: Vec<Vec<ParamChange>>,
+    /// Gestures that have started, with the value before the gesture.
+    pending: Vec<(ParamPtr, f32)>,
+}
+
+impl UndoHistory {
+    fn push(&mut self, changes: Vec<ParamChange>) {
+        if changes.is_empty() {
+            return;
+        }
+
+        if self.undo.len() == MAX_HISTORY {
+            self.undo.remove(0);
+        }
+        self.undo.push(changes);
+        self.redo.clear();
+    }
+
+    /// Move the most recent entry to the redo stack, returning the changes to revert.
+    fn undo(&mut self) -> Option<Vec<ParamChange>> {
+        let changes = self.undo.pop()?;
+        self.redo.push(changes.clone());
+        Some(changes)
+    }
+
+    /// Move the most recently undone entry back, returning the changes to reapply.
+    fn redo(&mut self) -> Option<Vec<ParamChange>> {
+        let changes = self.redo.pop()?;
+        self.undo.push(changes.clone());
+        Some(changes)
+    }
+}
+
+fn history_id() -> Id {
+    Id::new("ui_widgets::undo::UndoHistory")
+}
+
+fn with_history<R>(ctx: &Context, f: impl FnOnce(&mut UndoHistory) -> R) -> R {
+    ctx.data_mut(|data| f(data.get_temp_mut_or_default::<UndoHistory>(history_id())))
+}
+
+/// Start recording a gesture, e.g. when a knob drag starts.
+pub fn begin_gesture(ctx: &Context, param: ParamPtr, before: f32) {
+    with_history(ctx, |history| {
+        history.pending.retain(|(pending, _)| *pending != param);
+        history.pending.push((param, before));
+    });
+}
+
+/// Finish a gesture started with [`begin_gesture()`], recording it if the value changed.
+pub fn end_gesture(ctx: &Context, param: ParamPtr, after: f32) {
+    with_history(ctx, |history| {
+        let Some(idx) = history
+            .pending
+            .iter()
+            .position(|(pending, _)| *pending == param)
+        else {
+            return;
+        };
+
+        let (_, before) = history.pending.remove(idx);
+        if before != after {
+            history.push(vec![ParamChange {
+                param,
+                before,
+                after,
+            }]);
+        }
+    });
+}
+
+/// Record a single change that isn't part of a gesture, e.g. a reset to the default value.
+pub fn record_change(ctx: &Context, param: ParamPtr, before: f32, after: f32) {
+    if before != after {
+        with_history(ctx, |history| {
+            history.push(vec![ParamChange {
+                param,
+                before,
+                after,
+            }])
+        });
+    }
+}
+
+/// Record several changes that are undone together, e.g. a preset load.
+pub fn record_changes(ctx: &Context, changes: impl IntoIterator<Item = ParamChange>) {
+    let changes = changes
+        .into_iter()
+        .filter(|change| change.before != change.after)
+        .collect();
+    with_history(ctx, |history| history.push(changes));
+}
+
+/// Undo the most recent entry. Returns `false` if there was nothing to undo.
+pub fn undo(ctx: &Context, setter: &ParamSetter) -> bool {
+    let Some(changes) = with_history(ctx, UndoHistory::undo) else {
+        return false;
+    };
+
+    for change in changes.iter().rev() {
+        apply(setter, change.param, change.before);
+    }
+
+    true
+}
+
+/// Redo the most recently undone entry. Returns `false` if there was nothing to redo.
+pub fn redo(ctx: &Context, setter: &ParamSetter) -> bool {
+    let Some(changes) = with_history(ctx, UndoHistory::redo) else {
+        return false;
+    };
+
+    for change in &changes {
+        apply(setter, change.param, change.after);
+    }
+
+    true
+}
+
+/// Undo with Ctrl+Z (Cmd+Z on macOS), redo with Ctrl+Shift+Z or Ctrl+Y.
+pub fn handle_shortcuts(ctx: &Context, setter: &ParamSetter) {
+    // Shift is ignored when matching shortcuts that don't include it, so redo goes first
+    let redo_pressed = ctx.input_mut(|input| {
+        input.consume_shortcut(&KeyboardShortcut::new(
+            Modifiers::COMMAND | Modifiers::SHIFT,
+            Key::Z,
+        )) || input.consume_shortcut(&KeyboardShortcut::new(Modifiers::COMMAND, Key::Y))
+    });
+    if redo_pressed {
+        redo(ctx, setter);
+    } else if ctx.input_mut(|input| {
+        input.consume_shortcut(&KeyboardShortcut::new(Modifiers::COMMAND, Key::Z))
+    }) {
+        undo(ctx, setter);
+    }
+}
+
+pub fn can_undo(ctx: &Context) -> bool {
+    with_history(ctx, |history| !history.undo.is_empty())
+}
+
+pub fn can_redo(ctx: &Context) -> bool {
+    with_history(ctx, |history| !history.redo.is_empty())
+}
+
+pub(crate) fn apply(setter: &ParamSetter, param: ParamPtr, normalized: f32) {
+    // SAFETY: The parameters outlive the editor, and with it the history
+    unsafe {
+        setter.raw_context.raw_begin_set_parameter(param);
+        setter
+            .raw_context
+            .raw_set_parameter_normalized(param, normalized);
+        setter.raw_context.raw_end_set_parameter(param);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nih_plug::prelude::{FloatParam, FloatRange, Param};
+
+    fn test_param(name: &str) -> FloatParam {
+        FloatParam::new(name, 0.0, FloatRange::Linear { min: 0.0, max: 1.0 })
+    }
+
+    fn change(param: &FloatParam, before: f32, after: f32) -> ParamChange {
+        ParamChange {
+            param: param.as_ptr(),
+            before,
+            after,
+        }
+    }
+
+    #[test]
+    fn undo_and_redo_move_entries_between_the_stacks() {
+        let (a, b) = (test_param("a"), test_param("b"));
+        let mut history = UndoHistory::default();
+        history.push(vec![change(&a, 0.0, 0.5)]);
+        history.push(vec![change(&a, 0.5, 0.75), change(&b, 0.0, 1.0)]);
+
+        assert_eq!(
+            history.undo(),
+            Some(vec![change(&a, 0.5, 0.75), change(&b, 0.0, 1.0)])
+        );
+        assert_eq!(history.undo(), Some(vec![change(&a, 0.0, 0.5)]));
+        assert_eq!(history.undo(), None);
+
+        assert_eq!(history.redo(), Some(vec![change(&a, 0.0, 0.5)]));
+        assert_eq!(history.undo(), Some(vec![change(&a, 0.0, 0.5)]));
+        assert_eq!(history.redo(), Some(vec![change(&a, 0.0, 0.5)]));
+        assert_eq!(
+            history.redo(),
+            Some(vec![change(&a, 0.5, 0.75), change(&b, 0.0, 1.0)])
+        );
+        assert_eq!(history.redo(), None);
+    }
+
+    #[test]
+    fn new_changes_clear_the_redo_stack() {
+        let a = test_param("a");
+        let mut history = UndoHistory::default();
+        history.push(vec![change(&a, 0.0, 0.5)]);
+        history.undo();
+        history.push(vec![change(&a, 0.0, 0.25)]);
+
+        assert_eq!(history.redo(), None);
+        assert_eq!(history.undo(), Some(vec![change(&a, 0.0, 0.25)]));
+    }
+
+    #[test]
+    fn the_oldest_entries_are_dropped() {
+        let a = test_param("a");
+        let mut history = UndoHistory::default();
+        for n in 0..MAX_HISTORY + 10 {
+            history.push(vec![change(&a, n as f32, n as f32 + 1.0)]);
+        }
+
+        assert_eq!(history.undo.len(), MAX_HISTORY);
+        assert_eq!(history.undo[0], vec![change(&a, 10.0, 11.0)]);
+    }
+
+    #[test]
+    fn gestures_are_recorded_only_when_the_value_changed() {
+        let ctx = Context::default();
+        let (a, b) = (test_param("a"), test_param("b"));
+
+        begin_gesture(&ctx, a.as_ptr(), 0.25);
+        end_gesture(&ctx, a.as_ptr(), 0.25);
+        // Ending a gesture that never started is ignored
+        end_gesture(&ctx, b.as_ptr(), 0.5);
+        record_changes(&ctx, [change(&a, 0.5, 0.5)]);
+        assert!(!can_undo(&ctx));
+
+        begin_gesture(&ctx, a.as_ptr(), 0.25);
+        end_gesture(&ctx, a.as_ptr(), 0.75);
+        assert!(can_undo(&ctx));
+        assert_eq!(
+            with_history(&ctx, UndoHistory::undo),
+            Some(vec![change(&a, 0.25, 0.75)])
+        );
+        assert!(!can_undo(&ctx));
+        assert!(can_redo(&ctx));
+    }
+}
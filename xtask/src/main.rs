@@ -1,3 +1,235 @@
+use anyhow::{Context, bail};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const USAGE: &str = "Usage:
+  cargo xtask bundle-all [--release] [--universal]
+  cargo xtask install [--release] [--universal]
+  cargo xtask validate [--release]
+  cargo xtask <nih_plug_xtask command> ...
+
+bundle-all  Bundle every plugin in plugins/ as VST3 and CLAP
+install     Bundle every plugin and copy the bundles into the user's plugin directories
+validate    Bundle every plugin and run it through clap-validator and the VST3 SDK validator.
+            The validators are looked up on PATH, or set CLAP_VALIDATOR/VST3_VALIDATOR
+--universal Build universal x86_64/aarch64 binaries (macOS only)";
+
+/// Plugins are installed into a vendor subdirectory so they're easy to find and remove.
+const INSTALL_SUBDIR: &str = "blight-vsti";
+
 fn main() -> nih_plug_xtask::Result<()> {
-    nih_plug_xtask::main()
+    let mut args = std::env::args().skip(1);
+    let command = args.next();
+
+    match command.as_deref() {
+        Some("bundle-all") => {
+            nih_plug_xtask::chdir_workspace_root()?;
+            bundle_all(args)?;
+        }
+        Some("install") => {
+            nih_plug_xtask::chdir_workspace_root()?;
+            let plugins = bundle_all(args)?;
+            install(&plugins)?;
+        }
+        Some("validate") => {
+            nih_plug_xtask::chdir_workspace_root()?;
+            let plugins = bundle_all(args)?;
+            validate(&plugins)?;
+        }
+        Some("help" | "--help" | "-h") => println!("{USAGE}"),
+        // Everything else is handled by nih_plug's own xtask
+        _ => nih_plug_xtask::main()?,
+    }
+
+    Ok(())
+}
+
+/// Bundle all plugins in the workspace, returning their package names.
+fn bundle_all(args: impl Iterator<Item = String>) -> nih_plug_xtask::Result<Vec<String>> {
+    let mut universal = false;
+    let mut cargo_args = Vec::new();
+    for arg in args {
+        match arg.as_str() {
+            "--universal" => universal = true,
+            _ => cargo_args.push(arg),
+        }
+    }
+
+    if universal && !cfg!(target_os = "macos") {
+        bail!("--universal is only supported on macOS");
+    }
+
+    let plugins = workspace_plugins()?;
+    if plugins.is_empty() {
+        bail!("No plugins found in plugins/");
+    }
+
+    let bundle_command = if universal {
+        "bundle-universal"
+    } else {
+        "bundle"
+    };
+    let mut bundle_args = vec![bundle_command.to_owned()];
+    for plugin in &plugins {
+        bundle_args.push("-p".to_owned());
+        bundle_args.push(plugin.clone());
+    }
+    bundle_args.extend(cargo_args);
+
+    nih_plug_xtask::main_with_args("cargo xtask", bundle_args)?;
+
+    Ok(plugins)
+}
+
+/// The package names of every plugin crate under `plugins/`.
+fn workspace_plugins() -> nih_plug_xtask::Result<Vec<String>> {
+    let mut plugins = Vec::new();
+    for entry in fs::read_dir("plugins").context("Could not read the plugins directory")? {
+        let path = entry?.path();
+        let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+            continue;
+        };
+        if path.join("Cargo.toml").is_file() {
+            plugins.push(name.to_owned());
+        }
+    }
+    plugins.sort();
+
+    Ok(plugins)
+}
+
+/// Copy the bundled plugins from `target/bundled` into the OS's VST3 and CLAP directories.
+fn install(plugins: &[String]) -> nih_plug_xtask::Result<()> {
+    let bundled_dir = Path::new("target").join("bundled");
+    let (vst3_dir, clap_dir) = plugin_dirs()?;
+
+    for (format_dir, extension) in [(vst3_dir, "vst3"), (clap_dir, "clap")] {
+        let target_dir = format_dir.join(INSTALL_SUBDIR);
+        fs::create_dir_all(&target_dir)
+            .with_context(|| format!("Could not create '{}'", target_dir.display()))?;
+
+        for plugin in plugins {
+            let bundle_name = format!("{plugin}.{extension}");
+            let source = bundled_dir.join(&bundle_name);
+            let destination = target_dir.join(&bundle_name);
+            if !source.exists() {
+                eprintln!("Warning: '{}' not found, skipping", source.display());
+                continue;
+            }
+
+            remove_path(&destination)?;
+            copy_path(&source, &destination).with_context(|| {
+                format!(
+                    "Could not copy '{}' to '{}'",
+                    source.display(),
+                    destination.display()
+                )
+            })?;
+            eprintln!("Installed '{}'", destination.display());
+        }
+    }
+
+    Ok(())
+}
+
+/// Run each bundled plugin through the available validators, failing if any of them reports a
+/// problem. Validators that aren't installed are skipped with a warning.
+fn validate(plugins: &[String]) -> nih_plug_xtask::Result<()> {
+    let bundled_dir = Path::new("target").join("bundled");
+    let validators = [
+        (
+            "CLAP_VALIDATOR",
+            "clap-validator",
+            "clap",
+            &["validate"][..],
+        ),
+        ("VST3_VALIDATOR", "validator", "vst3", &[][..]),
+    ];
+
+    let mut failures = Vec::new();
+    for (env_var, default_program, extension, validator_args) in validators {
+        let program = std::env::var(env_var).unwrap_or_else(|_| default_program.to_owned());
+
+        for plugin in plugins {
+            let bundle = bundled_dir.join(format!("{plugin}.{extension}"));
+            if !bundle.exists() {
+                eprintln!("Warning: '{}' not found, skipping", bundle.display());
+                continue;
+            }
+
+            eprintln!("Validating '{}' with {program}", bundle.display());
+            match Command::new(&program)
+                .args(validator_args)
+                .arg(&bundle)
+                .status()
+            {
+                Ok(status) if status.success() => (),
+                Ok(status) => failures.push(format!("{} ({program}: {status})", bundle.display())),
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                    eprintln!(
+                        "Warning: {program} is not installed, set {env_var} to point to it. \
+                         Skipping {extension} validation."
+                    );
+                    break;
+                }
+                Err(err) => return Err(err).with_context(|| format!("Could not run {program}")),
+            }
+        }
+    }
+
+    if !failures.is_empty() {
+        bail!("Validation failed for:\n  {}", failures.join("\n  "));
+    }
+
+    eprintln!("All available validators passed");
+    Ok(())
+}
+
+/// The per-user (or, on Windows, system-wide) VST3 and CLAP plugin directories.
+fn plugin_dirs() -> nih_plug_xtask::Result<(PathBuf, PathBuf)> {
+    if cfg!(target_os = "macos") {
+        let plugins_dir = home_dir()?.join("Library/Audio/Plug-Ins");
+        Ok((plugins_dir.join("VST3"), plugins_dir.join("CLAP")))
+    } else if cfg!(target_os = "windows") {
+        let common_files = std::env::var_os("COMMONPROGRAMFILES")
+            .map(PathBuf::from)
+            .context("COMMONPROGRAMFILES is not set")?;
+        Ok((common_files.join("VST3"), common_files.join("CLAP")))
+    } else {
+        let home = home_dir()?;
+        Ok((home.join(".vst3"), home.join(".clap")))
+    }
+}
+
+fn home_dir() -> nih_plug_xtask::Result<PathBuf> {
+    std::env::var_os("HOME")
+        .map(PathBuf::from)
+        .context("HOME is not set")
+}
+
+/// Bundles are directories on most platforms but CLAP plugins are single files on Linux and
+/// Windows, so handle both.
+fn copy_path(source: &Path, destination: &Path) -> std::io::Result<()> {
+    if source.is_dir() {
+        fs::create_dir_all(destination)?;
+        for entry in fs::read_dir(source)? {
+            let entry = entry?;
+            copy_path(&entry.path(), &destination.join(entry.file_name()))?;
+        }
+    } else {
+        fs::copy(source, destination)?;
+    }
+
+    Ok(())
+}
+
+fn remove_path(path: &Path) -> std::io::Result<()> {
+    if path.is_dir() {
+        fs::remove_dir_all(path)
+    } else if path.exists() {
+        fs::remove_file(path)
+    } else {
+        Ok(())
+    }
 }